@@ -0,0 +1,183 @@
+//! Criterion benchmarks for the scan pipeline.
+//!
+//! Throughput is reported in elements per second where one element is one
+//! base, so `Melem/s` reads as megabases per second.
+
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::Arc;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use qgrs_rust::qgrs::testing::SyntheticGenome;
+use qgrs_rust::qgrs::{
+    ChromSequence, InputMode, LoadOptions, ScanConfig, ScanLimits, consolidate_g4s,
+    find_owned_bytes_with_limits, load_sequences_from_path, load_sequences_from_reader,
+    render_csv_results, scan_short, search_chromosome,
+};
+
+const SYNTHETIC_LEN: usize = 200_000;
+const PLASMID_LEN: usize = 20_000;
+const MIN_SCORE: i32 = 17;
+
+fn limits() -> ScanLimits {
+    ScanLimits::default()
+}
+
+/// One G4 every `spacing` bases on an A/C/T background.
+fn synthetic(seed: u64, len: usize, spacing: usize) -> Arc<Vec<u8>> {
+    let mut genome = SyntheticGenome::new(seed).chromosome("bench", len);
+    let mut pos = spacing / 2;
+    while pos + 40 < len {
+        let tetrads = 2 + pos % 3;
+        genome = genome.with_g4(pos, tetrads, [1 + pos % 5, 2, 3 + pos % 4]);
+        pos += spacing;
+    }
+    Arc::new(genome.sequences().remove(0).1)
+}
+
+/// Sparse G4s on an A/C/T background with a 20 kb assembly gap every 50 kb,
+/// so most windows hold no G at all.
+fn at_rich(seed: u64, len: usize) -> Arc<Vec<u8>> {
+    let mut genome = SyntheticGenome::new(seed).chromosome("bench", len);
+    let mut pos = 0;
+    while pos + 50_000 <= len {
+        genome = genome
+            .with_gap(pos + 10_000, 20_000)
+            .with_g4(pos + 40_000, 3, [1, 2, 3]);
+        pos += 50_000;
+    }
+    Arc::new(genome.sequences().remove(0).1)
+}
+
+fn inputs() -> Vec<(&'static str, Arc<Vec<u8>>)> {
+    let big = Path::new(env!("CARGO_MANIFEST_DIR")).join("big.txt");
+    let big = load_sequences_from_path(&big, InputMode::Mmap)
+        .expect("big.txt should load")
+        .into_iter()
+        .flat_map(|chrom| chrom.sequence().to_vec())
+        .collect();
+    let big = Arc::new(big);
+    vec![
+        ("big_txt", big),
+        ("g_dense", synthetic(1, SYNTHETIC_LEN, 60)),
+        ("g_sparse", synthetic(2, SYNTHETIC_LEN, 5_000)),
+        ("at_rich_gaps", at_rich(4, SYNTHETIC_LEN)),
+        ("plasmid_20kb", synthetic(5, PLASMID_LEN, 500)),
+    ]
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    for (name, sequence) in inputs() {
+        group.throughput(Throughput::Elements(sequence.len() as u64));
+        for min_tetrads in [2, 3] {
+            group.bench_with_input(
+                BenchmarkId::new(name, format!("min_tetrads={min_tetrads}")),
+                &sequence,
+                |b, sequence| {
+                    b.iter(|| {
+                        find_owned_bytes_with_limits(
+                            black_box(Arc::clone(sequence)),
+                            min_tetrads,
+                            MIN_SCORE,
+                            limits(),
+                        )
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_window_size(c: &mut Criterion) {
+    let sequence = synthetic(3, SYNTHETIC_LEN, 500);
+    let mut group = c.benchmark_group("window_size");
+    group.throughput(Throughput::Elements(sequence.len() as u64));
+    let sizes = [
+        None,
+        Some(45),
+        Some(64),
+        Some(256),
+        Some(1024),
+        Some(4096),
+        Some(16384),
+    ];
+    for chunk_size in sizes {
+        let limits = match chunk_size {
+            Some(chunk_size) => limits().with_chunk_size(chunk_size),
+            None => limits(),
+        };
+        let id = chunk_size.map_or_else(|| "adaptive".to_string(), |size| size.to_string());
+        group.bench_with_input(BenchmarkId::from_parameter(id), &sequence, |b, sequence| {
+            b.iter(|| {
+                find_owned_bytes_with_limits(black_box(Arc::clone(sequence)), 2, MIN_SCORE, limits)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_post_scan(c: &mut Criterion) {
+    let sequence = synthetic(4, SYNTHETIC_LEN, 60);
+    let raw = find_owned_bytes_with_limits(sequence, 2, MIN_SCORE, limits()).expect("valid limits");
+    let (hits, _) = consolidate_g4s(raw.clone());
+
+    let mut group = c.benchmark_group("post_scan");
+    group.throughput(Throughput::Elements(raw.len() as u64));
+    group.bench_function("consolidate", |b| {
+        b.iter_batched(|| raw.clone(), consolidate_g4s, BatchSize::SmallInput)
+    });
+    group.throughput(Throughput::Elements(hits.len() as u64));
+    group.bench_function("render_csv", |b| {
+        b.iter(|| render_csv_results(black_box(&hits)))
+    });
+    group.finish();
+}
+
+/// Oligo-sized inputs holding one and three G4 families: `scan_short`
+/// against the general path, both ending in records.
+fn bench_short(c: &mut Criterion) {
+    let mut group = c.benchmark_group("short");
+    for (name, oligo) in [
+        ("30bp", "ACGGGTTGGGAGGGTAGGGTCATCGATCGA"),
+        (
+            "100bp",
+            "ATCGATTACAGGGTTAGGGTTAGGGTTAGGGCATTACAGCGATCGATCGAGGGAGGGTGCTAGCTGATCGTGGGGAGGGGAGGGGAGGGGTACATCAGCT",
+        ),
+    ] {
+        let oligo = oligo.as_bytes();
+        let fasta = format!(">{name}\n{}\n", std::str::from_utf8(oligo).unwrap());
+        let chrom: ChromSequence =
+            load_sequences_from_reader(&mut fasta.as_bytes(), LoadOptions::default())
+                .expect("oligo FASTA")
+                .remove(0);
+        group.throughput(Throughput::Elements(oligo.len() as u64));
+        group.bench_with_input(BenchmarkId::new("scan_short", name), &oligo, |b, oligo| {
+            b.iter(|| scan_short(black_box(oligo), ScanConfig::default()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("search_chromosome", name),
+            &chrom,
+            |b, chrom| {
+                b.iter(|| {
+                    search_chromosome(black_box(chrom), ScanConfig::default(), false)
+                        .hits
+                        .iter()
+                        .map(|hit| hit.to_record())
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_scan,
+    bench_window_size,
+    bench_post_scan,
+    bench_short
+);
+criterion_main!(benches);
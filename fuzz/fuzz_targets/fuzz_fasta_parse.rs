@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qgrs_rust::qgrs::{
+    LoadOptions, QuartetBase, ScanConfig, ScanLimits, SequenceTopology, load_sequences_from_reader,
+    stream,
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Small limits keep each input cheap and split even short records into
+    // many stream windows.
+    let config = ScanConfig::new(
+        2,
+        0,
+        ScanLimits::new(12, 4),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    let mut scanned = Vec::new();
+    let streamed = stream::process_reader_with_config(data, config, false, &mut |results| {
+        for g4 in &results.hits {
+            assert!(g4.start >= 1 && g4.end <= results.sequence_len);
+        }
+        if results.sequence_len > 0 {
+            scanned.push((results.name, results.occurrence, results.sequence_len));
+        }
+        Ok(())
+    });
+    let loaded = load_sequences_from_reader(&mut &data[..], LoadOptions::default());
+    // Both read line by line as UTF-8, so they accept and reject the same
+    // inputs and must agree on every non-empty record.
+    match (streamed, loaded) {
+        (Ok(_), Ok(loaded)) => {
+            let loaded: Vec<_> = loaded
+                .iter()
+                .map(|chrom| {
+                    (
+                        chrom.name().to_string(),
+                        chrom.occurrence(),
+                        chrom.sequence().len(),
+                    )
+                })
+                .collect();
+            assert_eq!(scanned, loaded);
+        }
+        (Err(_), Err(_)) => {}
+        (streamed, loaded) => panic!(
+            "stream {:?} but loader {:?}",
+            streamed.map(|_| ()),
+            loaded.map(|_| ())
+        ),
+    }
+});
@@ -0,0 +1,548 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use qgrs_rust::qgrs::diff::{self, DiffOptions, DiffReport};
+use qgrs_rust::qgrs::{self, G4Record};
+
+use crate::{Args, CliError, Outcome, help, usage};
+
+type ParseResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn parse_output_file(path: &Path) -> ParseResult<Vec<G4Record>> {
+    match output_extension(path) {
+        Some("parquet") => parse_parquet_file(path),
+        _ => parse_csv_file(path),
+    }
+}
+
+fn parse_csv_file(path: &Path) -> ParseResult<Vec<G4Record>> {
+    let file = fs::File::open(path)?;
+    Ok(qgrs::parse_csv_records(io::BufReader::new(file))?)
+}
+
+fn parse_parquet_file(path: &Path) -> ParseResult<Vec<G4Record>> {
+    let file = fs::File::open(path)?;
+    Ok(qgrs::parse_parquet_records(file)?)
+}
+
+fn compare_records(mmap_records: &[G4Record], stream_records: &[G4Record]) -> (usize, Vec<String>) {
+    let mut mismatches = 0;
+    let mut details = Vec::new();
+
+    if mmap_records.len() != stream_records.len() {
+        details.push(format!(
+            "  ⚠️  记录数量不一致: mmap={}, stream={}",
+            mmap_records.len(),
+            stream_records.len()
+        ));
+        mismatches += 1;
+    }
+
+    let min_len = mmap_records.len().min(stream_records.len());
+    for i in 0..min_len {
+        let mmap = &mmap_records[i];
+        let stream = &stream_records[i];
+
+        if mmap != stream {
+            mismatches += 1;
+            if mismatches <= 10 {
+                details.push(format!("  ⚠️  第 {} 条记录不匹配:", i + 1));
+
+                if mmap.start != stream.start || mmap.end != stream.end {
+                    details.push(format!(
+                        "      位置: mmap={}..{}, stream={}..{}",
+                        mmap.start, mmap.end, stream.start, stream.end
+                    ));
+                }
+                if mmap.sequence != stream.sequence {
+                    details.push(format!(
+                        "      序列: mmap={}, stream={}",
+                        mmap.sequence, stream.sequence
+                    ));
+                }
+                if mmap.tetrads != stream.tetrads {
+                    details.push(format!(
+                        "      四联体: mmap={}, stream={}",
+                        mmap.tetrads, stream.tetrads
+                    ));
+                }
+                if mmap.score != stream.score {
+                    details.push(format!(
+                        "      score: mmap={}, stream={}",
+                        mmap.score, stream.score
+                    ));
+                }
+                if mmap.y1 != stream.y1 || mmap.y2 != stream.y2 || mmap.y3 != stream.y3 {
+                    details.push(format!(
+                        "      间隔: mmap=({},{},{}), stream=({},{},{})",
+                        mmap.y1, mmap.y2, mmap.y3, stream.y1, stream.y2, stream.y3
+                    ));
+                }
+            }
+        }
+    }
+
+    if mismatches > 10 {
+        details.push(format!("  ... (省略其余 {} 处差异)", mismatches - 10));
+    }
+
+    (mismatches, details)
+}
+
+/// Where `--concordance` writes its reports besides the stdout summary.
+#[derive(Default)]
+struct ConcordanceOutputs {
+    report: Option<PathBuf>,
+    json: Option<PathBuf>,
+}
+
+pub(crate) fn run<I>(mut args: Args<I>) -> Result<Outcome, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut dirs = Vec::new();
+    let mut concordance = false;
+    let mut min_overlap: Option<f64> = None;
+    let mut outputs = ConcordanceOutputs::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--concordance" => concordance = true,
+            "--min-overlap" => {
+                let value = args.parse::<f64>("--min-overlap", "a number between 0 and 1")?;
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(usage("--min-overlap must be between 0 and 1"));
+                }
+                min_overlap = Some(value);
+            }
+            "--report" => outputs.report = Some(PathBuf::from(args.value("--report")?)),
+            "--json" => outputs.json = Some(PathBuf::from(args.value("--json")?)),
+            "--help" | "-h" => return Err(help()),
+            other if other.starts_with("--") => {
+                return Err(usage(&format!("unknown argument '{other}'")));
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+    let [mmap_dir, stream_dir]: [PathBuf; 2] = dirs
+        .try_into()
+        .map_err(|_| usage("compare requires exactly two directories"))?;
+    if !concordance {
+        for (flag, set) in [
+            ("--min-overlap", min_overlap.is_some()),
+            ("--report", outputs.report.is_some()),
+            ("--json", outputs.json.is_some()),
+        ] {
+            if set {
+                return Err(usage(&format!("{flag} requires --concordance")));
+            }
+        }
+    }
+
+    if concordance {
+        let opts = DiffOptions {
+            min_overlap: min_overlap.unwrap_or_default(),
+        };
+        return run_concordance(&mmap_dir, &stream_dir, opts, &outputs).map_err(CliError::Check);
+    }
+    compare_dirs(&mmap_dir, &stream_dir).map_err(CliError::Check)
+}
+
+/// Diffs the files two output directories share, field by field.
+fn compare_dirs(mmap_dir: &Path, stream_dir: &Path) -> Result<Outcome, String> {
+    if !mmap_dir.exists() {
+        return Err(format!("mmap 目录不存在: {mmap_dir:?}"));
+    }
+
+    if !stream_dir.exists() {
+        return Err(format!("stream 目录不存在: {stream_dir:?}"));
+    }
+
+    println!("════════════════════════════════════════════════════════");
+    println!("🔍 比较 Mmap 和 Stream 输出文件");
+    println!("════════════════════════════════════════════════════════");
+    println!("Mmap 目录:   {}", mmap_dir.display());
+    println!("Stream 目录: {}", stream_dir.display());
+    println!("════════════════════════════════════════════════════════\n");
+
+    let mmap_files = list_output_files(mmap_dir)?;
+    let stream_files = list_output_files(stream_dir)?;
+    let mmap_stems: HashSet<&String> = mmap_files.keys().collect();
+    let stream_stems: HashSet<&String> = stream_files.keys().collect();
+
+    // 找出共同的文件(按去掉 .csv/.parquet 后的文件名匹配)
+    let mut common_files: Vec<String> = mmap_stems
+        .intersection(&stream_stems)
+        .map(|stem| stem.to_string())
+        .collect();
+    common_files.sort();
+    for stem in &common_files {
+        let (mmap_path, stream_path) = (&mmap_files[stem], &stream_files[stem]);
+        if output_extension(mmap_path) != output_extension(stream_path) {
+            return Err(format!(
+                "{stem} 的格式不一致: mmap 为 {}, stream 为 {}",
+                mmap_path.display(),
+                stream_path.display()
+            ));
+        }
+    }
+
+    // 找出只在一个目录中的文件
+    let only_mmap: Vec<String> = mmap_stems
+        .difference(&stream_stems)
+        .map(|stem| file_name(&mmap_files[*stem]))
+        .collect();
+    let only_stream: Vec<String> = stream_stems
+        .difference(&mmap_stems)
+        .map(|stem| file_name(&stream_files[*stem]))
+        .collect();
+
+    if !only_mmap.is_empty() {
+        println!("⚠️  只在 mmap 目录中的文件:");
+        for f in &only_mmap {
+            println!("    {}", f);
+        }
+        println!();
+    }
+
+    if !only_stream.is_empty() {
+        println!("⚠️  只在 stream 目录中的文件:");
+        for f in &only_stream {
+            println!("    {}", f);
+        }
+        println!();
+    }
+
+    println!("📁 找到 {} 个共同文件\n", common_files.len());
+
+    // 比较每个文件
+    let mut total_mismatches = 0;
+    let mut file_results = Vec::new();
+
+    for stem in &common_files {
+        let mmap_path = &mmap_files[stem];
+        let stream_path = &stream_files[stem];
+        let file_name = file_name(mmap_path);
+
+        print!("🔍 比较 {}... ", file_name);
+
+        let mmap_records = match parse_output_file(mmap_path) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("❌");
+                eprintln!("  读取 mmap 文件失败: {}", e);
+                total_mismatches += 1;
+                continue;
+            }
+        };
+
+        let stream_records = match parse_output_file(stream_path) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("❌");
+                eprintln!("  读取 stream 文件失败: {}", e);
+                total_mismatches += 1;
+                continue;
+            }
+        };
+
+        let (mismatches, details) = compare_records(&mmap_records, &stream_records);
+
+        if mismatches == 0 {
+            println!("✅ ({} 条记录)", mmap_records.len());
+            file_results.push((file_name, true, mmap_records.len(), details));
+        } else {
+            println!("❌ ({} 处差异)", mismatches);
+            file_results.push((file_name, false, mismatches, details));
+            total_mismatches += 1;
+        }
+    }
+
+    // 显示详细差异
+    println!("\n════════════════════════════════════════════════════════");
+    println!("📊 详细结果:");
+    println!("════════════════════════════════════════════════════════\n");
+
+    for (file_name, is_match, count, details) in &file_results {
+        if *is_match {
+            println!("✅ {}: {} 条记录完全匹配", file_name, count);
+        } else {
+            println!("❌ {}: {} 处差异", file_name, count);
+            for detail in details {
+                println!("{}", detail);
+            }
+            println!();
+        }
+    }
+
+    // 总结
+    println!("════════════════════════════════════════════════════════");
+    println!("📈 总结:");
+    println!("════════════════════════════════════════════════════════");
+    println!("共同文件数: {}", common_files.len());
+    println!("完全匹配: {}", common_files.len() - total_mismatches);
+    println!("有差异: {}", total_mismatches);
+
+    if !only_mmap.is_empty() {
+        println!("只在 mmap: {}", only_mmap.len());
+    }
+    if !only_stream.is_empty() {
+        println!("只在 stream: {}", only_stream.len());
+    }
+
+    if total_mismatches == 0 && only_mmap.is_empty() && only_stream.is_empty() {
+        println!("\n✅ 所有文件完全一致!");
+        Ok(Outcome::Success)
+    } else {
+        println!("\n❌ 发现差异!");
+        Ok(Outcome::Mismatch)
+    }
+}
+
+/// Pairs the hits of each file by position instead of row by row, for runs
+/// made with different parameters. A file present on one side only is
+/// compared with an empty set. Prints one summary row per file and returns
+/// `Mismatch` unless every hit has an identical partner.
+fn run_concordance(
+    dir_a: &Path,
+    dir_b: &Path,
+    opts: DiffOptions,
+    outputs: &ConcordanceOutputs,
+) -> Result<Outcome, String> {
+    for dir in [dir_a, dir_b] {
+        if !dir.exists() {
+            return Err(format!("directory does not exist: {dir:?}"));
+        }
+    }
+    let files_a = list_output_files(dir_a)?;
+    let files_b = list_output_files(dir_b)?;
+    let mut stems: Vec<&String> = files_a.keys().chain(files_b.keys()).collect();
+    stems.sort();
+    stems.dedup();
+
+    let read = |files: &HashMap<String, PathBuf>, stem: &str| -> Result<Vec<G4Record>, String> {
+        files.get(stem).map_or(Ok(Vec::new()), |path| {
+            parse_output_file(path).map_err(|err| format!("failed to read {path:?}: {err}"))
+        })
+    };
+    let mut reports: Vec<(&str, DiffReport)> = Vec::with_capacity(stems.len());
+    for stem in stems {
+        let report = diff::diff_results(&read(&files_a, stem)?, &read(&files_b, stem)?, opts);
+        reports.push((stem.as_str(), report));
+    }
+    let named: Vec<(&str, &DiffReport)> = reports
+        .iter()
+        .map(|(name, report)| (*name, report))
+        .collect();
+
+    print!("{}", diff::render_diff_summary_tsv(&named));
+    if let Some(path) = &outputs.report {
+        fs::write(path, diff::render_diff_tsv(&named))
+            .map_err(|err| format!("failed to write {path:?}: {err}"))?;
+    }
+    if let Some(path) = &outputs.json {
+        let json = diff::render_diff_json(&named)
+            .map_err(|err| format!("failed to serialize report: {err}"))?;
+        fs::write(path, json).map_err(|err| format!("failed to write {path:?}: {err}"))?;
+    }
+    if reports.iter().all(|(_, report)| report.is_identical()) {
+        Ok(Outcome::Success)
+    } else {
+        Ok(Outcome::Mismatch)
+    }
+}
+
+fn list_output_files(dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("无法读取目录 {dir:?}: {err}"))?;
+    let mut files = HashMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(extension) = output_extension(&path) else {
+            continue;
+        };
+        let name = file_name(&path);
+        let stem = name[..name.len() - extension.len() - 1].to_string();
+        if let Some(previous) = files.insert(stem.clone(), path) {
+            return Err(format!(
+                "{dir:?} 中 {stem} 同时存在 csv 与 parquet 输出: {}",
+                previous.display()
+            ));
+        }
+    }
+    Ok(files)
+}
+
+fn output_extension(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "csv" => Some("csv"),
+        "parquet" => Some("parquet"),
+        _ => None,
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qgrs_rust::qgrs::{self, G4};
+    use std::env;
+    use std::sync::Arc;
+
+    const HEADER: &str = "start,end,length,tetrads,y1,y2,y3,score,sequence\n";
+
+    #[test]
+    fn compare_reports_field_mismatch_and_missing_files() {
+        let root = env::temp_dir().join(format!("qgrs_compare_{}", std::process::id()));
+        let (a, b) = (root.join("a"), root.join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        let row = "1,19,19,4,1,1,1,63,GGGGAGGGGAGGGGAGGGG\n";
+        fs::write(a.join("chr1.g4.csv"), format!("{HEADER}{row}")).unwrap();
+        fs::write(b.join("chr1.g4.csv"), format!("{HEADER}{row}")).unwrap();
+
+        let dirs = || {
+            [&a, &b]
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .into_iter()
+        };
+        assert_eq!(run(Args::new(dirs())), Ok(Outcome::Success));
+
+        let changed = row.replace(",63,", ",62,");
+        fs::write(b.join("chr1.g4.csv"), format!("{HEADER}{changed}")).unwrap();
+        let records_a = parse_csv_file(&a.join("chr1.g4.csv")).unwrap();
+        let records_b = parse_csv_file(&b.join("chr1.g4.csv")).unwrap();
+        let (mismatches, details) = compare_records(&records_a, &records_b);
+        assert_eq!(mismatches, 1);
+        assert!(
+            details
+                .iter()
+                .any(|line| line.contains("score: mmap=63, stream=62"))
+        );
+        assert_eq!(run(Args::new(dirs())), Ok(Outcome::Mismatch));
+
+        fs::write(b.join("chr1.g4.csv"), format!("{HEADER}{row}")).unwrap();
+        fs::write(a.join("chr2.g4.csv"), HEADER).unwrap();
+        assert_eq!(run(Args::new(dirs())), Ok(Outcome::Mismatch));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    fn scan(sequence: &str) -> Vec<G4> {
+        let raw = qgrs::find_owned_bytes(Arc::new(sequence.as_bytes().to_ascii_lowercase()), 2, 17);
+        qgrs::consolidate_g4s(raw).0
+    }
+
+    fn write_parquet(path: &Path, hits: &[G4]) {
+        let file = fs::File::create(path).unwrap();
+        qgrs::write_parquet_results(hits, file).unwrap();
+    }
+
+    #[test]
+    fn compare_reads_parquet_and_reports_score_mismatch() {
+        let root = env::temp_dir().join(format!("qgrs_compare_parquet_{}", std::process::id()));
+        let (a, b) = (root.join("a"), root.join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        let hits = scan("GGGGAGGGGAGGGGAGGGG");
+        let mut changed = hits.clone();
+        changed[0].score -= 1;
+        write_parquet(&a.join("chr1.g4.parquet"), &hits);
+        write_parquet(&b.join("chr1.g4.parquet"), &changed);
+
+        let records_a = parse_output_file(&a.join("chr1.g4.parquet")).unwrap();
+        let records_b = parse_output_file(&b.join("chr1.g4.parquet")).unwrap();
+        assert_eq!(records_a.len(), 1);
+        assert_eq!(records_a[0].sequence, "GGGGAGGGGAGGGGAGGGG");
+        let (mismatches, details) = compare_records(&records_a, &records_b);
+        assert_eq!(mismatches, 1);
+        let expected = format!(
+            "score: mmap={}, stream={}",
+            hits[0].score,
+            hits[0].score - 1
+        );
+        assert!(details.iter().any(|line| line.contains(&expected)));
+
+        let dirs = || {
+            [&a, &b]
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .into_iter()
+        };
+        assert_eq!(run(Args::new(dirs())), Ok(Outcome::Mismatch));
+
+        // Parquet on one side and CSV on the other for the same stem is an error.
+        fs::remove_file(b.join("chr1.g4.parquet")).unwrap();
+        fs::write(b.join("chr1.g4.csv"), qgrs::render_csv_results(&hits)).unwrap();
+        let err = run(Args::new(dirs())).unwrap_err();
+        assert!(
+            matches!(&err, CliError::Check(message) if message.contains("chr1.g4")),
+            "{err:?}"
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn concordance_pairs_shifted_hits_across_runs() {
+        let root = env::temp_dir().join(format!("qgrs_compare_concordance_{}", std::process::id()));
+        let (a, b) = (root.join("a"), root.join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        let row = "1,19,19,4,1,1,1,63,GGGGAGGGGAGGGGAGGGG\n";
+        let shifted = "3,19,17,3,2,2,2,42,GGAGGGGAGGGGAGGGG\n";
+        fs::write(a.join("chr1.g4.csv"), format!("{HEADER}{row}")).unwrap();
+        fs::write(b.join("chr1.g4.csv"), format!("{HEADER}{shifted}")).unwrap();
+        fs::write(b.join("chr2.g4.csv"), format!("{HEADER}{row}")).unwrap();
+
+        let (report, json) = (root.join("diff.tsv"), root.join("diff.json"));
+        let args = |extra: &[&str]| {
+            let mut args: Vec<String> = extra.iter().map(|arg| arg.to_string()).collect();
+            args.extend([&a, &b].map(|dir| dir.to_string_lossy().into_owned()));
+            Args::new(args.into_iter())
+        };
+        let flags = [
+            "--concordance",
+            "--report",
+            report.to_str().unwrap(),
+            "--json",
+            json.to_str().unwrap(),
+        ];
+        assert_eq!(run(args(&flags)), Ok(Outcome::Mismatch));
+        let rows = fs::read_to_string(&report).unwrap();
+        assert!(
+            rows.contains("chr1.g4\tboundaries\t1\t19\t63\t3\t19\t42\t"),
+            "{rows}"
+        );
+        assert!(
+            rows.contains("chr2.g4\tonly_b\t\t\t\t1\t19\t63\t"),
+            "{rows}"
+        );
+        let parsed: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(parsed["reports"][0]["summary"]["hit_jaccard"], 1.0);
+        assert_eq!(parsed["reports"][1]["summary"]["only_b"], 1);
+
+        fs::write(b.join("chr1.g4.csv"), format!("{HEADER}{row}")).unwrap();
+        fs::remove_file(b.join("chr2.g4.csv")).unwrap();
+        assert_eq!(run(args(&["--concordance"])), Ok(Outcome::Success));
+
+        let err = run(args(&["--min-overlap", "0.5"])).unwrap_err();
+        assert!(
+            matches!(&err, CliError::Usage(message)
+                if message.starts_with("--min-overlap requires --concordance")),
+            "{err:?}"
+        );
+        let err = run(args(&["--concordance", "--min-overlap", "1.5"])).unwrap_err();
+        assert!(
+            matches!(&err, CliError::Usage(message) if message.contains("between 0 and 1")),
+            "{err:?}"
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
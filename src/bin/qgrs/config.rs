@@ -0,0 +1,212 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Every `qgrs scan` option as given, before defaults and validation. The
+/// argument parser fills one from the command line and `--config` reads
+/// another from a file; [`RunConfig::or`] lets the command line win, and
+/// `scan::run` applies the defaults to the result. Keys are the flag names
+/// without `--`, e.g. `min-score = 30` or `output-dir = "out"`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct RunConfig {
+    pub(crate) sequence: Option<String>,
+    pub(crate) file: Option<PathBuf>,
+    pub(crate) glob: Option<String>,
+    pub(crate) fail_fast: Option<bool>,
+    pub(crate) min_tetrads: Option<usize>,
+    pub(crate) min_score: Option<MinScore>,
+    pub(crate) no_g_loops: Option<bool>,
+    pub(crate) loop_alphabet: Option<String>,
+    pub(crate) allow_empty_config: Option<bool>,
+    pub(crate) base: Option<String>,
+    pub(crate) motif: Option<String>,
+    pub(crate) max_run: Option<usize>,
+    pub(crate) max_g4_length: Option<usize>,
+    pub(crate) chunk_size: Option<usize>,
+    pub(crate) format: Option<String>,
+    pub(crate) coords: Option<u8>,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) filename_index: Option<bool>,
+    pub(crate) filename_hash: Option<bool>,
+    pub(crate) stdout: Option<bool>,
+    pub(crate) mode: Option<String>,
+    pub(crate) populate: Option<bool>,
+    pub(crate) record_parallel: Option<String>,
+    pub(crate) overlap: Option<bool>,
+    pub(crate) pair_strands: Option<bool>,
+    pub(crate) circular: Option<bool>,
+    pub(crate) family_policy: Option<String>,
+    pub(crate) keep_empty: Option<bool>,
+    pub(crate) chrom_regex: Option<String>,
+    pub(crate) resume: Option<bool>,
+    pub(crate) checkpoint: Option<PathBuf>,
+    pub(crate) checkpoint_every: Option<usize>,
+    pub(crate) force: Option<bool>,
+    pub(crate) no_fsync: Option<bool>,
+    pub(crate) dry_run: Option<bool>,
+    pub(crate) log: Option<String>,
+    pub(crate) verbose: Option<bool>,
+    pub(crate) gruns_output: Option<PathBuf>,
+    pub(crate) anchors: Option<PathBuf>,
+    pub(crate) motif_summary: Option<PathBuf>,
+    pub(crate) aggregate_by: Option<PathBuf>,
+    pub(crate) report_html: Option<PathBuf>,
+    pub(crate) chrom_sizes: Option<PathBuf>,
+    pub(crate) report_top: Option<usize>,
+    pub(crate) max_hits: Option<usize>,
+    pub(crate) exclude_bed: Option<PathBuf>,
+    pub(crate) exclude_min_overlap: Option<f64>,
+    pub(crate) rank: Option<bool>,
+    pub(crate) preserve_case: Option<bool>,
+    pub(crate) annotate: Option<PathBuf>,
+    pub(crate) gtf: Option<PathBuf>,
+    pub(crate) feature: Option<String>,
+}
+
+/// `min-score`: a number, or `"auto"` for a threshold per tetrad count.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub(crate) enum MinScore {
+    Fixed(i32),
+    Named(String),
+}
+
+impl RunConfig {
+    /// Reads a TOML file, or JSON when the name ends in `.json`. Unknown keys
+    /// are an error. Relative paths in the file are taken relative to the
+    /// file's directory, so a run does not depend on where it is started.
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read config {path:?}: {err}"))?;
+        let parsed: Result<Self, String> = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            toml::from_str(&text).map_err(|err| err.to_string().trim_end().to_string())
+        };
+        let mut config = parsed.map_err(|err| format!("invalid config {path:?}: {err}"))?;
+        config.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            &mut self.file,
+            &mut self.output,
+            &mut self.output_dir,
+            &mut self.checkpoint,
+            &mut self.gruns_output,
+            &mut self.anchors,
+            &mut self.motif_summary,
+            &mut self.aggregate_by,
+            &mut self.report_html,
+            &mut self.chrom_sizes,
+            &mut self.exclude_bed,
+            &mut self.annotate,
+            &mut self.gtf,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            // `-` is stdout, not a file.
+            if path.is_relative() && path.as_os_str() != "-" {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// The switch a `--no-<flag>` argument turns off, so the command line can
+    /// override `<flag> = true` from a `--config` file. `--g-loops` and
+    /// `--fsync` undo `no-g-loops` and `no-fsync`.
+    pub(crate) fn negated_switch(&mut self, arg: &str) -> Option<&mut Option<bool>> {
+        let flag = arg.strip_prefix("--")?;
+        let name = match flag {
+            "g-loops" => "no-g-loops",
+            "fsync" => "no-fsync",
+            _ => flag.strip_prefix("no-")?,
+        };
+        let switch = match name {
+            "fail-fast" => &mut self.fail_fast,
+            "no-g-loops" => &mut self.no_g_loops,
+            "allow-empty-config" => &mut self.allow_empty_config,
+            "filename-index" => &mut self.filename_index,
+            "filename-hash" => &mut self.filename_hash,
+            "stdout" => &mut self.stdout,
+            "populate" => &mut self.populate,
+            "overlap" => &mut self.overlap,
+            "pair-strands" => &mut self.pair_strands,
+            "circular" => &mut self.circular,
+            "keep-empty" => &mut self.keep_empty,
+            "resume" => &mut self.resume,
+            "force" => &mut self.force,
+            "no-fsync" => &mut self.no_fsync,
+            "dry-run" => &mut self.dry_run,
+            "verbose" => &mut self.verbose,
+            "rank" => &mut self.rank,
+            "preserve-case" => &mut self.preserve_case,
+            _ => return None,
+        };
+        Some(switch)
+    }
+
+    /// Each option from `self` when set, otherwise from `fallback`.
+    pub(crate) fn or(self, fallback: Self) -> Self {
+        Self {
+            sequence: self.sequence.or(fallback.sequence),
+            file: self.file.or(fallback.file),
+            glob: self.glob.or(fallback.glob),
+            fail_fast: self.fail_fast.or(fallback.fail_fast),
+            min_tetrads: self.min_tetrads.or(fallback.min_tetrads),
+            min_score: self.min_score.or(fallback.min_score),
+            no_g_loops: self.no_g_loops.or(fallback.no_g_loops),
+            loop_alphabet: self.loop_alphabet.or(fallback.loop_alphabet),
+            allow_empty_config: self.allow_empty_config.or(fallback.allow_empty_config),
+            base: self.base.or(fallback.base),
+            motif: self.motif.or(fallback.motif),
+            max_run: self.max_run.or(fallback.max_run),
+            max_g4_length: self.max_g4_length.or(fallback.max_g4_length),
+            chunk_size: self.chunk_size.or(fallback.chunk_size),
+            format: self.format.or(fallback.format),
+            coords: self.coords.or(fallback.coords),
+            output: self.output.or(fallback.output),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            filename_index: self.filename_index.or(fallback.filename_index),
+            filename_hash: self.filename_hash.or(fallback.filename_hash),
+            stdout: self.stdout.or(fallback.stdout),
+            mode: self.mode.or(fallback.mode),
+            populate: self.populate.or(fallback.populate),
+            record_parallel: self.record_parallel.or(fallback.record_parallel),
+            overlap: self.overlap.or(fallback.overlap),
+            pair_strands: self.pair_strands.or(fallback.pair_strands),
+            circular: self.circular.or(fallback.circular),
+            family_policy: self.family_policy.or(fallback.family_policy),
+            keep_empty: self.keep_empty.or(fallback.keep_empty),
+            chrom_regex: self.chrom_regex.or(fallback.chrom_regex),
+            resume: self.resume.or(fallback.resume),
+            checkpoint: self.checkpoint.or(fallback.checkpoint),
+            checkpoint_every: self.checkpoint_every.or(fallback.checkpoint_every),
+            force: self.force.or(fallback.force),
+            no_fsync: self.no_fsync.or(fallback.no_fsync),
+            dry_run: self.dry_run.or(fallback.dry_run),
+            log: self.log.or(fallback.log),
+            verbose: self.verbose.or(fallback.verbose),
+            gruns_output: self.gruns_output.or(fallback.gruns_output),
+            anchors: self.anchors.or(fallback.anchors),
+            motif_summary: self.motif_summary.or(fallback.motif_summary),
+            aggregate_by: self.aggregate_by.or(fallback.aggregate_by),
+            report_html: self.report_html.or(fallback.report_html),
+            chrom_sizes: self.chrom_sizes.or(fallback.chrom_sizes),
+            report_top: self.report_top.or(fallback.report_top),
+            max_hits: self.max_hits.or(fallback.max_hits),
+            exclude_bed: self.exclude_bed.or(fallback.exclude_bed),
+            exclude_min_overlap: self.exclude_min_overlap.or(fallback.exclude_min_overlap),
+            rank: self.rank.or(fallback.rank),
+            preserve_case: self.preserve_case.or(fallback.preserve_case),
+            annotate: self.annotate.or(fallback.annotate),
+            gtf: self.gtf.or(fallback.gtf),
+            feature: self.feature.or(fallback.feature),
+        }
+    }
+}
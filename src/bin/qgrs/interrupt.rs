@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use qgrs_rust::qgrs::CancellationToken;
+use signal_hook::consts::SIGINT;
+use signal_hook::flag;
+
+use crate::EXIT_INTERRUPTED;
+
+/// How long a stream scan may keep reading the record in progress after
+/// Ctrl-C; past that the record is dropped unwritten.
+const GRACE: Duration = Duration::from_secs(10);
+
+/// Ctrl-C state of a `scan --file` run. The first SIGINT sets `requested`:
+/// no new record is started, records already scanned are still written, and
+/// `cancel` fires after [`GRACE`] to drop a stream record still being read.
+/// A second SIGINT exits at once with [`EXIT_INTERRUPTED`]; atomic renames
+/// leave no partial outputs either way, only `*.tmp` files.
+struct Interrupt {
+    requested: Arc<AtomicBool>,
+    cancel: CancellationToken,
+}
+
+static INTERRUPT: OnceLock<Interrupt> = OnceLock::new();
+
+/// Installs the SIGINT handlers once per process.
+pub(crate) fn install() {
+    INTERRUPT.get_or_init(|| {
+        let requested = Arc::new(AtomicBool::new(false));
+        let cancel = CancellationToken::new();
+        // The conditional exit must come first: it sees the flag as it was
+        // before this signal set it.
+        let registered =
+            flag::register_conditional_shutdown(SIGINT, EXIT_INTERRUPTED, Arc::clone(&requested))
+                .and_then(|_| flag::register(SIGINT, Arc::clone(&requested)));
+        match registered {
+            Ok(_) => {
+                let (requested, cancel) = (Arc::clone(&requested), cancel.clone());
+                thread::spawn(move || watch(&requested, &cancel));
+            }
+            Err(err) => tracing::warn!(%err, "failed to install the Ctrl-C handler"),
+        }
+        Interrupt { requested, cancel }
+    });
+}
+
+fn watch(requested: &AtomicBool, cancel: &CancellationToken) {
+    while !requested.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(100));
+    }
+    eprintln!("Interrupted: finishing the records in progress; press Ctrl-C again to quit now");
+    thread::sleep(GRACE);
+    cancel.cancel();
+}
+
+/// Whether Ctrl-C was pressed since [`install`].
+pub(crate) fn requested() -> bool {
+    INTERRUPT
+        .get()
+        .is_some_and(|interrupt| interrupt.requested.load(Ordering::Relaxed))
+}
+
+/// Fires [`GRACE`] after Ctrl-C; never without [`install`].
+pub(crate) fn token() -> CancellationToken {
+    INTERRUPT
+        .get()
+        .map(|interrupt| interrupt.cancel.clone())
+        .unwrap_or_default()
+}
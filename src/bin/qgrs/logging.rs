@@ -0,0 +1,40 @@
+use std::io;
+
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+pub(crate) fn parse_log_format(value: &str) -> Result<LogFormat, crate::CliError> {
+    match value {
+        "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(crate::usage("--log must be either 'text' or 'json'")),
+    }
+}
+
+/// Info-level events (one per written record) go to `writer`; the library's
+/// debug events stay filtered out.
+pub(crate) fn subscriber<W>(format: LogFormat, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().flatten_event(true).finish()),
+    }
+}
+
+/// Without `--log` no subscriber is installed, so every event compiles down
+/// to a disabled-callsite check.
+pub(crate) fn install(format: LogFormat) {
+    // A second install (e.g. from tests in one process) keeps the first.
+    let _ = tracing::subscriber::set_global_default(subscriber(format, io::stderr));
+}
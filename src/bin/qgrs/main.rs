@@ -0,0 +1,449 @@
+use std::env;
+use std::str::FromStr;
+
+use rayon::ThreadPoolBuilder;
+
+mod compare;
+mod config;
+mod interrupt;
+mod logging;
+mod manifest;
+mod report;
+mod resume;
+mod revcomp;
+mod scan;
+#[cfg(feature = "testkit")]
+mod self_test;
+mod sink;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod verify;
+mod writer;
+
+fn main() {
+    // Initialize Rayon global thread pool to match machine CPU count.
+    // This makes parallelism deterministic across runs and avoids relying on
+    // the environment variable `RAYON_NUM_THREADS`.
+    let threads = num_cpus::get();
+    let _ = ThreadPoolBuilder::new().num_threads(threads).build_global();
+
+    std::process::exit(exit_code(run_env(env::args().skip(1))));
+}
+
+fn exit_code(result: Result<Outcome, CliError>) -> i32 {
+    match result {
+        Ok(Outcome::Success) => 0,
+        Ok(Outcome::Mismatch) => EXIT_MISMATCH,
+        Ok(Outcome::Interrupted) => EXIT_INTERRUPTED,
+        Err(CliError::Help(text)) => {
+            print!("{text}");
+            0
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err.message());
+            err.status()
+        }
+    }
+}
+
+// diff(1)-style exit codes so `verify` and `compare` can gate CI jobs.
+const EXIT_MISMATCH: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+// `scan` and `revcomp` have no "differences" outcome, so their errors keep
+// the status 1 that `qgrs` exited with before it had subcommands.
+const EXIT_SCAN_ERROR: i32 = 1;
+// 128 + SIGINT, what shells report for a process stopped by Ctrl-C.
+const EXIT_INTERRUPTED: i32 = 130;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    Mismatch,
+    /// `scan --file` stopped by Ctrl-C after writing the records it had.
+    Interrupted,
+}
+
+/// Why a subcommand stopped early; the kind picks the exit status.
+#[derive(Debug, PartialEq, Eq)]
+enum CliError {
+    /// Bad or missing arguments, with the usage text appended.
+    Usage(String),
+    /// `scan` or `revcomp` failed while running.
+    Scan(String),
+    /// `verify`, `compare` or `self-test` could not run to a verdict.
+    Check(String),
+    /// `help` or `--help`: the usage text the user asked for, not an error.
+    Help(String),
+}
+
+impl CliError {
+    fn status(&self) -> i32 {
+        match self {
+            CliError::Help(_) => 0,
+            CliError::Scan(_) => EXIT_SCAN_ERROR,
+            CliError::Usage(_) | CliError::Check(_) => EXIT_ERROR,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            CliError::Usage(message)
+            | CliError::Scan(message)
+            | CliError::Check(message)
+            | CliError::Help(message) => message,
+        }
+    }
+
+    #[cfg(test)]
+    fn into_message(self) -> String {
+        self.message().to_string()
+    }
+}
+
+/// Errors from the scanning code are plain strings.
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Scan(message)
+    }
+}
+
+fn run_env<I>(args: I) -> Result<Outcome, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut args = Args::new(args);
+    match args.peek().map(String::as_str) {
+        Some("scan") => {
+            args.next();
+            scan_outcome(scan::run(args))
+        }
+        Some("verify") => {
+            args.next();
+            verify::run(args)
+        }
+        Some("compare") => {
+            args.next();
+            compare::run(args)
+        }
+        Some("revcomp") => {
+            args.next();
+            revcomp::run(args)
+        }
+        #[cfg(feature = "testkit")]
+        Some("self-test") => {
+            args.next();
+            self_test::run(args)
+        }
+        Some("help") => Err(help()),
+        // Invocations without a subcommand predate `qgrs scan`.
+        _ => scan_outcome(scan::run(args)),
+    }
+}
+
+/// A scan that failed because of Ctrl-C reports what it wrote instead of an
+/// error.
+fn scan_outcome(result: Result<(), CliError>) -> Result<Outcome, CliError> {
+    match result {
+        Err(CliError::Scan(summary)) if interrupt::requested() => {
+            eprintln!("{summary}");
+            Ok(Outcome::Interrupted)
+        }
+        result => result.map(|()| Outcome::Success),
+    }
+}
+
+struct Args<I: Iterator<Item = String>> {
+    inner: std::iter::Peekable<I>,
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    fn new(inner: I) -> Self {
+        Self {
+            inner: inner.peekable(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&String> {
+        self.inner.peek()
+    }
+
+    fn value(&mut self, flag: &str) -> Result<String, CliError> {
+        self.inner
+            .next()
+            .ok_or_else(|| usage(&format!("missing value for {flag}")))
+    }
+
+    fn parse<T: FromStr>(&mut self, flag: &str, expected: &str) -> Result<T, CliError> {
+        self.value(flag)?
+            .parse::<T>()
+            .map_err(|_| usage(&format!("{flag} must be {expected}")))
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Args<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.inner.next()
+    }
+}
+
+fn usage(reason: &str) -> CliError {
+    CliError::Usage(format!("{reason}\n{}", usage_text()))
+}
+
+fn help() -> CliError {
+    CliError::Help(usage_text())
+}
+
+fn usage_text() -> String {
+    let mut msg = String::new();
+    msg.push_str("Usage: qgrs [scan] [--sequence <SEQ> | --file <PATH>] [options]\n");
+    msg.push_str("       qgrs verify --file <PATH> [--min-tetrads <N>] [--min-score <S>] [--report <JSON>]\n");
+    msg.push_str("       qgrs compare <DIR_A> <DIR_B>\n");
+    msg.push_str(
+        "       qgrs compare --concordance [--min-overlap <F>] [--report <TSV>] [--json <PATH>] <DIR_A> <DIR_B>\n",
+    );
+    msg.push_str("       qgrs revcomp --file <PATH> --output <PATH>\n");
+    msg.push_str("       qgrs self-test\n");
+    msg.push_str("Commands:\n");
+    msg.push_str("  scan                 Search for G4/i-motif hits (default when omitted)\n");
+    msg.push_str("  verify               Check that mmap and stream modes give identical hits\n");
+    msg.push_str("  compare              Diff two directories of CSV/Parquet outputs\n");
+    msg.push_str(
+        "  revcomp              Write the reverse complement of every FASTA record, e.g. to check strands\n",
+    );
+    msg.push_str(
+        "  self-test            Scan a built-in synthetic genome and check modes and exports agree\n",
+    );
+    msg.push_str("Scan options:\n");
+    msg.push_str(
+        "  --config <PATH>      Read options from a TOML (or .json) file; flags given here win\n",
+    );
+    msg.push_str(
+        "                       and --no-<flag> turns off a switch the file sets, e.g. --no-force\n",
+    );
+    msg.push_str("  --sequence <SEQ>     Inline DNA/RNA sequence to scan\n");
+    msg.push_str(
+        "  --file <PATH>        Read sequences from FASTA/FASTA.gz/FASTA.zst (chromosomes split independently)\n",
+    );
+    msg.push_str(
+        "                       A directory scans each FASTA in it into <output-dir>/<file stem>/\n",
+    );
+    msg.push_str(
+        "  --glob <PATTERN>     With a directory --file, scan only names matching PATTERN\n",
+    );
+    msg.push_str("  --fail-fast          With a directory --file, stop at the first failed file\n");
+    msg.push_str("  --min-tetrads <N>    Minimum tetrads to seed (default 2)\n");
+    msg.push_str(
+        "  --min-score <S|auto> Minimum score, or auto for a threshold per tetrad count (default auto)\n",
+    );
+    msg.push_str(
+        "  --no-g-loops         Reject loops containing the tetrad base (G, or C with --base c)\n",
+    );
+    msg.push_str(
+        "  --loop-alphabet <B>  Only accept loops made of these bases, e.g. CT (default any)\n",
+    );
+    msg.push_str(
+        "  --allow-empty-config Scan even when no hit could pass --min-score under the limits\n",
+    );
+    msg.push_str(
+        "  --base <g|c>         Tetrad base to scan: g for G4, c for i-motif (default g)\n",
+    );
+    msg.push_str(
+        "  --motif <g4|imotif|both>  g4 = --base g, imotif = --base c, both adds a motif_type column\n",
+    );
+    msg.push_str("  --max-run <N>        Maximum allowed target-base run length (default 10)\n");
+    msg.push_str("  --max-g4-length <N>  Maximum allowed G4 length in bp (default 45)\n");
+    msg.push_str(
+        "  --max-hits <N>       Write at most N hits per record, lowest starts first (--file only)\n",
+    );
+    msg.push_str(
+        "  --chunk-size <BP>    Scan window length, ≥ --max-g4-length (default derived from it)\n",
+    );
+    msg.push_str(
+        "  --format <csv|parquet|parquet-dataset|arrow|qgrs-mapper|family-ranges|sqlite>\n",
+    );
+    msg.push_str(
+        "                       Output format (default csv; qgrs-mapper writes .txt, family-ranges .bed)\n",
+    );
+    msg.push_str(
+        "                       sqlite needs the `sqlite` feature and writes one --file run to --output\n",
+    );
+    msg.push_str(
+        "  --coords <1|0>       Hit starts 1-based inclusive (default) or 0-based half-open; BED is always 0\n",
+    );
+    msg.push_str(
+        "  --output <PATH>     Destination file when using --sequence (required for parquet/arrow)\n",
+    );
+    msg.push_str("  --output-dir <DIR>  Directory for per-chromosome exports when using --file\n");
+    msg.push_str(
+        "  --filename-index     Prefix output filenames with the record's 0-padded position in the file\n",
+    );
+    msg.push_str(
+        "  --filename-hash      Append a short hash of the record name to output filenames\n",
+    );
+    msg.push_str(
+        "  --stdout             Print every --file record as one CSV with a chrom column, no files\n",
+    );
+    msg.push_str("  --mode <mmap|stream|auto>\n");
+    msg.push_str(
+        "                       Input mode for --file; auto streams files over half the free memory\n",
+    );
+    msg.push_str(
+        "  --populate           Prefault the whole mmap input when it is mapped (Linux)\n",
+    );
+    msg.push_str(
+        "  --record-parallel <auto|on|off>\n                       Scan short mmap records whole, one per thread (default auto)\n",
+    );
+    msg.push_str(
+        "  --overlap            Emit raw hits (.overlap.<format>) and family ranges (.family.<format>)\n",
+    );
+    msg.push_str(
+        "  --pair-strands       Also scan the opposite strand and write overlapping loci (.pairs.csv)\n",
+    );
+    msg.push_str("  --circular           Treat each sequence/chromosome as circular\n");
+    msg.push_str("  --family-policy <score|length|tetrads|first>\n");
+    msg.push_str(
+        "                       Member reported for each overlap family (default score)\n",
+    );
+    msg.push_str(
+        "  --keep-empty         Write header-only outputs for FASTA records without sequence\n",
+    );
+    msg.push_str("  --chrom-regex <RE>   Scan only records whose name matches RE (--file only)\n");
+    msg.push_str(
+        "  --resume             Skip records whose outputs already exist in --output-dir\n",
+    );
+    msg.push_str(
+        "  --checkpoint <PATH>  Save stream progress to PATH and resume inside a record from it\n",
+    );
+    msg.push_str("  --checkpoint-every <CHUNKS>\n");
+    msg.push_str(
+        "                       Windows scanned between checkpoints (default: save every 30 s)\n",
+    );
+    msg.push_str(
+        "  --force              Write even if an output would replace the input or another output\n",
+    );
+    msg.push_str(
+        "  --no-fsync           Rename outputs into place without syncing them to disk first\n",
+    );
+    msg.push_str(
+        "  --dry-run            List records and output files from FASTA headers, write nothing\n",
+    );
+    msg.push_str(
+        "  --log <text|json>    Log per-chromosome hit counts and timings to stderr (default off)\n",
+    );
+    msg.push_str(
+        "  --verbose            Print per-record search work counts to stderr (--file only)\n",
+    );
+    msg.push_str(
+        "  --gruns-output <DIR>  Write each record's seeding G-runs to <DIR>/<name>.gruns.tsv (mmap)\n",
+    );
+    msg.push_str(
+        "  --anchors <TSV>      Seed only at the chrom<TAB>pos positions listed, not every G-run (mmap)\n",
+    );
+    msg.push_str(
+        "  --motif-summary <PATH>  Write per-sequence counts across all chromosomes (--file only)\n",
+    );
+    msg.push_str(
+        "  --aggregate-by <BED>  Write hit counts per BED interval, e.g. gene, to aggregate.csv (--file only)\n",
+    );
+    msg.push_str(
+        "  --report-html <PATH> Write a self-contained HTML summary of the run (--file only)\n",
+    );
+    msg.push_str(
+        "  --report-top <N>     Top-scoring hits listed per chromosome in the report (default 100)\n",
+    );
+    msg.push_str(
+        "  --chrom-sizes <PATH> Write a UCSC chrom.sizes file of the scanned records (--file only)\n",
+    );
+    msg.push_str(
+        "  --exclude-bed <BED>  Drop hits overlapping these regions, e.g. a blacklist (--file only)\n",
+    );
+    msg.push_str(
+        "  --exclude-min-overlap <F>  Only drop hits with at least this fraction covered (default any)\n",
+    );
+    msg.push_str("  --rank               Add per-chromosome score rank and percentile columns\n");
+    msg.push_str(
+        "  --preserve-case      Keep the input case in the sequence column (default uppercase)\n",
+    );
+    msg.push_str(
+        "  --annotate <BED>     Add overlapping feature names and nearest-feature distance (--file only)\n",
+    );
+    msg.push_str(
+        "  --gtf <GTF>          Scan each transcript spliced from its exons; adds genomic columns (mmap)\n",
+    );
+    msg.push_str(
+        "  --feature transcript GTF feature to scan with --gtf (transcript, the default, is the only one)\n",
+    );
+    msg.push_str("  --help               Show this message\n");
+    msg.push_str(
+        "Exit status: 0 on success or help, 1 on scan/revcomp errors or when verify/compare/self-test\n",
+    );
+    msg.push_str(
+        "             find differences, 2 on usage errors or when verify/compare/self-test cannot run,\n",
+    );
+    msg.push_str(
+        "             130 when Ctrl-C stopped a --file scan (rerun with --resume to finish it)\n",
+    );
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Outcome, String> {
+        run_env(args.iter().map(|arg| arg.to_string())).map_err(CliError::into_message)
+    }
+
+    fn status(args: &[&str]) -> i32 {
+        exit_code(run_env(args.iter().map(|arg| arg.to_string())))
+    }
+
+    #[test]
+    fn scan_subcommand_and_legacy_invocation_are_equivalent() {
+        let legacy = run(&["--sequence", "GGGGAGGGGAGGGGAGGGG"]);
+        let scan = run(&["scan", "--sequence", "GGGGAGGGGAGGGGAGGGG"]);
+        assert_eq!(legacy, Ok(Outcome::Success));
+        assert_eq!(scan, Ok(Outcome::Success));
+    }
+
+    #[test]
+    fn usage_lists_every_subcommand() {
+        let msg = usage_text();
+        assert_eq!(
+            run_env(["help".to_string()].into_iter()),
+            Err(CliError::Help(msg.clone()))
+        );
+        for command in ["scan", "verify", "compare", "revcomp", "self-test"] {
+            assert!(
+                msg.contains(&format!("\n  {command} ")),
+                "{command} missing"
+            );
+        }
+        let err = run(&["scan", "--min-score"]).unwrap_err();
+        assert!(err.starts_with("missing value for --min-score"));
+    }
+
+    #[test]
+    fn scan_errors_exit_1_usage_and_verify_errors_2_and_help_0() {
+        let missing = env::temp_dir().join(format!("qgrs_exit_missing_{}.fa", std::process::id()));
+        let missing = missing.to_str().unwrap();
+        let out = env::temp_dir().join(format!("qgrs_exit_out_{}", std::process::id()));
+        let out = out.to_str().unwrap();
+
+        assert_eq!(status(&["--sequence", "GGGGAGGGGAGGGGAGGGG"]), 0);
+        assert_eq!(status(&["--file", missing, "--output-dir", out]), 1);
+        assert_eq!(status(&["scan", "--file", missing, "--output-dir", out]), 1);
+        assert_eq!(status(&["revcomp", "--file", missing, "--output", out]), 1);
+        assert_eq!(status(&["scan", "--min-score"]), 2);
+        assert_eq!(status(&["--bogus"]), 2);
+        assert_eq!(status(&["revcomp"]), 2);
+        assert_eq!(status(&["help"]), 0);
+        assert_eq!(status(&["--help"]), 0);
+        assert_eq!(status(&["verify", "-h"]), 0);
+        assert_eq!(status(&["verify", "--file", missing]), 2);
+        assert_eq!(status(&["compare", missing, out]), 2);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use qgrs_rust::qgrs::ScanMetrics;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub(crate) const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// `manifest.json` written into `--output-dir` after a successful `--file` run.
+/// Timestamps are seconds since the Unix epoch.
+#[derive(Debug, Serialize)]
+pub(crate) struct Manifest {
+    pub(crate) qgrs_version: &'static str,
+    pub(crate) input: InputInfo,
+    pub(crate) parameters: RunParameters,
+    pub(crate) started_at: u64,
+    pub(crate) finished_at: u64,
+    pub(crate) chromosomes: Vec<ChromosomeEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct InputInfo {
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<u64>,
+}
+
+impl InputInfo {
+    pub(crate) fn from_path(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Self {
+            path: path.display().to_string(),
+            size: meta.len(),
+            modified: meta.modified().ok().map(unix_seconds),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct RunParameters {
+    pub(crate) min_tetrads: usize,
+    /// `None` for `--min-score auto`.
+    pub(crate) min_score: Option<i32>,
+    /// Effective threshold per tetrad count, whichever way it was chosen.
+    pub(crate) min_scores: BTreeMap<usize, i32>,
+    pub(crate) max_g4_length: usize,
+    pub(crate) max_run: usize,
+    pub(crate) chunk_size: Option<usize>,
+    pub(crate) base: &'static str,
+    /// `g4`, `imotif` or `both`; `base` is the tetrad base of the main scan.
+    pub(crate) motif: &'static str,
+    pub(crate) topology: &'static str,
+    pub(crate) family_policy: &'static str,
+    pub(crate) loop_policy: &'static str,
+    /// `--loop-alphabet` bases in ACGT order; `None` allows any base.
+    pub(crate) loop_alphabet: Option<String>,
+    pub(crate) mode: &'static str,
+    pub(crate) format: &'static str,
+    /// `--coords`: `1-based-inclusive` or `0-based-half-open`.
+    pub(crate) coordinates: &'static str,
+    /// `--filename-index` and `--filename-hash`.
+    pub(crate) filename_index: bool,
+    pub(crate) filename_hash: bool,
+    pub(crate) overlap: bool,
+    pub(crate) pair_strands: bool,
+    pub(crate) keep_empty: bool,
+    pub(crate) chrom_regex: Option<String>,
+    pub(crate) resume: bool,
+    pub(crate) rank: bool,
+    /// `--preserve-case`: `sequence` columns keep the input case.
+    pub(crate) preserve_case: bool,
+    pub(crate) annotate: Option<PathBuf>,
+    pub(crate) exclude_bed: Option<PathBuf>,
+    pub(crate) exclude_min_overlap: Option<f64>,
+    pub(crate) motif_summary: Option<PathBuf>,
+    /// `--aggregate-by`: hits were totalled per interval of this BED.
+    pub(crate) aggregate_by: Option<PathBuf>,
+    pub(crate) report_html: Option<PathBuf>,
+    pub(crate) chrom_sizes: Option<PathBuf>,
+    pub(crate) gruns_output: Option<PathBuf>,
+    /// `--anchors`: the scan only seeded at the positions listed here.
+    pub(crate) anchors: Option<PathBuf>,
+    /// `--max-hits`: most hits written per record.
+    pub(crate) max_hits: Option<usize>,
+    /// `--gtf`: records are the transcripts spliced from this annotation.
+    pub(crate) gtf: Option<PathBuf>,
+}
+
+/// One FASTA record's primary output. `file` is relative to the output
+/// directory. `resumed` entries were left in place by `--resume`, so their
+/// counts are read back from the existing file, and their header
+/// `description` and sequence `length` are not known.
+#[derive(Debug, Serialize)]
+pub(crate) struct ChromosomeEntry {
+    pub(crate) name: String,
+    pub(crate) occurrence: usize,
+    pub(crate) description: Option<String>,
+    /// Bases in the record, line breaks excluded.
+    pub(crate) length: Option<usize>,
+    pub(crate) file: String,
+    pub(crate) records: usize,
+    pub(crate) sha256: String,
+    pub(crate) resumed: bool,
+    /// `--max-hits` cut the record's hits short; unknown, so false, for
+    /// `resumed` entries.
+    pub(crate) truncated: bool,
+    /// Search work counts, with `--verbose`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) metrics: Option<ScanMetrics>,
+}
+
+impl ChromosomeEntry {
+    pub(crate) fn new(
+        name: &str,
+        occurrence: usize,
+        dir: &Path,
+        file: &str,
+        records: usize,
+        resumed: bool,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            occurrence,
+            description: None,
+            length: None,
+            file: file.to_string(),
+            records,
+            sha256: sha256_file(&dir.join(file))?,
+            resumed,
+            truncated: false,
+            metrics: None,
+        })
+    }
+
+    pub(crate) fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub(crate) fn with_length(mut self, length: usize) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Option<ScanMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub(crate) fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+}
+
+impl Manifest {
+    pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+pub(crate) fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,297 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use qgrs_rust::qgrs::{G4, G4Record};
+
+use crate::manifest::Manifest;
+
+pub(crate) const DEFAULT_REPORT_TOP_HITS: usize = 100;
+const SCORE_BIN_WIDTH: i32 = 10;
+const BAR_MAX_PX: usize = 300;
+const BAR_ROW_PX: usize = 20;
+
+/// Data for `--report-html`, gathered per record while outputs are written.
+/// Only the best `top_hits` hits of each record are kept, so the report stays
+/// small however many hits a genome has.
+#[derive(Debug, Default)]
+pub(crate) struct HtmlReport {
+    top_hits: usize,
+    records: BTreeMap<(String, usize), RecordDetails>,
+}
+
+/// One record's share of an [`HtmlReport`]; built without holding the report.
+#[derive(Debug, Default)]
+pub(crate) struct RecordDetails {
+    sequence_len: usize,
+    hits: usize,
+    /// Hit count per score bin, keyed by the bin's lower bound.
+    score_bins: BTreeMap<i32, usize>,
+    tetrads: BTreeMap<usize, usize>,
+    /// Highest scores first; ties in start order.
+    top: Vec<(G4Record, String)>,
+}
+
+impl RecordDetails {
+    pub(crate) fn new(sequence_len: usize, hits: &[G4], top_hits: usize) -> Self {
+        let mut details = Self {
+            sequence_len,
+            hits: hits.len(),
+            ..Self::default()
+        };
+        for hit in hits {
+            let bin = hit.score.div_euclid(SCORE_BIN_WIDTH) * SCORE_BIN_WIDTH;
+            *details.score_bins.entry(bin).or_default() += 1;
+            *details.tetrads.entry(hit.tetrads).or_default() += 1;
+        }
+        let mut ranked: Vec<&G4> = hits.iter().collect();
+        ranked.sort_by_key(|hit| (Reverse(hit.score), hit.start));
+        details.top = ranked
+            .into_iter()
+            .take(top_hits)
+            .map(|hit| (hit.to_record(), hit.marked_sequence()))
+            .collect();
+        details
+    }
+}
+
+impl HtmlReport {
+    pub(crate) fn new(top_hits: usize) -> Self {
+        Self {
+            top_hits,
+            records: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn top_hits(&self) -> usize {
+        self.top_hits
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, occurrence: usize, details: RecordDetails) {
+        self.records.insert((name.to_string(), occurrence), details);
+    }
+
+    /// A standalone page: parameters, the manifest's per-record table, score
+    /// and tetrad bar charts over every scanned record, then the top hits of
+    /// each record. Records skipped by `--resume` only appear in the summary.
+    pub(crate) fn render(&self, manifest: &Manifest) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>QGRS report</title>\n<style>\n\
+             body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; margin-bottom: 1.5em; }\n\
+             th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }\n\
+             th.sortable { cursor: pointer; background: #f0f0f0; }\n\
+             .motif { font-family: monospace; }\n\
+             .tetrad { color: #b00; font-weight: bold; }\n\
+             .bar { fill: #4a7ab5; }\n\
+             svg text { font-size: 12px; }\n\
+             </style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(
+            out,
+            "<h1>QGRS report</h1>\n<p>qgrs {} &middot; input <code>{}</code></p>",
+            manifest.qgrs_version,
+            escape_html(&manifest.input.path)
+        );
+
+        out.push_str("<h2>Parameters</h2>\n<table>\n");
+        if let Ok(serde_json::Value::Object(parameters)) =
+            serde_json::to_value(&manifest.parameters)
+        {
+            for (key, value) in parameters {
+                let value = match value {
+                    serde_json::Value::Null => "-".to_string(),
+                    serde_json::Value::String(text) => text,
+                    other => other.to_string(),
+                };
+                let _ = writeln!(
+                    out,
+                    "<tr><th>{key}</th><td>{}</td></tr>",
+                    escape_html(&value)
+                );
+            }
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Records</h2>\n<table class=\"sortable\">\n<thead><tr>");
+        for column in ["#", "Name", "Description", "Length", "Hits", "Output"] {
+            let _ = write!(out, "<th class=\"sortable\">{column}</th>");
+        }
+        out.push_str("</tr></thead>\n<tbody>\n");
+        for (index, entry) in manifest.chromosomes.iter().enumerate() {
+            let details = self.records.get(&(entry.name.clone(), entry.occurrence));
+            let length = details.map_or_else(|| "-".to_string(), |d| d.sequence_len.to_string());
+            let note = if entry.resumed { " (resumed)" } else { "" };
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{length}</td><td>{}</td>\
+                 <td>{}{note}</td></tr>",
+                index + 1,
+                escape_html(&entry.name),
+                escape_html(entry.description.as_deref().unwrap_or("-")),
+                entry.records,
+                escape_html(&entry.file)
+            );
+        }
+        out.push_str("</tbody>\n</table>\n");
+
+        let mut score_bins: BTreeMap<i32, usize> = BTreeMap::new();
+        let mut tetrads: BTreeMap<usize, usize> = BTreeMap::new();
+        for details in self.records.values() {
+            for (bin, count) in &details.score_bins {
+                *score_bins.entry(*bin).or_default() += count;
+            }
+            for (tetrad_count, count) in &details.tetrads {
+                *tetrads.entry(*tetrad_count).or_default() += count;
+            }
+        }
+        out.push_str("<h2>Score distribution</h2>\n");
+        out.push_str(&svg_bars(score_bins.iter().map(|(bin, count)| {
+            (format!("{bin}–{}", bin + SCORE_BIN_WIDTH - 1), *count)
+        })));
+        out.push_str("<h2>Tetrads</h2>\n");
+        out.push_str(&svg_bars(tetrads.iter().map(|(tetrad_count, count)| {
+            (format!("{tetrad_count} tetrads"), *count)
+        })));
+
+        let _ = writeln!(out, "<h2>Top {} hits per record</h2>", self.top_hits);
+        for entry in &manifest.chromosomes {
+            let Some(details) = self.records.get(&(entry.name.clone(), entry.occurrence)) else {
+                continue;
+            };
+            let label = if entry.occurrence == 0 {
+                escape_html(&entry.name)
+            } else {
+                format!("{} (#{})", escape_html(&entry.name), entry.occurrence + 1)
+            };
+            let _ = writeln!(
+                out,
+                "<h3>{label}: {} of {} hits</h3>",
+                details.top.len(),
+                details.hits
+            );
+            if details.top.is_empty() {
+                continue;
+            }
+            out.push_str("<table class=\"sortable\">\n<thead><tr>");
+            for column in ["Start", "End", "Length", "Tetrads", "Score", "Motif"] {
+                let _ = write!(out, "<th class=\"sortable\">{column}</th>");
+            }
+            out.push_str("</tr></thead>\n<tbody>\n");
+            for (record, marked) in &details.top {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                     <td class=\"motif\">{}</td></tr>",
+                    record.start,
+                    record.end,
+                    record.length,
+                    record.tetrads,
+                    record.score,
+                    highlight_tetrads(marked)
+                );
+            }
+            out.push_str("</tbody>\n</table>\n");
+        }
+
+        out.push_str(SORT_SCRIPT);
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Horizontal bar chart, one labelled bar per row, scaled to the largest count.
+fn svg_bars<I>(rows: I) -> String
+where
+    I: IntoIterator<Item = (String, usize)>,
+{
+    let rows: Vec<(String, usize)> = rows.into_iter().collect();
+    if rows.is_empty() {
+        return "<p>No hits.</p>\n".to_string();
+    }
+    let max = rows
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        BAR_MAX_PX + 180,
+        rows.len() * BAR_ROW_PX
+    );
+    for (row, (label, count)) in rows.iter().enumerate() {
+        let y = row * BAR_ROW_PX;
+        let width = (count * BAR_MAX_PX).div_ceil(max);
+        let _ = writeln!(
+            svg,
+            "<text x=\"0\" y=\"{}\">{}</text><rect class=\"bar\" x=\"90\" y=\"{}\" \
+             width=\"{width}\" height=\"14\"/><text x=\"{}\" y=\"{}\">{count}</text>",
+            y + 14,
+            escape_html(label),
+            y + 3,
+            95 + width,
+            y + 14
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Wraps each uppercase (tetrad) run of a marked motif in a `tetrad` span.
+fn highlight_tetrads(marked: &str) -> String {
+    let mut out = String::new();
+    let mut in_tetrad = false;
+    for ch in marked.chars() {
+        let tetrad = ch.is_ascii_uppercase();
+        if tetrad != in_tetrad {
+            out.push_str(if tetrad {
+                "<span class=\"tetrad\">"
+            } else {
+                "</span>"
+            });
+            in_tetrad = tetrad;
+        }
+        out.push(ch);
+    }
+    if in_tetrad {
+        out.push_str("</span>");
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+// Click a header to sort its table; numeric columns sort numerically.
+const SORT_SCRIPT: &str = r#"<script>
+document.querySelectorAll("th.sortable").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var table = th.closest("table"), body = table.tBodies[0];
+    var index = Array.prototype.indexOf.call(th.parentNode.children, th);
+    var ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    var rows = Array.prototype.slice.call(body.rows);
+    rows.sort(function (a, b) {
+      var x = a.cells[index].textContent, y = b.cells[index].textContent;
+      var nx = parseFloat(x), ny = parseFloat(y);
+      var order = isNaN(nx) || isNaN(ny) ? x.localeCompare(y) : nx - ny;
+      return ascending ? order : -order;
+    });
+    rows.forEach(function (row) { body.appendChild(row); });
+  });
+});
+</script>
+"#;
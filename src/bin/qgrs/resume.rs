@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use qgrs_rust::qgrs::{InputMode, StreamCheckpoint};
+
+use crate::manifest::RunParameters;
+use crate::scan::{OutputFormat, family_path, overlap_path, pairs_path};
+use crate::{CliError, usage};
+
+/// Checks `--checkpoint-every` against the `--checkpoint` it tunes.
+pub(crate) fn check_interval(
+    checkpoint: Option<&Path>,
+    every: Option<usize>,
+) -> Result<(), CliError> {
+    if let Some(every) = every {
+        if checkpoint.is_none() {
+            return Err(usage("--checkpoint-every requires --checkpoint"));
+        }
+        if every == 0 {
+            return Err(usage("--checkpoint-every must be > 0"));
+        }
+    }
+    Ok(())
+}
+
+/// `--checkpoint` of a single stream-mode file, tagged with the run's
+/// parameters so one written with other options is refused. Saved every
+/// `--checkpoint-every` windows when given, on the library's timer otherwise.
+pub(crate) fn open_checkpoint(
+    path: Option<PathBuf>,
+    every: Option<usize>,
+    batch: bool,
+    mode: Option<InputMode>,
+    parameters: &RunParameters,
+    fsync: bool,
+) -> Result<Option<StreamCheckpoint>, CliError> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    if batch {
+        return Err(usage(
+            "--checkpoint needs a single FASTA file, not a directory",
+        ));
+    }
+    if !matches!(mode, Some(InputMode::Stream)) {
+        return Err(usage(
+            "--checkpoint requires --mode stream; mmap scans resume per record with --resume",
+        ));
+    }
+    let parameters = serde_json::to_string(parameters)
+        .map_err(|err| format!("failed to serialize parameters: {err}"))?;
+    let checkpoint = StreamCheckpoint::new(path)
+        .with_parameters(parameters)
+        .with_fsync(fsync);
+    Ok(Some(match every {
+        Some(every) => checkpoint.with_interval(every),
+        None => checkpoint,
+    }))
+}
+
+/// With `--resume`, a record is done when its primary output (and sidecars,
+/// if requested) exist and are non-empty. Outputs are renamed into place only
+/// after being fully written, so a crash cannot leave a partial file here.
+pub(crate) fn outputs_complete(
+    dir: &Path,
+    format: OutputFormat,
+    include_overlap: bool,
+    pair_strands: bool,
+    filename: &str,
+) -> bool {
+    let primary = dir.join(filename);
+    let mut required = vec![primary.clone()];
+    if include_overlap {
+        required.push(overlap_path(&primary, format));
+        required.push(family_path(&primary, format));
+    }
+    if pair_strands {
+        required.push(pairs_path(&primary));
+    }
+    required
+        .iter()
+        .all(|path| fs::metadata(path).is_ok_and(|meta| meta.len() > 0))
+}
+
+pub(crate) fn log_resumed(name: &str, occurrence: usize) {
+    if occurrence == 0 {
+        eprintln!("Resume: skipping {name}, outputs already exist");
+    } else {
+        eprintln!(
+            "Resume: skipping {name} (#{}), outputs already exist",
+            occurrence + 1
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use qgrs_rust::qgrs::io::write_atomically;
+use qgrs_rust::qgrs::write_reverse_complement;
+
+use crate::{Args, CliError, Outcome, help, usage};
+
+pub(crate) fn run<I>(mut args: Args<I>) -> Result<Outcome, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut path: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => {
+                path = Some(PathBuf::from(args.value("--file")?));
+            }
+            "--output" => {
+                output = Some(PathBuf::from(args.value("--output")?));
+            }
+            "--help" | "-h" => return Err(help()),
+            other => {
+                return Err(usage(&format!("unknown argument '{other}'")));
+            }
+        }
+    }
+
+    let path = path.ok_or_else(|| usage("revcomp requires --file"))?;
+    let output = output.ok_or_else(|| usage("revcomp requires --output"))?;
+    if !path.exists() {
+        return Err(CliError::Scan(format!("file does not exist: {path:?}")));
+    }
+    let mut records = 0;
+    write_atomically(&output, true, |tmp| {
+        let mut out = BufWriter::new(File::create(tmp)?);
+        records = write_reverse_complement(&path, &mut out)?;
+        out.flush()
+    })
+    .map_err(|err| format!("failed to write {output:?}: {err}"))?;
+    eprintln!("Wrote {records} reverse-complemented record(s) to {output:?}");
+    Ok(Outcome::Success)
+}
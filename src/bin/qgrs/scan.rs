@@ -0,0 +1,6721 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use arrow_ipc::reader::FileReader;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use qgrs_rust::qgrs::intervals::IntervalIndex;
+use qgrs_rust::qgrs::postprocess::{IntervalAggregate, MotifSummary};
+use qgrs_rust::qgrs::transcripts::{GenomicSpan, Transcript};
+use qgrs_rust::qgrs::{
+    self, ChromSequence, ColumnValues, CoordinateSystem, DEFAULT_MAX_G4_LENGTH, DEFAULT_MAX_RUN,
+    DEFAULT_PARQUET_BATCH_ROWS, ExtraColumn, Family, FamilyPolicy, FamilyStats, G4, G4Record,
+    InputMode, LoadOptions, LoopPolicy, MAX_CHUNK_SIZE, MOTIF_TYPE_COLUMN, MotifType, ParquetSink,
+    QgrsError, QuartetBase, RecordHeader, ScanConfig, ScanLimits, SearchResults, SequenceTopology,
+    StreamCheckpoint, WHOLE_RECORD_MAX_LEN,
+};
+use rayon::prelude::*;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::config::{MinScore, RunConfig};
+use crate::logging::parse_log_format;
+use crate::manifest::{
+    ChromosomeEntry, InputInfo, MANIFEST_FILENAME, Manifest, RunParameters, unix_seconds,
+};
+use crate::report::{DEFAULT_REPORT_TOP_HITS, HtmlReport, RecordDetails};
+use crate::resume::{self, log_resumed, outputs_complete};
+use crate::sink::{self, SinkOptions};
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteWriter;
+use crate::{Args, CliError, help, interrupt, usage, writer};
+
+/// `--min-tetrads` when neither the command line nor `--config` sets it.
+const DEFAULT_MIN_TETRADS: usize = 2;
+
+pub(crate) fn run<I>(args: Args<I>) -> Result<(), CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let config = read_config(args)?;
+
+    // Defaults and value checks, shared by both sources.
+    let RunConfig {
+        sequence: sequence_arg,
+        file: file_arg,
+        glob,
+        fail_fast,
+        min_tetrads,
+        min_score,
+        no_g_loops,
+        loop_alphabet,
+        allow_empty_config,
+        base,
+        motif,
+        max_run,
+        max_g4_length,
+        chunk_size,
+        format,
+        coords,
+        output: output_path,
+        output_dir,
+        filename_index,
+        filename_hash,
+        stdout,
+        mode,
+        populate,
+        record_parallel,
+        overlap: include_overlap,
+        pair_strands,
+        circular,
+        family_policy,
+        keep_empty,
+        chrom_regex,
+        resume,
+        checkpoint: checkpoint_path,
+        checkpoint_every,
+        force,
+        no_fsync,
+        dry_run,
+        log,
+        verbose,
+        gruns_output,
+        anchors: anchors_path,
+        motif_summary,
+        aggregate_by,
+        report_html,
+        chrom_sizes,
+        report_top,
+        max_hits,
+        exclude_bed: exclude_path,
+        exclude_min_overlap,
+        rank,
+        preserve_case,
+        annotate: annotate_path,
+        gtf: gtf_path,
+        feature,
+    } = config;
+    let min_tetrads = min_tetrads.unwrap_or(DEFAULT_MIN_TETRADS);
+    if min_tetrads == 0 {
+        return Err(usage("--min-tetrads must be > 0"));
+    }
+    // `None` is `--min-score auto`: a threshold per tetrad count.
+    let min_score = match min_score {
+        None => None,
+        Some(MinScore::Fixed(score)) => Some(score),
+        Some(MinScore::Named(value)) => parse_min_score(&value)?,
+    };
+    let max_run = max_run.unwrap_or(DEFAULT_MAX_RUN);
+    if max_run == 0 {
+        return Err(usage("--max-run must be > 0"));
+    }
+    let max_g4_length = max_g4_length.unwrap_or(DEFAULT_MAX_G4_LENGTH);
+    if max_g4_length == 0 {
+        return Err(usage("--max-g4-length must be > 0"));
+    }
+    if max_hits == Some(0) {
+        return Err(usage("--max-hits must be > 0"));
+    }
+    let format: OutputFormat = match format {
+        Some(value) => value.try_into()?,
+        None => OutputFormat::Csv,
+    };
+    let coordinates = match coords {
+        None | Some(1) => CoordinateSystem::OneBasedInclusive,
+        Some(0) => CoordinateSystem::ZeroBasedHalfOpen,
+        Some(_) => return Err(usage("--coords must be 1 or 0")),
+    };
+    // `None` is `auto`, settled per input file by `resolve_input_mode`.
+    let mode = mode.as_deref().map_or(Ok(None), parse_mode)?;
+    let record_parallel = record_parallel
+        .as_deref()
+        .map(parse_record_parallel)
+        .transpose()?;
+    let base = base.as_deref().map(parse_base).transpose()?;
+    let motif = motif.as_deref().map(parse_motif).transpose()?;
+    let family_policy = family_policy
+        .as_deref()
+        .map_or(Ok(FamilyPolicy::Score), parse_family_policy)?;
+    let loop_policy = if no_g_loops.unwrap_or(false) {
+        LoopPolicy::NoTargetBase
+    } else {
+        LoopPolicy::Any
+    };
+    let loop_alphabet = loop_alphabet
+        .as_deref()
+        .map(parse_loop_alphabet)
+        .transpose()?;
+    let chrom_regex = chrom_regex
+        .map(|value| {
+            Regex::new(&value).map_err(|err| usage(&format!("invalid --chrom-regex: {err}")))
+        })
+        .transpose()?;
+    let log_format = log.as_deref().map(parse_log_format).transpose()?;
+    if let Some(value) = exclude_min_overlap
+        && !(value > 0.0 && value <= 1.0)
+    {
+        return Err(usage("--exclude-min-overlap must be a fraction in (0, 1]"));
+    }
+    let include_overlap = include_overlap.unwrap_or(false);
+    let pair_strands = pair_strands.unwrap_or(false);
+    let circular = circular.unwrap_or(false);
+    let keep_empty = keep_empty.unwrap_or(false);
+    let resume = resume.unwrap_or(false);
+    let force = force.unwrap_or(false);
+    let fsync = !no_fsync.unwrap_or(false);
+    let dry_run = dry_run.unwrap_or(false);
+    let stdout = stdout.unwrap_or(false);
+    let fail_fast = fail_fast.unwrap_or(false);
+    let allow_empty_config = allow_empty_config.unwrap_or(false);
+    let verbose = verbose.unwrap_or(false);
+    let populate = populate.unwrap_or(false);
+    let naming = FilenameScheme {
+        index: filename_index.unwrap_or(false),
+        hash: filename_hash.unwrap_or(false),
+    };
+    // The library would count hits `--exclude-bed` removes afterwards, so
+    // with exclusion the cap is applied after it instead.
+    let deferred_max_hits = max_hits.filter(|_| exclude_path.is_some());
+    let mut post = PostProcessOptions {
+        max_hits: deferred_max_hits,
+        exclude_min_overlap,
+        rank: rank.unwrap_or(false),
+        motif_summary_path: motif_summary,
+        report_html_path: report_html,
+        chrom_sizes_path: chrom_sizes,
+        ..PostProcessOptions::default()
+    };
+
+    let motif = match (motif, base) {
+        (Some(_), Some(_)) => {
+            return Err(usage(
+                "--motif cannot be combined with --base; --motif imotif scans C tetrads",
+            ));
+        }
+        (Some(motif), None) => motif,
+        (None, base) => Motifs::from(base.unwrap_or(QuartetBase::G)),
+    };
+    let target_base = motif.primary_base();
+    let input = match (sequence_arg, file_arg) {
+        (Some(_), Some(_)) => {
+            return Err(usage("cannot provide both --sequence and --file"));
+        }
+        (Some(seq), None) => InputSpec::Inline(seq),
+        (None, Some(path)) => InputSpec::File(path),
+        (None, None) => return Err(usage("must provide --sequence or --file")),
+    };
+
+    let mut limits = ScanLimits::new(max_g4_length, max_run)
+        .with_loop_policy(loop_policy)
+        .with_loop_alphabet(loop_alphabet);
+    limits
+        .validate(min_tetrads)
+        .map_err(|err| usage(&format!("invalid scan limits: {err}")))?;
+    if let Some(chunk_size) = chunk_size {
+        if chunk_size < max_g4_length {
+            return Err(usage("--chunk-size must be ≥ --max-g4-length"));
+        }
+        if chunk_size > MAX_CHUNK_SIZE {
+            return Err(usage(&format!("--chunk-size must be ≤ {MAX_CHUNK_SIZE}")));
+        }
+        limits = limits.with_chunk_size(chunk_size);
+    }
+    let topology = if circular {
+        SequenceTopology::Circular
+    } else {
+        SequenceTopology::Linear
+    };
+    let scan = ScanConfig::new(
+        min_tetrads,
+        min_score.unwrap_or_default(),
+        limits,
+        topology,
+        target_base,
+    )
+    .with_min_score(min_score.map_or(qgrs::MinScore::Auto, qgrs::MinScore::Fixed))
+    .with_family_policy(family_policy)
+    .with_metrics(verbose)
+    .with_gruns(gruns_output.is_some())
+    .with_coordinates(coordinates)
+    .with_max_hits(max_hits.filter(|_| deferred_max_hits.is_none()))
+    .with_preserve_case(preserve_case.unwrap_or(false));
+    if !allow_empty_config {
+        scan.check_satisfiable()
+            .map_err(|err| usage(&format!("{err}; pass --allow-empty-config to scan anyway")))?;
+    }
+    if matches!(format, OutputFormat::Arrow | OutputFormat::ParquetDataset) && include_overlap {
+        // Sidecars inside a dataset's partitions would be read as extra parts.
+        return Err(usage(&format!(
+            "--overlap cannot be used with --format {}",
+            format.cli_name()
+        )));
+    }
+    // Options that work in one mode only settle `auto` up front, so they are
+    // never turned down because of a file's size.
+    let mode = mode.or_else(|| {
+        if motif == Motifs::Both
+            || pair_strands
+            || populate
+            || record_parallel == Some(RecordParallel::On)
+            || gruns_output.is_some()
+            || anchors_path.is_some()
+            || gtf_path.is_some()
+        {
+            Some(InputMode::Mmap)
+        } else if checkpoint_path.is_some() {
+            Some(InputMode::Stream)
+        } else {
+            None
+        }
+    });
+    if motif == Motifs::Both {
+        // Each motif is consolidated on its own, so one family list or pairing
+        // would mix them up.
+        if include_overlap || pair_strands {
+            return Err(usage(
+                "--overlap and --pair-strands cannot be used with --motif both",
+            ));
+        }
+        if stdout || matches!(format, OutputFormat::QgrsMapper) {
+            return Err(usage(
+                "--motif both needs a motif_type column; not available with --stdout or --format qgrs-mapper",
+            ));
+        }
+        #[cfg(feature = "sqlite")]
+        if matches!(format, OutputFormat::Sqlite) {
+            return Err(usage("--motif both cannot be used with --format sqlite"));
+        }
+        if matches!(mode, Some(InputMode::Stream)) {
+            return Err(usage(
+                "--motif both needs --mode mmap to scan each record a second time",
+            ));
+        }
+    }
+    if pair_strands {
+        // Pairs sit next to each record's output, like `--overlap` sidecars.
+        if matches!(format, OutputFormat::Arrow | OutputFormat::ParquetDataset) {
+            return Err(usage(&format!(
+                "--pair-strands cannot be used with --format {}",
+                format.cli_name()
+            )));
+        }
+        if stdout {
+            return Err(usage("--pair-strands cannot be used with --stdout"));
+        }
+        if matches!(mode, Some(InputMode::Stream)) {
+            return Err(usage(
+                "--pair-strands needs --mode mmap to scan each record a second time",
+            ));
+        }
+    }
+    if populate && matches!(mode, Some(InputMode::Stream)) {
+        return Err(usage("--populate needs --mode mmap"));
+    }
+    if record_parallel == Some(RecordParallel::On) && matches!(mode, Some(InputMode::Stream)) {
+        return Err(usage(
+            "--record-parallel on needs --mode mmap; stream mode scans one record at a time",
+        ));
+    }
+    if gruns_output.is_some() {
+        if stdout {
+            return Err(usage("--gruns-output cannot be used with --stdout"));
+        }
+        // Stream windows are scanned as they arrive; runs are only collected
+        // over a whole record.
+        if matches!(mode, Some(InputMode::Stream)) {
+            return Err(usage("--gruns-output needs --mode mmap"));
+        }
+    }
+    if anchors_path.is_some() {
+        if matches!(input, InputSpec::Inline(_)) {
+            return Err(usage("--anchors can only be used with --file"));
+        }
+        // The second scans and the G-runs would still cover every run.
+        if gruns_output.is_some() || pair_strands || motif == Motifs::Both {
+            return Err(usage(
+                "--anchors cannot be used with --gruns-output, --pair-strands or --motif both",
+            ));
+        }
+        if matches!(mode, Some(InputMode::Stream)) {
+            return Err(usage("--anchors needs --mode mmap"));
+        }
+    }
+    if let Some(feature) = feature.as_deref() {
+        if gtf_path.is_none() {
+            return Err(usage("--feature requires --gtf"));
+        }
+        if feature != "transcript" {
+            return Err(usage("--feature must be 'transcript'"));
+        }
+    }
+    if gtf_path.is_some() {
+        if matches!(input, InputSpec::Inline(_)) {
+            return Err(usage("--gtf can only be used with --file"));
+        }
+        // Transcripts are spliced out of the whole loaded genome.
+        if matches!(mode, Some(InputMode::Stream)) || checkpoint_path.is_some() {
+            return Err(usage("--gtf needs --mode mmap"));
+        }
+        if stdout {
+            return Err(usage("--gtf cannot be used with --stdout"));
+        }
+        // Their intervals and lengths are genomic, the rows are not.
+        if annotate_path.is_some()
+            || exclude_path.is_some()
+            || aggregate_by.is_some()
+            || post.chrom_sizes_path.is_some()
+        {
+            return Err(usage(
+                "--gtf cannot be used with --annotate, --exclude-bed, --aggregate-by or --chrom-sizes",
+            ));
+        }
+        if matches!(
+            format,
+            OutputFormat::QgrsMapper | OutputFormat::FamilyRanges
+        ) {
+            return Err(usage(&format!(
+                "--gtf adds genomic columns; not available with --format {}",
+                format.cli_name()
+            )));
+        }
+        #[cfg(feature = "sqlite")]
+        if matches!(format, OutputFormat::Sqlite) {
+            return Err(usage("--gtf cannot be used with --format sqlite"));
+        }
+    }
+    let anchors = anchors_path
+        .as_deref()
+        .map(|path| Anchors::from_path(path, coordinates))
+        .transpose()?;
+    if matches!(format, OutputFormat::QgrsMapper) {
+        // The fixed layout has no room for sidecars or opt-in columns.
+        if include_overlap {
+            return Err(usage("--overlap cannot be used with --format qgrs-mapper"));
+        }
+        if post.rank || annotate_path.is_some() {
+            return Err(usage(
+                "--rank and --annotate cannot be used with --format qgrs-mapper",
+            ));
+        }
+        // Case marks the tetrads there.
+        if scan.preserves_case() {
+            return Err(usage(
+                "--preserve-case cannot be used with --format qgrs-mapper",
+            ));
+        }
+        // QGRS Mapper numbers positions from 1.
+        if coordinates == CoordinateSystem::ZeroBasedHalfOpen {
+            return Err(usage("--coords 0 cannot be used with --format qgrs-mapper"));
+        }
+    }
+    if matches!(format, OutputFormat::FamilyRanges) {
+        // One row per family: there are no hit rows to add columns to, filter
+        // or pair.
+        if include_overlap || pair_strands || motif == Motifs::Both {
+            return Err(usage(
+                "--overlap, --pair-strands and --motif both cannot be used with --format family-ranges",
+            ));
+        }
+        if post.rank || annotate_path.is_some() || exclude_path.is_some() {
+            return Err(usage(
+                "--rank, --annotate and --exclude-bed cannot be used with --format family-ranges",
+            ));
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    if matches!(format, OutputFormat::Sqlite) {
+        // Hits go into one database, so per-file options have nothing to act on.
+        if include_overlap || pair_strands || resume || dry_run {
+            return Err(usage(
+                "--overlap, --pair-strands, --resume and --dry-run cannot be used with --format sqlite",
+            ));
+        }
+        if checkpoint_path.is_some() {
+            return Err(usage("--checkpoint cannot be used with --format sqlite"));
+        }
+        if post.rank || annotate_path.is_some() || post.report_html_path.is_some() {
+            return Err(usage(
+                "--rank, --annotate and --report-html cannot be used with --format sqlite",
+            ));
+        }
+    }
+    if stdout {
+        // Every record shares one CSV stream, so there is no file to name.
+        if output_path.is_some() || output_dir.is_some() {
+            return Err(usage(
+                "--stdout cannot be used with --output or --output-dir",
+            ));
+        }
+        if !matches!(format, OutputFormat::Csv) {
+            return Err(usage(&format!(
+                "--stdout only writes CSV; write --format {} to a file instead",
+                format.cli_name()
+            )));
+        }
+        if include_overlap || resume || dry_run {
+            return Err(usage(
+                "--overlap, --resume and --dry-run cannot be used with --stdout",
+            ));
+        }
+        if post.report_html_path.is_some() {
+            return Err(usage("--report-html cannot be used with --stdout"));
+        }
+        if aggregate_by.is_some() {
+            return Err(usage("--aggregate-by cannot be used with --stdout"));
+        }
+        if post.chrom_sizes_path.is_some() {
+            return Err(usage("--chrom-sizes cannot be used with --stdout"));
+        }
+        if checkpoint_path.is_some() {
+            return Err(usage("--checkpoint cannot be used with --stdout"));
+        }
+    }
+    if report_top.is_some() && post.report_html_path.is_none() {
+        return Err(usage("--report-top requires --report-html"));
+    }
+    resume::check_interval(checkpoint_path.as_deref(), checkpoint_every)?;
+    post.report = Mutex::new(HtmlReport::new(
+        report_top.unwrap_or(DEFAULT_REPORT_TOP_HITS),
+    ));
+    if let Some(format) = log_format {
+        crate::logging::install(format);
+    }
+
+    let parameters = RunParameters {
+        min_tetrads,
+        min_score,
+        min_scores: scan.min_scores().into_iter().collect(),
+        max_g4_length,
+        max_run,
+        chunk_size,
+        base: target_base.cli_name(),
+        motif: motif.cli_name(),
+        topology: if circular { "circular" } else { "linear" },
+        family_policy: family_policy.cli_name(),
+        loop_policy: loop_policy.cli_name(),
+        loop_alphabet: loop_alphabet.map(loop_alphabet_label),
+        mode: mode.map_or("auto", mode_name),
+        format: format.cli_name(),
+        coordinates: coordinates.label(),
+        filename_index: naming.index,
+        filename_hash: naming.hash,
+        overlap: include_overlap,
+        pair_strands,
+        keep_empty,
+        chrom_regex: chrom_regex.as_ref().map(|regex| regex.as_str().to_string()),
+        resume,
+        rank: post.rank,
+        preserve_case: scan.preserves_case(),
+        annotate: annotate_path.clone(),
+        exclude_bed: exclude_path.clone(),
+        exclude_min_overlap: post.exclude_min_overlap,
+        motif_summary: post.motif_summary_path.clone(),
+        aggregate_by: aggregate_by.clone(),
+        report_html: post.report_html_path.clone(),
+        chrom_sizes: post.chrom_sizes_path.clone(),
+        gruns_output: gruns_output.clone(),
+        anchors: anchors_path.clone(),
+        max_hits,
+        gtf: gtf_path.clone(),
+    };
+    let options = FileOptions {
+        mode,
+        populate,
+        record_parallel,
+        format,
+        output_dir: None,
+        include_overlap,
+        records: RecordFilter {
+            keep_empty,
+            chrom_regex,
+        },
+        resume,
+        checkpoint: None,
+        motif,
+        pair_strands,
+        gruns_dir: gruns_output,
+        anchors,
+        naming,
+        force,
+        fsync,
+        post,
+        parameters,
+    };
+    let sink = SinkOptions {
+        output: output_path,
+        output_dir,
+        stdout,
+        glob,
+        fail_fast,
+        dry_run,
+        max_hits,
+        annotate: annotate_path,
+        exclude: exclude_path,
+        aggregate_by,
+        gtf: gtf_path,
+        checkpoint: checkpoint_path,
+        checkpoint_every,
+    };
+    sink::write(input, scan, options, sink)
+}
+
+/// The `scan` flags on the command line, over the `--config` file when one
+/// is given.
+fn read_config<I>(mut args: Args<I>) -> Result<RunConfig, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut cli = RunConfig::default();
+    let mut config_path: Option<PathBuf> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let value = args.value("--config")?;
+                config_path = Some(PathBuf::from(value));
+            }
+            "--sequence" => {
+                cli.sequence = Some(args.value("--sequence")?);
+            }
+            "--file" => {
+                cli.file = Some(PathBuf::from(args.value("--file")?));
+            }
+            "--min-tetrads" => {
+                let value = args.parse::<usize>("--min-tetrads", "a positive integer")?;
+                cli.min_tetrads = Some(value);
+            }
+            "--min-score" => {
+                cli.min_score = Some(MinScore::Named(args.value("--min-score")?));
+            }
+            "--format" => {
+                cli.format = Some(args.value("--format")?);
+            }
+            "--coords" => {
+                cli.coords = Some(args.parse::<u8>("--coords", "1 or 0")?);
+            }
+            "--mode" => {
+                cli.mode = Some(args.value("--mode")?);
+            }
+            "--base" => {
+                cli.base = Some(args.value("--base")?);
+            }
+            "--motif" => {
+                cli.motif = Some(args.value("--motif")?);
+            }
+            "--family-policy" => {
+                cli.family_policy = Some(args.value("--family-policy")?);
+            }
+            "--max-run" => {
+                let value = args.parse::<usize>("--max-run", "a positive integer")?;
+                cli.max_run = Some(value);
+            }
+            "--max-g-run" => {
+                return Err(usage("--max-g-run was replaced by --max-run"));
+            }
+            "--max-g4-length" => {
+                let value = args.parse::<usize>("--max-g4-length", "a positive integer")?;
+                cli.max_g4_length = Some(value);
+            }
+            "--chunk-size" => {
+                let value = args.parse::<usize>("--chunk-size", "a positive integer")?;
+                cli.chunk_size = Some(value);
+            }
+            "--output" => {
+                cli.output = Some(PathBuf::from(args.value("--output")?));
+            }
+            "--output-dir" => {
+                cli.output_dir = Some(PathBuf::from(args.value("--output-dir")?));
+            }
+            "--overlap" => {
+                cli.overlap = Some(true);
+            }
+            "--pair-strands" => {
+                cli.pair_strands = Some(true);
+            }
+            "--circular" => {
+                cli.circular = Some(true);
+            }
+            "--keep-empty" => {
+                cli.keep_empty = Some(true);
+            }
+            "--no-g-loops" => {
+                cli.no_g_loops = Some(true);
+            }
+            "--loop-alphabet" => {
+                cli.loop_alphabet = Some(args.value("--loop-alphabet")?);
+            }
+            "--allow-empty-config" => {
+                cli.allow_empty_config = Some(true);
+            }
+            "--chrom-regex" => {
+                cli.chrom_regex = Some(args.value("--chrom-regex")?);
+            }
+            "--resume" => {
+                cli.resume = Some(true);
+            }
+            "--checkpoint" => {
+                cli.checkpoint = Some(PathBuf::from(args.value("--checkpoint")?));
+            }
+            "--checkpoint-every" => {
+                let value = args.parse::<usize>("--checkpoint-every", "a positive integer")?;
+                cli.checkpoint_every = Some(value);
+            }
+            "--force" => {
+                cli.force = Some(true);
+            }
+            "--filename-index" => {
+                cli.filename_index = Some(true);
+            }
+            "--filename-hash" => {
+                cli.filename_hash = Some(true);
+            }
+            "--no-fsync" => {
+                cli.no_fsync = Some(true);
+            }
+            "--dry-run" => {
+                cli.dry_run = Some(true);
+            }
+            "--stdout" => {
+                cli.stdout = Some(true);
+            }
+            "--glob" => {
+                cli.glob = Some(args.value("--glob")?);
+            }
+            "--fail-fast" => {
+                cli.fail_fast = Some(true);
+            }
+            "--verbose" => {
+                cli.verbose = Some(true);
+            }
+            "--populate" => {
+                cli.populate = Some(true);
+            }
+            "--record-parallel" => {
+                cli.record_parallel = Some(args.value("--record-parallel")?);
+            }
+            "--gruns-output" => {
+                cli.gruns_output = Some(PathBuf::from(args.value("--gruns-output")?));
+            }
+            "--anchors" => {
+                cli.anchors = Some(PathBuf::from(args.value("--anchors")?));
+            }
+            "--log" => {
+                cli.log = Some(args.value("--log")?);
+            }
+            "--rank" => {
+                cli.rank = Some(true);
+            }
+            "--preserve-case" => {
+                cli.preserve_case = Some(true);
+            }
+            "--annotate" => {
+                cli.annotate = Some(PathBuf::from(args.value("--annotate")?));
+            }
+            "--gtf" => {
+                cli.gtf = Some(PathBuf::from(args.value("--gtf")?));
+            }
+            "--feature" => {
+                cli.feature = Some(args.value("--feature")?);
+            }
+            "--motif-summary" => {
+                cli.motif_summary = Some(PathBuf::from(args.value("--motif-summary")?));
+            }
+            "--aggregate-by" => {
+                cli.aggregate_by = Some(PathBuf::from(args.value("--aggregate-by")?));
+            }
+            "--report-html" => {
+                cli.report_html = Some(PathBuf::from(args.value("--report-html")?));
+            }
+            "--chrom-sizes" => {
+                cli.chrom_sizes = Some(PathBuf::from(args.value("--chrom-sizes")?));
+            }
+            "--max-hits" => {
+                let value = args.parse::<usize>("--max-hits", "a positive integer")?;
+                cli.max_hits = Some(value);
+            }
+            "--report-top" => {
+                let value = args.parse::<usize>("--report-top", "a non-negative integer")?;
+                cli.report_top = Some(value);
+            }
+            "--exclude-bed" => {
+                cli.exclude_bed = Some(PathBuf::from(args.value("--exclude-bed")?));
+            }
+            "--exclude-min-overlap" => {
+                let value = args.parse::<f64>("--exclude-min-overlap", "a fraction in (0, 1]")?;
+                cli.exclude_min_overlap = Some(value);
+            }
+            "--help" | "-h" => return Err(help()),
+            other => match cli.negated_switch(other) {
+                Some(switch) => *switch = Some(false),
+                None => return Err(usage(&format!("unknown argument '{other}'"))),
+            },
+        }
+    }
+    // Flags given on the command line win over the file.
+    match config_path {
+        Some(path) => Ok(cli.or(RunConfig::load(&path)?)),
+        None => Ok(cli),
+    }
+}
+
+fn parse_min_score(value: &str) -> Result<Option<i32>, CliError> {
+    if value == "auto" {
+        return Ok(None);
+    }
+    value
+        .parse::<i32>()
+        .map(Some)
+        .map_err(|_| usage("--min-score must be an integer or auto"))
+}
+
+/// `--loop-alphabet`: a non-empty subset of ACGT, case-insensitive, with U
+/// accepted for T.
+fn parse_loop_alphabet(value: &str) -> Result<[bool; 4], CliError> {
+    let mut alphabet = [false; 4];
+    for byte in value.bytes() {
+        let index = match byte.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' | b'U' => 3,
+            _ => return Err(usage("--loop-alphabet must only contain A, C, G and T")),
+        };
+        alphabet[index] = true;
+    }
+    if alphabet == [false; 4] {
+        return Err(usage("--loop-alphabet must name at least one base"));
+    }
+    Ok(alphabet)
+}
+
+fn loop_alphabet_label(alphabet: [bool; 4]) -> String {
+    "ACGT"
+        .chars()
+        .zip(alphabet)
+        .filter_map(|(base, allowed)| allowed.then_some(base))
+        .collect()
+}
+
+fn parse_motif(value: &str) -> Result<Motifs, CliError> {
+    match value {
+        "g4" => Ok(Motifs::G4),
+        "imotif" => Ok(Motifs::IMotif),
+        "both" => Ok(Motifs::Both),
+        _ => Err(usage("--motif must be g4, imotif or both")),
+    }
+}
+
+fn parse_mode(value: &str) -> Result<Option<InputMode>, CliError> {
+    match value {
+        "mmap" => Ok(Some(InputMode::Mmap)),
+        "stream" => Ok(Some(InputMode::Stream)),
+        "auto" => Ok(None),
+        _ => Err(usage("--mode must be mmap, stream or auto")),
+    }
+}
+
+pub(crate) fn mode_name(mode: InputMode) -> &'static str {
+    match mode {
+        InputMode::Mmap => "mmap",
+        InputMode::Stream => "stream",
+    }
+}
+
+fn parse_base(value: &str) -> Result<QuartetBase, CliError> {
+    if value.len() != 1 {
+        return Err(usage("--base must be exactly one character: g or c"));
+    }
+    match value.as_bytes()[0].to_ascii_lowercase() {
+        b'g' => Ok(QuartetBase::G),
+        b'c' => Ok(QuartetBase::C),
+        _ => Err(usage("--base must be either 'g' for G4 or 'c' for i-motif")),
+    }
+}
+
+fn parse_record_parallel(value: &str) -> Result<RecordParallel, CliError> {
+    match value {
+        "auto" => Ok(RecordParallel::Auto),
+        "on" => Ok(RecordParallel::On),
+        "off" => Ok(RecordParallel::Off),
+        _ => Err(usage(
+            "--record-parallel must be one of 'auto', 'on' or 'off'",
+        )),
+    }
+}
+
+fn parse_family_policy(value: &str) -> Result<FamilyPolicy, CliError> {
+    match value {
+        "score" => Ok(FamilyPolicy::Score),
+        "length" => Ok(FamilyPolicy::Length),
+        "tetrads" => Ok(FamilyPolicy::Tetrads),
+        "first" => Ok(FamilyPolicy::First),
+        _ => Err(usage(
+            "--family-policy must be one of 'score', 'length', 'tetrads' or 'first'",
+        )),
+    }
+}
+
+/// Opt-in filtering and columns applied to primary outputs after consolidation.
+#[derive(Default)]
+pub(crate) struct PostProcessOptions {
+    pub(crate) exclude: Option<IntervalIndex>,
+    /// `--max-hits` when `--exclude-bed` is set: applied by
+    /// [`filter_results`](Self::filter_results) after exclusion, so only
+    /// surviving hits count.
+    pub(crate) max_hits: Option<usize>,
+    pub(crate) exclude_min_overlap: Option<f64>,
+    pub(crate) rank: bool,
+    pub(crate) annotations: Option<IntervalIndex>,
+    pub(crate) motif_summary_path: Option<PathBuf>,
+    pub(crate) motif_summary: Mutex<MotifSummary>,
+    /// `--aggregate-by`: hit totals per BED interval, written to
+    /// `aggregate_path`.
+    pub(crate) aggregate: Option<Mutex<IntervalAggregate>>,
+    pub(crate) aggregate_path: Option<PathBuf>,
+    pub(crate) report_html_path: Option<PathBuf>,
+    pub(crate) report: Mutex<HtmlReport>,
+    pub(crate) chrom_sizes_path: Option<PathBuf>,
+    /// `--gtf`: records are transcripts, and hits get their genomic span.
+    pub(crate) transcripts: Option<Transcripts>,
+}
+
+impl PostProcessOptions {
+    /// Drops excluded hits of a scanned record, caps what is left at a
+    /// deferred `--max-hits` and warns if the record was cut short.
+    fn filter_results(&self, results: &mut SearchResults) {
+        self.filter(&results.name, &mut results.hits);
+        if let Some(max_hits) = self.max_hits {
+            results.truncate_hits(max_hits);
+        }
+        warn_truncated(results);
+    }
+
+    fn filter(&self, chrom: &str, hits: &mut Vec<G4>) {
+        if let Some(exclude) = self.exclude.as_ref() {
+            let removed = qgrs::postprocess::exclude_overlapping(
+                hits,
+                chrom,
+                exclude,
+                self.exclude_min_overlap,
+            );
+            if removed > 0 {
+                eprintln!("Excluded {removed} hit(s) overlapping exclude regions on {chrom}");
+            }
+        }
+    }
+
+    fn record(&self, chrom: &str, hits: &[G4]) {
+        if let Some(aggregate) = self.aggregate.as_ref() {
+            aggregate
+                .lock()
+                .expect("aggregate lock poisoned")
+                .add(chrom, hits);
+        }
+        if self.motif_summary_path.is_some() {
+            // Build the chromosome's table unlocked so rayon tasks only contend on the merge.
+            let mut summary = MotifSummary::default();
+            summary.add(chrom, hits);
+            if !summary.is_empty() {
+                self.motif_summary
+                    .lock()
+                    .expect("motif summary lock poisoned")
+                    .merge(summary);
+            }
+        }
+    }
+
+    fn report(&self, results: &SearchResults) {
+        if self.report_html_path.is_none() {
+            return;
+        }
+        let mut report = self.report.lock().expect("html report lock poisoned");
+        let details = RecordDetails::new(results.sequence_len, &results.hits, report.top_hits());
+        report.insert(&results.name, results.occurrence, details);
+    }
+
+    fn write_report(&self, manifest: &Manifest, fsync: bool) -> Result<(), String> {
+        if let Some(path) = self.report_html_path.as_ref() {
+            let html = self
+                .report
+                .lock()
+                .expect("html report lock poisoned")
+                .render(manifest);
+            write_atomically(path, fsync, |tmp| {
+                fs::write(tmp, html).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Per-run outputs of a directory `--file` run go into each file's own
+    /// output directory, gathered afresh.
+    fn start_file(&mut self, dir: &Path) {
+        for path in [
+            &mut self.motif_summary_path,
+            &mut self.aggregate_path,
+            &mut self.report_html_path,
+            &mut self.chrom_sizes_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(name) = path.file_name() {
+                *path = dir.join(name);
+            }
+        }
+        self.motif_summary = Mutex::new(MotifSummary::default());
+        if let Some(aggregate) = self.aggregate.as_mut() {
+            aggregate
+                .get_mut()
+                .expect("aggregate lock poisoned")
+                .clear();
+        }
+        let top_hits = self
+            .report
+            .get_mut()
+            .expect("html report lock poisoned")
+            .top_hits();
+        self.report = Mutex::new(HtmlReport::new(top_hits));
+    }
+
+    fn finish(&self, fsync: bool) -> Result<(), String> {
+        if let Some(path) = self.motif_summary_path.as_ref() {
+            let csv = self
+                .motif_summary
+                .lock()
+                .expect("motif summary lock poisoned")
+                .render_csv();
+            write_atomically(path, fsync, |tmp| {
+                fs::write(tmp, csv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+            })?;
+        }
+        if let (Some(aggregate), Some(path)) =
+            (self.aggregate.as_ref(), self.aggregate_path.as_ref())
+        {
+            let csv = aggregate
+                .lock()
+                .expect("aggregate lock poisoned")
+                .render_csv();
+            write_atomically(path, fsync, |tmp| {
+                fs::write(tmp, csv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn columns(&self, chrom: &str, hits: &[G4]) -> Vec<ExtraColumn> {
+        let mut columns = Vec::new();
+        if self.rank {
+            let ranks = qgrs::postprocess::rank_by_score(hits);
+            columns.push(ExtraColumn::new(
+                "rank",
+                ColumnValues::UInt64(ranks.iter().map(|rank| rank.rank as u64).collect()),
+            ));
+            columns.push(ExtraColumn::new(
+                "percentile",
+                ColumnValues::Float64(ranks.iter().map(|rank| rank.percentile).collect()),
+            ));
+        }
+        if let Some(index) = self.annotations.as_ref() {
+            let (features, distances): (Vec<String>, Vec<Option<i64>>) = hits
+                .iter()
+                .map(|hit| match index.annotate(chrom, hit.start, hit.end) {
+                    Some(annotation) if !annotation.features.is_empty() => {
+                        (annotation.features.join(","), Some(annotation.distance))
+                    }
+                    Some(annotation) => (".".to_string(), Some(annotation.distance)),
+                    None => (".".to_string(), None),
+                })
+                .unzip();
+            columns.push(ExtraColumn::new("feature", ColumnValues::Utf8(features)));
+            columns.push(ExtraColumn::new("distance", ColumnValues::Int64(distances)));
+        }
+        if let Some(transcripts) = self.transcripts.as_ref() {
+            columns.extend(transcripts.columns(chrom, hits));
+        }
+        columns
+    }
+}
+
+/// `--gtf --feature transcript`: the transcripts scanned in place of the
+/// FASTA records, each spliced from its exons and read 5' to 3'. Hits keep
+/// transcript coordinates; [`Transcripts::columns`] maps them back.
+pub(crate) struct Transcripts {
+    list: Vec<Transcript>,
+    by_id: HashMap<String, usize>,
+    coordinates: CoordinateSystem,
+}
+
+impl Transcripts {
+    pub(crate) fn from_path(path: &Path, coordinates: CoordinateSystem) -> Result<Self, String> {
+        let list = qgrs::transcripts::read_gtf_path(path)
+            .map_err(|err| format!("failed to read GTF {path:?}: {err}"))?;
+        if list.is_empty() {
+            return Err(format!("no exon rows with a transcript_id in {path:?}"));
+        }
+        let by_id = list
+            .iter()
+            .enumerate()
+            .map(|(index, transcript)| (transcript.id.clone(), index))
+            .collect();
+        Ok(Self {
+            list,
+            by_id,
+            coordinates,
+        })
+    }
+
+    fn headers(&self, records: &RecordFilter) -> Vec<RecordHeader> {
+        qgrs::transcripts::transcript_headers(&self.list, |id| records.keeps(id))
+    }
+
+    /// The kept transcripts spliced out of `genome`; those it cannot place
+    /// are reported and left out.
+    fn splice(
+        &self,
+        path: &Path,
+        genome: &[ChromSequence],
+        records: &RecordFilter,
+    ) -> Vec<ChromSequence> {
+        let (spliced, unplaced) =
+            qgrs::transcripts::splice_transcripts(genome, &self.list, |id| records.keeps(id));
+        if let Some(first) = unplaced.first() {
+            eprintln!(
+                "Skipped {} transcript(s) whose exons are not all in {} (first: {first})",
+                unplaced.len(),
+                path.display()
+            );
+        }
+        spliced
+    }
+
+    /// `genomic_chrom`, `genomic_start`, `genomic_end`, `genomic_strand` and
+    /// `spans_junction` of each hit on transcript `id`. The span runs from
+    /// the lowest to the highest genomic base of the hit whatever the
+    /// strand, so across a junction it includes the intron.
+    fn columns(&self, id: &str, hits: &[G4]) -> Vec<ExtraColumn> {
+        let Some(transcript) = self.by_id.get(id).map(|&index| &self.list[index]) else {
+            return Vec::new();
+        };
+        let spans: Vec<GenomicSpan> = hits
+            .iter()
+            .map(|hit| {
+                transcript
+                    .project(hit.start, hit.end)
+                    .expect("hits lie within their transcript")
+            })
+            .collect();
+        let strand = qgrs::transcripts::strand_symbol(transcript.strand);
+        vec![
+            ExtraColumn::new(
+                "genomic_chrom",
+                ColumnValues::Utf8(vec![transcript.chrom.clone(); hits.len()]),
+            ),
+            ExtraColumn::new(
+                "genomic_start",
+                ColumnValues::UInt64(
+                    spans
+                        .iter()
+                        .map(|span| self.coordinates.start(span.start) as u64)
+                        .collect(),
+                ),
+            ),
+            ExtraColumn::new(
+                "genomic_end",
+                ColumnValues::UInt64(spans.iter().map(|span| span.end as u64).collect()),
+            ),
+            ExtraColumn::new(
+                "genomic_strand",
+                ColumnValues::Utf8(vec![strand.to_string(); hits.len()]),
+            ),
+            ExtraColumn::new(
+                "spans_junction",
+                ColumnValues::Boolean(spans.iter().map(|span| span.spans_junction).collect()),
+            ),
+        ]
+    }
+}
+
+pub(crate) enum InputSpec {
+    Inline(String),
+    File(PathBuf),
+}
+
+pub(crate) fn process_inline_sequence(
+    sequence: String,
+    output_path: Option<PathBuf>,
+    scan: ScanConfig,
+    options: &FileOptions,
+) -> Result<(), String> {
+    let &FileOptions {
+        format,
+        include_overlap,
+        motif,
+        fsync,
+        ref post,
+        ..
+    } = options;
+    let bytes = sequence.into_bytes();
+    let sequence_len = bytes.len();
+    let sequence = Arc::new(bytes);
+    let (mut results, family_ranges, family_stats, raw_hits) =
+        run_scan_for_export(Arc::clone(&sequence), scan, include_overlap, sequence_len);
+    if matches!(format, OutputFormat::FamilyRanges) {
+        let bed =
+            qgrs::render_family_ranges_bed(INLINE_SEQUENCE_NAME, &family_ranges, &family_stats);
+        return write_or_print(output_path.as_deref(), bed, fsync);
+    }
+    let mut columns = Vec::new();
+    if let Some(companion) = motif.companion_scan(scan) {
+        let (other, ..) = run_scan_for_export(sequence, companion, false, sequence_len);
+        let motif_types;
+        (results, motif_types) = merge_motifs(results, other);
+        columns.push(motif_type_column(&motif_types));
+    }
+    columns.extend(post.columns("", &results));
+    write_primary_output(
+        output_path.as_deref(),
+        format,
+        &results,
+        &columns,
+        scan,
+        sequence_len,
+        fsync,
+    )?;
+
+    if include_overlap {
+        let base = output_path
+            .as_ref()
+            .expect("overlap outputs require an explicit --output path");
+        write_overlap_exports(
+            base,
+            format,
+            raw_hits.as_ref().unwrap(),
+            &family_ranges,
+            scan.coordinates(),
+            fsync,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub(crate) struct FileOptions {
+    /// `--mode`; `None` is `auto`.
+    pub(crate) mode: Option<InputMode>,
+    /// `--populate`: prefault mmap inputs.
+    pub(crate) populate: bool,
+    /// `--record-parallel`; `None` is `auto`.
+    pub(crate) record_parallel: Option<RecordParallel>,
+    pub(crate) format: OutputFormat,
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) include_overlap: bool,
+    pub(crate) records: RecordFilter,
+    pub(crate) resume: bool,
+    /// `--checkpoint`: single stream-mode files only.
+    pub(crate) checkpoint: Option<StreamCheckpoint>,
+    pub(crate) motif: Motifs,
+    pub(crate) pair_strands: bool,
+    /// `--gruns-output`; each file of a directory `--file` gets a
+    /// subdirectory named like its output directory.
+    pub(crate) gruns_dir: Option<PathBuf>,
+    /// `--anchors`: mmap only.
+    pub(crate) anchors: Option<Anchors>,
+    /// `--filename-index` and `--filename-hash`.
+    pub(crate) naming: FilenameScheme,
+    /// `--force`: skip [`check_output_collisions`].
+    pub(crate) force: bool,
+    /// Sync each output to disk before renaming it into place; off with
+    /// `--no-fsync`.
+    pub(crate) fsync: bool,
+    pub(crate) post: PostProcessOptions,
+    pub(crate) parameters: RunParameters,
+}
+
+impl FileOptions {
+    /// The second scan each record gets: the opposite strand for
+    /// `--pair-strands`, the i-motif scan for `--motif both`.
+    fn companion_scan(&self, scan: ScanConfig) -> Option<ScanConfig> {
+        // Work counts and G-runs describe the main scan only.
+        let scan = scan.with_metrics(false).with_gruns(false);
+        if self.pair_strands {
+            return Some(scan.opposite_strand());
+        }
+        self.motif.companion_scan(scan)
+    }
+
+    fn load_options(&self) -> LoadOptions {
+        self.records.load_options().with_populate(self.populate)
+    }
+
+    /// [`qgrs::search_chromosome`], seeded at the record's `--anchors` only
+    /// when they are given.
+    fn search(&self, chrom: &ChromSequence, scan: ScanConfig, capture_raw: bool) -> SearchResults {
+        match self.anchors.as_ref() {
+            Some(anchors) => qgrs::search_chromosome_with_anchors(
+                chrom,
+                scan,
+                capture_raw,
+                anchors.positions(chrom.name()),
+            ),
+            None => qgrs::search_chromosome(chrom, scan, capture_raw),
+        }
+    }
+
+    /// Warns about `--anchors` on records `sequences` does not have.
+    fn warn_unused_anchors(&self, path: &Path, sequences: &[ChromSequence]) {
+        let Some(anchors) = self.anchors.as_ref() else {
+            return;
+        };
+        let unused = anchors.count_outside(sequences);
+        if unused > 0 {
+            eprintln!(
+                "Ignored {unused} anchor(s) on records not scanned in {}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// `--anchors`: 1-based seed positions per record name, read from
+/// `chrom<TAB>pos` lines. Positions are in the `--coords` system, like the
+/// `start` column a scan writes; further columns, blank lines and `#`
+/// comments are skipped.
+#[derive(Debug, Default)]
+pub(crate) struct Anchors(HashMap<String, Vec<usize>>);
+
+impl Anchors {
+    fn from_path(path: &Path, coordinates: CoordinateSystem) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read anchors {path:?}: {err}"))?;
+        Self::parse(&text, coordinates).map_err(|err| format!("invalid anchors {path:?}: {err}"))
+    }
+
+    fn parse(text: &str, coordinates: CoordinateSystem) -> Result<Self, String> {
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let (Some(name), Some(position)) = (fields.next(), fields.next()) else {
+                return Err(format!("line {}: expected chrom<TAB>pos", index + 1));
+            };
+            let position = position
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|position| match coordinates {
+                    CoordinateSystem::OneBasedInclusive => (position > 0).then_some(position),
+                    CoordinateSystem::ZeroBasedHalfOpen => position.checked_add(1),
+                })
+                .ok_or_else(|| {
+                    format!(
+                        "line {}: position must be a {} integer",
+                        index + 1,
+                        match coordinates {
+                            CoordinateSystem::OneBasedInclusive => "positive",
+                            CoordinateSystem::ZeroBasedHalfOpen => "non-negative",
+                        }
+                    )
+                })?;
+            by_name.entry(name.to_string()).or_default().push(position);
+        }
+        Ok(Self(by_name))
+    }
+
+    fn positions(&self, name: &str) -> &[usize] {
+        self.0.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Anchors on names none of `sequences` has.
+    fn count_outside(&self, sequences: &[ChromSequence]) -> usize {
+        let names: HashSet<&str> = sequences.iter().map(ChromSequence::name).collect();
+        self.0
+            .iter()
+            .filter(|(name, _)| !names.contains(name.as_str()))
+            .map(|(_, positions)| positions.len())
+            .sum()
+    }
+}
+
+/// FASTA records a `--file` run scans: empty ones only with `--keep-empty`,
+/// and only names matching `--chrom-regex` when it is given.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RecordFilter {
+    pub(crate) keep_empty: bool,
+    pub(crate) chrom_regex: Option<Regex>,
+}
+
+impl RecordFilter {
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions::new(self.keep_empty)
+    }
+
+    fn keeps(&self, name: &str) -> bool {
+        self.chrom_regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(name))
+    }
+}
+
+pub(crate) fn process_fasta_file(
+    path: &Path,
+    scan: ScanConfig,
+    options: &FileOptions,
+) -> Result<(), String> {
+    let &FileOptions {
+        format,
+        ref output_dir,
+        include_overlap,
+        resume,
+        motif,
+        pair_strands,
+        fsync,
+        ref post,
+        ref parameters,
+        ..
+    } = options;
+    let dir = output_dir
+        .as_ref()
+        .expect("run sets the output directory of every input file");
+    if !options.force {
+        check_output_collisions(path, dir, options)?;
+    }
+    let naming = RecordNaming::for_input(
+        path,
+        options.naming,
+        &options.records,
+        options.post.transcripts.as_ref(),
+    )?;
+    fs::create_dir_all(dir).map_err(|err| format!("failed to create {dir:?}: {err}"))?;
+    let started_at = SystemTime::now();
+    let resumed_entry = |name: &str, occurrence: usize| -> Result<ChromosomeEntry, String> {
+        let filename = naming.output_filename(name, occurrence, format, motif)?;
+        let primary = dir.join(&filename);
+        let records = count_records(&primary, format)?;
+        ChromosomeEntry::new(name, occurrence, dir, &filename, records, true)
+            .map_err(|err| format!("failed to hash {primary:?}: {err}"))
+    };
+    let skip = |name: &str, occurrence: usize| {
+        // A name that cannot be a file name fails when it is written.
+        let done = resume
+            && naming
+                .output_filename(name, occurrence, format, motif)
+                .is_ok_and(|filename| {
+                    outputs_complete(dir, format, include_overlap, pair_strands, &filename)
+                });
+        if done {
+            log_resumed(name, occurrence);
+        }
+        done
+    };
+    let mode = resolve_input_mode(path, options.mode, available_memory);
+    let chromosomes = scan_records(
+        path,
+        mode,
+        scan,
+        options,
+        &naming,
+        skip,
+        |results, companion| write_search_results(dir, &naming, scan, options, results, companion),
+    )?
+    .into_iter()
+    .map(|(name, occurrence, entry)| match entry {
+        Some(entry) => Ok(entry),
+        None => resumed_entry(&name, occurrence),
+    })
+    .collect::<Result<Vec<_>, String>>()?;
+    post.finish(fsync)?;
+    if let Some(sizes) = post.chrom_sizes_path.as_ref() {
+        write_chrom_sizes(sizes, path, &chromosomes, &options.records, fsync)?;
+    }
+
+    let manifest = Manifest {
+        qgrs_version: env!("CARGO_PKG_VERSION"),
+        input: InputInfo::from_path(path)
+            .map_err(|err| format!("failed to stat {path:?}: {err}"))?,
+        parameters: RunParameters {
+            mode: mode_name(mode),
+            ..parameters.clone()
+        },
+        started_at: unix_seconds(started_at),
+        finished_at: unix_seconds(SystemTime::now()),
+        chromosomes,
+    };
+    let json = manifest
+        .to_json()
+        .map_err(|err| format!("failed to serialize manifest: {err}"))?;
+    let manifest_path = dir.join(MANIFEST_FILENAME);
+    write_atomically(&manifest_path, fsync, |tmp| {
+        fs::write(tmp, json).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+    })?;
+    write_filename_map(
+        &dir.join(FILENAMES_TSV),
+        &manifest.chromosomes,
+        options.gruns_dir.is_some().then_some(&naming),
+        fsync,
+    )?;
+    post.write_report(&manifest, fsync)
+}
+
+/// `--format sqlite` counterpart of [`process_fasta_file`]: hits of every
+/// record go through one [`SqliteWriter`] instead of per-record files.
+#[cfg(feature = "sqlite")]
+pub(crate) fn process_fasta_to_sqlite(
+    path: PathBuf,
+    database: PathBuf,
+    scan: ScanConfig,
+    options: FileOptions,
+) -> Result<(), String> {
+    let FileOptions {
+        force,
+        fsync,
+        ref post,
+        ref parameters,
+        ..
+    } = options;
+    if !force && resolve_output_path(&database) == canonical_input(&path)? {
+        return Err(format!(
+            "--output {database:?} would overwrite the input; pass --force to write it anyway"
+        ));
+    }
+    let mode = resolve_input_mode(&path, options.mode, available_memory);
+    let started_at = SystemTime::now();
+    let writer = SqliteWriter::create(&database, scan.coordinates())?;
+    // `--filename-index` and `--filename-hash` are rejected with sqlite, so
+    // only `--gruns-output` files are named.
+    let naming = RecordNaming::default();
+    let scanned = scan_records(
+        &path,
+        mode,
+        scan,
+        &options,
+        &naming,
+        |_, _| false,
+        |mut results, _| {
+            post.filter_results(&mut results);
+            post.record(&results.name, &results.hits);
+            tracing::info!(
+                name = %results.name,
+                occurrence = results.occurrence,
+                description = results.description.as_deref(),
+                sequence_len = results.sequence_len,
+                raw_hits = results.stats.raw_hits,
+                hits = results.hits.len(),
+                "chromosome finished"
+            );
+            writer.send(
+                &results.name,
+                results.occurrence,
+                results.description.as_deref(),
+                &results.hits,
+            )
+        },
+    );
+    if let Err(err) = scanned {
+        return Err(writer.abandon(err));
+    }
+    post.finish(fsync)?;
+    let input =
+        InputInfo::from_path(&path).map_err(|err| format!("failed to stat {path:?}: {err}"))?;
+    let parameters = RunParameters {
+        mode: mode_name(mode),
+        ..parameters.clone()
+    };
+    writer.finish(&input, &parameters, started_at, fsync)
+}
+
+/// `--stdout`: every record's CSV rows, followed by a `chrom` column, go to
+/// `out` under a single header and no files are created. mmap workers finish
+/// out of order, so their rows are queued and written in file order; stream
+/// mode writes each record as it completes. A closed pipe (e.g. `| head`)
+/// ends the run quietly.
+pub(crate) fn process_fasta_to_writer<O: Write>(
+    path: &Path,
+    scan: ScanConfig,
+    options: &FileOptions,
+    out: &mut O,
+) -> Result<(), String> {
+    let FileOptions {
+        ref records,
+        ref post,
+        ..
+    } = *options;
+    let mode = resolve_input_mode(path, options.mode, available_memory);
+    let render = |mut results: SearchResults| -> String {
+        post.filter_results(&mut results);
+        post.record(&results.name, &results.hits);
+        let chrom = if results.occurrence == 0 {
+            results.name.clone()
+        } else {
+            format!("{}_{}", results.name, results.occurrence)
+        };
+        let mut extra = vec![ExtraColumn::new(
+            "chrom",
+            ColumnValues::Utf8(vec![chrom; results.hits.len()]),
+        )];
+        extra.extend(post.columns(&results.name, &results.hits));
+        tracing::info!(
+            name = %results.name,
+            occurrence = results.occurrence,
+            sequence_len = results.sequence_len,
+            hits = results.hits.len(),
+            "chromosome finished"
+        );
+        qgrs::render_csv_results_with_coordinates(&results.hits, &extra, scan.coordinates())
+    };
+    let mut header_written = false;
+    let mut emit = |csv: String| -> io::Result<()> {
+        let rows = if header_written {
+            csv.split_once('\n').map_or("", |(_, rows)| rows)
+        } else {
+            header_written = true;
+            csv.as_str()
+        };
+        out.write_all(rows.as_bytes())
+    };
+    let written = match mode {
+        InputMode::Mmap => {
+            let sequences = qgrs::load_sequences_from_path_with_filter(
+                path,
+                InputMode::Mmap,
+                options.load_options(),
+                |name| records.keeps(name),
+            )
+            .map_err(|err| format!("failed to read {path:?}: {err}"))?;
+            if sequences.is_empty() {
+                return Err(format!("no sequences found in {path:?}"));
+            }
+            warn_duplicate_names(
+                sequences
+                    .iter()
+                    .map(|chrom| (chrom.name(), chrom.occurrence())),
+            );
+            options.warn_unused_anchors(path, &sequences);
+            let scan = RecordParallel::apply(options.record_parallel, scan, &sequences);
+            let (sender, receiver) = mpsc::channel();
+            thread::scope(|scope| {
+                // A failed send means the writer below stopped; workers just quit.
+                scope.spawn(|| {
+                    sequences.par_iter().enumerate().try_for_each_with(
+                        sender,
+                        |sender, (index, chrom)| {
+                            let csv = render(options.search(chrom, scan, false));
+                            sender.send((index, csv))
+                        },
+                    )
+                });
+                let mut pending = BTreeMap::new();
+                let mut next = 0;
+                for (index, csv) in receiver {
+                    pending.insert(index, csv);
+                    while let Some(csv) = pending.remove(&next) {
+                        emit(csv)?;
+                        next += 1;
+                    }
+                }
+                Ok(())
+            })
+        }
+        InputMode::Stream => qgrs::stream::process_fasta_stream_with_skip(
+            path,
+            scan,
+            false,
+            |name, occurrence| {
+                let skipped = !records.keeps(name);
+                if skipped {
+                    tracing::debug!(name, occurrence, "record skipped by name filter");
+                }
+                skipped
+            },
+            |results| {
+                if results.sequence_len == 0 && !records.keep_empty {
+                    return Ok(());
+                }
+                emit(render(results))
+            },
+        )
+        .map(|_| ()),
+    };
+    match written.and_then(|()| out.flush()) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        Err(err) => return Err(format!("failed to write {path:?} results: {err}")),
+        Ok(()) => {}
+    }
+    if !header_written {
+        return Err(format!("no sequences found in {path:?}"));
+    }
+    post.finish(options.fsync)
+}
+
+/// `--record-parallel`: whether mmap runs scan each short record whole on
+/// one Rayon worker, see [`ScanConfig::with_whole_records`], instead of
+/// splitting every record into windows. Records are spread across the pool
+/// either way; the hits do not change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RecordParallel {
+    Auto,
+    On,
+    Off,
+}
+
+/// `auto` scans records whole once a file has this many records of at most
+/// [`WHOLE_RECORD_MAX_LEN`] bases per Rayon thread, enough to keep every
+/// thread busy without windows.
+const RECORD_PARALLEL_RECORDS_PER_THREAD: usize = 4;
+
+impl RecordParallel {
+    /// `scan`, set to scan `sequences` whole when this setting calls for it.
+    fn apply(setting: Option<Self>, scan: ScanConfig, sequences: &[ChromSequence]) -> ScanConfig {
+        let whole = match setting.unwrap_or(Self::Auto) {
+            Self::On => true,
+            Self::Off => false,
+            Self::Auto => {
+                let short = sequences
+                    .iter()
+                    .filter(|chrom| chrom.sequence().len() <= WHOLE_RECORD_MAX_LEN)
+                    .count();
+                short >= RECORD_PARALLEL_RECORDS_PER_THREAD * rayon::current_num_threads()
+            }
+        };
+        if whole {
+            tracing::debug!(records = sequences.len(), "scanning short records whole");
+        }
+        scan.with_whole_records(whole)
+    }
+}
+
+/// Files of a directory `--file` run in name order, each paired with its own
+/// `<output-dir>/<stem>` directory. Without `--glob`, FASTA extensions
+/// (optionally `.gz`/`.zst`-compressed) are picked up.
+pub(crate) fn batch_inputs(
+    dir: &Path,
+    pattern: Option<&str>,
+    output_dir: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("failed to read {dir:?}: {err}"))?;
+    let mut inputs = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("failed to read {dir:?}: {err}"))?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let selected = match pattern {
+            Some(pattern) => glob_matches(pattern.as_bytes(), name.as_bytes()),
+            None => fasta_stem(&name).is_some(),
+        };
+        if selected && !path.is_dir() {
+            let stem = fasta_stem(&name).unwrap_or(&name).to_string();
+            inputs.push((path, output_dir.join(stem)));
+        }
+    }
+    if inputs.is_empty() {
+        return Err(format!("no input files matched in {dir:?}"));
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// `sample.fa.gz` -> `sample`; `None` for names without a FASTA extension.
+fn fasta_stem(name: &str) -> Option<&str> {
+    let name = name
+        .strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(name);
+    let (stem, extension) = name.rsplit_once('.')?;
+    matches!(extension, "fa" | "fasta" | "fna" | "fas").then_some(stem)
+}
+
+/// Shell-style `*` and `?` over a whole file name.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, _) => name.is_empty(),
+        (Some((b'*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name_rest))) => glob_matches(rest, name_rest),
+        (Some((expected, rest)), Some((byte, name_rest))) => {
+            expected == byte && glob_matches(rest, name_rest)
+        }
+        (Some(_), None) => false,
+    }
+}
+
+/// Runs [`process_fasta_file`] over each input in turn, so every file still
+/// gets the whole Rayon pool for its chromosomes. A failed file is reported
+/// and the rest continue unless `fail_fast` is set; any failure fails the run.
+pub(crate) fn process_batch(
+    inputs: Vec<(PathBuf, PathBuf)>,
+    scan: ScanConfig,
+    mut options: FileOptions,
+    fail_fast: bool,
+) -> Result<(), String> {
+    let total = inputs.len();
+    let mut failed = 0usize;
+    let gruns_root = options.gruns_dir.clone();
+    for (path, dir) in inputs {
+        options.post.start_file(&dir);
+        if let (Some(root), Some(stem)) = (gruns_root.as_ref(), dir.file_name()) {
+            options.gruns_dir = Some(root.join(stem));
+        }
+        options.parameters.motif_summary = options.post.motif_summary_path.clone();
+        options.parameters.report_html = options.post.report_html_path.clone();
+        options.parameters.chrom_sizes = options.post.chrom_sizes_path.clone();
+        options.output_dir = Some(dir);
+        if let Err(err) = process_fasta_file(&path, scan, &options) {
+            if fail_fast || interrupt::requested() {
+                return Err(format!("{}: {err}", path.display()));
+            }
+            eprintln!("Error: {}: {err}", path.display());
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {total} input files failed"));
+    }
+    Ok(())
+}
+
+/// One FASTA record in file order; `None` when `skip` left it unscanned.
+type ScannedRecord<T> = (String, usize, Option<T>);
+
+/// Scans every record of `path` in `mode` and hands its results to `write`,
+/// unless `skip` reports it as already done. With `--pair-strands`
+/// or `--motif both`, `write` also gets the record's hits from the second
+/// scan, see [`FileOptions::companion_scan`]. The returned list is in file
+/// order.
+///
+/// mmap runs scan and consolidate records on Rayon workers while the calling
+/// thread renders and writes each finished one with `write`, see
+/// [`writer::scan_while_writing`].
+fn scan_records<T, S, W>(
+    path: &Path,
+    mode: InputMode,
+    scan: ScanConfig,
+    options: &FileOptions,
+    naming: &RecordNaming,
+    skip: S,
+    write: W,
+) -> Result<Vec<ScannedRecord<T>>, String>
+where
+    T: Send,
+    S: Fn(&str, usize) -> bool + Sync,
+    W: Fn(SearchResults, Option<Vec<G4>>) -> Result<T, String> + Sync,
+{
+    let FileOptions {
+        include_overlap,
+        ref records,
+        ref gruns_dir,
+        ..
+    } = *options;
+    let records = match mode {
+        InputMode::Mmap => {
+            let load_options = options.load_options();
+            if scan.collects_metrics() {
+                print_mmap_hints(path, load_options);
+            }
+            let loading = Instant::now();
+            // With `--gtf` the filter picks transcripts, not chromosomes.
+            let transcripts = options.post.transcripts.as_ref();
+            let sequences = qgrs::load_sequences_from_path_with_filter(
+                path,
+                InputMode::Mmap,
+                load_options,
+                |name| transcripts.is_some() || records.keeps(name),
+            )
+            .map_err(|err| format!("failed to read {path:?}: {err}"))?;
+            let sequences = match transcripts {
+                Some(transcripts) => transcripts.splice(path, &sequences, records),
+                None => sequences,
+            };
+            if sequences.is_empty() {
+                return Err(format!("no sequences found in {path:?}"));
+            }
+            tracing::info!(
+                records = sequences.len(),
+                load_seconds = loading.elapsed().as_secs_f64(),
+                "loaded input"
+            );
+            options.warn_unused_anchors(path, &sequences);
+            let scan = RecordParallel::apply(options.record_parallel, scan, &sequences);
+            let companion = options.companion_scan(scan);
+            let mut records = writer::scan_while_writing(
+                &sequences,
+                |chrom| {
+                    // After Ctrl-C, records not yet started are left out.
+                    if interrupt::requested() {
+                        return Ok(None);
+                    }
+                    let (name, occurrence) = (chrom.name(), chrom.occurrence());
+                    if skip(name, occurrence) {
+                        return Ok(Some((name.to_string(), occurrence, None)));
+                    }
+                    let results = options.search(chrom, scan, include_overlap);
+                    print_metrics(&results);
+                    // Before `write`, so `--resume` never skips a record
+                    // whose G-runs are missing.
+                    if let Some(dir) = gruns_dir {
+                        write_gruns(dir, naming, &results, scan.coordinates(), options.fsync)?;
+                    }
+                    let other = companion
+                        .map(|companion| qgrs::search_chromosome(chrom, companion, false).hits);
+                    Ok(Some((name.to_string(), occurrence, Some((results, other)))))
+                },
+                |(name, occurrence, scanned)| {
+                    let written = match scanned {
+                        Some((results, other)) => Some(write(results, other)?),
+                        None => None,
+                    };
+                    Ok((name, occurrence, written))
+                },
+            )?;
+            if interrupt::requested() {
+                let mut done = vec![false; sequences.len()];
+                for (index, _) in &records {
+                    done[*index] = true;
+                }
+                let missing: Vec<String> = sequences
+                    .iter()
+                    .zip(done)
+                    .filter(|(_, done)| !done)
+                    .map(|(chrom, _)| record_name(chrom.name(), chrom.occurrence()))
+                    .collect();
+                return Err(interrupted_summary(path, records.len(), &missing));
+            }
+            records.sort_by_key(|(index, _)| *index);
+            records.into_iter().map(|(_, record)| record).collect()
+        }
+        InputMode::Stream => {
+            let processed: RefCell<Vec<ScannedRecord<T>>> = RefCell::new(Vec::new());
+            let cancel = interrupt::token();
+            let skip_record = |name: &str, occurrence| {
+                if interrupt::requested() {
+                    // Nothing in progress is worth the grace period.
+                    cancel.cancel();
+                    return true;
+                }
+                if !records.keeps(name) {
+                    tracing::debug!(name, occurrence, "record skipped by name filter");
+                    return true;
+                }
+                let done = skip(name, occurrence);
+                if done {
+                    processed
+                        .borrow_mut()
+                        .push((name.to_string(), occurrence, None));
+                }
+                done
+            };
+            let deliver = |results: SearchResults| {
+                if results.sequence_len == 0 && !records.keep_empty {
+                    return Ok(());
+                }
+                let (name, occurrence) = (results.name.clone(), results.occurrence);
+                print_metrics(&results);
+                // A second scan per record is rejected in stream mode.
+                let written = write(results, None).map_err(io::Error::other)?;
+                processed
+                    .borrow_mut()
+                    .push((name, occurrence, Some(written)));
+                if interrupt::requested() {
+                    return Err(QgrsError::Cancelled.into());
+                }
+                Ok(())
+            };
+            match options.checkpoint.as_ref() {
+                // Records finished before the checkpoint keep the outputs the
+                // interrupted run wrote, like `--resume` leaves them.
+                Some(checkpoint) => qgrs::stream::process_fasta_stream_with_checkpoint(
+                    path,
+                    scan,
+                    include_overlap,
+                    checkpoint,
+                    skip_record,
+                    |name, occurrence, sequence_len| {
+                        if sequence_len > 0 || records.keep_empty {
+                            log_resumed(name, occurrence);
+                            processed
+                                .borrow_mut()
+                                .push((name.to_string(), occurrence, None));
+                        }
+                    },
+                    deliver,
+                ),
+                None => qgrs::stream::process_fasta_stream_with_cancellation(
+                    path,
+                    scan,
+                    include_overlap,
+                    skip_record,
+                    &cancel,
+                    deliver,
+                ),
+            }
+            .map_err(|err| {
+                if interrupt::requested() {
+                    let written = processed.borrow().len();
+                    interrupted_summary(
+                        path,
+                        written,
+                        &["the record being read and every later one".to_string()],
+                    )
+                } else {
+                    format!("failed to process {path:?}: {err}")
+                }
+            })?;
+            let processed = processed.into_inner();
+            if processed.is_empty() {
+                return Err(format!("no sequences found in {path:?}"));
+            }
+            processed
+        }
+    };
+    warn_duplicate_names(
+        records
+            .iter()
+            .map(|(name, occurrence, _)| (name.as_str(), *occurrence)),
+    );
+    Ok(records)
+}
+
+/// What a `--file` scan stopped by Ctrl-C reports: how many records are
+/// complete and, up to 10, which were not written.
+fn interrupted_summary(path: &Path, finished: usize, missing: &[String]) -> String {
+    let mut summary = format!(
+        "Interrupted: {finished} records of {} finished",
+        path.display()
+    );
+    if !missing.is_empty() {
+        summary.push_str("; not written: ");
+        summary.push_str(&missing[..missing.len().min(10)].join(", "));
+        if missing.len() > 10 {
+            summary.push_str(&format!(" and {} more", missing.len() - 10));
+        }
+    }
+    summary.push_str(". Rerun with --resume to write the rest.");
+    summary
+}
+
+/// `--gruns-output`: the record's seeding G-runs as a TSV of 1-based,
+/// inclusive `start`/`end` and `length`, in start order.
+fn write_gruns(
+    dir: &Path,
+    naming: &RecordNaming,
+    results: &SearchResults,
+    coordinates: CoordinateSystem,
+    fsync: bool,
+) -> Result<(), String> {
+    let Some(runs) = results.gruns.as_ref() else {
+        return Ok(());
+    };
+    fs::create_dir_all(dir).map_err(|err| format!("failed to create {dir:?}: {err}"))?;
+    let path = dir.join(naming.gruns_filename(&results.name, results.occurrence)?);
+    let mut tsv = String::from("start\tend\tlength\n");
+    for run in runs {
+        let _ = writeln!(
+            tsv,
+            "{}\t{}\t{}",
+            coordinates.start(run.start + 1),
+            run.start + run.len,
+            run.len
+        );
+    }
+    write_atomically(&path, fsync, |tmp| {
+        fs::write(tmp, tsv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+    })
+}
+
+/// `--mode auto` maps a file while it needs at most this fraction of the
+/// available memory, i.e. half of it, and streams anything larger.
+const AUTO_MMAP_MEMORY_DIVISOR: u64 = 2;
+
+/// The mode `path` is read in. An explicit `--mode mmap` is kept, with a
+/// warning on stderr when the file is larger than `available_memory` reports,
+/// as mmap mode holds every record in memory (compressed input even more than
+/// its file size). `auto` (`None`) picks mmap below
+/// [`AUTO_MMAP_MEMORY_DIVISOR`] and stream above it, and stays at mmap when
+/// the memory is unknown, e.g. off Linux. The probe is only called when it
+/// matters. The choice is logged, printed to stderr when it is stream, and
+/// recorded in `manifest.json` and `--dry-run`. Options that only work in
+/// one mode (`--motif both`, `--pair-strands`, `--populate`,
+/// `--record-parallel on`, `--gruns-output`, `--anchors` and `--gtf` need
+/// mmap, `--checkpoint` stream) settle `auto` before this is called.
+pub(crate) fn resolve_input_mode(
+    path: &Path,
+    requested: Option<InputMode>,
+    available_memory: impl FnOnce() -> Option<u64>,
+) -> InputMode {
+    if requested == Some(InputMode::Stream) {
+        return InputMode::Stream;
+    }
+    // An unreadable input is reported by the load that follows.
+    let Ok(size) = fs::metadata(path).map(|metadata| metadata.len()) else {
+        return requested.unwrap_or(InputMode::Mmap);
+    };
+    let available = available_memory();
+    let Some(requested) = requested else {
+        let mode = match available {
+            Some(available) if size > available / AUTO_MMAP_MEMORY_DIVISOR => InputMode::Stream,
+            _ => InputMode::Mmap,
+        };
+        tracing::info!(
+            path = %path.display(),
+            size,
+            available,
+            mode = mode_name(mode),
+            "--mode auto chose the input mode"
+        );
+        if mode == InputMode::Stream {
+            eprintln!(
+                "{}: {} is more than half of the {} of available memory; reading it with --mode stream",
+                path.display(),
+                format_gib(size),
+                format_gib(available.unwrap_or_default())
+            );
+        }
+        return mode;
+    };
+    if let Some(available) = available
+        && size > available
+    {
+        eprintln!(
+            "warning: {}: the file is {} but only {} of memory is available; \
+             --mode mmap loads every record into memory and may be killed. \
+             Use --mode stream (or --mode auto) to scan it with bounded memory.",
+            path.display(),
+            format_gib(size),
+            format_gib(available)
+        );
+    }
+    requested
+}
+
+/// Memory the system can hand out without swapping: `MemAvailable` from
+/// `/proc/meminfo`, or `MemFree` on kernels older than 3.14. `None` where
+/// there is no `/proc/meminfo`, e.g. on macOS and Windows.
+pub(crate) fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_meminfo(&meminfo)
+}
+
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let kib = line.strip_prefix(name)?.strip_prefix(':')?;
+            let kib = kib.trim().strip_suffix("kB")?.trim_end();
+            kib.parse::<u64>().ok()?.checked_mul(1024)
+        })
+    };
+    field("MemAvailable").or_else(|| field("MemFree"))
+}
+
+fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1u64 << 30) as f64)
+}
+
+/// `--verbose`: the paging hints the mmap loader gives for `path`, on stderr.
+fn print_mmap_hints(path: &Path, options: LoadOptions) {
+    // An unreadable input is reported by the load that follows.
+    let Ok(hints) = qgrs::mmap_hints(path, options) else {
+        return;
+    };
+    let hints = if hints.is_empty() {
+        "none".to_string()
+    } else {
+        hints.join(", ")
+    };
+    eprintln!("{}: mmap hints: {hints}", path.display());
+}
+
+/// `--verbose`: the search work counts of one record, how many `;` comment
+/// lines it had when there were any, and its score bounds, on stderr. The
+/// bounds are the best score the limits allow, the lowest and highest score
+/// among the reported hits, and the share of raw hits consolidation dropped.
+fn print_metrics(results: &SearchResults) {
+    let Some(metrics) = results.stats.metrics else {
+        return;
+    };
+    let comments = match results.stats.comment_lines {
+        0 => String::new(),
+        lines => format!(" comments={lines}"),
+    };
+    eprintln!(
+        "{}: seeded={} expanded={} complete={} viable={} deduped={} families={}{comments} {}",
+        record_label(results),
+        metrics.seeded,
+        metrics.expanded,
+        metrics.complete,
+        metrics.viable,
+        metrics.deduped,
+        metrics.families,
+        score_bounds(results)
+    );
+}
+
+/// Printed on every run, not just with `--verbose`, since the outputs are
+/// incomplete.
+fn warn_truncated(results: &SearchResults) {
+    if let Some(warning) = truncation_warning(results) {
+        eprintln!("{warning}");
+    }
+}
+
+/// The warning for a record `--max-hits` cut short, if it was.
+fn truncation_warning(results: &SearchResults) -> Option<String> {
+    if !results.stats.truncated {
+        return None;
+    }
+    // A truncated record keeps exactly `--max-hits` hits.
+    let max_hits = results.hits.len();
+    Some(format!(
+        "Warning: {} has more than {max_hits} hits; only the {max_hits} with the lowest starts \
+         were written (--max-hits)",
+        record_label(results)
+    ))
+}
+
+/// The record's name, with `_<n>` for a repeated name as in its outputs.
+fn record_label(results: &SearchResults) -> String {
+    record_name(&results.name, results.occurrence)
+}
+
+/// `name`, or `name_N` for its Nth repeat.
+fn record_name(name: &str, occurrence: usize) -> String {
+    if occurrence == 0 {
+        name.to_string()
+    } else {
+        format!("{name}_{occurrence}")
+    }
+}
+
+/// `max_possible=64 scores=17..42 discarded=92.5%` for [`print_metrics`];
+/// `-` stands for a bound there is nothing to take from.
+fn score_bounds(results: &SearchResults) -> String {
+    let max_possible = results
+        .config
+        .max_possible_score()
+        .map_or_else(|| "-".to_string(), |score| score.to_string());
+    let scores = results.hits.iter().map(|g4| g4.score);
+    let observed = match (scores.clone().min(), scores.max()) {
+        (Some(min), Some(max)) => format!("{min}..{max}"),
+        _ => "-".to_string(),
+    };
+    let raw = results.stats.raw_hits;
+    let discarded = if raw == 0 {
+        0.0
+    } else {
+        100.0 * raw.saturating_sub(results.hits.len()) as f64 / raw as f64
+    };
+    format!("max_possible={max_possible} scores={observed} discarded={discarded:.1}%")
+}
+
+/// Records a `--file` run would scan: the FASTA headers, or the transcripts
+/// with `--gtf`.
+fn record_headers(
+    path: &Path,
+    records: &RecordFilter,
+    transcripts: Option<&Transcripts>,
+) -> Result<Vec<RecordHeader>, String> {
+    if let Some(transcripts) = transcripts {
+        return Ok(transcripts.headers(records));
+    }
+    qgrs::read_record_headers_with_filter(path, records.load_options(), |name| records.keeps(name))
+        .map_err(|err| format!("failed to read {path:?}: {err}"))
+}
+
+/// Records a `--file` run would write, paired with their primary output
+/// filename, read from the FASTA headers (or the GTF) alone, and the naming
+/// that gave those filenames.
+pub(crate) fn plan_outputs(
+    path: &Path,
+    motif: Motifs,
+    format: OutputFormat,
+    records: &RecordFilter,
+    scheme: FilenameScheme,
+    transcripts: Option<&Transcripts>,
+) -> Result<(RecordNaming, Vec<(RecordHeader, String)>), String> {
+    let headers = record_headers(path, records, transcripts)?;
+    if headers.is_empty() {
+        return Err(format!("no sequences found in {path:?}"));
+    }
+    let naming = RecordNaming::from_headers(scheme, &headers);
+    let plan = headers
+        .into_iter()
+        .map(|header| {
+            let filename =
+                naming.output_filename(&header.name, header.occurrence, format, motif)?;
+            Ok((header, filename))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((naming, plan))
+}
+
+/// Refuses a `--file` run that would overwrite its own input, or in which two
+/// records, or a record and a run-level file, would write the same path. A
+/// record called `chr1_1` meets the second `chr1`, `chr1/a` meets `chr1_a`
+/// once sanitized, and `--resume` would then take one record's output for
+/// the other's. Both sides are compared with symlinks resolved. Skipped with
+/// `--force`.
+fn check_output_collisions(input: &Path, dir: &Path, options: &FileOptions) -> Result<(), String> {
+    let FileOptions {
+        format,
+        include_overlap,
+        motif,
+        pair_strands,
+        ref records,
+        ref gruns_dir,
+        ref post,
+        naming,
+        ..
+    } = *options;
+    let (naming, plan) = plan_outputs(
+        input,
+        motif,
+        format,
+        records,
+        naming,
+        post.transcripts.as_ref(),
+    )?;
+    let input = canonical_input(input)?;
+    let mut claimed: HashMap<PathBuf, String> = HashMap::new();
+    let mut claim = |path: PathBuf, owner: String| -> Result<(), String> {
+        let resolved = resolve_output_path(&path);
+        if resolved == input {
+            return Err(format!(
+                "{owner} would overwrite the input with {path:?}; \
+                 choose another --output-dir or pass --force"
+            ));
+        }
+        if let Some(previous) = claimed.insert(resolved, owner.clone()) {
+            return Err(format!(
+                "{previous} and {owner} would both write {path:?}; \
+                 rename a record or pass --force to let the later one overwrite it"
+            ));
+        }
+        Ok(())
+    };
+    claim(dir.join(MANIFEST_FILENAME), "the run manifest".to_string())?;
+    claim(dir.join(FILENAMES_TSV), "the filename map".to_string())?;
+    if let Some(path) = post.motif_summary_path.as_ref() {
+        claim(path.clone(), "--motif-summary".to_string())?;
+    }
+    if let Some(path) = post.aggregate_path.as_ref() {
+        claim(path.clone(), "--aggregate-by".to_string())?;
+    }
+    if let Some(path) = post.report_html_path.as_ref() {
+        claim(path.clone(), "--report-html".to_string())?;
+    }
+    if let Some(path) = post.chrom_sizes_path.as_ref() {
+        claim(path.clone(), "--chrom-sizes".to_string())?;
+    }
+    for (header, filename) in &plan {
+        let owner = if header.occurrence == 0 {
+            format!("record {}", header.name)
+        } else {
+            format!("record {} (#{})", header.name, header.occurrence + 1)
+        };
+        let primary = dir.join(filename);
+        if include_overlap {
+            claim(overlap_path(&primary, format), owner.clone())?;
+            claim(family_path(&primary, format), owner.clone())?;
+        }
+        if pair_strands {
+            claim(pairs_path(&primary), owner.clone())?;
+        }
+        if let Some(gruns_dir) = gruns_dir {
+            let gruns = gruns_dir.join(naming.gruns_filename(&header.name, header.occurrence)?);
+            claim(gruns, owner.clone())?;
+        }
+        claim(primary, owner)?;
+    }
+    Ok(())
+}
+
+fn canonical_input(path: &Path) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|err| format!("failed to resolve {path:?}: {err}"))
+}
+
+/// `path` with symlinks resolved as far as it exists, so outputs that are not
+/// written yet still compare equal to the canonical input they would replace.
+fn resolve_output_path(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        let probe = if existing.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            existing
+        };
+        if let Ok(mut resolved) = fs::canonicalize(probe) {
+            resolved.extend(missing.iter().rev());
+            return resolved;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            // `..` past a missing directory; compare the path as given.
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+pub(crate) fn render_dry_run<F>(
+    path: &Path,
+    dir: &Path,
+    parameters: &RunParameters,
+    plan: &[(RecordHeader, String)],
+    resumed: F,
+) -> String
+where
+    F: Fn(&str) -> bool,
+{
+    let mut out = format!("Dry run: {} -> {}\n", path.display(), dir.display());
+    let min_score = match parameters.min_score {
+        Some(min_score) => min_score.to_string(),
+        None => {
+            let thresholds: Vec<String> = parameters
+                .min_scores
+                .iter()
+                .map(|(tetrads, score)| format!("{tetrads}={score}"))
+                .collect();
+            format!("auto ({})", thresholds.join(", "))
+        }
+    };
+    out.push_str(&format!(
+        "Mode: {}, format: {}, base: {}, topology: {}, family policy: {}\n",
+        parameters.mode,
+        parameters.format,
+        parameters.base,
+        parameters.topology,
+        parameters.family_policy
+    ));
+    out.push_str(&format!(
+        "Limits: min_tetrads={}, min_score={}, max_g4_length={}, max_run={}, loops={}, loop_alphabet={}\n",
+        parameters.min_tetrads,
+        min_score,
+        parameters.max_g4_length,
+        parameters.max_run,
+        parameters.loop_policy,
+        parameters.loop_alphabet.as_deref().unwrap_or("any")
+    ));
+    out.push_str("index\tname\tlength\toutput\n");
+    for (index, (header, filename)) in plan.iter().enumerate() {
+        let note = if resumed(filename) {
+            "\t(resume: skip)"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{filename}{note}\n",
+            index + 1,
+            header.name,
+            header.sequence_len
+        ));
+    }
+    out
+}
+
+fn count_records(path: &Path, format: OutputFormat) -> Result<usize, String> {
+    match format {
+        OutputFormat::Csv => {
+            let csv = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read {path:?}: {err}"))?;
+            Ok(csv.lines().count().saturating_sub(1))
+        }
+        OutputFormat::QgrsMapper => {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read {path:?}: {err}"))?;
+            let rows = text.lines().filter(|line| !line.starts_with('#')).count();
+            Ok(rows.saturating_sub(1))
+        }
+        OutputFormat::FamilyRanges => {
+            let bed = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read {path:?}: {err}"))?;
+            Ok(bed.lines().filter(|line| !line.starts_with('#')).count())
+        }
+        OutputFormat::Parquet | OutputFormat::ParquetDataset => {
+            let file =
+                fs::File::open(path).map_err(|err| format!("failed to open {path:?}: {err}"))?;
+            let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+                .map_err(|err| format!("failed to read parquet {path:?}: {err}"))?;
+            Ok(builder.metadata().file_metadata().num_rows() as usize)
+        }
+        OutputFormat::Arrow => {
+            let file =
+                fs::File::open(path).map_err(|err| format!("failed to open {path:?}: {err}"))?;
+            let reader = FileReader::try_new(file, None)
+                .map_err(|err| format!("failed to read arrow {path:?}: {err}"))?;
+            reader.into_iter().try_fold(0, |rows, batch| {
+                batch
+                    .map(|batch| rows + batch.num_rows())
+                    .map_err(|err| format!("failed to read arrow {path:?}: {err}"))
+            })
+        }
+        #[cfg(feature = "sqlite")]
+        OutputFormat::Sqlite => unreachable!("sqlite runs are not resumed"),
+    }
+}
+
+fn write_search_results(
+    dir: &Path,
+    naming: &RecordNaming,
+    scan: ScanConfig,
+    options: &FileOptions,
+    mut results: SearchResults,
+    companion: Option<Vec<G4>>,
+) -> Result<ChromosomeEntry, String> {
+    let FileOptions {
+        format,
+        motif,
+        fsync,
+        ref post,
+        ..
+    } = *options;
+    post.filter_results(&mut results);
+    let mut companion = companion;
+    if let Some(hits) = companion.as_mut() {
+        post.filter(&results.name, hits);
+    }
+    let mut columns = Vec::new();
+    if motif == Motifs::Both {
+        let other = companion.take().unwrap_or_default();
+        let motif_types;
+        (results.hits, motif_types) = merge_motifs(mem::take(&mut results.hits), other);
+        columns.push(motif_type_column(&motif_types));
+    }
+    post.record(&results.name, &results.hits);
+    post.report(&results);
+    columns.extend(post.columns(&results.name, &results.hits));
+    let filename = naming.output_filename(&results.name, results.occurrence, format, motif)?;
+    let filepath = dir.join(&filename);
+    if let Some(parent) = filepath.parent() {
+        // Dataset layouts nest each record in its own partition directory.
+        fs::create_dir_all(parent).map_err(|err| format!("failed to create {parent:?}: {err}"))?;
+    }
+    let exporting = Instant::now();
+    write_atomically(&filepath, fsync, |tmp| {
+        if matches!(format, OutputFormat::FamilyRanges) {
+            // Winners and families line up one to one, so the manifest's hit
+            // count is also this file's row count.
+            let bed = qgrs::render_family_ranges_bed(
+                &results.name,
+                &results.family_ranges,
+                &results.family_stats,
+            );
+            return fs::write(tmp, bed).map_err(|err| format!("failed to write {tmp:?}: {err}"));
+        }
+        write_results(
+            tmp,
+            format,
+            &results.hits,
+            &columns,
+            &results.name,
+            scan,
+            results.sequence_len,
+        )
+    })?;
+    if let Some(raw_hits) = results.raw_hits.as_ref() {
+        write_overlap_exports(
+            &filepath,
+            format,
+            raw_hits,
+            &results.family_ranges,
+            scan.coordinates(),
+            fsync,
+        )?;
+    }
+    if let Some(opposite) = companion {
+        let pairs = qgrs::postprocess::pair_strands(&results.hits, &opposite);
+        let path = pairs_path(&filepath);
+        write_atomically(&path, fsync, |tmp| {
+            let csv = qgrs::postprocess::render_strand_pairs_csv_with_coordinates(
+                &pairs,
+                &results.hits,
+                &opposite,
+                scan.coordinates(),
+            );
+            fs::write(tmp, csv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+        })?;
+    }
+    let stats = results.stats;
+    tracing::info!(
+        name = %results.name,
+        occurrence = results.occurrence,
+        description = results.description.as_deref(),
+        sequence_len = results.sequence_len,
+        raw_hits = stats.raw_hits,
+        hits = results.hits.len(),
+        read_seconds = stats.read.as_secs_f64(),
+        scan_seconds = stats.scan.as_secs_f64(),
+        consolidate_seconds = stats.consolidate.as_secs_f64(),
+        export_seconds = exporting.elapsed().as_secs_f64(),
+        "chromosome finished"
+    );
+    ChromosomeEntry::new(
+        &results.name,
+        results.occurrence,
+        dir,
+        &filename,
+        results.hits.len(),
+        false,
+    )
+    .map(|entry| {
+        entry
+            .with_description(results.description)
+            .with_length(results.sequence_len)
+            .with_metrics(stats.metrics)
+            .with_truncated(stats.truncated)
+    })
+    .map_err(|err| format!("failed to hash {filepath:?}: {err}"))
+}
+
+/// `--chrom-sizes`: one `name<TAB>length` line per record of the run in file
+/// order, as UCSC `chrom.sizes` expects, with repeated names suffixed `_1`,
+/// `_2`, ... like their outputs. Records left in place by `--resume` were
+/// never loaded, so their lengths come from a header pass over `input`.
+fn write_chrom_sizes(
+    path: &Path,
+    input: &Path,
+    chromosomes: &[ChromosomeEntry],
+    records: &RecordFilter,
+    fsync: bool,
+) -> Result<(), String> {
+    let mut read = HashMap::new();
+    if chromosomes.iter().any(|entry| entry.length.is_none()) {
+        let headers =
+            qgrs::read_record_headers_with_filter(input, records.load_options(), |name| {
+                records.keeps(name)
+            })
+            .map_err(|err| format!("failed to read {input:?}: {err}"))?;
+        for header in headers {
+            read.insert((header.name, header.occurrence), header.sequence_len);
+        }
+    }
+    let mut sizes = String::new();
+    for entry in chromosomes {
+        let length = entry
+            .length
+            .or_else(|| read.get(&(entry.name.clone(), entry.occurrence)).copied())
+            .ok_or_else(|| format!("record {} is no longer in {input:?}", entry.name))?;
+        sizes.push_str(&entry.name);
+        if entry.occurrence > 0 {
+            sizes.push_str(&format!("_{}", entry.occurrence));
+        }
+        sizes.push_str(&format!("\t{length}\n"));
+    }
+    write_atomically(path, fsync, |tmp| {
+        fs::write(tmp, sizes).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+    })
+}
+
+fn warn_duplicate_names<'a, I>(records: I)
+where
+    I: IntoIterator<Item = (&'a str, usize)>,
+{
+    let duplicated: Vec<&str> = records
+        .into_iter()
+        .filter(|&(_, occurrence)| occurrence == 1)
+        .map(|(name, _)| name)
+        .collect();
+    if !duplicated.is_empty() {
+        eprintln!(
+            "Warning: duplicate sequence names {}; later records are written with _<n> suffixes",
+            duplicated.join(", ")
+        );
+    }
+}
+
+/// `--filename-index` and `--filename-hash`: what the per-record outputs in
+/// `--output-dir` add to the record name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FilenameScheme {
+    /// Prefix the record's zero-padded position in the file.
+    pub(crate) index: bool,
+    /// Append a short hash of the record name.
+    pub(crate) hash: bool,
+}
+
+/// Output filenames of one input's records. `--filename-index` needs each
+/// record's position in the file, so its headers are read up front.
+#[derive(Debug, Default)]
+pub(crate) struct RecordNaming {
+    scheme: FilenameScheme,
+    indices: HashMap<(String, usize), usize>,
+}
+
+impl RecordNaming {
+    fn for_input(
+        path: &Path,
+        scheme: FilenameScheme,
+        records: &RecordFilter,
+        transcripts: Option<&Transcripts>,
+    ) -> Result<Self, String> {
+        if !scheme.index {
+            return Ok(Self::from_headers(scheme, &[]));
+        }
+        let headers = record_headers(path, records, transcripts)?;
+        Ok(Self::from_headers(scheme, &headers))
+    }
+
+    fn from_headers(scheme: FilenameScheme, headers: &[RecordHeader]) -> Self {
+        let indices = headers
+            .iter()
+            .map(|header| ((header.name.clone(), header.occurrence), header.index))
+            .collect();
+        Self { scheme, indices }
+    }
+
+    /// `[{index:06}_]{name}[_{occurrence}][.{hash}]`, with the name
+    /// sanitized. The hash is the first eight hex digits of the name's
+    /// SHA-256, so it stays put when records are added or reordered, unlike
+    /// the index.
+    fn stem(&self, name: &str, occurrence: usize) -> Result<String, String> {
+        Ok(self.decorate(name, occurrence, &sanitize_name(name)?))
+    }
+
+    fn decorate(&self, name: &str, occurrence: usize, base: &str) -> String {
+        let mut stem = String::new();
+        if self.scheme.index
+            && let Some(index) = self.indices.get(&(name.to_string(), occurrence))
+        {
+            stem.push_str(&format!("{index:06}_"));
+        }
+        stem.push_str(base);
+        // 同名染色体按出现次序加后缀:第一条 chr1,第二条 chr1_1 ...
+        if occurrence > 0 {
+            stem.push_str(&format!("_{occurrence}"));
+        }
+        if self.scheme.hash {
+            let digest = format!("{:x}", Sha256::digest(name.as_bytes()));
+            stem.push('.');
+            stem.push_str(&digest[..8]);
+        }
+        stem
+    }
+
+    fn output_filename(
+        &self,
+        name: &str,
+        occurrence: usize,
+        format: OutputFormat,
+        motif: Motifs,
+    ) -> Result<String, String> {
+        if matches!(format, OutputFormat::ParquetDataset) {
+            // Percent-encoding already keeps the partition one directory, and
+            // unlike sanitizing it decodes back to the record name.
+            let stem = self.decorate(name, occurrence, name);
+            return Ok(format!(
+                "chrom={}/{DATASET_PART_FILENAME}",
+                partition_value(&stem)
+            ));
+        }
+        let stem = self.stem(name, occurrence)?;
+        Ok(format!(
+            "{stem}.{}.{}",
+            motif.file_label(),
+            format.extension()
+        ))
+    }
+
+    /// `--gruns-output` file of a record, named like its primary output.
+    fn gruns_filename(&self, name: &str, occurrence: usize) -> Result<String, String> {
+        Ok(format!("{}.gruns.tsv", self.stem(name, occurrence)?))
+    }
+}
+
+/// Maps each record of a `--file` run to its primary output, next to the
+/// manifest.
+const FILENAMES_TSV: &str = "filenames.tsv";
+
+/// Writes [`FILENAMES_TSV`]: one `name`, `occurrence`, `file` row per record,
+/// in file order, so outputs can be traced back to their FASTA headers. With
+/// `--gruns-output`, a `gruns` column names each record's file in that
+/// directory.
+fn write_filename_map(
+    path: &Path,
+    chromosomes: &[ChromosomeEntry],
+    gruns: Option<&RecordNaming>,
+    fsync: bool,
+) -> Result<(), String> {
+    let mut tsv = String::from("name\toccurrence\tfile");
+    tsv.push_str(if gruns.is_some() { "\tgruns\n" } else { "\n" });
+    for entry in chromosomes {
+        tsv.push_str(&format!(
+            "{}\t{}\t{}",
+            entry.name, entry.occurrence, entry.file
+        ));
+        if let Some(naming) = gruns {
+            tsv.push('\t');
+            tsv.push_str(&naming.gruns_filename(&entry.name, entry.occurrence)?);
+        }
+        tsv.push('\n');
+    }
+    write_atomically(path, fsync, |tmp| {
+        fs::write(tmp, tsv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+    })
+}
+
+/// `--aggregate-by` totals of a `--file` run, next to the manifest.
+pub(crate) const AGGREGATE_CSV: &str = "aggregate.csv";
+
+/// The single part file inside each `--format parquet-dataset` partition.
+const DATASET_PART_FILENAME: &str = "part-0.parquet";
+
+/// Hive partition values are percent-encoded like Spark does, so names with
+/// `/`, `=` or spaces stay one directory and still decode to the original.
+fn partition_value(name: &str) -> String {
+    let mut value = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+            value.push(char::from(byte));
+        } else {
+            value.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    value
+}
+
+/// `--motif`: one tetrad base, or G and C scanned and consolidated
+/// separately into one output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Motifs {
+    G4,
+    IMotif,
+    Both,
+}
+
+impl From<QuartetBase> for Motifs {
+    fn from(base: QuartetBase) -> Self {
+        match base {
+            QuartetBase::G => Self::G4,
+            QuartetBase::C => Self::IMotif,
+        }
+    }
+}
+
+impl Motifs {
+    fn cli_name(self) -> &'static str {
+        match self {
+            Self::G4 => "g4",
+            Self::IMotif => "imotif",
+            Self::Both => "both",
+        }
+    }
+
+    /// Label between the record name and the extension of output files.
+    fn file_label(self) -> &'static str {
+        match self {
+            Self::G4 => "g4",
+            Self::IMotif => "i-motif",
+            Self::Both => "g4-i-motif",
+        }
+    }
+
+    /// Tetrad base of the main scan; `Both` adds a C scan on top.
+    fn primary_base(self) -> QuartetBase {
+        match self {
+            Self::G4 | Self::Both => QuartetBase::G,
+            Self::IMotif => QuartetBase::C,
+        }
+    }
+
+    /// The i-motif scan `Both` runs next to `scan`.
+    fn companion_scan(self, scan: ScanConfig) -> Option<ScanConfig> {
+        (self == Self::Both).then(|| scan.with_target_base(QuartetBase::C))
+    }
+}
+
+/// Merges G4 and i-motif hits, each consolidated on its own so overlapping
+/// motifs of different types both survive, in start order (G4 first on
+/// ties). Returns each hit's type alongside.
+fn merge_motifs(g4s: Vec<G4>, imotifs: Vec<G4>) -> (Vec<G4>, Vec<MotifType>) {
+    let mut merged: Vec<(G4, MotifType)> = g4s
+        .into_iter()
+        .map(|hit| (hit, MotifType::G4))
+        .chain(imotifs.into_iter().map(|hit| (hit, MotifType::IMotif)))
+        .collect();
+    // Stable, so each motif keeps its own order among equal coordinates.
+    merged.sort_by_key(|(hit, motif_type)| (hit.start, hit.end, *motif_type != MotifType::G4));
+    merged.into_iter().unzip()
+}
+
+fn motif_type_column(motif_types: &[MotifType]) -> ExtraColumn {
+    ExtraColumn::new(
+        MOTIF_TYPE_COLUMN,
+        ColumnValues::Utf8(
+            motif_types
+                .iter()
+                .map(|motif_type| motif_type.label().to_string())
+                .collect(),
+        ),
+    )
+}
+
+/// File-name form of a record name: anything but ASCII letters, digits and
+/// `.`, `_`, `+`, `-` becomes `_`, so a name can neither leave the output
+/// directory nor open a subdirectory in it. Names that leave nothing but dots
+/// (`..`) are refused.
+fn sanitize_name(raw: &str) -> Result<String, String> {
+    let sanitized: String = raw
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '+' | '-') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.bytes().all(|byte| byte == b'.') {
+        return Err(format!(
+            "record name {raw:?} cannot be used as a file name; rename the record"
+        ));
+    }
+    Ok(sanitized)
+}
+
+/// Sequence name in `--format qgrs-mapper` headers for `--sequence` scans.
+const INLINE_SEQUENCE_NAME: &str = "sequence";
+
+type ConsolidatedResults = (
+    Vec<G4>,
+    Vec<(usize, usize)>,
+    Vec<FamilyStats>,
+    Option<Vec<G4>>,
+);
+
+fn consolidate_for_export(
+    raw: Vec<G4>,
+    capture_raw: bool,
+    scan: ScanConfig,
+    sequence_len: usize,
+) -> ConsolidatedResults {
+    let raw_copy = capture_raw.then(|| raw.clone());
+    let families = qgrs::consolidate_with_families_with_topology(
+        raw,
+        scan.topology(),
+        sequence_len,
+        scan.family_policy(),
+    );
+    let stats = families.iter().map(Family::stats).collect();
+    let ranges = families.iter().map(|family| family.range).collect();
+    let hits = families.into_iter().map(Family::into_best).collect();
+    (hits, ranges, stats, raw_copy)
+}
+
+fn run_scan_for_export(
+    sequence: Arc<Vec<u8>>,
+    scan: ScanConfig,
+    capture_raw: bool,
+    sequence_len: usize,
+) -> ConsolidatedResults {
+    let mut raw = qgrs::find_owned_bytes_with_config(sequence, scan);
+    for hit in &mut raw {
+        hit.set_preserve_case(scan.preserves_case());
+    }
+    consolidate_for_export(raw, capture_raw, scan, sequence_len)
+}
+
+fn write_primary_output(
+    output_path: Option<&Path>,
+    format: OutputFormat,
+    results: &[G4],
+    extra: &[ExtraColumn],
+    scan: ScanConfig,
+    sequence_len: usize,
+    fsync: bool,
+) -> Result<(), String> {
+    let text = match format {
+        OutputFormat::Csv => {
+            qgrs::render_csv_results_with_coordinates(results, extra, scan.coordinates())
+        }
+        OutputFormat::QgrsMapper => {
+            qgrs::render_qgrs_mapper(INLINE_SEQUENCE_NAME, sequence_len, scan, results)
+        }
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            let path = output_path.expect("run requires --output for binary formats");
+            return write_atomically(path, fsync, |tmp| {
+                write_results(
+                    tmp,
+                    format,
+                    results,
+                    extra,
+                    INLINE_SEQUENCE_NAME,
+                    scan,
+                    sequence_len,
+                )
+            });
+        }
+        OutputFormat::ParquetDataset => unreachable!("--format parquet-dataset requires --file"),
+        OutputFormat::FamilyRanges => unreachable!("family ranges are written before hits"),
+        #[cfg(feature = "sqlite")]
+        OutputFormat::Sqlite => unreachable!("--format sqlite requires --file"),
+    };
+    write_or_print(output_path, text, fsync)
+}
+
+/// Text outputs of `--sequence` scans go to `--output`, or stdout without it.
+fn write_or_print(output_path: Option<&Path>, text: String, fsync: bool) -> Result<(), String> {
+    if let Some(path) = output_path {
+        write_atomically(path, fsync, |tmp| {
+            fs::write(tmp, text).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+        })?;
+    } else {
+        print!("{text}");
+    }
+    Ok(())
+}
+
+fn write_results(
+    path: &Path,
+    format: OutputFormat,
+    results: &[G4],
+    extra: &[ExtraColumn],
+    name: &str,
+    scan: ScanConfig,
+    sequence_len: usize,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Csv => {
+            let csv = qgrs::render_csv_results_with_coordinates(results, extra, scan.coordinates());
+            fs::write(path, csv).map_err(|err| format!("failed to write {path:?}: {err}"))
+        }
+        OutputFormat::QgrsMapper => {
+            let text = qgrs::render_qgrs_mapper(name, sequence_len, scan, results);
+            fs::write(path, text).map_err(|err| format!("failed to write {path:?}: {err}"))
+        }
+        OutputFormat::Parquet | OutputFormat::ParquetDataset => {
+            let file = fs::File::create(path)
+                .map_err(|err| format!("failed to create {path:?}: {err}"))?;
+            write_parquet_sink(file, results, extra, scan)
+                .map_err(|err| format!("failed to write parquet {path:?}: {err}"))
+        }
+        OutputFormat::Arrow => {
+            let file = fs::File::create(path)
+                .map_err(|err| format!("failed to create {path:?}: {err}"))?;
+            qgrs::write_arrow_ipc_results_with_coordinates(
+                name,
+                results,
+                extra,
+                scan.coordinates(),
+                io::BufWriter::new(file),
+            )
+            .map_err(|err| format!("failed to write arrow {path:?}: {err}"))
+        }
+        OutputFormat::FamilyRanges => unreachable!("family ranges are not hit rows"),
+        #[cfg(feature = "sqlite")]
+        OutputFormat::Sqlite => unreachable!("sqlite hits go through SqliteWriter"),
+    }
+}
+
+/// Hits through a [`ParquetSink`] in batches of
+/// [`DEFAULT_PARQUET_BATCH_ROWS`], so only one batch of owned records and
+/// Arrow columns is resident next to the hits, however dense the record.
+fn write_parquet_sink(
+    file: fs::File,
+    results: &[G4],
+    extra: &[ExtraColumn],
+    scan: ScanConfig,
+) -> Result<(), qgrs::ExportError> {
+    let mut sink =
+        ParquetSink::try_new(file, extra, &threshold_metadata(scan), scan.coordinates())?;
+    let mut start = 0;
+    for batch in results.chunks(DEFAULT_PARQUET_BATCH_ROWS) {
+        let records: Vec<G4Record> = batch.iter().map(G4::to_record).collect();
+        sink.append_with_columns(&records, extra, start..start + batch.len())?;
+        start += batch.len();
+    }
+    sink.close()
+}
+
+/// Parquet footer entries recording the score thresholds: `qgrs.min_score`
+/// is the number given or `auto`, `qgrs.min_scores` a JSON object of the
+/// effective threshold per tetrad count.
+fn threshold_metadata(scan: ScanConfig) -> Vec<(&'static str, String)> {
+    let min_score = match scan.min_score() {
+        qgrs::MinScore::Fixed(min_score) => min_score.to_string(),
+        qgrs::MinScore::Auto => "auto".to_string(),
+    };
+    let min_scores: BTreeMap<String, i32> = scan
+        .min_scores()
+        .into_iter()
+        .map(|(tetrads, score)| (tetrads.to_string(), score))
+        .collect();
+    let min_scores = serde_json::to_string(&min_scores).unwrap_or_default();
+    vec![
+        ("qgrs.min_score", min_score),
+        ("qgrs.min_scores", min_scores),
+    ]
+}
+
+/// [`qgrs::io::write_atomically`] for writers that report errors as strings.
+fn write_atomically<F>(path: &Path, fsync: bool, write: F) -> Result<(), String>
+where
+    F: FnOnce(&Path) -> Result<(), String>,
+{
+    qgrs::io::write_atomically(path, fsync, |tmp| write(tmp).map_err(io::Error::other))
+        .map_err(|err| err.to_string())
+}
+
+fn write_overlap_exports(
+    base: &Path,
+    format: OutputFormat,
+    raw_hits: &[G4],
+    family_ranges: &[(usize, usize)],
+    coordinates: CoordinateSystem,
+    fsync: bool,
+) -> Result<(), String> {
+    let overlap_path = overlap_path(base, format);
+    let family_path = family_path(base, format);
+    match format {
+        OutputFormat::Csv => {
+            write_atomically(&overlap_path, fsync, |tmp| {
+                let overlap_csv =
+                    qgrs::render_csv_results_with_coordinates(raw_hits, &[], coordinates);
+                fs::write(tmp, overlap_csv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+            })?;
+            write_atomically(&family_path, fsync, |tmp| {
+                let family_csv =
+                    qgrs::render_family_ranges_csv_with_coordinates(family_ranges, coordinates);
+                fs::write(tmp, family_csv).map_err(|err| format!("failed to write {tmp:?}: {err}"))
+            })?;
+        }
+        OutputFormat::Parquet => {
+            write_atomically(&overlap_path, fsync, |tmp| {
+                let overlap_file = fs::File::create(tmp)
+                    .map_err(|err| format!("failed to create {tmp:?}: {err}"))?;
+                qgrs::write_parquet_results_with_coordinates(
+                    raw_hits,
+                    &[],
+                    &[],
+                    coordinates,
+                    overlap_file,
+                )
+                .map_err(|err| format!("failed to write parquet {tmp:?}: {err}"))
+            })?;
+            write_atomically(&family_path, fsync, |tmp| {
+                let family_file = fs::File::create(tmp)
+                    .map_err(|err| format!("failed to create {tmp:?}: {err}"))?;
+                qgrs::write_parquet_family_ranges_with_coordinates(
+                    family_ranges,
+                    coordinates,
+                    family_file,
+                )
+                .map_err(|err| format!("failed to write parquet {tmp:?}: {err}"))
+            })?;
+        }
+        OutputFormat::ParquetDataset
+        | OutputFormat::Arrow
+        | OutputFormat::QgrsMapper
+        | OutputFormat::FamilyRanges => {
+            unreachable!(
+                "--overlap is rejected with --format parquet-dataset, arrow, qgrs-mapper and family-ranges"
+            )
+        }
+        #[cfg(feature = "sqlite")]
+        OutputFormat::Sqlite => unreachable!("--overlap is rejected with --format sqlite"),
+    }
+    Ok(())
+}
+
+pub(crate) fn overlap_path(base: &Path, format: OutputFormat) -> PathBuf {
+    append_output_suffix(base, ".overlap", format)
+}
+
+pub(crate) fn family_path(base: &Path, format: OutputFormat) -> PathBuf {
+    append_output_suffix(base, ".family", format)
+}
+
+/// `--pair-strands` output; always CSV, whatever the primary format.
+pub(crate) fn pairs_path(base: &Path) -> PathBuf {
+    append_output_suffix(base, ".pairs", OutputFormat::Csv)
+}
+
+fn append_output_suffix(path: &Path, suffix: &str, format: OutputFormat) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("chromosome");
+    let name = format!("{stem}{suffix}.{}", format.extension());
+    parent.join(name)
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum OutputFormat {
+    Csv,
+    Parquet,
+    /// Hive-partitioned directory dataset: `chrom=<name>/part-0.parquet`.
+    ParquetDataset,
+    /// Arrow IPC file per chromosome, with a `chrom` column.
+    Arrow,
+    /// Fixed-width text laid out like QGRS Mapper's results table.
+    QgrsMapper,
+    /// BED of consolidated family ranges with member counts and best scores.
+    FamilyRanges,
+    /// One database per `--file` run, written to `--output`.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl TryFrom<String> for OutputFormat {
+    type Error = CliError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "parquet-dataset" => Ok(OutputFormat::ParquetDataset),
+            "arrow" => Ok(OutputFormat::Arrow),
+            "qgrs-mapper" => Ok(OutputFormat::QgrsMapper),
+            "family-ranges" => Ok(OutputFormat::FamilyRanges),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(OutputFormat::Sqlite),
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => Err(usage(
+                "--format sqlite requires qgrs built with `--features sqlite`",
+            )),
+            _ => Err(usage(
+                "--format must be one of 'csv', 'parquet', 'parquet-dataset', 'arrow', 'qgrs-mapper', 'family-ranges' or 'sqlite'",
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Parquet | OutputFormat::ParquetDataset => "parquet",
+            OutputFormat::Arrow => "arrow",
+            OutputFormat::QgrsMapper => "txt",
+            OutputFormat::FamilyRanges => "bed",
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => "sqlite",
+        }
+    }
+
+    pub(crate) fn cli_name(&self) -> &'static str {
+        match self {
+            OutputFormat::QgrsMapper => "qgrs-mapper",
+            OutputFormat::ParquetDataset => "parquet-dataset",
+            OutputFormat::FamilyRanges => "family-ranges",
+            other => other.extension(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    #[test]
+    fn default_limits_are_valid() {
+        let limits = ScanLimits::default();
+        assert_eq!(limits.max_g4_length, DEFAULT_MAX_G4_LENGTH);
+        assert_eq!(limits.max_run, DEFAULT_MAX_RUN);
+        assert!(limits.max_run >= 2);
+        assert!(limits.max_g4_length >= 8);
+    }
+
+    #[test]
+    fn usage_fails_on_invalid_limits() {
+        let err = run_with_args([
+            "--sequence",
+            "GGGG",
+            "--min-tetrads",
+            "4",
+            "--max-g4-length",
+            "12",
+            "--max-run",
+            "4",
+        ]);
+        assert!(err.is_err());
+        let msg = err.unwrap_err().to_string();
+        assert!(msg.contains("max-g4-length"));
+        // The library's ScanLimits::validate supplies the reason.
+        assert!(msg.starts_with(
+            "invalid scan limits: a 4-tetrad motif needs at least 18 bp, but at most 12 bp is allowed"
+        ));
+
+        let err = run_with_args(["--sequence", "GGGG", "--min-tetrads", "3", "--max-run", "2"]);
+        assert!(
+            err.unwrap_err()
+                .starts_with("invalid scan limits: max_run (2) must be ≥ min_tetrads (3)")
+        );
+    }
+
+    #[test]
+    fn unreachable_min_score_fails_unless_allowed() {
+        let err = run_with_args(["--sequence", "GGGGAGGGGAGGGGAGGGG", "--min-score", "101"]);
+        assert!(err.unwrap_err().starts_with(
+            "no hit can reach min_score 101: the best score these limits allow is 100; \
+             pass --allow-empty-config to scan anyway"
+        ));
+
+        let out = unique_test_path("qgrs_allow_empty").with_extension("csv");
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "GGGGAGGGGAGGGGAGGGG".to_string(),
+            "--min-score".to_string(),
+            "101".to_string(),
+            "--allow-empty-config".to_string(),
+            "--output".to_string(),
+            out.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(fs::read_to_string(&out).unwrap().lines().count(), 1);
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn no_g_loops_is_applied_and_recorded() {
+        let fasta = unique_test_path("qgrs_no_g_loops").with_extension("fa");
+        fs::write(&fasta, b">chr1\nGGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        for strict in [false, true] {
+            let out_dir = unique_test_path("qgrs_no_g_loops_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--overlap".to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            if strict {
+                args.push("--no-g-loops".to_string());
+            }
+            assert!(run_with_owned_args(args).is_ok());
+
+            let raw = fs::File::open(out_dir.join("chr1.g4.overlap.csv")).unwrap();
+            let raw = qgrs::parse_csv_records(io::BufReader::new(raw)).unwrap();
+            // Candidates on the leading fifth G all need a loop through G.
+            assert_eq!(raw.iter().any(|record| record.start == 1), !strict);
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let expected = if strict { "no-target-base" } else { "any" };
+            assert_eq!(manifest["parameters"]["loop_policy"], expected);
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn loop_alphabet_is_applied_and_recorded() {
+        let fasta = unique_test_path("qgrs_loop_alphabet").with_extension("fa");
+        // The only hit's second loop is the A.
+        fs::write(&fasta, b">chr1\nGGGTGGGAGGGTGGG\n").unwrap();
+        for alphabet in [None, Some("tc"), Some("ACT")] {
+            let out_dir = unique_test_path("qgrs_loop_alphabet_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            if let Some(alphabet) = alphabet {
+                args.extend(["--loop-alphabet".to_string(), alphabet.to_string()]);
+            }
+            assert!(run_with_owned_args(args).is_ok());
+
+            let hits = fs::File::open(out_dir.join("chr1.g4.csv")).unwrap();
+            let hits = qgrs::parse_csv_records(io::BufReader::new(hits)).unwrap();
+            assert_eq!(hits.len(), usize::from(alphabet != Some("tc")));
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let expected = match alphabet {
+                None => serde_json::Value::Null,
+                Some("tc") => "CT".into(),
+                Some(_) => "ACT".into(),
+            };
+            assert_eq!(manifest["parameters"]["loop_alphabet"], expected);
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+
+        for bad in ["", "CTN"] {
+            let err = run_with_args(["--sequence", "GGGG", "--loop-alphabet", bad]).unwrap_err();
+            assert!(err.starts_with("--loop-alphabet must"), "{err}");
+        }
+    }
+
+    #[test]
+    fn verbose_records_scan_metrics_in_the_manifest() {
+        let fasta = unique_test_path("qgrs_verbose").with_extension("fa");
+        fs::write(&fasta, b">chr1\nGGGTGGGAGGGTGGGAAAGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        for (verbose, mode) in [(false, "mmap"), (true, "mmap"), (true, "stream")] {
+            let out_dir = unique_test_path("qgrs_verbose_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+            ];
+            if verbose {
+                args.push("--verbose".to_string());
+            }
+            assert!(run_with_owned_args(args).is_ok());
+
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let entry = &manifest["chromosomes"][0];
+            let metrics = &entry["metrics"];
+            if verbose {
+                let count = |key: &str| metrics[key].as_u64().unwrap();
+                assert!(count("viable") <= count("complete"), "{mode}: {metrics}");
+                assert!(count("complete") <= count("expanded"), "{mode}: {metrics}");
+                assert!(count("deduped") <= count("viable"), "{mode}: {metrics}");
+                assert_eq!(count("families"), entry["records"].as_u64().unwrap());
+            } else {
+                assert!(metrics.is_null(), "{metrics}");
+            }
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+
+        let err = run_with_args(["--sequence", "GGGG", "--verbose"]).unwrap_err();
+        assert!(
+            err.starts_with("--verbose can only be used with --file"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn populate_prefaults_mmap_input_without_changing_outputs() {
+        let fasta = unique_test_path("qgrs_populate").with_extension("fa");
+        fs::write(&fasta, b">chr1\nGGGTGGGAGGGTGGGAAAGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        let scan = |extra: &[&str]| {
+            let out_dir = unique_test_path("qgrs_populate_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            let result = run_with_owned_args(args)
+                .map(|()| fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap());
+            let _ = fs::remove_dir_all(&out_dir);
+            result
+        };
+        let plain = scan(&[]).unwrap();
+        assert_eq!(scan(&["--populate"]).unwrap(), plain);
+        assert_eq!(scan(&["--populate", "--verbose"]).unwrap(), plain);
+
+        let err = scan(&["--populate", "--mode", "stream"]).unwrap_err();
+        assert!(err.starts_with("--populate needs --mode mmap"), "{err}");
+        let err = run_with_args(["--sequence", "GGGG", "--populate"]).unwrap_err();
+        assert!(
+            err.starts_with("--populate can only be used with --file"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn anchors_from_a_full_run_reproduce_its_outputs() {
+        let fasta = unique_test_path("qgrs_anchors").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGGGTGGGAGGGTGGGAAAGGGGAGGGGAGGGGAGGGGTTTTGGTTGGTTGGTTGG\n>chr2\nAAGGGGTTGGGGTTGGGGTTGGGGAA\n",
+        )
+        .unwrap();
+        let scan = |extra: &[&str]| {
+            let out_dir = unique_test_path("qgrs_anchors_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            let result = run_with_owned_args(args).map(|()| {
+                ["chr1", "chr2"]
+                    .map(|name| fs::read_to_string(out_dir.join(format!("{name}.g4.csv"))).unwrap())
+            });
+            let _ = fs::remove_dir_all(&out_dir);
+            result
+        };
+        let anchors = unique_test_path("qgrs_anchors").with_extension("tsv");
+        for coords in ["1", "0"] {
+            let full = scan(&["--coords", coords]).unwrap();
+            // The `start` column of the full run, plus a record not in the input.
+            let mut tsv = String::from("# chrom\tpos\nchrX\t5\n");
+            for (name, csv) in ["chr1", "chr2"].iter().zip(&full) {
+                for row in csv.lines().skip(1) {
+                    let start = row.split(',').next().unwrap();
+                    let _ = writeln!(tsv, "{name}\t{start}\textra");
+                }
+            }
+            fs::write(&anchors, tsv).unwrap();
+            let anchors = anchors.to_string_lossy().into_owned();
+            assert_eq!(
+                scan(&["--coords", coords, "--anchors", &anchors]).unwrap(),
+                full
+            );
+        }
+
+        let path = anchors.to_string_lossy().into_owned();
+        let err = scan(&["--anchors", &path, "--mode", "stream"]).unwrap_err();
+        assert!(err.starts_with("--anchors needs --mode mmap"), "{err}");
+        let err = scan(&["--anchors", &path, "--motif", "both"]).unwrap_err();
+        assert!(err.starts_with("--anchors cannot be used with"), "{err}");
+        let err = run_with_owned_args(
+            ["--sequence", "GGGG", "--anchors", &path]
+                .map(str::to_string)
+                .to_vec(),
+        )
+        .unwrap_err();
+        assert!(
+            err.starts_with("--anchors can only be used with --file"),
+            "{err}"
+        );
+        fs::write(&anchors, "chr1\t0\n").unwrap();
+        let err = scan(&["--anchors", &path]).unwrap_err();
+        assert!(
+            err.contains("line 1: position must be a positive integer"),
+            "{err}"
+        );
+        assert!(scan(&["--anchors", &path, "--coords", "0"]).is_ok());
+        fs::write(&anchors, "chr1 12\n").unwrap();
+        let err = scan(&["--anchors", &path]).unwrap_err();
+        assert!(err.contains("line 1: expected chrom<TAB>pos"), "{err}");
+        let _ = fs::remove_file(&anchors);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn record_parallel_scans_short_records_whole_without_changing_outputs() {
+        let fasta = unique_test_path("qgrs_record_parallel").with_extension("fa");
+        let mut text = String::new();
+        for index in 0..12 {
+            let _ = write!(
+                text,
+                ">t{index}\n{}GGGTGGGAGGGTGGG{}\n",
+                "A".repeat(index * 7),
+                "C".repeat(index)
+            );
+        }
+        fs::write(&fasta, &text).unwrap();
+        let scan = |extra: &[&str]| {
+            let out_dir = unique_test_path("qgrs_record_parallel_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            let result = run_with_owned_args(args).map(|()| {
+                (0..12)
+                    .map(|index| fs::read_to_string(out_dir.join(format!("t{index}.g4.csv"))))
+                    .collect::<io::Result<Vec<String>>>()
+                    .unwrap()
+            });
+            let _ = fs::remove_dir_all(&out_dir);
+            result
+        };
+        let plain = scan(&[]).unwrap();
+        assert!(plain.iter().all(|csv| csv.lines().count() == 2));
+        for setting in ["on", "off", "auto"] {
+            assert_eq!(scan(&["--record-parallel", setting]).unwrap(), plain);
+        }
+        assert_eq!(
+            scan(&["--record-parallel", "auto", "--mode", "stream"]).unwrap(),
+            plain
+        );
+
+        let sequences = qgrs::load_sequences_from_path(&fasta, InputMode::Mmap).unwrap();
+        let whole = |setting| {
+            RecordParallel::apply(setting, ScanConfig::default(), &sequences).scans_whole_records()
+        };
+        assert!(whole(Some(RecordParallel::On)));
+        assert!(!whole(Some(RecordParallel::Off)));
+        let enough = RECORD_PARALLEL_RECORDS_PER_THREAD * rayon::current_num_threads();
+        assert_eq!(whole(None), sequences.len() >= enough);
+        assert!(
+            !RecordParallel::apply(None, ScanConfig::default(), &sequences[..1])
+                .scans_whole_records()
+        );
+
+        let err = scan(&["--record-parallel", "on", "--mode", "stream"]).unwrap_err();
+        assert!(
+            err.starts_with("--record-parallel on needs --mode mmap"),
+            "{err}"
+        );
+        let err = scan(&["--record-parallel", "always"]).unwrap_err();
+        assert!(err.starts_with("--record-parallel must be one of"), "{err}");
+        let err = run_with_args(["--sequence", "GGGG", "--record-parallel", "on"]).unwrap_err();
+        assert!(
+            err.starts_with("--record-parallel can only be used with --file"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn outputs_that_would_overwrite_the_input_or_each_other_are_refused() {
+        let dir = unique_test_path("qgrs_collision");
+        fs::create_dir_all(&dir).unwrap();
+        // The record's primary output in the input's own directory is the
+        // input file itself.
+        let fasta = dir.join("genome.g4.csv");
+        let genome = b">genome\nGGGTGGGAGGGTGGG\n".to_vec();
+        fs::write(&fasta, &genome).unwrap();
+        let run = |input: &Path, out_dir: &Path, extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                input.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            run_with_owned_args(args)
+        };
+        // `.` reaches the same file through a different spelling.
+        let err = run(&fasta, &dir.join("."), &[]).unwrap_err();
+        assert!(
+            err.contains("record genome would overwrite the input"),
+            "{err}"
+        );
+        assert_eq!(fs::read(&fasta).unwrap(), genome);
+        assert!(!dir.join(MANIFEST_FILENAME).exists());
+
+        // The second `chr1` is written as chr1_1, like the third record.
+        let fasta = dir.join("dups.fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGGGTGGGAGGGTGGG\n>chr1\nGGGAGGGAGGGAGGG\n>chr1_1\nGGGCGGGCGGGCGGG\n",
+        )
+        .unwrap();
+        let out_dir = dir.join("out");
+        let err = run(&fasta, &out_dir, &[]).unwrap_err();
+        assert!(
+            err.starts_with("record chr1 (#2) and record chr1_1 would both write"),
+            "{err}"
+        );
+        assert!(!out_dir.exists());
+        let summary = out_dir.join("chr1.g4.csv").to_string_lossy().into_owned();
+        let err = run(&fasta, &out_dir, &["--motif-summary", &summary]).unwrap_err();
+        assert!(
+            err.starts_with("--motif-summary and record chr1 would both write"),
+            "{err}"
+        );
+
+        assert!(run(&fasta, &out_dir, &["--force"]).is_ok());
+        assert!(out_dir.join("chr1_1.g4.csv").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_file_values_yield_to_command_line_flags() {
+        let fasta = unique_test_path("qgrs_config").with_extension("fa");
+        fs::write(&fasta, b">chr1\nGGGTGGGAGGGTGGGAAAGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        let out_dir = unique_test_path("qgrs_config_out");
+        let config = unique_test_path("qgrs_config").with_extension("toml");
+        fs::write(
+            &config,
+            format!(
+                "file = {:?}\noutput-dir = {:?}\nmin-score = 30\noverlap = true\n",
+                fasta.to_string_lossy(),
+                out_dir.to_string_lossy()
+            ),
+        )
+        .unwrap();
+        let manifest_parameters = || -> serde_json::Value {
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            manifest["parameters"].clone()
+        };
+        let run_config = |path: &Path, extra: &[&str]| {
+            let mut args = vec!["--config".to_string(), path.to_string_lossy().into_owned()];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            run_with_owned_args(args)
+        };
+
+        assert!(run_config(&config, &[]).is_ok());
+        let parameters = manifest_parameters();
+        assert_eq!(parameters["min_score"], 30);
+        assert_eq!(parameters["overlap"], true);
+        assert_eq!(parameters["min_tetrads"], 2);
+
+        assert!(run_config(&config, &["--min-score", "20"]).is_ok());
+        let parameters = manifest_parameters();
+        assert_eq!(parameters["min_score"], 20);
+        assert_eq!(parameters["overlap"], true);
+
+        // Switches set in the file are turned off with `--no-<flag>`.
+        assert!(run_config(&config, &["--no-overlap"]).is_ok());
+        let parameters = manifest_parameters();
+        assert_eq!(parameters["overlap"], false);
+        assert_eq!(parameters["min_score"], 30);
+        let err = run_config(&config, &["--no-min-score"]).unwrap_err();
+        assert!(
+            err.starts_with("unknown argument '--no-min-score'"),
+            "{err}"
+        );
+
+        // Relative paths are taken from the config file's directory.
+        let dir = unique_test_path("qgrs_config_dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(&fasta, dir.join("genome.fa")).unwrap();
+        let relative = dir.join("run.toml");
+        fs::write(&relative, "file = \"genome.fa\"\noutput-dir = \"out\"\n").unwrap();
+        assert!(run_config(&relative, &[]).is_ok());
+        assert!(dir.join("out").join("chr1.g4.csv").exists());
+        let _ = fs::remove_dir_all(&dir);
+
+        // The same keys work as JSON.
+        let json = config.with_extension("json");
+        fs::write(
+            &json,
+            serde_json::json!({
+                "file": fasta,
+                "output-dir": out_dir,
+                "min-score": "auto",
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert!(run_config(&json, &[]).is_ok());
+        assert!(manifest_parameters()["min_score"].is_null());
+
+        fs::write(&config, "min-score = 30\nmin-tetrad = 3\n").unwrap();
+        let err = run_config(&config, &[]).unwrap_err();
+        assert!(err.starts_with("invalid config"), "{err}");
+        assert!(err.contains("unknown field `min-tetrad`"), "{err}");
+
+        fs::write(&config, "min-score = [30\n").unwrap();
+        let err = run_config(&config, &[]).unwrap_err();
+        assert!(err.starts_with("invalid config"), "{err}");
+        fs::write(&config, "min-tetrads = 0\n").unwrap();
+        let err = run_config(&config, &[]).unwrap_err();
+        assert!(err.starts_with("--min-tetrads must be > 0"), "{err}");
+
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&config);
+        let _ = fs::remove_file(&json);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn example_config_file_parses() {
+        let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/run.toml");
+        let config = RunConfig::load(&example).unwrap();
+        assert_eq!(config.min_score, Some(MinScore::Named("auto".to_string())));
+        assert_eq!(
+            config.output_dir,
+            Some(example.parent().unwrap().join("qgrs_out"))
+        );
+    }
+
+    #[test]
+    fn no_fsync_writes_the_same_outputs_and_leaves_no_tmp_files() {
+        let fasta = unique_test_path("qgrs_fsync").with_extension("fa");
+        fs::write(&fasta, b">chr1\nGGGTGGGAGGGTGGGAAAGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        let listing = |dir: &Path| {
+            let mut files = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        let mut outputs = Vec::new();
+        for extra in [None, Some("--no-fsync")] {
+            let out_dir = unique_test_path("qgrs_fsync_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--overlap".to_string(),
+                "--pair-strands".to_string(),
+                "--motif-summary".to_string(),
+                out_dir.join("motifs.csv").to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.map(str::to_string));
+            assert!(run_with_owned_args(args).is_ok());
+            let files = listing(&out_dir);
+            assert!(
+                files.iter().all(|name| !name.ends_with(".tmp")),
+                "{files:?}"
+            );
+            let contents = files
+                .iter()
+                .filter(|name| name.as_str() != MANIFEST_FILENAME)
+                .map(|name| fs::read(out_dir.join(name)).unwrap())
+                .collect::<Vec<_>>();
+            outputs.push((files, contents));
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        assert_eq!(outputs[0], outputs[1]);
+
+        let output = unique_test_path("qgrs_fsync_inline").with_extension("csv");
+        let output_arg = output.to_string_lossy().into_owned();
+        let args = [
+            "--sequence",
+            "GGGGAGGGGAGGGGAGGGG",
+            "--no-fsync",
+            "--output",
+        ];
+        let mut args = args.map(str::to_string).to_vec();
+        args.push(output_arg);
+        assert!(run_with_owned_args(args).is_ok());
+        assert!(
+            fs::read_to_string(&output)
+                .unwrap()
+                .contains("GGGGAGGGGAGGGGAGGGG")
+        );
+        assert!(!qgrs::io::tmp_path(&output).exists());
+        let _ = fs::remove_file(&output);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn gruns_output_writes_one_based_runs_per_record() {
+        let fasta = unique_test_path("qgrs_gruns").with_extension("fa");
+        // The 15-G run is longer than the default --max-run and is not seeded.
+        fs::write(&fasta, b">chr1\nGGAGGGTTGGGGGGGGGGGGGGGA\n>chr1\nAGG\n").unwrap();
+        let out_dir = unique_test_path("qgrs_gruns_out");
+        let gruns_dir = unique_test_path("qgrs_gruns_runs");
+        let args = |mode: &str| {
+            vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--gruns-output".to_string(),
+                gruns_dir.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+            ]
+        };
+        assert!(run_with_owned_args(args("mmap")).is_ok());
+        assert_eq!(
+            fs::read_to_string(gruns_dir.join("chr1.gruns.tsv")).unwrap(),
+            "start\tend\tlength\n1\t2\t2\n4\t6\t3\n"
+        );
+        assert_eq!(
+            fs::read_to_string(gruns_dir.join("chr1_1.gruns.tsv")).unwrap(),
+            "start\tend\tlength\n2\t3\t2\n"
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        assert_eq!(
+            manifest["parameters"]["gruns_output"],
+            serde_json::json!(gruns_dir.to_string_lossy())
+        );
+
+        let err = run_with_owned_args(args("stream")).unwrap_err();
+        assert!(err.starts_with("--gruns-output needs --mode mmap"), "{err}");
+        let err = run_with_args(["--sequence", "GGGG", "--gruns-output", "runs"]).unwrap_err();
+        assert!(
+            err.starts_with("--gruns-output can only be used with --file"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_dir_all(&gruns_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn runs_and_hits_never_span_records_in_either_mode() {
+        let fasta = unique_test_path("qgrs_record_edges").with_extension("fa");
+        // chr1 ends in GGG and chr2 starts with GGG; joined, the two would
+        // make a 6-G run and a G4 ending inside chr2.
+        fs::write(
+            &fasta,
+            b">chr1\nGGGTTGGGTTGGGTTGGGACGGGAGGG\n>chr2\nGGGTGGGACGT\n",
+        )
+        .unwrap();
+        let scan = |mode: &str, gruns_dir: Option<&Path>| {
+            let out_dir = unique_test_path("qgrs_record_edges_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            if let Some(dir) = gruns_dir {
+                args.push("--gruns-output".to_string());
+                args.push(dir.to_string_lossy().into_owned());
+            }
+            let result = run_with_owned_args(args);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            assert_eq!(
+                fs::read_to_string(out_dir.join("chr2.g4.csv")).unwrap(),
+                "start,end,length,tetrads,y1,y2,y3,score,sequence\n",
+                "{mode}"
+            );
+            let chr1 = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+            let _ = fs::remove_dir_all(&out_dir);
+            chr1
+        };
+
+        let gruns_dir = unique_test_path("qgrs_record_edges_runs");
+        let mmap = scan("mmap", Some(&gruns_dir));
+        assert_eq!(mmap, scan("stream", None));
+        for line in mmap.lines().skip(1) {
+            let end: usize = line.split(',').nth(1).unwrap().parse().unwrap();
+            assert!(end <= 27, "hit runs past chr1: {line}");
+        }
+        let chr1_runs = fs::read_to_string(gruns_dir.join("chr1.gruns.tsv")).unwrap();
+        assert!(chr1_runs.ends_with("\n25\t27\t3\n"), "{chr1_runs}");
+        assert_eq!(
+            fs::read_to_string(gruns_dir.join("chr2.gruns.tsv")).unwrap(),
+            "start\tend\tlength\n1\t3\t3\n5\t7\t3\n"
+        );
+        let _ = fs::remove_dir_all(&gruns_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn pair_strands_writes_loci_with_hits_on_both_strands() {
+        let fasta = unique_test_path("qgrs_pair_strands").with_extension("fa");
+        // A G4 interleaved with its reverse complement's C runs, then a lone
+        // plus-strand G4 with nothing on the minus strand.
+        let sequence = format!("GGGCCCGGGCCCGGGCCCGGGCCC{}GGGTGGGTGGGTGGG", "A".repeat(30));
+        fs::write(&fasta, format!(">chr1\n{sequence}\n")).unwrap();
+        let out_dir = unique_test_path("qgrs_pair_strands_out");
+        let args = vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--output-dir".to_string(),
+            out_dir.to_string_lossy().into_owned(),
+            "--pair-strands".to_string(),
+        ];
+        assert!(run_with_owned_args(args.clone()).is_ok());
+
+        let hits = fs::File::open(out_dir.join("chr1.g4.csv")).unwrap();
+        let hits = qgrs::parse_csv_records(io::BufReader::new(hits)).unwrap();
+        assert_eq!(hits.len(), 2);
+        let pairs = fs::read_to_string(out_dir.join("chr1.g4.pairs.csv")).unwrap();
+        let rows: Vec<&str> = pairs.lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            ["1,24,1,21,64,GGGCCCGGGCCCGGGCCCGGG,4,24,64,CCCGGGCCCGGGCCCGGGCCC"]
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        assert_eq!(manifest["parameters"]["pair_strands"], true);
+
+        let mut stream = args;
+        stream.extend(["--mode".to_string(), "stream".to_string()]);
+        let err = run_with_owned_args(stream).unwrap_err();
+        assert!(err.starts_with("--pair-strands needs --mode mmap"), "{err}");
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn motif_both_keeps_overlapping_g4s_and_imotifs_apart() {
+        let fasta = unique_test_path("qgrs_motif_both").with_extension("fa");
+        // A G4 overlapping an i-motif, then a lone i-motif.
+        let sequence = format!("GGGCCCGGGCCCGGGCCCGGGCCC{}CCCTCCCTCCCTCCC", "A".repeat(30));
+        fs::write(&fasta, format!(">chr1\n{sequence}\n")).unwrap();
+        let out_dir = unique_test_path("qgrs_motif_both_out");
+        let args = |extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args
+        };
+        assert!(run_with_owned_args(args(&["--motif", "both"])).is_ok());
+
+        let csv = fs::read_to_string(out_dir.join("chr1.g4-i-motif.csv")).unwrap();
+        assert!(csv.starts_with("start,end,length,tetrads,y1,y2,y3,score,sequence,motif_type\n"));
+        let hits = qgrs::parse_csv_records(csv.as_bytes()).unwrap();
+        let found: Vec<(usize, MotifType)> = hits
+            .iter()
+            .map(|record| (record.start, record.motif_type))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, MotifType::G4),
+                (4, MotifType::IMotif),
+                (55, MotifType::IMotif)
+            ]
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        assert_eq!(manifest["parameters"]["motif"], "both");
+
+        // `--motif imotif` is `--base c` under another name.
+        assert!(run_with_owned_args(args(&["--motif", "imotif"])).is_ok());
+        let imotif = fs::read_to_string(out_dir.join("chr1.i-motif.csv")).unwrap();
+        assert!(run_with_owned_args(args(&["--base", "c"])).is_ok());
+        assert_eq!(
+            fs::read_to_string(out_dir.join("chr1.i-motif.csv")).unwrap(),
+            imotif
+        );
+
+        for (extra, expected) in [
+            (
+                &["--motif", "both", "--base", "g"][..],
+                "--motif cannot be combined with --base",
+            ),
+            (
+                &["--motif", "both", "--mode", "stream"][..],
+                "--motif both needs --mode mmap",
+            ),
+            (
+                &["--motif", "both", "--overlap"][..],
+                "--overlap and --pair-strands cannot",
+            ),
+            (
+                &["--motif", "rna"][..],
+                "--motif must be g4, imotif or both",
+            ),
+        ] {
+            let err = run_with_owned_args(args(extra)).unwrap_err();
+            assert!(err.starts_with(expected), "{err}");
+        }
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn overlap_requires_output_for_inline() {
+        let err = run_with_args(["--sequence", "GGGG", "--overlap"]);
+        assert!(err.is_err());
+        let msg = err.unwrap_err();
+        assert!(msg.contains("--overlap requires --output"));
+    }
+
+    #[test]
+    fn old_max_g_run_is_rejected_with_migration_guidance() {
+        let err = run_with_args(["--sequence", "GGGG", "--max-g-run", "4"]);
+        assert!(err.is_err());
+        let msg = err.unwrap_err();
+        assert!(msg.contains("--max-g-run was replaced by --max-run"));
+    }
+
+    #[test]
+    fn invalid_base_values_are_rejected() {
+        for value in ["a", "t", "gc", ""] {
+            let err = run_with_owned_args(vec![
+                "--sequence".to_string(),
+                "GGGG".to_string(),
+                "--base".to_string(),
+                value.to_string(),
+            ]);
+            assert!(err.is_err(), "base value {value:?} should fail");
+        }
+    }
+
+    #[test]
+    fn family_policy_changes_winner_but_not_family_range() {
+        let mut winners = Vec::new();
+        let mut families = Vec::new();
+        for policy in ["score", "length", "first"] {
+            let output = unique_test_path("qgrs_family_policy").with_extension("csv");
+            let result = run_with_owned_args(vec![
+                "--sequence".to_string(),
+                "GGGGAGGGGAGGGGAGGGG".to_string(),
+                "--family-policy".to_string(),
+                policy.to_string(),
+                "--overlap".to_string(),
+                "--output".to_string(),
+                output.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{policy}: {result:?}");
+            winners.push(fs::read_to_string(&output).expect("main output"));
+            families.push(
+                fs::read_to_string(family_path(&output, OutputFormat::Csv)).expect("family output"),
+            );
+            let _ = fs::remove_file(&output);
+            let _ = fs::remove_file(overlap_path(&output, OutputFormat::Csv));
+            let _ = fs::remove_file(family_path(&output, OutputFormat::Csv));
+        }
+        assert_ne!(winners[0], winners[1]);
+        assert_ne!(winners[0], winners[2]);
+        assert!(families.iter().all(|family| *family == families[0]));
+
+        let err = run_with_args(["--sequence", "GGGG", "--family-policy", "longest"]);
+        assert!(err.unwrap_err().contains("--family-policy must be one of"));
+    }
+
+    #[test]
+    fn rank_flag_appends_rank_and_percentile_columns() {
+        let output = unique_test_path("qgrs_rank").with_extension("csv");
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            format!("GGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG", "T".repeat(40)),
+            "--rank".to_string(),
+            "--output".to_string(),
+            output.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+
+        let csv = fs::read_to_string(&output).expect("ranked output");
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("start,end,length,tetrads,y1,y2,y3,score,sequence,rank,percentile")
+        );
+        let ranks: Vec<(String, String)> = lines
+            .map(|line| {
+                let cols: Vec<&str> = line.split(',').collect();
+                (cols[9].to_string(), cols[10].to_string())
+            })
+            .collect();
+        assert_eq!(
+            ranks,
+            [
+                ("1".to_string(), "100".to_string()),
+                ("2".to_string(), "50".to_string())
+            ]
+        );
+        let _ = fs::remove_file(&output);
+    }
+
+    #[test]
+    fn output_filename_includes_motif_label() {
+        assert_eq!(
+            RecordNaming::default().output_filename("chr1", 0, OutputFormat::Parquet, Motifs::G4),
+            Ok("chr1.g4.parquet".to_string())
+        );
+        assert_eq!(
+            RecordNaming::default().output_filename("chr1", 1, OutputFormat::Parquet, Motifs::G4),
+            Ok("chr1_1.g4.parquet".to_string())
+        );
+        assert_eq!(
+            RecordNaming::default().output_filename("chr2", 0, OutputFormat::Csv, Motifs::IMotif),
+            Ok("chr2.i-motif.csv".to_string())
+        );
+        assert_eq!(
+            RecordNaming::default().output_filename("chr2", 0, OutputFormat::Csv, Motifs::Both),
+            Ok("chr2.g4-i-motif.csv".to_string())
+        );
+    }
+
+    #[test]
+    fn circular_flag_is_supported_for_inline_scan() {
+        let result = run_with_args([
+            "--sequence",
+            "GAGGGGAGGGGAGGGGGGG",
+            "--min-tetrads",
+            "4",
+            "--min-score",
+            "17",
+            "--circular",
+        ]);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn circular_cli_outputs_keep_expanded_coordinates() {
+        let base = unique_test_path("qgrs_circular_cli");
+        let output = base.with_extension("csv");
+        let output_str = output.to_string_lossy().into_owned();
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "GAGGGGAGGGGAGGGGGGG".to_string(),
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--circular".to_string(),
+            "--overlap".to_string(),
+            "--output".to_string(),
+            output_str.clone(),
+        ]);
+        assert!(result.is_ok());
+
+        let csv = fs::read_to_string(&output).expect("main output");
+        assert!(csv.contains("\n17,35,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG\n"));
+
+        let overlap =
+            fs::read_to_string(overlap_path(&output, OutputFormat::Csv)).expect("overlap output");
+        for line in overlap.lines().skip(1) {
+            let mut cols = line.split(',');
+            let start = cols.next().unwrap().parse::<usize>().unwrap();
+            let end = cols.next().unwrap().parse::<usize>().unwrap();
+            assert!(start <= 19);
+            assert!(end >= start);
+        }
+        assert!(overlap.lines().skip(1).any(|line| {
+            let mut cols = line.split(',');
+            let _start = cols.next().unwrap().parse::<usize>().unwrap();
+            let end = cols.next().unwrap().parse::<usize>().unwrap();
+            end > 19
+        }));
+
+        let family =
+            fs::read_to_string(family_path(&output, OutputFormat::Csv)).expect("family output");
+        let family_line = family.lines().nth(1).expect("family row");
+        let mut cols = family_line.split(',');
+        assert_eq!(cols.next(), Some("1"));
+        let start = cols.next().unwrap().parse::<usize>().unwrap();
+        let end = cols.next().unwrap().parse::<usize>().unwrap();
+        assert!(start <= 19);
+        assert!(end > 19);
+        assert!(end >= start);
+
+        let _ = fs::remove_file(&output);
+        let _ = fs::remove_file(overlap_path(&output, OutputFormat::Csv));
+        let _ = fs::remove_file(family_path(&output, OutputFormat::Csv));
+    }
+
+    #[test]
+    fn parquet_overlap_and_family_follow_format() {
+        let base = unique_test_path("qgrs_parquet_sidecars");
+        let output = base.with_extension("parquet");
+        let output_str = output.to_string_lossy().into_owned();
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "GGGGAGGGGAGGGGAGGGG".to_string(),
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--format".to_string(),
+            "parquet".to_string(),
+            "--overlap".to_string(),
+            "--output".to_string(),
+            output_str,
+        ]);
+        assert!(result.is_ok());
+
+        let overlap = overlap_path(&output, OutputFormat::Parquet);
+        let family = family_path(&output, OutputFormat::Parquet);
+        let overlap_meta = fs::metadata(&overlap).expect("overlap parquet output");
+        let family_meta = fs::metadata(&family).expect("family parquet output");
+        assert!(overlap_meta.len() > 0);
+        assert!(family_meta.len() > 0);
+
+        let _ = fs::remove_file(&output);
+        let _ = fs::remove_file(overlap);
+        let _ = fs::remove_file(family);
+    }
+
+    #[test]
+    fn base_c_inline_outputs_i_motif_hits_on_original_sequence() {
+        let base = unique_test_path("qgrs_base_c_inline");
+        let output = base.with_extension("csv");
+        let output_str = output.to_string_lossy().into_owned();
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "AAAAAAACCCCTCCCCTCCCCTCCCCTT".to_string(),
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--base".to_string(),
+            "c".to_string(),
+            "--output".to_string(),
+            output_str,
+        ]);
+        assert!(result.is_ok());
+
+        let csv = fs::read_to_string(&output).expect("base c output");
+        assert!(csv.starts_with("start,end,length,tetrads,y1,y2,y3,score,sequence\n"));
+        assert!(csv.contains("\n8,26,19,4,1,1,1,84,CCCCTCCCCTCCCCTCCCC\n"));
+        assert!(!csv.contains("GGGGAGGGGAGGGGAGGGG"));
+
+        let _ = fs::remove_file(&output);
+    }
+
+    #[test]
+    fn base_c_circular_inline_outputs_expanded_coordinates() {
+        let base = unique_test_path("qgrs_base_c_circular");
+        let output = base.with_extension("csv");
+        let output_str = output.to_string_lossy().into_owned();
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "CACCCCACCCCACCCCCCC".to_string(),
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--base".to_string(),
+            "c".to_string(),
+            "--circular".to_string(),
+            "--output".to_string(),
+            output_str,
+        ]);
+        assert!(result.is_ok());
+
+        let csv = fs::read_to_string(&output).expect("base c circular output");
+        assert!(csv.contains("\n17,35,19,4,1,1,1,84,CCCCACCCCACCCCACCCC\n"));
+
+        let _ = fs::remove_file(&output);
+    }
+
+    #[test]
+    fn base_c_with_overlap_outputs_primary_sidecars() {
+        let base = unique_test_path("qgrs_base_c_overlap");
+        let output = base.with_extension("csv");
+        let output_str = output.to_string_lossy().into_owned();
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "AAAAAAACCCCTCCCCTCCCCTCCCCTT".to_string(),
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--base".to_string(),
+            "C".to_string(),
+            "--overlap".to_string(),
+            "--output".to_string(),
+            output_str,
+        ]);
+        assert!(result.is_ok());
+
+        let overlap_path = overlap_path(&output, OutputFormat::Csv);
+        let family_path = family_path(&output, OutputFormat::Csv);
+        assert!(fs::metadata(&overlap_path).is_ok());
+        assert!(fs::metadata(&family_path).is_ok());
+        let overlap = fs::read_to_string(&overlap_path).expect("overlap output");
+        assert!(overlap.contains("CCCCTCCCCTCCCCTCCCC"));
+        assert!(!overlap.contains("GGGGAGGGGAGGGGAGGGG"));
+
+        let _ = fs::remove_file(&output);
+        let _ = fs::remove_file(overlap_path);
+        let _ = fs::remove_file(family_path);
+    }
+
+    #[test]
+    fn circular_file_outputs_match_between_mmap_and_stream() {
+        let fasta = unique_test_path("qgrs_circular_modes").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGAGGGGAGGGGAGGGGGGG\n>chr2\nGGGCGGGGAGGGGAGGGGAG\n",
+        )
+        .unwrap();
+        let mmap_dir = unique_test_path("qgrs_mmap_out");
+        let stream_dir = unique_test_path("qgrs_stream_out");
+        fs::create_dir_all(&mmap_dir).unwrap();
+        fs::create_dir_all(&stream_dir).unwrap();
+
+        let fasta_str = fasta.to_string_lossy().into_owned();
+        let mmap_dir_str = mmap_dir.to_string_lossy().into_owned();
+        let stream_dir_str = stream_dir.to_string_lossy().into_owned();
+
+        let mmap_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_str.clone(),
+            "--mode".to_string(),
+            "mmap".to_string(),
+            "--output-dir".to_string(),
+            mmap_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--circular".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(mmap_result.is_ok());
+
+        let stream_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_str,
+            "--mode".to_string(),
+            "stream".to_string(),
+            "--output-dir".to_string(),
+            stream_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--circular".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(stream_result.is_ok());
+
+        for filename in [
+            "chr1.g4.csv",
+            "chr1.g4.overlap.csv",
+            "chr1.g4.family.csv",
+            "chr2.g4.csv",
+            "chr2.g4.overlap.csv",
+            "chr2.g4.family.csv",
+        ] {
+            let mmap_contents = fs::read_to_string(mmap_dir.join(filename)).unwrap();
+            let stream_contents = fs::read_to_string(stream_dir.join(filename)).unwrap();
+            assert_eq!(mmap_contents, stream_contents, "mismatch for {filename}");
+        }
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_dir_all(&mmap_dir);
+        let _ = fs::remove_dir_all(&stream_dir);
+    }
+
+    #[test]
+    fn base_c_file_outputs_match_between_mmap_and_stream() {
+        let fasta = unique_test_path("qgrs_base_c_modes").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nAAACCCTCCCCTCCCCTCCCCAAA\n>chr2\nTTTTCCCCCTCCCCTCCCCTCCCCGG\n",
+        )
+        .unwrap();
+        let mmap_dir = unique_test_path("qgrs_base_c_mmap_out");
+        let stream_dir = unique_test_path("qgrs_base_c_stream_out");
+        fs::create_dir_all(&mmap_dir).unwrap();
+        fs::create_dir_all(&stream_dir).unwrap();
+
+        let fasta_str = fasta.to_string_lossy().into_owned();
+        let mmap_dir_str = mmap_dir.to_string_lossy().into_owned();
+        let stream_dir_str = stream_dir.to_string_lossy().into_owned();
+
+        let mmap_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_str.clone(),
+            "--mode".to_string(),
+            "mmap".to_string(),
+            "--output-dir".to_string(),
+            mmap_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--base".to_string(),
+            "c".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(mmap_result.is_ok());
+
+        let stream_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_str,
+            "--mode".to_string(),
+            "stream".to_string(),
+            "--output-dir".to_string(),
+            stream_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--base".to_string(),
+            "c".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(stream_result.is_ok());
+
+        for filename in [
+            "chr1.i-motif.csv",
+            "chr1.i-motif.overlap.csv",
+            "chr1.i-motif.family.csv",
+            "chr2.i-motif.csv",
+            "chr2.i-motif.overlap.csv",
+            "chr2.i-motif.family.csv",
+        ] {
+            let mmap_contents = fs::read_to_string(mmap_dir.join(filename)).unwrap();
+            let stream_contents = fs::read_to_string(stream_dir.join(filename)).unwrap();
+            assert_eq!(mmap_contents, stream_contents, "mismatch for {filename}");
+        }
+        let chr2 = fs::read_to_string(mmap_dir.join("chr2.i-motif.csv")).unwrap();
+        assert!(chr2.contains("CCCCTCCCCTCCCCTCCCC"));
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_dir_all(&mmap_dir);
+        let _ = fs::remove_dir_all(&stream_dir);
+    }
+
+    #[test]
+    fn gzip_file_outputs_match_between_default_mmap_and_stream() {
+        let base = unique_test_path("qgrs_gzip_modes");
+        let fasta = base.with_extension("fa");
+        let fasta_gz = base.with_extension("fna.data");
+        let fasta_bytes = b">chr1\nGAGGGGAGGGGAGGGGGGG\n>chr2\nGGGCGGGGAGGGGAGGGGAG\n";
+        fs::write(&fasta, fasta_bytes).unwrap();
+        write_gzip(&fasta_gz, fasta_bytes);
+
+        let mmap_dir = unique_test_path("qgrs_gzip_mmap_out");
+        let stream_dir = unique_test_path("qgrs_gzip_stream_out");
+        fs::create_dir_all(&mmap_dir).unwrap();
+        fs::create_dir_all(&stream_dir).unwrap();
+
+        let fasta_gz_str = fasta_gz.to_string_lossy().into_owned();
+        let mmap_dir_str = mmap_dir.to_string_lossy().into_owned();
+        let stream_dir_str = stream_dir.to_string_lossy().into_owned();
+
+        let mmap_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_gz_str.clone(),
+            "--output-dir".to_string(),
+            mmap_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(mmap_result.is_ok());
+
+        let stream_result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_gz_str,
+            "--mode".to_string(),
+            "stream".to_string(),
+            "--output-dir".to_string(),
+            stream_dir_str,
+            "--min-tetrads".to_string(),
+            "4".to_string(),
+            "--min-score".to_string(),
+            "17".to_string(),
+            "--overlap".to_string(),
+        ]);
+        assert!(stream_result.is_ok());
+
+        for filename in [
+            "chr1.g4.csv",
+            "chr1.g4.overlap.csv",
+            "chr1.g4.family.csv",
+            "chr2.g4.csv",
+            "chr2.g4.overlap.csv",
+            "chr2.g4.family.csv",
+        ] {
+            let mmap_contents = fs::read_to_string(mmap_dir.join(filename)).unwrap();
+            let stream_contents = fs::read_to_string(stream_dir.join(filename)).unwrap();
+            assert_eq!(mmap_contents, stream_contents, "mismatch for {filename}");
+        }
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&fasta_gz);
+        let _ = fs::remove_dir_all(&mmap_dir);
+        let _ = fs::remove_dir_all(&stream_dir);
+    }
+
+    #[test]
+    fn keep_empty_writes_header_only_outputs_in_both_modes() {
+        let fasta = unique_test_path("qgrs_keep_empty").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chrM\n>chr1\nGGGGAGGGGAGGGGAGGGG\n>chrE\n>chr2\nGGGGTTGGGGTTGGGGTTGGGG\n>chrZ\n",
+        )
+        .unwrap();
+        let fasta_str = fasta.to_string_lossy().into_owned();
+
+        for mode in ["mmap", "stream"] {
+            let default_dir = unique_test_path("qgrs_keep_empty_default");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta_str.clone(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                default_dir.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            assert!(default_dir.join("chr1.g4.csv").exists());
+            for empty in ["chrM", "chrE", "chrZ"] {
+                assert!(!default_dir.join(format!("{empty}.g4.csv")).exists());
+            }
+
+            let keep_dir = unique_test_path("qgrs_keep_empty_kept");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta_str.clone(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                keep_dir.to_string_lossy().into_owned(),
+                "--keep-empty".to_string(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            for empty in ["chrM", "chrE", "chrZ"] {
+                let csv = fs::read_to_string(keep_dir.join(format!("{empty}.g4.csv")))
+                    .unwrap_or_else(|err| panic!("{mode}: missing {empty}: {err}"));
+                assert_eq!(csv, "start,end,length,tetrads,y1,y2,y3,score,sequence\n");
+            }
+            let chr1 = fs::read_to_string(keep_dir.join("chr1.g4.csv")).unwrap();
+            assert!(chr1.contains("GGGGAGGGGAGGGGAGGGG"));
+
+            let _ = fs::remove_dir_all(&default_dir);
+            let _ = fs::remove_dir_all(&keep_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn chrom_regex_keeps_only_matching_records_in_both_modes() {
+        let fasta = unique_test_path("qgrs_chrom_regex").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr1_alt\nGGGGTTGGGGTTGGGGTTGGGG\n\
+              >chrUn\nGGGGCGGGGCGGGGCGGGG\n",
+        )
+        .unwrap();
+        let fasta_str = fasta.to_string_lossy().into_owned();
+
+        for mode in ["mmap", "stream"] {
+            let dir = unique_test_path("qgrs_chrom_regex_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta_str.clone(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                dir.to_string_lossy().into_owned(),
+                "--chrom-regex".to_string(),
+                "^chr[0-9XY]+$".to_string(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let mut written: Vec<String> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            written.sort();
+            assert_eq!(
+                written,
+                ["chr1.g4.csv", FILENAMES_TSV, MANIFEST_FILENAME],
+                "{mode}"
+            );
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let names: Vec<&str> = manifest["chromosomes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["name"].as_str().unwrap())
+                .collect();
+            assert_eq!(names, ["chr1"], "{mode}");
+            assert_eq!(manifest["parameters"]["chrom_regex"], "^chr[0-9XY]+$");
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        let records = RecordFilter {
+            keep_empty: false,
+            chrom_regex: Some(Regex::new("^chr[0-9XY]+$").unwrap()),
+        };
+        let (_, plan) = plan_outputs(
+            &fasta,
+            Motifs::G4,
+            OutputFormat::Csv,
+            &records,
+            FilenameScheme::default(),
+            None,
+        )
+        .unwrap();
+        let planned: Vec<&str> = plan
+            .iter()
+            .map(|(header, _)| header.name.as_str())
+            .collect();
+        assert_eq!(planned, ["chr1"]);
+
+        let err = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta_str,
+            "--chrom-regex".to_string(),
+            "chr(".to_string(),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("invalid --chrom-regex"), "{err}");
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn header_descriptions_reach_the_manifest_but_not_file_names() {
+        let fasta = unique_test_path("qgrs_descriptions").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">NC_000913.3 Escherichia coli str. K-12\nGGGGAGGGGAGGGGAGGGG\n\
+              >plasmid\tcircular\tF\nGGGGTTGGGGTTGGGGTTGGGG\n>chr2\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let dir = unique_test_path("qgrs_descriptions_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                dir.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            for file in ["NC_000913.3.g4.csv", "plasmid.g4.csv", "chr2.g4.csv"] {
+                assert!(dir.join(file).exists(), "{mode}: {file}");
+            }
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let descriptions: Vec<&serde_json::Value> = manifest["chromosomes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| &entry["description"])
+                .collect();
+            assert_eq!(
+                descriptions,
+                [
+                    &serde_json::json!("Escherichia coli str. K-12"),
+                    &serde_json::json!("circular\tF"),
+                    &serde_json::Value::Null,
+                ],
+                "{mode}"
+            );
+            let _ = fs::remove_dir_all(&dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn stdout_writes_every_record_under_one_header_in_file_order() {
+        let fasta = unique_test_path("qgrs_stdout").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr2\nGGGGTTGGGGTTGGGGTTGGGG\n>chr1\nGGGGAGGGGAGGGGAGGGG\n",
+        )
+        .unwrap();
+        for mode in [InputMode::Mmap, InputMode::Stream] {
+            let options = FileOptions {
+                mode: Some(mode),
+                populate: false,
+                record_parallel: None,
+                format: OutputFormat::Csv,
+                output_dir: None,
+                include_overlap: false,
+                records: RecordFilter::default(),
+                resume: false,
+                checkpoint: None,
+                motif: Motifs::G4,
+                pair_strands: false,
+                gruns_dir: None,
+                anchors: None,
+                naming: FilenameScheme::default(),
+                force: false,
+                fsync: true,
+                post: PostProcessOptions::default(),
+                parameters: RunParameters::default(),
+            };
+            let mut out = Vec::new();
+            process_fasta_to_writer(&fasta, ScanConfig::default(), &options, &mut out).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                "start,end,length,tetrads,y1,y2,y3,score,sequence,chrom\n\
+                 1,22,22,4,2,2,2,84,GGGGTTGGGGTTGGGGTTGGGG,chr2\n\
+                 1,19,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG,chr1\n",
+                "{mode:?}"
+            );
+        }
+
+        let fasta_str = fasta.to_string_lossy().into_owned();
+        let conflicts = [
+            (
+                "--output-dir",
+                "out",
+                "--stdout cannot be used with --output",
+            ),
+            (
+                "--output",
+                "out.csv",
+                "--stdout cannot be used with --output",
+            ),
+            ("--format", "parquet", "--stdout only writes CSV"),
+        ];
+        for (flag, value, expected) in conflicts {
+            let err = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta_str.clone(),
+                "--stdout".to_string(),
+                flag.to_string(),
+                value.to_string(),
+            ])
+            .unwrap_err();
+            assert!(err.starts_with(expected), "{flag}: {err}");
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn duplicate_names_map_to_files_by_record_order() {
+        let fasta = unique_test_path("qgrs_duplicate_names").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1 first\nGGGGAGGGGAGGGGAGGGG\n>chr2\nGGGTTGGGTTGGGTTGGG\n>chr1 second\nGGGGTTGGGGTTGGGGTTGGGG\n>chr1 third\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        let fasta_str = fasta.to_string_lossy().into_owned();
+
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_duplicate_names_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta_str.clone(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--min-score".to_string(),
+                "1".to_string(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let expected = [
+                ("chr1.g4.csv", "GGGGAGGGGAGGGGAGGGG"),
+                ("chr2.g4.csv", "GGGTTGGGTTGGGTTGGG"),
+                ("chr1_1.g4.csv", "GGGGTTGGGGTTGGGGTTGGGG"),
+                ("chr1_2.g4.csv", "GGGAAGGGAAGGGAAGGG"),
+            ];
+            for (file, motif) in expected {
+                let csv = fs::read_to_string(out_dir.join(file))
+                    .unwrap_or_else(|err| panic!("{mode}: missing {file}: {err}"));
+                assert!(csv.contains(motif), "{mode}: {file} lacks {motif}: {csv}");
+            }
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn annotate_adds_feature_and_distance_columns() {
+        let fasta = unique_test_path("qgrs_annotate").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\nGGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG\n>chr2\nGGGTTGGGTTGGGTTGGG\n",
+                "T".repeat(40)
+            ),
+        )
+        .unwrap();
+        let bed = unique_test_path("qgrs_annotate").with_extension("bed");
+        fs::write(
+            &bed,
+            "chr1\t0\t5\tpromoterA\nchr1\t5\t10\texonB\nchr1\t77\t90\tgeneC\n",
+        )
+        .unwrap();
+        let out_dir = unique_test_path("qgrs_annotate_out");
+        let result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--annotate".to_string(),
+            bed.to_string_lossy().into_owned(),
+            "--output-dir".to_string(),
+            out_dir.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+
+        let chr1 = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+        let rows: Vec<&str> = chr1.lines().collect();
+        assert_eq!(
+            rows[0],
+            "start,end,length,tetrads,y1,y2,y3,score,sequence,feature,distance"
+        );
+        assert!(rows[1].ends_with(",\"promoterA,exonB\",0"), "{}", rows[1]);
+        // geneC covers 78..=90 and abuts the hit ending at 77.
+        assert!(rows[2].starts_with("60,77,"), "{}", rows[2]);
+        assert!(rows[2].ends_with(",.,1"), "{}", rows[2]);
+
+        let chr2 = fs::read_to_string(out_dir.join("chr2.g4.csv")).unwrap();
+        assert!(chr2.lines().nth(1).unwrap().ends_with(",.,."));
+
+        let err = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "GGGG".to_string(),
+            "--annotate".to_string(),
+            bed.to_string_lossy().into_owned(),
+        ]);
+        assert!(
+            err.unwrap_err()
+                .contains("--annotate can only be used with --file")
+        );
+
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&bed);
+    }
+
+    #[test]
+    fn gtf_transcripts_report_hits_across_splice_junctions() {
+        // Each transcript has two exons holding half of a G4 each, so the
+        // motif only exists once the intron is spliced out. `minus` holds
+        // the C-rich complement and reads as the G4 on its own strand.
+        let fasta = unique_test_path("qgrs_gtf").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\n{a}AAAGGGTGGG{t}TGGGTGGGAA{a}AACCCACCCA{t}CCCACCCTTT{a}\n",
+                a = "A".repeat(10),
+                t = "T".repeat(10)
+            ),
+        )
+        .unwrap();
+        let gtf = unique_test_path("qgrs_gtf").with_extension("gtf");
+        let exon = |start, end, strand, id| {
+            format!("chr1\ttest\texon\t{start}\t{end}\t.\t{strand}\t.\ttranscript_id \"{id}\";\n")
+        };
+        fs::write(
+            &gtf,
+            [
+                exon(11, 20, '+', "plus"),
+                exon(31, 40, '+', "plus"),
+                exon(71, 80, '-', "minus"),
+                exon(51, 60, '-', "minus"),
+            ]
+            .concat(),
+        )
+        .unwrap();
+        let out_dir = unique_test_path("qgrs_gtf_out");
+        let args = |extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--gtf".to_string(),
+                gtf.to_string_lossy().into_owned(),
+                "--feature".to_string(),
+                "transcript".to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args
+        };
+        let result = run_with_owned_args(args(&[]));
+        assert!(result.is_ok(), "{result:?}");
+
+        let header = "start,end,length,tetrads,y1,y2,y3,score,sequence,\
+            genomic_chrom,genomic_start,genomic_end,genomic_strand,spans_junction";
+        let plus = fs::read_to_string(out_dir.join("plus.g4.csv")).unwrap();
+        assert_eq!(
+            plus.lines().collect::<Vec<_>>(),
+            [
+                header,
+                "4,18,15,3,1,1,1,64,GGGTGGGTGGGTGGG,chr1,14,38,+,true"
+            ]
+        );
+        // Transcript bases 4..=18 are genomic 77..=71, then 60..=53.
+        let minus = fs::read_to_string(out_dir.join("minus.g4.csv")).unwrap();
+        assert_eq!(
+            minus.lines().collect::<Vec<_>>(),
+            [
+                header,
+                "4,18,15,3,1,1,1,64,GGGTGGGTGGGTGGG,chr1,53,77,-,true"
+            ]
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        assert_eq!(manifest["chromosomes"][1]["name"], "minus");
+        assert_eq!(manifest["chromosomes"][1]["description"], "chr1:51-80(-)");
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let result = run_with_owned_args(args(&["--coords", "0"]));
+        assert!(result.is_ok(), "{result:?}");
+        let plus = fs::read_to_string(out_dir.join("plus.g4.csv")).unwrap();
+        assert!(plus.ends_with("\n3,18,15,3,1,1,1,64,GGGTGGGTGGGTGGG,chr1,13,38,+,true\n"));
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let err = run_with_owned_args(args(&["--mode", "stream"])).unwrap_err();
+        assert!(err.contains("--gtf needs --mode mmap"), "{err}");
+        let mut exon_feature = args(&[]);
+        exon_feature[5] = "exon".to_string();
+        let err = run_with_owned_args(exon_feature).unwrap_err();
+        assert!(err.contains("--feature must be 'transcript'"), "{err}");
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&gtf);
+    }
+
+    #[test]
+    fn exclude_bed_drops_overlapping_hits_in_both_modes() {
+        let fasta = unique_test_path("qgrs_exclude").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\nGGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG\n",
+                "T".repeat(40)
+            ),
+        )
+        .unwrap();
+        // Covers 1..=19 exactly, i.e. the whole first hit and nothing of the second.
+        let bed = unique_test_path("qgrs_exclude").with_extension("bed");
+        fs::write(&bed, "chr1\t0\t19\tblacklisted\n").unwrap();
+
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_exclude_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--exclude-bed".to_string(),
+                bed.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let csv = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+            let starts: Vec<&str> = csv
+                .lines()
+                .skip(1)
+                .map(|line| line.split(',').next().unwrap())
+                .collect();
+            assert_eq!(starts, ["60"], "{mode}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let err = run_with_args(["--sequence", "GGGG", "--exclude-min-overlap", "1.5"]);
+        assert!(err.unwrap_err().contains("--exclude-min-overlap must be"));
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&bed);
+    }
+
+    #[test]
+    fn chrom_sizes_and_manifest_lengths_exclude_line_breaks() {
+        let fasta = unique_test_path("qgrs_chrom_sizes").with_extension("fa");
+        // 19 + 22 bases wrapped over several lines, with CRLF endings on chr2.
+        fs::write(
+            &fasta,
+            b">chr1\nGGGGAGGGG\nAGGGGAGGGG\n>chr2 x\r\nGGGGTTGGGG\r\nTTGGGGTTGGGG\r\n>chr1\nACGT\n",
+        )
+        .unwrap();
+        let expected = "chr1\t19\nchr2\t22\nchr1_1\t4\n";
+        let scan = |mode: &str, out_dir: &Path, sizes: &Path, extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--chrom-sizes".to_string(),
+                sizes.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            run_with_owned_args(args)
+        };
+        let lengths = |out_dir: &Path| -> Vec<serde_json::Value> {
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            manifest["chromosomes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["length"].clone())
+                .collect()
+        };
+
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_chrom_sizes_out");
+            let sizes = out_dir.join("genome.chrom.sizes");
+            let result = scan(mode, &out_dir, &sizes, &[]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            assert_eq!(fs::read_to_string(&sizes).unwrap(), expected, "{mode}");
+            assert_eq!(
+                lengths(&out_dir),
+                [
+                    serde_json::json!(19),
+                    serde_json::json!(22),
+                    serde_json::json!(4)
+                ],
+                "{mode}"
+            );
+
+            // Resumed records were never loaded; their sizes are read back.
+            fs::remove_file(out_dir.join("chr2.g4.csv")).unwrap();
+            let result = scan(mode, &out_dir, &sizes, &["--resume"]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            assert_eq!(fs::read_to_string(&sizes).unwrap(), expected, "{mode}");
+            assert_eq!(
+                lengths(&out_dir),
+                [
+                    serde_json::Value::Null,
+                    serde_json::json!(22),
+                    serde_json::Value::Null
+                ],
+                "{mode}"
+            );
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let err = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--stdout".to_string(),
+            "--chrom-sizes".to_string(),
+            "sizes".to_string(),
+        ])
+        .unwrap_err();
+        assert!(
+            err.starts_with("--chrom-sizes cannot be used with --stdout"),
+            "{err}"
+        );
+        let err = run_with_args(["--sequence", "GGGG", "--chrom-sizes", "sizes"]).unwrap_err();
+        assert!(
+            err.starts_with("--chrom-sizes can only be used with --file"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn family_ranges_format_writes_one_row_per_family_in_every_mode() {
+        // A family of three overlapping hits, then a lone hit 40 bases later.
+        let sequence = format!("GGGTGGGTGGGTGGGGG{}GGGAGGGAGGGAGGG", "A".repeat(40));
+        let rows = "#chrom\tstart\tend\tmembers\tbest_gscore\n\
+                    {chrom}\t0\t17\t3\t64\n\
+                    {chrom}\t57\t72\t1\t64\n";
+
+        let output = unique_test_path("qgrs_family_ranges").with_extension("bed");
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            sequence.clone(),
+            "--min-tetrads".to_string(),
+            "3".to_string(),
+            "--format".to_string(),
+            "family-ranges".to_string(),
+            "--output".to_string(),
+            output.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            rows.replace("{chrom}", "sequence")
+        );
+        let _ = fs::remove_file(&output);
+
+        let fasta = unique_test_path("qgrs_family_ranges").with_extension("fa");
+        fs::write(&fasta, format!(">chr1\n{sequence}\n")).unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_family_ranges_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--min-tetrads".to_string(),
+                "3".to_string(),
+                "--format".to_string(),
+                "family-ranges".to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            assert_eq!(
+                fs::read_to_string(out_dir.join("chr1.g4.bed")).unwrap(),
+                rows.replace("{chrom}", "chr1"),
+                "{mode}"
+            );
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let err = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--format".to_string(),
+            "family-ranges".to_string(),
+            "--overlap".to_string(),
+        ])
+        .unwrap_err();
+        assert!(
+            err.starts_with("--overlap, --pair-strands and --motif both cannot be used"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn motif_summary_counts_a_motif_shared_by_two_chromosomes() {
+        let fasta = unique_test_path("qgrs_motif_summary").with_extension("fa");
+        fs::write(
+            &fasta,
+            ">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nttttggggaggggaggggaggggtttt\n>chr3\nGGGTTGGGTTGGGTTGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_motif_summary_out");
+            let summary = unique_test_path("qgrs_motif_summary").with_extension("csv");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--motif-summary".to_string(),
+                summary.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let csv = fs::read_to_string(&summary).unwrap();
+            let lines: Vec<&str> = csv.lines().collect();
+            assert_eq!(lines[0], "sequence,count,mean_gscore,chromosomes");
+            assert!(
+                lines[1].starts_with("GGGGAGGGGAGGGGAGGGG,2,") && lines[1].ends_with(",chr1;chr2"),
+                "{mode}: {csv}"
+            );
+            assert!(
+                lines[2].starts_with("GGGTTGGGTTGGGTTGGG,1,"),
+                "{mode}: {csv}"
+            );
+            let _ = fs::remove_dir_all(&out_dir);
+            let _ = fs::remove_file(&summary);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn preserve_case_keeps_soft_masked_bases_only_under_the_flag() {
+        let fasta = unique_test_path("qgrs_preserve_case").with_extension("fa");
+        let motif = "GGGGaGGGGaggggAGGGG";
+        fs::write(&fasta, format!(">chr1\nttt{motif}ttt\n")).unwrap();
+        let sequence_column = |csv: &str| -> String {
+            let row = csv.lines().nth(1).unwrap();
+            row.split(',').nth(8).unwrap().to_string()
+        };
+        for mode in ["mmap", "stream"] {
+            for (preserve, expected) in [
+                (false, motif.to_ascii_uppercase()),
+                (true, motif.to_string()),
+            ] {
+                let out_dir = unique_test_path("qgrs_preserve_case_out");
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--output-dir".to_string(),
+                    out_dir.to_string_lossy().into_owned(),
+                ];
+                if preserve {
+                    args.push("--preserve-case".to_string());
+                }
+                let result = run_with_owned_args(args);
+                assert!(result.is_ok(), "{mode}: {result:?}");
+                let csv = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+                assert_eq!(sequence_column(&csv), expected, "{mode} {preserve}");
+                let _ = fs::remove_dir_all(&out_dir);
+            }
+        }
+        let _ = fs::remove_file(&fasta);
+
+        let output = unique_test_path("qgrs_preserve_case").with_extension("csv");
+        let result = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            format!("ttt{motif}ttt"),
+            "--output".to_string(),
+            output.to_string_lossy().into_owned(),
+            "--preserve-case".to_string(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+        let csv = fs::read_to_string(&output).unwrap();
+        assert_eq!(sequence_column(&csv), motif);
+        let _ = fs::remove_file(&output);
+
+        let err = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            motif.to_string(),
+            "--format".to_string(),
+            "qgrs-mapper".to_string(),
+            "--preserve-case".to_string(),
+        ])
+        .unwrap_err();
+        assert!(
+            err.starts_with("--preserve-case cannot be used with --format qgrs-mapper"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn aggregate_by_counts_hits_per_gene_including_nested_and_empty_genes() {
+        let fasta = unique_test_path("qgrs_aggregate").with_extension("fa");
+        let bed = unique_test_path("qgrs_aggregate").with_extension("bed");
+        let padding = "T".repeat(10);
+        // The only hit spans 11..=29.
+        fs::write(
+            &fasta,
+            format!(">chr1\n{padding}GGGGAGGGGAGGGGAGGGG{padding}{padding}\n>chr2\nAAAA\n"),
+        )
+        .unwrap();
+        fs::write(
+            &bed,
+            "chr1\t0\t49\touter\nchr1\t5\t15\tinner\nchr2\t0\t4\tlonely\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_aggregate_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--aggregate-by".to_string(),
+                bed.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let hits = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+            let score = hits.lines().nth(1).unwrap().split(',').nth(7).unwrap();
+            let csv = fs::read_to_string(out_dir.join(AGGREGATE_CSV)).unwrap();
+            assert_eq!(
+                csv,
+                format!(
+                    "name,chrom,count,best_gscore,covered_bases\n\
+                     outer,chr1,1,{score},19\n\
+                     inner,chr1,1,{score},5\n\
+                     lonely,chr2,0,,0\n"
+                ),
+                "{mode}"
+            );
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        for (flag, message) in [
+            ("--sequence", "--aggregate-by can only be used with --file"),
+            ("--stdout", "--aggregate-by cannot be used with --stdout"),
+        ] {
+            let mut args = vec![
+                "--aggregate-by".to_string(),
+                bed.to_string_lossy().into_owned(),
+            ];
+            if flag == "--sequence" {
+                args.extend(["--sequence".to_string(), "GGGGAGGGGAGGGGAGGGG".to_string()]);
+            } else {
+                args.extend([
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--stdout".to_string(),
+                ]);
+            }
+            let err = run_with_owned_args(args).unwrap_err();
+            assert!(err.starts_with(message), "{err}");
+        }
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&bed);
+    }
+
+    #[test]
+    fn report_html_lists_top_hits_with_highlighted_tetrads() {
+        let fasta = unique_test_path("qgrs_report").with_extension("fa");
+        let spacer = "T".repeat(30);
+        fs::write(
+            &fasta,
+            format!(">chr1\nGGGTTGGGTTGGGTTGGG{spacer}GGGGAGGGGAGGGGAGGGG\n>chr<2>\nAAAA\n"),
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_report_out");
+            let report = unique_test_path("qgrs_report").with_extension("html");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--report-html".to_string(),
+                report.to_string_lossy().into_owned(),
+                "--report-top".to_string(),
+                "1".to_string(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+            let html = fs::read_to_string(&report).unwrap();
+            assert!(html.starts_with("<!DOCTYPE html>"), "{mode}");
+            assert!(
+                html.contains("<h3>chr1: 1 of 2 hits</h3>"),
+                "{mode}: {html}"
+            );
+            // Only the higher-scoring four-tetrad motif makes the cut.
+            assert!(
+                html.contains(
+                    "<span class=\"tetrad\">GGGG</span>a<span class=\"tetrad\">GGGG</span>"
+                ),
+                "{mode}: {html}"
+            );
+            assert!(!html.contains(">GGG</span>tt"), "{mode}: {html}");
+            assert!(html.contains("chr&lt;2&gt;"), "{mode}: {html}");
+            assert!(html.contains("<svg"), "{mode}");
+            let _ = fs::remove_dir_all(&out_dir);
+            let _ = fs::remove_file(&report);
+        }
+        let _ = fs::remove_file(&fasta);
+
+        let err = run_with_owned_args(vec![
+            "--sequence".to_string(),
+            "GGGGAGGGGAGGGGAGGGG".to_string(),
+            "--report-html".to_string(),
+            "report.html".to_string(),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("--report-html can only be used with --file"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_format_matches_csv_row_counts_per_chromosome() {
+        let fasta = unique_test_path("qgrs_sqlite").with_extension("fa");
+        fs::write(
+            &fasta,
+            ">chr1 primary assembly\nGGGGAGGGGAGGGGAGGGGttttttttttttttttttttttttttttGGGTTGGGTTGGGTTGGG\n\
+             >chr2\nAAAA\n>chr1\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_sqlite_csv");
+            let database = unique_test_path("qgrs_sqlite").with_extension("db");
+            let base = |extra: [&str; 2]| {
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                ];
+                args.extend(extra.iter().map(|arg| arg.to_string()));
+                args
+            };
+            let csv = run_with_owned_args(base(["--output-dir", &out_dir.to_string_lossy()]));
+            assert!(csv.is_ok(), "{mode}: {csv:?}");
+            let mut sqlite = base(["--output", &database.to_string_lossy()]);
+            sqlite.extend(["--format".to_string(), "sqlite".to_string()]);
+            let result = run_with_owned_args(sqlite);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+
+            let conn = rusqlite::Connection::open(&database).unwrap();
+            for chrom in ["chr1", "chr1_1", "chr2"] {
+                let rows: usize = conn
+                    .query_row(
+                        "SELECT COUNT(*) FROM hits WHERE chrom = ?1",
+                        [chrom],
+                        |row| row.get(0),
+                    )
+                    .unwrap();
+                let csv = fs::read_to_string(out_dir.join(format!("{chrom}.g4.csv")))
+                    .map(|csv| csv.lines().count() - 1)
+                    .unwrap_or(0);
+                assert_eq!(rows, csv, "{mode}: {chrom}");
+            }
+            let (gscore, sequence, description): (i32, String, Option<String>) = conn
+                .query_row(
+                    "SELECT gscore, sequence, description FROM hits \
+                     WHERE chrom = 'chr1' ORDER BY start LIMIT 1",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!((gscore, sequence.as_str()), (84, "GGGGAGGGGAGGGGAGGGG"));
+            assert_eq!(description.as_deref(), Some("primary assembly"));
+            let described: usize = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM hits WHERE chrom = 'chr1_1' AND description IS NOT NULL",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(described, 0, "{mode}");
+            let parameters: String = conn
+                .query_row("SELECT parameters FROM runs", [], |row| row.get(0))
+                .unwrap();
+            assert!(parameters.contains("\"format\":\"sqlite\""), "{parameters}");
+            let indexed: usize = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'hits'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(indexed, 1);
+            let _ = fs::remove_dir_all(&out_dir);
+            let _ = fs::remove_file(&database);
+        }
+        let _ = fs::remove_file(&fasta);
+
+        let err =
+            run_with_args(["--sequence", "GGGGAGGGGAGGGGAGGGG", "--format", "sqlite"]).unwrap_err();
+        assert!(err.starts_with("--format sqlite requires --file"));
+    }
+
+    #[test]
+    fn parquet_dataset_partitions_by_chromosome() {
+        use arrow_array::{Array, Int32Array, StringArray, UInt64Array};
+        use std::collections::BTreeSet;
+
+        let fasta = unique_test_path("qgrs_dataset").with_extension("fa");
+        fs::write(
+            &fasta,
+            ">chr1\nGGGGAGGGGAGGGGAGGGGttttttttttttttttttttttttttttGGGTTGGGTTGGGTTGGG\n\
+             >chr1\nGGGAAGGGAAGGGAAGGG\n>chrUn/7 x\nGGGTGGGTGGGTGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let csv_dir = unique_test_path("qgrs_dataset_csv");
+            let dataset = unique_test_path("qgrs_dataset_out");
+            let args = |format: &str, dir: &Path| {
+                vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--format".to_string(),
+                    format.to_string(),
+                    "--output-dir".to_string(),
+                    dir.to_string_lossy().into_owned(),
+                ]
+            };
+            assert!(run_with_owned_args(args("csv", &csv_dir)).is_ok(), "{mode}");
+            let result = run_with_owned_args(args("parquet-dataset", &dataset));
+            assert!(result.is_ok(), "{mode}: {result:?}");
+
+            let mut partitions: Vec<String> = fs::read_dir(&dataset)
+                .unwrap()
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.file_type().unwrap().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            partitions.sort();
+            assert_eq!(
+                partitions,
+                ["chrom=chr1", "chrom=chr1_1", "chrom=chrUn%2F7"],
+                "{mode}"
+            );
+
+            let mut from_parts = BTreeSet::new();
+            for partition in &partitions {
+                let part = dataset.join(partition).join(DATASET_PART_FILENAME);
+                let reader =
+                    ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&part).unwrap())
+                        .unwrap()
+                        .build()
+                        .unwrap();
+                for batch in reader {
+                    let batch = batch.unwrap();
+                    assert!(batch.column_by_name("chrom").is_none());
+                    let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+                    let starts = column("start");
+                    let starts = starts.as_any().downcast_ref::<UInt64Array>().unwrap();
+                    let scores = column("score");
+                    let scores = scores.as_any().downcast_ref::<Int32Array>().unwrap();
+                    let sequences = column("sequence");
+                    let sequences = sequences.as_any().downcast_ref::<StringArray>().unwrap();
+                    for row in 0..batch.num_rows() {
+                        from_parts.insert((
+                            partition.clone(),
+                            starts.value(row) as usize,
+                            scores.value(row),
+                            sequences.value(row).to_string(),
+                        ));
+                    }
+                }
+            }
+            let mut from_csv = BTreeSet::new();
+            for (partition, file) in [
+                ("chrom=chr1", "chr1.g4.csv"),
+                ("chrom=chr1_1", "chr1_1.g4.csv"),
+                ("chrom=chrUn%2F7", "chrUn_7.g4.csv"),
+            ] {
+                let csv = fs::File::open(csv_dir.join(file)).unwrap();
+                for record in qgrs::parse_csv_records(io::BufReader::new(csv)).unwrap() {
+                    from_csv.insert((
+                        partition.to_string(),
+                        record.start,
+                        record.score,
+                        record.sequence,
+                    ));
+                }
+            }
+            // Under the auto thresholds the 3-tetrad hit at the end of the
+            // first chr1 no longer shares a family with the 4-tetrad one.
+            assert_eq!(from_parts.len(), 4, "{mode}");
+            assert_eq!(from_parts, from_csv, "{mode}");
+
+            let manifest = fs::read_to_string(dataset.join(MANIFEST_FILENAME)).unwrap();
+            assert!(manifest.contains("\"file\": \"chrom=chr1_1/part-0.parquet\""));
+            let _ = fs::remove_dir_all(&csv_dir);
+            let _ = fs::remove_dir_all(&dataset);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn directory_input_scans_each_fasta_into_its_own_output_dir() {
+        let inputs = unique_test_path("qgrs_batch_in");
+        fs::create_dir_all(&inputs).unwrap();
+        fs::write(inputs.join("a.fa"), ">chr1\nGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        write_gzip(&inputs.join("b.fasta.gz"), b">chr2\nGGGTTGGGTTGGGTTGGG\n");
+        // Gzip magic followed by garbage: found by the scan, fails to decode.
+        fs::write(inputs.join("0bad.fa.gz"), b"\x1f\x8bnot really gzip").unwrap();
+        fs::write(inputs.join("notes.txt"), "not a fasta\n").unwrap();
+
+        let args = |out_dir: &Path, extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                inputs.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args
+        };
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_batch_out");
+            let err = run_with_owned_args(args(&out_dir, &["--mode", mode])).unwrap_err();
+            assert_eq!(err, "1 of 3 input files failed", "{mode}");
+            let a = fs::read_to_string(out_dir.join("a").join("chr1.g4.csv")).unwrap();
+            assert!(a.contains("GGGGAGGGGAGGGGAGGGG"), "{mode}: {a}");
+            let b = fs::read_to_string(out_dir.join("b").join("chr2.g4.csv")).unwrap();
+            assert!(b.contains("GGGTTGGGTTGGGTTGGG"), "{mode}: {b}");
+            let manifest = fs::read_to_string(out_dir.join("b").join(MANIFEST_FILENAME)).unwrap();
+            assert!(manifest.contains("b.fasta.gz"), "{mode}: {manifest}");
+            assert!(!out_dir.join("notes").exists(), "{mode}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let out_dir = unique_test_path("qgrs_batch_out");
+        let err = run_with_owned_args(args(&out_dir, &["--fail-fast"])).unwrap_err();
+        assert!(err.contains("0bad.fa.gz"), "{err}");
+        assert!(!out_dir.join("a").exists());
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let out_dir = unique_test_path("qgrs_batch_out");
+        let result = run_with_owned_args(args(&out_dir, &["--glob", "?.fa*"]));
+        assert!(result.is_ok(), "{result:?}");
+        assert!(out_dir.join("a").join("chr1.g4.csv").exists());
+        assert!(out_dir.join("b").join("chr2.g4.csv").exists());
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_dir_all(&inputs);
+
+        assert!(glob_matches(b"*.fa", b"sample.fa"));
+        assert!(!glob_matches(b"*.fa", b"sample.fa.fai"));
+        assert_eq!(fasta_stem("sample.fna.zst"), Some("sample"));
+        assert_eq!(fasta_stem("sample.fa.fai"), None);
+    }
+
+    #[test]
+    fn resume_skips_complete_outputs_and_redoes_partial_ones() {
+        let fasta = unique_test_path("qgrs_resume").with_extension("fa");
+        fs::write(
+            &fasta,
+            ">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr1\nGGGTTGGGTTGGGTTGGG\n>chr2\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_resume_out");
+            let args = |resume: bool| {
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--overlap".to_string(),
+                    "--output-dir".to_string(),
+                    out_dir.to_string_lossy().into_owned(),
+                ];
+                if resume {
+                    args.push("--resume".to_string());
+                }
+                args
+            };
+            assert!(run_with_owned_args(args(false)).is_ok(), "{mode}");
+            let fresh = fs::read_to_string(out_dir.join("chr2.g4.csv")).unwrap();
+
+            // chr1 looks complete, chr1_1 lost a sidecar, chr2 was cut off mid-write.
+            fs::write(out_dir.join("chr1.g4.csv"), "kept\n").unwrap();
+            fs::remove_file(out_dir.join("chr1_1.g4.family.csv")).unwrap();
+            fs::write(out_dir.join("chr2.g4.csv"), "").unwrap();
+            fs::write(out_dir.join("chr2.g4.csv.tmp"), "partial").unwrap();
+
+            assert!(run_with_owned_args(args(true)).is_ok(), "{mode}");
+            let read = |file: &str| fs::read_to_string(out_dir.join(file)).unwrap();
+            assert_eq!(read("chr1.g4.csv"), "kept\n", "{mode}");
+            assert!(read("chr1_1.g4.family.csv").starts_with("family_index"));
+            assert_eq!(read("chr2.g4.csv"), fresh, "{mode}");
+            let leftovers: Vec<_> = fs::read_dir(&out_dir)
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "tmp"))
+                .collect();
+            assert!(leftovers.is_empty(), "{mode}: {leftovers:?}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn checkpoint_resumes_an_interrupted_stream_scan_inside_a_record() {
+        let record = |repeats: usize| {
+            let unit = format!("GGGAGGGAGGGAGGG{}", "TACGATCGTA".repeat(5));
+            let sequence = unit.repeat(repeats);
+            let lines: Vec<_> = sequence
+                .as_bytes()
+                .chunks(60)
+                .map(|line| line.to_vec())
+                .collect();
+            String::from_utf8(lines.join(&b'\n')).unwrap()
+        };
+        let fasta = unique_test_path("qgrs_checkpoint").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\n{}\n>chr2\n{}\n>chr3\n{}\n",
+                record(40),
+                record(60),
+                record(3)
+            ),
+        )
+        .unwrap();
+        let fresh_dir = unique_test_path("qgrs_checkpoint_fresh");
+        let out_dir = unique_test_path("qgrs_checkpoint_out");
+        let checkpoint = unique_test_path("qgrs_checkpoint_state");
+        let args = |dir: &Path, min_score: &str, checkpointed: bool| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                "stream".to_string(),
+                "--min-score".to_string(),
+                min_score.to_string(),
+                "--overlap".to_string(),
+                "--output-dir".to_string(),
+                dir.to_string_lossy().into_owned(),
+            ];
+            if checkpointed {
+                args.extend([
+                    "--checkpoint".to_string(),
+                    checkpoint.to_string_lossy().into_owned(),
+                    "--checkpoint-every".to_string(),
+                    "5".to_string(),
+                ]);
+            }
+            args
+        };
+        assert!(run_with_owned_args(args(&fresh_dir, "17", false)).is_ok());
+
+        // chr2's output cannot be written, so the run dies after chr2 was
+        // scanned, with its last checkpoint inside chr2.
+        let blocker = out_dir.join("chr2.g4.csv");
+        fs::create_dir_all(&blocker).unwrap();
+        assert!(run_with_owned_args(args(&out_dir, "17", true)).is_err());
+        assert!(checkpoint.exists());
+        fs::remove_dir(&blocker).unwrap();
+        let _ = fs::remove_file(out_dir.join("chr2.g4.csv.tmp"));
+
+        let refused = run_with_owned_args(args(&out_dir, "30", true)).unwrap_err();
+        assert!(refused.contains("delete it to start over"), "{refused}");
+
+        assert!(run_with_owned_args(args(&out_dir, "17", true)).is_ok());
+        assert!(!checkpoint.exists());
+        let names = |dir: &Path| {
+            let mut names: Vec<_> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .filter(|name| name != MANIFEST_FILENAME)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&out_dir), names(&fresh_dir));
+        for name in names(&fresh_dir) {
+            assert_eq!(
+                fs::read(out_dir.join(&name)).unwrap(),
+                fs::read(fresh_dir.join(&name)).unwrap(),
+                "{name:?}"
+            );
+        }
+        let manifest = fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap();
+        assert_eq!(manifest.matches("\"name\"").count(), 3, "{manifest}");
+        for path in [&fresh_dir, &out_dir] {
+            let _ = fs::remove_dir_all(path);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn coords_zero_shifts_starts_everywhere_but_bed() {
+        let fasta = unique_test_path("qgrs_coords").with_extension("fa");
+        fs::write(&fasta, ">chr1\nttGGGGAGGGGAGGGGAGGGGtt\n").unwrap();
+        let scan = |coords: &str, format: &str, gruns: Option<&Path>| {
+            let out_dir = unique_test_path("qgrs_coords_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--coords".to_string(),
+                coords.to_string(),
+                "--format".to_string(),
+                format.to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            if let Some(dir) = gruns {
+                args.push("--gruns-output".to_string());
+                args.push(dir.to_string_lossy().into_owned());
+            }
+            let result = run_with_owned_args(args);
+            assert!(result.is_ok(), "{coords} {format}: {result:?}");
+            out_dir
+        };
+        let read = |dir: &Path, name: &str| fs::read_to_string(dir.join(name)).unwrap();
+
+        let gruns = unique_test_path("qgrs_coords_gruns");
+        let one = scan("1", "csv", None);
+        let zero = scan("0", "csv", Some(&gruns));
+        assert_eq!(
+            read(&one, "chr1.g4.csv").lines().nth(1),
+            Some("3,21,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG")
+        );
+        assert_eq!(
+            read(&zero, "chr1.g4.csv").lines().nth(1),
+            Some("2,21,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG")
+        );
+        assert_eq!(
+            read(&gruns, "chr1.gruns.tsv").lines().nth(1),
+            Some("2\t6\t4")
+        );
+        let manifest = |dir: &Path| -> serde_json::Value {
+            serde_json::from_str(&read(dir, MANIFEST_FILENAME)).unwrap()
+        };
+        assert_eq!(
+            manifest(&one)["parameters"]["coordinates"],
+            "1-based-inclusive"
+        );
+        assert_eq!(
+            manifest(&zero)["parameters"]["coordinates"],
+            "0-based-half-open"
+        );
+
+        let bed_one = scan("1", "family-ranges", None);
+        let bed_zero = scan("0", "family-ranges", None);
+        assert_eq!(
+            read(&bed_one, "chr1.g4.bed"),
+            read(&bed_zero, "chr1.g4.bed")
+        );
+        assert_eq!(
+            read(&bed_zero, "chr1.g4.bed").lines().nth(1),
+            Some("chr1\t2\t21\t157\t84")
+        );
+
+        let err = run_with_args(["--sequence", "GGG", "--coords", "2"]).unwrap_err();
+        assert!(err.starts_with("--coords must be 1 or 0"), "{err}");
+        let err = run_with_args([
+            "--sequence",
+            "GGG",
+            "--coords",
+            "0",
+            "--format",
+            "qgrs-mapper",
+        ])
+        .unwrap_err();
+        assert!(
+            err.starts_with("--coords 0 cannot be used with --format qgrs-mapper"),
+            "{err}"
+        );
+        for dir in [&one, &zero, &gruns, &bed_one, &bed_zero] {
+            let _ = fs::remove_dir_all(dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn auto_mode_streams_files_larger_than_half_the_available_memory() {
+        let fasta = unique_test_path("qgrs_auto_mode").with_extension("fa");
+        fs::write(&fasta, format!(">chr1\n{}\n", "GGGTTA".repeat(100))).unwrap();
+        let size = fs::metadata(&fasta).unwrap().len();
+        let resolve =
+            |requested, available: Option<u64>| resolve_input_mode(&fasta, requested, || available);
+        assert_eq!(resolve(None, Some(size * 2)), InputMode::Mmap);
+        assert_eq!(resolve(None, Some(size * 2 - 2)), InputMode::Stream);
+        // Unknown memory keeps the old default.
+        assert_eq!(resolve(None, None), InputMode::Mmap);
+        // An explicit mode is kept; mmap only warns.
+        assert_eq!(resolve(Some(InputMode::Mmap), Some(1)), InputMode::Mmap);
+        let stream = resolve_input_mode(&fasta, Some(InputMode::Stream), || {
+            panic!("stream mode needs no memory probe")
+        });
+        assert_eq!(stream, InputMode::Stream);
+
+        assert_eq!(
+            parse_meminfo(
+                "MemTotal:       16318020 kB\nMemFree:         1024 kB\nMemAvailable:    2048 kB\n"
+            ),
+            Some(2048 * 1024)
+        );
+        assert_eq!(parse_meminfo("MemFree:    512 kB\n"), Some(512 * 1024));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+
+        // Options that need one mode settle `auto` instead of failing on a
+        // large file.
+        let out_dir = unique_test_path("qgrs_auto_mode_out");
+        let result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--mode".to_string(),
+            "auto".to_string(),
+            "--pair-strands".to_string(),
+            "--output-dir".to_string(),
+            out_dir.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+        let manifest = fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap();
+        assert!(manifest.contains("\"mode\": \"mmap\""), "{manifest}");
+        let err = run_with_args(["--sequence", "GGG", "--mode", "fast"]).unwrap_err();
+        assert!(
+            err.starts_with("--mode must be mmap, stream or auto"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn file_runs_write_a_manifest_with_per_record_counts() {
+        use sha2::{Digest, Sha256};
+
+        let fasta = unique_test_path("qgrs_manifest").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\nGGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG\n>chr2\nGGGAAGGGAAGGGAAGGG\n",
+                "T".repeat(40)
+            ),
+        )
+        .unwrap();
+        // A few bytes are far below half of any real machine's memory.
+        for (mode, resolved) in [("mmap", "mmap"), ("stream", "stream"), ("auto", "mmap")] {
+            let out_dir = unique_test_path("qgrs_manifest_out");
+            let result = run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--min-score".to_string(),
+                "20".to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ]);
+            assert!(result.is_ok(), "{mode}: {result:?}");
+
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            assert_eq!(manifest["qgrs_version"], env!("CARGO_PKG_VERSION"));
+            assert_eq!(manifest["parameters"]["min_score"], 20);
+            assert_eq!(manifest["parameters"]["min_scores"]["3"], 20);
+            assert_eq!(manifest["parameters"]["mode"], resolved);
+            assert_eq!(
+                manifest["input"]["size"],
+                fs::metadata(&fasta).unwrap().len()
+            );
+            assert!(manifest["finished_at"].as_u64() >= manifest["started_at"].as_u64());
+
+            let chromosomes = manifest["chromosomes"].as_array().unwrap();
+            let summary: Vec<(&str, u64)> = chromosomes
+                .iter()
+                .map(|entry| {
+                    (
+                        entry["file"].as_str().unwrap(),
+                        entry["records"].as_u64().unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(summary, [("chr1.g4.csv", 2), ("chr2.g4.csv", 1)], "{mode}");
+            for entry in chromosomes {
+                let bytes = fs::read(out_dir.join(entry["file"].as_str().unwrap())).unwrap();
+                assert_eq!(
+                    entry["sha256"],
+                    format!("{:x}", Sha256::digest(&bytes)),
+                    "{mode}"
+                );
+            }
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn auto_min_score_is_recorded_in_manifest_and_parquet_footer() {
+        let fasta = unique_test_path("qgrs_auto_score").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nGGGAGGG{}GGGAGGG\n",
+                "T".repeat(20)
+            ),
+        )
+        .unwrap();
+        let out_dir = unique_test_path("qgrs_auto_score_out");
+        let result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--format".to_string(),
+            "parquet".to_string(),
+            "--output-dir".to_string(),
+            out_dir.to_string_lossy().into_owned(),
+        ]);
+        assert!(result.is_ok(), "{result:?}");
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        let parameters = &manifest["parameters"];
+        assert!(parameters["min_score"].is_null());
+        for (tetrads, score) in [("2", 17), ("3", 52), ("4", 68), ("5", 77)] {
+            assert_eq!(parameters["min_scores"][tetrads], score, "{tetrads}");
+        }
+        // chr2's only candidate is a 3-tetrad hit scoring 51.
+        let records: Vec<u64> = manifest["chromosomes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["records"].as_u64().unwrap())
+            .collect();
+        assert_eq!(records, [1, 0]);
+
+        let file = fs::File::open(out_dir.join("chr1.g4.parquet")).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        let footer: BTreeMap<&str, &str> = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .filter_map(|kv| Some((kv.key.as_str(), kv.value.as_deref()?)))
+            .collect();
+        assert_eq!(footer["qgrs.min_score"], "auto");
+        let min_scores: serde_json::Value =
+            serde_json::from_str(footer["qgrs.min_scores"]).unwrap();
+        assert_eq!(min_scores["3"], 52);
+        assert_eq!(min_scores, parameters["min_scores"]);
+
+        let err = run_with_args(["--sequence", "GGGG", "--min-score", "high"]).unwrap_err();
+        assert!(err.starts_with("--min-score must be an integer or auto"));
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn keep_empty_writes_parquet_with_schema() {
+        let fasta = unique_test_path("qgrs_keep_empty_parquet").with_extension("fa");
+        fs::write(&fasta, b">chrM\n>chr1\nGGGGAGGGGAGGGGAGGGG\n").unwrap();
+        let out_dir = unique_test_path("qgrs_keep_empty_parquet_out");
+        let result = run_with_owned_args(vec![
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--output-dir".to_string(),
+            out_dir.to_string_lossy().into_owned(),
+            "--format".to_string(),
+            "parquet".to_string(),
+            "--keep-empty".to_string(),
+        ]);
+        assert!(result.is_ok());
+        let meta = fs::metadata(out_dir.join("chrM.g4.parquet")).expect("empty parquet output");
+        assert!(meta.len() > 0);
+
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_dir_all(&out_dir);
+    }
+
+    #[test]
+    fn qgrs_mapper_format_writes_text_tables_per_chromosome() {
+        let fasta = unique_test_path("qgrs_mapper").with_extension("fa");
+        fs::write(
+            &fasta,
+            format!(
+                ">chr1\nGGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG\n>chr2\nACGT\n",
+                "T".repeat(40)
+            ),
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_mapper_out");
+            let args = |extra: &[&str]| {
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--format".to_string(),
+                    "qgrs-mapper".to_string(),
+                    "--output-dir".to_string(),
+                    out_dir.to_string_lossy().into_owned(),
+                ];
+                args.extend(extra.iter().map(|arg| arg.to_string()));
+                args
+            };
+            let err = run_with_owned_args(args(&["--overlap"])).unwrap_err();
+            assert!(err.contains("--overlap cannot be used"), "{err}");
+            let err = run_with_owned_args(args(&["--rank"])).unwrap_err();
+            assert!(err.contains("--rank and --annotate"), "{err}");
+
+            run_with_owned_args(args(&[])).unwrap();
+            let text = fs::read_to_string(out_dir.join("chr1.g4.txt")).unwrap();
+            assert!(
+                text.starts_with("# QGRS Mapper-compatible output\n# Sequence: chr1 (77 bp)\n")
+            );
+            let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+            assert_eq!(rows.len(), 3, "{mode}: {text}");
+            assert!(rows[0].starts_with("Position  Length  QGRS"));
+            assert!(rows[1].starts_with("1         19      GGGGaGGGGaGGGGaGGGG"));
+            assert!(rows[2].starts_with("60        18      GGGttGGGttGGGttGGG"));
+            assert_eq!(
+                count_records(&out_dir.join("chr1.g4.txt"), OutputFormat::QgrsMapper),
+                Ok(2)
+            );
+
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            assert_eq!(manifest["parameters"]["format"], "qgrs-mapper");
+            assert_eq!(manifest["chromosomes"][0]["records"], 2, "{mode}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn max_hits_caps_each_record_and_marks_it_truncated() {
+        let fasta = unique_test_path("qgrs_max_hits").with_extension("fa");
+        let many = format!("GGGTTGGGTTGGGTTGGG{}", "A".repeat(30)).repeat(6);
+        fs::write(
+            &fasta,
+            format!(">chr1\n{many}\n>chr1\n{many}\n>chr2\nGGGTTGGGTTGGGTTGGG\n"),
+        )
+        .unwrap();
+        let run = |out_dir: &Path, mode: &str, max_hits: Option<&str>| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+            ];
+            if let Some(max_hits) = max_hits {
+                args.extend(["--max-hits".to_string(), max_hits.to_string()]);
+            }
+            run_with_owned_args(args).unwrap();
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let csv = |file: &str| fs::read_to_string(out_dir.join(file)).unwrap();
+            let outputs: Vec<String> = ["chr1.g4.csv", "chr1_1.g4.csv", "chr2.g4.csv"]
+                .map(csv)
+                .to_vec();
+            (manifest, outputs)
+        };
+        for mode in ["mmap", "stream"] {
+            let full_dir = unique_test_path("qgrs_max_hits_full");
+            let (_, full) = run(&full_dir, mode, None);
+            assert_eq!(full[0].lines().count(), 7, "{mode}");
+            let capped_dir = unique_test_path("qgrs_max_hits_capped");
+            let (manifest, capped) = run(&capped_dir, mode, Some("2"));
+            for (capped, full) in capped.iter().zip(&full) {
+                let kept: Vec<&str> = full.lines().take(3).collect();
+                assert_eq!(capped.lines().collect::<Vec<_>>(), kept, "{mode}");
+            }
+            assert_eq!(manifest["parameters"]["max_hits"], 2, "{mode}");
+            let truncated: Vec<bool> = manifest["chromosomes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["truncated"].as_bool().unwrap())
+                .collect();
+            assert_eq!(truncated, [true, true, false], "{mode}");
+            let _ = fs::remove_dir_all(&full_dir);
+            let _ = fs::remove_dir_all(&capped_dir);
+        }
+
+        let motifs = format!("GGGTTGGGTTGGGTTGGG{}", "A".repeat(30))
+            .repeat(3)
+            .into_bytes();
+        let records = || vec![("chr1".to_string(), motifs.clone()); 2];
+        let scan = ScanConfig::default().with_max_hits(Some(2));
+        let results = qgrs::scan_many(records(), scan);
+        assert_eq!(results[1].hits.len(), 2);
+        assert_eq!(
+            truncation_warning(&results[1]).as_deref(),
+            Some(
+                "Warning: chr1_1 has more than 2 hits; only the 2 with the lowest starts were \
+                 written (--max-hits)"
+            )
+        );
+        let results = qgrs::scan_many(records(), scan.with_max_hits(Some(3)));
+        assert_eq!(truncation_warning(&results[1]), None);
+
+        let err = run_with_args(["--sequence", "GGGG", "--max-hits", "5"]).unwrap_err();
+        assert!(
+            err.starts_with("--max-hits can only be used with --file"),
+            "{err}"
+        );
+        let err = run_with_args(["--sequence", "GGGG", "--max-hits", "0"]).unwrap_err();
+        assert!(err.starts_with("--max-hits must be > 0"), "{err}");
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn max_hits_counts_only_hits_left_by_exclude_bed() {
+        let fasta = unique_test_path("qgrs_max_hits_exclude").with_extension("fa");
+        let many = format!("GGGTTGGGTTGGGTTGGG{}", "A".repeat(30)).repeat(6);
+        fs::write(&fasta, format!(">chr1\n{many}\n")).unwrap();
+        // Covers the first two of chr1's six hits, which start 48 bp apart.
+        let bed = fasta.with_extension("bed");
+        fs::write(&bed, "chr1\t0\t60\n").unwrap();
+        let run = |mode: &str, extra: &[&str]| {
+            let out_dir = unique_test_path("qgrs_max_hits_exclude_out");
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                mode.to_string(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            run_with_owned_args(args).unwrap();
+            let csv = fs::read_to_string(out_dir.join("chr1.g4.csv")).unwrap();
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            let _ = fs::remove_dir_all(&out_dir);
+            (csv, manifest["chromosomes"][0]["truncated"].as_bool())
+        };
+        let bed_arg = bed.to_string_lossy().into_owned();
+        for mode in ["mmap", "stream"] {
+            let (full, _) = run(mode, &[]);
+            assert_eq!(full.lines().count(), 7, "{mode}");
+            let (excluded, _) = run(mode, &["--exclude-bed", &bed_arg]);
+            assert_eq!(excluded.lines().count(), 5, "{mode}");
+
+            let (capped, truncated) = run(mode, &["--exclude-bed", &bed_arg, "--max-hits", "3"]);
+            let kept: Vec<&str> = excluded.lines().take(4).collect();
+            assert_eq!(capped.lines().collect::<Vec<_>>(), kept, "{mode}");
+            assert_eq!(truncated, Some(true), "{mode}");
+
+            let (capped, truncated) = run(mode, &["--exclude-bed", &bed_arg, "--max-hits", "4"]);
+            assert_eq!(capped, excluded, "{mode}");
+            assert_eq!(truncated, Some(false), "{mode}");
+        }
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&bed);
+    }
+
+    #[test]
+    fn filename_index_and_hash_keep_grc_alt_records_apart() {
+        // Real GRCh38 ALT, HLA and RefSeq names, a duplicate, and a record
+        // named like the duplicate's default output.
+        const NAMES: [&str; 8] = [
+            "chr6_GL000250v2_alt",
+            "HLA-A*01:01:01:01",
+            "HLA-A*01:01:01:02N",
+            "chr1_KI270762v1_alt",
+            "chr19_KI270938v1_alt",
+            "NC_000001.11",
+            "chr6_GL000250v2_alt",
+            "chr6_GL000250v2_alt_1",
+        ];
+        let fasta = unique_test_path("qgrs_alt_names").with_extension("fa");
+        let mut contents = String::new();
+        for name in NAMES {
+            contents.push_str(&format!(">{name} GRC record\nGGGTTGGGTTGGGTTGGG\n"));
+        }
+        fs::write(&fasta, contents).unwrap();
+        let run = |out_dir: &Path, flags: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ];
+            args.extend(flags.iter().map(|flag| flag.to_string()));
+            run_with_owned_args(args)
+        };
+
+        let out_dir = unique_test_path("qgrs_alt_names_out");
+        let err = run(&out_dir, &[]).unwrap_err();
+        assert!(err.contains("would both write"), "{err}");
+
+        let hash = |name: &str| format!("{:x}", Sha256::digest(name.as_bytes()))[..8].to_string();
+        for (flags, expected) in [
+            (
+                &["--filename-hash"][..],
+                format!("NC_000001.11.{}.g4.csv", hash("NC_000001.11")),
+            ),
+            (
+                &["--filename-index"][..],
+                "000001_HLA-A_01_01_01_01.g4.csv".to_string(),
+            ),
+            (
+                &["--filename-index", "--filename-hash"][..],
+                format!(
+                    "000006_chr6_GL000250v2_alt_1.{}.g4.csv",
+                    hash("chr6_GL000250v2_alt")
+                ),
+            ),
+        ] {
+            let out_dir = unique_test_path("qgrs_alt_names_out");
+            run(&out_dir, flags).unwrap();
+            let tsv = fs::read_to_string(out_dir.join(FILENAMES_TSV)).unwrap();
+            let mut lines = tsv.lines();
+            assert_eq!(lines.next(), Some("name\toccurrence\tfile"));
+            let rows: Vec<Vec<&str>> = lines.map(|line| line.split('\t').collect()).collect();
+            let names: Vec<&str> = rows.iter().map(|row| row[0]).collect();
+            assert_eq!(names, NAMES, "{flags:?}");
+            assert_eq!(rows[6][1], "1", "{flags:?}");
+            let files: HashSet<&str> = rows.iter().map(|row| row[2]).collect();
+            assert_eq!(files.len(), NAMES.len(), "{flags:?}: {tsv}");
+            assert!(files.contains(expected.as_str()), "{flags:?}: {tsv}");
+            for file in &files {
+                assert!(out_dir.join(file).is_file(), "{flags:?}: {file}");
+            }
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap())
+                    .unwrap();
+            assert_eq!(
+                manifest["parameters"]["filename_index"],
+                flags.contains(&"--filename-index")
+            );
+            let manifest_files: Vec<&str> = manifest["chromosomes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["file"].as_str().unwrap())
+                .collect();
+            let mapped: Vec<&str> = rows.iter().map(|row| row[2]).collect();
+            assert_eq!(manifest_files, mapped, "{flags:?}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let err = run_with_args(["--sequence", "GGGG", "--filename-index"]).unwrap_err();
+        assert!(
+            err.starts_with("--filename-index and --filename-hash can only be used with --file"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn record_names_with_slashes_and_dots_stay_inside_the_output_dir() {
+        let fasta = unique_test_path("qgrs_sanitize").with_extension("fa");
+        fs::write(
+            &fasta,
+            ">../qgrs_sanitize_escape\nGGGTTGGGTTGGGTTGGG\n>HLA/A*01:01\nGGGTTGGGTTGGGTTGGG\n",
+        )
+        .unwrap();
+        let out_dir = unique_test_path("qgrs_sanitize_out");
+        let gruns_dir = unique_test_path("qgrs_sanitize_runs");
+        let args = |fasta: &Path, extra: &[&str]| {
+            let mut args = vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+                "--gruns-output".to_string(),
+                gruns_dir.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                "mmap".to_string(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args
+        };
+        let listing = |dir: &Path| {
+            let mut files = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        run_with_owned_args(args(&fasta, &["--filename-index"])).unwrap();
+        let files = listing(&out_dir);
+        assert_eq!(
+            files,
+            [
+                "000000_.._qgrs_sanitize_escape.g4.csv",
+                "000001_HLA_A_01_01.g4.csv",
+                FILENAMES_TSV,
+                MANIFEST_FILENAME,
+            ]
+        );
+        assert!(
+            !out_dir
+                .parent()
+                .unwrap()
+                .join("qgrs_sanitize_escape.g4.csv")
+                .exists()
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join(FILENAMES_TSV)).unwrap(),
+            "name\toccurrence\tfile\tgruns\n\
+             ../qgrs_sanitize_escape\t0\t000000_.._qgrs_sanitize_escape.g4.csv\t\
+             000000_.._qgrs_sanitize_escape.gruns.tsv\n\
+             HLA/A*01:01\t0\t000001_HLA_A_01_01.g4.csv\t000001_HLA_A_01_01.gruns.tsv\n"
+        );
+        assert_eq!(
+            listing(&gruns_dir),
+            [
+                "000000_.._qgrs_sanitize_escape.gruns.tsv",
+                "000001_HLA_A_01_01.gruns.tsv",
+            ]
+        );
+
+        // Nothing is left of `..` once sanitized; the preflight and, with
+        // --force, the writer refuse it.
+        let dots = unique_test_path("qgrs_sanitize_dots").with_extension("fa");
+        fs::write(&dots, ">..\nGGGTTGGGTTGGGTTGGG\n").unwrap();
+        for extra in [&[][..], &["--force"][..]] {
+            let err = run_with_owned_args(args(&dots, extra)).unwrap_err();
+            assert!(err.contains("cannot be used as a file name"), "{err}");
+        }
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_dir_all(&gruns_dir);
+        let _ = fs::remove_file(&fasta);
+        let _ = fs::remove_file(&dots);
+    }
+
+    #[test]
+    fn dry_run_writes_nothing_and_lists_the_real_outputs() {
+        let fasta = unique_test_path("qgrs_dry_run").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGGGGAGGGGAGGGGAGGGG\n>chrE\n>chr1\nACGT\n>chr2\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        for mode in ["mmap", "stream"] {
+            let out_dir = unique_test_path("qgrs_dry_run_out");
+            let args = |dry_run: bool| {
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--output-dir".to_string(),
+                    out_dir.to_string_lossy().into_owned(),
+                ];
+                if dry_run {
+                    args.push("--dry-run".to_string());
+                }
+                args
+            };
+            assert!(run_with_owned_args(args(true)).is_ok(), "{mode}");
+            assert!(!out_dir.exists(), "{mode}: dry run created {out_dir:?}");
+
+            let (_, plan) = plan_outputs(
+                &fasta,
+                Motifs::G4,
+                OutputFormat::Csv,
+                &RecordFilter::default(),
+                FilenameScheme::default(),
+                None,
+            )
+            .unwrap();
+            let planned: Vec<&str> = plan.iter().map(|(_, file)| file.as_str()).collect();
+            assert_eq!(planned, ["chr1.g4.csv", "chr1_1.g4.csv", "chr2.g4.csv"]);
+
+            assert!(run_with_owned_args(args(false)).is_ok(), "{mode}");
+            let mut written: Vec<String> = fs::read_dir(&out_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .filter(|name| name != MANIFEST_FILENAME && name != FILENAMES_TSV)
+                .collect();
+            written.sort();
+            assert_eq!(written, planned, "{mode}");
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn json_log_has_one_timed_line_per_chromosome() {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl io::Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let fasta = unique_test_path("qgrs_log").with_extension("fa");
+        fs::write(
+            &fasta,
+            b">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nGGGAAGGGAAGGGAAGGG\n",
+        )
+        .unwrap();
+        let out_dir = unique_test_path("qgrs_log_out");
+        let buffer = Buffer::default();
+        let sink = buffer.clone();
+        let subscriber =
+            crate::logging::subscriber(crate::logging::LogFormat::Json, move || sink.clone());
+        // Stream mode writes on this thread, which the scoped subscriber covers.
+        let result = tracing::subscriber::with_default(subscriber, || {
+            run_with_owned_args(vec![
+                "--file".to_string(),
+                fasta.to_string_lossy().into_owned(),
+                "--mode".to_string(),
+                "stream".to_string(),
+                "--output-dir".to_string(),
+                out_dir.to_string_lossy().into_owned(),
+            ])
+        });
+        assert!(result.is_ok(), "{result:?}");
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let names: Vec<&str> = lines
+            .iter()
+            .map(|line| line["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["chr1", "chr2"]);
+        for line in &lines {
+            assert_eq!(line["level"], "INFO");
+            assert_eq!(line["hits"], 1);
+            assert!(line["raw_hits"].as_u64().unwrap() >= 1);
+            for field in [
+                "read_seconds",
+                "scan_seconds",
+                "consolidate_seconds",
+                "export_seconds",
+            ] {
+                assert!(line[field].is_f64(), "{field} missing from {line}");
+            }
+        }
+
+        let _ = fs::remove_dir_all(&out_dir);
+        let _ = fs::remove_file(&fasta);
+    }
+
+    #[test]
+    fn chunk_size_changes_nothing_but_is_validated_and_recorded() {
+        let fasta = unique_test_path("qgrs_chunk_size").with_extension("fa");
+        let motif = "GGGAGGGAGGGAGGG";
+        let sequence = (0..40)
+            .map(|i| format!("{motif}{}", "T".repeat(7 + i % 13)))
+            .collect::<String>();
+        fs::write(&fasta, format!(">chr1\n{sequence}\n>chr2\n{motif}\n")).unwrap();
+        for mode in ["mmap", "stream"] {
+            let mut outputs = Vec::new();
+            for chunk_size in [None, Some("45"), Some("97"), Some("100000")] {
+                let out_dir = unique_test_path("qgrs_chunk_size_out");
+                let mut args = vec![
+                    "--file".to_string(),
+                    fasta.to_string_lossy().into_owned(),
+                    "--mode".to_string(),
+                    mode.to_string(),
+                    "--overlap".to_string(),
+                    "--output-dir".to_string(),
+                    out_dir.to_string_lossy().into_owned(),
+                ];
+                if let Some(chunk_size) = chunk_size {
+                    args.extend(["--chunk-size".to_string(), chunk_size.to_string()]);
+                }
+                let result = run_with_owned_args(args);
+                assert!(result.is_ok(), "{mode} {chunk_size:?}: {result:?}");
+                let manifest: serde_json::Value = serde_json::from_str(
+                    &fs::read_to_string(out_dir.join(MANIFEST_FILENAME)).unwrap(),
+                )
+                .unwrap();
+                assert_eq!(
+                    manifest["parameters"]["chunk_size"].as_u64(),
+                    chunk_size.map(|size| size.parse().unwrap())
+                );
+                outputs.push(
+                    ["chr1.g4.csv", "chr1.g4.overlap.csv", "chr2.g4.csv"]
+                        .map(|name| fs::read_to_string(out_dir.join(name)).unwrap()),
+                );
+                let _ = fs::remove_dir_all(&out_dir);
+            }
+            assert!(outputs.iter().all(|output| *output == outputs[0]), "{mode}");
+        }
+
+        let err = run_with_args(["--sequence", "GGGG", "--chunk-size", "44"]);
+        assert!(
+            err.unwrap_err()
+                .contains("--chunk-size must be ≥ --max-g4-length")
+        );
+        let err = run_with_args(["--sequence", "GGGG", "--chunk-size", "999999999"]);
+        assert!(err.unwrap_err().contains("--chunk-size must be ≤"));
+
+        let _ = fs::remove_file(&fasta);
+    }
+
+    /// Child half of `ctrl_c_writes_finished_records_and_exits_130`: runs the
+    /// scan in `QGRS_INTERRUPT_ARGS` (tab-separated) and exits like `qgrs`.
+    #[test]
+    #[ignore = "spawned by ctrl_c_writes_finished_records_and_exits_130"]
+    fn interrupted_scan_child() {
+        let Ok(args) = env::var("QGRS_INTERRUPT_ARGS") else {
+            return;
+        };
+        let args: Vec<String> = args.split('\t').map(String::from).collect();
+        std::process::exit(crate::exit_code(crate::run_env(args.into_iter())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ctrl_c_writes_finished_records_and_exits_130() {
+        use std::process::{Command, Stdio};
+
+        let root = unique_test_path("qgrs_interrupt");
+        fs::create_dir_all(&root).unwrap();
+        let fasta = root.join("genome.fa");
+        // Enough records that the scan is still running when the signal lands.
+        let mut state = 7u64;
+        let mut records = Vec::new();
+        for index in 0..400 {
+            let mut sequence: Vec<u8> = (0..50_000)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    b"ACGT"[(state >> 62) as usize]
+                })
+                .collect();
+            for at in (0..sequence.len() - 30).step_by(1000) {
+                sequence[at..at + 21].copy_from_slice(b"GGGTTAGGGTTAGGGTTAGGG");
+            }
+            records.push((format!("rec{index:03}"), sequence));
+        }
+        let mut contents = Vec::new();
+        for (name, sequence) in &records {
+            contents.extend_from_slice(format!(">{name}\n").as_bytes());
+            contents.extend_from_slice(sequence);
+            contents.push(b'\n');
+        }
+        fs::write(&fasta, contents).unwrap();
+
+        for mode in ["mmap", "stream"] {
+            let out_dir = root.join(mode);
+            let args = [
+                "scan",
+                "--file",
+                fasta.to_str().unwrap(),
+                "--output-dir",
+                out_dir.to_str().unwrap(),
+                "--mode",
+                mode,
+            ];
+            let child = Command::new(env::current_exe().unwrap())
+                .args(["--exact", "scan::tests::interrupted_scan_child"])
+                .args(["--ignored", "--nocapture", "--test-threads", "1"])
+                .env("QGRS_INTERRUPT_ARGS", args.join("\t"))
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+            let written = || -> Vec<String> {
+                fs::read_dir(&out_dir)
+                    .map(|entries| {
+                        entries
+                            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            let deadline = Instant::now() + std::time::Duration::from_secs(120);
+            while !written().iter().any(|file| file.ends_with(".g4.csv")) {
+                assert!(Instant::now() < deadline, "{mode}: no output appeared");
+                std::thread::sleep(std::time::Duration::from_millis(2));
+            }
+            let killed = Command::new("kill")
+                .args(["-INT", &child.id().to_string()])
+                .status()
+                .unwrap();
+            assert!(killed.success());
+            let output = child.wait_with_output().unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.code(), Some(130), "{mode}: {stderr}");
+            assert!(stderr.contains("Interrupted: "), "{mode}: {stderr}");
+            assert!(stderr.contains("Rerun with --resume"), "{mode}: {stderr}");
+
+            // Finished records are complete; the rest are absent, not truncated.
+            let files = written();
+            assert!(
+                !files.iter().any(|file| file.ends_with(".tmp")),
+                "{mode}: {files:?}"
+            );
+            assert!(!files.contains(&MANIFEST_FILENAME.to_string()), "{mode}");
+            let finished: Vec<&(String, Vec<u8>)> = records
+                .iter()
+                .filter(|(name, _)| files.contains(&format!("{name}.g4.csv")))
+                .collect();
+            assert!(
+                !finished.is_empty() && finished.len() < records.len(),
+                "{mode}: {} records written",
+                finished.len()
+            );
+            // A clean scan of just those records writes the same files.
+            let subset = root.join(format!("{mode}_finished.fa"));
+            let mut contents = Vec::new();
+            for (name, sequence) in &finished {
+                contents.extend_from_slice(format!(">{name}\n").as_bytes());
+                contents.extend_from_slice(sequence);
+                contents.push(b'\n');
+            }
+            fs::write(&subset, contents).unwrap();
+            let clean_dir = root.join(format!("{mode}_clean"));
+            run_with_owned_args(
+                [
+                    "--file",
+                    subset.to_str().unwrap(),
+                    "--output-dir",
+                    clean_dir.to_str().unwrap(),
+                ]
+                .map(String::from)
+                .to_vec(),
+            )
+            .unwrap();
+            for (name, _) in &finished {
+                let file = format!("{name}.g4.csv");
+                assert_eq!(
+                    fs::read(out_dir.join(&file)).unwrap(),
+                    fs::read(clean_dir.join(&file)).unwrap(),
+                    "{mode}: {file}"
+                );
+            }
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    fn run_with_args<const N: usize>(args: [&'static str; N]) -> Result<(), String> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        run_with_owned_args(args)
+    }
+
+    fn run_with_owned_args(args: Vec<String>) -> Result<(), String> {
+        let mut argv = vec![String::from("qgrs")];
+        argv.extend(args);
+        run(Args::new(argv.into_iter().skip(1))).map_err(CliError::into_message)
+    }
+
+    fn unique_test_path(prefix: &str) -> PathBuf {
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_nanos();
+        env::temp_dir().join(format!("{prefix}_{}_{}", std::process::id(), nonce))
+    }
+
+    fn write_gzip(path: &Path, bytes: &[u8]) {
+        let file = fs::File::create(path).expect("create gzip file");
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(bytes).expect("write gzip data");
+        encoder.finish().expect("finish gzip");
+    }
+}
@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::time::Instant;
+
+use qgrs_rust::qgrs::verify::self_test;
+
+use crate::{Args, CliError, Outcome, help, usage};
+
+/// `qgrs self-test`: runs [`self_test`] in a fresh temp directory, prints one
+/// PASS/FAIL line per check and removes the directory again.
+pub(crate) fn run<I>(mut args: Args<I>) -> Result<Outcome, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    if let Some(arg) = args.next() {
+        return match arg.as_str() {
+            "--help" | "-h" => Err(help()),
+            other => Err(usage(&format!("unknown argument '{other}'"))),
+        };
+    }
+    let dir = env::temp_dir().join(format!("qgrs_self_test_{}", std::process::id()));
+    fs::create_dir_all(&dir)
+        .map_err(|err| CliError::Check(format!("failed to create {dir:?}: {err}")))?;
+    let started = Instant::now();
+    let checks = self_test(&dir);
+    let _ = fs::remove_dir_all(&dir);
+    let checks = checks
+        .map_err(|err| CliError::Check(format!("self-test failed to run in {dir:?}: {err}")))?;
+
+    for check in &checks {
+        match &check.detail {
+            None => println!("PASS  {}", check.name),
+            Some(detail) => println!("FAIL  {}: {detail}", check.name),
+        }
+    }
+    let passed = checks.iter().all(|check| check.passed);
+    println!(
+        "{} ({} checks in {:.2}s)",
+        if passed { "PASS" } else { "FAIL" },
+        checks.len(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(if passed {
+        Outcome::Success
+    } else {
+        Outcome::Mismatch
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_passes_and_rejects_arguments() {
+        let run_with = |args: &[&str]| run(Args::new(args.iter().map(|arg| arg.to_string())));
+        assert_eq!(run_with(&[]), Ok(Outcome::Success));
+        let err = run_with(&["--seed", "1"]).unwrap_err();
+        assert!(
+            matches!(&err, CliError::Usage(message)
+                if message.starts_with("unknown argument '--seed'")),
+            "{err:?}"
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use qgrs_rust::qgrs::ScanConfig;
+use qgrs_rust::qgrs::intervals::IntervalIndex;
+use qgrs_rust::qgrs::postprocess::IntervalAggregate;
+
+use crate::manifest::RunParameters;
+use crate::resume::{open_checkpoint, outputs_complete};
+#[cfg(feature = "sqlite")]
+use crate::scan::process_fasta_to_sqlite;
+use crate::scan::{
+    AGGREGATE_CSV, FileOptions, FilenameScheme, InputSpec, OutputFormat, Transcripts,
+    available_memory, batch_inputs, mode_name, plan_outputs, process_batch, process_fasta_file,
+    process_fasta_to_writer, process_inline_sequence, render_dry_run, resolve_input_mode,
+};
+use crate::{CliError, interrupt, usage};
+
+/// `scan` options that decide where hits go, or that only some of those
+/// destinations read; the rest travel in [`FileOptions`].
+pub(crate) struct SinkOptions {
+    /// `--output`: the `--sequence` result or the `--format sqlite` database.
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) stdout: bool,
+    pub(crate) glob: Option<String>,
+    pub(crate) fail_fast: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) max_hits: Option<usize>,
+    pub(crate) annotate: Option<PathBuf>,
+    pub(crate) exclude: Option<PathBuf>,
+    pub(crate) aggregate_by: Option<PathBuf>,
+    pub(crate) gtf: Option<PathBuf>,
+    pub(crate) checkpoint: Option<PathBuf>,
+    pub(crate) checkpoint_every: Option<usize>,
+}
+
+/// Scans `input` into the sink its options pick: stdout or `--output` for
+/// `--sequence`, one database for `--format sqlite`, one CSV stream for
+/// `--stdout`, and per-record files under `--output-dir` otherwise. Options
+/// the picked sink has no use for are rejected here.
+pub(crate) fn write(
+    input: InputSpec,
+    scan: ScanConfig,
+    options: FileOptions,
+    sink: SinkOptions,
+) -> Result<(), CliError> {
+    match input {
+        InputSpec::Inline(seq) => inline(seq, scan, options, sink),
+        #[cfg(feature = "sqlite")]
+        InputSpec::File(path) if matches!(options.format, OutputFormat::Sqlite) => {
+            sqlite(path, scan, options, sink)
+        }
+        InputSpec::File(path) if sink.stdout => stdout(path, scan, options, sink),
+        InputSpec::File(path) => output_dir(path, scan, options, sink),
+    }
+}
+
+fn inline(
+    seq: String,
+    scan: ScanConfig,
+    options: FileOptions,
+    sink: SinkOptions,
+) -> Result<(), CliError> {
+    let post = &options.post;
+    if sink.output_dir.is_some() {
+        return Err(usage("--output-dir can only be used with --file"));
+    }
+    if options.records.keep_empty {
+        return Err(usage("--keep-empty can only be used with --file"));
+    }
+    if options.records.chrom_regex.is_some() {
+        return Err(usage("--chrom-regex can only be used with --file"));
+    }
+    if sink.annotate.is_some() {
+        return Err(usage("--annotate can only be used with --file"));
+    }
+    if sink.exclude.is_some() {
+        return Err(usage("--exclude-bed can only be used with --file"));
+    }
+    if post.motif_summary_path.is_some() {
+        return Err(usage("--motif-summary can only be used with --file"));
+    }
+    if sink.aggregate_by.is_some() {
+        return Err(usage("--aggregate-by can only be used with --file"));
+    }
+    if post.report_html_path.is_some() {
+        return Err(usage("--report-html can only be used with --file"));
+    }
+    if post.chrom_sizes_path.is_some() {
+        return Err(usage("--chrom-sizes can only be used with --file"));
+    }
+    if options.resume {
+        return Err(usage("--resume can only be used with --file"));
+    }
+    if sink.checkpoint.is_some() {
+        return Err(usage("--checkpoint can only be used with --file"));
+    }
+    if sink.dry_run {
+        return Err(usage("--dry-run can only be used with --file"));
+    }
+    if sink.glob.is_some() || sink.fail_fast {
+        return Err(usage("--glob and --fail-fast can only be used with --file"));
+    }
+    if sink.stdout {
+        return Err(usage(
+            "--stdout can only be used with --file; --sequence already prints CSV",
+        ));
+    }
+    if options.pair_strands {
+        return Err(usage("--pair-strands can only be used with --file"));
+    }
+    if scan.collects_metrics() {
+        return Err(usage("--verbose can only be used with --file"));
+    }
+    if options.populate {
+        return Err(usage("--populate can only be used with --file"));
+    }
+    if options.record_parallel.is_some() {
+        return Err(usage("--record-parallel can only be used with --file"));
+    }
+    if options.gruns_dir.is_some() {
+        return Err(usage("--gruns-output can only be used with --file"));
+    }
+    if options.naming != FilenameScheme::default() {
+        return Err(usage(
+            "--filename-index and --filename-hash can only be used with --file",
+        ));
+    }
+    if sink.max_hits.is_some() {
+        return Err(usage("--max-hits can only be used with --file"));
+    }
+    #[cfg(feature = "sqlite")]
+    if matches!(options.format, OutputFormat::Sqlite) {
+        return Err(usage("--format sqlite requires --file"));
+    }
+    if matches!(options.format, OutputFormat::ParquetDataset) {
+        return Err(usage("--format parquet-dataset requires --file"));
+    }
+    if sink.output.is_none() {
+        if options.include_overlap {
+            return Err(usage("--overlap requires --output when using --sequence"));
+        }
+        if matches!(options.format, OutputFormat::Parquet | OutputFormat::Arrow) {
+            return Err(usage(&format!(
+                "--output is required when --format {}",
+                options.format.cli_name()
+            )));
+        }
+    }
+    process_inline_sequence(seq, sink.output, scan, &options)?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite(
+    path: PathBuf,
+    scan: ScanConfig,
+    mut options: FileOptions,
+    sink: SinkOptions,
+) -> Result<(), CliError> {
+    if sink.output_dir.is_some() {
+        return Err(usage(
+            "--output-dir cannot be used with --format sqlite; pass the database path with --output",
+        ));
+    }
+    if path.is_dir() {
+        return Err(usage(
+            "--format sqlite needs a single FASTA file, not a directory",
+        ));
+    }
+    let database = sink
+        .output
+        .ok_or_else(|| usage("--output is required when --format sqlite"))?;
+    if options.post.chrom_sizes_path.is_some() {
+        return Err(usage("--chrom-sizes cannot be used with --format sqlite"));
+    }
+    if sink.aggregate_by.is_some() {
+        return Err(usage("--aggregate-by cannot be used with --format sqlite"));
+    }
+    if options.naming != FilenameScheme::default() {
+        return Err(usage(
+            "--filename-index and --filename-hash cannot be used with --format sqlite",
+        ));
+    }
+    if let Some(bed) = sink.exclude {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read exclude regions {bed:?}: {err}"))?;
+        options.post.exclude = Some(index);
+    }
+    process_fasta_to_sqlite(path, database, scan, options)?;
+    Ok(())
+}
+
+fn stdout(
+    path: PathBuf,
+    scan: ScanConfig,
+    mut options: FileOptions,
+    sink: SinkOptions,
+) -> Result<(), CliError> {
+    if path.is_dir() {
+        return Err(usage("--stdout needs a single FASTA file, not a directory"));
+    }
+    if sink.glob.is_some() {
+        return Err(usage("--glob requires --file to be a directory"));
+    }
+    if options.naming != FilenameScheme::default() {
+        return Err(usage(
+            "--filename-index and --filename-hash cannot be used with --stdout",
+        ));
+    }
+    if let Some(bed) = sink.annotate {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read annotation {bed:?}: {err}"))?;
+        options.post.annotations = Some(index);
+    }
+    if let Some(bed) = sink.exclude {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read exclude regions {bed:?}: {err}"))?;
+        options.post.exclude = Some(index);
+    }
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    process_fasta_to_writer(&path, scan, &options, &mut out)?;
+    Ok(())
+}
+
+fn output_dir(
+    path: PathBuf,
+    scan: ScanConfig,
+    mut options: FileOptions,
+    sink: SinkOptions,
+) -> Result<(), CliError> {
+    if sink.output.is_some() {
+        return Err(usage(
+            "--output is only valid with --sequence; use --output-dir for --file",
+        ));
+    }
+    let dir = sink
+        .output_dir
+        .ok_or_else(|| usage("--output-dir is required when --file is used"))?;
+    if sink.aggregate_by.is_some() {
+        options.post.aggregate_path = Some(dir.join(AGGREGATE_CSV));
+    }
+    let batch = path.is_dir();
+    if batch && sink.gtf.is_some() {
+        return Err(usage("--gtf needs a single FASTA file, not a directory"));
+    }
+    let transcripts = sink
+        .gtf
+        .as_deref()
+        .map(|gtf| Transcripts::from_path(gtf, scan.coordinates()))
+        .transpose()?;
+    options.checkpoint = open_checkpoint(
+        sink.checkpoint,
+        sink.checkpoint_every,
+        batch,
+        options.mode,
+        &options.parameters,
+        options.fsync,
+    )?;
+    let inputs = if batch {
+        batch_inputs(&path, sink.glob.as_deref(), &dir)?
+    } else if sink.glob.is_some() {
+        return Err(usage("--glob requires --file to be a directory"));
+    } else {
+        vec![(path, dir)]
+    };
+    if sink.dry_run {
+        print_dry_run(&inputs, &options, transcripts.as_ref())?;
+        return Ok(());
+    }
+    let post = &mut options.post;
+    if let Some(bed) = sink.annotate {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read annotation {bed:?}: {err}"))?;
+        post.annotations = Some(index);
+    }
+    if let Some(bed) = sink.exclude {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read exclude regions {bed:?}: {err}"))?;
+        post.exclude = Some(index);
+    }
+    if let Some(bed) = sink.aggregate_by {
+        let index = IntervalIndex::from_bed_path(&bed)
+            .map_err(|err| format!("failed to read aggregate regions {bed:?}: {err}"))?;
+        post.aggregate = Some(Mutex::new(IntervalAggregate::new(index)));
+    }
+    post.transcripts = transcripts;
+    interrupt::install();
+    if !batch {
+        let (path, dir) = inputs.into_iter().next().expect("one input file");
+        options.output_dir = Some(dir);
+        return process_fasta_file(&path, scan, &options).map_err(CliError::Scan);
+    }
+    process_batch(inputs, scan, options, sink.fail_fast)?;
+    Ok(())
+}
+
+/// `--dry-run`: prints what each input would write, without reading the
+/// BED files or scanning anything.
+fn print_dry_run(
+    inputs: &[(PathBuf, PathBuf)],
+    options: &FileOptions,
+    transcripts: Option<&Transcripts>,
+) -> Result<(), CliError> {
+    for (path, dir) in inputs {
+        let (_, plan) = plan_outputs(
+            path,
+            options.motif,
+            options.format,
+            &options.records,
+            options.naming,
+            transcripts,
+        )?;
+        let parameters = RunParameters {
+            mode: mode_name(resolve_input_mode(path, options.mode, available_memory)),
+            ..options.parameters.clone()
+        };
+        print!(
+            "{}",
+            render_dry_run(path, dir, &parameters, &plan, |filename| {
+                options.resume
+                    && outputs_complete(
+                        dir,
+                        options.format,
+                        options.include_overlap,
+                        options.pair_strands,
+                        filename,
+                    )
+            })
+        );
+    }
+    Ok(())
+}
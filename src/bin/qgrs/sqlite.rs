@@ -0,0 +1,214 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use qgrs_rust::qgrs::{self, CoordinateSystem, G4, G4Record};
+use rusqlite::{Connection, params};
+
+use crate::manifest::{InputInfo, RunParameters, unix_seconds};
+
+/// Rows inserted per transaction; queued chromosomes are merged up to this.
+const TRANSACTION_ROWS: usize = 50_000;
+/// Chromosomes a worker may queue before it blocks on the writer.
+const QUEUE_DEPTH: usize = 64;
+
+// The database is built under `<path>.tmp` and renamed when complete, so
+// journaling would only slow the bulk insert down.
+const SCHEMA: &str = "
+PRAGMA journal_mode = OFF;
+PRAGMA synchronous = OFF;
+CREATE TABLE runs (
+    qgrs_version TEXT NOT NULL,
+    input TEXT NOT NULL,
+    input_size INTEGER NOT NULL,
+    input_modified INTEGER,
+    parameters TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL
+);
+CREATE TABLE hits (
+    chrom TEXT NOT NULL,
+    start INTEGER NOT NULL,
+    end INTEGER NOT NULL,
+    length INTEGER NOT NULL,
+    tetrads INTEGER NOT NULL,
+    y1 INTEGER NOT NULL,
+    y2 INTEGER NOT NULL,
+    y3 INTEGER NOT NULL,
+    gscore INTEGER NOT NULL,
+    sequence TEXT NOT NULL,
+    description TEXT
+);
+";
+
+/// One record's hits and its FASTA header description.
+type RecordHits = (Option<String>, Vec<G4Record>);
+
+const INSERT_HIT: &str = "INSERT INTO hits \
+     (chrom, start, end, length, tetrads, y1, y2, y3, gscore, sequence, description) \
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)";
+
+/// `--format sqlite`: every record of a run goes into one database. Rayon
+/// workers finish out of order, so they only convert their hits and queue
+/// them; a single thread owns the connection and inserts in batched
+/// transactions.
+pub(crate) struct SqliteWriter {
+    path: PathBuf,
+    tmp: PathBuf,
+    sender: SyncSender<RecordHits>,
+    writer: JoinHandle<rusqlite::Result<Connection>>,
+    /// `--coords` of the `start` column.
+    coordinates: CoordinateSystem,
+}
+
+impl SqliteWriter {
+    /// Replaces any database already at `path` once [`finish`](Self::finish)
+    /// succeeds.
+    pub(crate) fn create(path: &Path, coordinates: CoordinateSystem) -> Result<Self, String> {
+        let tmp = qgrs::io::tmp_path(path);
+        if tmp.exists() {
+            fs::remove_file(&tmp).map_err(|err| format!("failed to remove {tmp:?}: {err}"))?;
+        }
+        let conn = Connection::open(&tmp)
+            .and_then(|conn| conn.execute_batch(SCHEMA).map(|()| conn))
+            .map_err(|err| format!("failed to create sqlite database {tmp:?}: {err}"))?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_DEPTH);
+        let writer = thread::spawn(move || insert_batches(conn, receiver));
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp,
+            sender,
+            writer,
+            coordinates,
+        })
+    }
+
+    /// Queues one record's hits; duplicate names get the same `_<n>` suffix
+    /// as per-chromosome files. `description` fills the nullable
+    /// `description` column of each hit.
+    pub(crate) fn send(
+        &self,
+        name: &str,
+        occurrence: usize,
+        description: Option<&str>,
+        hits: &[G4],
+    ) -> Result<(), String> {
+        if hits.is_empty() {
+            return Ok(());
+        }
+        let chrom = if occurrence == 0 {
+            name.to_string()
+        } else {
+            format!("{name}_{occurrence}")
+        };
+        let records = hits
+            .iter()
+            .map(|hit| {
+                hit.to_record()
+                    .in_coordinates(self.coordinates)
+                    .with_chrom(chrom.as_str())
+            })
+            .collect();
+        // A closed channel means the writer failed; `abandon` reports why.
+        self.sender
+            .send((description.map(str::to_string), records))
+            .map_err(|_| format!("sqlite writer for {:?} stopped", self.path))
+    }
+
+    /// Waits for queued hits, records the run, indexes `hits` on
+    /// `(chrom, start)` and moves the database into place, synced to disk
+    /// first with `fsync`.
+    pub(crate) fn finish(
+        self,
+        input: &InputInfo,
+        parameters: &RunParameters,
+        started_at: SystemTime,
+        fsync: bool,
+    ) -> Result<(), String> {
+        let Self {
+            path,
+            tmp,
+            sender,
+            writer,
+            ..
+        } = self;
+        drop(sender);
+        let failed = |err: rusqlite::Error| format!("failed to write sqlite {tmp:?}: {err}");
+        let conn = writer
+            .join()
+            .map_err(|_| format!("sqlite writer for {path:?} panicked"))?
+            .map_err(failed)?;
+        let parameters = serde_json::to_string(parameters)
+            .map_err(|err| format!("failed to serialize parameters: {err}"))?;
+        conn.execute(
+            "INSERT INTO runs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                env!("CARGO_PKG_VERSION"),
+                input.path,
+                input.size,
+                input.modified,
+                parameters,
+                unix_seconds(started_at),
+                unix_seconds(SystemTime::now()),
+            ],
+        )
+        .map_err(failed)?;
+        conn.execute_batch("CREATE INDEX hits_chrom_start ON hits (chrom, start);")
+            .map_err(failed)?;
+        conn.close().map_err(|(_, err)| failed(err))?;
+        qgrs::io::commit(&tmp, &path, fsync).map_err(|err| err.to_string())
+    }
+
+    /// Gives up after a failed scan, removing the partial database. Returns
+    /// the writer's own error when it is what stopped the scan.
+    pub(crate) fn abandon(self, err: String) -> String {
+        drop(self.sender);
+        let err = match self.writer.join() {
+            Ok(Err(write_err)) => format!("failed to write sqlite {:?}: {write_err}", self.tmp),
+            _ => err,
+        };
+        let _ = fs::remove_file(&self.tmp);
+        err
+    }
+}
+
+fn insert_batches(
+    mut conn: Connection,
+    receiver: Receiver<RecordHits>,
+) -> rusqlite::Result<Connection> {
+    while let Ok(first) = receiver.recv() {
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(INSERT_HIT)?;
+            let mut rows = 0;
+            let mut next = Some(first);
+            while let Some((description, records)) = next {
+                for record in &records {
+                    insert.execute(params![
+                        record.chrom,
+                        record.start,
+                        record.end,
+                        record.length,
+                        record.tetrads,
+                        record.y1,
+                        record.y2,
+                        record.y3,
+                        record.score,
+                        record.sequence,
+                        description,
+                    ])?;
+                }
+                rows += records.len();
+                next = if rows < TRANSACTION_ROWS {
+                    receiver.try_recv().ok()
+                } else {
+                    None
+                };
+            }
+        }
+        tx.commit()?;
+    }
+    Ok(conn)
+}
@@ -0,0 +1,253 @@
+use qgrs_rust::qgrs::G4Record;
+use qgrs_rust::qgrs::verify::{ChromosomeReport, VerifyReport, verify_modes};
+use qgrs_rust::qgrs::{QuartetBase, ScanConfig, ScanLimits, SequenceTopology};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Args, CliError, Outcome, help, usage};
+
+const MAX_PRINTED_MISMATCHES: usize = 10;
+
+pub(crate) fn run<I>(mut args: Args<I>) -> Result<Outcome, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut path: Option<PathBuf> = None;
+    let mut report_path: Option<PathBuf> = None;
+    let mut min_tetrads: usize = 2;
+    let mut min_score: i32 = 17;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => {
+                path = Some(PathBuf::from(args.value("--file")?));
+            }
+            "--report" => {
+                report_path = Some(PathBuf::from(args.value("--report")?));
+            }
+            "--min-tetrads" => {
+                min_tetrads = args.parse::<usize>("--min-tetrads", "a positive integer")?;
+                if min_tetrads == 0 {
+                    return Err(usage("--min-tetrads must be > 0"));
+                }
+            }
+            "--min-score" => {
+                min_score = args.parse::<i32>("--min-score", "an integer")?;
+            }
+            "--help" | "-h" => return Err(help()),
+            other => {
+                return Err(usage(&format!("unknown argument '{other}'")));
+            }
+        }
+    }
+
+    let path = path.ok_or_else(|| usage("verify requires --file"))?;
+    verify(&path, report_path.as_deref(), min_tetrads, min_score).map_err(CliError::Check)
+}
+
+/// Scans `path` in both modes, prints the comparison and writes the JSON
+/// report when asked.
+fn verify(
+    path: &Path,
+    report_path: Option<&Path>,
+    min_tetrads: usize,
+    min_score: i32,
+) -> Result<Outcome, String> {
+    if !path.exists() {
+        return Err(format!("file does not exist: {path:?}"));
+    }
+    let config = ScanConfig::new(
+        min_tetrads,
+        min_score,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+
+    println!("════════════════════════════════════════════════════════");
+    println!("🔬 QGRS Stream vs Mmap Mode Performance Comparison");
+    println!("════════════════════════════════════════════════════════");
+    println!("File: {}", path.display());
+    println!(
+        "Parameters: min_tetrads={}, min_score={}",
+        min_tetrads, min_score
+    );
+    println!("════════════════════════════════════════════════════════\n");
+
+    let report =
+        verify_modes(path, config).map_err(|err| format!("failed to verify {path:?}: {err}"))?;
+    if let Some(report_path) = report_path {
+        let json = report
+            .to_json()
+            .map_err(|err| format!("failed to serialize report: {err}"))?;
+        fs::write(report_path, json)
+            .map_err(|err| format!("failed to write {report_path:?}: {err}"))?;
+    }
+    print_report(&report);
+
+    if report.consistent {
+        Ok(Outcome::Success)
+    } else {
+        Ok(Outcome::Mismatch)
+    }
+}
+
+fn print_report(report: &VerifyReport) {
+    let total = |side: fn(&ChromosomeReport) -> Option<usize>| -> usize {
+        report.chromosomes.iter().filter_map(side).sum()
+    };
+    let count = |side: fn(&ChromosomeReport) -> Option<usize>| -> usize {
+        report
+            .chromosomes
+            .iter()
+            .filter(|chrom| side(chrom).is_some())
+            .count()
+    };
+    let mmap_hits = |chrom: &ChromosomeReport| chrom.mmap_hits;
+    let stream_hits = |chrom: &ChromosomeReport| chrom.stream_hits;
+
+    println!("📊 Per-chromosome results (mmap / stream):");
+    for chrom in &report.chromosomes {
+        println!(
+            "    {}: {} / {} G4s",
+            display_name(chrom),
+            display_count(chrom.mmap_hits),
+            display_count(chrom.stream_hits)
+        );
+    }
+    println!(
+        "  Chromosome count: {} / {}",
+        count(mmap_hits),
+        count(stream_hits)
+    );
+    println!("  Total G4s: {} / {}", total(mmap_hits), total(stream_hits));
+
+    println!("\n════════════════════════════════════════════════════════\n");
+
+    println!("⚡ Performance comparison:");
+    println!("  Batch/Mmap: {:.3}s", report.mmap_seconds);
+    println!("  Stream:     {:.3}s", report.stream_seconds);
+    if report.stream_seconds > 0.0 && report.mmap_seconds > 0.0 {
+        let speedup = report.mmap_seconds / report.stream_seconds;
+        if speedup > 1.0 {
+            println!("  Stream is {:.2}x faster", speedup);
+        } else {
+            println!("  Batch/Mmap is {:.2}x faster", 1.0 / speedup);
+        }
+    }
+
+    println!("\n════════════════════════════════════════════════════════\n");
+
+    println!("🔍 Verifying result consistency...");
+    if report.consistent {
+        println!("  ✅ All results are completely consistent!");
+        println!("     - All G4 fields (position, length, sequence, tetrads, loops, score) match");
+        return;
+    }
+
+    println!("  ❌ Found {} mismatch(es):", report.mismatch_count());
+    let mut printed = 0;
+    for chrom in &report.chromosomes {
+        if printed >= MAX_PRINTED_MISMATCHES {
+            println!("  ... (additional mismatches omitted)");
+            break;
+        }
+        match (chrom.mmap_hits, chrom.stream_hits) {
+            (None, _) => {
+                println!(
+                    "  ⚠️  Batch mode missing chromosome: {}",
+                    display_name(chrom)
+                );
+                printed += 1;
+            }
+            (_, None) => {
+                println!(
+                    "  ⚠️  Stream mode missing chromosome: {}",
+                    display_name(chrom)
+                );
+                printed += 1;
+            }
+            _ => {}
+        }
+        for mismatch in &chrom.mismatches {
+            if printed >= MAX_PRINTED_MISMATCHES {
+                break;
+            }
+            println!(
+                "  ⚠️  G4 #{} mismatch in chromosome {}:",
+                mismatch.index + 1,
+                display_name(chrom)
+            );
+            println!("      Batch:  {}", display_hit(mismatch.mmap.as_ref()));
+            println!("      Stream: {}", display_hit(mismatch.stream.as_ref()));
+            printed += 1;
+        }
+    }
+    println!("\n════════════════════════════════════════════════════════");
+}
+
+fn display_name(chrom: &ChromosomeReport) -> String {
+    if chrom.occurrence == 0 {
+        chrom.name.clone()
+    } else {
+        format!("{} (#{})", chrom.name, chrom.occurrence + 1)
+    }
+}
+
+fn display_count(count: Option<usize>) -> String {
+    count.map_or_else(|| "-".to_string(), |count| count.to_string())
+}
+
+fn display_hit(hit: Option<&G4Record>) -> String {
+    match hit {
+        Some(hit) => format!(
+            "pos={}..{}, len={}, seq={}, tetrads={}, y=({},{},{}), score={}",
+            hit.start,
+            hit.end,
+            hit.length,
+            hit.sequence,
+            hit.tetrads,
+            hit.y1,
+            hit.y2,
+            hit.y3,
+            hit.score
+        ),
+        None => "(no hit)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn report_flag_writes_json_document() {
+        let root = env::temp_dir().join(format!("qgrs_verify_report_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let fasta = root.join("input.fa");
+        fs::write(&fasta, b">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nACGT\n").unwrap();
+        let report_path = root.join("report.json");
+        let args = [
+            "--file".to_string(),
+            fasta.to_string_lossy().into_owned(),
+            "--report".to_string(),
+            report_path.to_string_lossy().into_owned(),
+        ];
+        assert_eq!(run(Args::new(args.into_iter())), Ok(Outcome::Success));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+        assert_eq!(json["consistent"], true);
+        assert!(json["mmap_seconds"].is_number());
+        assert!(json["stream_seconds"].is_number());
+        let chromosomes = json["chromosomes"].as_array().unwrap();
+        assert_eq!(chromosomes.len(), 2);
+        assert_eq!(chromosomes[0]["name"], "chr1");
+        assert_eq!(chromosomes[0]["mmap_hits"], 1);
+        assert_eq!(chromosomes[0]["stream_hits"], 1);
+        assert!(chromosomes[0]["mismatches"].as_array().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
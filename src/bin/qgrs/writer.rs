@@ -0,0 +1,53 @@
+use std::sync::mpsc;
+use std::thread;
+
+use rayon::prelude::*;
+
+/// Runs `scan` over `items` on Rayon workers while the calling thread hands
+/// each finished one to `write`, in completion order, so exporting a
+/// dominant chromosome overlaps the scans still running. The queue between
+/// them holds one finished item per Rayon thread, so workers cannot pile up
+/// results faster than they are written. Items `scan` returns `None` for are
+/// left out; the rest come back with their index in `items`, in the order
+/// they were written.
+///
+/// The first error stops the run: a failed `write` drops the queue, so the
+/// workers stop after the items in progress.
+pub(crate) fn scan_while_writing<I, R, T, S, W>(
+    items: &[I],
+    scan: S,
+    mut write: W,
+) -> Result<Vec<(usize, T)>, String>
+where
+    I: Sync,
+    R: Send,
+    S: Fn(&I) -> Result<Option<R>, String> + Sync,
+    W: FnMut(R) -> Result<T, String>,
+{
+    let (sender, receiver) = mpsc::sync_channel(rayon::current_num_threads());
+    thread::scope(|scope| {
+        // A failed send means the loop below stopped with its own error;
+        // workers just quit.
+        let scanning = scope.spawn(|| {
+            items.par_iter().enumerate().try_for_each_with(
+                sender,
+                |sender, (index, item)| -> Result<(), String> {
+                    let Some(scanned) = scan(item)? else {
+                        return Ok(());
+                    };
+                    sender
+                        .send((index, scanned))
+                        .map_err(|err| format!("failed to collect results: {err}"))
+                },
+            )
+        });
+        let mut written = Vec::new();
+        for (index, scanned) in receiver {
+            written.push((index, write(scanned)?));
+        }
+        scanning
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        Ok(written)
+    })
+}
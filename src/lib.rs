@@ -1 +1,4 @@
+#[cfg(feature = "core")]
+pub mod prelude;
+#[cfg(feature = "core")]
 pub mod qgrs;
@@ -0,0 +1,56 @@
+//! The supported library surface in one import. Without the `mmap` and
+//! `export` features it leaves out the file readers and writers.
+//!
+//! ```
+//! # #[cfg(all(feature = "mmap", feature = "export"))] {
+//! use qgrs_rust::prelude::*;
+//!
+//! let hits = find_in_str("ttGGGGAGGGGAGGGGAGGGGtt", 4, 17);
+//! let (best, ranges) = consolidate_g4s(hits);
+//! assert_eq!((best[0].start, best[0].end), (3, 21));
+//! assert_eq!(ranges, [(3, 21)]);
+//! assert!(render_csv_results(&best).starts_with("start,end,"));
+//!
+//! let config = ScanConfig::new(
+//!     2,
+//!     17,
+//!     ScanLimits::default(),
+//!     SequenceTopology::Linear,
+//!     QuartetBase::G,
+//! );
+//! let mut records: Vec<SearchResults> = Vec::new();
+//! let fasta = ">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nACGT\n";
+//! process_reader_with_config(fasta.as_bytes(), config, false, &mut |results| {
+//!     records.push(results);
+//!     Ok(())
+//! })
+//! .unwrap();
+//! assert_eq!(records[0].name, "chr1");
+//! assert_eq!(records[0].hits.len(), 1);
+//! assert_eq!(records[0].family_stats[0].members, records[0].stats.raw_hits);
+//! # }
+//! ```
+
+#[cfg(feature = "mmap")]
+pub use crate::qgrs::stream::{
+    IncrementalScanner, process_fasta_stream_with_cancellation, process_fasta_stream_with_config,
+    process_fasta_stream_with_hits, process_reader_with_config,
+};
+pub use crate::qgrs::{
+    CancellationToken, ChromSequence, CoordinateSystem, DeliveryOrder, Family, FamilyPolicy,
+    FamilyStats, G4, G4Record, InputMode, LoopPolicy, MinScore, QgrsError, QuartetBase, ScanConfig,
+    ScanLimits, ScanMetrics, SearchResults, SearchStats, SequenceTopology, consolidate_g4s,
+    consolidate_with_families, find_in_bytes, find_in_str, find_owned, find_owned_bytes,
+    find_owned_bytes_with_cancellation, find_owned_bytes_with_limits, parse_csv_records, refilter,
+    scan_many, scan_short, search_chromosome, search_chromosome_with_cancellation,
+};
+#[cfg(feature = "export")]
+pub use crate::qgrs::{
+    ExportError, render_csv_results, render_family_ranges_bed, render_family_ranges_csv,
+    render_qgrs_mapper, write_arrow_ipc_results, write_parquet_results,
+};
+#[cfg(feature = "mmap")]
+pub use crate::qgrs::{
+    LoadOptions, load_sequences_from_path, load_sequences_from_path_with_options,
+    load_sequences_from_reader,
+};
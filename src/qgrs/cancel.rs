@@ -0,0 +1,29 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Shared flag that asks a running scan to stop. Clones observe the same flag,
+/// so one clone can be handed to another thread (or a signal handler) while
+/// the scan holds the other.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Candidate pops between cancellation checks in the search loop.
+pub(crate) const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+pub(crate) fn is_cancelled(cancel: Option<&CancellationToken>) -> bool {
+    cancel.is_some_and(CancellationToken::is_cancelled)
+}
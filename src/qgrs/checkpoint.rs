@@ -0,0 +1,612 @@
+//! Checkpoints for stream scans of very long records.
+//!
+//! Resuming record by record does not help a single multi-gigabase
+//! chromosome: a preempted job starts it over. A checkpointed scan
+//! periodically waits for its in-flight windows and saves where it is: the
+//! input byte offset after the last line read, the open record's scheduler
+//! state (sequence offset, the unscanned tail and circular boundary bases)
+//! and which earlier records were delivered or skipped.
+//!
+//! Each save also consolidates the open record's families that no later
+//! window can join. Only their reported hits are kept, or every member when
+//! raw hits were asked for, and they are appended to a sidecar file next to
+//! the checkpoint, so a save writes only the families settled since the
+//! previous one. The raw hits of the families still open are few and go in
+//! the checkpoint itself.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Family, FamilyPolicy, FamilyStats, G4, ScanConfig, consolidate_with_families, count_distinct,
+    io::write_atomically,
+};
+
+/// Time between two saves unless [`StreamCheckpoint::with_period`] or
+/// [`StreamCheckpoint::with_interval`] says otherwise.
+pub const DEFAULT_CHECKPOINT_PERIOD: Duration = Duration::from_secs(30);
+
+const CHECKPOINT_VERSION: u32 = 2;
+/// Range, member count, best score, best member and stored hit count of an
+/// encoded family; its hits follow.
+const FAMILY_HEADER_LEN: usize = 8 + 8 + 8 + 4 + 4 + 4;
+/// `start`, `tetrads`, three loop lengths and `score` of an encoded hit;
+/// its bases follow.
+const HIT_HEADER_LEN: usize = 8 + 4 + 3 * 4 + 4;
+
+/// Where and how often
+/// [`process_fasta_stream_with_checkpoint`](crate::qgrs::stream::process_fasta_stream_with_checkpoint)
+/// saves its progress.
+///
+/// `parameters` is compared along with the scan's own settings before a
+/// checkpoint is resumed, so a caller can add anything else its outputs
+/// depend on, such as an output format.
+#[derive(Clone, Debug)]
+pub struct StreamCheckpoint {
+    path: PathBuf,
+    interval: Option<usize>,
+    period: Duration,
+    parameters: String,
+    fsync: bool,
+}
+
+impl StreamCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            interval: None,
+            period: DEFAULT_CHECKPOINT_PERIOD,
+            parameters: String::new(),
+            fsync: true,
+        }
+    }
+
+    /// Saves after every `chunks` dispatched windows instead of on a timer;
+    /// 0 is treated as 1.
+    pub fn with_interval(mut self, chunks: usize) -> Self {
+        self.interval = Some(chunks.max(1));
+        self
+    }
+
+    /// Saves once `period` has passed since the last save, checked whenever
+    /// a window is dispatched.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn with_parameters(mut self, parameters: impl Into<String>) -> Self {
+        self.parameters = parameters.into();
+        self
+    }
+
+    /// Whether each save is synced to disk before it replaces the last one.
+    pub fn with_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn interval(&self) -> Option<usize> {
+        self.interval
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Settled families of the record at `index`, next to the checkpoint
+    /// itself.
+    fn hits_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(format!(".{index}.hits"));
+        PathBuf::from(path)
+    }
+}
+
+/// Everything a resumed scan needs, saved as JSON at the checkpoint path.
+#[derive(Serialize, Deserialize)]
+struct CheckpointState {
+    version: u32,
+    parameters: String,
+    input_len: u64,
+    /// Input bytes read when the checkpoint was taken, always at a line end.
+    byte_offset: u64,
+    earlier: Vec<EarlierRecord>,
+    record: RecordState,
+}
+
+/// A record that ended before the checkpointed one.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct EarlierRecord {
+    pub(crate) name: String,
+    pub(crate) occurrence: usize,
+    /// Sequence length of a delivered record; `None` when it was skipped.
+    pub(crate) delivered: Option<usize>,
+}
+
+/// The record being read at the checkpoint and its scheduler state.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RecordState {
+    pub(crate) name: String,
+    pub(crate) occurrence: usize,
+    pub(crate) index: usize,
+    pub(crate) description: Option<String>,
+    pub(crate) comment_lines: usize,
+    pub(crate) sequence_len: usize,
+    /// Bases already dispatched as window primaries.
+    pub(crate) offset: usize,
+    /// Windows dispatched, all of them scanned.
+    pub(crate) chunks: usize,
+    /// Bases read but not yet dispatched.
+    pub(crate) buffer: Vec<u8>,
+    pub(crate) circular_head: Vec<u8>,
+    pub(crate) circular_tail: Vec<u8>,
+    /// Settled families in the sidecar file, the bytes they take and the
+    /// distinct raw hits they merged.
+    families: usize,
+    families_bytes: u64,
+    distinct: usize,
+    /// Raw hits of the families not settled yet, encoded like the sidecar's.
+    open_hits: Vec<u8>,
+}
+
+impl RecordState {
+    pub(crate) fn new(name: String, occurrence: usize, index: usize) -> Self {
+        Self {
+            name,
+            occurrence,
+            index,
+            description: None,
+            comment_lines: 0,
+            sequence_len: 0,
+            offset: 0,
+            chunks: 0,
+            buffer: Vec::new(),
+            circular_head: Vec::new(),
+            circular_tail: Vec::new(),
+            families: 0,
+            families_bytes: 0,
+            distinct: 0,
+            open_hits: Vec::new(),
+        }
+    }
+}
+
+/// Where a resumed scan picks up.
+pub(crate) struct ResumePoint {
+    pub(crate) byte_offset: u64,
+    pub(crate) earlier: Vec<EarlierRecord>,
+    pub(crate) record: RecordState,
+    pub(crate) settled: SettledFamilies,
+    pub(crate) hits: Vec<G4>,
+}
+
+/// Families of the open record that no later window can join, consolidated
+/// when a checkpoint is saved so their raw hits are neither kept until the
+/// record ends nor written again. Each keeps only its reported hit, or every
+/// member when raw hits are kept.
+#[derive(Debug, Default)]
+pub(crate) struct SettledFamilies {
+    pub(crate) families: Vec<Family>,
+    /// Member count and best score of each family, in `families` order.
+    pub(crate) stats: Vec<FamilyStats>,
+    /// Raw hits the families merged, and how many of them were distinct.
+    pub(crate) raw_hits: usize,
+    pub(crate) distinct: usize,
+}
+
+impl SettledFamilies {
+    /// Consolidates `hits` and moves the families whose range `closed`
+    /// accepts here, in start order, leaving the members of the others in
+    /// `hits`. Settled hits are detached so the windows they were found in
+    /// can be freed.
+    fn settle(
+        &mut self,
+        hits: &mut Vec<G4>,
+        settling: Settling,
+        closed: impl Fn((usize, usize)) -> bool,
+    ) {
+        hits.sort_unstable_by_key(G4::key);
+        for mut family in consolidate_with_families(std::mem::take(hits), settling.policy) {
+            if !closed(family.range) {
+                hits.append(&mut family.members);
+                continue;
+            }
+            self.raw_hits += family.members.len();
+            if settling.count_distinct {
+                self.distinct += count_distinct(&family.members);
+            }
+            self.stats.push(family.stats());
+            if !settling.keep_raw {
+                let best = family.members.swap_remove(family.best_index);
+                family.members = vec![best];
+                family.best_index = 0;
+            }
+            for member in &mut family.members {
+                member.detach();
+            }
+            self.families.push(family);
+        }
+    }
+}
+
+/// How a checkpointed scan consolidates its settled families.
+#[derive(Clone, Copy)]
+struct Settling {
+    policy: FamilyPolicy,
+    keep_raw: bool,
+    count_distinct: bool,
+}
+
+/// Saves the progress of one scan and tracks the records it has finished.
+pub(crate) struct Checkpointer {
+    options: StreamCheckpoint,
+    parameters: String,
+    input_len: u64,
+    earlier: Vec<EarlierRecord>,
+    open: Option<EarlierRecord>,
+    settling: Settling,
+    /// Record whose settled families the sidecar holds, how many and in how
+    /// many bytes.
+    hits_index: Option<usize>,
+    persisted: usize,
+    hits_bytes: u64,
+    /// Dispatched windows of the open record before the next save is
+    /// considered, and when the last one was taken.
+    next_at: usize,
+    saved_at: Instant,
+}
+
+impl Checkpointer {
+    pub(crate) fn new(
+        options: &StreamCheckpoint,
+        config: ScanConfig,
+        capture_raw: bool,
+        input_len: u64,
+    ) -> Self {
+        Self {
+            parameters: scan_fingerprint(&options.parameters, config, capture_raw),
+            options: options.clone(),
+            input_len,
+            earlier: Vec::new(),
+            open: None,
+            settling: Settling {
+                policy: config.family_policy(),
+                keep_raw: capture_raw || config.keeps_raw(),
+                count_distinct: config.collects_metrics(),
+            },
+            hits_index: None,
+            persisted: 0,
+            hits_bytes: 0,
+            next_at: options.interval.unwrap_or(1),
+            saved_at: Instant::now(),
+        }
+    }
+
+    /// Reads the saved checkpoint, if there is one. A checkpoint of another
+    /// input or taken with other parameters is an `InvalidInput` error
+    /// rather than being resumed or overwritten.
+    pub(crate) fn load(&mut self) -> io::Result<Option<ResumePoint>> {
+        let path = self.options.path.clone();
+        let text = match fs::read(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let state: CheckpointState = serde_json::from_slice(&text).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checkpoint {path:?} is not readable: {err}"),
+            )
+        })?;
+        if state.version != CHECKPOINT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checkpoint {path:?} has version {}, expected {CHECKPOINT_VERSION}",
+                    state.version
+                ),
+            ));
+        }
+        if state.parameters != self.parameters || state.input_len != self.input_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "checkpoint {path:?} was written for another input or with other \
+                     parameters; delete it to start over"
+                ),
+            ));
+        }
+        let record = state.record;
+        let settled = self.read_settled(&record)?;
+        let hits = decode_hits(&record.open_hits).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checkpoint {path:?} has corrupt open hits"),
+            )
+        })?;
+        self.earlier = state.earlier.clone();
+        self.open = Some(EarlierRecord {
+            name: record.name.clone(),
+            occurrence: record.occurrence,
+            delivered: None,
+        });
+        self.hits_index = Some(record.index);
+        self.persisted = record.families;
+        self.hits_bytes = record.families_bytes;
+        self.next_at = record.chunks + self.options.interval.unwrap_or(1);
+        Ok(Some(ResumePoint {
+            byte_offset: state.byte_offset,
+            earlier: state.earlier,
+            record,
+            settled,
+            hits,
+        }))
+    }
+
+    /// Reads the families settled for `record`, dropping anything appended
+    /// to the sidecar after the checkpoint that points at it.
+    fn read_settled(&self, record: &RecordState) -> io::Result<SettledFamilies> {
+        let path = self.options.hits_path(record.index);
+        let mut bytes = Vec::new();
+        if record.families_bytes > 0 {
+            let file = OpenOptions::new().read(true).write(true).open(&path)?;
+            file.set_len(record.families_bytes)?;
+            (&file).read_to_end(&mut bytes)?;
+        }
+        let settled = decode_families(&bytes)
+            .filter(|settled| settled.families.len() == record.families)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("checkpoint hits {path:?} are truncated or corrupt"),
+                )
+            })?;
+        Ok(SettledFamilies {
+            distinct: record.distinct,
+            ..settled
+        })
+    }
+
+    pub(crate) fn record_started(&mut self, name: &str, occurrence: usize) {
+        self.earlier.extend(self.open.take());
+        self.open = Some(EarlierRecord {
+            name: name.to_string(),
+            occurrence,
+            delivered: None,
+        });
+        self.next_at = self.options.interval.unwrap_or(1);
+    }
+
+    /// The open record ended: delivered with `sequence_len` bases, or skipped.
+    pub(crate) fn record_finished(&mut self, sequence_len: Option<usize>) {
+        if let Some(open) = self.open.as_mut() {
+            open.delivered = sequence_len;
+        }
+    }
+
+    /// Whether to save now that the open record has dispatched `chunks`
+    /// windows; the timer is only read once per new window.
+    pub(crate) fn due(&mut self, chunks: usize) -> bool {
+        if chunks < self.next_at {
+            return false;
+        }
+        if self.options.interval.is_some() || self.saved_at.elapsed() >= self.options.period {
+            return true;
+        }
+        self.next_at = chunks + 1;
+        false
+    }
+
+    /// Moves the families of `hits` whose range `closed` accepts to
+    /// `settled`, see [`SettledFamilies`].
+    pub(crate) fn settle(
+        &self,
+        settled: &mut SettledFamilies,
+        hits: &mut Vec<G4>,
+        closed: impl Fn((usize, usize)) -> bool,
+    ) {
+        settled.settle(hits, self.settling, closed);
+    }
+
+    /// Saves `record` with its `settled` families and the raw `hits` of the
+    /// open ones; only the families not saved before are written. The
+    /// sidecar is appended first and the checkpoint replaced atomically
+    /// after, so a crash at any point leaves the last complete checkpoint
+    /// valid.
+    pub(crate) fn save(
+        &mut self,
+        byte_offset: u64,
+        mut record: RecordState,
+        settled: &SettledFamilies,
+        hits: &[G4],
+    ) -> io::Result<()> {
+        let fsync = self.options.fsync;
+        let previous = if self.hits_index == Some(record.index) {
+            None
+        } else {
+            self.persisted = 0;
+            self.hits_bytes = 0;
+            File::create(self.options.hits_path(record.index))?;
+            self.hits_index.replace(record.index)
+        };
+        let mut encoded = Vec::new();
+        let unsaved = settled
+            .families
+            .iter()
+            .zip(&settled.stats)
+            .skip(self.persisted);
+        for (family, stats) in unsaved {
+            encode_family(family, *stats, &mut encoded);
+        }
+        let hits_path = self.options.hits_path(record.index);
+        let mut file = OpenOptions::new().append(true).open(&hits_path)?;
+        file.write_all(&encoded)?;
+        if fsync {
+            file.sync_all()?;
+        }
+        self.persisted = settled.families.len();
+        self.hits_bytes += encoded.len() as u64;
+        record.families = self.persisted;
+        record.families_bytes = self.hits_bytes;
+        record.distinct = settled.distinct;
+        for g4 in hits {
+            encode_hit(g4, &mut record.open_hits);
+        }
+        self.next_at = record.chunks + self.options.interval.unwrap_or(1);
+        self.saved_at = Instant::now();
+
+        let state = CheckpointState {
+            version: CHECKPOINT_VERSION,
+            parameters: self.parameters.clone(),
+            input_len: self.input_len,
+            byte_offset,
+            earlier: self.earlier.clone(),
+            record,
+        };
+        let json = serde_json::to_vec(&state).map_err(io::Error::other)?;
+        write_atomically(&self.options.path, fsync, |tmp| fs::write(tmp, json))?;
+        if let Some(previous) = previous {
+            remove_if_present(&self.options.hits_path(previous))?;
+        }
+        tracing::debug!(
+            path = ?self.options.path,
+            byte_offset,
+            families = self.persisted,
+            open_hits = hits.len(),
+            "checkpoint saved"
+        );
+        Ok(())
+    }
+
+    /// Removes the checkpoint once the scan it describes has finished.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        remove_if_present(&self.options.path)?;
+        if let Some(index) = self.hits_index {
+            remove_if_present(&self.options.hits_path(index))?;
+        }
+        Ok(())
+    }
+}
+
+/// The scan settings a checkpoint must be resumed with, after the caller's
+/// own `parameters`.
+fn scan_fingerprint(parameters: &str, config: ScanConfig, capture_raw: bool) -> String {
+    format!(
+        "{parameters}\nmin_tetrads={} min_scores={:?} limits={:?} topology={:?} base={:?} \
+         family_policy={:?} raw_hits={capture_raw}",
+        config.min_tetrads(),
+        config.min_scores(),
+        config.limits(),
+        config.topology(),
+        config.target_base(),
+        config.family_policy(),
+    )
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Appends a settled family as its little-endian range, member count, best
+/// score, best member and the number of hits that follow, then those hits.
+fn encode_family(family: &Family, stats: FamilyStats, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(family.range.0 as u64).to_le_bytes());
+    out.extend_from_slice(&(family.range.1 as u64).to_le_bytes());
+    out.extend_from_slice(&(stats.members as u64).to_le_bytes());
+    out.extend_from_slice(&stats.best_score.to_le_bytes());
+    out.extend_from_slice(&(family.best_index as u32).to_le_bytes());
+    out.extend_from_slice(&(family.members.len() as u32).to_le_bytes());
+    for member in &family.members {
+        encode_hit(member, out);
+    }
+}
+
+/// Inverse of [`encode_family`] over a whole sidecar; `None` when the bytes
+/// end inside a family or describe an impossible one. `distinct` is left 0.
+fn decode_families(mut bytes: &[u8]) -> Option<SettledFamilies> {
+    let mut settled = SettledFamilies::default();
+    while !bytes.is_empty() {
+        let (header, rest) = bytes.split_at_checked(FAMILY_HEADER_LEN)?;
+        let long = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap()) as usize;
+        let int = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap()) as usize;
+        let range = (long(0), long(8));
+        let members = long(16);
+        let best_score = i32::from_le_bytes(header[24..28].try_into().unwrap());
+        let (best_index, stored) = (int(28), int(32));
+        if best_index >= stored || stored > members {
+            return None;
+        }
+        let mut family_hits = Vec::with_capacity(stored);
+        bytes = rest;
+        for _ in 0..stored {
+            bytes = decode_hit(bytes, &mut family_hits)?;
+        }
+        settled.raw_hits += members;
+        settled.stats.push(FamilyStats {
+            members,
+            best_score,
+        });
+        settled.families.push(Family {
+            members: family_hits,
+            best_index,
+            range,
+        });
+    }
+    Some(settled)
+}
+
+/// Appends `g4` as its little-endian `start`, `tetrads`, loop lengths and
+/// `score`, then its bases as scanned; the other fields follow from these.
+fn encode_hit(g4: &G4, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(g4.start as u64).to_le_bytes());
+    out.extend_from_slice(&(g4.tetrads as u32).to_le_bytes());
+    for value in [g4.y1, g4.y2, g4.y3, g4.score] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(g4.sequence_slice().bytes());
+}
+
+/// Inverse of [`encode_hit`] over a run of hits; `None` when the bytes end
+/// inside a hit or describe an impossible one.
+fn decode_hits(mut bytes: &[u8]) -> Option<Vec<G4>> {
+    let mut hits = Vec::new();
+    while !bytes.is_empty() {
+        bytes = decode_hit(bytes, &mut hits)?;
+    }
+    Some(hits)
+}
+
+/// Decodes the hit at the front of `bytes` into `hits` and returns the rest.
+fn decode_hit<'a>(bytes: &'a [u8], hits: &mut Vec<G4>) -> Option<&'a [u8]> {
+    let (header, rest) = bytes.split_at_checked(HIT_HEADER_LEN)?;
+    let int = |at: usize| i32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let start = u64::from_le_bytes(header[..8].try_into().unwrap()) as usize;
+    let tetrads = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let loops = [int(12), int(16), int(20)];
+    let score = int(24);
+    if start == 0 || tetrads == 0 || loops.iter().any(|&y| y < 0) {
+        return None;
+    }
+    let length = 4 * tetrads + loops.iter().map(|&y| y as usize).sum::<usize>();
+    let (sequence, rest) = rest.split_at_checked(length)?;
+    hits.push(G4::from_parts(
+        start,
+        tetrads,
+        loops,
+        score,
+        sequence.to_vec(),
+    ));
+    Some(rest)
+}
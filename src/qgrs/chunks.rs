@@ -1,14 +1,66 @@
+#[cfg(test)]
+use std::cell::Cell;
 use std::sync::Arc;
 
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
-use crate::qgrs::data::{QuartetBase, ScanLimits, SequenceData, SequenceTopology};
-use crate::qgrs::search::{G4, RawSearchWindow, find_raw_on_window_bytes, find_raw_with_sequence};
+use crate::qgrs::cancel::{CancellationToken, is_cancelled};
+use crate::qgrs::data::{
+    MinScore, QuartetBase, ScanConfig, ScanLimits, SequenceData, SequenceTopology,
+};
+use crate::qgrs::error::QgrsError;
+use crate::qgrs::gruns::GRun;
+use crate::qgrs::search::{
+    G4, RawSearchWindow, RunCollector, SearchHooks, find_raw_on_window_bytes,
+    find_raw_with_sequence,
+};
 
 const WINDOW_MIN_BP: usize = 32;
 const WINDOW_MAX_BP: usize = 64;
 const WINDOW_PADDING_BP: usize = 27;
+// Adaptive sizing targets a few windows per thread for load balancing. The
+// cap keeps each window's seed list cache-sized: past a few kb the
+// `window_size` benchmark slows down again.
+const WINDOWS_PER_THREAD: usize = 4;
+const ADAPTIVE_WINDOW_MAX_BP: usize = 4096;
+
+/// Linear G4 scan of `sequence` in any case, under the default limits. The
+/// bases are lowercased into one new buffer that the hits then share; use
+/// [`find_owned`] to reuse a `Vec` you no longer need, or
+/// [`find_owned_bytes`] to scan a buffer that is already lowercase and
+/// shared.
+///
+/// ```
+/// use qgrs_rust::qgrs::find_in_str;
+///
+/// let hits = find_in_str("GGGGAGGGGAGGGGAGGGG", 4, 17);
+/// assert_eq!(hits[0].start, 1);
+/// assert_eq!(hits[0].sequence(), "GGGGAGGGGAGGGGAGGGG");
+/// ```
+pub fn find_in_str(sequence: &str, min_tetrads: usize, min_score: i32) -> Vec<G4> {
+    find_in_bytes(sequence.as_bytes(), min_tetrads, min_score)
+}
 
+/// [`find_in_str`] for bytes, e.g. a slice of a FASTA buffer.
+pub fn find_in_bytes(sequence: &[u8], min_tetrads: usize, min_score: i32) -> Vec<G4> {
+    find_owned_bytes(
+        Arc::new(sequence.to_ascii_lowercase()),
+        min_tetrads,
+        min_score,
+    )
+}
+
+/// Like [`find_in_bytes`], but lowercases `sequence` in place and keeps it as
+/// the hits' buffer, so nothing is copied.
+pub fn find_owned(mut sequence: Vec<u8>, min_tetrads: usize, min_score: i32) -> Vec<G4> {
+    sequence.make_ascii_lowercase();
+    find_owned_bytes(Arc::new(sequence), min_tetrads, min_score)
+}
+
+/// Linear G4 scan of a lowercase `sequence` under the default limits. The
+/// hits point into the `Arc` rather than copying it, so one buffer can be
+/// shared with other scans and threads.
 pub fn find_owned_bytes(sequence: Arc<Vec<u8>>, min_tetrads: usize, min_score: i32) -> Vec<G4> {
     find_owned_bytes_with_topology_and_base(
         sequence,
@@ -20,20 +72,24 @@ pub fn find_owned_bytes(sequence: Arc<Vec<u8>>, min_tetrads: usize, min_score: i
     )
 }
 
+/// Linear G4 scan under `limits`, which are checked with
+/// [`ScanLimits::validate`] first so impossible combinations report why
+/// rather than finding nothing.
 pub fn find_owned_bytes_with_limits(
     sequence: Arc<Vec<u8>>,
     min_tetrads: usize,
     min_score: i32,
     limits: ScanLimits,
-) -> Vec<G4> {
-    find_owned_bytes_with_topology_and_base(
+) -> Result<Vec<G4>, QgrsError> {
+    limits.validate(min_tetrads)?;
+    Ok(find_owned_bytes_with_topology_and_base(
         sequence,
         min_tetrads,
         min_score,
         limits,
         SequenceTopology::Linear,
         QuartetBase::G,
-    )
+    ))
 }
 
 pub fn find_owned_bytes_with_topology(
@@ -61,62 +117,200 @@ pub fn find_owned_bytes_with_topology_and_base(
     topology: SequenceTopology,
     target_base: QuartetBase,
 ) -> Vec<G4> {
+    let min_score = MinScore::Fixed(min_score);
     if topology.is_circular() {
-        return find_owned_bytes_circular(sequence, min_tetrads, min_score, limits, target_base);
+        return find_owned_bytes_circular(
+            sequence,
+            min_tetrads,
+            min_score,
+            limits,
+            target_base,
+            SearchHooks::default(),
+        );
+    }
+    find_owned_bytes_linear(
+        sequence,
+        min_tetrads,
+        min_score,
+        limits,
+        target_base,
+        SearchHooks::default(),
+    )
+}
+
+/// Raw hits of a lowercase `sequence` under `config`: its topology, target
+/// base and limits, and a [`MinScore::Auto`] threshold, which the
+/// `min_score: i32` functions above cannot express. Limits are not
+/// validated here; see [`ScanConfig::check_satisfiable`].
+pub fn find_owned_bytes_with_config(sequence: Arc<Vec<u8>>, config: ScanConfig) -> Vec<G4> {
+    find_owned_bytes_for_config(sequence, config, SearchHooks::default())
+}
+
+/// Scans under every setting of `config`, reporting to whichever `hooks` are
+/// given. Limits are not validated here.
+pub(crate) fn find_owned_bytes_for_config(
+    sequence: Arc<Vec<u8>>,
+    config: ScanConfig,
+    hooks: SearchHooks<'_>,
+) -> Vec<G4> {
+    if config.scans_whole(sequence.len()) {
+        let seq = Arc::new(SequenceData::from_bytes(sequence));
+        return find_with_sequence_and_base(
+            seq,
+            config.min_tetrads(),
+            config.min_score(),
+            config.limits(),
+            config.target_base(),
+            hooks,
+        );
     }
-    find_owned_bytes_linear(sequence, min_tetrads, min_score, limits, target_base)
+    let find = if config.topology().is_circular() {
+        find_owned_bytes_circular
+    } else {
+        find_owned_bytes_linear
+    };
+    find(
+        sequence,
+        config.min_tetrads(),
+        config.min_score(),
+        config.limits(),
+        config.target_base(),
+        hooks,
+    )
 }
 
-fn find_owned_bytes_linear(
+/// Like [`find_owned_bytes_with_topology_and_base`], but gives up with
+/// [`QgrsError::Cancelled`] soon after `cancel` is set: chunk workers skip
+/// windows that have not started and running searches stop between
+/// candidates. Limits are checked with [`ScanLimits::validate`] first.
+pub fn find_owned_bytes_with_cancellation(
     sequence: Arc<Vec<u8>>,
     min_tetrads: usize,
     min_score: i32,
     limits: ScanLimits,
+    topology: SequenceTopology,
     target_base: QuartetBase,
+    cancel: &CancellationToken,
+) -> Result<Vec<G4>, QgrsError> {
+    limits.validate(min_tetrads)?;
+    let min_score = MinScore::Fixed(min_score);
+    let hits = if topology.is_circular() {
+        find_owned_bytes_circular(
+            sequence,
+            min_tetrads,
+            min_score,
+            limits,
+            target_base,
+            SearchHooks::cancellable(Some(cancel)),
+        )
+    } else {
+        find_owned_bytes_linear(
+            sequence,
+            min_tetrads,
+            min_score,
+            limits,
+            target_base,
+            SearchHooks::cancellable(Some(cancel)),
+        )
+    };
+    if cancel.is_cancelled() {
+        return Err(QgrsError::Cancelled);
+    }
+    Ok(hits)
+}
+
+fn find_owned_bytes_linear(
+    sequence: Arc<Vec<u8>>,
+    min_tetrads: usize,
+    min_score: MinScore,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
-    let chunk_size = chunk_size_for_limits(limits);
+    let chunk_size = window_size_for(sequence.len(), limits);
     if sequence.len() > chunk_size {
         let len = sequence.len();
         let overlap = compute_chunk_overlap(min_tetrads, limits);
+        #[cfg(test)]
+        let overlap = OVERLAP_OVERRIDE.with(Cell::get).unwrap_or(overlap);
         let mut start = 0usize;
         let seq_data = Arc::new(SequenceData::from_bytes(sequence.clone()));
+        #[cfg(feature = "tracing")]
+        let mut empty_windows = 0usize;
         let windows: Vec<(usize, usize, usize)> = {
             let mut v = Vec::new();
             while start < len {
                 let primary_end = (start + chunk_size).min(len);
                 let window_end = (primary_end + overlap).min(len);
-                v.push((start, primary_end, window_end));
+                // Hits start on a target base in the primary section; windows
+                // without one are not dispatched at all.
+                if target_base.occurs_in(&sequence[start..primary_end]) {
+                    v.push((start, primary_end, window_end));
+                } else {
+                    #[cfg(feature = "tracing")]
+                    {
+                        empty_windows += 1;
+                    }
+                }
                 start = primary_end;
             }
             v
         };
+        #[cfg(feature = "tracing")]
+        let window_count = windows.len();
+        let scan_window = |(index, (offset, primary_end, window_end))| {
+            if is_cancelled(hooks.cancel) || hooks.cap.is_some_and(|cap| cap.reached(offset)) {
+                return Vec::new().into_iter();
+            }
+            let hits = find_raw_on_window_bytes(
+                seq_data.clone(),
+                RawSearchWindow::new(offset, primary_end, window_end),
+                min_tetrads,
+                min_score,
+                limits,
+                target_base,
+                hooks,
+            );
+            if let Some(cap) = hooks.cap {
+                cap.finish_window(index, primary_end, &hits);
+            }
+            hits.into_iter()
+        };
+        #[cfg(feature = "parallel")]
         let merged_raw: Vec<G4> = windows
             .into_par_iter()
-            .flat_map_iter(|(offset, primary_end, window_end)| {
-                let hits = find_raw_on_window_bytes(
-                    seq_data.clone(),
-                    RawSearchWindow::new(offset, primary_end, window_end),
-                    min_tetrads,
-                    min_score,
-                    limits,
-                    target_base,
-                );
-                hits.into_iter()
-            })
+            .enumerate()
+            .flat_map_iter(scan_window)
             .collect();
+        // Without `parallel` the windows are scanned in order on this thread.
+        #[cfg(not(feature = "parallel"))]
+        let merged_raw: Vec<G4> = windows
+            .into_iter()
+            .enumerate()
+            .flat_map(scan_window)
+            .collect();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            sequence_len = len,
+            windows = window_count,
+            empty_windows,
+            raw_hits = merged_raw.len(),
+            "chunked scan finished"
+        );
 
         return merged_raw;
     }
     let seq = Arc::new(SequenceData::from_bytes(sequence));
-    find_with_sequence_and_base(seq, min_tetrads, min_score, limits, target_base)
+    find_with_sequence_and_base(seq, min_tetrads, min_score, limits, target_base, hooks)
 }
 
 fn find_owned_bytes_circular(
     sequence: Arc<Vec<u8>>,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
     let sequence_len = sequence.len();
     if sequence_len == 0 {
@@ -128,14 +322,37 @@ fn find_owned_bytes_circular(
     if prefix_len > 0 {
         extended.extend_from_slice(&sequence[..prefix_len]);
     }
+    let wrapped_runs = hooks.runs.map(|_| RunCollector::default());
     let mut hits = find_owned_bytes_linear(
         Arc::new(extended),
         min_tetrads,
         min_score,
         limits,
         target_base,
+        // Families may join across the origin, so no window is skippable.
+        SearchHooks {
+            runs: wrapped_runs.as_ref(),
+            cap: None,
+            ..hooks
+        },
     );
     retain_circular_raw_hits(&mut hits, sequence_len);
+    if let (Some(collector), Some(wrapped_runs)) = (hooks.runs, wrapped_runs) {
+        // A run across the origin was measured whole from its start near the
+        // end; its head at 0 and the copied prefix repeat it.
+        let wraps = target_base.matches(sequence[sequence_len - 1]);
+        let runs = wrapped_runs
+            .into_sorted()
+            .into_iter()
+            .filter(|run| run.start < sequence_len)
+            .filter(|run| !(run.start == 0 && wraps && run.len < sequence_len))
+            .map(|run| GRun {
+                len: run.len.min(sequence_len),
+                ..run
+            })
+            .collect();
+        collector.extend(runs);
+    }
     hits
 }
 
@@ -144,7 +361,7 @@ fn circular_prefix_len(sequence_len: usize, limits: ScanLimits) -> usize {
         return 0;
     }
     limits
-        .max_g4_length
+        .max_motif_length()
         .saturating_sub(1)
         .min(sequence_len.saturating_sub(1))
 }
@@ -155,18 +372,62 @@ pub(crate) fn retain_circular_raw_hits(raw_hits: &mut Vec<G4>, sequence_len: usi
         return;
     }
     raw_hits.retain(|g4| g4.start <= sequence_len && g4.length <= sequence_len);
-    raw_hits.sort_by_key(|a| (a.start, a.end));
+    raw_hits.sort_unstable_by_key(G4::key);
 }
 
 pub(crate) fn chunk_size_for_limits(limits: ScanLimits) -> usize {
+    if let Some(chunk_size) = limits.chunk_size {
+        return chunk_size.max(1);
+    }
     let desired = limits.max_g4_length.saturating_add(WINDOW_PADDING_BP);
     desired.clamp(WINDOW_MIN_BP, WINDOW_MAX_BP)
 }
 
+/// Primary window length for scanning `sequence_len` bases at once: the
+/// minimum from [`chunk_size_for_limits`], grown so a short sequence becomes
+/// about `WINDOWS_PER_THREAD` windows per rayon thread instead of hundreds of
+/// tiny tasks. An explicit `ScanLimits::chunk_size` is used as-is.
+pub(crate) fn window_size_for(sequence_len: usize, limits: ScanLimits) -> usize {
+    let minimum = chunk_size_for_limits(limits);
+    if limits.chunk_size.is_some() {
+        return minimum;
+    }
+    let windows = scan_threads() * WINDOWS_PER_THREAD;
+    sequence_len
+        .div_ceil(windows)
+        .min(ADAPTIVE_WINDOW_MAX_BP)
+        .max(minimum)
+}
+
+/// Threads a record's windows are spread over: the rayon pool, or only the
+/// calling thread without the `parallel` feature.
+fn scan_threads() -> usize {
+    #[cfg(feature = "parallel")]
+    return rayon::current_num_threads().max(1);
+    #[cfg(not(feature = "parallel"))]
+    return 1;
+}
+
+#[cfg(test)]
+thread_local! {
+    static OVERLAP_OVERRIDE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Runs `scan` with chunked windows on this thread overlapping by only
+/// `overlap` bases, so candidates run into window ends.
+#[cfg(all(test, feature = "mmap", feature = "export"))]
+pub(crate) fn with_chunk_overlap<T>(overlap: usize, scan: impl FnOnce() -> T) -> T {
+    let previous = OVERLAP_OVERRIDE.with(|cell| cell.replace(Some(overlap)));
+    let result = scan();
+    OVERLAP_OVERRIDE.with(|cell| cell.set(previous));
+    result
+}
+
 pub(crate) fn compute_chunk_overlap(_min_tetrads: usize, limits: ScanLimits) -> usize {
-    limits.max_g4_length.max(1)
+    limits.max_motif_length().max(1)
 }
 
+#[cfg(feature = "mmap")]
 pub(crate) fn shift_g4(g4: &mut G4, offset: usize) {
     g4.start += offset;
     g4.end += offset;
@@ -176,22 +437,30 @@ pub(crate) fn shift_g4(g4: &mut G4, offset: usize) {
     g4.tetrad4 += offset;
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mmap", feature = "export"))]
 pub(crate) fn find_with_sequence(
     seq: Arc<SequenceData>,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
 ) -> Vec<G4> {
-    find_with_sequence_and_base(seq, min_tetrads, min_score, limits, QuartetBase::G)
+    find_with_sequence_and_base(
+        seq,
+        min_tetrads,
+        min_score,
+        limits,
+        QuartetBase::G,
+        SearchHooks::default(),
+    )
 }
 
 pub(crate) fn find_with_sequence_and_base(
     seq: Arc<SequenceData>,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
-    find_raw_with_sequence(seq, min_tetrads, min_score, limits, target_base)
+    find_raw_with_sequence(seq, min_tetrads, min_score, limits, target_base, hooks)
 }
@@ -1,15 +1,163 @@
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 
 use crate::qgrs::data::SequenceTopology;
-use crate::qgrs::search::G4;
+use crate::qgrs::search::{G4, intervals_overlap};
+
+/// Rule for picking the reported member of an overlap family. Every policy
+/// falls back to the earliest `start`; family ranges do not depend on it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FamilyPolicy {
+    /// Highest score, then shortest length (QGRS Mapper behaviour).
+    #[default]
+    Score,
+    /// Longest motif.
+    Length,
+    /// Most tetrads.
+    Tetrads,
+    /// Earliest start.
+    First,
+}
+
+impl FamilyPolicy {
+    pub const fn cli_name(self) -> &'static str {
+        match self {
+            Self::Score => "score",
+            Self::Length => "length",
+            Self::Tetrads => "tetrads",
+            Self::First => "first",
+        }
+    }
+
+    fn prefers(self, current: &G4, candidate: &G4) -> bool {
+        self.prefers_rank(FamilyRank::from(current), FamilyRank::from(candidate))
+    }
+
+    /// Whether `candidate` should replace `current` as a family's reported
+    /// hit.
+    pub(crate) fn prefers_rank(self, current: FamilyRank, candidate: FamilyRank) -> bool {
+        let ordering = match self {
+            Self::Score => candidate
+                .score
+                .cmp(&current.score)
+                .then(current.length.cmp(&candidate.length)),
+            Self::Length => candidate.length.cmp(&current.length),
+            Self::Tetrads => candidate.tetrads.cmp(&current.tetrads),
+            Self::First => Ordering::Equal,
+        };
+        ordering.then(current.start.cmp(&candidate.start)) == Ordering::Greater
+    }
+}
+
+/// The fields of a hit that a [`FamilyPolicy`] compares.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FamilyRank {
+    pub(crate) score: i32,
+    pub(crate) length: usize,
+    pub(crate) tetrads: usize,
+    pub(crate) start: usize,
+}
+
+impl From<&G4> for FamilyRank {
+    fn from(g4: &G4) -> Self {
+        Self {
+            score: g4.score,
+            length: g4.length,
+            tetrads: g4.tetrads,
+            start: g4.start,
+        }
+    }
+}
+
+/// A group of overlapping raw hits.
+///
+/// Hits are visited in `(start, end)` order and a hit joins the current
+/// family when its `start` is at or before the largest `end` seen so far in
+/// that family (coordinates are 1-based inclusive, so sharing a single base
+/// is an overlap). `range` spans the whole family and `best_index` points at
+/// the member chosen by the requested [`FamilyPolicy`].
+#[derive(Clone, Debug)]
+pub struct Family {
+    pub members: Vec<G4>,
+    pub best_index: usize,
+    pub range: (usize, usize),
+}
 
-fn is_better_candidate(current: &G4, candidate: &G4) -> bool {
-    candidate.score > current.score
-        || (candidate.score == current.score && candidate.length < current.length)
+/// What a family's single reported hit leaves out: how many raw hits it
+/// merged and the best score among them, which under a policy other than
+/// [`FamilyPolicy::Score`] can beat the reported hit's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FamilyStats {
+    pub members: usize,
+    pub best_score: i32,
 }
 
+impl Family {
+    pub fn best(&self) -> &G4 {
+        &self.members[self.best_index]
+    }
+
+    pub fn stats(&self) -> FamilyStats {
+        FamilyStats {
+            members: self.members.len(),
+            best_score: self
+                .members
+                .iter()
+                .map(|member| member.score)
+                .max()
+                .expect("a family has at least one member"),
+        }
+    }
+
+    pub fn into_best(mut self) -> G4 {
+        self.members.swap_remove(self.best_index)
+    }
+
+    fn from_members(members: Vec<G4>, range: (usize, usize), policy: FamilyPolicy) -> Self {
+        let mut best_index = 0;
+        for (index, candidate) in members.iter().enumerate().skip(1) {
+            if policy.prefers(&members[best_index], candidate) {
+                best_index = index;
+            }
+        }
+        Self {
+            members,
+            best_index,
+            range,
+        }
+    }
+}
+
+/// Groups overlapping raw hits of a linear sequence into families in start
+/// order, each with the member `policy` picks as its best.
+pub fn consolidate_with_families(raw_g4s: Vec<G4>, policy: FamilyPolicy) -> Vec<Family> {
+    families_linear(raw_g4s, policy)
+}
+
+/// Circular sequences also join families across the origin; wrap-around
+/// ranges keep expanded coordinates (`end` may exceed `sequence_len`).
+pub fn consolidate_with_families_with_topology(
+    raw_g4s: Vec<G4>,
+    topology: SequenceTopology,
+    sequence_len: usize,
+    policy: FamilyPolicy,
+) -> Vec<Family> {
+    #[cfg(feature = "tracing")]
+    let raw_hits = raw_g4s.len();
+    let families = if topology.is_circular() {
+        families_circular(raw_g4s, sequence_len, policy)
+    } else {
+        families_linear(raw_g4s, policy)
+    };
+    #[cfg(feature = "tracing")]
+    tracing::debug!(raw_hits, families = families.len(), "consolidated");
+    families
+}
+
+/// One hit per overlap family (the highest score) and each family's 1-based
+/// inclusive range, for a linear sequence.
 pub fn consolidate_g4s(raw_g4s: Vec<G4>) -> (Vec<G4>, Vec<(usize, usize)>) {
-    consolidate_linear(raw_g4s)
+    winners(families_linear(raw_g4s, FamilyPolicy::Score))
 }
 
 pub fn consolidate_g4s_with_topology(
@@ -17,17 +165,43 @@ pub fn consolidate_g4s_with_topology(
     topology: SequenceTopology,
     sequence_len: usize,
 ) -> (Vec<G4>, Vec<(usize, usize)>) {
-    if topology.is_circular() {
-        return consolidate_circular(raw_g4s, sequence_len);
-    }
-    consolidate_linear(raw_g4s)
+    consolidate_g4s_with_policy(raw_g4s, topology, sequence_len, FamilyPolicy::Score)
+}
+
+pub fn consolidate_g4s_with_policy(
+    raw_g4s: Vec<G4>,
+    topology: SequenceTopology,
+    sequence_len: usize,
+    policy: FamilyPolicy,
+) -> (Vec<G4>, Vec<(usize, usize)>) {
+    winners(consolidate_with_families_with_topology(
+        raw_g4s,
+        topology,
+        sequence_len,
+        policy,
+    ))
 }
 
-fn consolidate_linear(raw_g4s: Vec<G4>) -> (Vec<G4>, Vec<(usize, usize)>) {
-    if raw_g4s.is_empty() {
-        return (Vec::new(), Vec::new());
+pub(crate) fn winners(families: Vec<Family>) -> (Vec<G4>, Vec<(usize, usize)>) {
+    let mut consolidated = Vec::with_capacity(families.len());
+    let mut family_ranges = Vec::with_capacity(families.len());
+    for family in families {
+        family_ranges.push(family.range);
+        consolidated.push(family.into_best());
     }
+    (consolidated, family_ranges)
+}
+
+/// Hits left once exact repeats (same coordinates, tetrads and loops) are
+/// counted once; `ScanMetrics::deduped` for consolidation's input.
+pub(crate) fn count_distinct<'a, I>(hits: I) -> usize
+where
+    I: IntoIterator<Item = &'a G4>,
+{
+    hits.into_iter().collect::<HashSet<_>>().len()
+}
 
+fn families_linear(raw_g4s: Vec<G4>, policy: FamilyPolicy) -> Vec<Family> {
     debug_assert!(
         raw_g4s
             .windows(2)
@@ -35,36 +209,78 @@ fn consolidate_linear(raw_g4s: Vec<G4>) -> (Vec<G4>, Vec<(usize, usize)>) {
         "consolidate_g4s expects raw hits sorted by start"
     );
 
-    let mut consolidated = Vec::with_capacity(raw_g4s.len());
-    let mut family_ranges: Vec<(usize, usize)> = Vec::new();
-    let mut iter = raw_g4s.into_iter();
-    let mut current_best = iter.next().expect("iterator is non-empty");
-    let mut family_start = current_best.start;
-    let mut family_end = current_best.end;
-
-    for candidate in iter {
-        if candidate.start <= family_end {
-            family_end = family_end.max(candidate.end);
-            if is_better_candidate(&current_best, &candidate) {
-                current_best = candidate;
-            }
+    let mut builder = FamilyBuilder::new(policy);
+    let mut families: Vec<Family> = raw_g4s
+        .into_iter()
+        .filter_map(|candidate| builder.push(candidate))
+        .collect();
+    families.extend(builder.finish());
+    families
+}
+
+/// Groups hits fed in `(start, end)` order into linear families, handing each
+/// family back as soon as it can no longer grow.
+pub(crate) struct FamilyBuilder {
+    policy: FamilyPolicy,
+    members: Vec<G4>,
+    family_start: usize,
+    family_end: usize,
+}
+
+impl FamilyBuilder {
+    pub(crate) fn new(policy: FamilyPolicy) -> Self {
+        Self {
+            policy,
+            members: Vec::new(),
+            family_start: 0,
+            family_end: 0,
+        }
+    }
+
+    /// Adds the next hit and returns the previous family if `candidate`
+    /// starts past its end.
+    pub(crate) fn push(&mut self, candidate: G4) -> Option<Family> {
+        let mut finished = None;
+        if !self.members.is_empty()
+            && intervals_overlap(candidate.interval(), (self.family_start, self.family_end))
+        {
+            self.family_end = self.family_end.max(candidate.end);
         } else {
-            family_ranges.push((family_start, family_end));
-            consolidated.push(current_best);
-            current_best = candidate;
-            family_start = current_best.start;
-            family_end = current_best.end;
+            finished = self.finish();
+            self.family_start = candidate.start;
+            self.family_end = candidate.end;
         }
+        self.members.push(candidate);
+        finished
     }
 
-    family_ranges.push((family_start, family_end));
-    consolidated.push(current_best);
-    (consolidated, family_ranges)
+    /// Closes the open family when no hit starting at `next_start` or later
+    /// can join it.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn close_before(&mut self, next_start: usize) -> Option<Family> {
+        if self.family_end < next_start {
+            self.finish()
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn finish(&mut self) -> Option<Family> {
+        if self.members.is_empty() {
+            return None;
+        }
+        let members = std::mem::take(&mut self.members);
+        Some(Family::from_members(
+            members,
+            (self.family_start, self.family_end),
+            self.policy,
+        ))
+    }
 }
 
-fn consolidate_circular(raw_g4s: Vec<G4>, sequence_len: usize) -> (Vec<G4>, Vec<(usize, usize)>) {
+fn families_circular(raw_g4s: Vec<G4>, sequence_len: usize, policy: FamilyPolicy) -> Vec<Family> {
     if raw_g4s.is_empty() || sequence_len == 0 {
-        return (Vec::new(), Vec::new());
+        return Vec::new();
     }
     debug_assert!(
         raw_g4s.iter().all(|g4| g4.start <= sequence_len),
@@ -108,26 +324,31 @@ fn consolidate_circular(raw_g4s: Vec<G4>, sequence_len: usize) -> (Vec<G4>, Vec<
         members_by_root.entry(root).or_default().push(index);
     }
 
-    let mut grouped: Vec<(usize, usize, G4)> = Vec::with_capacity(members_by_root.len());
-    for members in members_by_root.values() {
-        let mut best_index = members[0];
-        for &candidate_index in members.iter().skip(1) {
-            if is_better_candidate(&raw_g4s[best_index], &raw_g4s[candidate_index]) {
-                best_index = candidate_index;
-            }
-        }
-        let family_range = circular_family_range(&raw_g4s, members, sequence_len);
-        grouped.push((family_range.0, family_range.1, raw_g4s[best_index].clone()));
-    }
-
-    grouped.sort_by_key(|(start, end, best)| (*start, *end, best.start, best.end));
-    let mut consolidated = Vec::with_capacity(grouped.len());
-    let mut family_ranges = Vec::with_capacity(grouped.len());
-    for (start, end, best) in grouped {
-        family_ranges.push((start, end));
-        consolidated.push(best);
-    }
-    (consolidated, family_ranges)
+    // Members are moved out of the raw list by index, so no hit is cloned
+    // (cloning would also drop its cached sequence string).
+    let mut grouped: Vec<((usize, usize), Vec<usize>)> = members_by_root
+        .into_values()
+        .map(|members| {
+            let range = circular_family_range(&raw_g4s, &members, sequence_len);
+            (range, members)
+        })
+        .collect();
+    let mut slots: Vec<Option<G4>> = raw_g4s.into_iter().map(Some).collect();
+    let mut families: Vec<Family> = grouped
+        .drain(..)
+        .map(|(range, indices)| {
+            let members = indices
+                .into_iter()
+                .map(|index| slots[index].take().expect("each hit belongs to one family"))
+                .collect();
+            Family::from_members(members, range, policy)
+        })
+        .collect();
+    families.sort_by_key(|family| {
+        let best = family.best();
+        (family.range.0, family.range.1, best.start, best.end)
+    });
+    families
 }
 
 fn circular_family_range(raw_g4s: &[G4], members: &[usize], sequence_len: usize) -> (usize, usize) {
@@ -235,13 +456,123 @@ impl DisjointSet {
 mod tests {
     use std::sync::Arc;
 
-    use super::{circular_family_range, consolidate_circular};
-    use crate::qgrs::{ScanLimits, SequenceTopology, find_owned_bytes_with_topology};
+    use super::{Family, FamilyPolicy, circular_family_range, consolidate_with_families};
+    use crate::qgrs::{
+        ScanLimits, SequenceTopology, consolidate_g4s, consolidate_g4s_with_topology, find_in_str,
+        find_owned_bytes_with_topology,
+    };
 
     fn arc_from_sequence(seq: &str) -> Arc<Vec<u8>> {
         Arc::new(seq.bytes().map(|b| b.to_ascii_lowercase()).collect())
     }
 
+    #[test]
+    fn duplicate_coordinates_keep_highest_score_in_either_order() {
+        let raw = find_in_str("GGGGAGGGGAGGGGAGGGG", 4, 17);
+        assert_eq!(raw.len(), 1);
+        let weaker = raw[0].clone();
+        let mut stronger = raw[0].clone();
+        stronger.score += 5;
+
+        for batch in [
+            vec![weaker.clone(), stronger.clone()],
+            vec![stronger.clone(), weaker.clone()],
+        ] {
+            let (hits, ranges) = consolidate_g4s(batch);
+            assert_eq!(hits, [stronger.clone()]);
+            assert_eq!(ranges, [(weaker.start, weaker.end)]);
+        }
+    }
+
+    #[test]
+    fn families_expose_every_member_and_the_reported_best() {
+        let raw = find_in_str("GGGGAGGGGAGGGGAGGGG", 4, 17);
+        assert_eq!(raw.len(), 1);
+        let base = &raw[0];
+        let mut first = base.clone();
+        first.score -= 3;
+        let mut middle = base.clone();
+        middle.start += 2;
+        middle.end += 2;
+        middle.score += 4;
+        let mut last = base.clone();
+        last.start += 4;
+        last.end += 4;
+
+        let families = consolidate_with_families(vec![first, middle, last], FamilyPolicy::Score);
+        assert_eq!(families.len(), 1);
+        let family: &Family = &families[0];
+        assert_eq!(family.members.len(), 3);
+        assert_eq!(family.best_index, 1);
+        assert_eq!(family.range, (base.start, base.end + 4));
+        assert_eq!(family.best().score, base.score + 4);
+
+        let (hits, ranges) = consolidate_g4s(families[0].members.clone());
+        assert_eq!(ranges, [family.range]);
+        assert_eq!(families.into_iter().next().unwrap().into_best(), hits[0]);
+    }
+
+    #[test]
+    fn each_family_policy_picks_a_different_winner() {
+        let raw = find_in_str("GGGGAGGGGAGGGGAGGGG", 4, 17);
+        let base = &raw[0];
+        let shifted = |offset: usize, length: usize, tetrads: usize, score: i32| {
+            let mut g4 = base.clone();
+            g4.start += offset;
+            g4.end = g4.start + length - 1;
+            g4.length = length;
+            g4.tetrads = tetrads;
+            g4.score = score;
+            g4
+        };
+        let members = vec![
+            shifted(0, 20, 3, 30),
+            shifted(1, 40, 3, 10),
+            shifted(2, 15, 3, 60),
+            shifted(3, 25, 4, 20),
+        ];
+
+        let mut ranges = Vec::new();
+        for (policy, expected) in [
+            (FamilyPolicy::First, 0),
+            (FamilyPolicy::Length, 1),
+            (FamilyPolicy::Score, 2),
+            (FamilyPolicy::Tetrads, 3),
+        ] {
+            let families = consolidate_with_families(members.clone(), policy);
+            assert_eq!(families.len(), 1);
+            assert_eq!(families[0].best_index, expected, "{policy:?}");
+            ranges.push(families[0].range);
+        }
+        assert!(ranges.iter().all(|range| *range == ranges[0]));
+    }
+
+    #[test]
+    fn winners_are_moved_not_cloned() {
+        let sequence = "GGGGAGGGGAGGGGAGGGGTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTGGGTTGGGTTGGGTTGGGTTTTTTTTTTTTTTTTTTTTTTTTTTTTTT";
+        for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+            let raw = find_owned_bytes_with_topology(
+                arc_from_sequence(sequence),
+                2,
+                17,
+                ScanLimits::default(),
+                topology,
+            );
+            assert!(raw.len() > 2);
+            for g4 in &raw {
+                g4.sequence();
+            }
+            let (hits, ranges) = consolidate_g4s_with_topology(raw, topology, sequence.len());
+            assert_eq!(hits.len(), 2);
+            assert_eq!(ranges.len(), 2);
+            // `G4::clone` resets the sequence cache, so a cached winner was moved.
+            assert!(
+                hits.iter().all(|g4| g4.sequence_is_cached()),
+                "{topology:?}"
+            );
+        }
+    }
+
     #[test]
     fn circular_family_ranges_have_at_most_one_wraparound_interval() {
         let sequence_len = 20;
@@ -253,7 +584,8 @@ mod tests {
             SequenceTopology::Circular,
         );
 
-        let (_hits, family_ranges) = consolidate_circular(raw_g4s, sequence_len);
+        let (_hits, family_ranges) =
+            consolidate_g4s_with_topology(raw_g4s, SequenceTopology::Circular, sequence_len);
         let wraparound_count = family_ranges
             .iter()
             .filter(|(_, end)| *end > sequence_len)
@@ -308,7 +640,11 @@ mod tests {
             SequenceTopology::Circular,
         );
 
-        let (hits, family_ranges) = consolidate_circular(raw_g4s.clone(), sequence.len());
+        let (hits, family_ranges) = consolidate_g4s_with_topology(
+            raw_g4s.clone(),
+            SequenceTopology::Circular,
+            sequence.len(),
+        );
 
         eprintln!(
             "sequence_len={} raw_hits={} consolidated_hits={} family_ranges={:?}",
@@ -1,12 +1,24 @@
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 
+use memchr::memchr2;
+
+use crate::qgrs::consolidation::FamilyPolicy;
+use crate::qgrs::error::QgrsError;
+use crate::qgrs::record::CoordinateSystem;
+use crate::qgrs::search::{max_possible_score, maximum_length};
+
+/// How FASTA files are read: memory-mapped whole, or streamed record by
+/// record with bounded memory.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InputMode {
     Mmap,
     Stream,
 }
 
+/// Whether a record's end joins its start, as in plasmids and mitochondria.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SequenceTopology {
     Linear,
@@ -19,6 +31,26 @@ impl SequenceTopology {
     }
 }
 
+/// Order in which stream scans hand finished records to their callback, see
+/// [`ScanConfig::with_delivery_order`]. Records are scanned one after
+/// another today, so both orders deliver file order; the choice matters once
+/// records finish out of order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeliveryOrder {
+    /// Each record after every earlier one. Records finished early wait in a
+    /// buffer of at most [`ScanConfig::max_buffered_records`], and no record
+    /// further ahead is started until the buffer drains.
+    #[default]
+    FileOrder,
+    /// Each record as soon as it finishes; callers can restore file order
+    /// from [`SearchResults::index`](crate::qgrs::SearchResults::index).
+    Completion,
+}
+
+/// Default [`ScanConfig::with_max_buffered_records`].
+pub const DEFAULT_MAX_BUFFERED_RECORDS: usize = 64;
+
+/// Base the tetrads are made of: `G` for G4s, `C` for i-motifs.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum QuartetBase {
     #[default]
@@ -48,16 +80,74 @@ impl QuartetBase {
         }
     }
 
+    /// The base pairing with this one, i.e. the tetrad base of the motif's
+    /// reverse complement.
+    pub const fn complement(self) -> Self {
+        match self {
+            Self::G => Self::C,
+            Self::C => Self::G,
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn matches(self, byte: u8) -> bool {
         byte == self.lowercase_byte() || byte == self.uppercase_byte()
     }
+
+    /// Whether `bytes` holds this base in either case, found with `memchr2`.
+    #[inline]
+    pub(crate) fn occurs_in(self, bytes: &[u8]) -> bool {
+        memchr2(self.lowercase_byte(), self.uppercase_byte(), bytes).is_some()
+    }
+}
+
+/// What a loop between two tetrad runs may contain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopPolicy {
+    /// Any base, including the tetrad base (QGRS Mapper behaviour), so a
+    /// loop can hold a run too short to be a tetrad.
+    #[default]
+    Any,
+    /// No tetrad base: only loops free of G (C for i-motifs) are expanded.
+    NoTargetBase,
+}
+
+impl LoopPolicy {
+    pub const fn cli_name(self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::NoTargetBase => "no-target-base",
+        }
+    }
+}
+
+// Every header counts towards the occurrence of its name, including records
+// that are later dropped for being empty, so mmap, stream loaders, and the
+// streaming scanner agree on which duplicate is which.
+#[derive(Default)]
+pub(crate) struct OccurrenceCounter {
+    counts: HashMap<String, usize>,
 }
 
+impl OccurrenceCounter {
+    pub(crate) fn tag(&mut self, name: String) -> (String, usize) {
+        let count = self.counts.entry(name.clone()).or_insert(0);
+        let occurrence = *count;
+        *count += 1;
+        (name, occurrence)
+    }
+}
+
+/// One FASTA record, its bases in the case they were read and shared through
+/// an `Arc`.
 #[derive(Clone, Debug)]
 pub struct ChromSequence {
     pub(crate) name: String,
+    pub(crate) occurrence: usize,
+    pub(crate) description: Option<String>,
     pub(crate) sequence: Arc<Vec<u8>>,
+    pub(crate) comment_lines: usize,
+    pub(crate) index: usize,
 }
 
 impl ChromSequence {
@@ -65,6 +155,29 @@ impl ChromSequence {
         &self.name
     }
 
+    /// Position of the record in its file, from 0. Records left out by a
+    /// name filter still count, so this matches a stream scan of the file.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Number of earlier records in the same file that carry the same name
+    /// (0 for the first `chr1`, 1 for the second, ...).
+    pub fn occurrence(&self) -> usize {
+        self.occurrence
+    }
+
+    /// Header text after the name token, e.g. `Escherichia coli str. K-12`
+    /// for `>NC_000913.3 Escherichia coli str. K-12`.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// `;` comment lines skipped while reading the record.
+    pub fn comment_lines(&self) -> usize {
+        self.comment_lines
+    }
+
     pub fn sequence(&self) -> Arc<Vec<u8>> {
         Arc::clone(&self.sequence)
     }
@@ -74,19 +187,83 @@ impl ChromSequence {
     }
 
     pub fn as_uppercase_string(&self) -> String {
-        let mut seq = unsafe { String::from_utf8_unchecked(self.sequence.as_ref().clone()) };
-        seq.make_ascii_uppercase();
-        seq
+        display_string(&self.sequence, false)
+    }
+}
+
+/// Sequence bytes as text: uppercase, or in the case they were read in with
+/// `preserve_case`. Loaders keep every non-whitespace byte, so a stray
+/// non-ASCII byte (binary junk, a Latin-1 file) is a position like any
+/// other invalid base; it is shown as `N` instead of producing invalid
+/// UTF-8.
+pub(crate) fn display_string(bytes: &[u8], preserve_case: bool) -> String {
+    let mut text = bytes.to_vec();
+    for byte in &mut text {
+        *byte = match *byte {
+            byte if !byte.is_ascii() => b'N',
+            byte if preserve_case => byte,
+            byte => byte.to_ascii_uppercase(),
+        };
     }
+    String::from_utf8(text).expect("bytes were mapped to ASCII")
 }
 
 pub const DEFAULT_MAX_G4_LENGTH: usize = 45;
 pub const DEFAULT_MAX_RUN: usize = 10;
+/// Largest `--chunk-size` the CLI accepts. Stream mode buffers one chunk plus
+/// its overlap per in-flight window, so this keeps memory bounded.
+pub const MAX_CHUNK_SIZE: usize = 1 << 24;
+/// Longest record [`ScanConfig::with_whole_records`] scans in one piece.
+/// Past this a record is long enough to keep several threads busy on its own
+/// windows.
+pub const WHOLE_RECORD_MAX_LEN: usize = 1 << 16;
+/// Share of the best score reachable at a tetrad count that a hit needs
+/// under [`MinScore::Auto`]. 0.8 keeps the classic threshold of 17 for
+/// 2-tetrad hits under the default limits.
+pub const AUTO_MIN_SCORE_FRACTION: f64 = 0.8;
 
+/// Score threshold of a [`ScanConfig`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinScore {
+    /// Every hit needs this score, whatever its tetrad count.
+    Fixed(i32),
+    /// Each hit needs [`ScanLimits::auto_min_score`] for its tetrad count.
+    Auto,
+}
+
+impl MinScore {
+    /// Score a `tetrads`-tetrad hit must reach under `limits`.
+    pub fn for_tetrads(self, tetrads: usize, limits: ScanLimits) -> i32 {
+        match self {
+            MinScore::Fixed(min_score) => min_score,
+            MinScore::Auto => limits.auto_min_score(tetrads),
+        }
+    }
+}
+
+impl From<i32> for MinScore {
+    fn from(min_score: i32) -> Self {
+        MinScore::Fixed(min_score)
+    }
+}
+
+/// Size limits on a hit and on the runs it is built from. Build them with
+/// [`ScanLimits::new`] or [`Default`] and the `with_*` methods; fields may
+/// be added in minor releases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct ScanLimits {
     pub max_g4_length: usize,
     pub max_run: usize,
+    /// Primary window length of chunked and stream scans. `None` derives it
+    /// from `max_g4_length`; results never depend on it, only speed does.
+    pub chunk_size: Option<usize>,
+    /// Which loops candidate expansion accepts.
+    pub loop_policy: LoopPolicy,
+    /// Bases loops may contain, indexed A, C, G, T (U counts as T); any
+    /// other byte is rejected. `None` allows everything. Zero-length loops
+    /// always pass.
+    pub loop_alphabet: Option<[bool; 4]>,
 }
 
 impl ScanLimits {
@@ -94,8 +271,104 @@ impl ScanLimits {
         Self {
             max_g4_length,
             max_run,
+            chunk_size: None,
+            loop_policy: LoopPolicy::Any,
+            loop_alphabet: None,
         }
     }
+
+    pub const fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub const fn with_loop_policy(mut self, loop_policy: LoopPolicy) -> Self {
+        self.loop_policy = loop_policy;
+        self
+    }
+
+    pub const fn with_loop_alphabet(mut self, loop_alphabet: Option<[bool; 4]>) -> Self {
+        self.loop_alphabet = loop_alphabet;
+        self
+    }
+
+    /// Whether a loop may contain `byte` under [`ScanLimits::loop_policy`]
+    /// and [`ScanLimits::loop_alphabet`]; `target_base` is the tetrad base.
+    pub(crate) fn allows_loop_byte(self, byte: u8, target_base: QuartetBase) -> bool {
+        if self.loop_policy == LoopPolicy::NoTargetBase && target_base.matches(byte) {
+            return false;
+        }
+        let Some(alphabet) = self.loop_alphabet else {
+            return true;
+        };
+        match byte.to_ascii_lowercase() {
+            b'a' => alphabet[0],
+            b'c' => alphabet[1],
+            b'g' => alphabet[2],
+            b't' | b'u' => alphabet[3],
+            _ => false,
+        }
+    }
+
+    /// Whether [`ScanLimits::allows_loop_byte`] can reject anything.
+    pub(crate) fn restricts_loops(self) -> bool {
+        self.loop_policy != LoopPolicy::Any || self.loop_alphabet.is_some()
+    }
+
+    /// Checks that `min_tetrads`-tetrad hits can exist under these limits.
+    /// Scans given limits that fail here would find nothing, so the chunked
+    /// and stream entry points return this error instead.
+    pub fn validate(self, min_tetrads: usize) -> Result<(), QgrsError> {
+        if min_tetrads == 0 {
+            return Err(QgrsError::ZeroTetrads);
+        }
+        if self.max_run < min_tetrads {
+            return Err(QgrsError::MaxRunTooShort {
+                max_run: self.max_run,
+                min_tetrads,
+            });
+        }
+        // One loop may be empty; the other two need at least one base.
+        let required = min_tetrads
+            .checked_mul(4)
+            .and_then(|bases| bases.checked_add(2))
+            .ok_or(QgrsError::TooManyTetrads { min_tetrads })?;
+        let max_length = maximum_length(min_tetrads, self);
+        if max_length < required {
+            return Err(QgrsError::MaxLengthTooShort {
+                max_length,
+                min_tetrads,
+                required,
+            });
+        }
+        Ok(())
+    }
+
+    /// Longest motif any tetrad count allows: `max_g4_length`, capped at the
+    /// 45 bp legacy limit of 3+-tetrad motifs. Chunk overlaps and circular
+    /// wrap-around buffers only need this much.
+    pub fn max_motif_length(self) -> usize {
+        maximum_length(3, self)
+    }
+
+    /// Most tetrads a hit can have: each is one base of a run no longer than
+    /// `max_run`, and four runs must fit in [`ScanLimits::max_motif_length`].
+    pub fn max_tetrads(self) -> usize {
+        self.max_run.min(self.max_motif_length() / 4)
+    }
+
+    /// Best score a `tetrads`-tetrad hit can reach, see
+    /// [`max_possible_score`].
+    pub fn max_score(self, tetrads: usize) -> i64 {
+        max_possible_score(tetrads, self)
+    }
+
+    /// `ceil(AUTO_MIN_SCORE_FRACTION * max_score(tetrads))`: 17, 52, 68 and
+    /// 77 for 2 to 5 tetrads under the default limits. Saturates at the
+    /// `i32` bounds.
+    pub fn auto_min_score(self, tetrads: usize) -> i32 {
+        (AUTO_MIN_SCORE_FRACTION * self.max_score(tetrads) as f64).ceil() as i32
+    }
 }
 
 impl Default for ScanLimits {
@@ -104,13 +377,342 @@ impl Default for ScanLimits {
     }
 }
 
+/// Everything one scan needs: thresholds, limits, topology, target base and
+/// the family policy, plus which optional outputs to collect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanConfig {
+    pub(crate) min_tetrads: usize,
+    pub(crate) min_score: MinScore,
+    pub(crate) limits: ScanLimits,
+    pub(crate) topology: SequenceTopology,
+    pub(crate) target_base: QuartetBase,
+    pub(crate) family_policy: FamilyPolicy,
+    pub(crate) detach_hits: bool,
+    pub(crate) collect_metrics: bool,
+    pub(crate) collect_gruns: bool,
+    pub(crate) keep_raw: bool,
+    pub(crate) whole_records: bool,
+    pub(crate) delivery_order: DeliveryOrder,
+    pub(crate) max_buffered_records: usize,
+    pub(crate) coordinates: CoordinateSystem,
+    pub(crate) max_hits: Option<usize>,
+    pub(crate) preserve_case: bool,
+    pub(crate) window_timeout: Option<Duration>,
+}
+
+impl ScanConfig {
+    /// A scan with the fixed `min_score`; see [`ScanConfig::with_min_score`]
+    /// for [`MinScore::Auto`].
+    pub const fn new(
+        min_tetrads: usize,
+        min_score: i32,
+        limits: ScanLimits,
+        topology: SequenceTopology,
+        target_base: QuartetBase,
+    ) -> Self {
+        Self {
+            min_tetrads,
+            min_score: MinScore::Fixed(min_score),
+            limits,
+            topology,
+            target_base,
+            family_policy: FamilyPolicy::Score,
+            detach_hits: false,
+            collect_metrics: false,
+            collect_gruns: false,
+            keep_raw: false,
+            whole_records: false,
+            delivery_order: DeliveryOrder::FileOrder,
+            max_buffered_records: DEFAULT_MAX_BUFFERED_RECORDS,
+            coordinates: CoordinateSystem::OneBasedInclusive,
+            max_hits: None,
+            preserve_case: false,
+            window_timeout: None,
+        }
+    }
+
+    pub const fn with_min_score(mut self, min_score: MinScore) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    pub const fn with_family_policy(mut self, family_policy: FamilyPolicy) -> Self {
+        self.family_policy = family_policy;
+        self
+    }
+
+    /// Detach every hit from the scanned sequence when results are built,
+    /// see [`G4::detach`](crate::qgrs::G4::detach). Off by default: results
+    /// written out and dropped per chromosome gain nothing from the copy.
+    pub const fn with_detached_hits(mut self, detach_hits: bool) -> Self {
+        self.detach_hits = detach_hits;
+        self
+    }
+
+    /// Count the work done per record into
+    /// [`SearchStats::metrics`](crate::qgrs::SearchStats::metrics). Off by
+    /// default, and the search loop skips its counters while off.
+    pub const fn with_metrics(mut self, collect_metrics: bool) -> Self {
+        self.collect_metrics = collect_metrics;
+        self
+    }
+
+    /// Keep the target-base runs that seeded the search in
+    /// [`SearchResults::gruns`](crate::qgrs::SearchResults::gruns): every
+    /// run of `min_tetrads` to `max_run` bases, found by the same pass as the
+    /// seeds. Only [`search_chromosome`](crate::qgrs::search_chromosome) and
+    /// its cancellable form fill it; stream scans leave it `None`.
+    pub const fn with_gruns(mut self, collect_gruns: bool) -> Self {
+        self.collect_gruns = collect_gruns;
+        self
+    }
+
+    /// Keep the unconsolidated hits in
+    /// [`SearchResults::raw_hits`](crate::qgrs::SearchResults::raw_hits)
+    /// whatever `capture_raw` the search was called with, so the results can
+    /// be passed to [`refilter`](crate::qgrs::refilter).
+    pub const fn with_keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// Scan records of up to [`WHOLE_RECORD_MAX_LEN`] bases in one piece on
+    /// a single rayon worker instead of splitting them into windows, for
+    /// callers that already run many records in parallel, like
+    /// [`scan_many`](crate::qgrs::scan_many). Hits are the same either way.
+    /// Circular records are always windowed, and stream scans ignore it.
+    pub const fn with_whole_records(mut self, whole_records: bool) -> Self {
+        self.whole_records = whole_records;
+        self
+    }
+
+    /// Order in which stream scans deliver records; file order by default.
+    pub const fn with_delivery_order(mut self, delivery_order: DeliveryOrder) -> Self {
+        self.delivery_order = delivery_order;
+        self
+    }
+
+    /// Most finished records a [`DeliveryOrder::FileOrder`] stream scan
+    /// holds back while an earlier record is still running, which bounds
+    /// the memory spent on results nobody has received yet. At least 1.
+    pub const fn with_max_buffered_records(mut self, max_buffered_records: usize) -> Self {
+        self.max_buffered_records = if max_buffered_records == 0 {
+            1
+        } else {
+            max_buffered_records
+        };
+        self
+    }
+
+    /// Coordinates the results are meant to be exported in, for the
+    /// `*_with_coordinates` exporters such as
+    /// [`render_csv_results_with_coordinates`](crate::qgrs::render_csv_results_with_coordinates).
+    /// The search and its results stay 1-based inclusive either way.
+    pub const fn with_coordinates(mut self, coordinates: CoordinateSystem) -> Self {
+        self.coordinates = coordinates;
+        self
+    }
+
+    /// Report at most `max_hits` hits per record, the ones with the lowest
+    /// starts, and set
+    /// [`SearchStats::truncated`](crate::qgrs::SearchStats::truncated) when
+    /// more were found. Raw hits are cut to the kept families. Windowed
+    /// linear scans stop dispatching windows once the kept hits are settled;
+    /// other scans find every hit first. `None`, the default, keeps all.
+    pub const fn with_max_hits(mut self, max_hits: Option<usize>) -> Self {
+        self.max_hits = max_hits;
+        self
+    }
+
+    /// Show each hit's [`G4::sequence`](crate::qgrs::G4::sequence) in the
+    /// case of the scanned bytes, e.g. to keep soft-masked repeats
+    /// lowercase, instead of uppercase. Matching ignores case either way.
+    pub const fn with_preserve_case(mut self, preserve_case: bool) -> Self {
+        self.preserve_case = preserve_case;
+        self
+    }
+
+    /// Fail a stream record with [`QgrsError::WorkerTimedOut`] when no
+    /// window finishes within `timeout` while it waits for one, as a guard against a worker that never reports back. `None`, the
+    /// default, waits as long as the windows take.
+    pub const fn with_window_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.window_timeout = timeout;
+        self
+    }
+
+    pub const fn with_target_base(mut self, target_base: QuartetBase) -> Self {
+        self.target_base = target_base;
+        self
+    }
+
+    /// The same scan on the reverse complement strand: the complementary
+    /// tetrad base, with any loop alphabet complemented too. Hits still come
+    /// out in plus-strand coordinates and orientation.
+    pub const fn opposite_strand(mut self) -> Self {
+        self.target_base = self.target_base.complement();
+        if let Some([a, c, g, t]) = self.limits.loop_alphabet {
+            self.limits.loop_alphabet = Some([t, g, c, a]);
+        }
+        self
+    }
+
+    pub const fn min_tetrads(self) -> usize {
+        self.min_tetrads
+    }
+
+    /// The score threshold; see [`ScanConfig::min_scores`] for what it
+    /// comes to at each tetrad count.
+    pub const fn min_score(self) -> MinScore {
+        self.min_score
+    }
+
+    /// Effective minimum score for each tetrad count the scan can report,
+    /// from `min_tetrads` up to [`ScanLimits::max_tetrads`].
+    pub fn min_scores(self) -> Vec<(usize, i32)> {
+        (self.min_tetrads..=self.limits.max_tetrads())
+            .map(|tetrads| (tetrads, self.min_score.for_tetrads(tetrads, self.limits)))
+            .collect()
+    }
+
+    /// Cheap pre-check that some hit could pass the filters, so a scan that
+    /// can only come back empty fails before it starts. A tetrad count is
+    /// possible when its shortest motif (`4 * tetrads + 2` bp) fits the
+    /// length limit and its [`ScanLimits::max_score`] reaches the threshold.
+    /// Runs [`ScanLimits::validate`] first.
+    pub fn check_satisfiable(self) -> Result<(), QgrsError> {
+        self.limits.validate(self.min_tetrads)?;
+        let closest = self
+            .min_scores()
+            .into_iter()
+            .filter(|&(tetrads, _)| self.fits(tetrads))
+            .map(|(tetrads, min_score)| (self.limits.max_score(tetrads), min_score))
+            .max_by_key(|&(best_score, min_score)| best_score.saturating_sub(i64::from(min_score)));
+        match closest {
+            Some((best_score, min_score)) if best_score < i64::from(min_score) => {
+                Err(QgrsError::Unsatisfiable {
+                    min_score,
+                    best_score,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Best score any hit of this scan can reach: the highest
+    /// [`ScanLimits::max_score`] among the tetrad counts
+    /// [`ScanConfig::check_satisfiable`] considers possible, or `None` when
+    /// no motif fits the length limit.
+    pub fn max_possible_score(self) -> Option<i64> {
+        (self.min_tetrads..=self.limits.max_tetrads())
+            .filter(|&tetrads| self.fits(tetrads))
+            .map(|tetrads| self.limits.max_score(tetrads))
+            .max()
+    }
+
+    /// Whether the shortest `tetrads`-tetrad motif (`4 * tetrads + 2` bp)
+    /// fits the length limit.
+    fn fits(self, tetrads: usize) -> bool {
+        4 * tetrads + 2 <= maximum_length(tetrads, self.limits)
+    }
+
+    /// `17`, or `auto (2=17, 3=52, ...)` listing [`ScanConfig::min_scores`].
+    pub fn min_score_label(self) -> String {
+        if let MinScore::Fixed(min_score) = self.min_score {
+            return min_score.to_string();
+        }
+        let thresholds: Vec<String> = self
+            .min_scores()
+            .iter()
+            .map(|(tetrads, score)| format!("{tetrads}={score}"))
+            .collect();
+        format!("auto ({})", thresholds.join(", "))
+    }
+
+    pub const fn limits(self) -> ScanLimits {
+        self.limits
+    }
+
+    pub const fn topology(self) -> SequenceTopology {
+        self.topology
+    }
+
+    pub const fn target_base(self) -> QuartetBase {
+        self.target_base
+    }
+
+    pub const fn family_policy(self) -> FamilyPolicy {
+        self.family_policy
+    }
+
+    pub const fn detach_hits(self) -> bool {
+        self.detach_hits
+    }
+
+    pub const fn collects_metrics(self) -> bool {
+        self.collect_metrics
+    }
+
+    pub const fn collects_gruns(self) -> bool {
+        self.collect_gruns
+    }
+
+    pub const fn keeps_raw(self) -> bool {
+        self.keep_raw
+    }
+
+    pub const fn scans_whole_records(self) -> bool {
+        self.whole_records
+    }
+
+    pub const fn delivery_order(self) -> DeliveryOrder {
+        self.delivery_order
+    }
+
+    pub const fn max_buffered_records(self) -> usize {
+        self.max_buffered_records
+    }
+
+    pub const fn coordinates(self) -> CoordinateSystem {
+        self.coordinates
+    }
+
+    pub const fn preserves_case(self) -> bool {
+        self.preserve_case
+    }
+
+    pub const fn max_hits(self) -> Option<usize> {
+        self.max_hits
+    }
+
+    pub const fn window_timeout(self) -> Option<Duration> {
+        self.window_timeout
+    }
+
+    /// Whether a linear record of `len` bases is scanned without windows.
+    pub(crate) const fn scans_whole(self, len: usize) -> bool {
+        self.whole_records && !self.topology.is_circular() && len <= WHOLE_RECORD_MAX_LEN
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig::new(
+            2,
+            17,
+            ScanLimits::default(),
+            SequenceTopology::Linear,
+            QuartetBase::G,
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct SequenceData {
     pub(crate) normalized: Arc<Vec<u8>>,
 }
 
 impl SequenceData {
-    #[cfg_attr(not(test), allow(dead_code))]
+    #[cfg(all(test, feature = "mmap", feature = "export"))]
     pub(crate) fn new(sequence: &str) -> Self {
         let normalized = Arc::new(sequence.to_ascii_lowercase().into_bytes());
         Self { normalized }
@@ -142,10 +744,8 @@ impl SequenceSlice {
         &self.normalized[self.start..end]
     }
 
-    pub(crate) fn to_uppercase_string(&self) -> String {
-        let mut sequence = unsafe { String::from_utf8_unchecked(self.bytes().to_vec()) };
-        sequence.make_ascii_uppercase();
-        sequence
+    pub(crate) fn to_display_string(&self, preserve_case: bool) -> String {
+        display_string(self.bytes(), preserve_case)
     }
 }
 
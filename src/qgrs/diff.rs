@@ -0,0 +1,448 @@
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::qgrs::G4Record;
+
+/// How [`diff_results`] pairs hits of the two sides.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffOptions {
+    /// Least overlap fraction, shared bases over the bases of either hit, for
+    /// two hits to be paired. `0.0`, the default, pairs any hits that share a
+    /// base; `1.0` pairs only hits with the same boundaries.
+    pub min_overlap: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { min_overlap: 0.0 }
+    }
+}
+
+/// What differs between two paired hits. Boundaries are checked first, so a
+/// shifted hit whose score also changed is [`HitChange::Boundaries`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitChange {
+    Identical,
+    Boundaries,
+    Score,
+    /// Same boundaries and score, different tetrads, loops or sequence.
+    Structure,
+}
+
+impl HitChange {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Identical => "identical",
+            Self::Boundaries => "boundaries",
+            Self::Score => "score",
+            Self::Structure => "structure",
+        }
+    }
+
+    fn between(a: &G4Record, b: &G4Record) -> Self {
+        if (a.start, a.end) != (b.start, b.end) {
+            Self::Boundaries
+        } else if a.score != b.score {
+            Self::Score
+        } else if (a.tetrads, a.y1, a.y2, a.y3, &a.sequence)
+            != (b.tetrads, b.y1, b.y2, b.y3, &b.sequence)
+        {
+            Self::Structure
+        } else {
+            Self::Identical
+        }
+    }
+}
+
+/// One hit of each side that [`diff_results`] paired, with their overlap
+/// fraction.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HitPair {
+    pub a: G4Record,
+    pub b: G4Record,
+    pub overlap: f64,
+    pub change: HitChange,
+}
+
+/// Counts behind a [`DiffReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct DiffSummary {
+    pub a_hits: usize,
+    pub b_hits: usize,
+    pub identical: usize,
+    pub boundaries: usize,
+    pub score: usize,
+    pub structure: usize,
+    pub only_a: usize,
+    pub only_b: usize,
+    /// Paired hits over the hits of either side, `pairs / (a + b - pairs)`.
+    pub hit_jaccard: f64,
+    /// Bases covered by hits of both sides over bases covered by either.
+    pub base_jaccard: f64,
+}
+
+/// Concordance of two result sets of one record, see [`diff_results`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DiffReport {
+    pub summary: DiffSummary,
+    /// In the order of their `a` hit.
+    pub pairs: Vec<HitPair>,
+    pub only_a: Vec<G4Record>,
+    pub only_b: Vec<G4Record>,
+}
+
+impl DiffReport {
+    /// Every hit is paired with an identical one.
+    pub fn is_identical(&self) -> bool {
+        let s = &self.summary;
+        s.identical == s.a_hits && s.identical == s.b_hits
+    }
+}
+
+/// Compares the hits of one record from two runs, e.g. made with different
+/// limits, by position rather than row by row. Overlapping hits of the two
+/// sides are paired greedily, largest overlap fraction first (ties go to the
+/// earlier hits), and each hit is paired at most once. Hits left over are
+/// unique to their side. Both sides must use the same coordinates; they need
+/// not be sorted.
+///
+/// ```
+/// use qgrs_rust::qgrs::G4Record;
+/// use qgrs_rust::qgrs::diff::{DiffOptions, HitChange, diff_results};
+///
+/// let a = [G4Record::new(10, 28, 4, [1, 1, 1], 63, "GGGGAGGGGAGGGGAGGGG")];
+/// let b = [G4Record::new(12, 28, 3, [2, 2, 2], 42, "GGAGGGGAGGGGAGGGG")];
+/// let report = diff_results(&a, &b, DiffOptions::default());
+/// assert_eq!(report.pairs[0].change, HitChange::Boundaries);
+/// assert_eq!(report.summary.hit_jaccard, 1.0);
+/// ```
+pub fn diff_results(a: &[G4Record], b: &[G4Record], opts: DiffOptions) -> DiffReport {
+    let mut by_start: Vec<usize> = (0..b.len()).collect();
+    by_start.sort_by_key(|&index| (b[index].start, b[index].end));
+    let max_len = b.iter().map(span_len).max().unwrap_or(0);
+
+    let mut candidates = Vec::new();
+    for (ia, hit) in a.iter().enumerate() {
+        // A `b` hit starting before `hit.start + 1 - max_len` ends before it.
+        let first = by_start.partition_point(|&ib| b[ib].start + max_len <= hit.start);
+        for &ib in &by_start[first..] {
+            if b[ib].start > hit.end {
+                break;
+            }
+            let overlap = overlap_fraction(hit, &b[ib]);
+            if overlap > 0.0 && overlap >= opts.min_overlap {
+                candidates.push((overlap, ia, ib));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+    let mut paired_a = vec![None; a.len()];
+    let mut paired_b = vec![false; b.len()];
+    for (overlap, ia, ib) in candidates {
+        if paired_a[ia].is_none() && !paired_b[ib] {
+            paired_a[ia] = Some((ib, overlap));
+            paired_b[ib] = true;
+        }
+    }
+
+    let mut summary = DiffSummary {
+        a_hits: a.len(),
+        b_hits: b.len(),
+        ..DiffSummary::default()
+    };
+    let mut pairs = Vec::new();
+    let mut only_a = Vec::new();
+    for (hit, paired) in a.iter().zip(paired_a) {
+        let Some((ib, overlap)) = paired else {
+            only_a.push(hit.clone());
+            continue;
+        };
+        let change = HitChange::between(hit, &b[ib]);
+        *match change {
+            HitChange::Identical => &mut summary.identical,
+            HitChange::Boundaries => &mut summary.boundaries,
+            HitChange::Score => &mut summary.score,
+            HitChange::Structure => &mut summary.structure,
+        } += 1;
+        pairs.push(HitPair {
+            a: hit.clone(),
+            b: b[ib].clone(),
+            overlap,
+            change,
+        });
+    }
+    let only_b: Vec<G4Record> = b
+        .iter()
+        .zip(paired_b)
+        .filter(|(_, paired)| !paired)
+        .map(|(hit, _)| hit.clone())
+        .collect();
+    summary.only_a = only_a.len();
+    summary.only_b = only_b.len();
+    summary.hit_jaccard = ratio(pairs.len(), a.len() + b.len() - pairs.len());
+    summary.base_jaccard = base_jaccard(a, b);
+    DiffReport {
+        summary,
+        pairs,
+        only_a,
+        only_b,
+    }
+}
+
+/// One row per hit of every report: its change, or `only_a`/`only_b`, then
+/// the `a` and `b` start, end and score (empty for the missing side) and the
+/// overlap fraction. `name` labels each report, e.g. the record.
+pub fn render_diff_tsv(reports: &[(&str, &DiffReport)]) -> String {
+    let mut out =
+        String::from("name\tchange\ta_start\ta_end\ta_score\tb_start\tb_end\tb_score\toverlap\n");
+    let fields = |hit: Option<&G4Record>| {
+        hit.map_or_else(
+            || "\t\t".to_string(),
+            |hit| format!("{}\t{}\t{}", hit.start, hit.end, hit.score),
+        )
+    };
+    for (name, report) in reports {
+        for pair in &report.pairs {
+            let _ = writeln!(
+                out,
+                "{name}\t{}\t{}\t{}\t{:.4}",
+                pair.change.label(),
+                fields(Some(&pair.a)),
+                fields(Some(&pair.b)),
+                pair.overlap
+            );
+        }
+        for hit in &report.only_a {
+            let _ = writeln!(
+                out,
+                "{name}\tonly_a\t{}\t{}\t",
+                fields(Some(hit)),
+                fields(None)
+            );
+        }
+        for hit in &report.only_b {
+            let _ = writeln!(
+                out,
+                "{name}\tonly_b\t{}\t{}\t",
+                fields(None),
+                fields(Some(hit))
+            );
+        }
+    }
+    out
+}
+
+/// One [`DiffSummary`] row per report.
+pub fn render_diff_summary_tsv(reports: &[(&str, &DiffReport)]) -> String {
+    let mut out = String::from(
+        "name\ta_hits\tb_hits\tidentical\tboundaries\tscore\tstructure\tonly_a\tonly_b\thit_jaccard\tbase_jaccard\n",
+    );
+    for (name, report) in reports {
+        let s = report.summary;
+        let _ = writeln!(
+            out,
+            "{name}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}",
+            s.a_hits,
+            s.b_hits,
+            s.identical,
+            s.boundaries,
+            s.score,
+            s.structure,
+            s.only_a,
+            s.only_b,
+            s.hit_jaccard,
+            s.base_jaccard
+        );
+    }
+    out
+}
+
+#[derive(Serialize)]
+struct NamedReport<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    report: &'a DiffReport,
+}
+
+/// `{"reports": [{"name", "summary", "pairs", "only_a", "only_b"}, ...]}`.
+pub fn render_diff_json(reports: &[(&str, &DiffReport)]) -> serde_json::Result<String> {
+    let reports: Vec<NamedReport> = reports
+        .iter()
+        .map(|&(name, report)| NamedReport { name, report })
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({ "reports": reports }))
+}
+
+/// Bases in `start..=end`.
+fn span_len(hit: &G4Record) -> usize {
+    (hit.end + 1).saturating_sub(hit.start)
+}
+
+fn overlap_fraction(a: &G4Record, b: &G4Record) -> f64 {
+    let shared = (a.end.min(b.end) + 1).saturating_sub(a.start.max(b.start));
+    ratio(shared, span_len(a) + span_len(b) - shared)
+}
+
+/// `part / whole`, with nothing over nothing counting as full agreement.
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        1.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// Merged `start..=end` spans of `hits`, sorted.
+fn covered(hits: &[G4Record]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = hits.iter().map(|hit| (hit.start, hit.end)).collect();
+    spans.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn base_jaccard(a: &[G4Record], b: &[G4Record]) -> f64 {
+    let (a, b) = (covered(a), covered(b));
+    let total = |spans: &[(usize, usize)]| -> usize {
+        spans.iter().map(|&(start, end)| end + 1 - start).sum()
+    };
+    let mut shared = 0;
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].0.max(b[j].0);
+        let end = a[i].1.min(b[j].1);
+        shared += (end + 1).saturating_sub(start);
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    ratio(shared, total(&a) + total(&b) - shared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffOptions, HitChange, diff_results, render_diff_json, render_diff_tsv};
+    use crate::qgrs::G4Record;
+
+    fn hit(start: usize, end: usize, score: i32) -> G4Record {
+        G4Record::new(start, end, 3, [1, 1, 1], score, "G".repeat(end + 1 - start))
+    }
+
+    #[test]
+    fn identical_sets_pair_every_hit() {
+        let a = [hit(10, 30, 40), hit(100, 120, 35)];
+        let report = diff_results(&a, &a, DiffOptions::default());
+        assert!(report.is_identical());
+        assert_eq!(report.summary.identical, 2);
+        assert_eq!(report.summary.hit_jaccard, 1.0);
+        assert_eq!(report.summary.base_jaccard, 1.0);
+
+        let empty = diff_results(&[], &[], DiffOptions::default());
+        assert!(empty.is_identical());
+        assert_eq!(empty.summary.hit_jaccard, 1.0);
+    }
+
+    #[test]
+    fn shifted_and_rescored_hits_are_paired_not_dropped() {
+        let a = [hit(10, 30, 40), hit(100, 120, 35), hit(500, 520, 20)];
+        // Second hit lost 4 bases at its start, third changed score only,
+        // and a new hit appears far from the rest.
+        let b = [hit(104, 120, 33), hit(500, 520, 21), hit(900, 918, 17)];
+        let report = diff_results(&a, &b, DiffOptions::default());
+        let changes: Vec<(usize, HitChange)> = report
+            .pairs
+            .iter()
+            .map(|pair| (pair.a.start, pair.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [(100, HitChange::Boundaries), (500, HitChange::Score)]
+        );
+        assert!((report.pairs[0].overlap - 17.0 / 21.0).abs() < 1e-9);
+        assert_eq!(report.only_a, [hit(10, 30, 40)]);
+        assert_eq!(report.only_b, [hit(900, 918, 17)]);
+        let summary = report.summary;
+        assert_eq!((summary.only_a, summary.only_b), (1, 1));
+        assert_eq!(summary.hit_jaccard, 2.0 / 4.0);
+        // Bases: a covers 21 + 21 + 21, b covers 17 + 21 + 19, 38 shared.
+        assert!((summary.base_jaccard - 38.0 / (63.0 + 57.0 - 38.0)).abs() < 1e-9);
+        assert!(!report.is_identical());
+    }
+
+    #[test]
+    fn greedy_pairing_prefers_the_larger_overlap() {
+        // b[0] overlaps both a hits; it belongs to a[1], which it matches
+        // more closely, leaving a[0] to b[1].
+        let a = [hit(10, 30, 40), hit(20, 40, 40)];
+        let b = [hit(21, 40, 40), hit(8, 28, 40)];
+        let report = diff_results(&a, &b, DiffOptions::default());
+        let pairs: Vec<((usize, usize), (usize, usize))> = report
+            .pairs
+            .iter()
+            .map(|pair| ((pair.a.start, pair.a.end), (pair.b.start, pair.b.end)))
+            .collect();
+        assert_eq!(pairs, [((10, 30), (8, 28)), ((20, 40), (21, 40))]);
+
+        // Shared bases over all bases: 19 / 23 for (10..=30, 8..=28).
+        let strict = diff_results(&a, &b, DiffOptions { min_overlap: 0.9 });
+        assert_eq!(strict.pairs.len(), 1);
+        assert_eq!(strict.only_a, [hit(10, 30, 40)]);
+        assert_eq!(strict.only_b, [hit(8, 28, 40)]);
+    }
+
+    #[test]
+    fn same_bounds_and_score_with_other_loops_is_a_structure_change() {
+        let a = [G4Record::new(
+            1,
+            19,
+            4,
+            [1, 1, 1],
+            63,
+            "GGGGAGGGGAGGGGAGGGG",
+        )];
+        let b = [G4Record::new(
+            1,
+            19,
+            4,
+            [1, 1, 1],
+            63,
+            "GGGGTGGGGAGGGGAGGGG",
+        )];
+        let report = diff_results(&a, &b, DiffOptions::default());
+        assert_eq!(report.pairs[0].change, HitChange::Structure);
+    }
+
+    #[test]
+    fn reports_render_as_tsv_and_json() {
+        let a = [hit(10, 30, 40), hit(100, 120, 35)];
+        let b = [hit(12, 30, 38)];
+        let report = diff_results(&a, &b, DiffOptions::default());
+        let tsv = render_diff_tsv(&[("chr1", &report)]);
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "name\tchange\ta_start\ta_end\ta_score\tb_start\tb_end\tb_score\toverlap",
+                "chr1\tboundaries\t10\t30\t40\t12\t30\t38\t0.9048",
+                "chr1\tonly_a\t100\t120\t35\t\t\t\t",
+            ]
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render_diff_json(&[("chr1", &report)]).unwrap()).unwrap();
+        let entry = &json["reports"][0];
+        assert_eq!(entry["name"], "chr1");
+        assert_eq!(entry["summary"]["boundaries"], 1);
+        assert_eq!(entry["pairs"][0]["change"], "boundaries");
+        assert_eq!(entry["only_a"][0]["start"], 100);
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Why a scan stopped or could not start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QgrsError {
+    /// The scan's `CancellationToken` was cancelled before it finished.
+    Cancelled,
+    /// [`ScanLimits::validate`](crate::qgrs::ScanLimits::validate): no
+    /// hit has zero tetrads.
+    ZeroTetrads,
+    /// `max_run` is shorter than the runs `min_tetrads` tetrads need.
+    MaxRunTooShort { max_run: usize, min_tetrads: usize },
+    /// The shortest `min_tetrads`-tetrad motif, `required` bp, does not fit
+    /// the `max_length` allowed for it (`max_g4_length`, capped at 30 bp
+    /// below 3 tetrads and 45 bp from 3).
+    MaxLengthTooShort {
+        max_length: usize,
+        min_tetrads: usize,
+        required: usize,
+    },
+    /// `min_tetrads` is too large for the motif length to be computed.
+    TooManyTetrads { min_tetrads: usize },
+    /// [`ScanConfig::check_satisfiable`](crate::qgrs::ScanConfig::check_satisfiable):
+    /// no tetrad count can reach its `min_score`; `best_score` is the
+    /// highest score any of them allows.
+    Unsatisfiable { min_score: i32, best_score: i64 },
+    /// [`refilter`](crate::qgrs::refilter) needs `SearchResults::raw_hits`.
+    MissingRawHits,
+    /// [`refilter`](crate::qgrs::refilter) cannot lower the threshold:
+    /// `min_score` is below `scanned`, the highest threshold the results
+    /// were found with.
+    RefilterBelowScan { min_score: i32, scanned: i32 },
+    /// A stream worker panicked while scanning the window that starts
+    /// `offset` bases into the record.
+    WorkerPanicked { offset: usize },
+    /// No stream window finished within the
+    /// [`ScanConfig::with_window_timeout`](crate::qgrs::ScanConfig::with_window_timeout)
+    /// a record waited for one; a worker is stuck.
+    WorkerTimedOut { timeout: Duration },
+    /// Every stream worker went away while a record still waited for a
+    /// window, so its result can never arrive.
+    WorkerLost,
+}
+
+impl fmt::Display for QgrsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QgrsError::Cancelled => write!(f, "scan cancelled"),
+            QgrsError::ZeroTetrads => write!(f, "min_tetrads must be > 0"),
+            QgrsError::MaxRunTooShort {
+                max_run,
+                min_tetrads,
+            } => write!(
+                f,
+                "max_run ({max_run}) must be ≥ min_tetrads ({min_tetrads})"
+            ),
+            QgrsError::MaxLengthTooShort {
+                max_length,
+                min_tetrads,
+                required,
+            } => write!(
+                f,
+                "a {min_tetrads}-tetrad motif needs at least {required} bp, \
+                 but at most {max_length} bp is allowed"
+            ),
+            QgrsError::TooManyTetrads { min_tetrads } => {
+                write!(f, "min_tetrads ({min_tetrads}) is too large")
+            }
+            QgrsError::Unsatisfiable {
+                min_score,
+                best_score,
+            } => write!(
+                f,
+                "no hit can reach min_score {min_score}: the best score these limits \
+                 allow is {best_score}"
+            ),
+            QgrsError::MissingRawHits => write!(
+                f,
+                "results kept no raw hits; scan with ScanConfig::with_keep_raw(true)"
+            ),
+            QgrsError::RefilterBelowScan { min_score, scanned } => write!(
+                f,
+                "cannot refilter to min_score {min_score}: the results were scanned \
+                 with a threshold of {scanned}"
+            ),
+            QgrsError::WorkerPanicked { offset } => write!(
+                f,
+                "a stream worker panicked scanning the window at offset {offset}"
+            ),
+            QgrsError::WorkerTimedOut { timeout } => write!(
+                f,
+                "no stream window finished within {timeout:?}; a worker is stuck"
+            ),
+            QgrsError::WorkerLost => {
+                write!(f, "a stream worker exited without reporting its window")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QgrsError {}
+
+/// Stream APIs report cancellation as an `Interrupted` I/O error wrapping
+/// `QgrsError::Cancelled`, failed or lost workers as `Other`, a window
+/// timeout as `TimedOut`, and rejected limits as `InvalidInput`.
+impl From<QgrsError> for io::Error {
+    fn from(value: QgrsError) -> Self {
+        let kind = match value {
+            QgrsError::Cancelled => io::ErrorKind::Interrupted,
+            QgrsError::WorkerPanicked { .. } | QgrsError::WorkerLost => io::ErrorKind::Other,
+            QgrsError::WorkerTimedOut { .. } => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, value)
+    }
+}
+
+impl QgrsError {
+    /// Recovers the `QgrsError` carried by an I/O error from the stream APIs.
+    pub fn from_io(err: &io::Error) -> Option<QgrsError> {
+        err.get_ref()?.downcast_ref::<QgrsError>().copied()
+    }
+}
@@ -1,19 +1,50 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::io::Write;
+use std::ops::Range;
 use std::sync::Arc;
 
-use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray, UInt64Array};
+use arrow_array::builder::StringBuilder;
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    UInt64Array,
+};
+use arrow_ipc::writer::FileWriter;
 use arrow_schema::{DataType, Field, Schema};
 use parquet::arrow::arrow_writer::ArrowWriter;
 use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::format::KeyValue;
 
-use crate::qgrs::data::SequenceTopology;
+use crate::qgrs::consolidation::FamilyStats;
+use crate::qgrs::data::{ScanConfig, SequenceTopology};
+use crate::qgrs::record::{
+    COORDINATES_METADATA_KEY, CoordinateSystem, G4Record, RECORD_COLUMNS, push_csv_field,
+};
 use crate::qgrs::search::G4;
 
+/// Rows per Parquet `RecordBatch` written by the `write_parquet_results*`
+/// functions; see [`write_parquet_results_batched`].
+pub const DEFAULT_PARQUET_BATCH_ROWS: usize = 65_536;
+
+/// `family_index,start,end` rows for family ranges, 1-based inclusive.
 pub fn render_family_ranges_csv(ranges: &[(usize, usize)]) -> String {
+    render_family_ranges_csv_with_coordinates(ranges, CoordinateSystem::OneBasedInclusive)
+}
+
+/// [`render_family_ranges_csv`] with `start` in `coordinates`.
+pub fn render_family_ranges_csv_with_coordinates(
+    ranges: &[(usize, usize)],
+    coordinates: CoordinateSystem,
+) -> String {
     let mut out = String::from("family_index,start,end\n");
     for (index, (start, end)) in ranges.iter().enumerate() {
-        out.push_str(&format!("{},{},{}\n", index + 1, start, end));
+        out.push_str(&format!(
+            "{},{},{}\n",
+            index + 1,
+            coordinates.start(*start),
+            end
+        ));
     }
     out
 }
@@ -26,18 +57,141 @@ pub fn render_family_ranges_csv_with_projection(
     render_family_ranges_csv(ranges)
 }
 
-pub fn render_csv_results(g4s: &[G4]) -> String {
-    let mut out = String::from("start,end,length,tetrads,y1,y2,y3,score,sequence\n");
-    for g4 in g4s {
-        let sequence_field = escape_csv_field(g4.sequence());
+/// One BED line per family for shading merged intervals in a genome browser:
+/// `chrom`, 0-based `start`, `end`, then the member count in BED's name
+/// column and the best member score in its score column. BED is 0-based by
+/// definition, so there is no [`CoordinateSystem`] to choose. `stats` is
+/// [`SearchResults::family_stats`](crate::qgrs::SearchResults::family_stats),
+/// in `ranges` order. Circular wrap-around families keep expanded
+/// coordinates, so `end` may exceed the record length.
+pub fn render_family_ranges_bed(
+    chrom: &str,
+    ranges: &[(usize, usize)],
+    stats: &[FamilyStats],
+) -> String {
+    debug_assert_eq!(ranges.len(), stats.len());
+    let mut out = String::from("#chrom\tstart\tend\tmembers\tbest_gscore\n");
+    for (&(start, end), stats) in ranges.iter().zip(stats) {
         out.push_str(&format!(
-            "{},{},{},{},{},{},{},{},{}\n",
-            g4.start, g4.end, g4.length, g4.tetrads, g4.y1, g4.y2, g4.y3, g4.score, sequence_field
+            "{chrom}\t{}\t{end}\t{}\t{}\n",
+            start - 1,
+            stats.members,
+            stats.best_score
         ));
     }
     out
 }
 
+/// Values of a column appended after `sequence`, one per exported hit.
+/// Missing `Int64` values are written as `.` in CSV and null in Parquet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnValues {
+    UInt64(Vec<u64>),
+    Int64(Vec<Option<i64>>),
+    Float64(Vec<f64>),
+    Utf8(Vec<String>),
+    Boolean(Vec<bool>),
+}
+
+impl ColumnValues {
+    fn len(&self) -> usize {
+        match self {
+            ColumnValues::UInt64(values) => values.len(),
+            ColumnValues::Int64(values) => values.len(),
+            ColumnValues::Float64(values) => values.len(),
+            ColumnValues::Utf8(values) => values.len(),
+            ColumnValues::Boolean(values) => values.len(),
+        }
+    }
+
+    fn push_csv_field(&self, row: usize, out: &mut String) {
+        match self {
+            ColumnValues::UInt64(values) => out.push_str(&values[row].to_string()),
+            ColumnValues::Int64(values) => match values[row] {
+                Some(value) => out.push_str(&value.to_string()),
+                None => out.push('.'),
+            },
+            ColumnValues::Float64(values) => out.push_str(&values[row].to_string()),
+            ColumnValues::Utf8(values) => push_csv_field(out, &values[row]),
+            ColumnValues::Boolean(values) => out.push_str(&values[row].to_string()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnValues::UInt64(_) => DataType::UInt64,
+            ColumnValues::Int64(_) => DataType::Int64,
+            ColumnValues::Float64(_) => DataType::Float64,
+            ColumnValues::Utf8(_) => DataType::Utf8,
+            ColumnValues::Boolean(_) => DataType::Boolean,
+        }
+    }
+
+    fn is_nullable(&self) -> bool {
+        matches!(self, ColumnValues::Int64(_))
+    }
+
+    fn to_array(&self, rows: Range<usize>) -> ArrayRef {
+        match self {
+            ColumnValues::UInt64(values) => Arc::new(UInt64Array::from(values[rows].to_vec())),
+            ColumnValues::Int64(values) => Arc::new(Int64Array::from(values[rows].to_vec())),
+            ColumnValues::Float64(values) => Arc::new(Float64Array::from(values[rows].to_vec())),
+            ColumnValues::Utf8(values) => Arc::new(StringArray::from_iter_values(&values[rows])),
+            ColumnValues::Boolean(values) => Arc::new(BooleanArray::from(values[rows].to_vec())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtraColumn {
+    pub name: &'static str,
+    pub values: ColumnValues,
+}
+
+impl ExtraColumn {
+    pub fn new(name: &'static str, values: ColumnValues) -> Self {
+        Self { name, values }
+    }
+}
+
+/// Hits as CSV under the [`RECORD_COLUMNS`](crate::qgrs::RECORD_COLUMNS)
+/// header, 1-based coordinates.
+pub fn render_csv_results(g4s: &[G4]) -> String {
+    render_csv_results_with_columns(g4s, &[])
+}
+
+pub fn render_csv_results_with_columns(g4s: &[G4], extra: &[ExtraColumn]) -> String {
+    render_csv_results_with_coordinates(g4s, extra, CoordinateSystem::OneBasedInclusive)
+}
+
+/// [`render_csv_results_with_columns`] with `start` in `coordinates`; the
+/// other columns do not depend on it.
+pub fn render_csv_results_with_coordinates(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    coordinates: CoordinateSystem,
+) -> String {
+    debug_assert!(extra.iter().all(|column| column.values.len() == g4s.len()));
+    let mut out = RECORD_COLUMNS.join(",");
+    for column in extra {
+        out.push(',');
+        out.push_str(column.name);
+    }
+    out.push('\n');
+    for (row, g4) in g4s.iter().enumerate() {
+        g4.to_record()
+            .in_coordinates(coordinates)
+            .push_csv_fields(&mut out);
+        for column in extra {
+            out.push(',');
+            column.values.push_csv_field(row, &mut out);
+        }
+        out.push('\n');
+    }
+    tracing::debug!(rows = g4s.len(), bytes = out.len(), "rendered csv results");
+    out
+}
+
 pub fn render_csv_results_with_projection(
     g4s: &[G4],
     _topology: SequenceTopology,
@@ -46,26 +200,59 @@ pub fn render_csv_results_with_projection(
     render_csv_results(g4s)
 }
 
-fn escape_csv_field(value: &str) -> String {
-    if value.is_empty() {
-        return String::new();
-    }
-    let needs_quotes = value.contains([',', '"', '\n']);
-    if !needs_quotes {
-        return value.to_string();
-    }
-    let mut escaped = String::from('"');
-    for ch in value.chars() {
-        if ch == '"' {
-            escaped.push_str("\"\"");
+/// Fixed-width text in the layout of the QGRS Mapper web tool: a `#` header
+/// recording the sequence and scan parameters, then one row per hit with its
+/// position, length, [`G4::marked_sequence`] and G-score.
+pub fn render_qgrs_mapper(
+    name: &str,
+    sequence_len: usize,
+    config: ScanConfig,
+    g4s: &[G4],
+) -> String {
+    let limits = config.limits();
+    let mut out = format!(
+        "# QGRS Mapper-compatible output\n\
+         # Sequence: {name} ({sequence_len} bp)\n\
+         # Max QGRS length: {}\n\
+         # Min G-group size: {}\n\
+         # Min G-score: {}\n\
+         # Max G-run: {}\n\
+         # Base: {}, topology: {}, family policy: {}\n",
+        limits.max_g4_length,
+        config.min_tetrads(),
+        config.min_score_label(),
+        limits.max_run,
+        config.target_base().cli_name(),
+        if config.topology().is_circular() {
+            "circular"
         } else {
-            escaped.push(ch);
-        }
+            "linear"
+        },
+        config.family_policy().cli_name(),
+    );
+    let width = g4s
+        .iter()
+        .map(|g4| g4.length)
+        .chain([limits.max_g4_length, "QGRS".len()])
+        .max()
+        .unwrap_or_default();
+    out.push_str(&format!(
+        "{:<10}{:<8}{:<width$}  {}\n",
+        "Position", "Length", "QGRS", "G-Score"
+    ));
+    for g4 in g4s {
+        out.push_str(&format!(
+            "{:<10}{:<8}{:<width$}  {}\n",
+            g4.start,
+            g4.length,
+            g4.marked_sequence(),
+            g4.score
+        ));
     }
-    escaped.push('"');
-    escaped
+    out
 }
 
+/// Failure while building or writing Arrow or Parquet output.
 #[derive(Debug)]
 pub enum ExportError {
     Arrow(arrow_schema::ArrowError),
@@ -102,11 +289,291 @@ impl std::error::Error for ExportError {
     }
 }
 
+/// Hits as a Parquet file with the CSV columns, written in batches of
+/// [`DEFAULT_PARQUET_BATCH_ROWS`].
 pub fn write_parquet_results<W: Write + Send + 'static>(
     g4s: &[G4],
     writer: W,
 ) -> Result<(), ExportError> {
-    write_parquet_from_results(g4s, writer)
+    write_parquet_from_results(g4s, &[], writer)
+}
+
+pub fn write_parquet_results_with_columns<W: Write + Send + 'static>(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    writer: W,
+) -> Result<(), ExportError> {
+    write_parquet_from_results(g4s, extra, writer)
+}
+
+/// Writes hits as consecutive `RecordBatch`es of at most `batch_rows` rows
+/// (0 means one batch), so only one batch of Arrow columns is resident at a
+/// time however many hits a chromosome has. The file reads back the same as
+/// one written in a single batch.
+pub fn write_parquet_results_batched<W: Write + Send + 'static>(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    batch_rows: usize,
+    writer: W,
+) -> Result<(), ExportError> {
+    write_parquet_batches(
+        g4s,
+        extra,
+        batch_rows,
+        &[],
+        CoordinateSystem::OneBasedInclusive,
+        writer,
+    )
+}
+
+/// Like [`write_parquet_results_with_columns`], with `metadata` stored as
+/// key-value pairs in the file footer, e.g. the thresholds a scan used.
+pub fn write_parquet_results_with_metadata<W: Write + Send + 'static>(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    metadata: &[(&str, String)],
+    writer: W,
+) -> Result<(), ExportError> {
+    write_parquet_batches(
+        g4s,
+        extra,
+        DEFAULT_PARQUET_BATCH_ROWS,
+        metadata,
+        CoordinateSystem::OneBasedInclusive,
+        writer,
+    )
+}
+
+/// [`write_parquet_results_with_metadata`] with `start` in `coordinates`,
+/// which is also recorded in the footer under [`COORDINATES_METADATA_KEY`].
+pub fn write_parquet_results_with_coordinates<W: Write + Send + 'static>(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    metadata: &[(&str, String)],
+    coordinates: CoordinateSystem,
+    writer: W,
+) -> Result<(), ExportError> {
+    let mut metadata = metadata.to_vec();
+    metadata.push((COORDINATES_METADATA_KEY, coordinates.label().to_string()));
+    write_parquet_batches(
+        g4s,
+        extra,
+        DEFAULT_PARQUET_BATCH_ROWS,
+        &metadata,
+        coordinates,
+        writer,
+    )
+}
+
+/// Footer key-value pairs for `ArrowWriter`; `None` when there are none.
+fn writer_properties(metadata: &[(&str, String)]) -> Option<WriterProperties> {
+    (!metadata.is_empty()).then(|| {
+        let pairs = metadata
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.to_string(), value.clone()))
+            .collect();
+        WriterProperties::builder()
+            .set_key_value_metadata(Some(pairs))
+            .build()
+    })
+}
+
+fn write_parquet_batches<W: Write + Send + 'static>(
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    batch_rows: usize,
+    metadata: &[(&str, String)],
+    coordinates: CoordinateSystem,
+    writer: W,
+) -> Result<(), ExportError> {
+    debug_assert!(extra.iter().all(|column| column.values.len() == g4s.len()));
+    let schema = results_schema(false, extra);
+    let mut arrow_writer =
+        ArrowWriter::try_new(writer, schema.clone(), writer_properties(metadata))?;
+    let batch_rows = if batch_rows == 0 {
+        g4s.len().max(1)
+    } else {
+        batch_rows
+    };
+    let mut scratch = String::new();
+    let mut batches = 0usize;
+    let mut start = 0usize;
+    loop {
+        let end = (start + batch_rows).min(g4s.len());
+        let batch = results_batch(
+            &schema,
+            None,
+            &g4s[start..end],
+            extra,
+            start..end,
+            coordinates,
+            &mut scratch,
+        )?;
+        arrow_writer.write(&batch)?;
+        batches += 1;
+        start = end;
+        if start >= g4s.len() {
+            break;
+        }
+    }
+    arrow_writer.close()?;
+    tracing::debug!(rows = g4s.len(), batches, "wrote parquet results");
+    Ok(())
+}
+
+/// Incremental Parquet writer for result rows arriving in batches, e.g. a
+/// stream scan handing over consolidated hits a batch at a time. Each
+/// [`ParquetSink::append`] becomes one `RecordBatch` whose Arrow columns are
+/// dropped once written, so only the current batch is resident. The file
+/// reads back the same as one written in a single batch; it is complete
+/// only after [`ParquetSink::close`].
+pub struct ParquetSink<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: Arc<Schema>,
+    coordinates: CoordinateSystem,
+    rows: usize,
+    batches: usize,
+}
+
+impl<W: Write + Send> ParquetSink<W> {
+    /// Starts a file with the CSV columns followed by `extra`, of which only
+    /// the names and types are used here. `metadata` goes into the footer
+    /// next to `coordinates` under [`COORDINATES_METADATA_KEY`], as with
+    /// [`write_parquet_results_with_coordinates`].
+    pub fn try_new(
+        writer: W,
+        extra: &[ExtraColumn],
+        metadata: &[(&str, String)],
+        coordinates: CoordinateSystem,
+    ) -> Result<Self, ExportError> {
+        let mut metadata = metadata.to_vec();
+        metadata.push((COORDINATES_METADATA_KEY, coordinates.label().to_string()));
+        let schema = results_schema(false, extra);
+        let writer = ArrowWriter::try_new(writer, schema.clone(), writer_properties(&metadata))?;
+        Ok(Self {
+            writer,
+            schema,
+            coordinates,
+            rows: 0,
+            batches: 0,
+        })
+    }
+
+    /// Writes `records`, 1-based as [`G4Record`]s always are, as one batch.
+    /// Only for sinks started without extra columns.
+    pub fn append(&mut self, records: &[G4Record]) -> Result<(), ExportError> {
+        self.append_with_columns(records, &[], 0..records.len())
+    }
+
+    /// [`ParquetSink::append`] for sinks with extra columns: `records` are
+    /// rows `rows` of `extra`, which must be the columns the sink started
+    /// with.
+    pub fn append_with_columns(
+        &mut self,
+        records: &[G4Record],
+        extra: &[ExtraColumn],
+        rows: Range<usize>,
+    ) -> Result<(), ExportError> {
+        debug_assert_eq!(records.len(), rows.len());
+        let batch = records_batch(&self.schema, records, extra, rows, self.coordinates)?;
+        self.writer.write(&batch)?;
+        self.rows += records.len();
+        self.batches += 1;
+        Ok(())
+    }
+
+    /// Flushes the last row group and writes the footer.
+    pub fn close(self) -> Result<(), ExportError> {
+        self.writer.close()?;
+        tracing::debug!(
+            rows = self.rows,
+            batches = self.batches,
+            "wrote parquet results"
+        );
+        Ok(())
+    }
+}
+
+/// Writes one chromosome's hits as an Arrow IPC file holding a single
+/// `RecordBatch`: the Parquet columns with `chrom` inserted after
+/// `sequence`, ahead of any `extra` columns. polars and pyarrow can
+/// memory-map the result directly.
+pub fn write_arrow_ipc_results<W: Write>(
+    chrom: &str,
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    writer: W,
+) -> Result<(), ExportError> {
+    let schema = results_schema(true, extra);
+    write_arrow_ipc(
+        schema,
+        chrom,
+        g4s,
+        extra,
+        CoordinateSystem::OneBasedInclusive,
+        writer,
+    )
+}
+
+/// [`write_arrow_ipc_results`] with `start` in `coordinates`, which is also
+/// recorded in the schema metadata under [`COORDINATES_METADATA_KEY`].
+pub fn write_arrow_ipc_results_with_coordinates<W: Write>(
+    chrom: &str,
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    coordinates: CoordinateSystem,
+    writer: W,
+) -> Result<(), ExportError> {
+    let metadata = HashMap::from([(
+        COORDINATES_METADATA_KEY.to_string(),
+        coordinates.label().to_string(),
+    )]);
+    let schema = Arc::new(
+        results_schema(true, extra)
+            .as_ref()
+            .clone()
+            .with_metadata(metadata),
+    );
+    write_arrow_ipc(schema, chrom, g4s, extra, coordinates, writer)
+}
+
+fn write_arrow_ipc<W: Write>(
+    schema: Arc<Schema>,
+    chrom: &str,
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    coordinates: CoordinateSystem,
+    writer: W,
+) -> Result<(), ExportError> {
+    debug_assert!(extra.iter().all(|column| column.values.len() == g4s.len()));
+    let batch = results_batch(
+        &schema,
+        Some(chrom),
+        g4s,
+        extra,
+        0..g4s.len(),
+        coordinates,
+        &mut String::new(),
+    )?;
+    let mut ipc_writer = FileWriter::try_new(writer, &schema)?;
+    ipc_writer.write(&batch)?;
+    ipc_writer.finish()?;
+    Ok(())
+}
+
+/// The Arrow batch behind both columnar exports: the core result columns,
+/// followed by `chrom` when one is given.
+pub fn build_record_batch(chrom: Option<&str>, g4s: &[G4]) -> Result<RecordBatch, ExportError> {
+    let schema = results_schema(chrom.is_some(), &[]);
+    results_batch(
+        &schema,
+        chrom,
+        g4s,
+        &[],
+        0..g4s.len(),
+        CoordinateSystem::OneBasedInclusive,
+        &mut String::new(),
+    )
 }
 
 pub fn write_parquet_results_with_projection<W: Write + Send + 'static>(
@@ -121,6 +588,27 @@ pub fn write_parquet_results_with_projection<W: Write + Send + 'static>(
 pub fn write_parquet_family_ranges<W: Write + Send + 'static>(
     ranges: &[(usize, usize)],
     writer: W,
+) -> Result<(), ExportError> {
+    write_family_ranges(ranges, CoordinateSystem::OneBasedInclusive, None, writer)
+}
+
+/// [`write_parquet_family_ranges`] with `start` in `coordinates`, which is
+/// also recorded in the footer under [`COORDINATES_METADATA_KEY`].
+pub fn write_parquet_family_ranges_with_coordinates<W: Write + Send + 'static>(
+    ranges: &[(usize, usize)],
+    coordinates: CoordinateSystem,
+    writer: W,
+) -> Result<(), ExportError> {
+    let properties =
+        writer_properties(&[(COORDINATES_METADATA_KEY, coordinates.label().to_string())]);
+    write_family_ranges(ranges, coordinates, properties, writer)
+}
+
+fn write_family_ranges<W: Write + Send + 'static>(
+    ranges: &[(usize, usize)],
+    coordinates: CoordinateSystem,
+    properties: Option<WriterProperties>,
+    writer: W,
 ) -> Result<(), ExportError> {
     let schema = Arc::new(Schema::new(vec![
         Field::new("family_index", DataType::UInt64, false),
@@ -129,7 +617,10 @@ pub fn write_parquet_family_ranges<W: Write + Send + 'static>(
     ]));
 
     let family_indices: Vec<u64> = (0..ranges.len()).map(|index| (index + 1) as u64).collect();
-    let starts: Vec<u64> = ranges.iter().map(|(start, _)| *start as u64).collect();
+    let starts: Vec<u64> = ranges
+        .iter()
+        .map(|(start, _)| coordinates.start(*start) as u64)
+        .collect();
     let ends: Vec<u64> = ranges.iter().map(|(_, end)| *end as u64).collect();
 
     let columns: Vec<ArrayRef> = vec![
@@ -139,7 +630,7 @@ pub fn write_parquet_family_ranges<W: Write + Send + 'static>(
     ];
 
     let batch = RecordBatch::try_new(schema.clone(), columns)?;
-    let mut arrow_writer = ArrowWriter::try_new(writer, schema, None)?;
+    let mut arrow_writer = ArrowWriter::try_new(writer, schema, properties)?;
     arrow_writer.write(&batch)?;
     arrow_writer.close()?;
     Ok(())
@@ -156,21 +647,58 @@ pub fn write_parquet_family_ranges_with_projection<W: Write + Send + 'static>(
 
 fn write_parquet_from_results<W: Write + Send + 'static>(
     g4s: &[G4],
+    extra: &[ExtraColumn],
     writer: W,
 ) -> Result<(), ExportError> {
-    let schema = Arc::new(Schema::new(vec![
-        Field::new("start", DataType::UInt64, false),
-        Field::new("end", DataType::UInt64, false),
-        Field::new("length", DataType::UInt64, false),
-        Field::new("tetrads", DataType::UInt64, false),
-        Field::new("y1", DataType::Int32, false),
-        Field::new("y2", DataType::Int32, false),
-        Field::new("y3", DataType::Int32, false),
-        Field::new("score", DataType::Int32, false),
-        Field::new("sequence", DataType::Utf8, false),
-    ]));
+    write_parquet_results_batched(g4s, extra, DEFAULT_PARQUET_BATCH_ROWS, writer)
+}
+
+fn results_schema(with_chrom: bool, extra: &[ExtraColumn]) -> Arc<Schema> {
+    let types = [
+        DataType::UInt64,
+        DataType::UInt64,
+        DataType::UInt64,
+        DataType::UInt64,
+        DataType::Int32,
+        DataType::Int32,
+        DataType::Int32,
+        DataType::Int32,
+        DataType::Utf8,
+    ];
+    let mut fields: Vec<Field> = RECORD_COLUMNS
+        .iter()
+        .zip(types)
+        .map(|(name, data_type)| Field::new(*name, data_type, false))
+        .collect();
+    if with_chrom {
+        fields.push(Field::new("chrom", DataType::Utf8, false));
+    }
+    for column in extra {
+        fields.push(Field::new(
+            column.name,
+            column.values.data_type(),
+            column.values.is_nullable(),
+        ));
+    }
+    Arc::new(Schema::new(fields))
+}
 
-    let starts: Vec<u64> = g4s.iter().map(|g| g.start as u64).collect();
+/// `rows` locates `g4s` within the extra columns. Sequences are uppercased
+/// through `scratch` straight into the Arrow buffer, without caching a
+/// `String` per hit. `chrom` must match the schema's `chrom` column.
+fn results_batch(
+    schema: &Arc<Schema>,
+    chrom: Option<&str>,
+    g4s: &[G4],
+    extra: &[ExtraColumn],
+    rows: Range<usize>,
+    coordinates: CoordinateSystem,
+    scratch: &mut String,
+) -> Result<RecordBatch, ExportError> {
+    let starts: Vec<u64> = g4s
+        .iter()
+        .map(|g| coordinates.start(g.start) as u64)
+        .collect();
     let ends: Vec<u64> = g4s.iter().map(|g| g.end as u64).collect();
     let lengths: Vec<u64> = g4s.iter().map(|g| g.length as u64).collect();
     let tetrads: Vec<u64> = g4s.iter().map(|g| g.tetrads as u64).collect();
@@ -178,9 +706,22 @@ fn write_parquet_from_results<W: Write + Send + 'static>(
     let y2s: Vec<i32> = g4s.iter().map(|g| g.y2).collect();
     let y3s: Vec<i32> = g4s.iter().map(|g| g.y3).collect();
     let scores: Vec<i32> = g4s.iter().map(|g| g.score).collect();
-    let sequences: Vec<String> = g4s.iter().map(|g| g.sequence().to_string()).collect();
+    let sequence_bytes = g4s.iter().map(|g| g.length).sum();
+    let mut sequences = StringBuilder::with_capacity(g4s.len(), sequence_bytes);
+    for g in g4s {
+        scratch.clear();
+        let preserve_case = g.preserves_case();
+        scratch.extend(g.sequence_slice().bytes().iter().map(|&byte| {
+            char::from(if preserve_case {
+                byte
+            } else {
+                byte.to_ascii_uppercase()
+            })
+        }));
+        sequences.append_value(&*scratch);
+    }
 
-    let columns: Vec<ArrayRef> = vec![
+    let mut columns: Vec<ArrayRef> = vec![
         Arc::new(UInt64Array::from(starts)),
         Arc::new(UInt64Array::from(ends)),
         Arc::new(UInt64Array::from(lengths)),
@@ -189,12 +730,60 @@ fn write_parquet_from_results<W: Write + Send + 'static>(
         Arc::new(Int32Array::from(y2s)),
         Arc::new(Int32Array::from(y3s)),
         Arc::new(Int32Array::from(scores)),
-        Arc::new(StringArray::from(sequences)),
+        Arc::new(sequences.finish()),
     ];
+    if let Some(chrom) = chrom {
+        let mut chroms = StringBuilder::with_capacity(g4s.len(), g4s.len() * chrom.len());
+        for _ in g4s {
+            chroms.append_value(chrom);
+        }
+        columns.push(Arc::new(chroms.finish()));
+    }
+    columns.extend(
+        extra
+            .iter()
+            .map(|column| column.values.to_array(rows.clone())),
+    );
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
 
-    let batch = RecordBatch::try_new(schema.clone(), columns)?;
-    let mut arrow_writer = ArrowWriter::try_new(writer, schema, None)?;
-    arrow_writer.write(&batch)?;
-    arrow_writer.close()?;
-    Ok(())
+/// [`results_batch`] for owned records, whose sequences are already
+/// uppercase.
+fn records_batch(
+    schema: &Arc<Schema>,
+    records: &[G4Record],
+    extra: &[ExtraColumn],
+    rows: Range<usize>,
+    coordinates: CoordinateSystem,
+) -> Result<RecordBatch, ExportError> {
+    let starts: Vec<u64> = records
+        .iter()
+        .map(|r| coordinates.start(r.start) as u64)
+        .collect();
+    let ends: Vec<u64> = records.iter().map(|r| r.end as u64).collect();
+    let lengths: Vec<u64> = records.iter().map(|r| r.length as u64).collect();
+    let tetrads: Vec<u64> = records.iter().map(|r| r.tetrads as u64).collect();
+    let y1s: Vec<i32> = records.iter().map(|r| r.y1).collect();
+    let y2s: Vec<i32> = records.iter().map(|r| r.y2).collect();
+    let y3s: Vec<i32> = records.iter().map(|r| r.y3).collect();
+    let scores: Vec<i32> = records.iter().map(|r| r.score).collect();
+    let sequences = StringArray::from_iter_values(records.iter().map(|r| &r.sequence));
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(starts)),
+        Arc::new(UInt64Array::from(ends)),
+        Arc::new(UInt64Array::from(lengths)),
+        Arc::new(UInt64Array::from(tetrads)),
+        Arc::new(Int32Array::from(y1s)),
+        Arc::new(Int32Array::from(y2s)),
+        Arc::new(Int32Array::from(y3s)),
+        Arc::new(Int32Array::from(scores)),
+        Arc::new(sequences),
+    ];
+    columns.extend(
+        extra
+            .iter()
+            .map(|column| column.values.to_array(rows.clone())),
+    );
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
 }
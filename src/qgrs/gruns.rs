@@ -0,0 +1,84 @@
+use memchr::memchr2;
+
+use crate::qgrs::data::QuartetBase;
+
+/// A maximal run of the target base: 0-based `start` and run length `len`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GRun {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// Iterates the maximal G runs (case-insensitive) of at least `min_tetrads`
+/// bases in `data`, in order.
+///
+/// ```
+/// use qgrs_rust::qgrs::gruns::{GRun, scan_bytes};
+///
+/// let runs: Vec<GRun> = scan_bytes(b"ggGAtGGGG", 3).collect();
+/// assert_eq!(runs, [GRun { start: 0, len: 3 }, GRun { start: 5, len: 4 }]);
+/// ```
+pub fn scan_bytes(data: &[u8], min_tetrads: usize) -> impl Iterator<Item = GRun> + '_ {
+    BaseRunScanner::new(data, min_tetrads, QuartetBase::G)
+}
+
+/// Same as [`scan_bytes`] for an arbitrary tetrad base.
+pub fn scan_bytes_with_base(
+    data: &[u8],
+    min_tetrads: usize,
+    target_base: QuartetBase,
+) -> impl Iterator<Item = GRun> + '_ {
+    BaseRunScanner::new(data, min_tetrads, target_base)
+}
+
+pub(crate) struct BaseRunScanner<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    min_tetrads: usize,
+    target_base: QuartetBase,
+}
+
+impl<'a> BaseRunScanner<'a> {
+    pub(crate) fn new(data: &'a [u8], min_tetrads: usize, target_base: QuartetBase) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            min_tetrads,
+            target_base,
+        }
+    }
+}
+
+impl<'a> Iterator for BaseRunScanner<'a> {
+    type Item = GRun;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.data.len();
+        while self.cursor < len {
+            let search_slice = &self.data[self.cursor..];
+            let relative = memchr2(
+                self.target_base.lowercase_byte(),
+                self.target_base.uppercase_byte(),
+                search_slice,
+            )?;
+            let run_start = self.cursor + relative;
+            let mut run_end = run_start;
+            while run_end < len
+                && self
+                    .target_base
+                    .matches(unsafe { *self.data.get_unchecked(run_end) })
+            {
+                run_end += 1;
+            }
+            self.cursor = if run_end < len { run_end + 1 } else { len };
+            let run_len = run_end - run_start;
+            if run_len >= self.min_tetrads {
+                return Some(GRun {
+                    start: run_start,
+                    len: run_len,
+                });
+            }
+        }
+        None
+    }
+}
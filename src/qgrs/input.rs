@@ -5,30 +5,106 @@ use std::path::Path;
 use flate2::read::MultiGzDecoder;
 
 const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 pub(crate) const INPUT_BUFFER_CAPACITY: usize = 1 << 20;
+pub(crate) const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
 
-pub(crate) fn is_gzip_path(path: &Path) -> io::Result<bool> {
-    let mut file = File::open(path)?;
-    let mut magic = [0u8; 2];
-    let bytes_read = file.read(&mut magic)?;
-    file.seek(SeekFrom::Start(0))?;
-    Ok(bytes_read == 2 && magic == GZIP_MAGIC)
+/// Drops a UTF-8 byte order mark at the start of the input so the first
+/// header is still recognised. Returns the number of bytes dropped.
+pub(crate) fn skip_utf8_bom<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<usize> {
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+        return Ok(UTF8_BOM.len());
+    }
+    Ok(0)
+}
+
+/// Whether `line` is a record header: its first byte other than spaces and
+/// tabs is `>`, so a hand-edited ` >chr2` still starts a record.
+pub(crate) fn is_header_line(line: &[u8]) -> bool {
+    line.iter().find(|byte| !matches!(byte, b' ' | b'\t')) == Some(&b'>')
 }
 
-pub(crate) fn open_input_reader(path: &Path) -> io::Result<Box<dyn BufRead>> {
-    let file = File::open(path)?;
-    open_reader_from_file(file)
+/// Whether `line` is a comment of the original Pearson FASTA format: its
+/// first byte other than spaces and tabs is `;`. Comments are dropped
+/// instead of being read as sequence.
+pub(crate) fn is_comment_line(line: &[u8]) -> bool {
+    line.iter().find(|byte| !matches!(byte, b' ' | b'\t')) == Some(&b';')
 }
 
-fn open_reader_from_file(mut file: File) -> io::Result<Box<dyn BufRead>> {
-    let mut magic = [0u8; 2];
-    let bytes_read = file.read(&mut magic)?;
+/// Compression wrapping an input file, decided by [`detect_compression`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Compression {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+/// Sniffs the leading magic bytes; a `.zst` extension also selects zstd so
+/// a truncated or skippable-frame file still gets a decoder error rather
+/// than being parsed as FASTA.
+pub(crate) fn detect_compression(path: &Path) -> io::Result<Compression> {
+    let mut file = File::open(path)?;
+    compression_of(&mut file, path)
+}
+
+fn compression_of(file: &mut File, path: &Path) -> io::Result<Compression> {
+    let mut magic = [0u8; 4];
+    let mut filled = 0;
+    while filled < magic.len() {
+        let bytes_read = file.read(&mut magic[filled..])?;
+        if bytes_read == 0 {
+            break;
+        }
+        filled += bytes_read;
+    }
     file.seek(SeekFrom::Start(0))?;
-    if bytes_read == 2 && magic == GZIP_MAGIC {
-        let reader = BufReader::with_capacity(INPUT_BUFFER_CAPACITY, MultiGzDecoder::new(file));
-        Ok(Box::new(reader))
+    let magic = &magic[..filled];
+    if magic.starts_with(&GZIP_MAGIC) {
+        Ok(Compression::Gzip)
+    } else if magic.starts_with(&ZSTD_MAGIC) || path.extension().is_some_and(|ext| ext == "zst") {
+        Ok(Compression::Zstd)
     } else {
-        let reader = BufReader::with_capacity(INPUT_BUFFER_CAPACITY, file);
-        Ok(Box::new(reader))
+        Ok(Compression::Plain)
+    }
+}
+
+/// Opens `path` for line-oriented reading, transparently decompressing gzip
+/// and zstd. Every loader goes through here, so a new input format only
+/// needs a [`Compression`] variant and an arm below.
+pub(crate) fn open_sequence_reader(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    open_sequence_reader_at(path, 0)
+}
+
+/// [`open_sequence_reader`] positioned `offset` bytes into the decompressed
+/// input. Plain files seek there; compressed ones are decoded and the bytes
+/// before `offset` discarded.
+pub(crate) fn open_sequence_reader_at(path: &Path, offset: u64) -> io::Result<Box<dyn BufRead>> {
+    let mut file = File::open(path)?;
+    let compression = compression_of(&mut file, path)?;
+    let mut reader: Box<dyn BufRead> = match compression {
+        Compression::Plain => {
+            file.seek(SeekFrom::Start(offset))?;
+            return Ok(Box::new(BufReader::with_capacity(
+                INPUT_BUFFER_CAPACITY,
+                file,
+            )));
+        }
+        Compression::Gzip => Box::new(BufReader::with_capacity(
+            INPUT_BUFFER_CAPACITY,
+            MultiGzDecoder::new(file),
+        )),
+        Compression::Zstd => Box::new(BufReader::with_capacity(
+            INPUT_BUFFER_CAPACITY,
+            zstd::stream::read::Decoder::new(file)?,
+        )),
+    };
+    let skipped = io::copy(&mut reader.by_ref().take(offset), &mut io::sink())?;
+    if skipped < offset {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{path:?} ends before byte {offset}"),
+        ));
     }
+    Ok(reader)
 }
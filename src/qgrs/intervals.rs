@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::qgrs::input::open_sequence_reader;
+use crate::qgrs::search::intervals_overlap;
+
+/// A feature interval in 1-based inclusive coordinates (BED `start + 1 ..= end`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub start: usize,
+    pub end: usize,
+    pub name: String,
+}
+
+/// Overlapping feature names and signed distance for one hit.
+///
+/// `distance` is 0 when `features` is non-empty; otherwise it is negative when
+/// the nearest interval lies before the hit and positive when it lies after
+/// (an interval ending right before the hit's start is at -1). Equidistant
+/// neighbours resolve to the upstream one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation<'a> {
+    pub features: Vec<&'a str>,
+    pub distance: i64,
+}
+
+#[derive(Debug, Default)]
+struct ChromIntervals {
+    /// Sorted by `(start, end)`.
+    by_start: Vec<Interval>,
+    /// Indices into `by_start`, sorted by `end`.
+    by_end: Vec<usize>,
+    max_len: usize,
+}
+
+impl ChromIntervals {
+    fn build(mut intervals: Vec<Interval>) -> Self {
+        intervals.sort_by_key(|interval| (interval.start, interval.end));
+        let mut by_end: Vec<usize> = (0..intervals.len()).collect();
+        by_end.sort_by_key(|&index| intervals[index].end);
+        let max_len = intervals
+            .iter()
+            .map(|interval| interval.end - interval.start + 1)
+            .max()
+            .unwrap_or(0);
+        Self {
+            by_start: intervals,
+            by_end,
+            max_len,
+        }
+    }
+
+    /// Intervals overlapping `start..=end`, in start order.
+    fn overlapping(&self, start: usize, end: usize) -> impl Iterator<Item = &Interval> {
+        self.overlapping_positions(start, end)
+            .map(|position| &self.by_start[position])
+    }
+
+    /// Positions in `by_start` of the intervals overlapping `start..=end`.
+    fn overlapping_positions(&self, start: usize, end: usize) -> impl Iterator<Item = usize> {
+        // An interval starting before `start - max_len` cannot reach `start`.
+        let lower = start.saturating_sub(self.max_len);
+        let first = self.by_start.partition_point(|iv| iv.start < lower);
+        let last = self.by_start.partition_point(|iv| iv.start <= end);
+        (first..last).filter(move |&position| {
+            let iv = &self.by_start[position];
+            intervals_overlap((iv.start, iv.end), (start, end))
+        })
+    }
+
+    fn overlap_len(&self, start: usize, end: usize) -> usize {
+        let mut covered = 0;
+        let mut next_uncovered = start;
+        for iv in self.overlapping(start, end) {
+            let from = iv.start.max(next_uncovered);
+            let to = iv.end.min(end);
+            if from <= to {
+                covered += to - from + 1;
+                next_uncovered = to + 1;
+            }
+        }
+        covered
+    }
+
+    fn annotate(&self, start: usize, end: usize) -> Option<Annotation<'_>> {
+        if self.by_start.is_empty() {
+            return None;
+        }
+        let features: Vec<&str> = self
+            .overlapping(start, end)
+            .map(|iv| iv.name.as_str())
+            .collect();
+        if !features.is_empty() {
+            return Some(Annotation {
+                features,
+                distance: 0,
+            });
+        }
+
+        let upstream = self
+            .by_end
+            .partition_point(|&index| self.by_start[index].end < start)
+            .checked_sub(1)
+            .map(|pos| -((start - self.by_start[self.by_end[pos]].end) as i64));
+        let last = self.by_start.partition_point(|iv| iv.start <= end);
+        let downstream = self.by_start.get(last).map(|iv| (iv.start - end) as i64);
+        let distance = match (upstream, downstream) {
+            (Some(up), Some(down)) if down < -up => down,
+            (Some(up), _) => up,
+            (None, Some(down)) => down,
+            (None, None) => unreachable!("chromosome has at least one interval"),
+        };
+        Some(Annotation { features, distance })
+    }
+}
+
+/// Per-chromosome feature intervals answering overlap and nearest queries by
+/// binary search.
+#[derive(Debug, Default)]
+pub struct IntervalIndex {
+    chroms: HashMap<String, ChromIntervals>,
+}
+
+impl IntervalIndex {
+    pub fn from_intervals<I>(intervals: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Interval)>,
+    {
+        let mut grouped: HashMap<String, Vec<Interval>> = HashMap::new();
+        for (chrom, interval) in intervals {
+            grouped.entry(chrom).or_default().push(interval);
+        }
+        let chroms = grouped
+            .into_iter()
+            .map(|(chrom, intervals)| (chrom, ChromIntervals::build(intervals)))
+            .collect();
+        Self { chroms }
+    }
+
+    /// Loads a BED file (optionally gzip-compressed). `track`, `browser` and
+    /// `#` lines are skipped; records without a name column are named
+    /// `chrom:start-end`.
+    pub fn from_bed_path(path: &Path) -> io::Result<Self> {
+        Self::from_bed_reader(open_sequence_reader(path)?)
+    }
+
+    pub fn from_bed_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut intervals = Vec::new();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty()
+                || trimmed.starts_with('#')
+                || trimmed.starts_with("track")
+                || trimmed.starts_with("browser")
+            {
+                continue;
+            }
+            let invalid = |reason: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("BED line {}: {reason}", line_index + 1),
+                )
+            };
+            let mut fields = trimmed.split('\t');
+            let chrom = fields.next().unwrap_or_default();
+            let bed_start: usize = fields
+                .next()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| invalid("missing or invalid start"))?;
+            let bed_end: usize = fields
+                .next()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| invalid("missing or invalid end"))?;
+            if bed_end <= bed_start {
+                return Err(invalid("end must be greater than start"));
+            }
+            let name = match fields.next().map(str::trim) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => format!("{chrom}:{bed_start}-{bed_end}"),
+            };
+            intervals.push((
+                chrom.to_string(),
+                Interval {
+                    start: bed_start + 1,
+                    end: bed_end,
+                    name,
+                },
+            ));
+        }
+        Ok(Self::from_intervals(intervals))
+    }
+
+    /// Annotates the 1-based inclusive hit `start..=end`; `None` when `chrom`
+    /// has no intervals.
+    pub fn annotate(&self, chrom: &str, start: usize, end: usize) -> Option<Annotation<'_>> {
+        self.chroms.get(chrom)?.annotate(start, end)
+    }
+
+    /// Every chromosome's intervals, each list sorted by `(start, end)`.
+    pub(crate) fn chromosomes(&self) -> impl Iterator<Item = (&str, &[Interval])> {
+        self.chroms
+            .iter()
+            .map(|(chrom, intervals)| (chrom.as_str(), intervals.by_start.as_slice()))
+    }
+
+    /// `chrom`'s intervals sorted by `(start, end)`; empty when absent.
+    pub(crate) fn intervals(&self, chrom: &str) -> &[Interval] {
+        self.chroms
+            .get(chrom)
+            .map_or(&[], |intervals| intervals.by_start.as_slice())
+    }
+
+    /// Positions, in [`IntervalIndex::intervals`] of `chrom`, of
+    /// the intervals overlapping the 1-based inclusive `start..=end`.
+    pub(crate) fn overlapping_positions(
+        &self,
+        chrom: &str,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = usize> {
+        self.chroms
+            .get(chrom)
+            .into_iter()
+            .flat_map(move |intervals| intervals.overlapping_positions(start, end))
+    }
+
+    /// Number of bases of `start..=end` covered by at least one interval.
+    pub fn overlap_len(&self, chrom: &str, start: usize, end: usize) -> usize {
+        self.chroms
+            .get(chrom)
+            .map_or(0, |intervals| intervals.overlap_len(start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntervalIndex;
+
+    const BED: &str = "track name=genes\n\
+        chr1\t100\t200\tgeneA\n\
+        chr1\t150\t400\tgeneB\n\
+        chr1\t1000\t1010\n\
+        chr2\t0\t10\tgeneC\n";
+
+    #[test]
+    fn overlaps_list_every_feature_and_report_zero_distance() {
+        let index = IntervalIndex::from_bed_reader(BED.as_bytes()).unwrap();
+        let hit = index.annotate("chr1", 190, 210).unwrap();
+        assert_eq!(hit.features, ["geneA", "geneB"]);
+        assert_eq!(hit.distance, 0);
+
+        let unnamed = index.annotate("chr1", 1005, 1020).unwrap();
+        assert_eq!(unnamed.features, ["chr1:1000-1010"]);
+    }
+
+    #[test]
+    fn abutting_intervals_are_one_base_away() {
+        let index = IntervalIndex::from_bed_reader(BED.as_bytes()).unwrap();
+        // BED chr2:0-10 covers 1..=10, so a hit at 11 abuts it.
+        let after = index.annotate("chr2", 11, 30).unwrap();
+        assert!(after.features.is_empty());
+        assert_eq!(after.distance, -1);
+        // BED chr1:100-200 starts at base 101, right after a hit ending at 100.
+        let before = index.annotate("chr1", 80, 100).unwrap();
+        assert!(before.features.is_empty());
+        assert_eq!(before.distance, 1);
+    }
+
+    #[test]
+    fn nearest_interval_picks_the_closer_side() {
+        let index = IntervalIndex::from_bed_reader(BED.as_bytes()).unwrap();
+        assert_eq!(index.annotate("chr1", 450, 460).unwrap().distance, -50);
+        assert_eq!(index.annotate("chr1", 900, 950).unwrap().distance, 51);
+        assert!(index.annotate("chrX", 1, 10).is_none());
+    }
+
+    #[test]
+    fn overlap_len_counts_each_base_once() {
+        let index = IntervalIndex::from_bed_reader(BED.as_bytes()).unwrap();
+        // geneA (101..=200) and geneB (151..=400) overlap each other.
+        assert_eq!(index.overlap_len("chr1", 91, 210), 110);
+        assert_eq!(index.overlap_len("chr1", 401, 1000), 0);
+        assert_eq!(index.overlap_len("chrX", 1, 10), 0);
+    }
+
+    #[test]
+    fn malformed_lines_report_their_line_number() {
+        let err = IntervalIndex::from_bed_reader("chr1\t10\t5\tbad\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("BED line 1"));
+    }
+}
@@ -0,0 +1,75 @@
+//! Crash-safe output files.
+//!
+//! Every export is written to `<path>.tmp` next to its destination and renamed
+//! into place only once complete, so a killed job leaves a `.tmp` file rather
+//! than a truncated output that looks finished. With `fsync` the data is on
+//! disk before the rename, and the directory is synced after it, which is
+//! what makes the rename itself durable on NFS or Lustre.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The temporary file [`write_atomically`] writes before renaming it to `path`.
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Calls `write` with [`tmp_path`]`(path)`, then [`commit`]s it. When `write`
+/// fails, `path` is left untouched and whatever it wrote stays in the `.tmp`
+/// file.
+///
+/// ```
+/// use qgrs_rust::qgrs::io::{tmp_path, write_atomically};
+///
+/// let path = std::env::temp_dir().join("qgrs_io_doc.csv");
+/// write_atomically(&path, true, |tmp| std::fs::write(tmp, "start,end\n")).unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), "start,end\n");
+/// assert!(!tmp_path(&path).exists());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn write_atomically<F>(path: &Path, fsync: bool, write: F) -> io::Result<()>
+where
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let tmp = tmp_path(path);
+    write(&tmp)?;
+    commit(&tmp, path, fsync)
+}
+
+/// Moves a finished `tmp` over `path`. With `fsync`, `tmp` is synced first and
+/// the parent directory afterwards. Errors name the paths involved.
+pub fn commit(tmp: &Path, path: &Path, fsync: bool) -> io::Result<()> {
+    let context = |action: &str, err: io::Error| {
+        io::Error::new(err.kind(), format!("failed to {action}: {err}"))
+    };
+    if fsync {
+        File::open(tmp)
+            .and_then(|file| file.sync_all())
+            .map_err(|err| context(&format!("sync {tmp:?}"), err))?;
+    }
+    fs::rename(tmp, path).map_err(|err| context(&format!("move {tmp:?} to {path:?}"), err))?;
+    if fsync {
+        sync_parent(path)
+            .map_err(|err| context(&format!("sync the directory of {path:?}"), err))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+// Directories cannot be opened as files here; the rename is as durable as
+// the platform makes it.
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -1,74 +1,232 @@
 use std::fs::File;
-use std::io::{self, BufRead, Read};
+use std::io::{self, BufRead, Read, Write};
 use std::path::Path;
 use std::sync::Arc;
 
-use memmap2::MmapOptions;
+use memmap2::{Mmap, MmapOptions};
 
-use crate::qgrs::data::{ChromSequence, InputMode};
+use crate::qgrs::data::{ChromSequence, InputMode, OccurrenceCounter};
 
-use super::input::{is_gzip_path, open_input_reader};
+use super::input::{
+    Compression, UTF8_BOM, detect_compression, is_comment_line, is_header_line,
+    open_sequence_reader, skip_utf8_bom,
+};
 
+/// Options for the `load_sequences_*` and header readers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Keep records whose header is followed by no sequence bytes as
+    /// zero-length `ChromSequence`s instead of dropping them.
+    pub keep_empty: bool,
+    /// mmap mode: fault the whole file in when it is mapped
+    /// (`MAP_POPULATE`, Linux only) instead of page by page while parsing.
+    pub populate: bool,
+}
+
+impl LoadOptions {
+    pub const fn new(keep_empty: bool) -> Self {
+        Self {
+            keep_empty,
+            populate: false,
+        }
+    }
+
+    pub const fn with_populate(mut self, populate: bool) -> Self {
+        self.populate = populate;
+        self
+    }
+}
+
+/// The paging hints mmap mode gives the kernel when loading `path` on this
+/// platform. Compressed input is decoded into memory instead of mapped and
+/// gets none.
+pub fn mmap_hints(path: &Path, options: LoadOptions) -> io::Result<Vec<&'static str>> {
+    let mut hints = Vec::new();
+    if detect_compression(path)? != Compression::Plain {
+        return Ok(hints);
+    }
+    if cfg!(unix) {
+        hints.extend(["MADV_SEQUENTIAL", "MADV_WILLNEED"]);
+    }
+    if options.populate && cfg!(any(target_os = "linux", target_os = "android")) {
+        hints.push("MAP_POPULATE");
+    }
+    Ok(hints)
+}
+
+/// Reads every non-empty record of a FASTA file, plain or compressed, with
+/// the default [`LoadOptions`].
 pub fn load_sequences_from_path(path: &Path, mode: InputMode) -> io::Result<Vec<ChromSequence>> {
+    load_sequences_from_path_with_options(path, mode, LoadOptions::default())
+}
+
+/// [`load_sequences_from_path`] with explicit [`LoadOptions`].
+pub fn load_sequences_from_path_with_options(
+    path: &Path,
+    mode: InputMode,
+    options: LoadOptions,
+) -> io::Result<Vec<ChromSequence>> {
+    load_sequences_from_path_with_filter(path, mode, options, |_| true)
+}
+
+/// Like [`load_sequences_from_path_with_options`], but only records whose
+/// name passes `keep` are returned. Rejected records are dropped at their
+/// header, so their sequence bytes are never buffered.
+pub fn load_sequences_from_path_with_filter<K>(
+    path: &Path,
+    mode: InputMode,
+    options: LoadOptions,
+    keep: K,
+) -> io::Result<Vec<ChromSequence>>
+where
+    K: Fn(&str) -> bool,
+{
     match mode {
-        InputMode::Mmap => load_sequences_mmap(path),
-        InputMode::Stream => load_sequences_stream(path),
+        InputMode::Mmap => load_sequences_mmap(path, options, &keep),
+        InputMode::Stream => {
+            let mut reader = open_sequence_reader(path)?;
+            parse_sequences_from_reader(reader.as_mut(), options, &keep)
+        }
     }
 }
 
-fn load_sequences_stream(path: &Path) -> io::Result<Vec<ChromSequence>> {
-    let mut reader = open_input_reader(path)?;
-    parse_sequences_from_reader(reader.as_mut())
+/// Parses FASTA from any reader the way `InputMode::Stream` does.
+///
+/// Sequence lines before the first header form their own record named
+/// `chromosome_<n>`, and headers without a name fall back to the same
+/// pattern, where `n` counts every record so far (dropped empty ones
+/// included), matching the streaming scanner.
+pub fn load_sequences_from_reader(
+    reader: &mut dyn BufRead,
+    options: LoadOptions,
+) -> io::Result<Vec<ChromSequence>> {
+    parse_sequences_from_reader(reader, options, &|_| true)
 }
 
-fn parse_sequences_from_reader(reader: &mut dyn BufRead) -> io::Result<Vec<ChromSequence>> {
+fn parse_sequences_from_reader(
+    reader: &mut dyn BufRead,
+    options: LoadOptions,
+    keep: &dyn Fn(&str) -> bool,
+) -> io::Result<Vec<ChromSequence>> {
+    skip_utf8_bom(reader)?;
     let mut sequences = Vec::new();
-    let mut current_name: Option<String> = None;
+    let mut current_name: Option<OpenRecord> = None;
+    let mut skipping = false;
+    let mut occurrences = OccurrenceCounter::default();
+    let mut record_index = 0usize;
     let mut sequence: Vec<u8> = Vec::new();
-    let mut line = String::new();
+    let mut comment_lines = 0usize;
+    let mut line = Vec::new();
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
-            finalize_sequence(&mut current_name, &mut sequence, &mut sequences);
-            current_name = Some(parse_chrom_name(&line, sequences.len() + 1));
+        if is_header_line(&line) {
+            finalize_sequence(
+                &mut current_name,
+                &mut sequence,
+                &mut comment_lines,
+                &mut sequences,
+                options,
+            );
+            record_index += 1;
+            let (name, description) = parse_chrom_header_bytes(&line, record_index);
+            current_name = open_record(name, description, record_index - 1, &mut occurrences, keep);
+            skipping = current_name.is_none();
+            continue;
+        }
+        if is_comment_line(&line) {
+            comment_lines += usize::from(current_name.is_some());
             continue;
         }
-        for byte in line.bytes() {
+        if skipping {
+            continue;
+        }
+        for byte in line.iter() {
             if byte.is_ascii_whitespace() {
                 continue;
             }
-            sequence.push(byte.to_ascii_lowercase());
+            if current_name.is_none() {
+                record_index += 1;
+                let name = format!("chromosome_{record_index}");
+                current_name = open_record(name, None, record_index - 1, &mut occurrences, keep);
+                skipping = current_name.is_none();
+                if skipping {
+                    break;
+                }
+            }
+            sequence.push(*byte);
         }
     }
-    finalize_sequence(&mut current_name, &mut sequence, &mut sequences);
-    if !sequence.is_empty() {
-        sequences.push(ChromSequence {
-            name: format!("chromosome_{}", sequences.len() + 1),
-            sequence: Arc::new(std::mem::take(&mut sequence)),
-        });
-    }
+    finalize_sequence(
+        &mut current_name,
+        &mut sequence,
+        &mut comment_lines,
+        &mut sequences,
+        options,
+    );
     Ok(sequences)
 }
 
-fn load_sequences_mmap(path: &Path) -> io::Result<Vec<ChromSequence>> {
-    if is_gzip_path(path)? {
-        let mut reader = open_input_reader(path)?;
+fn load_sequences_mmap(
+    path: &Path,
+    options: LoadOptions,
+    keep: &dyn Fn(&str) -> bool,
+) -> io::Result<Vec<ChromSequence>> {
+    let compression = detect_compression(path)?;
+    if compression != Compression::Plain {
+        // Compressed input cannot be mapped, so it is decoded into memory.
+        tracing::warn!(
+            path = %path.display(),
+            ?compression,
+            "compressed input is decompressed into memory; mmap mode does not apply"
+        );
+        let mut reader = open_sequence_reader(path)?;
         let mut decompressed = Vec::new();
         reader.read_to_end(&mut decompressed)?;
-        return Ok(parse_sequences_from_bytes(&decompressed));
+        return Ok(parse_sequences_from_bytes(&decompressed, options, keep));
     }
     let file = File::open(path)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
-    Ok(parse_sequences_from_bytes(&mmap))
+    let mut map = MmapOptions::new();
+    if options.populate {
+        map.populate();
+    }
+    let mmap = unsafe { map.map(&file)? };
+    advise_sequential(&mmap);
+    Ok(parse_sequences_from_bytes(&mmap, options, keep))
 }
 
-fn parse_sequences_from_bytes(bytes: &[u8]) -> Vec<ChromSequence> {
+/// The parse reads the mapping once from start to end: ask for aggressive
+/// readahead and for the whole file to be read in the background, instead of
+/// one page fault at a time. Hints only, so a refusal is just logged.
+#[cfg(unix)]
+fn advise_sequential(mmap: &Mmap) {
+    use memmap2::Advice;
+
+    for advice in [Advice::Sequential, Advice::WillNeed] {
+        if let Err(err) = mmap.advise(advice) {
+            tracing::debug!(?advice, %err, "madvise failed");
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn advise_sequential(_mmap: &Mmap) {}
+
+fn parse_sequences_from_bytes(
+    bytes: &[u8],
+    options: LoadOptions,
+    keep: &dyn Fn(&str) -> bool,
+) -> Vec<ChromSequence> {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
     let mut sequences = Vec::new();
     let mut sequence = Vec::with_capacity(bytes.len());
-    let mut current_name: Option<String> = None;
+    let mut current_name: Option<OpenRecord> = None;
+    let mut skipping = false;
+    let mut occurrences = OccurrenceCounter::default();
+    let mut record_index = 0usize;
+    let mut comment_lines = 0usize;
     let mut at_line_start = true;
     let mut i = 0;
     while i < bytes.len() {
@@ -78,64 +236,350 @@ fn parse_sequences_from_bytes(bytes: &[u8]) -> Vec<ChromSequence> {
             i += 1;
             continue;
         }
-        if at_line_start && byte == b'>' {
-            finalize_sequence(&mut current_name, &mut sequence, &mut sequences);
-            i += 1;
+        if at_line_start && is_header_line(&bytes[i..]) {
+            finalize_sequence(
+                &mut current_name,
+                &mut sequence,
+                &mut comment_lines,
+                &mut sequences,
+                options,
+            );
             let header_start = i;
             while i < bytes.len() && bytes[i] != b'\n' && bytes[i] != b'\r' {
                 i += 1;
             }
             let header = &bytes[header_start..i];
-            current_name = Some(parse_chrom_name_bytes(header, sequences.len() + 1));
+            record_index += 1;
+            let (name, description) = parse_chrom_header_bytes(header, record_index);
+            current_name = open_record(name, description, record_index - 1, &mut occurrences, keep);
+            skipping = current_name.is_none();
             at_line_start = true;
             continue;
         }
+        if at_line_start && is_comment_line(&bytes[i..]) {
+            comment_lines += usize::from(current_name.is_some());
+            while i < bytes.len() && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                i += 1;
+            }
+            continue;
+        }
         at_line_start = false;
         if byte.is_ascii_whitespace() {
             i += 1;
             continue;
         }
-        sequence.push(byte.to_ascii_lowercase());
+        if current_name.is_none() && !skipping {
+            record_index += 1;
+            let name = format!("chromosome_{record_index}");
+            current_name = open_record(name, None, record_index - 1, &mut occurrences, keep);
+            skipping = current_name.is_none();
+        }
+        if skipping {
+            while i < bytes.len() && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                i += 1;
+            }
+            continue;
+        }
+        sequence.push(byte);
         i += 1;
     }
-    finalize_sequence(&mut current_name, &mut sequence, &mut sequences);
-    if !sequence.is_empty() {
-        let fallback =
-            current_name.unwrap_or_else(|| format!("chromosome_{}", sequences.len() + 1));
-        sequences.push(ChromSequence {
-            name: fallback,
-            sequence: Arc::new(std::mem::take(&mut sequence)),
-        });
-    }
+    finalize_sequence(
+        &mut current_name,
+        &mut sequence,
+        &mut comment_lines,
+        &mut sequences,
+        options,
+    );
     sequences
 }
 
+/// Name, occurrence, header description and file index of the record being
+/// read.
+type OpenRecord = (String, usize, Option<String>, usize);
+
+/// Tags the record starting here, or returns `None` when `keep` rejects its
+/// name. Rejected names still count towards occurrences.
+fn open_record(
+    name: String,
+    description: Option<String>,
+    index: usize,
+    occurrences: &mut OccurrenceCounter,
+    keep: &dyn Fn(&str) -> bool,
+) -> Option<OpenRecord> {
+    let (name, occurrence) = occurrences.tag(name);
+    if keep(&name) {
+        Some((name, occurrence, description, index))
+    } else {
+        tracing::debug!(name = %name, occurrence, "record skipped by name filter");
+        None
+    }
+}
+
 fn finalize_sequence(
-    current_name: &mut Option<String>,
+    current_name: &mut Option<OpenRecord>,
     sequence: &mut Vec<u8>,
+    comment_lines: &mut usize,
     sequences: &mut Vec<ChromSequence>,
+    options: LoadOptions,
 ) {
-    if let Some(name) = current_name.take()
-        && !sequence.is_empty()
+    let comment_lines = std::mem::take(comment_lines);
+    if let Some((name, occurrence, description, index)) = current_name.take()
+        && (options.keep_empty || !sequence.is_empty())
     {
         sequences.push(ChromSequence {
             name,
+            occurrence,
+            description,
             sequence: Arc::new(std::mem::take(sequence)),
+            comment_lines,
+            index,
         });
     }
 }
 
-pub(crate) fn parse_chrom_name(line: &str, index: usize) -> String {
-    let header = line.trim_start_matches('>');
+/// Name, occurrence and length of one FASTA record, as the loaders would
+/// report it, without keeping its sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub name: String,
+    pub occurrence: usize,
+    pub sequence_len: usize,
+    /// Position of the record among every header in the file, from 0, as in
+    /// [`ChromSequence::index`].
+    pub index: usize,
+}
+
+/// Lists the records [`load_sequences_from_path_with_options`] would return.
+/// Sequence lines are only counted, never buffered, so this stays cheap on
+/// whole genomes.
+pub fn read_record_headers(path: &Path, options: LoadOptions) -> io::Result<Vec<RecordHeader>> {
+    read_record_headers_with_filter(path, options, |_| true)
+}
+
+/// [`read_record_headers`] for the records
+/// [`load_sequences_from_path_with_filter`] would return.
+pub fn read_record_headers_with_filter<K>(
+    path: &Path,
+    options: LoadOptions,
+    keep: K,
+) -> io::Result<Vec<RecordHeader>>
+where
+    K: Fn(&str) -> bool,
+{
+    let mut reader = open_sequence_reader(path)?;
+    skip_utf8_bom(&mut reader)?;
+    let mut records = Vec::new();
+    let mut current_name: Option<OpenRecord> = None;
+    let mut skipping = false;
+    let mut occurrences = OccurrenceCounter::default();
+    let mut record_index = 0usize;
+    let mut sequence_len = 0usize;
+    let mut line = Vec::new();
+    let finalize = |current_name: &mut Option<OpenRecord>,
+                    sequence_len: &mut usize,
+                    records: &mut Vec<RecordHeader>| {
+        if let Some((name, occurrence, _, index)) = current_name.take()
+            && (options.keep_empty || *sequence_len > 0)
+        {
+            records.push(RecordHeader {
+                name,
+                occurrence,
+                sequence_len: std::mem::take(sequence_len),
+                index,
+            });
+        }
+    };
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if is_header_line(&line) {
+            finalize(&mut current_name, &mut sequence_len, &mut records);
+            record_index += 1;
+            let (name, description) = parse_chrom_header_bytes(&line, record_index);
+            current_name =
+                open_record(name, description, record_index - 1, &mut occurrences, &keep);
+            skipping = current_name.is_none();
+            continue;
+        }
+        if skipping || is_comment_line(&line) {
+            continue;
+        }
+        let line_len = line
+            .iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .count();
+        if line_len > 0 && current_name.is_none() {
+            record_index += 1;
+            let name = format!("chromosome_{record_index}");
+            current_name = open_record(name, None, record_index - 1, &mut occurrences, &keep);
+            skipping = current_name.is_none();
+            if skipping {
+                continue;
+            }
+        }
+        sequence_len += line_len;
+    }
+    finalize(&mut current_name, &mut sequence_len, &mut records);
+    Ok(records)
+}
+
+pub(crate) fn parse_chrom_name(line: &[u8], index: usize) -> String {
+    parse_chrom_header_bytes(line, index).0
+}
+
+/// Splits a header line into its name token and the description after it,
+/// e.g. `>NC_000913.3 Escherichia coli` into `NC_000913.3` and
+/// `Escherichia coli`. Whitespace inside the description, tabs included, is
+/// kept; a header with nothing after the name has no description.
+fn parse_chrom_header(line: &str, index: usize) -> (String, Option<String>) {
+    let header = line
+        .trim_start_matches([' ', '\t'])
+        .trim_start_matches('>')
+        .trim_start();
+    let (name, rest) = header
+        .split_once(char::is_whitespace)
+        .unwrap_or((header, ""));
+    let name = if name.is_empty() {
+        format!("chromosome_{index}")
+    } else {
+        name.to_string()
+    };
+    let description = rest.trim();
+    (
+        name,
+        (!description.is_empty()).then(|| description.to_string()),
+    )
+}
+
+/// [`parse_chrom_header`] for raw bytes. Every reader works on bytes, so
+/// invalid UTF-8 in a header only costs those characters (each becomes
+/// U+FFFD) rather than failing the read or the whole name.
+pub(crate) fn parse_chrom_header_bytes(header: &[u8], index: usize) -> (String, Option<String>) {
+    parse_chrom_header(&String::from_utf8_lossy(header), index)
+}
+
+/// Bases per line in the FASTA written by [`write_reverse_complement`].
+pub const FASTA_LINE_WIDTH: usize = 60;
+
+/// Writes the reverse complement of every record of the FASTA at `path`,
+/// plain or compressed, to `out`; see
+/// [`write_reverse_complement_from_reader`].
+pub fn write_reverse_complement<W: Write>(path: &Path, out: &mut W) -> io::Result<usize> {
+    let mut reader = open_sequence_reader(path)?;
+    write_reverse_complement_from_reader(reader.as_mut(), out)
+}
+
+/// Writes every record of `reader` in file order with its bases passed
+/// through [`reverse_complement`] and wrapped at [`FASTA_LINE_WIDTH`]. Each
+/// header keeps its text with ` rc` appended, so the record keeps its name.
+/// Records are read and written one at a time: memory use follows the
+/// longest record, not the file. `;` comment lines are dropped, and sequence
+/// before the first header or a header without a name is named
+/// `chromosome_<n>`, as the loaders name it. Returns the number of records
+/// written, empty ones included.
+pub fn write_reverse_complement_from_reader<W: Write>(
+    reader: &mut dyn BufRead,
+    out: &mut W,
+) -> io::Result<usize> {
+    skip_utf8_bom(reader)?;
+    let mut records = 0usize;
+    let mut header: Option<Vec<u8>> = None;
+    let mut sequence: Vec<u8> = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if is_header_line(&line) {
+            if let Some(header) = header.take() {
+                write_reverse_complement_record(out, &header, &mut sequence)?;
+            }
+            records += 1;
+            header = Some(reverse_complement_header(&line, records));
+            continue;
+        }
+        if is_comment_line(&line) {
+            continue;
+        }
+        let start = sequence.len();
+        sequence.extend(line.iter().filter(|byte| !byte.is_ascii_whitespace()));
+        if header.is_none() && sequence.len() > start {
+            records += 1;
+            header = Some(format!("chromosome_{records} rc").into_bytes());
+        }
+    }
+    if let Some(header) = header {
+        write_reverse_complement_record(out, &header, &mut sequence)?;
+    }
+    Ok(records)
+}
+
+/// The header text of `line` without `>` and surrounding whitespace, or
+/// `chromosome_<index>` when there is none, followed by ` rc`.
+fn reverse_complement_header(line: &[u8], index: usize) -> Vec<u8> {
+    let text = line.trim_ascii();
+    let text = text.strip_prefix(b">").unwrap_or(text).trim_ascii();
+    let mut header = if text.is_empty() {
+        format!("chromosome_{index}").into_bytes()
+    } else {
+        text.to_vec()
+    };
+    header.extend_from_slice(b" rc");
     header
-        .split_whitespace()
-        .next()
-        .map(|s| s.to_string())
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| format!("chromosome_{index}"))
 }
 
-pub(crate) fn parse_chrom_name_bytes(header: &[u8], index: usize) -> String {
-    let header_str = std::str::from_utf8(header).unwrap_or("");
-    parse_chrom_name(header_str, index)
+/// Writes one record of [`write_reverse_complement_from_reader`] and
+/// empties `sequence` for the next.
+fn write_reverse_complement_record<W: Write>(
+    out: &mut W,
+    header: &[u8],
+    sequence: &mut Vec<u8>,
+) -> io::Result<()> {
+    reverse_complement(sequence);
+    out.write_all(b">")?;
+    out.write_all(header)?;
+    out.write_all(b"\n")?;
+    for line in sequence.chunks(FASTA_LINE_WIDTH) {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    sequence.clear();
+    Ok(())
+}
+
+/// Reverse-complements `sequence` in place. IUPAC ambiguity codes map to
+/// their complements (`R`/`Y`, `K`/`M`, `B`/`V`, `D`/`H`; `S`, `W` and `N`
+/// pair with themselves), `U` pairs with `A`, case is kept, and any other
+/// byte is only moved.
+pub fn reverse_complement(sequence: &mut [u8]) {
+    sequence.reverse();
+    for byte in sequence.iter_mut() {
+        *byte = complement_base(*byte);
+    }
+}
+
+fn complement_base(byte: u8) -> u8 {
+    let complement = match byte.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'G' => b'C',
+        b'C' => b'G',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => return byte,
+    };
+    if byte.is_ascii_lowercase() {
+        complement.to_ascii_lowercase()
+    } else {
+        complement
+    }
 }
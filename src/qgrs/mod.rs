@@ -1,37 +1,108 @@
+// Modules outside `core` follow the feature that brings in what they use;
+// see `[features]` in Cargo.toml.
+#[cfg(feature = "export")]
+pub mod diff;
+pub mod gruns;
+#[cfg(feature = "mmap")]
+pub mod intervals;
+#[cfg(feature = "mmap")]
+pub mod io;
+#[cfg(feature = "mmap")]
+pub mod postprocess;
+#[cfg(feature = "mmap")]
 pub mod stream;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testing;
+#[cfg(feature = "mmap")]
+pub mod transcripts;
+#[cfg(all(feature = "mmap", feature = "export"))]
+pub mod verify;
 
+mod cancel;
+#[cfg(feature = "mmap")]
+mod checkpoint;
 mod chunks;
 mod consolidation;
 mod data;
+mod error;
+#[cfg(feature = "export")]
 mod export;
+#[cfg(feature = "mmap")]
 mod input;
+#[cfg(feature = "mmap")]
 mod loaders;
+mod record;
+mod results;
 mod search;
 #[cfg(test)]
 mod tests;
 
+pub use cancel::CancellationToken;
+#[cfg(feature = "mmap")]
+pub use checkpoint::{DEFAULT_CHECKPOINT_PERIOD, StreamCheckpoint};
 pub use chunks::{
-    find_owned_bytes, find_owned_bytes_with_limits, find_owned_bytes_with_topology,
+    find_in_bytes, find_in_str, find_owned, find_owned_bytes, find_owned_bytes_with_cancellation,
+    find_owned_bytes_with_config, find_owned_bytes_with_limits, find_owned_bytes_with_topology,
     find_owned_bytes_with_topology_and_base,
 };
-pub use consolidation::{consolidate_g4s, consolidate_g4s_with_topology};
+pub use consolidation::{
+    Family, FamilyPolicy, FamilyStats, consolidate_g4s, consolidate_g4s_with_policy,
+    consolidate_g4s_with_topology, consolidate_with_families,
+    consolidate_with_families_with_topology,
+};
+pub(crate) use data::OccurrenceCounter;
 pub use data::{
-    ChromSequence, DEFAULT_MAX_G4_LENGTH, DEFAULT_MAX_RUN, InputMode, QuartetBase, ScanLimits,
-    SequenceTopology,
+    AUTO_MIN_SCORE_FRACTION, ChromSequence, DEFAULT_MAX_BUFFERED_RECORDS, DEFAULT_MAX_G4_LENGTH,
+    DEFAULT_MAX_RUN, DeliveryOrder, InputMode, LoopPolicy, MAX_CHUNK_SIZE, MinScore, QuartetBase,
+    ScanConfig, ScanLimits, SequenceTopology, WHOLE_RECORD_MAX_LEN,
 };
+pub use error::QgrsError;
+#[cfg(feature = "export")]
 pub use export::{
-    ExportError, render_csv_results, render_csv_results_with_projection, render_family_ranges_csv,
-    render_family_ranges_csv_with_projection, write_parquet_family_ranges,
-    write_parquet_family_ranges_with_projection, write_parquet_results,
+    ColumnValues, DEFAULT_PARQUET_BATCH_ROWS, ExportError, ExtraColumn, ParquetSink,
+    build_record_batch, render_csv_results, render_csv_results_with_columns,
+    render_csv_results_with_coordinates, render_csv_results_with_projection,
+    render_family_ranges_bed, render_family_ranges_csv, render_family_ranges_csv_with_coordinates,
+    render_family_ranges_csv_with_projection, render_qgrs_mapper, write_arrow_ipc_results,
+    write_arrow_ipc_results_with_coordinates, write_parquet_family_ranges,
+    write_parquet_family_ranges_with_coordinates, write_parquet_family_ranges_with_projection,
+    write_parquet_results, write_parquet_results_batched, write_parquet_results_with_columns,
+    write_parquet_results_with_coordinates, write_parquet_results_with_metadata,
     write_parquet_results_with_projection,
 };
-pub use loaders::load_sequences_from_path;
-pub use search::G4;
+#[cfg(feature = "mmap")]
+pub use loaders::{
+    FASTA_LINE_WIDTH, LoadOptions, RecordHeader, load_sequences_from_path,
+    load_sequences_from_path_with_filter, load_sequences_from_path_with_options,
+    load_sequences_from_reader, mmap_hints, read_record_headers, read_record_headers_with_filter,
+    reverse_complement, write_reverse_complement, write_reverse_complement_from_reader,
+};
+#[cfg(feature = "export")]
+pub use record::parse_parquet_records;
+pub use record::{
+    COORDINATES_METADATA_KEY, CoordinateSystem, G4Record, MOTIF_TYPE_COLUMN, MotifType,
+    RECORD_COLUMNS, Strand, parse_csv_records,
+};
+pub use results::{
+    ScanMetrics, SearchResults, SearchStats, refilter, scan_many, scan_short, search_chromosome,
+    search_chromosome_with_anchors, search_chromosome_with_cancellation,
+};
+pub use search::{G4, detach_all, max_possible_score};
 
-#[cfg(test)]
-pub(crate) use chunks::find_with_sequence;
+#[cfg(feature = "mmap")]
+pub(crate) use cancel::is_cancelled;
+pub(crate) use chunks::find_owned_bytes_for_config;
+#[cfg(feature = "mmap")]
 pub(crate) use chunks::{
     chunk_size_for_limits, compute_chunk_overlap, retain_circular_raw_hits, shift_g4,
 };
-pub(crate) use loaders::parse_chrom_name;
+#[cfg(all(test, feature = "mmap", feature = "export"))]
+pub(crate) use chunks::{find_with_sequence, window_size_for, with_chunk_overlap};
+#[cfg(feature = "mmap")]
+pub(crate) use consolidation::FamilyBuilder;
+pub(crate) use consolidation::{count_distinct, winners};
+#[cfg(feature = "mmap")]
+pub(crate) use loaders::{parse_chrom_header_bytes, parse_chrom_name};
+#[cfg(feature = "mmap")]
 pub(crate) use search::find_raw_bytes_no_chunking;
+pub(crate) use search::{HitCap, RunCollector, SearchCounters, SearchHooks, scan_short_linear};
@@ -0,0 +1,531 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::qgrs::intervals::{Interval, IntervalIndex};
+use crate::qgrs::record::CoordinateSystem;
+use crate::qgrs::search::G4;
+
+/// Position of one hit's score among the hits it was ranked with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreRank {
+    /// Dense rank by score, 1 for the best score; equal scores share a rank.
+    pub rank: usize,
+    /// Share of hits scoring at or below this one, in percent (two decimals).
+    pub percentile: f64,
+}
+
+/// Ranks `g4s` by score, returning one entry per hit in input order.
+pub fn rank_by_score(g4s: &[G4]) -> Vec<ScoreRank> {
+    let mut scores: Vec<i32> = g4s.iter().map(|g4| g4.score).collect();
+    scores.sort_unstable();
+    let mut distinct = scores.clone();
+    distinct.dedup();
+
+    let total = scores.len() as f64;
+    g4s.iter()
+        .map(|g4| {
+            let rank = distinct.len() - distinct.partition_point(|&score| score < g4.score);
+            let at_or_below = scores.partition_point(|&score| score <= g4.score);
+            let percentile = (at_or_below as f64 * 10_000.0 / total).round() / 100.0;
+            ScoreRank { rank, percentile }
+        })
+        .collect()
+}
+
+/// Drops hits on `chrom` that overlap `exclude`, returning how many were
+/// removed. With `min_overlap` set, a hit is only dropped when at least that
+/// fraction of its bases is covered; otherwise any overlap counts.
+pub fn exclude_overlapping(
+    hits: &mut Vec<G4>,
+    chrom: &str,
+    exclude: &IntervalIndex,
+    min_overlap: Option<f64>,
+) -> usize {
+    let before = hits.len();
+    hits.retain(|hit| {
+        let (start, end) = hit.interval();
+        let covered = exclude.overlap_len(chrom, start, end);
+        let excluded = match min_overlap {
+            Some(fraction) => covered > 0 && covered as f64 >= fraction * hit.length as f64,
+            None => covered > 0,
+        };
+        !excluded
+    });
+    before - hits.len()
+}
+
+/// Hits on opposite strands of one locus, see [`pair_strands`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrandPair {
+    /// 1-based inclusive span of the locus' overlapping hits on both strands.
+    pub start: usize,
+    pub end: usize,
+    /// Index of the locus' best plus-strand hit.
+    pub plus: usize,
+    /// Index of the locus' best minus-strand hit.
+    pub minus: usize,
+}
+
+/// Pairs hits on opposite strands whose plus-strand intervals overlap. A
+/// sorted sweep over both lists chains overlapping hits into loci, and every
+/// locus holding hits of both strands yields one pair with its best hit per
+/// strand (highest score, then earliest start). Pairs come in locus order.
+pub fn pair_strands(plus: &[G4], minus: &[G4]) -> Vec<StrandPair> {
+    // (start, end, strand, index), with strand 0 for plus and 1 for minus.
+    let mut hits: Vec<(usize, usize, usize, usize)> = plus
+        .iter()
+        .enumerate()
+        .map(|(index, hit)| (hit.start, hit.end, 0, index))
+        .chain(
+            minus
+                .iter()
+                .enumerate()
+                .map(|(index, hit)| (hit.start, hit.end, 1, index)),
+        )
+        .collect();
+    hits.sort_unstable();
+
+    let strands = [plus, minus];
+    let mut pairs = Vec::new();
+    let mut locus: Option<(usize, usize, [Option<usize>; 2])> = None;
+    let close = |pairs: &mut Vec<StrandPair>,
+                 (start, end, best): (usize, usize, [Option<usize>; 2])| {
+        if let [Some(plus), Some(minus)] = best {
+            pairs.push(StrandPair {
+                start,
+                end,
+                plus,
+                minus,
+            });
+        }
+    };
+    for (start, end, strand, index) in hits {
+        match locus.as_mut() {
+            Some((_, locus_end, best)) if start <= *locus_end => {
+                *locus_end = (*locus_end).max(end);
+                let score = strands[strand][index].score;
+                if best[strand].is_none_or(|current| score > strands[strand][current].score) {
+                    best[strand] = Some(index);
+                }
+            }
+            _ => {
+                if let Some(done) = locus.take() {
+                    close(&mut pairs, done);
+                }
+                let mut best = [None; 2];
+                best[strand] = Some(index);
+                locus = Some((start, end, best));
+            }
+        }
+    }
+    if let Some(done) = locus {
+        close(&mut pairs, done);
+    }
+    pairs
+}
+
+/// `locus_start,locus_end` followed by `start,end,score,sequence` of the plus
+/// and then the minus hit of each pair.
+pub fn render_strand_pairs_csv(pairs: &[StrandPair], plus: &[G4], minus: &[G4]) -> String {
+    render_strand_pairs_csv_with_coordinates(
+        pairs,
+        plus,
+        minus,
+        CoordinateSystem::OneBasedInclusive,
+    )
+}
+
+/// [`render_strand_pairs_csv`] with every start column in `coordinates`.
+pub fn render_strand_pairs_csv_with_coordinates(
+    pairs: &[StrandPair],
+    plus: &[G4],
+    minus: &[G4],
+    coordinates: CoordinateSystem,
+) -> String {
+    let mut out = String::from(
+        "locus_start,locus_end,plus_start,plus_end,plus_score,plus_sequence,\
+         minus_start,minus_end,minus_score,minus_sequence\n",
+    );
+    for pair in pairs {
+        let (plus, minus) = (&plus[pair.plus], &minus[pair.minus]);
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            coordinates.start(pair.start),
+            pair.end,
+            coordinates.start(plus.start),
+            plus.end,
+            plus.score,
+            plus.sequence(),
+            coordinates.start(minus.start),
+            minus.end,
+            minus.score,
+            minus.sequence()
+        ));
+    }
+    out
+}
+
+/// Totals of one interval of an [`IntervalAggregate`].
+#[derive(Clone, Debug, Default)]
+struct IntervalTotals {
+    count: usize,
+    best_score: Option<i32>,
+    /// The overlapping hits' spans clipped to the interval.
+    spans: Vec<(usize, usize)>,
+}
+
+/// One interval's row of [`IntervalAggregate::rows`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntervalRow<'a> {
+    pub name: &'a str,
+    pub chrom: &'a str,
+    /// Hits overlapping the interval by at least one base.
+    pub count: usize,
+    /// Highest score among them; `None` without hits.
+    pub best_score: Option<i32>,
+    /// Interval bases inside at least one hit.
+    pub covered_bases: usize,
+}
+
+/// Hit totals for every interval of an [`IntervalIndex`], e.g. G4s per gene.
+/// A hit overlapping several intervals counts towards each, and intervals no
+/// hit reaches keep a count of 0.
+#[derive(Debug)]
+pub struct IntervalAggregate {
+    index: IntervalIndex,
+    totals: HashMap<String, Vec<IntervalTotals>>,
+}
+
+impl IntervalAggregate {
+    pub fn new(index: IntervalIndex) -> Self {
+        let mut aggregate = Self {
+            index,
+            totals: HashMap::new(),
+        };
+        aggregate.clear();
+        aggregate
+    }
+
+    /// Drops every hit added so far.
+    pub fn clear(&mut self) {
+        self.totals = self
+            .index
+            .chromosomes()
+            .map(|(chrom, intervals)| {
+                let totals = vec![IntervalTotals::default(); intervals.len()];
+                (chrom.to_string(), totals)
+            })
+            .collect();
+    }
+
+    pub fn add(&mut self, chrom: &str, hits: &[G4]) {
+        let Some(totals) = self.totals.get_mut(chrom) else {
+            return;
+        };
+        let intervals = self.index.intervals(chrom);
+        for hit in hits {
+            let (start, end) = hit.interval();
+            for position in self.index.overlapping_positions(chrom, start, end) {
+                let interval = &intervals[position];
+                let entry = &mut totals[position];
+                entry.count += 1;
+                entry.best_score = entry.best_score.max(Some(hit.score));
+                entry
+                    .spans
+                    .push((start.max(interval.start), end.min(interval.end)));
+            }
+        }
+    }
+
+    /// One row per interval, by chromosome name and then interval start.
+    pub fn rows(&self) -> Vec<IntervalRow<'_>> {
+        let mut chromosomes: Vec<(&str, &[Interval])> = self.index.chromosomes().collect();
+        chromosomes.sort_unstable_by_key(|&(chrom, _)| chrom);
+        let mut rows = Vec::new();
+        for (chrom, intervals) in chromosomes {
+            for (interval, totals) in intervals.iter().zip(&self.totals[chrom]) {
+                rows.push(IntervalRow {
+                    name: &interval.name,
+                    chrom,
+                    count: totals.count,
+                    best_score: totals.best_score,
+                    covered_bases: union_len(&totals.spans),
+                });
+            }
+        }
+        rows
+    }
+
+    /// `name,chrom,count,best_gscore,covered_bases`, one line per
+    /// [`IntervalAggregate::rows`] entry; `best_gscore` is empty at count 0.
+    pub fn render_csv(&self) -> String {
+        let mut out = String::from("name,chrom,count,best_gscore,covered_bases\n");
+        for row in self.rows() {
+            let best = row.best_score.map(|score| score.to_string());
+            out.push_str(&format!(
+                "{},{},{},{},{}\n",
+                row.name,
+                row.chrom,
+                row.count,
+                best.unwrap_or_default(),
+                row.covered_bases
+            ));
+        }
+        out
+    }
+}
+
+/// Bases inside at least one of the 1-based inclusive `spans`.
+fn union_len(spans: &[(usize, usize)]) -> usize {
+    let mut sorted = spans.to_vec();
+    sorted.sort_unstable();
+    let mut covered = 0;
+    let mut next_uncovered = 0;
+    for (start, end) in sorted {
+        let from = start.max(next_uncovered);
+        if from <= end {
+            covered += end - from + 1;
+            next_uncovered = end + 1;
+        }
+    }
+    covered
+}
+
+#[derive(Debug, Default)]
+struct MotifStats {
+    count: usize,
+    score_sum: i64,
+    chromosomes: BTreeSet<String>,
+}
+
+/// Occurrence counts of each distinct motif sequence (uppercased) across the
+/// chromosomes of a run. Memory grows with the number of distinct motifs.
+#[derive(Debug, Default)]
+pub struct MotifSummary {
+    motifs: HashMap<String, MotifStats>,
+}
+
+impl MotifSummary {
+    pub fn add(&mut self, chrom: &str, hits: &[G4]) {
+        for hit in hits {
+            let stats = self
+                .motifs
+                .entry(hit.sequence().to_ascii_uppercase())
+                .or_default();
+            stats.count += 1;
+            stats.score_sum += i64::from(hit.score);
+            if !stats.chromosomes.contains(chrom) {
+                stats.chromosomes.insert(chrom.to_string());
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: MotifSummary) {
+        for (sequence, theirs) in other.motifs {
+            let ours = self.motifs.entry(sequence).or_default();
+            ours.count += theirs.count;
+            ours.score_sum += theirs.score_sum;
+            ours.chromosomes.extend(theirs.chromosomes);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.motifs.is_empty()
+    }
+
+    /// `sequence,count,mean_gscore,chromosomes` sorted by count (descending),
+    /// then sequence; chromosome names are `;`-joined in sorted order.
+    pub fn render_csv(&self) -> String {
+        let mut rows: Vec<(&String, &MotifStats)> = self.motifs.iter().collect();
+        rows.sort_by(|(a_seq, a), (b_seq, b)| b.count.cmp(&a.count).then(a_seq.cmp(b_seq)));
+        let mut out = String::from("sequence,count,mean_gscore,chromosomes\n");
+        for (sequence, stats) in rows {
+            let mean = stats.score_sum as f64 / stats.count as f64;
+            let chromosomes: Vec<&str> = stats.chromosomes.iter().map(String::as_str).collect();
+            out.push_str(&format!(
+                "{sequence},{},{mean:.2},{}\n",
+                stats.count,
+                chromosomes.join(";")
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        IntervalAggregate, MotifSummary, ScoreRank, StrandPair, exclude_overlapping, pair_strands,
+        rank_by_score, render_strand_pairs_csv,
+    };
+    use crate::qgrs::find_owned_bytes;
+    use crate::qgrs::intervals::IntervalIndex;
+
+    #[test]
+    fn ties_share_rank_and_percentile() {
+        let raw = find_owned_bytes(Arc::new(b"ggggaggggaggggagggg".to_vec()), 4, 17);
+        let hits: Vec<_> = [40, 84, 40, 12, 84, 84]
+            .into_iter()
+            .map(|score| {
+                let mut g4 = raw[0].clone();
+                g4.score = score;
+                g4
+            })
+            .collect();
+
+        let ranks = rank_by_score(&hits);
+        let expected = [
+            (2, 50.0),
+            (1, 100.0),
+            (2, 50.0),
+            (3, 16.67),
+            (1, 100.0),
+            (1, 100.0),
+        ];
+        for (actual, (rank, percentile)) in ranks.iter().zip(expected) {
+            assert_eq!(*actual, ScoreRank { rank, percentile });
+        }
+        assert!(rank_by_score(&[]).is_empty());
+    }
+
+    #[test]
+    fn exclusion_uses_inclusive_hit_coordinates_and_min_overlap() {
+        let raw = find_owned_bytes(Arc::new(b"ggggaggggaggggagggg".to_vec()), 4, 17);
+        let at = |start: usize| {
+            let mut g4 = raw[0].clone();
+            g4.start = start;
+            g4.end = start + g4.length - 1;
+            g4
+        };
+        // The hits span 1..=19, 101..=119 and 191..=209.
+        let hits = vec![at(1), at(101), at(191)];
+        // BED 19-30 covers 20..=30 (just past the first hit); 90-130 contains
+        // the second hit; 207-300 covers 2 of the third hit's 19 bases.
+        let bed = "chr1\t19\t30\nchr1\t90\t130\nchr1\t207\t300\n";
+        let index = IntervalIndex::from_bed_reader(bed.as_bytes()).unwrap();
+
+        let mut any = hits.clone();
+        assert_eq!(exclude_overlapping(&mut any, "chr1", &index, None), 2);
+        assert_eq!(any.iter().map(|g4| g4.start).collect::<Vec<_>>(), [1]);
+
+        let mut half = hits.clone();
+        assert_eq!(exclude_overlapping(&mut half, "chr1", &index, Some(0.5)), 1);
+        assert_eq!(half.iter().map(|g4| g4.start).collect::<Vec<_>>(), [1, 191]);
+
+        let mut other = hits;
+        assert_eq!(exclude_overlapping(&mut other, "chr2", &index, None), 0);
+    }
+
+    #[test]
+    fn strand_pairs_join_overlapping_loci_and_keep_the_best_hit_per_strand() {
+        let raw = find_owned_bytes(Arc::new(b"ggggaggggaggggagggg".to_vec()), 4, 17);
+        let at = |start: usize, length: usize, score: i32| {
+            let mut g4 = raw[0].clone();
+            g4.start = start;
+            g4.end = start + length - 1;
+            g4.score = score;
+            g4
+        };
+        // Loci: 1..=30 (both strands, the 25..=30 minus hit chained in via
+        // the second plus hit), 100..=119 (plus only), 200..=219 and
+        // 215..=230 (both strands, touching at 215..=219).
+        let plus = vec![
+            at(1, 20, 40),
+            at(15, 12, 50),
+            at(100, 20, 60),
+            at(200, 20, 30),
+        ];
+        let minus = vec![at(25, 6, 20), at(215, 16, 35), at(300, 20, 90)];
+
+        let pairs = pair_strands(&plus, &minus);
+        assert_eq!(
+            pairs,
+            [
+                StrandPair {
+                    start: 1,
+                    end: 30,
+                    plus: 1,
+                    minus: 0
+                },
+                StrandPair {
+                    start: 200,
+                    end: 230,
+                    plus: 3,
+                    minus: 1
+                },
+            ]
+        );
+        let csv = render_strand_pairs_csv(&pairs, &plus, &minus);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("locus_start,locus_end,plus_start,"));
+        assert!(lines[2].starts_with("200,230,200,219,30,"));
+        assert!(pair_strands(&plus, &[]).is_empty());
+    }
+
+    #[test]
+    fn motif_summary_counts_across_chromosomes_and_merges() {
+        let hit = find_owned_bytes(Arc::new(b"ggggaggggaggggagggg".to_vec()), 4, 17).remove(0);
+        let other = find_owned_bytes(Arc::new(b"gggttgggttgggttggg".to_vec()), 3, 17).remove(0);
+
+        let mut left = MotifSummary::default();
+        left.add("chr1", std::slice::from_ref(&hit));
+        let mut right = MotifSummary::default();
+        right.add("chr2", &[hit.clone(), other.clone()]);
+        left.merge(right);
+
+        let csv = left.render_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "sequence,count,mean_gscore,chromosomes");
+        assert_eq!(
+            lines[1],
+            format!(
+                "GGGGAGGGGAGGGGAGGGG,2,{:.2},chr1;chr2",
+                f64::from(hit.score)
+            )
+        );
+        assert!(lines[2].starts_with("GGGTTGGGTTGGGTTGGG,1,"));
+        assert!(lines[2].ends_with(",chr2"));
+    }
+
+    #[test]
+    fn interval_aggregate_counts_hits_in_nested_genes() {
+        let raw = find_owned_bytes(Arc::new(b"ggggaggggaggggagggg".to_vec()), 4, 17);
+        let at = |start: usize, length: usize, score: i32| {
+            let mut g4 = raw[0].clone();
+            g4.start = start;
+            g4.end = start + length - 1;
+            g4.score = score;
+            g4
+        };
+        // outer covers 1..=100 and nests inner 21..=40; lonely gets no hits.
+        let bed = "chr1\t0\t100\touter\nchr1\t20\t40\tinner\nchr2\t0\t50\tlonely\n";
+        let index = IntervalIndex::from_bed_reader(bed.as_bytes()).unwrap();
+        let mut aggregate = IntervalAggregate::new(index);
+
+        // 15..=24 reaches 4 bases into inner; 30..=49 runs 11 bases into it.
+        aggregate.add("chr1", &[at(15, 10, 30), at(30, 20, 55)]);
+        // The minus strand's 35..=54 overlaps 30..=49 and 95..=110 runs past
+        // outer's end; chr3 has no intervals at all.
+        aggregate.add("chr1", &[at(35, 20, 40), at(95, 16, 20)]);
+        aggregate.add("chr3", &[at(1, 20, 90)]);
+
+        let csv = aggregate.render_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "name,chrom,count,best_gscore,covered_bases",
+                // 15..=24, 30..=54 and 95..=100.
+                "outer,chr1,4,55,41",
+                // 21..=24 and 30..=40.
+                "inner,chr1,3,55,15",
+                "lonely,chr2,0,,0",
+            ]
+        );
+
+        aggregate.clear();
+        assert!(aggregate.rows().iter().all(|row| row.count == 0));
+    }
+}
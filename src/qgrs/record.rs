@@ -0,0 +1,378 @@
+use std::io::{self, BufRead};
+
+#[cfg(feature = "export")]
+use arrow_array::{Array, Int32Array, RecordBatch, StringArray, UInt64Array};
+#[cfg(feature = "export")]
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+#[cfg(feature = "export")]
+use parquet::file::reader::ChunkReader;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::qgrs::data::QuartetBase;
+use crate::qgrs::search::G4;
+
+/// Columns every CSV/Parquet result file starts with, in order; opt-in
+/// columns such as `rank` follow them.
+pub const RECORD_COLUMNS: [&str; 9] = [
+    "start", "end", "length", "tetrads", "y1", "y2", "y3", "score", "sequence",
+];
+
+/// Parquet footer key (and Arrow schema metadata key) naming the
+/// [`CoordinateSystem`] of a results file.
+pub const COORDINATES_METADATA_KEY: &str = "qgrs.coordinates";
+
+/// How exported `start`/`end` columns count positions. Hits are always held
+/// 1-based inclusive; [`ZeroBasedHalfOpen`](Self::ZeroBasedHalfOpen) only
+/// moves `start` back by one when rendering, so `end - start` is the length
+/// and the values paste into BED-style tools unchanged. BED output is
+/// 0-based half-open whichever is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CoordinateSystem {
+    #[default]
+    OneBasedInclusive,
+    ZeroBasedHalfOpen,
+}
+
+impl CoordinateSystem {
+    /// `start` of a 1-based inclusive interval in this system; `end` is the
+    /// same in both.
+    pub const fn start(self, one_based: usize) -> usize {
+        match self {
+            Self::OneBasedInclusive => one_based,
+            Self::ZeroBasedHalfOpen => one_based - 1,
+        }
+    }
+
+    /// The value recorded under [`COORDINATES_METADATA_KEY`] and in
+    /// `manifest.json`.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::OneBasedInclusive => "1-based-inclusive",
+            Self::ZeroBasedHalfOpen => "0-based-half-open",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Strand {
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Plus,
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Minus,
+}
+
+/// Opt-in column naming each hit's [`MotifType`] when one file holds both.
+pub const MOTIF_TYPE_COLUMN: &str = "motif_type";
+
+/// Which quadruplex a hit is, from the tetrad base it was scanned for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MotifType {
+    /// G-tetrads.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "G4"))]
+    G4,
+    /// C-tetrads.
+    #[cfg_attr(feature = "serde", serde(rename = "i-motif"))]
+    IMotif,
+}
+
+impl MotifType {
+    /// The value written to the `motif_type` column.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::G4 => "G4",
+            Self::IMotif => "i-motif",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "G4" => Some(Self::G4),
+            "i-motif" => Some(Self::IMotif),
+            _ => None,
+        }
+    }
+
+    pub const fn is_g4(&self) -> bool {
+        matches!(self, Self::G4)
+    }
+}
+
+impl From<QuartetBase> for MotifType {
+    fn from(base: QuartetBase) -> Self {
+        match base {
+            QuartetBase::G => Self::G4,
+            QuartetBase::C => Self::IMotif,
+        }
+    }
+}
+
+/// Owned, plain-data form of a [`G4`] with the same 1-based inclusive
+/// coordinates, for serialization and for reading results back. `chrom` and
+/// `strand` are not part of the exported columns and are left unset by
+/// `From<&G4>`; `motif_type` defaults to G4, which a `G4` cannot tell apart
+/// from an i-motif on its own.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct G4Record {
+    pub start: usize,
+    pub end: usize,
+    pub length: usize,
+    pub tetrads: usize,
+    pub y1: i32,
+    pub y2: i32,
+    pub y3: i32,
+    pub score: i32,
+    pub sequence: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub chrom: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub strand: Option<Strand>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "MotifType::is_g4")
+    )]
+    pub motif_type: MotifType,
+}
+
+impl G4Record {
+    /// `length` is derived from `start..=end`.
+    pub fn new(
+        start: usize,
+        end: usize,
+        tetrads: usize,
+        loops: [i32; 3],
+        score: i32,
+        sequence: impl Into<String>,
+    ) -> Self {
+        let [y1, y2, y3] = loops;
+        Self {
+            start,
+            end,
+            length: (end + 1).saturating_sub(start),
+            tetrads,
+            y1,
+            y2,
+            y3,
+            score,
+            sequence: sequence.into(),
+            chrom: None,
+            strand: None,
+            motif_type: MotifType::G4,
+        }
+    }
+
+    pub fn with_chrom(mut self, chrom: impl Into<String>) -> Self {
+        self.chrom = Some(chrom.into());
+        self
+    }
+
+    pub fn with_strand(mut self, strand: Strand) -> Self {
+        self.strand = Some(strand);
+        self
+    }
+
+    pub fn with_motif_type(mut self, motif_type: MotifType) -> Self {
+        self.motif_type = motif_type;
+        self
+    }
+
+    /// The record with `start` in `coordinates`, e.g. before serializing it
+    /// as JSON for a 0-based consumer. Apply it once: `start` is assumed to
+    /// be 1-based.
+    pub fn in_coordinates(mut self, coordinates: CoordinateSystem) -> Self {
+        self.start = coordinates.start(self.start);
+        self
+    }
+
+    /// Appends the nine core CSV fields, without a trailing newline.
+    #[cfg(feature = "export")]
+    pub(crate) fn push_csv_fields(&self, out: &mut String) {
+        use std::fmt::Write as _;
+        let _ = write!(
+            out,
+            "{},{},{},{},{},{},{},{},",
+            self.start, self.end, self.length, self.tetrads, self.y1, self.y2, self.y3, self.score
+        );
+        push_csv_field(out, &self.sequence);
+    }
+}
+
+impl From<&G4> for G4Record {
+    fn from(g4: &G4) -> Self {
+        Self {
+            start: g4.start,
+            end: g4.end,
+            length: g4.length,
+            tetrads: g4.tetrads,
+            y1: g4.y1,
+            y2: g4.y2,
+            y3: g4.y3,
+            score: g4.score,
+            sequence: g4.sequence_string(),
+            chrom: None,
+            strand: None,
+            motif_type: MotifType::G4,
+        }
+    }
+}
+
+#[cfg(feature = "export")]
+pub(crate) fn push_csv_field(out: &mut String, value: &str) {
+    if !value.contains([',', '"', '\n']) {
+        out.push_str(value);
+        return;
+    }
+    out.push('"');
+    for ch in value.chars() {
+        if ch == '"' {
+            out.push_str("\"\"");
+        } else {
+            out.push(ch);
+        }
+    }
+    out.push('"');
+}
+
+/// Reads the core columns of a CSV written by
+/// [`render_csv_results`](crate::qgrs::render_csv_results), plus
+/// [`MOTIF_TYPE_COLUMN`] when present (G4 otherwise); other opt-in columns
+/// are ignored. Rows that are short or fail to parse are reported as
+/// `InvalidData` with their line number.
+pub fn parse_csv_records<R: BufRead>(reader: R) -> io::Result<Vec<G4Record>> {
+    let mut records = Vec::new();
+    // Offset of `motif_type` among the fields after `sequence`.
+    let mut motif_type_field = None;
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if index == 0 {
+            motif_type_field = line
+                .split(',')
+                .skip(RECORD_COLUMNS.len())
+                .position(|column| column == MOTIF_TYPE_COLUMN);
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let record = parse_csv_row(&line, motif_type_field).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed result row at line {}: {line}", index + 1),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Reads the core columns of a Parquet file written by
+/// [`write_parquet_results`](crate::qgrs::write_parquet_results), plus
+/// [`MOTIF_TYPE_COLUMN`] when present (G4 otherwise), like
+/// [`parse_csv_records`]. A missing or mistyped column, or an unknown motif
+/// type, is `InvalidData`.
+#[cfg(feature = "export")]
+pub fn parse_parquet_records<R: ChunkReader + 'static>(reader: R) -> io::Result<Vec<G4Record>> {
+    let invalid =
+        |err: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, err.to_string());
+    let batches = ParquetRecordBatchReaderBuilder::try_new(reader)
+        .and_then(|builder| builder.build())
+        .map_err(|err| invalid(&err))?;
+    let mut records = Vec::new();
+    for batch in batches {
+        let batch = batch.map_err(|err| invalid(&err))?;
+        let start = parquet_column::<UInt64Array>(&batch, "start")?;
+        let end = parquet_column::<UInt64Array>(&batch, "end")?;
+        let length = parquet_column::<UInt64Array>(&batch, "length")?;
+        let tetrads = parquet_column::<UInt64Array>(&batch, "tetrads")?;
+        let y1 = parquet_column::<Int32Array>(&batch, "y1")?;
+        let y2 = parquet_column::<Int32Array>(&batch, "y2")?;
+        let y3 = parquet_column::<Int32Array>(&batch, "y3")?;
+        let score = parquet_column::<Int32Array>(&batch, "score")?;
+        let sequence = parquet_column::<StringArray>(&batch, "sequence")?;
+        let motif_types = batch
+            .column_by_name(MOTIF_TYPE_COLUMN)
+            .map(|_| parquet_column::<StringArray>(&batch, MOTIF_TYPE_COLUMN))
+            .transpose()?;
+        for row in 0..batch.num_rows() {
+            let motif_type = match motif_types {
+                Some(labels) => MotifType::from_label(labels.value(row)).ok_or_else(|| {
+                    invalid(&format!(
+                        "unknown motif type in row {row}: {}",
+                        labels.value(row)
+                    ))
+                })?,
+                None => MotifType::G4,
+            };
+            records.push(G4Record {
+                start: start.value(row) as usize,
+                end: end.value(row) as usize,
+                length: length.value(row) as usize,
+                tetrads: tetrads.value(row) as usize,
+                y1: y1.value(row),
+                y2: y2.value(row),
+                y3: y3.value(row),
+                score: score.value(row),
+                sequence: sequence.value(row).to_string(),
+                chrom: None,
+                strand: None,
+                motif_type,
+            });
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "export")]
+fn parquet_column<'a, T: Array + 'static>(batch: &'a RecordBatch, name: &str) -> io::Result<&'a T> {
+    batch
+        .column_by_name(name)
+        .and_then(|array| array.as_any().downcast_ref::<T>())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("missing or mistyped column: {name}"),
+            )
+        })
+}
+
+fn parse_csv_row(line: &str, motif_type_field: Option<usize>) -> Option<G4Record> {
+    let mut fields = line.splitn(RECORD_COLUMNS.len() + 1, ',');
+    let mut next = || fields.next();
+    let start = next()?.parse().ok()?;
+    let end = next()?.parse().ok()?;
+    let length = next()?.parse().ok()?;
+    let tetrads = next()?.parse().ok()?;
+    let y1 = next()?.parse().ok()?;
+    let y2 = next()?.parse().ok()?;
+    let y3 = next()?.parse().ok()?;
+    let score = next()?.parse().ok()?;
+    let sequence = next()?.to_string();
+    let motif_type = match motif_type_field {
+        Some(offset) => MotifType::from_label(fields.next()?.split(',').nth(offset)?)?,
+        None => MotifType::G4,
+    };
+    Some(G4Record {
+        start,
+        end,
+        length,
+        tetrads,
+        y1,
+        y2,
+        y3,
+        score,
+        sequence,
+        chrom: None,
+        strand: None,
+        motif_type,
+    })
+}
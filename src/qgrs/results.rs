@@ -0,0 +1,572 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "mmap")]
+use super::checkpoint::SettledFamilies;
+
+use super::{
+    CancellationToken, ChromSequence, Family, FamilyStats, G4, G4Record, HitCap, MinScore,
+    MotifType, OccurrenceCounter, QgrsError, RunCollector, ScanConfig, SearchCounters, SearchHooks,
+    consolidate_with_families_with_topology, count_distinct, detach_all,
+    find_owned_bytes_for_config, gruns::GRun, scan_short_linear, winners,
+};
+
+/// Consolidated hits for one FASTA record.
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    pub name: String,
+    /// Number of earlier records with the same name, see `ChromSequence::occurrence`.
+    pub occurrence: usize,
+    /// Position of the record in its input, from 0, see
+    /// `ChromSequence::index`. Stream scans count skipped records too.
+    pub index: usize,
+    /// Header text after the name token, see `ChromSequence::description`.
+    pub description: Option<String>,
+    pub sequence_len: usize,
+    pub hits: Vec<G4>,
+    pub family_ranges: Vec<(usize, usize)>,
+    /// Member count and best score of each family, in `family_ranges` order.
+    pub family_stats: Vec<FamilyStats>,
+    /// Unconsolidated hits, only present when raw capture was requested.
+    pub raw_hits: Option<Vec<G4>>,
+    /// Target-base runs in start order (0-based), present when
+    /// [`ScanConfig::with_gruns`] is set.
+    pub gruns: Option<Vec<GRun>>,
+    pub stats: SearchStats,
+    /// The scan these results came from.
+    pub config: ScanConfig,
+}
+
+/// Hit counts and wall-clock timings for one record.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// Hits before consolidation.
+    pub raw_hits: usize,
+    /// Time spent reading the record's sequence lines. Stream mode scans
+    /// completed chunks while reading, so this includes part of the scan;
+    /// mmap mode loads the whole file up front and leaves it zero.
+    pub read: Duration,
+    /// Time spent in the search itself (stream mode: the final chunks only).
+    pub scan: Duration,
+    pub consolidate: Duration,
+    /// `;` comment lines skipped while reading the record.
+    pub comment_lines: usize,
+    /// Search work counts, present when [`ScanConfig::with_metrics`] is set.
+    pub metrics: Option<ScanMetrics>,
+    /// More hits were found than [`ScanConfig::with_max_hits`] allows, so
+    /// only the first ones by start were kept.
+    pub truncated: bool,
+}
+
+/// How much work one record's scan did, for tuning [`ScanLimits`].
+///
+/// Each stage only sees what the one before it let through, so
+/// `viable ≤ complete ≤ expanded` and `families ≤ deduped ≤ viable`.
+/// Chunked and stream scans also count the work spent on window overlaps,
+/// so the search counts may differ between input modes.
+///
+/// [`ScanLimits`]: crate::qgrs::ScanLimits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScanMetrics {
+    /// Partial candidates started from target-base runs.
+    pub seeded: usize,
+    /// Candidates produced by filling a loop, partial or complete.
+    pub expanded: usize,
+    /// Candidates with all three loops filled.
+    pub complete: usize,
+    /// Complete candidates that passed the score, length and run checks.
+    pub viable: usize,
+    /// Distinct hits left for consolidation.
+    pub deduped: usize,
+    /// Overlap families, one reported hit each.
+    pub families: usize,
+    /// Expansions whose loop search ran into the end of a chunked window
+    /// before the length limit and were redone on the whole record. Windows
+    /// overlap by the longest motif, so this stays 0 outside tests.
+    pub rescanned: usize,
+}
+
+impl SearchResults {
+    pub(crate) fn from_raw(
+        name: String,
+        occurrence: usize,
+        sequence_len: usize,
+        raw: Vec<G4>,
+        config: ScanConfig,
+        capture_raw: bool,
+        metrics: Option<ScanMetrics>,
+    ) -> Self {
+        let started = Instant::now();
+        let raw_count = raw.len();
+        let deduped = metrics.map(|_| count_distinct(&raw));
+        let raw_hits = (capture_raw || config.keeps_raw()).then(|| raw.clone());
+        let families = consolidate_with_families_with_topology(
+            raw,
+            config.topology(),
+            sequence_len,
+            config.family_policy(),
+        );
+        let family_stats = families.iter().map(Family::stats).collect();
+        let (hits, family_ranges) = winners(families);
+        let mut results = Self {
+            name,
+            occurrence,
+            index: 0,
+            description: None,
+            sequence_len,
+            hits,
+            family_ranges,
+            family_stats,
+            raw_hits,
+            gruns: None,
+            stats: SearchStats {
+                raw_hits: raw_count,
+                consolidate: started.elapsed(),
+                ..SearchStats::default()
+            },
+            config,
+        };
+        results.stats.metrics = metrics.zip(deduped).map(|(search, deduped)| ScanMetrics {
+            deduped,
+            families: results.family_ranges.len(),
+            ..search
+        });
+        results.apply_max_hits();
+        results.apply_case();
+        if config.detach_hits() {
+            results.detach();
+        }
+        results
+    }
+
+    /// Builds results from families that were already closed, e.g. by the
+    /// incremental stream consolidator. Raw hits are the family members in
+    /// order.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn from_families(
+        name: String,
+        occurrence: usize,
+        sequence_len: usize,
+        families: Vec<Family>,
+        config: ScanConfig,
+        capture_raw: bool,
+        metrics: Option<ScanMetrics>,
+    ) -> Self {
+        let started = Instant::now();
+        let raw_count = families.iter().map(|family| family.members.len()).sum();
+        let deduped =
+            metrics.map(|_| count_distinct(families.iter().flat_map(|family| &family.members)));
+        let raw_hits = (capture_raw || config.keeps_raw()).then(|| {
+            families
+                .iter()
+                .flat_map(|family| family.members.iter().cloned())
+                .collect()
+        });
+        let family_stats = families.iter().map(Family::stats).collect();
+        let (hits, family_ranges) = winners(families);
+        let mut results = Self {
+            name,
+            occurrence,
+            index: 0,
+            description: None,
+            sequence_len,
+            hits,
+            family_ranges,
+            family_stats,
+            raw_hits,
+            gruns: None,
+            stats: SearchStats {
+                raw_hits: raw_count,
+                consolidate: started.elapsed(),
+                ..SearchStats::default()
+            },
+            config,
+        };
+        results.stats.metrics = metrics.zip(deduped).map(|(search, deduped)| ScanMetrics {
+            deduped,
+            families: results.family_ranges.len(),
+            ..search
+        });
+        results.apply_max_hits();
+        results.apply_case();
+        if config.detach_hits() {
+            results.detach();
+        }
+        results
+    }
+
+    /// Adds the families a checkpointed stream scan settled before the
+    /// record ended, in range order among the others, then caps and marks
+    /// the merged hits again. The cap already applied keeps every hit the
+    /// merged one can, since no settled family splits a later one.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn with_settled(mut self, settled: SettledFamilies) -> Self {
+        if settled.families.is_empty() {
+            return self;
+        }
+        self.stats.raw_hits += settled.raw_hits;
+        if let Some(metrics) = self.stats.metrics.as_mut() {
+            metrics.deduped += settled.distinct;
+            metrics.families += settled.families.len();
+        }
+        let mut merged: Vec<_> = std::mem::take(&mut self.family_ranges)
+            .into_iter()
+            .zip(std::mem::take(&mut self.family_stats))
+            .zip(std::mem::take(&mut self.hits))
+            .collect();
+        for (family, stats) in settled.families.into_iter().zip(settled.stats) {
+            if let Some(raw_hits) = self.raw_hits.as_mut() {
+                raw_hits.extend(family.members.iter().cloned());
+            }
+            merged.push(((family.range, stats), family.into_best()));
+        }
+        merged.sort_by_key(|&((range, _), _)| range);
+        for ((range, stats), hit) in merged {
+            self.family_ranges.push(range);
+            self.family_stats.push(stats);
+            self.hits.push(hit);
+        }
+        if let Some(raw_hits) = self.raw_hits.as_mut() {
+            raw_hits.sort_unstable_by_key(G4::key);
+        }
+        self.apply_max_hits();
+        self.apply_case();
+        if self.config.detach_hits() {
+            self.detach();
+        }
+        self
+    }
+
+    /// Keeps the first [`ScanConfig::max_hits`] hits, their families and the
+    /// raw hits that belong to them.
+    fn apply_max_hits(&mut self) {
+        if let Some(max_hits) = self.config.max_hits() {
+            self.truncate_hits(max_hits);
+        }
+    }
+
+    /// Keeps the first `max_hits` hits, the families up to the one holding
+    /// the last kept hit and the raw hits of those families, and sets
+    /// [`SearchStats::truncated`] if any hit was dropped. For capping hits
+    /// that were filtered after the scan, e.g. by
+    /// `postprocess::exclude_overlapping`, where
+    /// [`ScanConfig::with_max_hits`] would also count the hits removed later.
+    pub fn truncate_hits(&mut self, max_hits: usize) {
+        if self.hits.len() <= max_hits {
+            return;
+        }
+        self.hits.truncate(max_hits);
+        let families = self.hits.last().map_or(0, |last| {
+            self.family_ranges
+                .partition_point(|&(start, _)| start <= last.start)
+        });
+        self.family_ranges.truncate(families);
+        self.family_stats.truncate(families);
+        let last_end = self.family_ranges.last().map_or(0, |&(_, end)| end);
+        if let Some(raw_hits) = self.raw_hits.as_mut() {
+            raw_hits.retain(|hit| hit.start <= last_end);
+        }
+        self.stats.truncated = true;
+    }
+
+    /// Marks every hit to render its sequence the way
+    /// [`ScanConfig::with_preserve_case`] asks.
+    fn apply_case(&mut self) {
+        let preserve_case = self.config.preserves_case();
+        let raw_hits = self.raw_hits.iter_mut().flatten();
+        for hit in self.hits.iter_mut().chain(raw_hits) {
+            hit.set_preserve_case(preserve_case);
+        }
+    }
+
+    /// Detaches `hits` and `raw_hits` from the scanned sequence, see
+    /// [`G4::detach`].
+    pub fn detach(&mut self) {
+        detach_all(&mut self.hits);
+        if let Some(raw_hits) = self.raw_hits.as_mut() {
+            detach_all(raw_hits);
+        }
+    }
+}
+
+/// Scans and consolidates one record under `config`. With `capture_raw` the
+/// unconsolidated hits are kept in [`SearchResults::raw_hits`].
+pub fn search_chromosome(
+    chrom: &ChromSequence,
+    config: ScanConfig,
+    capture_raw: bool,
+) -> SearchResults {
+    search_seeded(chrom, config, capture_raw, None)
+}
+
+/// [`search_chromosome`] seeded only at `anchors`, 1-based positions such
+/// as the `start` column of an earlier scan, instead of at every
+/// target-base run: orders of magnitude cheaper when the candidates are
+/// already known. Each anchor still has to begin a run long enough for the
+/// tetrad count, and hits are held to every limit of `config`, so the hits
+/// of a full scan reproduce themselves when their starts are the anchors.
+/// Anchors outside the record are ignored; no G-runs are collected.
+pub fn search_chromosome_with_anchors(
+    chrom: &ChromSequence,
+    config: ScanConfig,
+    capture_raw: bool,
+    anchors: &[usize],
+) -> SearchResults {
+    let sequence_len = chrom.sequence().len();
+    let mut seeds: Vec<usize> = anchors
+        .iter()
+        .filter(|&&anchor| (1..=sequence_len).contains(&anchor))
+        .map(|anchor| anchor - 1)
+        .collect();
+    seeds.sort_unstable();
+    seeds.dedup();
+    search_seeded(chrom, config.with_gruns(false), capture_raw, Some(&seeds))
+}
+
+fn search_seeded(
+    chrom: &ChromSequence,
+    config: ScanConfig,
+    capture_raw: bool,
+    anchors: Option<&[usize]>,
+) -> SearchResults {
+    let sequence = chrom.sequence();
+    let sequence_len = sequence.len();
+    let counters = config.collects_metrics().then(SearchCounters::default);
+    let runs = config.collects_gruns().then(RunCollector::default);
+    let cap = config.max_hits().map(HitCap::new);
+    let hooks = SearchHooks {
+        cancel: None,
+        counters: counters.as_ref(),
+        runs: runs.as_ref(),
+        anchors,
+        cap: cap.as_ref(),
+    };
+    let started = Instant::now();
+    let raw = find_owned_bytes_for_config(sequence, config, hooks);
+    let scan = started.elapsed();
+    let mut results = SearchResults::from_raw(
+        chrom.name().to_string(),
+        chrom.occurrence(),
+        sequence_len,
+        raw,
+        config,
+        capture_raw,
+        counters.map(|counters| counters.snapshot()),
+    );
+    results.description = chrom.description.clone();
+    results.index = chrom.index;
+    results.gruns = runs.map(RunCollector::into_sorted);
+    results.stats.scan = scan;
+    results.stats.comment_lines = chrom.comment_lines;
+    results
+}
+
+/// Consolidated hits of a short sequence, such as a primer or probe, in any
+/// case, as records with `motif_type` set from the target base. The same
+/// hits as [`search_chromosome`] under `config`, found without windows, a
+/// shared copy of the sequence or a `G4` per raw hit; below a few hundred
+/// bases that setup costs more than the search itself. Circular configs
+/// take the general path, since families may join across the origin.
+///
+/// ```
+/// use qgrs_rust::qgrs::{ScanConfig, scan_short};
+///
+/// let hits = scan_short(b"ttGGGGaGGGGaGGGGaGGGGtt", ScanConfig::default());
+/// assert_eq!((hits[0].start, hits[0].end), (3, 21));
+/// assert_eq!(hits[0].sequence, "GGGGAGGGGAGGGGAGGGG");
+/// ```
+pub fn scan_short(sequence: &[u8], config: ScanConfig) -> Vec<G4Record> {
+    if !config.topology().is_circular() {
+        return scan_short_linear(sequence, config);
+    }
+    let motif_type = MotifType::from(config.target_base());
+    let mut raw =
+        find_owned_bytes_for_config(Arc::new(sequence.to_vec()), config, SearchHooks::default());
+    for hit in &mut raw {
+        hit.set_preserve_case(config.preserves_case());
+    }
+    consolidate_with_families_with_topology(
+        raw,
+        config.topology(),
+        sequence.len(),
+        config.family_policy(),
+    )
+    .iter()
+    .map(|family| G4Record {
+        motif_type,
+        ..family.best().to_record()
+    })
+    .collect()
+}
+
+/// [`search_chromosome`] on every `(name, sequence)` record, with whole
+/// records spread across the rayon pool rather than each record's windows:
+/// many short records, such as transcripts, keep every core busy without
+/// paying for windows on each one. Records longer than
+/// [`WHOLE_RECORD_MAX_LEN`](crate::qgrs::WHOLE_RECORD_MAX_LEN) are still
+/// split into windows, see [`ScanConfig::with_whole_records`]. Sequences may
+/// be in any case. Results come back in input order, with occurrences
+/// counted per name and indices set as the loaders do.
+///
+/// ```
+/// use qgrs_rust::qgrs::{ScanConfig, scan_many};
+///
+/// let records = vec![
+///     ("a".to_string(), b"GGGTGGGTGGGTGGG".to_vec()),
+///     ("b".to_string(), b"ACGT".to_vec()),
+/// ];
+/// let results = scan_many(records, ScanConfig::default());
+/// assert_eq!(results[0].name, "a");
+/// assert_eq!(results[0].hits.len(), 1);
+/// assert!(results[1].hits.is_empty());
+/// ```
+#[cfg(feature = "parallel")]
+pub fn scan_many<I>(records: I, config: ScanConfig) -> Vec<SearchResults>
+where
+    I: IntoParallelIterator<Item = (String, Vec<u8>)>,
+{
+    let config = config.with_whole_records(true);
+    let results = records
+        .into_par_iter()
+        .map(|(name, sequence)| scan_record(name, sequence, config))
+        .collect();
+    number_records(results)
+}
+
+/// [`scan_many`] without the `parallel` feature: records are scanned one
+/// after another on the calling thread.
+#[cfg(not(feature = "parallel"))]
+pub fn scan_many<I>(records: I, config: ScanConfig) -> Vec<SearchResults>
+where
+    I: IntoIterator<Item = (String, Vec<u8>)>,
+{
+    let config = config.with_whole_records(true);
+    let results = records
+        .into_iter()
+        .map(|(name, sequence)| scan_record(name, sequence, config))
+        .collect();
+    number_records(results)
+}
+
+fn scan_record(name: String, sequence: Vec<u8>, config: ScanConfig) -> SearchResults {
+    let chrom = ChromSequence {
+        name,
+        occurrence: 0,
+        description: None,
+        sequence: Arc::new(sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    search_chromosome(&chrom, config, false)
+}
+
+/// Sets occurrences and indices on [`scan_many`] results, as the loaders do.
+fn number_records(mut results: Vec<SearchResults>) -> Vec<SearchResults> {
+    let mut occurrences = OccurrenceCounter::default();
+    for (index, result) in results.iter_mut().enumerate() {
+        let (name, occurrence) = occurrences.tag(std::mem::take(&mut result.name));
+        result.name = name;
+        result.occurrence = occurrence;
+        result.index = index;
+    }
+    results
+}
+
+/// Like [`search_chromosome`], but returns [`QgrsError::Cancelled`] instead of
+/// consolidating once `cancel` is set.
+pub fn search_chromosome_with_cancellation(
+    chrom: &ChromSequence,
+    config: ScanConfig,
+    capture_raw: bool,
+    cancel: &CancellationToken,
+) -> Result<SearchResults, QgrsError> {
+    config.limits().validate(config.min_tetrads())?;
+    let sequence = chrom.sequence();
+    let sequence_len = sequence.len();
+    let counters = config.collects_metrics().then(SearchCounters::default);
+    let runs = config.collects_gruns().then(RunCollector::default);
+    let cap = config.max_hits().map(HitCap::new);
+    let hooks = SearchHooks {
+        cancel: Some(cancel),
+        counters: counters.as_ref(),
+        runs: runs.as_ref(),
+        anchors: None,
+        cap: cap.as_ref(),
+    };
+    let started = Instant::now();
+    let raw = find_owned_bytes_for_config(sequence, config, hooks);
+    if cancel.is_cancelled() {
+        return Err(QgrsError::Cancelled);
+    }
+    let scan = started.elapsed();
+    let mut results = SearchResults::from_raw(
+        chrom.name().to_string(),
+        chrom.occurrence(),
+        sequence_len,
+        raw,
+        config,
+        capture_raw,
+        counters.map(|counters| counters.snapshot()),
+    );
+    results.description = chrom.description.clone();
+    results.index = chrom.index;
+    results.gruns = runs.map(RunCollector::into_sorted);
+    results.stats.scan = scan;
+    results.stats.comment_lines = chrom.comment_lines;
+    Ok(results)
+}
+
+/// The results a scan of the same sequence with the stricter `min_score`
+/// would give, built from `results.raw_hits` instead of rescanning: hits
+/// below `min_score` are dropped and the rest consolidated again, so a
+/// family whose winner is dropped is reported by its best remaining member.
+///
+/// Needs raw hits, e.g. from a scan with [`ScanConfig::with_keep_raw`], and
+/// `min_score` must be at least every threshold the results were scanned
+/// with, since hits below those were never found. The new results keep
+/// their filtered raw hits and can be refiltered again; `stats` only
+/// describes the consolidation and `config` carries the fixed threshold.
+pub fn refilter(results: &SearchResults, min_score: i32) -> Result<SearchResults, QgrsError> {
+    let raw = results.raw_hits.as_ref().ok_or(QgrsError::MissingRawHits)?;
+    let scanned = results.config;
+    let threshold = scanned
+        .min_scores()
+        .into_iter()
+        .map(|(_, threshold)| threshold)
+        .max()
+        .unwrap_or_else(|| {
+            scanned
+                .min_score
+                .for_tetrads(scanned.min_tetrads, scanned.limits)
+        });
+    if min_score < threshold {
+        return Err(QgrsError::RefilterBelowScan {
+            min_score,
+            scanned: threshold,
+        });
+    }
+    let config = scanned.with_min_score(MinScore::Fixed(min_score));
+    let raw = raw
+        .iter()
+        .filter(|hit| hit.score >= min_score)
+        .cloned()
+        .collect();
+    let mut refiltered = SearchResults::from_raw(
+        results.name.clone(),
+        results.occurrence,
+        results.sequence_len,
+        raw,
+        config,
+        true,
+        None,
+    );
+    refiltered.description = results.description.clone();
+    refiltered.gruns = results.gruns.clone();
+    Ok(refiltered)
+}
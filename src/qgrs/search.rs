@@ -1,10 +1,19 @@
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::sync::{Arc, OnceLock};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, OnceLock};
 
-use memchr::memchr2;
+use smallvec::SmallVec;
 
-use crate::qgrs::data::{QuartetBase, ScanLimits, SequenceData, SequenceSlice};
+use crate::qgrs::cancel::{CANCEL_CHECK_INTERVAL, CancellationToken, is_cancelled};
+use crate::qgrs::consolidation::FamilyRank;
+use crate::qgrs::data::{
+    MinScore, QuartetBase, ScanConfig, ScanLimits, SequenceData, SequenceSlice, display_string,
+};
+use crate::qgrs::gruns::{BaseRunScanner, GRun};
+use crate::qgrs::record::{G4Record, MotifType};
+use crate::qgrs::results::ScanMetrics;
 
 // Invariants for the raw-search layer:
 // 1. All coordinates remain 0-based half-open internally. `G4::start` is adjusted
@@ -17,10 +26,185 @@ use crate::qgrs::data::{QuartetBase, ScanLimits, SequenceData, SequenceSlice};
 //    concatenated hits to `consolidate_g4s` to preserve parity across mmap and
 //    streaming paths.
 
+// Loop lengths found by one `expand` call. Each is bounded by `max_length`
+// (at most 45), so 16 inline slots cover all but pathological poly-G runs.
+type LoopLengths = SmallVec<[i32; 16]>;
+
+#[cfg(test)]
 thread_local! {
-    static LOOP_BUFFER: RefCell<Vec<i32>> = RefCell::new(Vec::with_capacity(16));
+    static PEAK_STACK_LEN: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(test)]
+fn note_stack_len(len: usize) {
+    PEAK_STACK_LEN.with(|peak| peak.set(peak.get().max(len)));
+}
+
+/// Largest candidate stack seen on this thread since the last call.
+#[cfg(all(test, feature = "mmap", feature = "export"))]
+pub(crate) fn take_peak_stack_len() -> usize {
+    PEAK_STACK_LEN.with(|peak| peak.replace(0))
 }
 
+/// Search-side [`ScanMetrics`] shared by every window of one record. Each
+/// `drain_candidates` call counts locally and adds its totals once, so the
+/// atomics are only touched once per window.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SearchCounters(Arc<[AtomicUsize; 5]>);
+
+impl SearchCounters {
+    fn add(&self, local: &ScanMetrics) {
+        let [seeded, expanded, complete, viable, rescanned] = &*self.0;
+        seeded.fetch_add(local.seeded, AtomicOrdering::Relaxed);
+        expanded.fetch_add(local.expanded, AtomicOrdering::Relaxed);
+        complete.fetch_add(local.complete, AtomicOrdering::Relaxed);
+        viable.fetch_add(local.viable, AtomicOrdering::Relaxed);
+        rescanned.fetch_add(local.rescanned, AtomicOrdering::Relaxed);
+    }
+
+    /// Totals so far; `deduped` and `families` are left for consolidation.
+    pub(crate) fn snapshot(&self) -> ScanMetrics {
+        let [seeded, expanded, complete, viable, rescanned] = &*self.0;
+        ScanMetrics {
+            seeded: seeded.load(AtomicOrdering::Relaxed),
+            expanded: expanded.load(AtomicOrdering::Relaxed),
+            complete: complete.load(AtomicOrdering::Relaxed),
+            viable: viable.load(AtomicOrdering::Relaxed),
+            rescanned: rescanned.load(AtomicOrdering::Relaxed),
+            ..ScanMetrics::default()
+        }
+    }
+}
+
+/// Target-base runs seen while seeding, for [`ScanConfig::with_gruns`]. Each
+/// window collects locally and appends once.
+///
+/// [`ScanConfig::with_gruns`]: crate::qgrs::ScanConfig::with_gruns
+#[derive(Debug, Default)]
+pub(crate) struct RunCollector(Mutex<Vec<GRun>>);
+
+impl RunCollector {
+    pub(crate) fn extend(&self, runs: Vec<GRun>) {
+        self.0.lock().expect("run collector poisoned").extend(runs);
+    }
+
+    /// Every collected run, in start order.
+    pub(crate) fn into_sorted(self) -> Vec<GRun> {
+        let mut runs = self.0.into_inner().expect("run collector poisoned");
+        runs.sort_unstable_by_key(|run| run.start);
+        runs
+    }
+}
+
+/// Early stop for a windowed scan under [`ScanConfig::with_max_hits`].
+/// Windows finish in any order; the raw hits of those with no unfinished
+/// window before them are folded into overlap families in start order, and
+/// once `max_hits` families are closed and another has begun, windows that
+/// start past the folded prefix cannot change the first `max_hits` reported
+/// hits and are skipped.
+#[derive(Debug)]
+pub(crate) struct HitCap {
+    max_hits: usize,
+    /// 0-based offset from which windows are skipped; `usize::MAX` until the
+    /// cap is reached.
+    stop_at: AtomicUsize,
+    progress: Mutex<CapProgress>,
+}
+
+#[derive(Debug, Default)]
+struct CapProgress {
+    /// Finished windows waiting for an earlier one: `primary_end` and the
+    /// `(start, end)` of each raw hit.
+    pending: BTreeMap<usize, (usize, Vec<(usize, usize)>)>,
+    next_window: usize,
+    closed: usize,
+    /// Largest `end` in the family still open.
+    open_end: Option<usize>,
+}
+
+impl HitCap {
+    pub(crate) fn new(max_hits: usize) -> Self {
+        Self {
+            max_hits,
+            stop_at: AtomicUsize::new(usize::MAX),
+            progress: Mutex::default(),
+        }
+    }
+
+    /// Whether the window starting at `offset` can be skipped.
+    pub(crate) fn reached(&self, offset: usize) -> bool {
+        offset >= self.stop_at.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Records the hits of window `index` of the scan, whose primary section
+    /// ends at `primary_end`.
+    pub(crate) fn finish_window(&self, index: usize, primary_end: usize, hits: &[G4]) {
+        let mut spans: Vec<(usize, usize)> = hits.iter().map(|hit| (hit.start, hit.end)).collect();
+        spans.sort_unstable();
+        let mut guard = self.progress.lock().expect("hit cap poisoned");
+        let progress = &mut *guard;
+        progress.pending.insert(index, (primary_end, spans));
+        while let Some((primary_end, spans)) = progress.pending.remove(&progress.next_window) {
+            progress.next_window += 1;
+            for (start, end) in spans {
+                match progress.open_end {
+                    // 1-based inclusive: sharing a base joins the family.
+                    Some(open_end) if start <= open_end => {
+                        progress.open_end = Some(open_end.max(end));
+                    }
+                    Some(_) => {
+                        progress.closed += 1;
+                        progress.open_end = Some(end);
+                    }
+                    None => progress.open_end = Some(end),
+                }
+            }
+            // Later hits start past `primary_end`, 1-based.
+            if progress
+                .open_end
+                .is_some_and(|open_end| open_end <= primary_end)
+            {
+                progress.closed += 1;
+                progress.open_end = None;
+            }
+            if progress.closed > self.max_hits
+                || (progress.closed == self.max_hits && progress.open_end.is_some())
+            {
+                self.stop_at.store(primary_end, AtomicOrdering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+/// Optional observers shared by every window of one scan, and the anchors
+/// that replace run seeding when given.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SearchHooks<'a> {
+    pub(crate) cancel: Option<&'a CancellationToken>,
+    pub(crate) counters: Option<&'a SearchCounters>,
+    pub(crate) runs: Option<&'a RunCollector>,
+    /// Skips windows that cannot change the first hits kept by
+    /// [`ScanConfig::with_max_hits`]; windowed linear scans only.
+    pub(crate) cap: Option<&'a HitCap>,
+    /// Sorted, distinct 0-based positions to seed at instead of every
+    /// target-base run, see [`seed_anchors`]. No runs are collected then.
+    pub(crate) anchors: Option<&'a [usize]>,
+}
+
+impl<'a> SearchHooks<'a> {
+    pub(crate) fn cancellable(cancel: Option<&'a CancellationToken>) -> Self {
+        Self {
+            cancel,
+            ..Self::default()
+        }
+    }
+}
+
+/// One quadruplex hit. `start` and `end` are 1-based inclusive; `tetrad1`
+/// to `tetrad4` are the runs' 1-based starts and `y1` to `y3` the loop
+/// lengths. The motif is a slice of the scanned buffer, see
+/// [`G4::sequence`].
 #[derive(Debug)]
 pub struct G4 {
     pub start: usize,
@@ -37,12 +221,15 @@ pub struct G4 {
     pub score: i32,
     slice_start: usize,
     sequence_data: Arc<Vec<u8>>,
+    /// [`ScanConfig::with_preserve_case`]: `sequence()` keeps the scanned
+    /// case instead of uppercasing.
+    preserve_case: bool,
     slice_cache: OnceLock<SequenceSlice>,
     sequence_cache: OnceLock<String>,
 }
 
 impl G4 {
-    fn from_candidate(candidate: &G4Candidate) -> Self {
+    fn from_candidate(candidate: &G4Candidate, sequence_data: &Arc<Vec<u8>>) -> Self {
         let length = candidate.length();
         let end = candidate.start + length;
         Self {
@@ -57,9 +244,10 @@ impl G4 {
             y3: candidate.y3,
             tetrads: candidate.num_tetrads,
             length,
-            score: candidate.score(),
+            score: gscore_to_i32(candidate.score()),
             slice_start: candidate.start,
-            sequence_data: candidate.seq.normalized.clone(),
+            sequence_data: Arc::clone(sequence_data),
+            preserve_case: false,
             slice_cache: OnceLock::new(),
             sequence_cache: OnceLock::new(),
         }
@@ -67,7 +255,80 @@ impl G4 {
 
     pub fn sequence(&self) -> &str {
         self.sequence_cache
-            .get_or_init(|| self.sequence_slice().to_uppercase_string())
+            .get_or_init(|| self.sequence_slice().to_display_string(self.preserve_case))
+    }
+
+    /// Owned copy of [`G4::sequence`] that does not populate its cache.
+    pub(crate) fn sequence_string(&self) -> String {
+        match self.sequence_cache.get() {
+            Some(sequence) => sequence.clone(),
+            None => self.sequence_slice().to_display_string(self.preserve_case),
+        }
+    }
+
+    /// [`G4::sequence`] with the tetrad runs uppercase and the loops
+    /// lowercase, the way QGRS Mapper prints motifs. A zero-length loop
+    /// leaves two runs adjacent, so they read as one longer uppercase run.
+    pub fn marked_sequence(&self) -> String {
+        let mut marked = self.sequence_string().into_bytes();
+        marked.make_ascii_lowercase();
+        for tetrad in [self.tetrad1, self.tetrad2, self.tetrad3, self.tetrad4] {
+            let offset = tetrad - self.start;
+            marked[offset..offset + self.tetrads].make_ascii_uppercase();
+        }
+        String::from_utf8(marked).expect("sequence bytes are ASCII")
+    }
+
+    /// `(start, end)`: 1-based with both ends inclusive, like every exported
+    /// coordinate.
+    pub const fn interval(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+
+    /// True when the two hits share at least one base.
+    pub fn overlaps(&self, other: &G4) -> bool {
+        intervals_overlap(self.interval(), other.interval())
+    }
+
+    /// True when every base of `other` lies within `self`.
+    pub fn contains(&self, other: &G4) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Gap between the hits: 0 when they overlap, 1 when one ends right
+    /// before the other starts, and so on.
+    pub fn distance_to(&self, other: &G4) -> usize {
+        if self.overlaps(other) {
+            0
+        } else if self.end < other.start {
+            other.start - self.end
+        } else {
+            self.start - other.end
+        }
+    }
+
+    pub fn to_record(&self) -> G4Record {
+        G4Record::from(self)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn sequence_is_cached(&self) -> bool {
+        self.sequence_cache.get().is_some()
+    }
+
+    /// Whether [`G4::sequence`] keeps the case of the scanned bytes.
+    pub fn preserves_case(&self) -> bool {
+        self.preserve_case
+    }
+
+    /// Sets [`G4::preserves_case`], dropping a sequence already rendered the
+    /// other way. Results built under [`ScanConfig::with_preserve_case`]
+    /// come with it set.
+    pub fn set_preserve_case(&mut self, preserve_case: bool) {
+        if self.preserve_case != preserve_case {
+            self.preserve_case = preserve_case;
+            self.sequence_cache = OnceLock::new();
+        }
     }
 
     pub(crate) fn sequence_slice(&self) -> SequenceSlice {
@@ -77,6 +338,71 @@ impl G4 {
             })
             .clone()
     }
+
+    /// Copies the motif's own bases into a buffer of its own and drops the
+    /// reference to the scanned sequence, so long-lived results stop keeping
+    /// whole chromosomes alive. `sequence()` and every field are unchanged.
+    pub fn detach(&mut self) {
+        if self.is_detached() {
+            return;
+        }
+        let end = self.slice_start + self.length;
+        self.sequence_data = Arc::new(self.sequence_data[self.slice_start..end].to_vec());
+        self.slice_start = 0;
+        self.slice_cache = OnceLock::new();
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.slice_start == 0 && self.sequence_data.len() == self.length
+    }
+
+    /// A detached hit rebuilt from its 1-based `start`, tetrad count, loop
+    /// lengths, score and scanned bases; the tetrad positions, `end` and
+    /// `length` follow from them. Used to read back checkpointed hits.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn from_parts(
+        start: usize,
+        tetrads: usize,
+        [y1, y2, y3]: [i32; 3],
+        score: i32,
+        sequence: Vec<u8>,
+    ) -> Self {
+        let tetrad2 = start + tetrads + y1 as usize;
+        let tetrad3 = tetrad2 + tetrads + y2 as usize;
+        let tetrad4 = tetrad3 + tetrads + y3 as usize;
+        let length = tetrad4 + tetrads - start;
+        Self {
+            start,
+            end: start + length - 1,
+            tetrad1: start,
+            tetrad2,
+            tetrad3,
+            tetrad4,
+            y1,
+            y2,
+            y3,
+            tetrads,
+            length,
+            score,
+            slice_start: 0,
+            sequence_data: Arc::new(sequence),
+            preserve_case: false,
+            slice_cache: OnceLock::new(),
+            sequence_cache: OnceLock::new(),
+        }
+    }
+}
+
+/// Whether two 1-based inclusive `(start, end)` intervals share a position.
+pub(crate) const fn intervals_overlap(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// [`G4::detach`] for every hit.
+pub fn detach_all(g4s: &mut [G4]) {
+    for g4 in g4s {
+        g4.detach();
+    }
 }
 
 impl Clone for G4 {
@@ -96,54 +422,107 @@ impl Clone for G4 {
             score: self.score,
             slice_start: self.slice_start,
             sequence_data: self.sequence_data.clone(),
+            preserve_case: self.preserve_case,
             slice_cache: OnceLock::new(),
             sequence_cache: OnceLock::new(),
         }
     }
 }
 
-#[derive(Clone)]
+// Identity is the coordinates, loop lengths and score. Sequence bytes are
+// left out on purpose: within one chromosome they follow from the
+// coordinates, and comparing them would touch the sequence caches.
+impl PartialEq for G4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for G4 {}
+
+impl Hash for G4 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Orders by `start`, then `end`, then higher `score` first; the remaining
+/// identity fields only break ties so the order agrees with `==`.
+impl Ord for G4 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.start
+            .cmp(&other.start)
+            .then(self.end.cmp(&other.end))
+            .then(other.score.cmp(&self.score))
+            .then_with(|| self.key().cmp(&other.key()))
+    }
+}
+
+impl PartialOrd for G4 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl G4 {
+    /// Every identity field, coordinates first. Raw hits are sorted by it
+    /// before consolidation so ties between hits with the same coordinates
+    /// are broken the same way whichever window or thread found them first.
+    pub(crate) fn key(&self) -> (usize, usize, usize, i32, i32, i32, i32) {
+        (
+            self.start,
+            self.end,
+            self.tetrads,
+            self.score,
+            self.y1,
+            self.y2,
+            self.y3,
+        )
+    }
+}
+
+// Shared by every candidate of one scan; candidates only carry numeric state
+// so queue pushes and expansions never touch the sequence refcount. `seq` is
+// the bytes a candidate may explore: a chunked window's bytes up to its
+// `window_end`, with the whole `record` kept for candidates cut off there.
+#[derive(Clone, Copy)]
+struct SearchContext<'a> {
+    seq: &'a [u8],
+    record: Option<&'a [u8]>,
+    max_run: usize,
+    target_base: QuartetBase,
+    limits: ScanLimits,
+    restricts_loops: bool,
+}
+
+#[derive(Clone, Copy)]
 struct G4Candidate {
-    seq: Arc<SequenceData>,
     num_tetrads: usize,
     start: usize,
     y1: i32,
     y2: i32,
     y3: i32,
     max_length: usize,
-    max_run: usize,
-    target_base: QuartetBase,
 }
 
 impl G4Candidate {
-    fn new(
-        seq: Arc<SequenceData>,
-        num_tetrads: usize,
-        start: usize,
-        limits: ScanLimits,
-        target_base: QuartetBase,
-    ) -> Self {
+    fn new(num_tetrads: usize, start: usize, limits: ScanLimits) -> Self {
         Self {
-            seq,
             num_tetrads,
             start,
             y1: -1,
             y2: -1,
             y3: -1,
             max_length: maximum_length(num_tetrads, limits),
-            max_run: limits.max_run,
-            target_base,
         }
     }
 
-    fn score(&self) -> i32 {
-        let gavg = (f64::from((self.y1 - self.y2).abs())
-            + f64::from((self.y2 - self.y3).abs())
-            + f64::from((self.y1 - self.y3).abs()))
-            / 3.0;
-        let gmax = (self.max_length as i32 - (self.num_tetrads as i32 * 4 + 1)) as f64;
-        let bonus = gmax * ((self.num_tetrads as i32 - 2) as f64);
-        (gmax - gavg + bonus).floor() as i32
+    fn score(&self) -> i64 {
+        gscore(
+            self.num_tetrads,
+            self.max_length,
+            [self.y1, self.y2, self.y3],
+        )
     }
 
     fn length(&self) -> usize {
@@ -212,14 +591,14 @@ impl G4Candidate {
         self.y1 >= 0 && self.y2 >= 0 && self.y3 >= 0
     }
 
-    fn viable(&self, min_score: i32) -> bool {
-        if self.score() < min_score {
+    fn viable(&self, ctx: &SearchContext, min_score: i32) -> bool {
+        if self.score() < i64::from(min_score) {
             return false;
         }
         if self.length() > self.max_length {
             return false;
         }
-        if self.exceeds_target_run_limit() {
+        if self.exceeds_target_run_limit(ctx) {
             return false;
         }
         let mut zero_loops = 0;
@@ -240,16 +619,16 @@ impl G4Candidate {
             .unwrap_or_else(|| self.start.saturating_add(self.length()))
     }
 
-    fn exceeds_target_run_limit(&self) -> bool {
-        let end = self.covered_end().min(self.seq.normalized.len());
-        let Some(window) = self.seq.normalized.get(self.start..end) else {
+    fn exceeds_target_run_limit(&self, ctx: &SearchContext) -> bool {
+        let end = self.covered_end().min(ctx.seq.len());
+        let Some(window) = ctx.seq.get(self.start..end) else {
             return false;
         };
         let mut run_len = 0usize;
         for &byte in window {
-            if self.target_base.matches(byte) {
+            if ctx.target_base.matches(byte) {
                 run_len += 1;
-                if run_len > self.max_run {
+                if run_len > ctx.max_run {
                     return true;
                 }
             } else {
@@ -259,9 +638,17 @@ impl G4Candidate {
         false
     }
 
-    fn find_loop_lengths_from(&self, ys: &mut Vec<i32>, cursor: usize) {
+    /// Collects the loop lengths that place the next tetrad. Returns `false`
+    /// when `ctx.seq` ended before the length limit did and the record goes
+    /// on: the lengths found are then incomplete.
+    fn find_loop_lengths_from(
+        &self,
+        ctx: &SearchContext,
+        ys: &mut LoopLengths,
+        cursor: usize,
+    ) -> bool {
         let mut p = cursor;
-        let seq = &self.seq.normalized;
+        let seq = ctx.seq;
         let max_pos = self.start + self.max_length + 1;
         let target_len = self.num_tetrads;
         let min_loop = self.min_acceptable_loop_length();
@@ -270,96 +657,93 @@ impl G4Candidate {
             if p >= max_pos {
                 break;
             }
+            // Every longer loop would contain this rejected base too.
+            if ctx.restricts_loops
+                && p > cursor
+                && !ctx.limits.allows_loop_byte(seq[p - 1], ctx.target_base)
+            {
+                break;
+            }
             if seq[p..p + target_len]
                 .iter()
-                .all(|&b| self.target_base.matches(b))
+                .all(|&b| ctx.target_base.matches(b))
             {
                 let y = (p - cursor) as i32;
                 if y >= min_loop && (p - self.start + target_len - 1) < self.max_length {
                     ys.push(y);
                 } else {
-                    break;
+                    return true;
                 }
             }
             p += 1;
         }
+        let cut_off = p + target_len > seq.len()
+            && p < max_pos
+            && (p - self.start + target_len - 1) < self.max_length;
+        !(cut_off && ctx.record.is_some())
     }
 
-    fn expand(&self) -> Vec<G4Candidate> {
-        let mut results = Vec::new();
-        if let Some(cursor) = self.cursor() {
-            LOOP_BUFFER.with(|slot| {
-                let mut ys = slot.borrow_mut();
-                ys.clear();
-                self.find_loop_lengths_from(&mut ys, cursor);
-                for &y in ys.iter() {
-                    let mut next = self.clone();
-                    if next.y1 < 0 {
-                        next.y1 = y;
-                    } else if next.y2 < 0 {
-                        next.y2 = y;
-                    } else if next.y3 < 0 {
-                        next.y3 = y;
-                    }
-                    if next.partial_length() <= next.max_length as i32
-                        && !next.exceeds_target_run_limit()
-                    {
-                        results.push(next);
-                    }
-                }
-            });
+    /// Pushes the candidates that fill the next loop onto `stack`, longest
+    /// loop first, so the shortest is popped next. Returns `false`, leaving
+    /// `stack` partly filled, when the loop search was cut off by the end of
+    /// `ctx.seq`.
+    fn expand(&self, ctx: &SearchContext, stack: &mut Vec<G4Candidate>) -> bool {
+        let Some(cursor) = self.cursor() else {
+            return true;
+        };
+        let mut ys = LoopLengths::new();
+        let whole = self.find_loop_lengths_from(ctx, &mut ys, cursor);
+        for &y in ys.iter().rev() {
+            let mut next = *self;
+            if next.y1 < 0 {
+                next.y1 = y;
+            } else if next.y2 < 0 {
+                next.y2 = y;
+            } else if next.y3 < 0 {
+                next.y3 = y;
+            }
+            if next.partial_length() <= next.max_length as i32
+                && !next.exceeds_target_run_limit(ctx)
+            {
+                stack.push(next);
+            }
         }
-        results
+        whole
     }
 }
 
-pub(crate) struct BaseRunScanner<'a> {
-    data: &'a [u8],
-    cursor: usize,
-    min_tetrads: usize,
-    target_base: QuartetBase,
-}
-
-impl<'a> BaseRunScanner<'a> {
-    pub(crate) fn new(data: &'a [u8], min_tetrads: usize, target_base: QuartetBase) -> Self {
-        Self {
-            data,
-            cursor: 0,
-            min_tetrads,
-            target_base,
-        }
-    }
+/// The QGRS Mapper G-score that ranks and filters every hit:
+///
+/// `gscore = floor(gmax - gavg + gmax * (tetrads - 2))`
+///
+/// `gmax = max_length - (4 * tetrads + 1)` is the loop budget a motif of
+/// `max_length` bp leaves once its tetrads and one base per loop are placed,
+/// and `gavg` is the mean of `|y1 - y2|`, `|y2 - y3|` and `|y1 - y3|`. Equal
+/// loops give the best score, `gmax * (tetrads - 1)`.
+///
+/// Everything is `i64` with saturating arithmetic, and `floor(x - s / 3)`
+/// for an integer `x` is `x - ceil(s / 3)`, so no limits can wrap it or
+/// lose precision. [`gscore_to_i32`] narrows it for [`G4::score`].
+pub(crate) fn gscore(tetrads: usize, max_length: usize, loops: [i32; 3]) -> i64 {
+    let [y1, y2, y3] = loops.map(i64::from);
+    let spread = (y1 - y2).abs() + (y2 - y3).abs() + (y1 - y3).abs();
+    let tetrads = i64::try_from(tetrads).unwrap_or(i64::MAX);
+    let gmax = i64::try_from(max_length)
+        .unwrap_or(i64::MAX)
+        .saturating_sub(tetrads.saturating_mul(4).saturating_add(1));
+    let bonus = gmax.saturating_mul(tetrads - 2);
+    gmax.saturating_add(bonus).saturating_sub((spread + 2) / 3)
 }
 
-impl<'a> Iterator for BaseRunScanner<'a> {
-    type Item = (usize, usize);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let len = self.data.len();
-        while self.cursor < len {
-            let search_slice = &self.data[self.cursor..];
-            let relative = memchr2(
-                self.target_base.lowercase_byte(),
-                self.target_base.uppercase_byte(),
-                search_slice,
-            )?;
-            let run_start = self.cursor + relative;
-            let mut run_end = run_start;
-            while run_end < len
-                && self
-                    .target_base
-                    .matches(unsafe { *self.data.get_unchecked(run_end) })
-            {
-                run_end += 1;
-            }
-            self.cursor = if run_end < len { run_end + 1 } else { len };
-            let run_len = run_end - run_start;
-            if run_len >= self.min_tetrads {
-                return Some((run_start, run_len));
-            }
-        }
-        None
-    }
+/// Converts a [`gscore`] to the `i32` stored on [`G4`] and exported as
+/// `score`. Out-of-range values are clamped with a warning, never wrapped.
+pub(crate) fn gscore_to_i32(score: i64) -> i32 {
+    i32::try_from(score).unwrap_or_else(|_| {
+        let clamped = if score < 0 { i32::MIN } else { i32::MAX };
+        #[cfg(feature = "tracing")]
+        tracing::warn!(score, clamped, "G-score outside the i32 range, clamped");
+        clamped
+    })
 }
 
 pub(crate) fn maximum_length(num_tetrads: usize, limits: ScanLimits) -> usize {
@@ -367,16 +751,149 @@ pub(crate) fn maximum_length(num_tetrads: usize, limits: ScanLimits) -> usize {
     base.min(limits.max_g4_length)
 }
 
+/// Best G-score a `tetrads`-tetrad hit can reach under `limits`: one-base
+/// equal loops in the longest motif [`maximum_length`] allows, which
+/// [`gscore`] scores `gmax * (tetrads - 1)`. 21, 64, 84 and 96 for 2 to 5
+/// tetrads under the default limits. Says nothing about whether such a motif
+/// fits the length limit at all; `i64::MIN` or below zero when it cannot.
+pub fn max_possible_score(tetrads: usize, limits: ScanLimits) -> i64 {
+    gscore(tetrads, maximum_length(tetrads, limits), [1, 1, 1])
+}
+
+#[cfg(feature = "mmap")]
 pub(crate) fn find_raw_bytes_no_chunking(
-    mut sequence: Vec<u8>,
+    sequence: Vec<u8>,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
-    sequence.make_ascii_lowercase();
     let seq = Arc::new(SequenceData::from_bytes(Arc::new(sequence)));
-    find_raw_with_sequence(seq, min_tetrads, min_score, limits, target_base)
+    find_raw_with_sequence(seq, min_tetrads, min_score, limits, target_base, hooks)
+}
+
+/// [`scan_short`](crate::qgrs::scan_short) for a linear sequence: the raw
+/// search runs on the borrowed bytes, and consolidation keeps only the
+/// current family's best candidate, so no [`G4`] or shared buffer is built.
+/// Candidates are visited in [`G4::key`] order, as [`consolidate_g4s`]
+/// visits hits, so the same member wins each family.
+///
+/// [`consolidate_g4s`]: crate::qgrs::consolidate_g4s
+pub(crate) fn scan_short_linear(sequence: &[u8], config: ScanConfig) -> Vec<G4Record> {
+    let limits = config.limits();
+    let target_base = config.target_base();
+    let mut seeds = Vec::new();
+    seed_candidates(
+        &mut seeds,
+        None,
+        sequence,
+        config.min_tetrads(),
+        limits,
+        target_base,
+    );
+    let mut raw = Vec::new();
+    drain_candidates_with(
+        seeds,
+        sequence,
+        sequence.len(),
+        config.min_score(),
+        limits,
+        target_base,
+        SearchHooks::default(),
+        |candidate| raw.push(ShortHit::new(*candidate)),
+    );
+    raw.sort_unstable_by_key(ShortHit::key);
+
+    let policy = config.family_policy();
+    let mut records = Vec::new();
+    let mut best: Option<ShortHit> = None;
+    let mut family_end = 0;
+    for hit in raw {
+        match best.as_mut() {
+            Some(current) if hit.start() <= family_end => {
+                family_end = family_end.max(hit.end());
+                if policy.prefers_rank(current.rank(), hit.rank()) {
+                    *current = hit;
+                }
+            }
+            _ => {
+                records.extend(best.map(|winner| winner.to_record(sequence, config)));
+                family_end = hit.end();
+                best = Some(hit);
+            }
+        }
+    }
+    records.extend(best.map(|winner| winner.to_record(sequence, config)));
+    records
+}
+
+/// A viable candidate with its score computed once for sorting and ranking.
+#[derive(Clone, Copy)]
+struct ShortHit {
+    candidate: G4Candidate,
+    score: i32,
+}
+
+impl ShortHit {
+    fn new(candidate: G4Candidate) -> Self {
+        Self {
+            score: gscore_to_i32(candidate.score()),
+            candidate,
+        }
+    }
+
+    /// 1-based, like [`G4::start`].
+    fn start(&self) -> usize {
+        self.candidate.start + 1
+    }
+
+    fn end(&self) -> usize {
+        self.candidate.start + self.candidate.length()
+    }
+
+    /// Same order as [`G4::key`].
+    fn key(&self) -> (usize, usize, usize, i32, i32, i32, i32) {
+        let candidate = &self.candidate;
+        (
+            self.start(),
+            self.end(),
+            candidate.num_tetrads,
+            self.score,
+            candidate.y1,
+            candidate.y2,
+            candidate.y3,
+        )
+    }
+
+    fn rank(&self) -> FamilyRank {
+        FamilyRank {
+            score: self.score,
+            length: self.candidate.length(),
+            tetrads: self.candidate.num_tetrads,
+            start: self.start(),
+        }
+    }
+
+    fn to_record(self, sequence: &[u8], config: ScanConfig) -> G4Record {
+        let candidate = self.candidate;
+        let length = candidate.length();
+        let bases = &sequence[candidate.start..candidate.start + length];
+        G4Record {
+            start: self.start(),
+            end: self.end(),
+            length,
+            tetrads: candidate.num_tetrads,
+            y1: candidate.y1,
+            y2: candidate.y2,
+            y3: candidate.y3,
+            score: self.score,
+            sequence: display_string(bases, config.preserves_case()),
+            chrom: None,
+            strand: None,
+            motif_type: MotifType::from(config.target_base()),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -400,26 +917,74 @@ pub(crate) fn find_raw_on_window_bytes(
     seq: Arc<SequenceData>,
     window_bounds: RawSearchWindow,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
     // Called by chunked batch scans only. The chunk scheduler already shapes
     // windows as (primary, primary+overlap) and expects this function to avoid
     // emitting hits whose start ≥ primary_end so that overlap regions don't
     // double-count.
+    // Every seed is a target base before primary_end, so a primary section
+    // without one (a gap, or AT-rich sequence) has nothing to search.
+    let primary = &seq.normalized[window_bounds.base_offset..window_bounds.primary_end];
+    if !target_base.occurs_in(primary) {
+        return Vec::new();
+    }
     let window = &seq.normalized[window_bounds.base_offset..window_bounds.window_end];
-    let mut cands = VecDeque::new();
-    let mut max_tetrads_allowed = limits.max_run;
-    if limits.max_g4_length >= 4 {
-        max_tetrads_allowed = max_tetrads_allowed.min(limits.max_g4_length / 4);
+    let mut seeds = Vec::new();
+    if let Some(anchors) = hooks.anchors {
+        let first = anchors.partition_point(|&anchor| anchor < window_bounds.base_offset);
+        let last = anchors.partition_point(|&anchor| anchor < window_bounds.primary_end);
+        seed_anchors(
+            &mut seeds,
+            window,
+            window_bounds.base_offset,
+            &anchors[first..last],
+            min_tetrads,
+            limits,
+            target_base,
+        );
+        return drain_chunk_window(
+            seeds,
+            &seq.normalized,
+            window_bounds,
+            min_score,
+            limits,
+            target_base,
+            hooks,
+        );
     }
+    let mut runs = hooks.runs.map(|_| Vec::new());
+    let max_tetrads_allowed = limits.max_tetrads();
     if max_tetrads_allowed >= min_tetrads {
-        for (run_start_rel, run_len) in BaseRunScanner::new(window, min_tetrads, target_base) {
+        for GRun {
+            start: run_start_rel,
+            len: run_len,
+        } in BaseRunScanner::new(window, min_tetrads, target_base)
+        {
             let run_start = window_bounds.base_offset + run_start_rel;
             if run_start >= window_bounds.primary_end {
                 continue;
             }
+            // Runs are clamped like hits: a run cut by the window start was
+            // reported whole by the window it began in, and one cut by the
+            // window end is measured on the full sequence.
+            if let Some(runs) = runs.as_mut() {
+                let continued = run_start_rel == 0
+                    && run_start > 0
+                    && target_base.matches(seq.normalized[run_start - 1]);
+                if !continued {
+                    let len = run_len_from(&seq.normalized, run_start, run_len, target_base);
+                    if len <= limits.max_run {
+                        runs.push(GRun {
+                            start: run_start,
+                            len,
+                        });
+                    }
+                }
+            }
             let max_tetrads_for_run = run_len.min(max_tetrads_allowed);
             let mut tetrads = min_tetrads;
             while tetrads <= max_tetrads_for_run {
@@ -433,77 +998,284 @@ pub(crate) fn find_raw_on_window_bytes(
                 let allowed_offset = base_max_offset.min(boundary_offset);
                 for offset in 0..=allowed_offset {
                     let start = run_start + offset;
-                    cands.push_back(G4Candidate::new(
-                        seq.clone(),
-                        tetrads,
-                        start,
-                        limits,
-                        target_base,
-                    ));
+                    seeds.push(G4Candidate::new(tetrads, start, limits));
                 }
                 tetrads += 1;
             }
         }
     }
 
-    let mut raw_g4s = Vec::new();
-    while let Some(cand) = cands.pop_front() {
-        if cand.complete() {
-            if cand.viable(min_score) {
-                let g4 = G4::from_candidate(&cand);
-                raw_g4s.push(g4);
-            }
-        } else {
-            for expanded in cand.expand() {
-                cands.push_back(expanded);
-            }
-        }
+    if let (Some(collector), Some(runs)) = (hooks.runs, runs) {
+        collector.extend(runs);
     }
-    raw_g4s.sort_by_key(|a| (a.start, a.end));
+    drain_chunk_window(
+        seeds,
+        &seq.normalized,
+        window_bounds,
+        min_score,
+        limits,
+        target_base,
+        hooks,
+    )
+}
+
+/// Expands a chunked window's seeds on the window's own bytes. Its overlap
+/// holds the longest motif, so candidates only run into `window_end` when a
+/// test shortens it; they are expanded again on the whole record then.
+fn drain_chunk_window(
+    seeds: Vec<G4Candidate>,
+    sequence_data: &Arc<Vec<u8>>,
+    window_bounds: RawSearchWindow,
+    min_score: MinScore,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
+) -> Vec<G4> {
+    let (raw_g4s, rescanned) = drain_window(
+        seeds,
+        sequence_data,
+        window_bounds.window_end,
+        min_score,
+        limits,
+        target_base,
+        hooks,
+    );
+    debug_assert!(
+        rescanned == 0
+            || window_bounds.window_end - window_bounds.primary_end < limits.max_motif_length(),
+        "window ending at {} cut off {rescanned} candidates despite a full overlap",
+        window_bounds.window_end
+    );
     raw_g4s
 }
 
+/// Length of the target-base run at `start`, of which `known` bases were
+/// already seen.
+fn run_len_from(seq: &[u8], start: usize, known: usize, target_base: QuartetBase) -> usize {
+    let mut end = start + known;
+    while end < seq.len() && target_base.matches(seq[end]) {
+        end += 1;
+    }
+    end - start
+}
+
 pub(crate) fn find_raw_with_sequence(
     seq: Arc<SequenceData>,
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
 ) -> Vec<G4> {
-    let mut cands = VecDeque::new();
-    seed_queue(&mut cands, seq.clone(), min_tetrads, limits, target_base);
+    let mut seeds = Vec::new();
+    if let Some(anchors) = hooks.anchors {
+        seed_anchors(
+            &mut seeds,
+            &seq.normalized,
+            0,
+            anchors,
+            min_tetrads,
+            limits,
+            target_base,
+        );
+    } else {
+        let mut runs = hooks.runs.map(|_| Vec::new());
+        seed_candidates(
+            &mut seeds,
+            runs.as_mut(),
+            &seq.normalized,
+            min_tetrads,
+            limits,
+            target_base,
+        );
+        if let (Some(collector), Some(runs)) = (hooks.runs, runs) {
+            collector.extend(runs);
+        }
+    }
+    drain_candidates(
+        seeds,
+        &seq.normalized,
+        min_score,
+        limits,
+        target_base,
+        hooks,
+    )
+}
+
+/// Expands each seed depth-first on an explicit stack, so at most one seed's
+/// partial candidates are alive at a time: the stack never holds more than
+/// the three loop levels' pending siblings. Expansions are pushed in reverse
+/// so hits complete in (seed, y1, y2, y3) order, but nothing relies on that:
+/// [`drain_window`] orders the hits with `sort_unstable_by_key(G4::key)`, and
+/// the result is deterministic only because [`G4::key`] covers every field
+/// that tells two hits apart, so hits with equal keys are identical.
+///
+/// Stops early, returning the hits found so far, once `hooks.cancel` is set; callers
+/// check the token again and discard the partial result.
+///
+/// With `hooks.counters`, the work done here is added to them on return.
+fn drain_candidates(
+    seeds: Vec<G4Candidate>,
+    sequence_data: &Arc<Vec<u8>>,
+    min_score: MinScore,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
+) -> Vec<G4> {
+    drain_window(
+        seeds,
+        sequence_data,
+        sequence_data.len(),
+        min_score,
+        limits,
+        target_base,
+        hooks,
+    )
+    .0
+}
+
+/// [`drain_candidates`] on the bytes before `window_end`. A candidate whose
+/// loop search reaches `window_end` before its length limit is expanded
+/// again on the whole record, so no hit depends on the window overlap
+/// being long enough. Also returns how many expansions that took.
+fn drain_window(
+    seeds: Vec<G4Candidate>,
+    sequence_data: &Arc<Vec<u8>>,
+    window_end: usize,
+    min_score: MinScore,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
+) -> (Vec<G4>, usize) {
     let mut raw_g4s = Vec::new();
-    while let Some(cand) = cands.pop_front() {
-        if cand.complete() {
-            if cand.viable(min_score) {
-                let g4 = G4::from_candidate(&cand);
-                raw_g4s.push(g4);
-            }
+    let rescanned = drain_candidates_with(
+        seeds,
+        sequence_data,
+        window_end,
+        min_score,
+        limits,
+        target_base,
+        hooks,
+        |candidate| raw_g4s.push(G4::from_candidate(candidate, sequence_data)),
+    );
+    raw_g4s.sort_unstable_by_key(G4::key);
+    (raw_g4s, rescanned)
+}
+
+/// The search loop of [`drain_window`], handing each viable candidate to
+/// `emit` instead of building a [`G4`] for it. Returns the number of
+/// expansions redone on the whole record.
+#[allow(clippy::too_many_arguments)]
+fn drain_candidates_with<E>(
+    seeds: Vec<G4Candidate>,
+    sequence: &[u8],
+    window_end: usize,
+    min_score: MinScore,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+    hooks: SearchHooks<'_>,
+    mut emit: E,
+) -> usize
+where
+    E: FnMut(&G4Candidate),
+{
+    let ctx = SearchContext {
+        seq: &sequence[..window_end],
+        record: (window_end < sequence.len()).then_some(sequence),
+        max_run: limits.max_run,
+        target_base,
+        limits,
+        restricts_loops: limits.restricts_loops(),
+    };
+    let whole = SearchContext {
+        seq: sequence,
+        record: None,
+        ..ctx
+    };
+    // Candidates that already reach past the window were expanded on the
+    // whole record and stay there.
+    let ctx_for = |cand: &G4Candidate| {
+        if cand.covered_end() > window_end {
+            &whole
         } else {
-            for expanded in cand.expand() {
-                cands.push_back(expanded);
+            &ctx
+        }
+    };
+    let mut rescanned = 0usize;
+    let counting = hooks.counters.is_some();
+    let mut metrics = ScanMetrics {
+        seeded: seeds.len(),
+        ..ScanMetrics::default()
+    };
+    let mut viable = 0usize;
+    let mut stack = Vec::new();
+    let mut pops = 0usize;
+    'seeds: for seed in seeds {
+        stack.push(seed);
+        while let Some(cand) = stack.pop() {
+            pops += 1;
+            if pops.is_multiple_of(CANCEL_CHECK_INTERVAL) && is_cancelled(hooks.cancel) {
+                break 'seeds;
+            }
+            if cand.complete() {
+                if counting {
+                    metrics.complete += 1;
+                }
+                if cand.viable(
+                    ctx_for(&cand),
+                    min_score.for_tetrads(cand.num_tetrads, limits),
+                ) {
+                    viable += 1;
+                    emit(&cand);
+                }
+            } else {
+                let before = stack.len();
+                if !cand.expand(ctx_for(&cand), &mut stack) {
+                    stack.truncate(before);
+                    cand.expand(&whole, &mut stack);
+                    rescanned += 1;
+                }
+                if counting {
+                    metrics.expanded += stack.len() - before;
+                }
+                #[cfg(test)]
+                note_stack_len(stack.len());
             }
         }
     }
-    raw_g4s.sort_by_key(|a| (a.start, a.end));
-    raw_g4s
+    if let Some(counters) = hooks.counters {
+        metrics.viable = viable;
+        metrics.rescanned = rescanned;
+        counters.add(&metrics);
+    }
+    rescanned
 }
 
-fn seed_queue(
-    cands: &mut VecDeque<G4Candidate>,
-    seq: Arc<SequenceData>,
+fn seed_candidates(
+    seeds: &mut Vec<G4Candidate>,
+    mut runs: Option<&mut Vec<GRun>>,
+    seq: &[u8],
     min_tetrads: usize,
     limits: ScanLimits,
     target_base: QuartetBase,
 ) {
-    let mut max_tetrads_allowed = limits.max_run;
-    if limits.max_g4_length >= 4 {
-        max_tetrads_allowed = max_tetrads_allowed.min(limits.max_g4_length / 4);
-    }
+    let max_tetrads_allowed = limits.max_tetrads();
     if max_tetrads_allowed < min_tetrads {
         return;
     }
-    for (run_start, run_len) in BaseRunScanner::new(&seq.normalized, min_tetrads, target_base) {
+    for GRun {
+        start: run_start,
+        len: run_len,
+    } in BaseRunScanner::new(seq, min_tetrads, target_base)
+    {
+        if let Some(runs) = runs.as_mut()
+            && run_len <= limits.max_run
+        {
+            runs.push(GRun {
+                start: run_start,
+                len: run_len,
+            });
+        }
         let max_tetrads_for_run = run_len.min(max_tetrads_allowed);
         let mut tetrads = min_tetrads;
         while tetrads <= max_tetrads_for_run {
@@ -513,14 +1285,37 @@ fn seed_queue(
             let max_offset = run_len.saturating_sub(tetrads);
             for offset in 0..=max_offset {
                 let start = run_start + offset;
-                cands.push_back(G4Candidate::new(
-                    seq.clone(),
-                    tetrads,
-                    start,
-                    limits,
-                    target_base,
-                ));
+                seeds.push(G4Candidate::new(tetrads, start, limits));
+            }
+            tetrads += 1;
+        }
+    }
+}
+
+/// The seeds [`seed_candidates`] would place at each of `anchors` and
+/// nowhere else: every tetrad count the target-base run from the anchor to
+/// the end of `seq` allows. Anchors are positions in the sequence `seq`
+/// starts at `offset` of, and must lie within it; one off a target-base run
+/// seeds nothing.
+fn seed_anchors(
+    seeds: &mut Vec<G4Candidate>,
+    seq: &[u8],
+    offset: usize,
+    anchors: &[usize],
+    min_tetrads: usize,
+    limits: ScanLimits,
+    target_base: QuartetBase,
+) {
+    let max_tetrads_allowed = limits.max_tetrads();
+    for &anchor in anchors {
+        let run_len = run_len_from(seq, anchor - offset, 0, target_base);
+        let max_tetrads_for_run = run_len.min(max_tetrads_allowed);
+        let mut tetrads = min_tetrads;
+        while tetrads <= max_tetrads_for_run {
+            if tetrads * 4 > limits.max_g4_length {
+                break;
             }
+            seeds.push(G4Candidate::new(tetrads, anchor, limits));
             tetrads += 1;
         }
     }
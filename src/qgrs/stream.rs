@@ -1,16 +1,34 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
 use std::io::{self, BufRead};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "parallel")]
 use rayon::spawn;
 
+use super::checkpoint::{Checkpointer, RecordState, ResumePoint, SettledFamilies};
 use super::{
-    G4, QuartetBase, ScanLimits, SequenceTopology, chunk_size_for_limits, compute_chunk_overlap,
-    consolidate_g4s_with_topology, find_raw_bytes_no_chunking, input::open_input_reader,
-    parse_chrom_name, retain_circular_raw_hits, shift_g4,
+    CancellationToken, DeliveryOrder, Family, FamilyBuilder, FamilyPolicy, G4, MinScore,
+    OccurrenceCounter, QgrsError, QuartetBase, ScanConfig, ScanLimits, SearchCounters, SearchHooks,
+    SearchResults, SequenceTopology, StreamCheckpoint, chunk_size_for_limits,
+    compute_chunk_overlap, consolidate_g4s_with_topology, find_raw_bytes_no_chunking,
+    input::{
+        is_comment_line, is_header_line, open_sequence_reader, open_sequence_reader_at,
+        skip_utf8_bom,
+    },
+    is_cancelled, parse_chrom_header_bytes, parse_chrom_name, retain_circular_raw_hits, shift_g4,
 };
 
+/// Without the `parallel` feature a window is scanned as soon as it is
+/// dispatched, on the reading thread.
+#[cfg(not(feature = "parallel"))]
+fn spawn<F: FnOnce()>(work: F) {
+    work();
+}
+
 pub struct StreamChromosomeResults {
     pub hits: Vec<G4>,
     pub family_ranges: Vec<(usize, usize)>,
@@ -86,7 +104,7 @@ pub fn process_fasta_stream_with_limits_topology<F>(
 where
     F: FnMut(String, Vec<G4>) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_topology(
         reader,
         min_tetrads,
@@ -108,7 +126,7 @@ pub fn process_fasta_stream_with_limits_topology_and_len<F>(
 where
     F: FnMut(String, Vec<G4>, usize) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_topology_and_len(
         reader,
         min_tetrads,
@@ -131,7 +149,7 @@ pub fn process_fasta_stream_with_limits_topology_and_len_with_base<F>(
 where
     F: FnMut(String, Vec<G4>, usize) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_topology_and_len_with_base(
         reader,
         min_tetrads,
@@ -154,7 +172,7 @@ pub fn process_fasta_stream_with_limits_topology_and_sequence<F>(
 where
     F: FnMut(String, Vec<G4>, Vec<u8>) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_topology_and_sequence(
         reader,
         min_tetrads,
@@ -196,7 +214,7 @@ pub fn process_fasta_stream_with_limits_overlap_topology<F>(
 where
     F: FnMut(String, StreamChromosomeResults) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_overlap_topology(
         reader,
         min_tetrads,
@@ -218,7 +236,7 @@ pub fn process_fasta_stream_with_limits_overlap_topology_and_len<F>(
 where
     F: FnMut(String, StreamChromosomeResults, usize) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_overlap_topology_and_len(
         reader,
         min_tetrads,
@@ -241,7 +259,7 @@ pub fn process_fasta_stream_with_limits_overlap_topology_and_len_with_base<F>(
 where
     F: FnMut(String, StreamChromosomeResults, usize) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_overlap_topology_and_len_with_base(
         reader,
         min_tetrads,
@@ -264,7 +282,7 @@ pub fn process_fasta_stream_with_limits_overlap_topology_and_sequence<F>(
 where
     F: FnMut(String, StreamChromosomeResults, Vec<u8>) -> io::Result<()>,
 {
-    let reader = open_input_reader(path)?;
+    let reader = open_sequence_reader(path)?;
     process_reader_with_limits_overlap_topology_and_sequence(
         reader,
         min_tetrads,
@@ -275,6 +293,155 @@ where
     )
 }
 
+/// Streams a FASTA file record by record, scanning each under `config` and
+/// handing its [`SearchResults`] to `on_chromosome`.
+pub fn process_fasta_stream_with_config<F>(
+    path: &Path,
+    config: ScanConfig,
+    capture_raw: bool,
+    mut on_chromosome: F,
+) -> io::Result<usize>
+where
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let reader = open_sequence_reader(path)?;
+    process_reader_with_config(reader, config, capture_raw, &mut on_chromosome)
+}
+
+pub fn process_fasta_stream_with_skip<S, F>(
+    path: &Path,
+    config: ScanConfig,
+    capture_raw: bool,
+    mut skip: S,
+    mut on_chromosome: F,
+) -> io::Result<usize>
+where
+    S: FnMut(&str, usize) -> bool,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let reader = open_sequence_reader(path)?;
+    process_reader_with_skip(reader, config, capture_raw, &mut skip, &mut on_chromosome)
+}
+
+/// Like [`process_fasta_stream_with_skip`], but stops with an `Interrupted`
+/// error wrapping [`QgrsError::Cancelled`] once `cancel` is set. Records
+/// already handed to `on_chromosome` are complete; the one in progress is
+/// dropped.
+pub fn process_fasta_stream_with_cancellation<S, F>(
+    path: &Path,
+    config: ScanConfig,
+    capture_raw: bool,
+    mut skip: S,
+    cancel: &CancellationToken,
+    mut on_chromosome: F,
+) -> io::Result<usize>
+where
+    S: FnMut(&str, usize) -> bool,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let reader = open_sequence_reader(path)?;
+    process_reader_with_cancellation(
+        reader,
+        config,
+        capture_raw,
+        &mut skip,
+        Some(cancel),
+        &mut on_chromosome,
+    )
+}
+
+/// Like [`process_fasta_stream_with_skip`], but saves its progress to
+/// `checkpoint` every [`StreamCheckpoint::period`] (or
+/// [`StreamCheckpoint::interval`] dispatched windows), so a
+/// killed scan run again with the same parameters continues inside the
+/// record it was reading instead of starting that record over.
+///
+/// When the checkpoint file exists, the input is opened at the saved byte
+/// offset and the open record's windows carry on from its saved state and
+/// hits. Records that ended before it are not read again: each one that
+/// was skipped is passed to `skip` once more, and each one delivered to
+/// `on_resumed` with its name, occurrence and sequence length, in file order.
+/// A checkpoint of another input, or written with other parameters, is an
+/// `InvalidInput` error. Families that no later window can join are
+/// consolidated at each save, the rest when the record ends, and the
+/// checkpoint files are removed once the scan finishes. The search counts in
+/// [`SearchStats::metrics`](crate::qgrs::SearchStats::metrics) of a resumed
+/// record cover only the windows scanned after the resume.
+pub fn process_fasta_stream_with_checkpoint<S, D, F>(
+    path: &Path,
+    config: ScanConfig,
+    capture_raw: bool,
+    checkpoint: &StreamCheckpoint,
+    mut skip: S,
+    mut on_resumed: D,
+    mut on_chromosome: F,
+) -> io::Result<usize>
+where
+    S: FnMut(&str, usize) -> bool,
+    D: FnMut(&str, usize, usize),
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let input_len = fs::metadata(path)?.len();
+    let mut checkpointer = Checkpointer::new(checkpoint, config, capture_raw, input_len);
+    let resume = checkpointer.load()?;
+    let mut byte_offset = 0;
+    if let Some(resume) = resume.as_ref() {
+        tracing::info!(
+            path = ?checkpoint.path(),
+            name = %resume.record.name,
+            occurrence = resume.record.occurrence,
+            sequence_len = resume.record.sequence_len,
+            "resuming from checkpoint"
+        );
+        for earlier in &resume.earlier {
+            match earlier.delivered {
+                Some(sequence_len) => on_resumed(&earlier.name, earlier.occurrence, sequence_len),
+                None => {
+                    skip(&earlier.name, earlier.occurrence);
+                }
+            }
+        }
+        byte_offset = resume.byte_offset;
+    }
+    let reader = open_sequence_reader_at(path, byte_offset)?;
+    let records = process_reader_core(
+        reader,
+        config,
+        capture_raw,
+        &mut skip,
+        CoreHooks {
+            checkpoint: Some(&mut checkpointer),
+            resume,
+            ..CoreHooks::default()
+        },
+        &mut on_chromosome,
+    )?;
+    checkpointer.finish()?;
+    Ok(records)
+}
+
+/// Like [`process_fasta_stream_with_config`], but also hands each consolidated
+/// hit to `on_hit` while its chromosome is still being read. A linear family
+/// is reported once every window up to its end has been scanned, so hits
+/// arrive in coordinate order long before the record ends. Circular records
+/// can gain families across the origin until the last base, so their hits are
+/// reported when the record finishes. `on_chromosome` still receives the full
+/// `SearchResults`, whose `hits` are exactly the hits passed to `on_hit`.
+pub fn process_fasta_stream_with_hits<H, F>(
+    path: &Path,
+    config: ScanConfig,
+    capture_raw: bool,
+    mut on_hit: H,
+    mut on_chromosome: F,
+) -> io::Result<usize>
+where
+    H: FnMut(&str, G4) -> io::Result<()>,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let reader = open_sequence_reader(path)?;
+    process_reader_with_hits(reader, config, capture_raw, &mut on_hit, &mut on_chromosome)
+}
+
 pub fn process_reader<R, F>(
     reader: R,
     min_tetrads: usize,
@@ -348,18 +515,19 @@ where
     R: BufRead,
     F: FnMut(String, Vec<G4>) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results) = chrom.finish();
+                let (name, results) = chrom.finish()?;
                 on_chromosome(name, results)?;
             }
             chrom_index += 1;
@@ -367,35 +535,38 @@ where
             current = Some(StreamChromosome::new(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results) = chrom.finish();
+        let (name, results) = chrom.finish()?;
         on_chromosome(name, results)?;
         Ok(chrom_index.max(1))
     } else {
@@ -439,18 +610,19 @@ where
     R: BufRead,
     F: FnMut(String, Vec<G4>, usize) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results, sequence_len) = chrom.finish_with_sequence_len();
+                let (name, results, sequence_len) = chrom.finish_with_sequence_len()?;
                 on_chromosome(name, results, sequence_len)?;
             }
             chrom_index += 1;
@@ -458,37 +630,40 @@ where
             current = Some(StreamChromosome::new_with_base(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 target_base,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new_with_base(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 target_base,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results, sequence_len) = chrom.finish_with_sequence_len();
+        let (name, results, sequence_len) = chrom.finish_with_sequence_len()?;
         on_chromosome(name, results, sequence_len)?;
         Ok(chrom_index.max(1))
     } else {
@@ -508,18 +683,19 @@ where
     R: BufRead,
     F: FnMut(String, Vec<G4>, Vec<u8>) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results, sequence) = chrom.finish_with_sequence();
+                let (name, results, sequence) = chrom.finish_with_sequence()?;
                 on_chromosome(name, results, sequence)?;
             }
             chrom_index += 1;
@@ -527,37 +703,40 @@ where
             current = Some(StreamChromosome::new_with_sequence_capture(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 true,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new_with_sequence_capture(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 true,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results, sequence) = chrom.finish_with_sequence();
+        let (name, results, sequence) = chrom.finish_with_sequence()?;
         on_chromosome(name, results, sequence)?;
         Ok(chrom_index.max(1))
     } else {
@@ -598,18 +777,19 @@ where
     R: BufRead,
     F: FnMut(String, StreamChromosomeResults) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results) = chrom.finish_with_overlap();
+                let (name, results) = chrom.finish_with_overlap()?;
                 on_chromosome(name, results)?;
             }
             chrom_index += 1;
@@ -617,35 +797,38 @@ where
             current = Some(StreamChromosome::new(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results) = chrom.finish_with_overlap();
+        let (name, results) = chrom.finish_with_overlap()?;
         on_chromosome(name, results)?;
         Ok(chrom_index.max(1))
     } else {
@@ -689,18 +872,19 @@ where
     R: BufRead,
     F: FnMut(String, StreamChromosomeResults, usize) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results, sequence_len) = chrom.finish_with_overlap_and_sequence_len();
+                let (name, results, sequence_len) = chrom.finish_with_overlap_and_sequence_len()?;
                 on_chromosome(name, results, sequence_len)?;
             }
             chrom_index += 1;
@@ -708,37 +892,40 @@ where
             current = Some(StreamChromosome::new_with_base(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 target_base,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new_with_base(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 target_base,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results, sequence_len) = chrom.finish_with_overlap_and_sequence_len();
+        let (name, results, sequence_len) = chrom.finish_with_overlap_and_sequence_len()?;
         on_chromosome(name, results, sequence_len)?;
         Ok(chrom_index.max(1))
     } else {
@@ -758,18 +945,19 @@ where
     R: BufRead,
     F: FnMut(String, StreamChromosomeResults, Vec<u8>) -> io::Result<()>,
 {
-    let mut line = String::new();
+    limits.validate(min_tetrads)?;
+    let mut line = Vec::new();
     let mut chrom_index = 0usize;
     let mut current: Option<StreamChromosome> = None;
 
     loop {
         line.clear();
-        if reader.read_line(&mut line)? == 0 {
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        if line.starts_with('>') {
+        if is_header_line(&line) {
             if let Some(chrom) = current.take() {
-                let (name, results, sequence) = chrom.finish_with_overlap_and_sequence();
+                let (name, results, sequence) = chrom.finish_with_overlap_and_sequence()?;
                 on_chromosome(name, results, sequence)?;
             }
             chrom_index += 1;
@@ -777,37 +965,40 @@ where
             current = Some(StreamChromosome::new_with_sequence_capture(
                 name,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 true,
             ));
             continue;
         }
+        if is_comment_line(&line) {
+            continue;
+        }
         if current.is_none() {
             chrom_index += 1;
             let fallback = format!("chromosome_{}", chrom_index);
             current = Some(StreamChromosome::new_with_sequence_capture(
                 fallback,
                 min_tetrads,
-                min_score,
+                MinScore::Fixed(min_score),
                 limits,
                 topology,
                 true,
             ));
         }
         if let Some(chrom) = current.as_mut() {
-            for byte in line.bytes() {
+            for byte in line.iter() {
                 if byte.is_ascii_whitespace() {
                     continue;
                 }
-                chrom.push_byte(byte.to_ascii_lowercase());
+                chrom.push_byte(*byte);
             }
         }
     }
 
     if let Some(chrom) = current {
-        let (name, results, sequence) = chrom.finish_with_overlap_and_sequence();
+        let (name, results, sequence) = chrom.finish_with_overlap_and_sequence()?;
         on_chromosome(name, results, sequence)?;
         Ok(chrom_index.max(1))
     } else {
@@ -815,17 +1006,472 @@ where
     }
 }
 
+/// [`process_fasta_stream_with_config`] for FASTA text from any reader.
+pub fn process_reader_with_config<R, F>(
+    reader: R,
+    config: ScanConfig,
+    capture_raw: bool,
+    on_chromosome: &mut F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    process_reader_with_skip(
+        reader,
+        config,
+        capture_raw,
+        &mut |_, _| false,
+        on_chromosome,
+    )
+}
+
+/// Like [`process_reader_with_config`], but records for which
+/// `skip(name, occurrence)` returns true are read past without scanning and
+/// never reach `on_chromosome`. They still count towards the returned total.
+pub fn process_reader_with_skip<R, S, F>(
+    reader: R,
+    config: ScanConfig,
+    capture_raw: bool,
+    skip: &mut S,
+    on_chromosome: &mut F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    S: FnMut(&str, usize) -> bool,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    process_reader_with_cancellation(reader, config, capture_raw, skip, None, on_chromosome)
+}
+
+/// Reader form of [`process_fasta_stream_with_cancellation`].
+pub fn process_reader_with_cancellation<R, S, F>(
+    reader: R,
+    config: ScanConfig,
+    capture_raw: bool,
+    skip: &mut S,
+    cancel: Option<&CancellationToken>,
+    on_chromosome: &mut F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    S: FnMut(&str, usize) -> bool,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    process_reader_core(
+        reader,
+        config,
+        capture_raw,
+        skip,
+        CoreHooks {
+            cancel,
+            ..CoreHooks::default()
+        },
+        on_chromosome,
+    )
+}
+
+/// Reader form of [`process_fasta_stream_with_hits`].
+pub fn process_reader_with_hits<R, H, F>(
+    reader: R,
+    config: ScanConfig,
+    capture_raw: bool,
+    on_hit: &mut H,
+    on_chromosome: &mut F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    H: FnMut(&str, G4) -> io::Result<()>,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    process_reader_core(
+        reader,
+        config,
+        capture_raw,
+        &mut |_, _| false,
+        CoreHooks {
+            on_hit: Some(on_hit),
+            ..CoreHooks::default()
+        },
+        on_chromosome,
+    )
+}
+
+type HitCallback<'a> = Option<&'a mut dyn FnMut(&str, G4) -> io::Result<()>>;
+
+/// What a stream scan does besides reading records: cancellation, per-hit
+/// delivery and checkpoints.
+#[derive(Default)]
+struct CoreHooks<'a> {
+    cancel: Option<&'a CancellationToken>,
+    on_hit: HitCallback<'a>,
+    checkpoint: Option<&'a mut Checkpointer>,
+    /// Where a checkpointed scan resumes; the reader starts at its offset.
+    resume: Option<ResumePoint>,
+}
+
+fn process_reader_core<R, S, F>(
+    mut reader: R,
+    config: ScanConfig,
+    capture_raw: bool,
+    skip: &mut S,
+    hooks: CoreHooks<'_>,
+    on_chromosome: &mut F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    S: FnMut(&str, usize) -> bool,
+    F: FnMut(SearchResults) -> io::Result<()>,
+{
+    let CoreHooks {
+        cancel,
+        mut on_hit,
+        mut checkpoint,
+        resume,
+    } = hooks;
+    let check_cancelled = || -> io::Result<()> {
+        if is_cancelled(cancel) {
+            return Err(QgrsError::Cancelled.into());
+        }
+        Ok(())
+    };
+    config.limits().validate(config.min_tetrads())?;
+    let mut line = Vec::new();
+    let mut chrom_index = 0usize;
+    let mut occurrences = OccurrenceCounter::default();
+    let mut delivery = RecordDelivery::new(config);
+    // `Some((None, ..))` is a record being skipped.
+    let mut current: Option<(Option<StreamChromosome>, usize, usize)> = None;
+    let incremental = on_hit.is_some();
+    let new_chromosome = |name: String, description: Option<String>| {
+        let chrom = StreamChromosome::new_with_base(
+            name,
+            config.min_tetrads(),
+            config.min_score(),
+            config.limits(),
+            config.topology(),
+            config.target_base(),
+        )
+        .with_description(description)
+        .with_cancellation(cancel.cloned())
+        .with_window_timeout(config.window_timeout())
+        .with_metrics(config.collects_metrics());
+        if incremental {
+            chrom.with_incremental_hits(config.family_policy())
+        } else {
+            chrom
+        }
+    };
+    // Input bytes read so far, a byte order mark included.
+    let mut consumed = match resume {
+        Some(resume) => {
+            let record = resume.record;
+            for earlier in resume.earlier {
+                occurrences.tag(earlier.name);
+            }
+            occurrences.tag(record.name.clone());
+            chrom_index = record.index + 1;
+            delivery.next = record.index;
+            let mut chrom = new_chromosome(record.name.clone(), record.description.clone());
+            chrom.comment_lines = record.comment_lines;
+            chrom
+                .scheduler
+                .restore(&record, resume.settled, resume.hits);
+            current = Some((Some(chrom), record.occurrence, record.index));
+            resume.byte_offset
+        }
+        None => skip_utf8_bom(&mut reader)? as u64,
+    };
+    let mut finish_record = |chrom: Option<StreamChromosome>,
+                             occurrence: usize,
+                             index: usize,
+                             on_hit: &mut HitCallback<'_>,
+                             checkpoint: &mut Option<&mut Checkpointer>|
+     -> io::Result<()> {
+        if let Some(checkpoint) = checkpoint.as_deref_mut() {
+            checkpoint.record_finished(chrom.as_ref().map(|chrom| chrom.scheduler.sequence_len()));
+        }
+        let Some(chrom) = chrom else {
+            return delivery.skipped(index, on_chromosome);
+        };
+        let mut results = match on_hit {
+            Some(on_hit) => {
+                let (results, remaining) =
+                    chrom.finish_search_results_with_hits(occurrence, config, capture_raw)?;
+                check_cancelled()?;
+                for g4 in remaining {
+                    on_hit(&results.name, g4)?;
+                }
+                results
+            }
+            None => chrom.finish_search_results(occurrence, config, capture_raw)?,
+        };
+        check_cancelled()?;
+        results.index = index;
+        delivery.finished(results, on_chromosome)
+    };
+    let mut start_chromosome =
+        |name: String,
+         description: Option<String>,
+         index: usize,
+         occurrences: &mut OccurrenceCounter,
+         checkpoint: &mut Option<&mut Checkpointer>| {
+            // Records are scanned one at a time: every earlier record has
+            // been delivered or skipped, so `RecordDelivery::can_start` holds.
+            let (name, occurrence) = occurrences.tag(name);
+            if let Some(checkpoint) = checkpoint.as_deref_mut() {
+                checkpoint.record_started(&name, occurrence);
+            }
+            if skip(&name, occurrence) {
+                return (None, occurrence, index);
+            }
+            (Some(new_chromosome(name, description)), occurrence, index)
+        };
+
+    loop {
+        check_cancelled()?;
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        consumed += read as u64;
+        if is_header_line(&line) {
+            if let Some((chrom, occurrence, index)) = current.take() {
+                finish_record(chrom, occurrence, index, &mut on_hit, &mut checkpoint)?;
+            }
+            chrom_index += 1;
+            let (name, description) = parse_chrom_header_bytes(&line, chrom_index);
+            current = Some(start_chromosome(
+                name,
+                description,
+                chrom_index - 1,
+                &mut occurrences,
+                &mut checkpoint,
+            ));
+            continue;
+        }
+        if is_comment_line(&line) {
+            if let Some((Some(chrom), ..)) = current.as_mut() {
+                chrom.comment_lines += 1;
+            }
+            continue;
+        }
+        if current.is_none() {
+            // Blank lines before the first header do not start a record.
+            if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+                continue;
+            }
+            chrom_index += 1;
+            let fallback = format!("chromosome_{}", chrom_index);
+            current = Some(start_chromosome(
+                fallback,
+                None,
+                chrom_index - 1,
+                &mut occurrences,
+                &mut checkpoint,
+            ));
+        }
+        if let Some((Some(chrom), occurrence, index)) = current.as_mut() {
+            for byte in line.iter() {
+                if byte.is_ascii_whitespace() {
+                    continue;
+                }
+                chrom.push_byte(*byte);
+            }
+            if let Some(on_hit) = on_hit.as_mut() {
+                for g4 in chrom.scheduler.take_closed_hits()? {
+                    on_hit(&chrom.name, g4)?;
+                }
+            }
+            if let Some(checkpoint) = checkpoint.as_deref_mut()
+                && checkpoint.due(chrom.scheduler.inflight)
+            {
+                chrom.scheduler.collect_dispatched()?;
+                // A cancelled window reports no hits; never save those.
+                check_cancelled()?;
+                chrom.save_checkpoint(checkpoint, *occurrence, *index, consumed)?;
+            }
+        }
+    }
+
+    match current {
+        Some((chrom, occurrence, index)) => {
+            finish_record(chrom, occurrence, index, &mut on_hit, &mut checkpoint)?;
+            Ok(chrom_index.max(1))
+        }
+        None => Ok(0),
+    }
+}
+
+/// Hands finished records to `on_chromosome` in the scan's
+/// [`DeliveryOrder`]. In file order, a record that finishes before an earlier
+/// one waits in `pending` until every earlier slot is filled; skipped records
+/// fill their slot without a delivery. A scheduler running records
+/// concurrently checks [`RecordDelivery::can_start`] before starting the next
+/// one, which keeps `pending` within `max_buffered`.
+pub(crate) struct RecordDelivery {
+    order: DeliveryOrder,
+    max_buffered: usize,
+    /// Index of the first record not yet delivered or skipped.
+    next: usize,
+    /// Finished records by index, `None` for skipped ones.
+    pending: BTreeMap<usize, Option<SearchResults>>,
+}
+
+impl RecordDelivery {
+    pub(crate) fn new(config: ScanConfig) -> Self {
+        Self {
+            order: config.delivery_order(),
+            max_buffered: config.max_buffered_records(),
+            next: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the record at `index` may start: in file order, only while
+    /// at most `max_buffered` records can finish ahead of the first
+    /// undelivered one.
+    pub(crate) fn can_start(&self, index: usize) -> bool {
+        self.order == DeliveryOrder::Completion || index <= self.next + self.max_buffered
+    }
+
+    /// Records finished but held back behind an earlier one.
+    #[cfg(all(test, feature = "mmap", feature = "export"))]
+    pub(crate) fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub(crate) fn finished<F>(
+        &mut self,
+        results: SearchResults,
+        on_chromosome: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(SearchResults) -> io::Result<()>,
+    {
+        match self.order {
+            DeliveryOrder::Completion => on_chromosome(results),
+            DeliveryOrder::FileOrder => {
+                debug_assert!(self.can_start(results.index), "record started too early");
+                self.pending.insert(results.index, Some(results));
+                self.flush(on_chromosome)
+            }
+        }
+    }
+
+    pub(crate) fn skipped<F>(&mut self, index: usize, on_chromosome: &mut F) -> io::Result<()>
+    where
+        F: FnMut(SearchResults) -> io::Result<()>,
+    {
+        match self.order {
+            DeliveryOrder::Completion => Ok(()),
+            DeliveryOrder::FileOrder => {
+                self.pending.insert(index, None);
+                self.flush(on_chromosome)
+            }
+        }
+    }
+
+    fn flush<F>(&mut self, on_chromosome: &mut F) -> io::Result<()>
+    where
+        F: FnMut(SearchResults) -> io::Result<()>,
+    {
+        while let Some(entry) = self.pending.remove(&self.next) {
+            self.next += 1;
+            if let Some(results) = entry {
+                on_chromosome(results)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scans one sequence whose bytes arrive in pieces, e.g. from a socket.
+///
+/// `push` takes raw sequence bytes only: FASTA headers, line breaks and other
+/// whitespace must already be stripped by the caller. Case is normalized here.
+/// Completed windows are scanned on the Rayon pool while more bytes arrive,
+/// exactly as in the FASTA stream pipeline, so the hits from `finish` match
+/// `find_owned_bytes_with_limits` on the concatenated bytes. `finish` consumes
+/// the scanner, so pushing after it is a compile error rather than a panic.
+pub struct IncrementalScanner {
+    chrom: StreamChromosome,
+    config: ScanConfig,
+    capture_raw: bool,
+}
+
+impl IncrementalScanner {
+    pub fn new(name: impl Into<String>, config: ScanConfig) -> Self {
+        let chrom = StreamChromosome::new_with_base(
+            name.into(),
+            config.min_tetrads(),
+            config.min_score(),
+            config.limits(),
+            config.topology(),
+            config.target_base(),
+        )
+        .with_window_timeout(config.window_timeout())
+        .with_metrics(config.collects_metrics());
+        Self {
+            chrom,
+            config,
+            capture_raw: false,
+        }
+    }
+
+    /// Also return the unconsolidated hits in `SearchResults::raw_hits`.
+    pub fn with_raw_hits(mut self, capture_raw: bool) -> Self {
+        self.capture_raw = capture_raw;
+        self
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.chrom.push_byte(byte);
+        }
+    }
+
+    /// Bytes pushed so far.
+    pub fn sequence_len(&self) -> usize {
+        self.chrom.scheduler.sequence_len()
+    }
+
+    /// Waits for outstanding windows and consolidates every hit. A window
+    /// whose scan panicked fails with [`QgrsError::WorkerPanicked`] and the
+    /// window's offset, and one that takes longer than
+    /// [`ScanConfig::with_window_timeout`] with
+    /// [`QgrsError::WorkerTimedOut`].
+    pub fn finish(self) -> Result<SearchResults, QgrsError> {
+        self.chrom
+            .finish_search_results(0, self.config, self.capture_raw)
+            .map_err(|err| {
+                QgrsError::from_io(&err).expect("stream windows only fail with a QgrsError")
+            })
+    }
+
+    /// Scans windows with `search` instead of the real search.
+    #[cfg(all(test, feature = "mmap", feature = "export"))]
+    pub(crate) fn with_window_search(mut self, search: WindowSearch) -> Self {
+        self.chrom.scheduler.search = search;
+        self
+    }
+}
+
 struct StreamChromosome {
     name: String,
+    description: Option<String>,
     scheduler: StreamChunkScheduler,
     captured_sequence: Option<Vec<u8>>,
+    comment_lines: usize,
+    started: Instant,
 }
 
 impl StreamChromosome {
     fn new(
         name: String,
         min_tetrads: usize,
-        min_score: i32,
+        min_score: MinScore,
         limits: ScanLimits,
         topology: SequenceTopology,
     ) -> Self {
@@ -842,7 +1488,7 @@ impl StreamChromosome {
     fn new_with_base(
         name: String,
         min_tetrads: usize,
-        min_score: i32,
+        min_score: MinScore,
         limits: ScanLimits,
         topology: SequenceTopology,
         target_base: QuartetBase,
@@ -861,7 +1507,7 @@ impl StreamChromosome {
     fn new_with_sequence_capture(
         name: String,
         min_tetrads: usize,
-        min_score: i32,
+        min_score: MinScore,
         limits: ScanLimits,
         topology: SequenceTopology,
         capture_sequence: bool,
@@ -880,7 +1526,7 @@ impl StreamChromosome {
     fn new_with_sequence_capture_and_base(
         name: String,
         min_tetrads: usize,
-        min_score: i32,
+        min_score: MinScore,
         limits: ScanLimits,
         topology: SequenceTopology,
         capture_sequence: bool,
@@ -888,6 +1534,7 @@ impl StreamChromosome {
     ) -> Self {
         Self {
             name,
+            description: None,
             scheduler: StreamChunkScheduler::new(
                 min_tetrads,
                 min_score,
@@ -896,7 +1543,60 @@ impl StreamChromosome {
                 target_base,
             ),
             captured_sequence: capture_sequence.then(Vec::new),
+            comment_lines: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Settles the families no later window can join and saves the record
+    /// to `checkpoint`; every dispatched window must have been collected.
+    fn save_checkpoint(
+        &mut self,
+        checkpoint: &mut Checkpointer,
+        occurrence: usize,
+        index: usize,
+        byte_offset: u64,
+    ) -> io::Result<()> {
+        let mut record = RecordState::new(self.name.clone(), occurrence, index);
+        record.description = self.description.clone();
+        record.comment_lines = self.comment_lines;
+        self.scheduler.settle(checkpoint);
+        self.scheduler.snapshot(&mut record);
+        let scheduler = &self.scheduler;
+        checkpoint.save(
+            byte_offset,
+            record,
+            &scheduler.settled,
+            &scheduler.collected,
+        )
+    }
+
+    fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+
+    fn with_cancellation(mut self, cancel: Option<CancellationToken>) -> Self {
+        self.scheduler.cancel = cancel;
+        self
+    }
+
+    fn with_window_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.scheduler.window_timeout = timeout;
+        self
+    }
+
+    fn with_metrics(mut self, collect_metrics: bool) -> Self {
+        self.scheduler.counters = collect_metrics.then(SearchCounters::default);
+        self
+    }
+
+    /// Linear records only: circular families may still wrap the origin.
+    fn with_incremental_hits(mut self, policy: FamilyPolicy) -> Self {
+        if !self.scheduler.topology.is_circular() {
+            self.scheduler.incremental = Some(IncrementalFamilies::new(policy));
         }
+        self
     }
 
     fn push_byte(&mut self, byte: u8) {
@@ -906,45 +1606,114 @@ impl StreamChromosome {
         self.scheduler.push_byte(byte);
     }
 
-    fn finish(self) -> (String, Vec<G4>) {
-        let results = self.scheduler.finish();
-        (self.name, results)
+    fn finish(self) -> io::Result<(String, Vec<G4>)> {
+        let results = self.scheduler.finish()?;
+        Ok((self.name, results))
     }
 
-    fn finish_with_sequence_len(self) -> (String, Vec<G4>, usize) {
+    fn finish_with_sequence_len(self) -> io::Result<(String, Vec<G4>, usize)> {
         let sequence_len = self.scheduler.sequence_len();
-        let results = self.scheduler.finish();
-        (self.name, results, sequence_len)
+        let results = self.scheduler.finish()?;
+        Ok((self.name, results, sequence_len))
     }
 
-    fn finish_with_sequence(self) -> (String, Vec<G4>, Vec<u8>) {
+    fn finish_with_sequence(self) -> io::Result<(String, Vec<G4>, Vec<u8>)> {
         let sequence = self.captured_sequence.unwrap_or_default();
-        let results = self.scheduler.finish();
-        (self.name, results, sequence)
+        let results = self.scheduler.finish()?;
+        Ok((self.name, results, sequence))
     }
 
-    fn finish_with_overlap(self) -> (String, StreamChromosomeResults) {
-        let (hits, ranges, raw_hits) = self.scheduler.finish_with_overlap();
-        (
+    fn finish_with_overlap(self) -> io::Result<(String, StreamChromosomeResults)> {
+        let (hits, ranges, raw_hits) = self.scheduler.finish_with_overlap()?;
+        Ok((
             self.name,
             StreamChromosomeResults {
                 hits,
                 family_ranges: ranges,
                 raw_hits: Some(raw_hits),
             },
+        ))
+    }
+
+    fn finish_with_overlap_and_sequence_len(
+        self,
+    ) -> io::Result<(String, StreamChromosomeResults, usize)> {
+        let sequence_len = self.scheduler.sequence_len();
+        let (name, results) = self.finish_with_overlap()?;
+        Ok((name, results, sequence_len))
+    }
+
+    fn finish_search_results(
+        mut self,
+        occurrence: usize,
+        config: ScanConfig,
+        capture_raw: bool,
+    ) -> io::Result<SearchResults> {
+        let read = self.started.elapsed();
+        let sequence_len = self.scheduler.sequence_len();
+        let counters = self.scheduler.counters.clone();
+        let finishing = Instant::now();
+        let settled = std::mem::take(&mut self.scheduler.settled);
+        let raw = self.scheduler.finish_raw()?;
+        let scan = finishing.elapsed();
+        let mut results = SearchResults::from_raw(
+            self.name,
+            occurrence,
+            sequence_len,
+            raw,
+            config,
+            capture_raw,
+            counters.map(|counters| counters.snapshot()),
         )
+        .with_settled(settled);
+        results.description = self.description;
+        results.stats.read = read;
+        results.stats.scan = scan;
+        results.stats.comment_lines = self.comment_lines;
+        Ok(results)
     }
 
-    fn finish_with_overlap_and_sequence_len(self) -> (String, StreamChromosomeResults, usize) {
+    /// Returns the results together with the hits not yet handed out by
+    /// `take_closed_hits`.
+    fn finish_search_results_with_hits(
+        self,
+        occurrence: usize,
+        config: ScanConfig,
+        capture_raw: bool,
+    ) -> io::Result<(SearchResults, Vec<G4>)> {
+        if self.scheduler.incremental.is_none() {
+            let results = self.finish_search_results(occurrence, config, capture_raw)?;
+            let remaining = results.hits.clone();
+            return Ok((results, remaining));
+        }
+        let read = self.started.elapsed();
         let sequence_len = self.scheduler.sequence_len();
-        let (name, results) = self.finish_with_overlap();
-        (name, results, sequence_len)
+        let counters = self.scheduler.counters.clone();
+        let finishing = Instant::now();
+        let (families, remaining) = self.scheduler.finish_families()?;
+        let scan = finishing.elapsed();
+        let mut results = SearchResults::from_families(
+            self.name,
+            occurrence,
+            sequence_len,
+            families,
+            config,
+            capture_raw,
+            counters.map(|counters| counters.snapshot()),
+        );
+        results.description = self.description;
+        results.stats.read = read;
+        results.stats.scan = scan;
+        results.stats.comment_lines = self.comment_lines;
+        Ok((results, remaining))
     }
 
-    fn finish_with_overlap_and_sequence(self) -> (String, StreamChromosomeResults, Vec<u8>) {
+    fn finish_with_overlap_and_sequence(
+        self,
+    ) -> io::Result<(String, StreamChromosomeResults, Vec<u8>)> {
         let sequence = self.captured_sequence.unwrap_or_default();
-        let (hits, ranges, raw_hits) = self.scheduler.finish_with_overlap();
-        (
+        let (hits, ranges, raw_hits) = self.scheduler.finish_with_overlap()?;
+        Ok((
             self.name,
             StreamChromosomeResults {
                 hits,
@@ -952,13 +1721,31 @@ impl StreamChromosome {
                 raw_hits: Some(raw_hits),
             },
             sequence,
-        )
+        ))
     }
 }
 
+/// Scans one window: `find_raw_bytes_no_chunking` outside tests, which can
+/// swap in a failing search to exercise the worker error path.
+pub(crate) type WindowSearch =
+    fn(Vec<u8>, usize, MinScore, ScanLimits, QuartetBase, SearchHooks<'_>) -> Vec<G4>;
+
+/// What a window worker sends back: its index and raw hits, or
+/// [`QgrsError::WorkerPanicked`] with the window's offset.
+type WindowResult = (usize, Result<Vec<G4>, QgrsError>);
+
+/// Cuts a record's bytes into overlapping windows and scans each on the
+/// Rayon pool while reading goes on.
+///
+/// The scheduler itself stays on the reading thread; it is `Send`, so a
+/// record may move between threads, but not `Sync`. Each worker owns its
+/// window's bytes and clones of the `Send + Sync` cancellation token and
+/// counters, and reports back over the channel. A panicking worker is
+/// caught and reported as [`QgrsError::WorkerPanicked`], so the record
+/// fails with the window's offset instead of waiting forever for it.
 struct StreamChunkScheduler {
     min_tetrads: usize,
-    min_score: i32,
+    min_score: MinScore,
     limits: ScanLimits,
     topology: SequenceTopology,
     target_base: QuartetBase,
@@ -970,17 +1757,36 @@ struct StreamChunkScheduler {
     circular_boundary_bp: usize,
     circular_head: VecDeque<u8>,
     circular_tail: VecDeque<u8>,
-    tx: Sender<Vec<G4>>,
-    rx: Receiver<Vec<G4>>,
+    tx: Sender<WindowResult>,
+    rx: Receiver<WindowResult>,
     inflight: usize,
+    received: usize,
+    /// Hits of windows already received; only checkpoints collect them
+    /// before the record ends, and then move the families they close to
+    /// `settled`.
+    collected: Vec<G4>,
+    settled: SettledFamilies,
+    cancel: Option<CancellationToken>,
+    /// [`ScanConfig::with_window_timeout`]; `None` waits for windows as long
+    /// as they take.
+    window_timeout: Option<Duration>,
+    counters: Option<SearchCounters>,
+    incremental: Option<IncrementalFamilies>,
+    search: WindowSearch,
 }
 
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<StreamChunkScheduler>();
+};
+
 type FinishParts = (Vec<G4>, Vec<(usize, usize)>, Option<Vec<G4>>);
+type OverlapParts = (Vec<G4>, Vec<(usize, usize)>, Vec<G4>);
 
 impl StreamChunkScheduler {
     fn new(
         min_tetrads: usize,
-        min_score: i32,
+        min_score: MinScore,
         limits: ScanLimits,
         topology: SequenceTopology,
         target_base: QuartetBase,
@@ -988,9 +1794,9 @@ impl StreamChunkScheduler {
         let (tx, rx) = mpsc::channel();
         let chunk_size = chunk_size_for_limits(limits);
         let overlap = compute_chunk_overlap(min_tetrads, limits);
-        let capacity = chunk_size + overlap;
+        let capacity = chunk_size.saturating_add(overlap);
         let circular_boundary_bp = if topology.is_circular() {
-            limits.max_g4_length.saturating_sub(1)
+            limits.max_motif_length().saturating_sub(1)
         } else {
             0
         };
@@ -1011,6 +1817,14 @@ impl StreamChunkScheduler {
             tx,
             rx,
             inflight: 0,
+            received: 0,
+            collected: Vec::new(),
+            settled: SettledFamilies::default(),
+            cancel: None,
+            window_timeout: None,
+            counters: None,
+            incremental: None,
+            search: find_raw_bytes_no_chunking,
         }
     }
 
@@ -1064,69 +1878,209 @@ impl StreamChunkScheduler {
         // Efficiently remove the primary_len elements from the front.
         self.buffer.drain(..primary_len);
         let offset = self.offset;
-        let _cutoff = offset + primary_len;
+        let cutoff = offset + primary_len;
         self.offset += primary_len;
+        if let Some(incremental) = self.incremental.as_mut() {
+            incremental.primary_ends.push_back(self.offset);
+        }
+        let index = self.inflight;
         let min_tetrads = self.min_tetrads;
         let min_score = self.min_score;
         let limits = self.limits;
         let target_base = self.target_base;
         let tx = self.tx.clone();
+        let cancel = self.cancel.clone();
+        let counters = self.counters.clone();
+        let search = self.search;
         self.inflight += 1;
         spawn(move || {
-            // Use the no-chunking variant here: the scheduler already supplied
-            // a window (primary + overlap) and we must not re-chunk it.
-            let mut hits =
-                find_raw_bytes_no_chunking(chunk, min_tetrads, min_score, limits, target_base);
-            for g4 in &mut hits {
-                shift_g4(g4, offset);
-            }
-            // worker-local dedup is disabled; send raw hits to consolidator
-            let _ = tx.send(hits);
+            let scanned = panic::catch_unwind(AssertUnwindSafe(|| {
+                if is_cancelled(cancel.as_ref()) {
+                    return Vec::new();
+                }
+                // The no-chunking search: the scheduler already supplied a
+                // window (primary + overlap) and we must not re-chunk it.
+                let mut hits = search(
+                    chunk,
+                    min_tetrads,
+                    min_score,
+                    limits,
+                    target_base,
+                    SearchHooks {
+                        cancel: cancel.as_ref(),
+                        counters: counters.as_ref(),
+                        runs: None,
+                        anchors: None,
+                        cap: None,
+                    },
+                );
+                for g4 in &mut hits {
+                    shift_g4(g4, offset);
+                }
+                // Hits starting in the overlap tail belong to the next window,
+                // which reports them again; keep only this window's primary
+                // part so raw hits match the chunked mmap scan one-for-one.
+                hits.retain(|g4| g4.start <= cutoff);
+                hits
+            }));
+            // The record may have failed already and dropped the receiver.
+            let _ = tx.send((
+                index,
+                scanned.map_err(|_| QgrsError::WorkerPanicked { offset }),
+            ));
         });
     }
 
-    fn finish(self) -> Vec<G4> {
-        let (hits, _, _) = self.finish_internal(false);
-        hits
+    /// Waits for the next finished window, failing with the first worker
+    /// error, or once `window_timeout` passes without one.
+    fn recv_window(&self) -> io::Result<(usize, Vec<G4>)> {
+        let received = match self.window_timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout).map_err(|err| match err {
+                RecvTimeoutError::Timeout => QgrsError::WorkerTimedOut { timeout },
+                RecvTimeoutError::Disconnected => QgrsError::WorkerLost,
+            }),
+            None => self.rx.recv().map_err(|_| QgrsError::WorkerLost),
+        };
+        let (index, hits) = received?;
+        Ok((index, hits?))
+    }
+
+    fn finish(self) -> io::Result<Vec<G4>> {
+        let (hits, _, _) = self.finish_internal(false)?;
+        Ok(hits)
     }
 
-    fn finish_with_overlap(self) -> (Vec<G4>, Vec<(usize, usize)>, Vec<G4>) {
-        let (hits, ranges, raw) = self.finish_internal(true);
-        (
+    fn finish_with_overlap(self) -> io::Result<OverlapParts> {
+        let (hits, ranges, raw) = self.finish_internal(true)?;
+        Ok((
             hits,
             ranges,
             raw.expect("raw hits must be captured when capture_raw is true"),
-        )
+        ))
     }
 
-    fn finish_internal(mut self, capture_raw: bool) -> FinishParts {
+    fn finish_internal(self, capture_raw: bool) -> io::Result<FinishParts> {
+        let topology = self.topology;
+        let sequence_len = self.sequence_len;
+        let combined = self.finish_raw()?;
+        let raw_hits = if capture_raw {
+            Some(combined.clone())
+        } else {
+            None
+        };
+        let (hits, ranges) = consolidate_g4s_with_topology(combined, topology, sequence_len);
+        Ok((hits, ranges, raw_hits))
+    }
+
+    fn finish_raw(mut self) -> io::Result<Vec<G4>> {
         self.flush_ready_chunks(true);
-        let mut combined = Vec::new();
-        for _ in 0..self.inflight {
-            if let Ok(mut chunk) = self.rx.recv() {
-                combined.append(&mut chunk);
-            }
+        let mut combined = std::mem::take(&mut self.collected);
+        for _ in self.received..self.inflight {
+            let (_, mut chunk) = self.recv_window()?;
+            combined.append(&mut chunk);
         }
         if self.topology.is_circular() {
             self.append_wraparound_hits(&mut combined);
             retain_circular_raw_hits(&mut combined, self.sequence_len);
         } else {
-            combined.sort_by_key(|a| (a.start, a.end));
+            combined.sort_unstable_by_key(G4::key);
         }
-        let raw_hits = if capture_raw {
-            Some(combined.clone())
-        } else {
-            None
+        tracing::debug!(
+            sequence_len = self.sequence_len,
+            chunks = self.inflight,
+            raw_hits = combined.len(),
+            "stream chunks merged"
+        );
+        Ok(combined)
+    }
+
+    /// Collects finished windows without blocking and returns the winners of
+    /// families that can no longer grow. Empty unless incremental delivery
+    /// was enabled.
+    fn take_closed_hits(&mut self) -> io::Result<Vec<G4>> {
+        let Some(incremental) = self.incremental.as_mut() else {
+            return Ok(Vec::new());
         };
-        let (hits, ranges) =
-            consolidate_g4s_with_topology(combined, self.topology, self.sequence_len);
-        (hits, ranges, raw_hits)
+        while let Ok((index, hits)) = self.rx.try_recv() {
+            self.received += 1;
+            incremental.accept(index, hits?);
+        }
+        Ok(incremental.take_closed_hits())
+    }
+
+    /// Incremental counterpart of `finish_raw`: waits for the remaining
+    /// windows and returns every family plus the winners not yet taken.
+    fn finish_families(mut self) -> io::Result<(Vec<Family>, Vec<G4>)> {
+        self.flush_ready_chunks(true);
+        let mut incremental = self
+            .incremental
+            .take()
+            .expect("finish_families requires incremental delivery");
+        for _ in self.received..self.inflight {
+            let (index, hits) = self.recv_window()?;
+            incremental.accept(index, hits);
+        }
+        tracing::debug!(
+            sequence_len = self.sequence_len,
+            chunks = self.inflight,
+            "stream chunks merged"
+        );
+        Ok(incremental.finish())
     }
 
     fn sequence_len(&self) -> usize {
         self.sequence_len
     }
 
+    /// Waits for every dispatched window and moves its hits to `collected`,
+    /// leaving nothing in flight for a checkpoint to miss.
+    fn collect_dispatched(&mut self) -> io::Result<()> {
+        for _ in self.received..self.inflight {
+            let (_, mut hits) = self.recv_window()?;
+            self.collected.append(&mut hits);
+        }
+        self.received = self.inflight;
+        Ok(())
+    }
+
+    /// Moves the collected families that no later hit can join to
+    /// `settled`; call `collect_dispatched` first. Later windows only report
+    /// hits starting after `offset`. On a circular record the hits found
+    /// across the origin at the end start after the last
+    /// `circular_boundary_bp` bases, and the families within that distance of
+    /// the start may still join them, so neither end settles.
+    fn settle(&mut self, checkpoint: &Checkpointer) {
+        let (offset, boundary) = (self.offset, self.circular_boundary_bp);
+        checkpoint.settle(&mut self.settled, &mut self.collected, |(start, end)| {
+            start > boundary && end + boundary <= offset
+        });
+    }
+
+    /// Copies the state a checkpoint saves into `record`; call
+    /// `collect_dispatched` first.
+    fn snapshot(&self, record: &mut RecordState) {
+        record.sequence_len = self.sequence_len;
+        record.offset = self.offset;
+        record.chunks = self.inflight;
+        record.buffer = self.buffer.iter().copied().collect();
+        record.circular_head = self.circular_head.iter().copied().collect();
+        record.circular_tail = self.circular_tail.iter().copied().collect();
+    }
+
+    /// Inverse of `snapshot`: continues a record from a checkpoint with the
+    /// families and raw hits saved in it.
+    fn restore(&mut self, record: &RecordState, settled: SettledFamilies, hits: Vec<G4>) {
+        self.sequence_len = record.sequence_len;
+        self.offset = record.offset;
+        self.inflight = record.chunks;
+        self.received = record.chunks;
+        self.buffer = record.buffer.iter().copied().collect();
+        self.circular_head = record.circular_head.iter().copied().collect();
+        self.circular_tail = record.circular_tail.iter().copied().collect();
+        self.settled = settled;
+        self.collected = hits;
+    }
+
     fn append_wraparound_hits(&self, combined: &mut Vec<G4>) {
         if self.sequence_len == 0
             || self.circular_boundary_bp == 0
@@ -1144,6 +2098,13 @@ impl StreamChunkScheduler {
             self.min_score,
             self.limits,
             self.target_base,
+            SearchHooks {
+                cancel: self.cancel.as_ref(),
+                counters: self.counters.as_ref(),
+                runs: None,
+                anchors: None,
+                cap: None,
+            },
         );
         let offset = self.sequence_len.saturating_sub(self.circular_tail.len());
         for g4 in &mut hits {
@@ -1153,3 +2114,83 @@ impl StreamChunkScheduler {
         combined.extend(hits);
     }
 }
+
+/// Consolidates linear stream hits while windows are still being scanned.
+///
+/// Workers finish out of order, so reported windows wait in `ready` until
+/// every earlier window is in. Once windows `0..=k` have reported, no later
+/// window can produce a hit starting at or before window `k`'s primary end
+/// (the next window starts there), so those hits are fed to the
+/// `FamilyBuilder` in `(start, end)` order and any family ending before that
+/// point is closed. The resulting families match `consolidate_g4s` on the
+/// whole record.
+struct IncrementalFamilies {
+    /// Primary end (0-based, exclusive) of each window from `next_chunk` on.
+    primary_ends: VecDeque<usize>,
+    ready: BTreeMap<usize, Vec<G4>>,
+    next_chunk: usize,
+    /// Hits from contiguous windows that a later window may still precede.
+    pending: Vec<G4>,
+    builder: FamilyBuilder,
+    families: Vec<Family>,
+    taken: usize,
+}
+
+impl IncrementalFamilies {
+    fn new(policy: FamilyPolicy) -> Self {
+        Self {
+            primary_ends: VecDeque::new(),
+            ready: BTreeMap::new(),
+            next_chunk: 0,
+            pending: Vec::new(),
+            builder: FamilyBuilder::new(policy),
+            families: Vec::new(),
+            taken: 0,
+        }
+    }
+
+    fn accept(&mut self, index: usize, hits: Vec<G4>) {
+        self.ready.insert(index, hits);
+        let mut watermark = None;
+        while let Some(mut hits) = self.ready.remove(&self.next_chunk) {
+            self.pending.append(&mut hits);
+            watermark = self.primary_ends.pop_front();
+            self.next_chunk += 1;
+        }
+        if let Some(watermark) = watermark {
+            self.release(watermark);
+        }
+    }
+
+    /// Feeds every pending hit starting at or before `watermark` (1-based
+    /// starts, so at or before the next window's 0-based offset).
+    fn release(&mut self, watermark: usize) {
+        self.pending.sort_unstable_by_key(G4::key);
+        let split = self.pending.partition_point(|g4| g4.start <= watermark);
+        let later = self.pending.split_off(split);
+        for g4 in std::mem::replace(&mut self.pending, later) {
+            self.families.extend(self.builder.push(g4));
+        }
+        self.families
+            .extend(self.builder.close_before(watermark + 1));
+    }
+
+    fn take_closed_hits(&mut self) -> Vec<G4> {
+        let fresh = self.families[self.taken..]
+            .iter()
+            .map(|family| family.best().clone())
+            .collect();
+        self.taken = self.families.len();
+        fresh
+    }
+
+    fn finish(mut self) -> (Vec<Family>, Vec<G4>) {
+        self.pending.sort_unstable_by_key(G4::key);
+        for g4 in std::mem::take(&mut self.pending) {
+            self.families.extend(self.builder.push(g4));
+        }
+        self.families.extend(self.builder.finish());
+        let remaining = self.take_closed_hits();
+        (self.families, remaining)
+    }
+}
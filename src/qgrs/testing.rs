@@ -0,0 +1,205 @@
+//! Seeded synthetic FASTA generation for tests and benchmarks.
+//!
+//! Background sequence is drawn from `A`, `C` and `T` only, so the G4s planted
+//! with [`SyntheticGenome::with_g4`] are the only ones a scan can report and
+//! the returned [`PlantedG4`] list is exact ground truth. Available to the
+//! crate's own tests and, with the `testkit` feature, to downstream crates.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const BACKGROUND: &[u8] = b"act";
+
+/// Seed of [`self_test_genome`].
+pub const SELF_TEST_SEED: u64 = 0x5e1f_7e57;
+
+/// The genome `qgrs self-test` scans: G4s of 2 to 5 tetrads every few
+/// hundred bases, so many straddle stream window boundaries, on three
+/// chromosomes with an assembly gap and a repeated name. Built from
+/// [`SELF_TEST_SEED`], so every build generates the same bytes.
+pub fn self_test_genome() -> SyntheticGenome {
+    let mut genome = SyntheticGenome::new(SELF_TEST_SEED).chromosome("chr1", 20_000);
+    for index in 0..40 {
+        genome = genome.with_g4(
+            150 + index * 487,
+            2 + index % 4,
+            [1 + index % 3, 2 + index % 5, 1 + index % 7],
+        );
+    }
+    genome
+        .chromosome("chr2", 6_000)
+        .with_g4(500, 3, [2, 2, 2])
+        .with_gap(2_000, 1_000)
+        .with_g4(4_000, 4, [1, 7, 3])
+        .chromosome("chr1", 1_200)
+        .with_g4(1_150, 2, [3, 3, 3])
+}
+
+/// A G4 written into a synthetic chromosome, in the 1-based inclusive
+/// coordinates used by [`G4`](crate::qgrs::G4).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlantedG4 {
+    pub chrom: String,
+    pub start: usize,
+    pub end: usize,
+    pub tetrads: usize,
+    pub loops: [usize; 3],
+}
+
+impl PlantedG4 {
+    pub fn length(&self) -> usize {
+        self.end + 1 - self.start
+    }
+}
+
+struct SyntheticChromosome {
+    name: String,
+    sequence: Vec<u8>,
+    planted: Vec<PlantedG4>,
+}
+
+/// Builder for a reproducible multi-record genome.
+///
+/// `with_g4` and `with_gap` edit the chromosome added last; positions are
+/// 0-based offsets into it and must fit inside its length. Planting motifs
+/// closer than `max_g4_length` to each other can merge them into one family,
+/// in which case the scan reports only the family winner.
+pub struct SyntheticGenome {
+    state: u64,
+    chromosomes: Vec<SyntheticChromosome>,
+    crlf: bool,
+}
+
+impl SyntheticGenome {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            chromosomes: Vec::new(),
+            crlf: false,
+        }
+    }
+
+    /// Appends a chromosome of random background bases.
+    pub fn chromosome(mut self, name: impl Into<String>, len: usize) -> Self {
+        let sequence = (0..len).map(|_| self.background_base()).collect();
+        self.chromosomes.push(SyntheticChromosome {
+            name: name.into(),
+            sequence,
+            planted: Vec::new(),
+        });
+        self
+    }
+
+    /// Writes `tetrads` G runs separated by background loops of the given
+    /// lengths, starting at `pos`.
+    pub fn with_g4(mut self, pos: usize, tetrads: usize, loops: [usize; 3]) -> Self {
+        let mut motif = Vec::with_capacity(4 * tetrads + loops.iter().sum::<usize>());
+        for run in 0..4 {
+            motif.extend(std::iter::repeat_n(b'g', tetrads));
+            if let Some(&loop_len) = loops.get(run) {
+                for _ in 0..loop_len {
+                    let base = self.background_base();
+                    motif.push(base);
+                }
+            }
+        }
+        let chrom = self.last_chromosome("with_g4");
+        let end = pos + motif.len();
+        assert!(
+            end <= chrom.sequence.len(),
+            "G4 at {pos}..{end} does not fit in {} ({} bp)",
+            chrom.name,
+            chrom.sequence.len()
+        );
+        chrom.sequence[pos..end].copy_from_slice(&motif);
+        chrom.planted.push(PlantedG4 {
+            chrom: chrom.name.clone(),
+            start: pos + 1,
+            end,
+            tetrads,
+            loops,
+        });
+        self
+    }
+
+    /// Replaces `len` bases at `pos` with `N`, like an assembly gap.
+    pub fn with_gap(mut self, pos: usize, len: usize) -> Self {
+        let chrom = self.last_chromosome("with_gap");
+        chrom.sequence[pos..pos + len].fill(b'n');
+        self
+    }
+
+    /// Use `\r\n` line endings when rendering FASTA.
+    pub fn with_crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+
+    /// Planted G4s of every chromosome, by chromosome then position.
+    pub fn expected(&self) -> Vec<PlantedG4> {
+        let mut expected: Vec<PlantedG4> = Vec::new();
+        for chrom in &self.chromosomes {
+            let mut planted = chrom.planted.clone();
+            planted.sort_by_key(|g4| g4.start);
+            expected.extend(planted);
+        }
+        expected
+    }
+
+    /// `(name, lowercase sequence)` for every chromosome.
+    pub fn sequences(&self) -> Vec<(String, Vec<u8>)> {
+        self.chromosomes
+            .iter()
+            .map(|chrom| (chrom.name.clone(), chrom.sequence.clone()))
+            .collect()
+    }
+
+    /// Renders uppercase FASTA with `line_width` bases per line; 0 keeps each
+    /// sequence on one line.
+    pub fn to_fasta(&self, line_width: usize) -> Vec<u8> {
+        let newline: &[u8] = if self.crlf { b"\r\n" } else { b"\n" };
+        let mut out = Vec::new();
+        for chrom in &self.chromosomes {
+            out.push(b'>');
+            out.extend_from_slice(chrom.name.as_bytes());
+            out.extend_from_slice(newline);
+            let width = if line_width == 0 {
+                chrom.sequence.len().max(1)
+            } else {
+                line_width
+            };
+            for line in chrom.sequence.chunks(width) {
+                out.extend(line.iter().map(u8::to_ascii_uppercase));
+                out.extend_from_slice(newline);
+            }
+        }
+        out
+    }
+
+    /// Writes [`to_fasta`](Self::to_fasta) to `path` and returns the planted
+    /// G4s.
+    pub fn write_fasta(&self, path: &Path, line_width: usize) -> io::Result<Vec<PlantedG4>> {
+        fs::write(path, self.to_fasta(line_width))?;
+        Ok(self.expected())
+    }
+
+    fn last_chromosome(&mut self, caller: &str) -> &mut SyntheticChromosome {
+        self.chromosomes
+            .last_mut()
+            .unwrap_or_else(|| panic!("{caller} called before chromosome"))
+    }
+
+    fn background_base(&mut self) -> u8 {
+        BACKGROUND[(self.next_u64() % BACKGROUND.len() as u64) as usize]
+    }
+
+    // SplitMix64: tiny, seedable and stable across platforms and releases.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
+use crate::qgrs::G4;
+#[cfg(all(feature = "mmap", feature = "export"))]
 use crate::qgrs::data::SequenceData;
-use crate::qgrs::{G4, ScanLimits, consolidate_g4s, find_with_sequence};
+#[cfg(all(feature = "mmap", feature = "export"))]
+use crate::qgrs::{MinScore, ScanLimits, consolidate_g4s, find_with_sequence};
 
 pub(super) type G4Signature = (usize, usize, usize, usize, i32, i32, i32, i32, String);
 
@@ -30,6 +33,7 @@ pub(super) fn g4_signatures(g4s: &[G4]) -> Vec<G4Signature> {
     sigs
 }
 
+#[cfg(all(feature = "mmap", feature = "export"))]
 pub(super) fn load_big_sequence() -> String {
     let fasta = include_str!("../../../big.txt");
     fasta
@@ -38,6 +42,7 @@ pub(super) fn load_big_sequence() -> String {
         .collect::<String>()
 }
 
+#[cfg(all(feature = "mmap", feature = "export"))]
 pub(super) fn run_internal_scan(
     sequence: &str,
     min_tetrads: usize,
@@ -45,7 +50,7 @@ pub(super) fn run_internal_scan(
     limits: ScanLimits,
 ) -> Vec<G4> {
     let seq = Arc::new(SequenceData::new(sequence));
-    let raw = find_with_sequence(seq, min_tetrads, min_score, limits);
+    let raw = find_with_sequence(seq, min_tetrads, MinScore::Fixed(min_score), limits);
     let (hits, _ranges) = consolidate_g4s(raw);
     hits
 }
@@ -1,73 +1,682 @@
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::qgrs::gruns::GRun;
+use crate::qgrs::stream::process_reader_with_config;
 use crate::qgrs::{
-    ScanLimits, chunk_size_for_limits, consolidate_g4s, find_owned_bytes_with_limits,
+    CancellationToken, ChromSequence, FamilyPolicy, MinScore, QgrsError, QuartetBase, ScanConfig,
+    ScanLimits, SearchResults, SequenceTopology, WHOLE_RECORD_MAX_LEN, chunk_size_for_limits,
+    consolidate_g4s, find_owned_bytes_with_cancellation, find_owned_bytes_with_limits, refilter,
+    scan_many, search_chromosome, search_chromosome_with_anchors, window_size_for,
+    with_chunk_overlap,
 };
 
+use crate::qgrs::testing::SyntheticGenome;
+
 use super::helpers::{arc_from_sequence, g4_signatures, load_big_sequence, run_internal_scan};
 
+fn synthetic_sequence(genome: &SyntheticGenome) -> String {
+    let (_, sequence) = genome.sequences().remove(0);
+    String::from_utf8(sequence).unwrap()
+}
+
 #[test]
-fn chunked_search_matches_internal_results() {
-    let limits = ScanLimits::default();
+fn chunked_search_finds_g4s_planted_across_boundaries() {
+    // Pin the window so adaptive sizing cannot move the boundaries.
+    let limits = ScanLimits::default().with_chunk_size(64);
     let chunk_size = chunk_size_for_limits(limits);
     assert!(chunk_size < 100);
-    let mut sequence = String::new();
-    sequence.push_str(&"A".repeat(chunk_size - 5));
-    sequence.push_str("GGGGAGGGGAGGGGAGGGG");
-    sequence.push_str(&"A".repeat(chunk_size / 2));
-    sequence.push_str("GGGGAGGGGAGGGGAGGGG");
-    sequence.push_str(&"T".repeat(10));
+    let config =
+        ScanConfig::new(2, 17, limits, SequenceTopology::Linear, QuartetBase::G).with_metrics(true);
+    let mut rescanned = 0;
+    for (tetrads, loops) in [(4, [1, 1, 1]), (3, [2, 5, 1]), (2, [3, 3, 3])] {
+        // Slide the motif from fully inside the first window to past the
+        // second boundary, so every split of the motif is covered.
+        for pos in chunk_size - 24..=chunk_size + 4 {
+            let genome = SyntheticGenome::new(pos as u64)
+                .chromosome("chr1", 4 * chunk_size)
+                .with_g4(pos, tetrads, loops)
+                .with_g4(pos + 2 * chunk_size, tetrads, loops);
+            let sequence = synthetic_sequence(&genome);
 
-    let chunked_raw = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 4, 17, limits);
-    let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+            let chunked_raw =
+                find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
+            let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+            let observed: Vec<_> = chunked
+                .iter()
+                .map(|g| (g.start, g.end, g.tetrads))
+                .collect();
+            let expected: Vec<_> = genome
+                .expected()
+                .iter()
+                .map(|g| (g.start, g.end, g.tetrads))
+                .collect();
+            assert_eq!(observed, expected, "tetrads {tetrads} at {pos}");
 
-    let starts: Vec<_> = chunked.iter().map(|g| g.start).collect();
-    assert_eq!(starts.len(), 2);
-    let expected_starts = vec![
-        chunk_size - 5 + 1,
-        (chunk_size - 5) + "GGGGAGGGGAGGGGAGGGG".len() + chunk_size / 2 + 1,
-    ];
-    assert_eq!(starts, expected_starts);
+            let reference = run_internal_scan(&sequence, 2, 17, limits);
+            assert_eq!(g4_signatures(&chunked), g4_signatures(&reference));
+
+            // An 8 bp overlap cuts the loop search of seeds near the primary
+            // end short; those candidates are expanded again on the record.
+            let chrom = ChromSequence {
+                name: "chr1".to_string(),
+                occurrence: 0,
+                description: None,
+                sequence: arc_from_sequence(&sequence),
+                comment_lines: 0,
+                index: 0,
+            };
+            let short = with_chunk_overlap(8, || search_chromosome(&chrom, config, false));
+            assert_eq!(
+                g4_signatures(&short.hits),
+                g4_signatures(&reference),
+                "tetrads {tetrads} at {pos} with a short overlap"
+            );
+            rescanned += short.stats.metrics.unwrap().rescanned;
+            let full = search_chromosome(&chrom, config, false);
+            assert_eq!(full.stats.metrics.unwrap().rescanned, 0);
+        }
+    }
+    assert!(rescanned > 0);
 }
 
 #[test]
-fn chunked_bytes_matches_full_scan_on_boundary() {
-    let limits = ScanLimits::default();
+fn chunked_bytes_handles_adjacent_cross_boundary_families() {
+    let limits = ScanLimits::default().with_chunk_size(64);
     let chunk_size = chunk_size_for_limits(limits);
-    let mut sequence = String::new();
-    sequence.push_str(&"A".repeat(chunk_size - 5));
-    sequence.push_str("GGGGAGGGGAGGGGAGGGG");
-    sequence.push_str(&"T".repeat(32));
+    for pos in chunk_size - 30..=chunk_size {
+        // Two motifs two bases apart also form G4s from each other's runs.
+        let genome = SyntheticGenome::new(pos as u64)
+            .chromosome("chr1", 3 * chunk_size)
+            .with_g4(pos, 4, [1, 1, 1])
+            .with_g4(pos + 21, 4, [2, 2, 2]);
+        let sequence = synthetic_sequence(&genome);
 
-    let chunked_raw = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 4, 17, limits);
-    let (chunked, _ranges) = consolidate_g4s(chunked_raw);
-    let reference = run_internal_scan(&sequence, 4, 17, limits);
+        let chunked_raw =
+            find_owned_bytes_with_limits(arc_from_sequence(&sequence), 4, 17, limits).unwrap();
+        let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+        let reference = run_internal_scan(&sequence, 4, 17, limits);
 
-    assert_eq!(g4_signatures(&chunked), g4_signatures(&reference));
+        assert!(!chunked.is_empty());
+        assert_eq!(
+            g4_signatures(&chunked),
+            g4_signatures(&reference),
+            "motifs at {pos}"
+        );
+    }
 }
 
 #[test]
-fn chunked_bytes_handles_adjacent_cross_boundary_families() {
-    let limits = ScanLimits::default();
-    let chunk_size = chunk_size_for_limits(limits);
-    let mut sequence = String::new();
-    sequence.push_str(&"C".repeat(chunk_size - 8));
-    sequence.push_str("GGGGAGGGGAGGGGAGGGG");
-    sequence.push_str("AA");
-    sequence.push_str("GGGGTTGGGGTTGGGGTTGGGG");
-    sequence.push_str(&"C".repeat(24));
+fn chunked_search_skips_windows_without_g_but_finds_everything_else() {
+    // Long gaps make most windows G-free; motifs sit against the gap edges,
+    // and one starts a window whose primary section holds only its first G.
+    let genome = SyntheticGenome::new(7)
+        .chromosome("chr1", 2_000)
+        .with_gap(0, 300)
+        .with_g4(300, 3, [1, 2, 3])
+        .with_gap(330, 600)
+        .with_g4(1_000, 4, [2, 2, 2])
+        .with_gap(1_100, 800)
+        .with_g4(1_919, 2, [1, 1, 1])
+        .with_g4(1_960, 3, [4, 1, 2]);
+    let sequence = synthetic_sequence(&genome);
+    let reference = run_internal_scan(&sequence, 2, 17, ScanLimits::default());
+    assert_eq!(reference.len(), genome.expected().len());
+    for chunk_size in [45, 64, 81, 1_000] {
+        let limits = ScanLimits::default().with_chunk_size(chunk_size);
+        let chunked_raw =
+            find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
+        let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+        assert_eq!(
+            g4_signatures(&chunked),
+            g4_signatures(&reference),
+            "chunk size {chunk_size}"
+        );
+    }
 
-    let chunked_raw = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 4, 17, limits);
-    let (chunked, _ranges) = consolidate_g4s(chunked_raw);
-    let reference = run_internal_scan(&sequence, 4, 17, limits);
-
-    assert_eq!(g4_signatures(&chunked), g4_signatures(&reference));
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let scan = |limits| {
+            let config = ScanConfig::new(2, 17, limits, topology, QuartetBase::G).with_gruns(true);
+            search_chromosome(&chrom, config, false)
+        };
+        let whole = scan(ScanLimits::default().with_chunk_size(sequence.len()));
+        let chunked = scan(ScanLimits::default().with_chunk_size(45));
+        assert_eq!(g4_signatures(&chunked.hits), g4_signatures(&whole.hits));
+        assert_eq!(chunked.gruns, whole.gruns, "{topology:?}");
+    }
 }
 
 #[test]
 fn big_sequence_internal_equals_chunked() {
     let sequence = load_big_sequence();
     let limits = ScanLimits::default();
-    let chunked_raw = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits);
+    let chunked_raw =
+        find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
     let (chunked, _ranges) = consolidate_g4s(chunked_raw);
     let internal = run_internal_scan(&sequence, 2, 17, limits);
     assert_eq!(g4_signatures(&chunked), g4_signatures(&internal));
 }
+
+#[test]
+fn chunked_search_with_custom_chunk_size_matches_unchunked() {
+    let sequence = load_big_sequence();
+    let internal = run_internal_scan(&sequence, 2, 17, ScanLimits::default());
+    for chunk_size in [45, 46, 100, 333, 4096] {
+        let limits = ScanLimits::default().with_chunk_size(chunk_size);
+        assert_eq!(chunk_size_for_limits(limits), chunk_size);
+        let chunked_raw =
+            find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
+        let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+        assert_eq!(
+            g4_signatures(&chunked),
+            g4_signatures(&internal),
+            "chunk size {chunk_size}"
+        );
+    }
+}
+
+#[test]
+fn adaptive_windows_give_the_same_hits_as_any_fixed_size() {
+    let limits = ScanLimits::default();
+    let mut genome = SyntheticGenome::new(20).chromosome("plasmid", 20_000);
+    for pos in (250..19_900).step_by(500) {
+        genome = genome.with_g4(pos, 2 + pos % 3, [1 + pos % 4, 2, 3]);
+    }
+    let sequence = synthetic_sequence(&genome);
+    let adaptive = window_size_for(sequence.len(), limits);
+    assert!(adaptive >= chunk_size_for_limits(limits));
+    assert!(adaptive <= 4096);
+    #[cfg(feature = "parallel")]
+    if rayon::current_num_threads() <= 8 {
+        // 20 kb split over at most 32 windows instead of ~300 minimum-sized ones.
+        assert!(sequence.len().div_ceil(adaptive) <= 32);
+    }
+
+    let reference = run_internal_scan(&sequence, 2, 17, limits);
+    assert_eq!(reference.len(), genome.expected().len());
+    let (adaptive_hits, _ranges) = consolidate_g4s(
+        find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap(),
+    );
+    assert_eq!(g4_signatures(&adaptive_hits), g4_signatures(&reference));
+    for chunk_size in [64, 499, 500, 501, 2_048, adaptive, 19_999] {
+        let fixed = limits.with_chunk_size(chunk_size);
+        assert_eq!(window_size_for(sequence.len(), fixed), chunk_size);
+        let (hits, _ranges) = consolidate_g4s(
+            find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, fixed).unwrap(),
+        );
+        assert_eq!(
+            g4_signatures(&hits),
+            g4_signatures(&reference),
+            "chunk size {chunk_size}"
+        );
+    }
+}
+
+#[test]
+fn cancelled_scan_returns_promptly_with_cancelled_error() {
+    let sequence = "GGGAGGGAGGGAGGGT".repeat(200_000);
+    let cancel = CancellationToken::new();
+    let canceller = {
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        })
+    };
+    let started = Instant::now();
+    let result = find_owned_bytes_with_cancellation(
+        arc_from_sequence(&sequence),
+        2,
+        17,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+        &cancel,
+    );
+    canceller.join().unwrap();
+    assert_eq!(result.unwrap_err(), QgrsError::Cancelled);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn uncancelled_token_matches_plain_scan() {
+    let sequence = load_big_sequence();
+    let limits = ScanLimits::default();
+    let plain = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
+    let with_token = find_owned_bytes_with_cancellation(
+        arc_from_sequence(&sequence),
+        2,
+        17,
+        limits,
+        SequenceTopology::Linear,
+        QuartetBase::G,
+        &CancellationToken::new(),
+    )
+    .unwrap();
+    assert_eq!(g4_signatures(&plain), g4_signatures(&with_token));
+}
+
+#[test]
+fn invalid_limits_are_reported_instead_of_finding_nothing() {
+    let cases = [
+        (ScanLimits::default(), 0, QgrsError::ZeroTetrads),
+        (
+            ScanLimits::new(8, 1),
+            2,
+            QgrsError::MaxRunTooShort {
+                max_run: 1,
+                min_tetrads: 2,
+            },
+        ),
+        // Two tetrads fit in 8 bp only without loops.
+        (
+            ScanLimits::new(9, 10),
+            2,
+            QgrsError::MaxLengthTooShort {
+                max_length: 9,
+                min_tetrads: 2,
+                required: 10,
+            },
+        ),
+        // 3+-tetrad motifs stay capped at 45 bp however long the limit.
+        (
+            ScanLimits::new(100, 20),
+            11,
+            QgrsError::MaxLengthTooShort {
+                max_length: 45,
+                min_tetrads: 11,
+                required: 46,
+            },
+        ),
+        (
+            ScanLimits::new(usize::MAX, usize::MAX),
+            usize::MAX / 2,
+            QgrsError::TooManyTetrads {
+                min_tetrads: usize::MAX / 2,
+            },
+        ),
+    ];
+    let sequence = "GGGGAGGGGAGGGGAGGGG";
+    for (limits, min_tetrads, expected) in cases {
+        assert_eq!(limits.validate(min_tetrads), Err(expected));
+        let chunked =
+            find_owned_bytes_with_limits(arc_from_sequence(sequence), min_tetrads, 17, limits);
+        assert_eq!(chunked.unwrap_err(), expected);
+
+        let config = ScanConfig::new(
+            min_tetrads,
+            17,
+            limits,
+            SequenceTopology::Linear,
+            QuartetBase::G,
+        );
+        let fasta = format!(">chr1\n{sequence}\n");
+        let err = process_reader_with_config(fasta.as_bytes(), config, false, &mut |_| Ok(()))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(QgrsError::from_io(&err), Some(expected));
+    }
+
+    assert_eq!(ScanLimits::new(10, 2).validate(2), Ok(()));
+    assert_eq!(ScanLimits::default().validate(10), Ok(()));
+}
+
+/// Maximal G runs of `min_len..=max_len` bases, joining the last and first
+/// runs of a circular sequence.
+fn reference_gruns(seq: &[u8], min_len: usize, max_len: usize, circular: bool) -> Vec<GRun> {
+    let mut runs: Vec<GRun> = Vec::new();
+    for (index, &byte) in seq.iter().enumerate() {
+        if byte != b'g' {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.start + run.len == index => run.len += 1,
+            _ => runs.push(GRun {
+                start: index,
+                len: 1,
+            }),
+        }
+    }
+    if circular && runs.len() > 1 {
+        let last = runs[runs.len() - 1];
+        if runs[0].start == 0 && last.start + last.len == seq.len() {
+            let head = runs.remove(0);
+            runs.last_mut().unwrap().len += head.len;
+        }
+    }
+    runs.retain(|run| (min_len..=max_len).contains(&run.len));
+    runs
+}
+
+#[test]
+fn gruns_match_a_reference_scan_across_windows_and_the_origin() {
+    // Runs of every length up to one longer than the 18 bp window overlap,
+    // starting and ending on G so a circular scan joins them at the origin.
+    let mut sequence = String::from("GGG");
+    for (index, len) in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 25, 3]
+        .into_iter()
+        .cycle()
+        .take(45)
+        .enumerate()
+    {
+        sequence.push_str(["A", "TC", "ATT"][index % 3]);
+        sequence.push_str(&"G".repeat(len));
+    }
+    sequence.push_str("AGGGG");
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    let bytes = chrom.sequence();
+
+    for chunk_size in [None, Some(18), Some(bytes.len())] {
+        let mut limits = ScanLimits::new(18, 30);
+        if let Some(chunk_size) = chunk_size {
+            limits = limits.with_chunk_size(chunk_size);
+        }
+        for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+            let config = ScanConfig::new(3, 17, limits, topology, QuartetBase::G).with_gruns(true);
+            let results = search_chromosome(&chrom, config, false);
+            let expected = reference_gruns(&bytes, 3, 30, topology.is_circular());
+            assert_eq!(
+                results.gruns.as_deref(),
+                Some(expected.as_slice()),
+                "chunk size {chunk_size:?}, {topology:?}"
+            );
+        }
+    }
+    let plain = search_chromosome(&chrom, ScanConfig::default(), false);
+    assert_eq!(plain.gruns, None);
+}
+
+#[test]
+fn anchored_scan_reproduces_the_hits_whose_starts_it_is_given() {
+    let mut sequence = String::new();
+    for index in 0..400 {
+        sequence.push_str(&"G".repeat(2 + index * 7 % 4));
+        sequence.push_str(&"ATCA"[..1 + index * 5 % 4]);
+        if index % 6 == 5 {
+            sequence.push_str(&"ACTT".repeat(10));
+        }
+    }
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    for chunk_size in [None, Some(64)] {
+        let mut limits = ScanLimits::default();
+        if let Some(chunk_size) = chunk_size {
+            limits = limits.with_chunk_size(chunk_size);
+        }
+        for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+            for policy in [FamilyPolicy::Score, FamilyPolicy::Length] {
+                let config = ScanConfig::new(2, 17, limits, topology, QuartetBase::G)
+                    .with_family_policy(policy)
+                    .with_metrics(true);
+                let full = search_chromosome(&chrom, config, false);
+                assert!(!full.hits.is_empty());
+                // Out-of-record and repeated anchors are dropped.
+                let mut anchors: Vec<usize> = full.hits.iter().map(|hit| hit.start).collect();
+                anchors.extend([0, sequence.len() + 1, anchors[0]]);
+                let anchored = search_chromosome_with_anchors(&chrom, config, false, &anchors);
+                let context = format!("{chunk_size:?}, {topology:?}, {policy:?}");
+                assert_eq!(
+                    g4_signatures(&anchored.hits),
+                    g4_signatures(&full.hits),
+                    "{context}"
+                );
+                let seeded = |results: &SearchResults| results.stats.metrics.unwrap().seeded;
+                assert!(seeded(&anchored) < seeded(&full) / 4, "{context}");
+            }
+        }
+    }
+
+    // An anchor off a G-run seeds nothing.
+    let config = ScanConfig::default();
+    let first = search_chromosome(&chrom, config, false).hits[0].start;
+    let off_run = sequence[first - 1..].find('A').unwrap() + first;
+    let anchored = search_chromosome_with_anchors(&chrom, config, false, &[off_run]);
+    assert!(anchored.hits.is_empty());
+}
+
+#[test]
+fn refilter_matches_a_fresh_scan_at_the_stricter_score() {
+    // Clusters of six runs of 2 to 5 Gs between loops of 1 to 4 bases, so
+    // families mix 2-tetrad hits below 30 with 3- and 4-tetrad hits above it.
+    let mut sequence = String::new();
+    for index in 0..400 {
+        sequence.push_str(&"G".repeat(2 + index * 7 % 4));
+        sequence.push_str(&"ATCA"[..1 + index * 5 % 4]);
+        if index % 6 == 5 {
+            sequence.push_str(&"ACTT".repeat(10));
+        }
+    }
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        for policy in [
+            FamilyPolicy::Score,
+            FamilyPolicy::Length,
+            FamilyPolicy::First,
+        ] {
+            let config = ScanConfig::new(2, 10, ScanLimits::default(), topology, QuartetBase::G)
+                .with_family_policy(policy);
+            let loose = search_chromosome(&chrom, config.with_keep_raw(true), false);
+            let refiltered = refilter(&loose, 30).unwrap();
+            let fresh = search_chromosome(
+                &chrom,
+                ScanConfig::new(2, 30, ScanLimits::default(), topology, QuartetBase::G)
+                    .with_family_policy(policy),
+                false,
+            );
+            let context = format!("{topology:?}, {policy:?}");
+            assert!(!fresh.hits.is_empty(), "{context}");
+            assert_eq!(
+                g4_signatures(&refiltered.hits),
+                g4_signatures(&fresh.hits),
+                "{context}"
+            );
+            assert_eq!(refiltered.family_ranges, fresh.family_ranges, "{context}");
+            assert_eq!(refiltered.config.min_score(), MinScore::Fixed(30));
+            // Families whose first or longest member scored below 30 are
+            // won by another member; score winners never change.
+            let kept = loose.hits.iter().filter(|hit| hit.score >= 30).count();
+            if policy == FamilyPolicy::Score {
+                assert_eq!(kept, refiltered.hits.len(), "{context}");
+            } else {
+                assert!(kept < refiltered.hits.len(), "{context}");
+            }
+        }
+    }
+
+    let scanned = search_chromosome(&chrom, ScanConfig::default().with_keep_raw(true), false);
+    assert_eq!(
+        refilter(&scanned, 16).unwrap_err(),
+        QgrsError::RefilterBelowScan {
+            min_score: 16,
+            scanned: 17,
+        }
+    );
+    assert!(refilter(&scanned, 17).is_ok());
+    let without_raw = search_chromosome(&chrom, ScanConfig::default(), false);
+    assert_eq!(
+        refilter(&without_raw, 30).unwrap_err(),
+        QgrsError::MissingRawHits
+    );
+}
+
+#[test]
+fn scan_many_matches_sequential_scans_in_input_order() {
+    // Transcript-sized records, a repeated name and one record long enough
+    // to be windowed anyway.
+    let mut genome = SyntheticGenome::new(389);
+    for index in 0..48 {
+        let name = if index == 30 {
+            "t3".to_string()
+        } else {
+            format!("t{index}")
+        };
+        genome = genome.chromosome(name, 1500 + index * 37).with_g4(
+            100 + index * 11,
+            2 + index % 3,
+            [1 + index % 4, 2, 3],
+        );
+        if index == 20 {
+            genome = genome
+                .chromosome("long", WHOLE_RECORD_MAX_LEN + 5000)
+                .with_g4(WHOLE_RECORD_MAX_LEN - 10, 3, [2, 2, 2])
+                .with_g4(WHOLE_RECORD_MAX_LEN + 3000, 4, [1, 1, 1]);
+        }
+    }
+    let mut records = genome.sequences();
+    // Any case is accepted.
+    for (_, sequence) in records.iter_mut().step_by(3) {
+        sequence.make_ascii_uppercase();
+    }
+
+    let config = ScanConfig::new(
+        2,
+        17,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    let results = scan_many(records.clone(), config);
+    assert_eq!(results.len(), records.len());
+    let mut seen = std::collections::HashMap::new();
+    for ((name, sequence), batched) in records.into_iter().zip(&results) {
+        let occurrence = seen.entry(name.clone()).or_insert(0usize);
+        let chrom = ChromSequence {
+            name: name.clone(),
+            occurrence: *occurrence,
+            description: None,
+            sequence: arc_from_sequence(std::str::from_utf8(&sequence).unwrap()),
+            comment_lines: 0,
+            index: 0,
+        };
+        *occurrence += 1;
+        let sequential = search_chromosome(&chrom, config, false);
+        assert_eq!(batched.name, name);
+        assert_eq!(batched.occurrence, sequential.occurrence, "{name}");
+        assert_eq!(batched.sequence_len, sequential.sequence_len, "{name}");
+        assert!(!sequential.hits.is_empty(), "{name}");
+        assert_eq!(
+            g4_signatures(&batched.hits),
+            g4_signatures(&sequential.hits),
+            "{name}"
+        );
+        assert_eq!(batched.family_ranges, sequential.family_ranges, "{name}");
+    }
+    assert_eq!(results[31].name, "t3");
+    assert_eq!(results[31].occurrence, 1);
+    assert_eq!(results[21].name, "long");
+}
+
+#[test]
+fn max_hits_keeps_the_first_hits_of_an_uncapped_scan() {
+    let mut sequence = String::new();
+    for index in 0..800 {
+        sequence.push_str(&"G".repeat(2 + index * 7 % 4));
+        sequence.push_str(&"ATCA"[..1 + index * 5 % 4]);
+        if index % 6 == 5 {
+            sequence.push_str(&"ACTT".repeat(10));
+        }
+    }
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    let fasta = format!(">chr1\n{sequence}\n");
+    for chunk_size in [None, Some(64)] {
+        let mut limits = ScanLimits::default();
+        if let Some(chunk_size) = chunk_size {
+            limits = limits.with_chunk_size(chunk_size);
+        }
+        for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+            let config =
+                ScanConfig::new(2, 17, limits, topology, QuartetBase::G).with_metrics(true);
+            let full = search_chromosome(&chrom, config, true);
+            let total = full.hits.len();
+            assert!(total > 10);
+            for max_hits in [1, 7, total - 1, total] {
+                let context = format!("{chunk_size:?}, {topology:?}, {max_hits}");
+                let capped_config = config.with_max_hits(Some(max_hits));
+                let capped = search_chromosome(&chrom, capped_config, true);
+                let kept = max_hits.min(total);
+                assert_eq!(
+                    g4_signatures(&capped.hits),
+                    g4_signatures(&full.hits[..kept]),
+                    "{context}"
+                );
+                assert_eq!(
+                    capped.family_ranges,
+                    full.family_ranges[..kept],
+                    "{context}"
+                );
+                assert_eq!(capped.stats.truncated, max_hits < total, "{context}");
+                let last_end = capped.family_ranges[kept - 1].1;
+                let raw = capped.raw_hits.as_ref().unwrap();
+                let expected_raw: Vec<_> = full
+                    .raw_hits
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .filter(|hit| hit.start <= last_end)
+                    .collect();
+                assert_eq!(raw.len(), expected_raw.len(), "{context}");
+                if max_hits != 7 {
+                    continue;
+                }
+                let mut streamed = Vec::new();
+                process_reader_with_config(
+                    fasta.as_bytes(),
+                    capped_config,
+                    false,
+                    &mut |results| {
+                        streamed.push((g4_signatures(&results.hits), results.stats.truncated));
+                        Ok(())
+                    },
+                )
+                .unwrap();
+                assert_eq!(
+                    streamed,
+                    [(g4_signatures(&capped.hits), capped.stats.truncated)],
+                    "{context}"
+                );
+            }
+            // Windows past the first settled families are never searched.
+            if chunk_size.is_some() && !topology.is_circular() {
+                let capped = search_chromosome(&chrom, config.with_max_hits(Some(1)), false);
+                let seeded = |results: &SearchResults| results.stats.metrics.unwrap().seeded;
+                assert!(seeded(&capped) < seeded(&full), "{chunk_size:?}");
+            }
+        }
+    }
+}
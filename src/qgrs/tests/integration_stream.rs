@@ -1,15 +1,23 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use flate2::Compression;
 use flate2::write::GzEncoder;
 
+use super::helpers::{arc_from_sequence, g4_signatures, load_big_sequence};
 use crate::qgrs::stream;
+use crate::qgrs::testing::SyntheticGenome;
 use crate::qgrs::{
-    InputMode, ScanLimits, SequenceTopology, consolidate_g4s, consolidate_g4s_with_topology,
-    find_owned_bytes, find_owned_bytes_with_topology,
+    CancellationToken, ChromSequence, DeliveryOrder, FamilyPolicy, FamilyStats, InputMode,
+    LoadOptions, LoopPolicy, QgrsError, QuartetBase, ScanConfig, ScanLimits, ScanMetrics,
+    SearchResults, SequenceTopology, StreamCheckpoint, consolidate_g4s,
+    consolidate_g4s_with_topology, find_in_str, find_owned_bytes, find_owned_bytes_with_limits,
+    find_owned_bytes_with_topology, find_owned_bytes_with_topology_and_base, render_csv_results,
+    search_chromosome, write_reverse_complement,
 };
 
 #[test]
@@ -176,9 +184,1071 @@ fn stream_pipeline_reads_gzip_and_matches_plain_results() {
     fs::remove_file(&gzip).unwrap();
 }
 
+#[test]
+fn stream_pipeline_reads_zstd_and_matches_plain_results() {
+    let plain = std::env::temp_dir().join("qgrs_stream_pipeline_zstd.fa");
+    let zstd = std::env::temp_dir().join("qgrs_stream_pipeline_zstd.fa.zst");
+    let fasta = b">chr1 desc\nGGGGAGGGGTTTTGGGG\n>chr2\nACACGGGGACACGGGG\n";
+    fs::write(&plain, fasta).unwrap();
+    fs::write(&zstd, zstd::encode_all(&fasta[..], 0).unwrap()).unwrap();
+
+    let mut results: [HashMap<String, Vec<_>>; 2] = Default::default();
+    for (path, results) in [&plain, &zstd].into_iter().zip(results.iter_mut()) {
+        stream::process_fasta_stream(path, 2, 17, |name, hits| {
+            results.insert(name, hits);
+            Ok(())
+        })
+        .unwrap();
+    }
+    let [plain_results, zstd_results] = results;
+    assert_eq!(plain_results.len(), 2);
+    assert_eq!(plain_results, zstd_results);
+
+    fs::remove_file(&plain).unwrap();
+    fs::remove_file(&zstd).unwrap();
+}
+
 fn write_gzip(path: &Path, bytes: &[u8]) {
     let file = fs::File::create(path).expect("create gzip file");
     let mut encoder = GzEncoder::new(file, Compression::default());
     encoder.write_all(bytes).expect("write gzip data");
     encoder.finish().expect("finish gzip");
 }
+
+#[test]
+fn duplicate_names_get_matching_occurrences_in_loaders_and_stream() {
+    let path = std::env::temp_dir().join("qgrs_stream_duplicate_names.fa");
+    let fasta = b">chr1\nGGGGAGGGGAGGGGAGGGG\n>chr2\nACGT\n>chr1\n>chr1\nGGGTTGGGTTGGGTTGGG\n";
+    fs::write(&path, fasta).unwrap();
+    let expected = [("chr1", 0), ("chr2", 0), ("chr1", 2)];
+
+    let config = ScanConfig::default();
+    let mut loaded_hits = Vec::new();
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let sequences = crate::qgrs::load_sequences_from_path(&path, mode).unwrap();
+        let tagged: Vec<_> = sequences
+            .iter()
+            .map(|chrom| (chrom.name(), chrom.occurrence()))
+            .collect();
+        assert_eq!(tagged, expected, "mode {mode:?}");
+        loaded_hits = sequences
+            .iter()
+            .map(|chrom| search_chromosome(chrom, config, false).hits)
+            .collect();
+    }
+
+    let mut streamed = Vec::new();
+    stream::process_fasta_stream_with_config(&path, config, false, |results| {
+        if results.sequence_len > 0 {
+            streamed.push(results);
+        }
+        Ok(())
+    })
+    .unwrap();
+    let tagged: Vec<_> = streamed
+        .iter()
+        .map(|results| (results.name.as_str(), results.occurrence))
+        .collect();
+    assert_eq!(tagged, expected);
+    for (results, hits) in streamed.iter().zip(&loaded_hits) {
+        assert_eq!(results.hits.len(), hits.len());
+        for (a, b) in results.hits.iter().zip(hits) {
+            assert_eq!((a.start, a.end, a.score), (b.start, b.end, b.score));
+        }
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn cancelled_stream_stops_with_cancelled_error() {
+    let mut fasta = b">chr1\n".to_vec();
+    for _ in 0..100_000 {
+        fasta.extend_from_slice(b"GGGAGGGAGGGAGGGT\n");
+    }
+    let cancel = CancellationToken::new();
+    let canceller = {
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        })
+    };
+    let started = Instant::now();
+    let mut delivered = 0usize;
+    let err = stream::process_reader_with_cancellation(
+        fasta.as_slice(),
+        ScanConfig::default(),
+        false,
+        &mut |_: &str, _: usize| false,
+        Some(&cancel),
+        &mut |_| {
+            delivered += 1;
+            Ok(())
+        },
+    )
+    .unwrap_err();
+    canceller.join().unwrap();
+    assert_eq!(QgrsError::from_io(&err), Some(QgrsError::Cancelled));
+    assert_eq!(delivered, 0);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn incremental_scanner_matches_batch_for_any_push_size() {
+    fn assert_send<T: Send>() {}
+    assert_send::<stream::IncrementalScanner>();
+
+    let sequence = load_big_sequence();
+    let limits = ScanLimits::default();
+    let raw = find_owned_bytes_with_limits(arc_from_sequence(&sequence), 2, 17, limits).unwrap();
+    let (expected, _ranges) = consolidate_g4s(raw);
+    assert!(!expected.is_empty());
+
+    for step in [1, 7, sequence.len()] {
+        let mut scanner = stream::IncrementalScanner::new("chr1", ScanConfig::default());
+        for piece in sequence.as_bytes().chunks(step) {
+            scanner.push(piece);
+        }
+        assert_eq!(scanner.sequence_len(), sequence.len());
+        let results = scanner.finish().unwrap();
+        assert_eq!(results.name, "chr1");
+        assert_eq!(results.sequence_len, sequence.len());
+        assert_eq!(
+            g4_signatures(&results.hits),
+            g4_signatures(&expected),
+            "step {step}"
+        );
+    }
+}
+
+#[test]
+fn panicking_window_fails_the_record_with_its_offset() {
+    // Panics on the one window that starts with an `n`.
+    fn search(
+        window: Vec<u8>,
+        _: usize,
+        _: crate::qgrs::MinScore,
+        _: ScanLimits,
+        _: QuartetBase,
+        _: crate::qgrs::SearchHooks<'_>,
+    ) -> Vec<crate::qgrs::G4> {
+        assert_ne!(window.first(), Some(&b'n'), "injected window failure");
+        Vec::new()
+    }
+
+    let chunk_size = crate::qgrs::chunk_size_for_limits(ScanLimits::default());
+    let mut sequence = vec![b'a'; 4 * chunk_size];
+    sequence[2 * chunk_size] = b'n';
+    let mut scanner =
+        stream::IncrementalScanner::new("chr1", ScanConfig::default()).with_window_search(search);
+    scanner.push(&sequence);
+    let started = Instant::now();
+    assert_eq!(
+        scanner.finish().unwrap_err(),
+        QgrsError::WorkerPanicked {
+            offset: 2 * chunk_size
+        }
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn slow_window_times_out_only_with_a_window_timeout() {
+    // Sleeps on the one window that starts with an `n`.
+    fn search(
+        window: Vec<u8>,
+        _: usize,
+        _: crate::qgrs::MinScore,
+        _: ScanLimits,
+        _: QuartetBase,
+        _: crate::qgrs::SearchHooks<'_>,
+    ) -> Vec<crate::qgrs::G4> {
+        if window.first() == Some(&b'n') {
+            thread::sleep(Duration::from_millis(500));
+        }
+        Vec::new()
+    }
+
+    let chunk_size = crate::qgrs::chunk_size_for_limits(ScanLimits::default());
+    let mut sequence = vec![b'a'; 4 * chunk_size];
+    sequence[2 * chunk_size] = b'n';
+    let timeout = Duration::from_millis(50);
+    for window_timeout in [Some(timeout), None] {
+        let config = ScanConfig::default().with_window_timeout(window_timeout);
+        let mut scanner =
+            stream::IncrementalScanner::new("chr1", config).with_window_search(search);
+        scanner.push(&sequence);
+        let finished = scanner.finish();
+        match window_timeout {
+            Some(_) => assert_eq!(finished.unwrap_err(), QgrsError::WorkerTimedOut { timeout }),
+            None => assert!(finished.unwrap().hits.is_empty()),
+        }
+    }
+}
+
+#[test]
+fn stream_and_mmap_paths_agree_for_custom_chunk_sizes() {
+    let sequence = load_big_sequence();
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let (expected, _ranges) = consolidate_g4s_with_topology(
+            find_owned_bytes_with_topology(
+                arc_from_sequence(&sequence),
+                2,
+                17,
+                ScanLimits::default(),
+                topology,
+            ),
+            topology,
+            sequence.len(),
+        );
+        for chunk_size in [45, 100, 4096] {
+            let limits = ScanLimits::default().with_chunk_size(chunk_size);
+            let batch_raw = find_owned_bytes_with_topology(
+                arc_from_sequence(&sequence),
+                2,
+                17,
+                limits,
+                topology,
+            );
+            let (batch, _ranges) =
+                consolidate_g4s_with_topology(batch_raw, topology, sequence.len());
+            assert_eq!(g4_signatures(&batch), g4_signatures(&expected));
+
+            let config = ScanConfig::new(2, 17, limits, topology, QuartetBase::G);
+            let mut scanner = stream::IncrementalScanner::new("chr1", config);
+            for piece in sequence.as_bytes().chunks(1000) {
+                scanner.push(piece);
+            }
+            assert_eq!(
+                g4_signatures(&scanner.finish().unwrap().hits),
+                g4_signatures(&expected),
+                "{topology:?} chunk size {chunk_size}"
+            );
+        }
+    }
+}
+
+#[test]
+fn streamed_hits_match_final_results_per_chromosome() {
+    let motif = load_big_sequence();
+    let mut fasta = String::new();
+    for (index, repeats) in [40, 1, 0, 25].into_iter().enumerate() {
+        fasta.push_str(&format!(">chr{index}\n"));
+        let sequence = vec![motif.as_str(); repeats].join("TTTTTTTTTT");
+        for line in sequence.as_bytes().chunks(60) {
+            fasta.push_str(std::str::from_utf8(line).unwrap());
+            fasta.push('\n');
+        }
+    }
+
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let config = ScanConfig::new(2, 17, ScanLimits::default(), topology, QuartetBase::G)
+            .with_family_policy(FamilyPolicy::Length);
+        let mut expected = Vec::new();
+        stream::process_reader_with_config(fasta.as_bytes(), config, true, &mut |results| {
+            expected.push(results);
+            Ok(())
+        })
+        .unwrap();
+
+        let mut streamed: Vec<(String, Vec<_>)> = Vec::new();
+        let mut finished = Vec::new();
+        stream::process_reader_with_hits(
+            fasta.as_bytes(),
+            config,
+            true,
+            &mut |name: &str, g4| {
+                if streamed.last().is_none_or(|(last, _)| last != name) {
+                    streamed.push((name.to_string(), Vec::new()));
+                }
+                streamed.last_mut().unwrap().1.push(g4);
+                Ok(())
+            },
+            &mut |results| {
+                finished.push(results);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(finished.len(), expected.len());
+        let with_hits: Vec<_> = finished.iter().filter(|r| !r.hits.is_empty()).collect();
+        assert_eq!(streamed.len(), with_hits.len(), "{topology:?}");
+        for ((name, hits), results) in streamed.iter().zip(with_hits) {
+            assert_eq!(name, &results.name);
+            assert_eq!(g4_signatures(hits), g4_signatures(&results.hits));
+            assert!(hits.windows(2).all(|pair| pair[0].start < pair[1].start));
+        }
+        for (results, reference) in finished.iter().zip(&expected) {
+            assert_eq!(results.name, reference.name);
+            assert_eq!(results.family_ranges, reference.family_ranges);
+            assert_eq!(g4_signatures(&results.hits), g4_signatures(&reference.hits));
+            assert_eq!(results.stats.raw_hits, reference.stats.raw_hits);
+        }
+    }
+}
+
+#[test]
+fn synthetic_fasta_round_trips_in_every_layout() {
+    let genome = |crlf| {
+        SyntheticGenome::new(7)
+            .chromosome("chr1", 2_000)
+            .with_g4(100, 3, [1, 4, 2])
+            .with_gap(500, 300)
+            .with_g4(1_200, 4, [2, 2, 2])
+            .chromosome("chr2", 150)
+            .with_g4(120, 2, [1, 2, 1])
+            .with_crlf(crlf)
+    };
+    let expected: Vec<_> = genome(false)
+        .expected()
+        .into_iter()
+        .map(|g| (g.chrom, g.start, g.end, g.tetrads))
+        .collect();
+    assert_eq!(expected.len(), 3);
+
+    let path = std::env::temp_dir().join("qgrs_synthetic_layouts.fa");
+    for crlf in [false, true] {
+        let genome = genome(crlf);
+        for line_width in [0, 60, 7] {
+            genome.write_fasta(&path, line_width).unwrap();
+            let layout = format!("crlf {crlf}, width {line_width}");
+            for mode in [InputMode::Mmap, InputMode::Stream] {
+                let sequences = crate::qgrs::load_sequences_from_path(&path, mode).unwrap();
+                let observed: Vec<_> = sequences
+                    .iter()
+                    .flat_map(|chrom| {
+                        search_chromosome(chrom, ScanConfig::default(), false)
+                            .hits
+                            .into_iter()
+                            .map(|g| (chrom.name().to_string(), g.start, g.end, g.tetrads))
+                    })
+                    .collect();
+                assert_eq!(observed, expected, "{layout}, {mode:?}");
+            }
+            let mut streamed = Vec::new();
+            stream::process_fasta_stream_with_config(
+                &path,
+                ScanConfig::default(),
+                false,
+                |results| {
+                    streamed.extend(
+                        results
+                            .hits
+                            .iter()
+                            .map(|g| (results.name.clone(), g.start, g.end, g.tetrads)),
+                    );
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(streamed, expected, "{layout}, stream pipeline");
+        }
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn no_target_base_loops_keep_only_clean_motifs() {
+    let sequence = "GGGGGAGGGGAGGGGAGGGG";
+    let strict = ScanLimits::default().with_loop_policy(LoopPolicy::NoTargetBase);
+    let permissive_raw =
+        find_owned_bytes_with_limits(arc_from_sequence(sequence), 2, 17, ScanLimits::default())
+            .unwrap();
+    let strict_raw =
+        find_owned_bytes_with_limits(arc_from_sequence(sequence), 2, 17, strict).unwrap();
+    // The leading fifth G lets permissive loops swallow G bases.
+    assert!(
+        permissive_raw
+            .iter()
+            .any(|g4| g4.marked_sequence().contains('g'))
+    );
+    assert!(permissive_raw.len() > strict_raw.len());
+    assert!(
+        strict_raw
+            .iter()
+            .all(|g4| !g4.marked_sequence().contains('g'))
+    );
+    let (hits, _ranges) = consolidate_g4s(strict_raw);
+    let clean: Vec<_> = hits
+        .iter()
+        .map(|g4| (g4.start, g4.tetrads, g4.sequence().to_string()))
+        .collect();
+    assert_eq!(clean, [(2, 4, "GGGGAGGGGAGGGGAGGGG".to_string())]);
+}
+
+#[test]
+fn no_target_base_loops_agree_across_chunked_and_stream_paths() {
+    let sequence = load_big_sequence();
+    let strict = ScanLimits::default().with_loop_policy(LoopPolicy::NoTargetBase);
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let scan = |limits| {
+            let raw = find_owned_bytes_with_topology(
+                arc_from_sequence(&sequence),
+                2,
+                17,
+                limits,
+                topology,
+            );
+            consolidate_g4s_with_topology(raw, topology, sequence.len()).0
+        };
+        let expected = scan(strict);
+        assert!(!expected.is_empty());
+        assert_ne!(
+            g4_signatures(&expected),
+            g4_signatures(&scan(ScanLimits::default())),
+            "{topology:?}"
+        );
+        for chunk_size in [45, 4096] {
+            let limits = strict.with_chunk_size(chunk_size);
+            assert_eq!(g4_signatures(&scan(limits)), g4_signatures(&expected));
+
+            let config = ScanConfig::new(2, 17, limits, topology, QuartetBase::G);
+            let fasta = format!(">chr1\n{sequence}\n");
+            let mut streamed = Vec::new();
+            stream::process_reader_with_config(fasta.as_bytes(), config, false, &mut |results| {
+                streamed = results.hits;
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(
+                g4_signatures(&streamed),
+                g4_signatures(&expected),
+                "{topology:?} chunk size {chunk_size}"
+            );
+        }
+    }
+}
+
+#[test]
+fn loop_alphabet_rejects_loops_with_other_bases() {
+    // The second loop is the only one holding an A.
+    let sequence = "GGGTGGGAGGGTGGG";
+    let pyrimidines = Some([false, true, false, true]);
+    for (alphabet, expected) in [(None, 1), (pyrimidines, 0), (Some([true; 4]), 1)] {
+        let limits = ScanLimits::default().with_loop_alphabet(alphabet);
+        let raw = find_owned_bytes_with_limits(arc_from_sequence(sequence), 2, 17, limits).unwrap();
+        let (hits, _ranges) = consolidate_g4s(raw);
+        assert_eq!(hits.len(), expected, "{alphabet:?}");
+
+        let config = ScanConfig::new(
+            2,
+            17,
+            limits.with_chunk_size(45),
+            SequenceTopology::Linear,
+            QuartetBase::G,
+        );
+        let fasta = format!(">chr1\n{sequence}\n");
+        let mut streamed = Vec::new();
+        stream::process_reader_with_config(fasta.as_bytes(), config, false, &mut |results| {
+            streamed = results.hits;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            g4_signatures(&streamed),
+            g4_signatures(&hits),
+            "{alphabet:?}"
+        );
+    }
+}
+
+#[test]
+fn imotif_scan_mirrors_the_g4_scan_of_the_complemented_sequence() {
+    let complement = |text: &str| -> String {
+        text.chars()
+            .map(|base| match base.to_ascii_lowercase() {
+                'g' => 'c',
+                'c' => 'g',
+                'a' => 't',
+                't' => 'a',
+                other => other,
+            })
+            .collect()
+    };
+    let sequence = load_big_sequence();
+    let g4s = consolidate_g4s(find_in_str(&sequence, 2, 17)).0;
+    assert!(!g4s.is_empty());
+    let expected: Vec<_> = g4_signatures(&g4s)
+        .into_iter()
+        .map(|mut signature| {
+            signature.8 = complement(&signature.8);
+            signature
+        })
+        .collect();
+
+    let complemented = complement(&sequence);
+    let limits = ScanLimits::default().with_chunk_size(4096);
+    let raw = find_owned_bytes_with_topology_and_base(
+        arc_from_sequence(&complemented),
+        2,
+        17,
+        limits,
+        SequenceTopology::Linear,
+        QuartetBase::C,
+    );
+    let chunked = consolidate_g4s(raw).0;
+    let config = ScanConfig::new(2, 17, limits, SequenceTopology::Linear, QuartetBase::C);
+    let fasta = format!(">chr1\n{complemented}\n");
+    let mut streamed = Vec::new();
+    stream::process_reader_with_config(fasta.as_bytes(), config, false, &mut |results| {
+        streamed = results.hits;
+        Ok(())
+    })
+    .unwrap();
+    for imotifs in [&chunked, &streamed] {
+        let mut actual = g4_signatures(imotifs);
+        for signature in &mut actual {
+            signature.8 = signature.8.to_ascii_lowercase();
+        }
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn scan_metrics_narrow_at_every_search_stage() {
+    let sequence = load_big_sequence();
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    let plain = search_chromosome(&chrom, ScanConfig::default(), false);
+    assert_eq!(plain.stats.metrics, None);
+
+    let config = ScanConfig::default().with_metrics(true);
+    let counted = search_chromosome(&chrom, config, false);
+    assert_eq!(g4_signatures(&counted.hits), g4_signatures(&plain.hits));
+
+    let mut scanner = stream::IncrementalScanner::new("chr1", config);
+    scanner.push(sequence.as_bytes());
+    let streamed = scanner.finish().unwrap();
+    assert_eq!(g4_signatures(&streamed.hits), g4_signatures(&plain.hits));
+
+    for (label, results) in [("mmap", &counted), ("stream", &streamed)] {
+        let metrics: ScanMetrics = results.stats.metrics.expect(label);
+        assert!(metrics.seeded > 0, "{label}: {metrics:?}");
+        assert!(metrics.viable <= metrics.complete, "{label}: {metrics:?}");
+        assert!(metrics.complete <= metrics.expanded, "{label}: {metrics:?}");
+        assert!(metrics.deduped <= metrics.viable, "{label}: {metrics:?}");
+        assert!(metrics.families <= metrics.deduped, "{label}: {metrics:?}");
+        assert_eq!(metrics.families, results.hits.len(), "{label}");
+    }
+    // Chunked windows never seed the same hit twice.
+    let metrics = counted.stats.metrics.unwrap();
+    assert_eq!(metrics.viable, counted.stats.raw_hits);
+    assert_eq!(metrics.deduped, counted.stats.raw_hits);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn csv_output_is_byte_identical_across_thread_counts() {
+    use crate::qgrs::render_family_ranges_csv;
+
+    let motif = load_big_sequence();
+    let sequence = [motif.as_str(); 6].join("TTGGG");
+    let fasta = format!(">chr1\n{sequence}\n");
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    let render = |results: SearchResults| {
+        let raw = results.raw_hits.as_deref().unwrap_or_default();
+        [
+            render_csv_results(&results.hits),
+            render_csv_results(raw),
+            render_family_ranges_csv(&results.family_ranges),
+        ]
+    };
+    // `first` and `tetrads` leave the reported member to tie-breaks most often.
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        for policy in [
+            FamilyPolicy::First,
+            FamilyPolicy::Tetrads,
+            FamilyPolicy::Score,
+        ] {
+            let limits = ScanLimits::default().with_chunk_size(60);
+            let config =
+                ScanConfig::new(2, 17, limits, topology, QuartetBase::G).with_family_policy(policy);
+            let [single, many] = [1, 8].map(|threads| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                render(pool.install(|| search_chromosome(&chrom, config, true)))
+            });
+            assert_eq!(many, single, "{topology:?} {policy:?}");
+
+            // The stream scheduler waits on its windows from the calling
+            // thread, so it runs on the global pool rather than inside one.
+            let mut streamed = None;
+            stream::process_reader_with_config(fasta.as_bytes(), config, true, &mut |results| {
+                streamed = Some(results);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(render(streamed.unwrap()), single, "{topology:?} {policy:?}");
+        }
+    }
+}
+
+#[test]
+fn family_stats_match_between_whole_record_and_stream_scans() {
+    // Three overlapping 3-tetrad hits, then a lone one 40 bases later.
+    let sequence = format!("GGGTGGGTGGGTGGGGG{}GGGAGGGAGGGAGGG", "A".repeat(40));
+    let config = ScanConfig::new(
+        3,
+        17,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    let chrom = ChromSequence {
+        name: "chr1".to_string(),
+        occurrence: 0,
+        description: None,
+        sequence: arc_from_sequence(&sequence),
+        comment_lines: 0,
+        index: 0,
+    };
+    let whole = search_chromosome(&chrom, config, false);
+    assert_eq!(whole.family_ranges, vec![(1, 17), (58, 72)]);
+    assert_eq!(
+        whole.family_stats,
+        vec![
+            FamilyStats {
+                members: 3,
+                best_score: 64,
+            },
+            FamilyStats {
+                members: 1,
+                best_score: 64,
+            },
+        ]
+    );
+    assert_eq!(whole.family_stats.len(), whole.hits.len());
+
+    let fasta = format!(">chr1\n{sequence}\n");
+    let mut streamed = Vec::new();
+    stream::process_reader_with_config(fasta.as_bytes(), config, false, &mut |results| {
+        streamed.push(results);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(streamed.len(), 1);
+    assert_eq!(streamed[0].family_ranges, whole.family_ranges);
+    assert_eq!(streamed[0].family_stats, whole.family_stats);
+}
+
+fn finished_record(index: usize) -> SearchResults {
+    SearchResults {
+        name: format!("chr{index}"),
+        index,
+        ..SearchResults::default()
+    }
+}
+
+#[test]
+fn file_order_delivery_holds_records_that_finish_early() {
+    let config = ScanConfig::default().with_max_buffered_records(2);
+    assert_eq!(config.delivery_order(), DeliveryOrder::FileOrder);
+    let mut delivery = stream::RecordDelivery::new(config);
+    let mut delivered = Vec::new();
+    let mut on_chromosome = |results: SearchResults| {
+        delivered.push(results.index);
+        Ok(())
+    };
+    // Records 0 to 2 run while 0 is unfinished; 3 would be a third waiting.
+    assert!(delivery.can_start(2));
+    assert!(!delivery.can_start(3));
+    delivery
+        .finished(finished_record(2), &mut on_chromosome)
+        .unwrap();
+    delivery
+        .finished(finished_record(1), &mut on_chromosome)
+        .unwrap();
+    assert_eq!(delivery.buffered(), 2);
+    delivery
+        .finished(finished_record(0), &mut on_chromosome)
+        .unwrap();
+    assert_eq!(delivery.buffered(), 0);
+    assert!(delivery.can_start(5));
+    // A skipped record releases the ones behind it without a delivery.
+    delivery
+        .finished(finished_record(4), &mut on_chromosome)
+        .unwrap();
+    delivery.skipped(3, &mut on_chromosome).unwrap();
+    delivery
+        .finished(finished_record(5), &mut on_chromosome)
+        .unwrap();
+    assert_eq!(delivered, [0, 1, 2, 4, 5]);
+
+    let mut delivery =
+        stream::RecordDelivery::new(config.with_delivery_order(DeliveryOrder::Completion));
+    let mut delivered = Vec::new();
+    let mut on_chromosome = |results: SearchResults| {
+        delivered.push((results.index, results.name));
+        Ok(())
+    };
+    assert!(delivery.can_start(10));
+    for index in [2, 0, 1] {
+        delivery
+            .finished(finished_record(index), &mut on_chromosome)
+            .unwrap();
+    }
+    delivery.skipped(3, &mut on_chromosome).unwrap();
+    assert_eq!(
+        delivered,
+        [
+            (2, "chr2".to_string()),
+            (0, "chr0".to_string()),
+            (1, "chr1".to_string())
+        ]
+    );
+}
+
+#[test]
+fn record_indices_count_skipped_records_in_stream_and_mmap_scans() {
+    let fasta = b">a\nGGGTGGGTGGGTGGG\n>b\nACGT\n>a\nGGGAGGGAGGGAGGG\n>c\nTTTT\n";
+    for order in [DeliveryOrder::FileOrder, DeliveryOrder::Completion] {
+        let config = ScanConfig::default().with_delivery_order(order);
+        let mut streamed = Vec::new();
+        stream::process_reader_with_skip(
+            &fasta[..],
+            config,
+            false,
+            &mut |name, _| name == "b",
+            &mut |results| {
+                streamed.push((results.name, results.occurrence, results.index));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            streamed,
+            [
+                ("a".to_string(), 0, 0),
+                ("a".to_string(), 1, 2),
+                ("c".to_string(), 0, 3)
+            ]
+        );
+    }
+
+    let path = std::env::temp_dir().join("qgrs_record_indices.fa");
+    fs::write(&path, fasta).unwrap();
+    let loaded = crate::qgrs::load_sequences_from_path_with_filter(
+        &path,
+        InputMode::Mmap,
+        LoadOptions::default(),
+        |name| name != "b",
+    )
+    .unwrap();
+    let scanned: Vec<_> = loaded
+        .iter()
+        .map(|chrom| {
+            let results = search_chromosome(chrom, ScanConfig::default(), false);
+            (chrom.index(), results.index)
+        })
+        .collect();
+    assert_eq!(scanned, [(0, 0), (2, 2), (3, 3)]);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn checkpointed_stream_scan_resumes_inside_a_record_after_a_kill() {
+    let genome = SyntheticGenome::new(11)
+        .chromosome("skipped", 500)
+        .with_g4(100, 3, [1, 2, 3])
+        .chromosome("chr1", 20_000)
+        .with_g4(1_000, 3, [2, 2, 2])
+        .with_g4(15_000, 4, [1, 5, 2])
+        .chromosome("chr2", 8_000)
+        .with_g4(500, 2, [1, 1, 1])
+        .with_g4(7_900, 3, [3, 1, 2])
+        .chromosome("chr3", 1_000)
+        .with_g4(10, 3, [1, 1, 1]);
+    let dir = std::env::temp_dir().join(format!("qgrs_checkpoint_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("genome.fa");
+    genome.write_fasta(&path, 60).unwrap();
+    let checkpoint = StreamCheckpoint::new(dir.join("scan.checkpoint")).with_interval(50);
+    let skip = |name: &str, _| name == "skipped";
+    let rendered = |results: &SearchResults| {
+        let raw = results.raw_hits.as_ref().map(|raw| g4_signatures(raw));
+        (results.name.clone(), render_csv_results(&results.hits), raw)
+    };
+
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let config = ScanConfig::new(2, 17, ScanLimits::default(), topology, QuartetBase::G);
+        let mut expected = Vec::new();
+        stream::process_fasta_stream_with_skip(&path, config, true, skip, |results| {
+            expected.push(rendered(&results));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(expected.len(), 3);
+
+        // Killed when chr2 is done: its last checkpoint is inside chr2.
+        let mut delivered = Vec::new();
+        let killed = stream::process_fasta_stream_with_checkpoint(
+            &path,
+            config,
+            true,
+            &checkpoint,
+            skip,
+            |_, _, _| panic!("nothing to resume yet"),
+            |results| {
+                if results.name == "chr2" {
+                    return Err(io::Error::other("killed"));
+                }
+                delivered.push(rendered(&results));
+                Ok(())
+            },
+        );
+        assert_eq!(killed.unwrap_err().to_string(), "killed");
+        let saved: serde_json::Value =
+            serde_json::from_slice(&fs::read(checkpoint.path()).unwrap()).unwrap();
+        assert_eq!(saved["record"]["name"], "chr2");
+        let offset = saved["record"]["sequence_len"].as_u64().unwrap();
+        assert!(offset > 0 && offset < 8_000, "{offset}");
+
+        let other = ScanConfig::new(2, 30, ScanLimits::default(), topology, QuartetBase::G);
+        let refused = stream::process_fasta_stream_with_checkpoint(
+            &path,
+            other,
+            true,
+            &checkpoint,
+            skip,
+            |_, _, _| {},
+            |_| Ok(()),
+        );
+        assert_eq!(refused.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(checkpoint.path().exists());
+
+        let mut replayed = Vec::new();
+        let mut resumed = Vec::new();
+        let records = stream::process_fasta_stream_with_checkpoint(
+            &path,
+            config,
+            true,
+            &checkpoint,
+            |name, occurrence| {
+                replayed.push((name.to_string(), occurrence));
+                name == "skipped"
+            },
+            |name, occurrence, sequence_len| {
+                resumed.push((name.to_string(), occurrence, sequence_len));
+            },
+            |results| {
+                delivered.push(rendered(&results));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(records, 4);
+        // chr1 and chr2 are not asked about again; chr3 is read afresh.
+        assert_eq!(
+            replayed,
+            [("skipped".to_string(), 0), ("chr3".to_string(), 0)]
+        );
+        assert_eq!(resumed, [("chr1".to_string(), 0, 20_000)]);
+        assert_eq!(delivered, expected, "{topology:?}");
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name != "genome.fa")
+            .collect();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checkpoints_save_settled_families_instead_of_every_raw_hit() {
+    let mut genome = SyntheticGenome::new(17).chromosome("chr1", 30_000);
+    for pos in (40..29_900).step_by(450) {
+        genome = genome.with_g4(pos, 4, [2, 3, 4]);
+    }
+    let genome = genome.chromosome("chr2", 1_000).with_g4(10, 3, [1, 1, 1]);
+    let dir = std::env::temp_dir().join(format!("qgrs_checkpoint_settled_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("genome.fa");
+    genome.write_fasta(&path, 60).unwrap();
+    let checkpoint = StreamCheckpoint::new(dir.join("scan.checkpoint")).with_period(Duration::ZERO);
+    let rendered = |results: &SearchResults| {
+        let metrics = results.stats.metrics.unwrap();
+        (
+            results.name.clone(),
+            render_csv_results(&results.hits),
+            results.family_ranges.clone(),
+            results.family_stats.clone(),
+            results.raw_hits.as_ref().map(|raw| g4_signatures(raw)),
+            results.stats.raw_hits,
+            (metrics.deduped, metrics.families),
+        )
+    };
+
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let mut sidecars = Vec::new();
+        for capture_raw in [false, true] {
+            let config = ScanConfig::new(2, 17, ScanLimits::default(), topology, QuartetBase::G)
+                .with_metrics(true);
+            let mut expected = Vec::new();
+            stream::process_fasta_stream_with_skip(
+                &path,
+                config,
+                capture_raw,
+                |_, _| false,
+                |results| {
+                    expected.push(rendered(&results));
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+            let killed = stream::process_fasta_stream_with_checkpoint(
+                &path,
+                config,
+                capture_raw,
+                &checkpoint,
+                |_, _| false,
+                |_, _, _| panic!("nothing to resume yet"),
+                |_| Err(io::Error::other("killed")),
+            );
+            assert_eq!(killed.unwrap_err().to_string(), "killed");
+            let saved: serde_json::Value =
+                serde_json::from_slice(&fs::read(checkpoint.path()).unwrap()).unwrap();
+            assert_eq!(saved["record"]["name"], "chr1");
+            let families = saved["record"]["families"].as_u64().unwrap();
+            assert!(families > 50, "{topology:?} {families}");
+            // Only the families at the end, and at the start of a circular
+            // record, are still open.
+            let open_hits = saved["record"]["open_hits"].as_array().unwrap().len();
+            assert!(open_hits < 10_000, "{topology:?} {open_hits}");
+            sidecars.push(saved["record"]["families_bytes"].as_u64().unwrap());
+
+            let mut delivered = Vec::new();
+            stream::process_fasta_stream_with_checkpoint(
+                &path,
+                config,
+                capture_raw,
+                &checkpoint,
+                |_, _| false,
+                |_, _, _| {},
+                |results| {
+                    delivered.push(rendered(&results));
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(delivered, expected, "{topology:?} {capture_raw}");
+        }
+        // Without raw hits each family keeps only its reported hit.
+        assert!(sidecars[0] * 3 < sidecars[1], "{topology:?} {sidecars:?}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The reverse complement of an uppercase `ACGT` sequence.
+fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .bytes()
+        .rev()
+        .map(|base| match base {
+            b'A' => 'T',
+            b'C' => 'G',
+            b'G' => 'C',
+            b'T' => 'A',
+            other => other as char,
+        })
+        .collect()
+}
+
+#[test]
+fn reverse_complement_fasta_scans_like_the_minus_strand_at_mirrored_coordinates() {
+    let genome = crate::qgrs::testing::self_test_genome();
+    let path = std::env::temp_dir().join("qgrs_revcomp_input.fa");
+    let rc_path = std::env::temp_dir().join("qgrs_revcomp_output.fa");
+    genome.write_fasta(&path, 70).unwrap();
+    let mut out = io::BufWriter::new(fs::File::create(&rc_path).unwrap());
+    assert_eq!(write_reverse_complement(&path, &mut out).unwrap(), 3);
+    out.flush().unwrap();
+    drop(out);
+
+    let original = crate::qgrs::load_sequences_from_path(&path, InputMode::Mmap).unwrap();
+    let reversed = crate::qgrs::load_sequences_from_path(&rc_path, InputMode::Stream).unwrap();
+    assert_eq!(original.len(), reversed.len());
+    let config = ScanConfig::default();
+    let mut total = 0;
+    for (forward, rc) in original.iter().zip(&reversed) {
+        assert_eq!(rc.name(), forward.name());
+        assert_eq!(rc.description(), Some("rc"));
+        let len = forward.sequence().len();
+        assert_eq!(rc.sequence().len(), len);
+
+        let plus = search_chromosome(rc, config, true);
+        let minus = search_chromosome(forward, config.opposite_strand(), true);
+        // Families cover the same bases at mirrored coordinates. Raw hits
+        // may differ inside long G runs, where loops are placed from the 5'
+        // end of whichever strand is read.
+        let mirrored = |start: usize, end: usize| (len + 1 - end, len + 1 - start);
+        let mut ranges: Vec<_> = minus
+            .family_ranges
+            .iter()
+            .map(|&(start, end)| mirrored(start, end))
+            .collect();
+        ranges.reverse();
+        assert_eq!(plus.family_ranges, ranges, "{}", forward.name());
+
+        // Each reported hit is a minus-strand hit read from the other
+        // strand: mirrored start and end, the same tetrads and G-score, and
+        // the reverse complement sequence. Between equally good members the
+        // earliest start wins, which is the other end on the other strand,
+        // so a tied family may report another of its best members.
+        let on_plus = |hit: &crate::qgrs::G4| {
+            let (start, end) = mirrored(hit.start, hit.end);
+            let sequence = reverse_complement(hit.sequence());
+            (start, end, hit.tetrads, hit.score, sequence)
+        };
+        let minus_raw: Vec<_> = minus
+            .raw_hits
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(on_plus)
+            .collect();
+        let mut tied = 0;
+        for (hit, winner) in plus.hits.iter().zip(minus.hits.iter().rev()) {
+            let found = (
+                hit.start,
+                hit.end,
+                hit.tetrads,
+                hit.score,
+                hit.sequence().to_string(),
+            );
+            let expected = on_plus(winner);
+            if found != expected {
+                assert!(minus_raw.contains(&found), "{}: {found:?}", forward.name());
+                assert_eq!(
+                    (hit.tetrads, hit.score, hit.length),
+                    (winner.tetrads, winner.score, winner.length),
+                    "{}: {found:?} for {expected:?}",
+                    forward.name()
+                );
+                tied += 1;
+            }
+        }
+        assert!(tied * 10 < plus.hits.len(), "{}: {tied}", forward.name());
+        total += plus.family_ranges.len();
+    }
+    assert!(total > 0);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&rc_path).unwrap();
+}
@@ -1,4 +1,10 @@
 mod helpers;
+#[cfg(all(feature = "mmap", feature = "export"))]
 mod integration_chunk;
+#[cfg(all(feature = "mmap", feature = "export"))]
 mod integration_stream;
+#[cfg(all(feature = "mmap", feature = "export"))]
+mod property;
+#[cfg(all(feature = "mmap", feature = "export"))]
 mod unit;
+mod unit_core;
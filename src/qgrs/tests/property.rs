@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use proptest::prelude::*;
+
+use crate::qgrs::stream::IncrementalScanner;
+use crate::qgrs::{
+    ChromSequence, FamilyPolicy, G4Record, LoopPolicy, MotifType, QuartetBase, ScanConfig,
+    ScanLimits, SequenceTopology, consolidate_g4s, find_owned_bytes_with_limits, scan_short,
+    search_chromosome,
+};
+
+use super::helpers::{arc_from_sequence, g4_signatures, run_internal_scan};
+
+/// G-rich DNA: runs of G separated by short loops, with the occasional N, so
+/// most inputs contain several overlapping candidates.
+fn g_rich_sequence() -> impl Strategy<Value = String> {
+    let base = prop_oneof![
+        6 => Just('g'),
+        1 => Just('a'),
+        1 => Just('c'),
+        1 => Just('t'),
+        1 => Just('n'),
+    ];
+    prop::collection::vec(base, 0..400).prop_map(|bases| bases.into_iter().collect())
+}
+
+/// Oligo-sized DNA in mixed case, rich in both G and C so either tetrad
+/// base finds candidates.
+fn short_sequence() -> impl Strategy<Value = Vec<u8>> {
+    let base = prop_oneof![
+        4 => Just(b'g'),
+        2 => Just(b'G'),
+        3 => Just(b'c'),
+        1 => Just(b'C'),
+        1 => Just(b'a'),
+        1 => Just(b'T'),
+        1 => Just(b'n'),
+    ];
+    prop::collection::vec(base, 0..=100)
+}
+
+/// Parameters `ScanLimits::validate` and the CLI accept: `max_run >=
+/// min_tetrads`, `max_g4_length >= 4 * min_tetrads + 2` and, when set,
+/// `chunk_size >= max_g4_length`, under either loop policy and an optional
+/// loop alphabet.
+fn scan_parameters() -> impl Strategy<Value = (usize, i32, ScanLimits)> {
+    (2usize..=5).prop_flat_map(|min_tetrads| {
+        (
+            Just(min_tetrads),
+            0i32..=60,
+            4 * min_tetrads + 2..=60,
+            min_tetrads..=12,
+            prop::option::of(0usize..=200),
+            prop_oneof![Just(LoopPolicy::Any), Just(LoopPolicy::NoTargetBase)],
+            prop::option::of(prop::array::uniform4(any::<bool>())),
+        )
+            .prop_map(
+                |(
+                    min_tetrads,
+                    min_score,
+                    max_g4_length,
+                    max_run,
+                    chunk_extra,
+                    loop_policy,
+                    loop_alphabet,
+                )| {
+                    let limits = ScanLimits::new(max_g4_length, max_run)
+                        .with_loop_policy(loop_policy)
+                        .with_loop_alphabet(loop_alphabet);
+                    let limits = match chunk_extra {
+                        Some(extra) => limits.with_chunk_size(max_g4_length + extra),
+                        None => limits,
+                    };
+                    (min_tetrads, min_score, limits)
+                },
+            )
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn unchunked_chunked_and_stream_scans_agree(
+        sequence in g_rich_sequence(),
+        (min_tetrads, min_score, limits) in scan_parameters(),
+        push_size in 1usize..64,
+    ) {
+        let reference = run_internal_scan(&sequence, min_tetrads, min_score, limits);
+
+        let chunked_raw =
+            find_owned_bytes_with_limits(arc_from_sequence(&sequence), min_tetrads, min_score, limits).unwrap();
+        let (chunked, _ranges) = consolidate_g4s(chunked_raw);
+        prop_assert_eq!(g4_signatures(&chunked), g4_signatures(&reference));
+
+        let config = ScanConfig::new(
+            min_tetrads,
+            min_score,
+            limits,
+            SequenceTopology::Linear,
+            QuartetBase::G,
+        );
+        let mut scanner = IncrementalScanner::new("chr1", config);
+        for piece in sequence.as_bytes().chunks(push_size) {
+            scanner.push(piece);
+        }
+        let streamed = scanner.finish().unwrap();
+        prop_assert_eq!(g4_signatures(&streamed.hits), g4_signatures(&reference));
+    }
+
+    #[test]
+    fn short_scans_match_search_chromosome(
+        sequence in short_sequence(),
+        (min_tetrads, min_score, limits) in scan_parameters(),
+        policy in prop_oneof![
+            Just(FamilyPolicy::Score),
+            Just(FamilyPolicy::Length),
+            Just(FamilyPolicy::Tetrads),
+            Just(FamilyPolicy::First),
+        ],
+        circular in any::<bool>(),
+        base in prop_oneof![Just(QuartetBase::G), Just(QuartetBase::C)],
+    ) {
+        let topology = if circular {
+            SequenceTopology::Circular
+        } else {
+            SequenceTopology::Linear
+        };
+        let config = ScanConfig::new(min_tetrads, min_score, limits, topology, base)
+            .with_family_policy(policy);
+        let chrom = ChromSequence {
+            name: "oligo".to_string(),
+            occurrence: 0,
+            description: None,
+            sequence: Arc::new(sequence.to_ascii_lowercase()),
+            comment_lines: 0,
+            index: 0,
+        };
+        let expected: Vec<G4Record> = search_chromosome(&chrom, config, false)
+            .hits
+            .iter()
+            .map(|hit| G4Record {
+                motif_type: MotifType::from(base),
+                ..hit.to_record()
+            })
+            .collect();
+        prop_assert_eq!(scan_short(&sequence, config), expected);
+    }
+}
@@ -2,63 +2,132 @@ use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 
 use flate2::Compression;
 use flate2::write::GzEncoder;
 
 use crate::qgrs::{
-    InputMode, QuartetBase, ScanLimits, SequenceTopology, consolidate_g4s,
-    consolidate_g4s_with_topology, find_owned_bytes, find_owned_bytes_with_topology,
-    find_owned_bytes_with_topology_and_base, load_sequences_from_path, render_csv_results,
-    render_csv_results_with_projection, render_family_ranges_csv_with_projection,
-    write_parquet_family_ranges, write_parquet_results,
+    COORDINATES_METADATA_KEY, ChromSequence, ColumnValues, CoordinateSystem, ExtraColumn,
+    FamilyStats, G4Record, InputMode, LoadOptions, MOTIF_TYPE_COLUMN, MinScore, MotifType,
+    ParquetSink, QuartetBase, ScanConfig, ScanLimits, SequenceTopology, Strand, consolidate_g4s,
+    consolidate_g4s_with_topology, find_in_str, find_owned_bytes, find_owned_bytes_with_topology,
+    find_owned_bytes_with_topology_and_base, load_sequences_from_path,
+    load_sequences_from_path_with_filter, load_sequences_from_path_with_options,
+    load_sequences_from_reader, mmap_hints, parse_csv_records, parse_parquet_records,
+    read_record_headers, read_record_headers_with_filter, render_csv_results,
+    render_csv_results_with_columns, render_csv_results_with_coordinates,
+    render_csv_results_with_projection, render_family_ranges_bed,
+    render_family_ranges_csv_with_coordinates, render_family_ranges_csv_with_projection,
+    render_qgrs_mapper, search_chromosome, write_arrow_ipc_results,
+    write_arrow_ipc_results_with_coordinates, write_parquet_family_ranges,
+    write_parquet_family_ranges_with_coordinates, write_parquet_results,
+    write_parquet_results_batched, write_parquet_results_with_columns,
+    write_parquet_results_with_coordinates, write_reverse_complement_from_reader,
 };
+use crate::qgrs::{io, stream};
 
-use super::helpers::arc_from_sequence;
+use crate::qgrs::data::SequenceData;
+use crate::qgrs::search::{
+    RawSearchWindow, SearchHooks, find_raw_bytes_no_chunking, find_raw_on_window_bytes,
+    take_peak_stack_len,
+};
 
-#[test]
-fn finds_single_g4() {
-    let sequence = "GGGGAGGGGAGGGGAGGGG";
-    let raw = find_owned_bytes(arc_from_sequence(sequence), 4, 17);
-    let (results, _ranges) = consolidate_g4s(raw);
-    assert_eq!(results.len(), 1);
-    let g = &results[0];
-    assert_eq!(g.start, 1);
-    assert_eq!(g.tetrads, 4);
-    assert_eq!(g.y1, 1);
-    assert_eq!(g.y2, 1);
-    assert_eq!(g.y3, 1);
-    assert_eq!(g.sequence(), sequence);
-}
+use super::helpers::{arc_from_sequence, g4_signatures};
 
 #[test]
-fn empty_sequence_has_no_hits() {
-    let raw = find_owned_bytes(arc_from_sequence("ACACAC"), 4, 17);
-    let (results, _ranges) = consolidate_g4s(raw);
-    assert!(results.is_empty());
-}
+fn depth_first_search_keeps_candidate_stack_small_on_poly_g() {
+    // 10-G runs split by single gaps: every run seeds 45 candidates and each
+    // expands into dozens of loop choices.
+    let sequence = "GGGGGGGGGGT".repeat(24).into_bytes();
+    let seeds_per_run: usize = (2..=10).map(|tetrads| 11 - tetrads).sum();
+    let seeds = seeds_per_run * 24;
+    let limits = ScanLimits::default();
 
-#[test]
-fn target_base_loops_do_not_exceed_max_run() {
-    let raw = find_owned_bytes_with_topology_and_base(
-        arc_from_sequence("CCCCCCCCCCCCCCCC"),
+    take_peak_stack_len();
+    let whole = find_raw_bytes_no_chunking(
+        sequence.clone(),
         2,
-        17,
-        ScanLimits::new(45, 10),
-        SequenceTopology::Linear,
-        QuartetBase::C,
+        MinScore::Fixed(17),
+        limits,
+        QuartetBase::G,
+        SearchHooks::default(),
     );
-    assert!(!raw.is_empty());
-    assert!(
-        raw.iter()
-            .all(|g4| longest_target_run(g4.sequence().as_bytes(), b'C') <= 10)
+    let whole_peak = take_peak_stack_len();
+
+    let data = Arc::new(SequenceData::from_bytes(Arc::new(sequence.clone())));
+    let window = RawSearchWindow::new(0, sequence.len(), sequence.len());
+    let windowed = find_raw_on_window_bytes(
+        data,
+        window,
+        2,
+        MinScore::Fixed(17),
+        limits,
+        QuartetBase::G,
+        SearchHooks::default(),
     );
+    let window_peak = take_peak_stack_len();
+
+    assert!(!whole.is_empty());
+    assert_eq!(whole.len(), windowed.len());
+    // The breadth-first queue peaked above the seed count; the stack is
+    // bounded by three loop levels of siblings, whatever the input length.
+    for peak in [whole_peak, window_peak] {
+        assert!(peak > 0);
+        assert!(peak <= 3 * limits.max_g4_length, "peak stack {peak}");
+        assert!(peak * 10 < seeds, "peak stack {peak} vs {seeds} seeds");
+    }
+}
+
+#[test]
+fn extreme_limits_scan_like_the_legacy_caps() {
+    let extreme = ScanLimits::new(usize::MAX, usize::MAX);
+    assert_eq!(extreme.max_motif_length(), 45);
+    assert_eq!(extreme.max_tetrads(), 11);
+    assert_eq!(extreme.max_score(3), 64);
+    assert_eq!(extreme.max_score(usize::MAX), i64::MIN);
+    assert_eq!(extreme.auto_min_score(usize::MAX), i32::MIN);
+    assert_eq!(extreme.validate(2), Ok(()));
+
+    // Anything past 45 bp behaves like 45 bp, linear or circular, chunked
+    // or streamed.
+    let sequence = format!("{}{}", "GGGGAGGGGAGGGGAGGGGTT".repeat(8), "G".repeat(16));
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let scan = |limits| {
+            let raw = find_owned_bytes_with_topology_and_base(
+                arc_from_sequence(&sequence),
+                2,
+                17,
+                limits,
+                topology,
+                QuartetBase::G,
+            );
+            consolidate_g4s_with_topology(raw, topology, sequence.len()).0
+        };
+        let expected = scan(ScanLimits::new(45, 16));
+        assert!(!expected.is_empty());
+        assert_eq!(g4_signatures(&scan(extreme)), g4_signatures(&expected));
+
+        let config = ScanConfig::new(2, 17, extreme, topology, QuartetBase::G);
+        let mut streamed = Vec::new();
+        stream::process_reader_with_config(
+            format!(">chr1\n{sequence}\n").as_bytes(),
+            config,
+            false,
+            &mut |results| {
+                streamed = results.hits;
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(g4_signatures(&streamed), g4_signatures(&expected));
+    }
 }
 
 #[test]
 fn csv_output_includes_header_and_rows() {
     let sequence = "GGGGAGGGGAGGGGAGGGG";
-    let raw = find_owned_bytes(arc_from_sequence(sequence), 4, 17);
+    let raw = find_in_str(sequence, 4, 17);
     let (results, _ranges) = consolidate_g4s(raw);
     let csv = render_csv_results(&results);
     assert!(csv.starts_with("start,end,length"));
@@ -70,7 +139,7 @@ fn parquet_writer_emits_bytes() {
     let sequence = "GGGGAGGGGAGGGGAGGGG";
     let path = env::temp_dir().join("qgrs_parquet_test.parquet");
     let file = fs::File::create(&path).expect("temp parquet file");
-    let raw = find_owned_bytes(arc_from_sequence(sequence), 4, 17);
+    let raw = find_in_str(sequence, 4, 17);
     let (results, _ranges) = consolidate_g4s(raw);
     write_parquet_results(&results, file).expect("parquet export");
     let metadata = fs::metadata(&path).expect("metadata");
@@ -89,6 +158,558 @@ fn family_parquet_writer_emits_bytes() {
     let _ = fs::remove_file(&path);
 }
 
+#[test]
+fn atomic_write_renames_into_place_and_leaves_no_tmp_file() {
+    let path = env::temp_dir().join("qgrs_atomic_write_test.csv");
+    let tmp = io::tmp_path(&path);
+    assert_eq!(tmp.file_name().unwrap(), "qgrs_atomic_write_test.csv.tmp");
+    fs::write(&path, "old\n").unwrap();
+    for fsync in [true, false] {
+        io::write_atomically(&path, fsync, |tmp| {
+            assert!(!tmp.exists());
+            fs::write(tmp, "start,end\n")
+        })
+        .expect("atomic write");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "start,end\n");
+        assert!(!tmp.exists());
+    }
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn failed_atomic_write_leaves_only_the_tmp_file() {
+    let path = env::temp_dir().join("qgrs_atomic_write_failure.csv");
+    let tmp = io::tmp_path(&path);
+    let _ = fs::remove_file(&path);
+    let err = io::write_atomically(&path, true, |tmp| {
+        fs::write(tmp, "start,end\n1,")?;
+        Err(std::io::Error::other("disk quota exceeded"))
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "disk quota exceeded");
+    assert!(!path.exists());
+    assert_eq!(fs::read_to_string(&tmp).unwrap(), "start,end\n1,");
+
+    // An earlier complete output survives a failed rewrite untouched.
+    fs::write(&path, "kept\n").unwrap();
+    let _ = io::write_atomically(&path, true, |tmp| {
+        fs::write(tmp, "partial")?;
+        Err(std::io::Error::other("killed"))
+    });
+    assert_eq!(fs::read_to_string(&path).unwrap(), "kept\n");
+    assert_eq!(fs::read_to_string(&tmp).unwrap(), "partial");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&tmp);
+}
+
+#[test]
+fn qgrs_mapper_text_marks_tetrads_upper_and_loops_lower() {
+    let config = ScanConfig::new(
+        4,
+        17,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    let sequence = "GGGGAGGGGAGGGGAGGGG";
+    let (hits, _ranges) = consolidate_g4s(find_in_str(sequence, 4, 17));
+    let expected = "\
+# QGRS Mapper-compatible output
+# Sequence: chr1 (19 bp)
+# Max QGRS length: 45
+# Min G-group size: 4
+# Min G-score: 17
+# Max G-run: 10
+# Base: g, topology: linear, family policy: score
+Position  Length  QGRS                                           G-Score
+1         19      GGGGaGGGGaGGGGaGGGG                            84
+";
+    assert_eq!(render_qgrs_mapper("chr1", 19, config, &hits), expected);
+
+    // Two tetrads with an empty first loop: the first two runs merge.
+    let (hits, _ranges) = consolidate_g4s(find_in_str("ttGGGGTGGTGGtt", 2, 0));
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].y1, hits[0].y2, hits[0].y3), (0, 1, 1));
+    assert_eq!(hits[0].marked_sequence(), "GGGGtGGtGG");
+    let rendered = render_qgrs_mapper("chr1", 14, config, &hits);
+    assert!(rendered.ends_with(&format!("3         10      {:<45}  20\n", "GGGGtGGtGG")));
+}
+
+#[test]
+fn csv_output_parses_back_into_the_same_records() {
+    let sequence = format!("ggggaggggaggggagggg{}", "t".repeat(60)).repeat(3);
+    let (results, _ranges) = consolidate_g4s(find_in_str(&sequence, 2, 17));
+    let expected: Vec<G4Record> = results.iter().map(|g4| g4.to_record()).collect();
+    assert!(!expected.is_empty());
+    assert!(results.iter().all(|g4| !g4.sequence_is_cached()));
+
+    let features = vec!["a,b".to_string(); results.len()];
+    let extra = [ExtraColumn::new("feature", ColumnValues::Utf8(features))];
+    for csv in [
+        render_csv_results(&results),
+        render_csv_results_with_columns(&results, &extra),
+    ] {
+        let parsed = parse_csv_records(csv.as_bytes()).expect("csv parses");
+        assert_eq!(parsed, expected);
+    }
+    assert_eq!(parse_csv_records("".as_bytes()).unwrap(), Vec::new());
+
+    // A motif_type column, wherever it sits among the opt-in columns.
+    let motif_types = vec!["i-motif".to_string(); results.len()];
+    let extra = [
+        ExtraColumn::new(
+            "feature",
+            ColumnValues::Utf8(vec![".".to_string(); results.len()]),
+        ),
+        ExtraColumn::new(MOTIF_TYPE_COLUMN, ColumnValues::Utf8(motif_types)),
+    ];
+    let parsed = parse_csv_records(render_csv_results_with_columns(&results, &extra).as_bytes())
+        .expect("csv parses");
+    let imotifs: Vec<G4Record> = expected
+        .iter()
+        .map(|record| record.clone().with_motif_type(MotifType::IMotif))
+        .collect();
+    assert_eq!(parsed, imotifs);
+
+    let err = parse_csv_records("start,end\n1,19,19,4\n".as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("line 2"), "{err}");
+}
+
+#[test]
+fn g4_records_round_trip_through_json() {
+    let record = G4Record::new(1, 19, 4, [1, 1, 1], 63, "GGGGAGGGGAGGGGAGGGG");
+    assert_eq!(record.length, 19);
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(
+        !json.contains("chrom") && !json.contains("strand") && !json.contains("motif_type"),
+        "{json}"
+    );
+    assert_eq!(serde_json::from_str::<G4Record>(&json).unwrap(), record);
+
+    let located = record.with_chrom("chr1").with_strand(Strand::Minus);
+    let json = serde_json::to_string(&located).unwrap();
+    assert!(json.contains(r#""strand":"-""#), "{json}");
+    assert_eq!(serde_json::from_str::<G4Record>(&json).unwrap(), located);
+
+    let imotif = located.with_motif_type(MotifType::IMotif);
+    let json = serde_json::to_string(&imotif).unwrap();
+    assert!(json.contains(r#""motif_type":"i-motif""#), "{json}");
+    assert_eq!(serde_json::from_str::<G4Record>(&json).unwrap(), imotif);
+}
+
+#[test]
+fn detached_hits_release_the_sequence_and_export_identically() {
+    let sequence = format!("ggggaggggaggggagggg{}", "t".repeat(60)).repeat(20);
+    let input = arc_from_sequence(&sequence);
+    let weak = Arc::downgrade(&input);
+    let (mut results, _ranges) = consolidate_g4s(find_owned_bytes(input, 2, 17));
+    let (expected, _ranges) = consolidate_g4s(find_in_str(&sequence, 2, 17));
+    assert!(!results.is_empty());
+    // Cache one hit's sequence first: detaching must keep it valid.
+    let _ = results[0].sequence();
+    assert!(weak.upgrade().is_some());
+
+    crate::qgrs::detach_all(&mut results);
+    assert!(
+        weak.upgrade().is_none(),
+        "detached hits still hold the input"
+    );
+    assert!(results.iter().all(|g4| g4.is_detached()));
+    assert_eq!(g4_signatures(&results), g4_signatures(&expected));
+    for (detached, original) in results.iter().zip(&expected) {
+        assert_eq!(detached.sequence(), original.sequence());
+        assert_eq!(detached.sequence_slice(), original.sequence_slice());
+    }
+    assert_eq!(render_csv_results(&results), render_csv_results(&expected));
+
+    let mut clone = results[1].clone();
+    clone.detach();
+    assert_eq!(clone.sequence(), expected[1].sequence());
+}
+
+#[test]
+fn detached_hits_config_applies_to_search_results() {
+    let sequence = format!("{}ggggaggggaggggagggg", "a".repeat(200)).repeat(5);
+    let config = ScanConfig::default();
+    let scan = |config: ScanConfig| {
+        let mut scanner = stream::IncrementalScanner::new("chr1", config).with_raw_hits(true);
+        scanner.push(sequence.as_bytes());
+        scanner.finish().unwrap()
+    };
+    let attached = scan(config);
+    let detached = scan(config.with_detached_hits(true));
+    assert!(!attached.hits.is_empty());
+    assert!(attached.hits.iter().all(|g4| !g4.is_detached()));
+    assert!(detached.hits.iter().all(|g4| g4.is_detached()));
+    let raw_hits = detached.raw_hits.as_deref().expect("raw hits captured");
+    assert!(raw_hits.iter().all(|g4| g4.is_detached()));
+    assert_eq!(g4_signatures(&detached.hits), g4_signatures(&attached.hits));
+    assert_eq!(
+        render_csv_results(&detached.hits),
+        render_csv_results(&attached.hits)
+    );
+}
+
+#[test]
+fn arrow_ipc_export_reads_back_with_chrom_column() {
+    use arrow_array::{Array, Float64Array, Int32Array, StringArray, UInt64Array};
+    use arrow_ipc::reader::FileReader;
+
+    let sequence = format!("ggggaggggaggggagggg{}gggttgggttgggttggg", "t".repeat(30));
+    let raw = find_in_str(&sequence, 2, 17);
+    let (results, _ranges) = consolidate_g4s(raw);
+    assert_eq!(results.len(), 2);
+    let extra = [ExtraColumn::new(
+        "percentile",
+        ColumnValues::Float64(vec![100.0, 50.0]),
+    )];
+
+    let mut bytes = Vec::new();
+    write_arrow_ipc_results("chr7", &results, &extra, &mut bytes).expect("arrow export");
+    let reader = FileReader::try_new(std::io::Cursor::new(bytes), None).expect("arrow reader");
+    let names: Vec<String> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "start",
+            "end",
+            "length",
+            "tetrads",
+            "y1",
+            "y2",
+            "y3",
+            "score",
+            "sequence",
+            "chrom",
+            "percentile"
+        ]
+    );
+    let batches: Vec<_> = reader.map(|batch| batch.expect("record batch")).collect();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), results.len());
+
+    // Every column of the second row against the hit it came from.
+    let g4 = &results[1];
+    let column = |name: &str| batch.column_by_name(name).expect(name).clone();
+    let uint = |name: &str| {
+        let array = column(name);
+        array
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap()
+            .value(1) as usize
+    };
+    let int = |name: &str| {
+        let array = column(name);
+        array
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap()
+            .value(1)
+    };
+    let text = |name: &str| {
+        let array = column(name);
+        array
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(1)
+            .to_string()
+    };
+    assert_eq!(
+        (uint("start"), uint("end"), uint("length"), uint("tetrads")),
+        (g4.start, g4.end, g4.length, g4.tetrads)
+    );
+    assert_eq!(
+        (int("y1"), int("y2"), int("y3"), int("score")),
+        (g4.y1, g4.y2, g4.y3, g4.score)
+    );
+    assert_eq!(text("sequence"), "GGGTTGGGTTGGGTTGGG");
+    assert_eq!(text("chrom"), "chr7");
+    let percentile = column("percentile");
+    let percentile = percentile.as_any().downcast_ref::<Float64Array>().unwrap();
+    assert_eq!(percentile.value(1), 50.0);
+
+    let batch = crate::qgrs::build_record_batch(None, &results).expect("record batch");
+    assert_eq!(batch.num_columns(), 9);
+    assert_eq!(batch.num_rows(), 2);
+}
+
+#[test]
+fn batched_parquet_export_reads_back_every_row() {
+    use arrow_array::{Array, StringArray, UInt64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let sequence = format!("ggggaggggaggggagggg{}", "t".repeat(60)).repeat(30);
+    let raw = find_in_str(&sequence, 4, 17);
+    let (results, _ranges) = consolidate_g4s(raw);
+    assert!(
+        results.len() > 7,
+        "need several batches, got {}",
+        results.len()
+    );
+    let ids = (0..results.len() as u64).collect();
+    let extra = [ExtraColumn::new("row", ColumnValues::UInt64(ids))];
+
+    let path = env::temp_dir().join("qgrs_batched_parquet_test.parquet");
+    let file = fs::File::create(&path).expect("temp parquet file");
+    write_parquet_results_batched(&results, &extra, 7, file).expect("parquet export");
+
+    let file = fs::File::open(&path).expect("reopen parquet file");
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .expect("parquet reader")
+        .build()
+        .expect("record batches");
+    let mut rows = 0usize;
+    for batch in reader {
+        let batch = batch.expect("record batch");
+        let starts = batch.column_by_name("start").expect("start column");
+        let starts = starts.as_any().downcast_ref::<UInt64Array>().unwrap();
+        let sequences = batch.column_by_name("sequence").expect("sequence column");
+        let sequences = sequences.as_any().downcast_ref::<StringArray>().unwrap();
+        let ids = batch.column_by_name("row").expect("extra column");
+        let ids = ids.as_any().downcast_ref::<UInt64Array>().unwrap();
+        for index in 0..batch.num_rows() {
+            let g4 = &results[ids.value(index) as usize];
+            assert_eq!(ids.value(index) as usize, rows + index);
+            assert_eq!(starts.value(index) as usize, g4.start);
+            assert_eq!(sequences.value(index), g4.sequence());
+        }
+        rows += batch.num_rows();
+    }
+    assert_eq!(rows, results.len());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn parquet_sink_batches_match_single_batch_output() {
+    use arrow_array::{Array, Int64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    /// Core rows, `rank` values and the coordinates footer entry.
+    fn read_rows(path: &std::path::Path) -> (Vec<G4Record>, Vec<Option<i64>>, Option<String>) {
+        let records = parse_parquet_records(fs::File::open(path).unwrap()).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(path).unwrap())
+            .expect("parquet reader");
+        let coordinates = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|pairs| pairs.iter().find(|kv| kv.key == COORDINATES_METADATA_KEY))
+            .and_then(|kv| kv.value.clone());
+        let mut ranks = Vec::new();
+        for batch in builder.build().expect("record batches") {
+            let batch = batch.expect("record batch");
+            let column = batch.column_by_name("rank").expect("rank column");
+            let column = column.as_any().downcast_ref::<Int64Array>().unwrap();
+            ranks.extend(column.iter());
+        }
+        (records, ranks, coordinates)
+    }
+
+    let sequence = format!("ggggaggggaggggagggg{}", "t".repeat(60)).repeat(30);
+    let (results, _ranges) = consolidate_g4s(find_in_str(&sequence, 4, 17));
+    assert!(results.len() > 7, "need several batches");
+    let ranks = (0..results.len()).map(|row| Some(row as i64 % 3)).collect();
+    let extra = [ExtraColumn::new("rank", ColumnValues::Int64(ranks))];
+    let metadata = [("qgrs.min_score", "17".to_string())];
+    let coordinates = CoordinateSystem::ZeroBasedHalfOpen;
+
+    let single = env::temp_dir().join("qgrs_sink_single_test.parquet");
+    write_parquet_results_with_coordinates(
+        &results,
+        &extra,
+        &metadata,
+        coordinates,
+        fs::File::create(&single).unwrap(),
+    )
+    .unwrap();
+
+    let sunk = env::temp_dir().join("qgrs_sink_batched_test.parquet");
+    let mut sink = ParquetSink::try_new(
+        fs::File::create(&sunk).unwrap(),
+        &extra,
+        &metadata,
+        coordinates,
+    )
+    .unwrap();
+    let mut start = 0;
+    for batch in results.chunks(7) {
+        let records: Vec<G4Record> = batch.iter().map(|g4| g4.to_record()).collect();
+        sink.append_with_columns(&records, &extra, start..start + batch.len())
+            .unwrap();
+        start += batch.len();
+    }
+    sink.close().unwrap();
+
+    let expected = read_rows(&single);
+    let actual = read_rows(&sunk);
+    assert_eq!(actual.0.len(), results.len());
+    assert_eq!(actual, expected);
+    assert_eq!(actual.2.as_deref(), Some(coordinates.label()));
+
+    // Without extra columns, plain `append` reads back like the hits.
+    let mut sink = ParquetSink::try_new(
+        fs::File::create(&sunk).unwrap(),
+        &[],
+        &[],
+        CoordinateSystem::OneBasedInclusive,
+    )
+    .unwrap();
+    let records: Vec<G4Record> = results.iter().map(|g4| g4.to_record()).collect();
+    for batch in records.chunks(5) {
+        sink.append(batch).unwrap();
+    }
+    sink.close().unwrap();
+    assert_eq!(
+        parse_parquet_records(fs::File::open(&sunk).unwrap()).unwrap(),
+        records
+    );
+    let _ = fs::remove_file(&single);
+    let _ = fs::remove_file(&sunk);
+}
+
+#[test]
+fn parquet_records_read_back_like_csv_records() {
+    let sequence = format!("GGGGAGGGGAGGGGAGGGG{}GGGTTGGGTTGGGTTGGG", "T".repeat(50));
+    let (results, _ranges) = consolidate_g4s(find_in_str(&sequence, 2, 17));
+    assert_eq!(results.len(), 2);
+    let path = env::temp_dir().join("qgrs_parquet_records_test.parquet");
+    let labels = vec!["G4".to_string(), "i-motif".to_string()];
+    let extra = [ExtraColumn::new(
+        MOTIF_TYPE_COLUMN,
+        ColumnValues::Utf8(labels),
+    )];
+    write_parquet_results_with_columns(&results, &extra, fs::File::create(&path).unwrap()).unwrap();
+    let records = parse_parquet_records(fs::File::open(&path).unwrap()).unwrap();
+    let csv = render_csv_results_with_columns(&results, &extra);
+    assert_eq!(records, parse_csv_records(csv.as_bytes()).unwrap());
+    assert_eq!(records[1].motif_type, MotifType::IMotif);
+
+    write_parquet_results(&results, fs::File::create(&path).unwrap()).unwrap();
+    let records = parse_parquet_records(fs::File::open(&path).unwrap()).unwrap();
+    let expected: Vec<G4Record> = results.iter().map(G4Record::from).collect();
+    assert_eq!(records, expected);
+
+    let extra = [ExtraColumn::new(
+        MOTIF_TYPE_COLUMN,
+        ColumnValues::Utf8(vec!["G4".to_string(), "Z-DNA".to_string()]),
+    )];
+    write_parquet_results_with_columns(&results, &extra, fs::File::create(&path).unwrap()).unwrap();
+    let err = parse_parquet_records(fs::File::open(&path).unwrap()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn zero_based_exports_only_move_start_and_bed_ignores_them() {
+    use arrow_ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let (results, ranges) = consolidate_g4s(find_in_str("ttGGGGAGGGGAGGGGAGGGGtt", 4, 17));
+    assert_eq!((results[0].start, results[0].end), (3, 21));
+    let one = CoordinateSystem::OneBasedInclusive;
+    let zero = CoordinateSystem::ZeroBasedHalfOpen;
+    assert_eq!(
+        render_csv_results_with_coordinates(&results, &[], one),
+        render_csv_results(&results)
+    );
+    let row = |coordinates| {
+        let csv = render_csv_results_with_coordinates(&results, &[], coordinates);
+        csv.lines().nth(1).unwrap().to_string()
+    };
+    assert_eq!(row(one), "3,21,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG");
+    assert_eq!(row(zero), "2,21,19,4,1,1,1,84,GGGGAGGGGAGGGGAGGGG");
+    assert_eq!(
+        render_family_ranges_csv_with_coordinates(&ranges, zero),
+        "family_index,start,end\n1,2,21\n"
+    );
+    let record = G4Record::from(&results[0]).in_coordinates(zero);
+    assert_eq!((record.start, record.end, record.length), (2, 21, 19));
+    assert!(
+        serde_json::to_string(&record)
+            .unwrap()
+            .starts_with("{\"start\":2,\"end\":21,")
+    );
+
+    let path = env::temp_dir().join("qgrs_zero_based_test.parquet");
+    let metadata = [("qgrs.min_score", "17".to_string())];
+    write_parquet_results_with_coordinates(
+        &results,
+        &[],
+        &metadata,
+        zero,
+        fs::File::create(&path).unwrap(),
+    )
+    .unwrap();
+    let builder = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&path).unwrap()).unwrap();
+    let footer: Vec<_> = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .filter(|kv| kv.key.starts_with("qgrs."))
+        .map(|kv| (kv.key.clone(), kv.value.clone().unwrap()))
+        .collect();
+    assert_eq!(
+        footer,
+        [
+            ("qgrs.min_score".to_string(), "17".to_string()),
+            (
+                COORDINATES_METADATA_KEY.to_string(),
+                "0-based-half-open".to_string()
+            ),
+        ]
+    );
+    let records = parse_parquet_records(fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(records, [G4Record::from(&results[0]).in_coordinates(zero)]);
+    write_parquet_family_ranges_with_coordinates(&ranges, zero, fs::File::create(&path).unwrap())
+        .unwrap();
+    let builder = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&path).unwrap()).unwrap();
+    let batch = builder.build().unwrap().next().unwrap().unwrap();
+    let starts = batch.column_by_name("start").unwrap();
+    let starts = starts
+        .as_any()
+        .downcast_ref::<arrow_array::UInt64Array>()
+        .unwrap();
+    assert_eq!(starts.value(0), 2);
+    let _ = fs::remove_file(&path);
+
+    let mut bytes = Vec::new();
+    write_arrow_ipc_results_with_coordinates("chr1", &results, &[], zero, &mut bytes).unwrap();
+    let reader = FileReader::try_new(std::io::Cursor::new(bytes), None).unwrap();
+    assert_eq!(
+        reader.schema().metadata()[COORDINATES_METADATA_KEY],
+        "0-based-half-open"
+    );
+    let batch = reader.into_iter().next().unwrap().unwrap();
+    let starts = batch.column_by_name("start").unwrap();
+    let starts = starts
+        .as_any()
+        .downcast_ref::<arrow_array::UInt64Array>()
+        .unwrap();
+    assert_eq!(starts.value(0), 2);
+
+    // BED is 0-based half-open by definition, whatever the exports use.
+    let bed = render_family_ranges_bed(
+        "chr1",
+        &ranges,
+        &[FamilyStats {
+            members: 1,
+            best_score: 84,
+        }],
+    );
+    assert_eq!(bed.lines().nth(1), Some("chr1\t2\t21\t1\t84"));
+}
+
 #[test]
 fn load_sequences_stream_mode_splits_chromosomes() {
     let path = env::temp_dir().join("qgrs_stream_input.fa");
@@ -115,6 +736,59 @@ fn load_sequences_mmap_mode_splits_chromosomes() {
     fs::remove_file(&path).unwrap();
 }
 
+#[test]
+fn reverse_complement_fasta_keeps_headers_case_and_iupac_pairs() {
+    let long = "A".repeat(130);
+    let fasta = format!(
+        ";comment\nACGTRYKMBVDHSWN\n>chr1 soft masked\r\nacgtNNGGGa\n;note\nccU\n>\nAAAA\n>long\n{long}\n"
+    );
+    let mut out = Vec::new();
+    let records = write_reverse_complement_from_reader(&mut fasta.as_bytes(), &mut out).unwrap();
+    assert_eq!(records, 4);
+    let t = |count: usize| "T".repeat(count);
+    let expected = format!(
+        ">chromosome_1 rc\nNWSDHBVKMRYACGT\n\
+         >chr1 soft masked rc\nAggtCCCNNacgt\n\
+         >chromosome_3 rc\nTTTT\n\
+         >long rc\n{}\n{}\n{}\n",
+        t(60),
+        t(60),
+        t(10)
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn populated_mmap_loads_the_same_records_and_reports_its_hints() {
+    let path = env::temp_dir().join("qgrs_mmap_populate.fa");
+    let gz_path = env::temp_dir().join("qgrs_mmap_populate.fa.gz");
+    let fasta = b">chr1\nGGGG\nAC\n>chrE\n>chrX\nCCCC\n";
+    fs::write(&path, fasta).unwrap();
+    write_gzip(&gz_path, fasta);
+    let options = LoadOptions::new(true);
+    let populated = options.with_populate(true);
+    let plain = load_sequences_from_path_with_options(&path, InputMode::Mmap, options).unwrap();
+    let loaded = load_sequences_from_path_with_options(&path, InputMode::Mmap, populated).unwrap();
+    assert_eq!(loaded.len(), 3);
+    for (lhs, rhs) in plain.iter().zip(&loaded) {
+        assert_eq!(lhs.name(), rhs.name());
+        assert_eq!(lhs.as_uppercase_string(), rhs.as_uppercase_string());
+    }
+
+    let hints = mmap_hints(&path, populated).unwrap();
+    if cfg!(target_os = "linux") {
+        assert_eq!(hints, ["MADV_SEQUENTIAL", "MADV_WILLNEED", "MAP_POPULATE"]);
+        assert_eq!(
+            mmap_hints(&path, options).unwrap(),
+            ["MADV_SEQUENTIAL", "MADV_WILLNEED"]
+        );
+    }
+    // Compressed input is decoded, not mapped.
+    assert!(mmap_hints(&gz_path, populated).unwrap().is_empty());
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&gz_path).unwrap();
+}
+
 #[test]
 fn load_sequences_stream_mode_reads_gzip_fasta() {
     let path = env::temp_dir().join("qgrs_stream_input.magic");
@@ -148,66 +822,368 @@ fn load_sequences_mmap_mode_reads_gzip_fasta_matching_plain() {
     fs::remove_file(&gz_path).unwrap();
 }
 
-fn longest_target_run(sequence: &[u8], target: u8) -> usize {
-    let target = target.to_ascii_uppercase();
-    let mut longest = 0usize;
-    let mut current = 0usize;
-    for byte in sequence {
-        if byte.to_ascii_uppercase() == target {
-            current += 1;
-            longest = longest.max(current);
-        } else {
-            current = 0;
+#[test]
+fn zstd_fasta_loads_like_plain_in_both_modes() {
+    let plain_path = env::temp_dir().join("qgrs_zstd_input.fa");
+    let zstd_path = env::temp_dir().join("qgrs_zstd_input.fa.zst");
+    let fasta: &[u8] =
+        b">chr1 description\r\nGGGGAGGGGAGGGGAGGGG\r\nAC\r\n>chrX\nCCCCTCCCCTCCCCTCCCC\n";
+    fs::write(&plain_path, fasta).unwrap();
+    // Two frames, as `zstd` produces when files are concatenated.
+    let (head, tail) = fasta.split_at(30);
+    let mut compressed = zstd::encode_all(head, 3).unwrap();
+    compressed.extend(zstd::encode_all(tail, 3).unwrap());
+    fs::write(&zstd_path, compressed).unwrap();
+
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let plain = load_sequences_from_path(&plain_path, mode).unwrap();
+        let zstd = load_sequences_from_path(&zstd_path, mode).unwrap();
+        assert_eq!(zstd.len(), 2, "mode {mode:?}");
+        for (lhs, rhs) in plain.iter().zip(zstd.iter()) {
+            assert_eq!(lhs.name(), rhs.name(), "mode {mode:?}");
+            assert_eq!(
+                lhs.as_uppercase_string(),
+                rhs.as_uppercase_string(),
+                "mode {mode:?}"
+            );
         }
     }
-    longest
+
+    fs::remove_file(&plain_path).unwrap();
+    fs::remove_file(&zstd_path).unwrap();
 }
 
 #[test]
-fn circular_mode_finds_wraparound_hit_when_linear_does_not() {
-    let sequence = "GAGGGGAGGGGAGGGGGGG";
-    let arc = arc_from_sequence(sequence);
-    let limits = ScanLimits::default();
+fn empty_records_are_dropped_by_default_and_kept_on_request() {
+    let path = env::temp_dir().join("qgrs_empty_records.fa");
+    fs::write(&path, b">chrM\n>chr1\nGGGG\n>chrE\n>chr2\nACGT\n>chrZ\n").unwrap();
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let dropped = load_sequences_from_path(&path, mode).unwrap();
+        let names: Vec<_> = dropped.iter().map(|chrom| chrom.name()).collect();
+        assert_eq!(names, ["chr1", "chr2"], "mode {mode:?}");
 
-    let linear_raw =
-        find_owned_bytes_with_topology(arc.clone(), 4, 17, limits, SequenceTopology::Linear);
-    let (linear_hits, _ranges) =
-        consolidate_g4s_with_topology(linear_raw, SequenceTopology::Linear, sequence.len());
-    assert!(linear_hits.is_empty());
+        let kept =
+            load_sequences_from_path_with_options(&path, mode, LoadOptions::new(true)).unwrap();
+        let names: Vec<_> = kept.iter().map(|chrom| chrom.name()).collect();
+        assert_eq!(
+            names,
+            ["chrM", "chr1", "chrE", "chr2", "chrZ"],
+            "mode {mode:?}"
+        );
+        let lengths: Vec<_> = kept.iter().map(|chrom| chrom.sequence().len()).collect();
+        assert_eq!(lengths, [0, 4, 0, 4, 0], "mode {mode:?}");
+    }
+    fs::remove_file(&path).unwrap();
+}
 
-    let circular_raw =
-        find_owned_bytes_with_topology(arc, 4, 17, limits, SequenceTopology::Circular);
-    let (circular_hits, family_ranges) =
-        consolidate_g4s_with_topology(circular_raw, SequenceTopology::Circular, sequence.len());
-    assert_eq!(circular_hits.len(), 1);
-    assert_eq!(family_ranges.len(), 1);
+#[test]
+fn record_headers_match_loaded_sequences() {
+    let path = env::temp_dir().join("qgrs_record_headers.fa");
+    fs::write(
+        &path,
+        b">chr1 desc\nGGGG\nAC GT\n>chrE\n>chr1\nACGT\n>\nGG\n",
+    )
+    .unwrap();
+    for keep_empty in [false, true] {
+        let options = LoadOptions::new(keep_empty);
+        let loaded =
+            load_sequences_from_path_with_options(&path, InputMode::Mmap, options).unwrap();
+        let headers = read_record_headers(&path, options).unwrap();
+        let expected: Vec<_> = loaded
+            .iter()
+            .map(|chrom| (chrom.name(), chrom.occurrence(), chrom.sequence().len()))
+            .collect();
+        let actual: Vec<_> = headers
+            .iter()
+            .map(|header| (header.name.as_str(), header.occurrence, header.sequence_len))
+            .collect();
+        assert_eq!(actual, expected, "keep_empty {keep_empty}");
+    }
+    fs::remove_file(&path).unwrap();
+}
 
-    let hit = &circular_hits[0];
-    assert!(hit.start > 1);
-    assert!(hit.end > sequence.len());
-    assert_eq!(hit.sequence(), "GGGGAGGGGAGGGGAGGGG");
+#[test]
+fn name_filter_drops_records_in_every_loader() {
+    let path = env::temp_dir().join("qgrs_name_filter.fa");
+    // The headerless leading record is named `chromosome_1` and filtered too.
+    fs::write(
+        &path,
+        b"GGGG\n>chr1\nGGGG\nACGT\n>chr1_alt\nTTTT\n>chrUn\nCC\n>chr1\nAC\n",
+    )
+    .unwrap();
+    let keep = |name: &str| name == "chr1";
+    let expected = vec![("chr1".to_string(), 0, 8), ("chr1".to_string(), 1, 2)];
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let loaded =
+            load_sequences_from_path_with_filter(&path, mode, LoadOptions::default(), keep)
+                .unwrap();
+        assert_eq!(tag_records(&loaded), expected, "{mode:?}");
+    }
+    let headers: Vec<_> = read_record_headers_with_filter(&path, LoadOptions::default(), keep)
+        .unwrap()
+        .into_iter()
+        .map(|header| (header.name, header.occurrence, header.sequence_len))
+        .collect();
+    assert_eq!(headers, expected);
+    fs::remove_file(&path).unwrap();
 }
 
 #[test]
-fn circular_consolidation_merges_wraparound_family() {
-    let sequence = "GAGGGGAGGGGAGGGGGGG";
-    let limits = ScanLimits::default();
-    let raw = find_owned_bytes_with_topology(
-        arc_from_sequence(sequence),
-        4,
-        17,
-        limits,
-        SequenceTopology::Circular,
-    );
-    assert!(raw.len() > 1);
-    let wrap_count = raw.iter().filter(|g4| g4.end > sequence.len()).count();
-    assert!(wrap_count >= 2);
+fn header_descriptions_are_kept_apart_from_names() {
+    let path = env::temp_dir().join("qgrs_header_descriptions.fa");
+    let data = b">NC_000913.3 Escherichia coli str. K-12\r\nGGGG\n>chr2\nACGT\n\
+                 >chr3\tassembled\tmolecule  \nGG\n> \nCC\n";
+    fs::write(&path, data).unwrap();
+    let expected = vec![
+        ("NC_000913.3", Some("Escherichia coli str. K-12")),
+        ("chr2", None),
+        ("chr3", Some("assembled\tmolecule")),
+        ("chromosome_4", None),
+    ];
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let loaded =
+            load_sequences_from_path_with_options(&path, mode, LoadOptions::default()).unwrap();
+        let actual: Vec<_> = loaded
+            .iter()
+            .map(|chrom| (chrom.name(), chrom.description()))
+            .collect();
+        assert_eq!(actual, expected, "{mode:?}");
+        let results = search_chromosome(&loaded[0], ScanConfig::default(), false);
+        assert_eq!(results.description.as_deref(), expected[0].1, "{mode:?}");
+    }
+    let mut scanned = Vec::new();
+    stream::process_reader_with_config(
+        data.as_slice(),
+        ScanConfig::default(),
+        false,
+        &mut |results| {
+            scanned.push((results.name, results.description));
+            Ok(())
+        },
+    )
+    .unwrap();
+    let scanned: Vec<_> = scanned
+        .iter()
+        .map(|(name, description)| (name.as_str(), description.as_deref()))
+        .collect();
+    assert_eq!(scanned, expected);
+    fs::remove_file(&path).unwrap();
+}
 
-    let (hits, ranges) =
-        consolidate_g4s_with_topology(raw, SequenceTopology::Circular, sequence.len());
-    assert_eq!(hits.len(), 1);
-    assert_eq!(ranges.len(), 1);
-    assert!(ranges[0].1 > sequence.len());
+type RecordTag = (String, usize, usize);
+
+fn tag_records(sequences: &[ChromSequence]) -> Vec<RecordTag> {
+    sequences
+        .iter()
+        .map(|chrom| {
+            let name = chrom.name().to_string();
+            (name, chrom.occurrence(), chrom.sequence().len())
+        })
+        .collect()
+}
+
+#[test]
+fn fuzz_corpus_parses_the_same_in_every_reader() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/fuzz_fasta_parse");
+    for entry in fs::read_dir(&corpus).unwrap() {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        let mut scanned = Vec::new();
+        stream::process_reader_with_config(
+            data.as_slice(),
+            ScanConfig::default(),
+            false,
+            &mut |results| {
+                if results.sequence_len > 0 {
+                    scanned.push((results.name, results.occurrence, results.sequence_len));
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        let loaded =
+            load_sequences_from_reader(&mut data.as_slice(), LoadOptions::default()).unwrap();
+        assert_eq!(tag_records(&loaded), scanned, "{path:?} reader");
+        let mapped = load_sequences_from_path(&path, InputMode::Mmap).unwrap();
+        assert_eq!(tag_records(&mapped), scanned, "{path:?} mmap");
+        let headers: Vec<_> = read_record_headers(&path, LoadOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|header| (header.name, header.occurrence, header.sequence_len))
+            .collect();
+        assert_eq!(headers, scanned, "{path:?} headers");
+    }
+}
+
+#[test]
+fn sequence_before_first_header_is_its_own_record() {
+    let data = b"\xef\xbb\xbfGGGAGGGAGGGAGGG\n>chr1\nACGT\n>\nGG\n";
+    let loaded = load_sequences_from_reader(&mut &data[..], LoadOptions::default()).unwrap();
+    let expected = [
+        ("chromosome_1".to_string(), 0, 15),
+        ("chr1".to_string(), 0, 4),
+        ("chromosome_3".to_string(), 0, 2),
+    ];
+    assert_eq!(tag_records(&loaded), expected);
+}
+
+#[test]
+fn comment_lines_are_skipped_in_every_reader() {
+    let commented = b";file comment\n>chr1 desc\n;Pearson comment\nGGGAGGGA\n  ; indented\r\n\
+GGGAGGG\n;\n>chr2\nACGTGGGTTGGG\n\t;tab\nTTGGGTTGGG\n";
+    let stripped = b">chr1 desc\nGGGAGGGA\nGGGAGGG\n>chr2\nACGTGGGTTGGG\nTTGGGTTGGG\n";
+    let dir = env::temp_dir();
+    let mut loaded = Vec::new();
+    for (label, data) in [("commented", &commented[..]), ("stripped", &stripped[..])] {
+        let path = dir.join(format!("qgrs_comment_lines_{label}.fa"));
+        fs::write(&path, data).unwrap();
+        let mut scanned = Vec::new();
+        stream::process_reader_with_config(data, ScanConfig::default(), false, &mut |results| {
+            let hits: Vec<_> = results.hits.iter().map(|g4| (g4.start, g4.end)).collect();
+            scanned.push((results.name, hits, results.stats.comment_lines));
+            Ok(())
+        })
+        .unwrap();
+        let mut searched = Vec::new();
+        for mode in [InputMode::Mmap, InputMode::Stream] {
+            let sequences = load_sequences_from_path(&path, mode).unwrap();
+            searched = sequences
+                .iter()
+                .map(|chrom| {
+                    let results = search_chromosome(chrom, ScanConfig::default(), false);
+                    let hits: Vec<_> = results.hits.iter().map(|g4| (g4.start, g4.end)).collect();
+                    (results.name, hits, results.stats.comment_lines)
+                })
+                .collect();
+            assert_eq!(searched, scanned, "{label} {mode:?}");
+        }
+        let lengths: Vec<_> = read_record_headers(&path, LoadOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|header| header.sequence_len)
+            .collect();
+        assert_eq!(lengths, [15, 22], "{label}");
+        loaded.push(searched);
+        fs::remove_file(&path).unwrap();
+    }
+    let counts: Vec<_> = loaded[0].iter().map(|record| record.2).collect();
+    assert_eq!(counts, [3, 1]);
+    for record in &mut loaded[0] {
+        record.2 = 0;
+    }
+    assert_eq!(loaded[0], loaded[1]);
+    assert!(!loaded[1][1].1.is_empty());
+}
+
+#[test]
+fn indented_headers_and_blank_lines_split_records_in_every_reader() {
+    let edited = b"\n\n>chr1 desc\nGGGAGGGA\n\n\nGGGAGGG\n  >chr2\nACGTGGGTTGGG\n \t \n\t>chr3\r\n\
+TTGGGTTGGGTTGGGTTGGG\n\n\n \t>  chr4 tail\nGG\n\n";
+    let tidy = b">chr1 desc\nGGGAGGGAGGGAGGG\n>chr2\nACGTGGGTTGGG\n>chr3\nTTGGGTTGGGTTGGGTTGGG\n>chr4 tail\nGG\n";
+    let dir = env::temp_dir();
+    let mut splits = Vec::new();
+    for (label, data) in [("edited", &edited[..]), ("tidy", &tidy[..])] {
+        let path = dir.join(format!("qgrs_indented_headers_{label}.fa"));
+        fs::write(&path, data).unwrap();
+        let mut scanned = Vec::new();
+        stream::process_reader_with_config(data, ScanConfig::default(), false, &mut |results| {
+            let hits: Vec<_> = results.hits.iter().map(|g4| (g4.start, g4.end)).collect();
+            scanned.push((
+                results.name,
+                results.description,
+                results.sequence_len,
+                hits,
+            ));
+            Ok(())
+        })
+        .unwrap();
+        for mode in [InputMode::Mmap, InputMode::Stream] {
+            let loaded: Vec<_> = load_sequences_from_path(&path, mode)
+                .unwrap()
+                .iter()
+                .map(|chrom| {
+                    let results = search_chromosome(chrom, ScanConfig::default(), false);
+                    let hits: Vec<_> = results.hits.iter().map(|g4| (g4.start, g4.end)).collect();
+                    (
+                        results.name,
+                        results.description,
+                        results.sequence_len,
+                        hits,
+                    )
+                })
+                .collect();
+            assert_eq!(loaded, scanned, "{label} {mode:?}");
+        }
+        let headers: Vec<_> = read_record_headers(&path, LoadOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|header| (header.name, header.sequence_len))
+            .collect();
+        let expected = [("chr1", 15), ("chr2", 12), ("chr3", 20), ("chr4", 2)];
+        assert_eq!(
+            headers,
+            expected.map(|(name, len)| (name.to_string(), len)),
+            "{label}"
+        );
+        let descriptions: Vec<_> = scanned.iter().map(|record| record.1.as_deref()).collect();
+        assert_eq!(
+            descriptions,
+            [Some("desc"), None, None, Some("tail")],
+            "{label}"
+        );
+        splits.push(scanned);
+        fs::remove_file(&path).unwrap();
+    }
+    assert_eq!(splits[0], splits[1]);
+    assert!(!splits[0][2].3.is_empty());
+}
+
+#[test]
+fn invalid_utf8_reads_the_same_in_both_modes() {
+    // 0xFF in a loop and a Latin-1 byte in the header.
+    let data = b">chr\xe91 desc\nGGGAGGG\xffGGGAGGG\n>chr2\nGG\xfe\n";
+    let path = env::temp_dir().join("qgrs_invalid_utf8.fa");
+    fs::write(&path, data).unwrap();
+    let mut results = Vec::new();
+    for mode in [InputMode::Mmap, InputMode::Stream] {
+        let records: Vec<_> = load_sequences_from_path(&path, mode)
+            .unwrap()
+            .iter()
+            .map(|chrom| {
+                let hits = search_chromosome(chrom, ScanConfig::default(), false).hits;
+                let hits: Vec<_> = hits
+                    .iter()
+                    .map(|g4| (g4.start, g4.sequence().to_string()))
+                    .collect();
+                (chrom.name().to_string(), chrom.as_uppercase_string(), hits)
+            })
+            .collect();
+        results.push(records);
+    }
+    let mut scanned = Vec::new();
+    stream::process_reader_with_config(&data[..], ScanConfig::default(), false, &mut |results| {
+        let hits: Vec<_> = results
+            .hits
+            .iter()
+            .map(|g4| (g4.start, g4.sequence().to_string()))
+            .collect();
+        scanned.push((results.name, hits));
+        Ok(())
+    })
+    .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(results[0], results[1]);
+    let chr1 = &results[0][0];
+    assert_eq!(chr1.0, "chr\u{fffd}1");
+    assert_eq!(chr1.1, "GGGAGGGNGGGAGGG");
+    assert_eq!(chr1.2, [(1, "GGGAGGGNGGGAGGG".to_string())]);
+    assert_eq!(results[0][1].1, "GGN");
+    let loaded: Vec<_> = results[0]
+        .iter()
+        .map(|(name, _, hits)| (name.clone(), hits.clone()))
+        .collect();
+    assert_eq!(scanned, loaded);
 }
 
 #[test]
@@ -0,0 +1,455 @@
+use crate::qgrs::{
+    MinScore, MotifType, QgrsError, QuartetBase, ScanConfig, ScanLimits, SequenceTopology,
+    consolidate_g4s, consolidate_g4s_with_topology, find_in_bytes, find_in_str, find_owned,
+    find_owned_bytes, find_owned_bytes_with_config, find_owned_bytes_with_topology,
+    find_owned_bytes_with_topology_and_base, max_possible_score, scan_many, scan_short,
+};
+
+use crate::qgrs::gruns::{GRun, scan_bytes, scan_bytes_with_base};
+use crate::qgrs::search::{gscore, gscore_to_i32};
+
+use super::helpers::{arc_from_sequence, g4_signatures};
+
+#[test]
+fn finds_single_g4() {
+    let sequence = "GGGGAGGGGAGGGGAGGGG";
+    let raw = find_in_str(sequence, 4, 17);
+    let (results, _ranges) = consolidate_g4s(raw);
+    assert_eq!(results.len(), 1);
+    let g = &results[0];
+    assert_eq!(g.start, 1);
+    assert_eq!(g.tetrads, 4);
+    assert_eq!(g.y1, 1);
+    assert_eq!(g.y2, 1);
+    assert_eq!(g.y3, 1);
+    assert_eq!(g.sequence(), sequence);
+}
+
+#[test]
+fn borrowed_and_owned_entry_points_match_the_shared_one() {
+    let sequence = "ttGGGGaGGGGTTggggAGGGGccGGGTGGGTGGGTGGG";
+    let expected = g4_signatures(&find_owned_bytes(arc_from_sequence(sequence), 2, 17));
+    assert!(!expected.is_empty());
+    assert_eq!(g4_signatures(&find_in_str(sequence, 2, 17)), expected);
+    assert_eq!(
+        g4_signatures(&find_in_bytes(sequence.as_bytes(), 2, 17)),
+        expected
+    );
+    assert_eq!(
+        g4_signatures(&find_owned(sequence.as_bytes().to_vec(), 2, 17)),
+        expected
+    );
+}
+
+#[test]
+fn finds_single_imotif() {
+    let sequence = "CCCCACCCCACCCCACCCC";
+    let raw = find_owned_bytes_with_topology_and_base(
+        arc_from_sequence(sequence),
+        4,
+        17,
+        ScanLimits::default(),
+        SequenceTopology::Linear,
+        QuartetBase::C,
+    );
+    let (results, _ranges) = consolidate_g4s(raw);
+    assert_eq!(results.len(), 1);
+    let c = &results[0];
+    assert_eq!(c.start, 1);
+    assert_eq!(c.tetrads, 4);
+    assert_eq!((c.y1, c.y2, c.y3), (1, 1, 1));
+    assert_eq!(c.sequence(), sequence);
+    assert_eq!(MotifType::from(QuartetBase::C), MotifType::IMotif);
+}
+
+#[test]
+fn empty_sequence_has_no_hits() {
+    let raw = find_in_str("ACACAC", 4, 17);
+    let (results, _ranges) = consolidate_g4s(raw);
+    assert!(results.is_empty());
+}
+
+#[test]
+fn auto_min_score_scales_with_tetrads_under_default_limits() {
+    let limits = ScanLimits::default();
+    let thresholds: Vec<i32> = (2..=5).map(|t| limits.auto_min_score(t)).collect();
+    assert_eq!(thresholds, [17, 52, 68, 77]);
+    assert_eq!(limits.max_tetrads(), 10);
+    assert_eq!(limits.max_score(3), 64);
+    assert_eq!(MinScore::Auto.for_tetrads(3, limits), 52);
+    assert_eq!(MinScore::Fixed(17).for_tetrads(3, limits), 17);
+
+    let config = ScanConfig::new(3, 0, limits, SequenceTopology::Linear, QuartetBase::G)
+        .with_min_score(MinScore::Auto);
+    assert_eq!(config.min_scores()[..2], [(3, 52), (4, 68)]);
+    assert!(
+        config
+            .min_score_label()
+            .starts_with("auto (3=52, 4=68, 5=77, ")
+    );
+
+    // One long loop leaves this 3-tetrad hit at 51: enough for a fixed 17,
+    // one short of the auto threshold.
+    let sequence = format!("GGGAGGG{}GGGAGGG", "T".repeat(20));
+    let find = |min_score| {
+        let config = ScanConfig::new(2, 17, limits, SequenceTopology::Linear, QuartetBase::G)
+            .with_min_score(min_score);
+        find_owned_bytes_with_config(arc_from_sequence(&sequence), config)
+    };
+    let fixed = find(MinScore::Fixed(17));
+    assert!(fixed.iter().any(|g4| g4.tetrads == 3 && g4.score == 51));
+    assert!(find(MinScore::Auto).is_empty());
+}
+
+#[test]
+fn gscore_matches_known_scores_and_never_wraps() {
+    assert_eq!(gscore(2, 30, [1, 1, 1]), 21);
+    assert_eq!(gscore(4, 45, [1, 1, 1]), 84);
+    assert_eq!(gscore(3, 45, [1, 20, 1]), 51);
+    // gavg = 2/3 rounds the score down, as floor() did.
+    assert_eq!(gscore(3, 45, [1, 2, 1]), 63);
+
+    // Tetrad counts past i32 used to wrap the old i32 arithmetic.
+    let huge = gscore(1 << 40, 45, [1, 1, 1]);
+    assert!(huge < i64::from(i32::MIN), "{huge}");
+    assert_eq!(
+        gscore(usize::MAX, usize::MAX, [0, 1, i32::MAX]),
+        -1_431_655_765
+    );
+    assert_eq!(gscore(usize::MAX, 0, [1, 1, 1]), i64::MIN);
+    assert_eq!(gscore_to_i32(84), 84);
+    assert_eq!(gscore_to_i32(huge), i32::MIN);
+    assert_eq!(gscore_to_i32(i64::MAX), i32::MAX);
+}
+
+#[test]
+fn max_possible_score_is_the_equal_loop_gscore_of_the_longest_motif() {
+    let limits = ScanLimits::default();
+    let scores: Vec<i64> = (2..=6).map(|t| max_possible_score(t, limits)).collect();
+    assert_eq!(scores, [21, 64, 84, 96, 100]);
+    assert_eq!(max_possible_score(3, limits), limits.max_score(3));
+    // Shorter motifs shrink the loop budget: 20 bp leaves 7 for 3 tetrads.
+    assert_eq!(
+        max_possible_score(
+            3,
+            ScanLimits {
+                max_g4_length: 20,
+                ..limits
+            }
+        ),
+        14
+    );
+    assert_eq!(
+        max_possible_score(
+            5,
+            ScanLimits {
+                max_g4_length: 20,
+                ..limits
+            }
+        ),
+        -4
+    );
+    assert_eq!(max_possible_score(usize::MAX, limits), i64::MIN);
+
+    // The scan-wide bound only counts tetrad counts that fit the limits.
+    let config = ScanConfig::default();
+    assert_eq!(config.max_possible_score(), Some(100));
+    let short = ScanConfig::new(
+        2,
+        17,
+        ScanLimits {
+            max_g4_length: 20,
+            ..limits
+        },
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    assert_eq!(short.max_possible_score(), Some(14));
+    let tight = ScanConfig::new(
+        5,
+        17,
+        ScanLimits {
+            max_g4_length: 20,
+            ..limits
+        },
+        SequenceTopology::Linear,
+        QuartetBase::G,
+    );
+    assert_eq!(tight.max_possible_score(), None);
+
+    // Every consolidated hit stays within the bound.
+    let raw = find_in_str("GGGGAGGGGAGGGGAGGGGTTGGGTGGGAGGGTGGG", 2, 17);
+    let (hits, _) = consolidate_g4s(raw);
+    assert!(!hits.is_empty());
+    assert!(
+        hits.iter()
+            .all(|g4| i64::from(g4.score) <= max_possible_score(g4.tetrads, limits))
+    );
+}
+
+#[test]
+fn check_satisfiable_rejects_thresholds_no_hit_can_reach() {
+    let config = |min_tetrads, min_score, limits| {
+        ScanConfig::new(
+            min_tetrads,
+            min_score,
+            limits,
+            SequenceTopology::Linear,
+            QuartetBase::G,
+        )
+    };
+    let unsatisfiable = |min_score, best_score| {
+        Err(QgrsError::Unsatisfiable {
+            min_score,
+            best_score,
+        })
+    };
+    let defaults = ScanLimits::default();
+    // 6 tetrads in 45 bp score at most 100.
+    assert_eq!(
+        config(2, 101, defaults).check_satisfiable(),
+        unsatisfiable(101, 100)
+    );
+    assert_eq!(
+        config(7, 97, defaults).check_satisfiable(),
+        unsatisfiable(97, 96)
+    );
+    // Runs of two allow only 2-tetrad hits, here in at most 20 bp.
+    let short = ScanLimits::new(20, 2);
+    assert_eq!(
+        config(2, 17, short).check_satisfiable(),
+        unsatisfiable(17, 11)
+    );
+    // Limits that fail validation report that instead.
+    assert_eq!(
+        config(2, 0, ScanLimits::new(9, 10)).check_satisfiable(),
+        Err(QgrsError::MaxLengthTooShort {
+            max_length: 9,
+            min_tetrads: 2,
+            required: 10,
+        })
+    );
+
+    assert_eq!(config(2, 100, defaults).check_satisfiable(), Ok(()));
+    assert_eq!(config(2, 11, short).check_satisfiable(), Ok(()));
+    let auto = config(2, 0, defaults).with_min_score(MinScore::Auto);
+    assert_eq!(auto.check_satisfiable(), Ok(()));
+}
+
+#[test]
+fn opposite_strand_flips_the_base_and_complements_the_loop_alphabet() {
+    let pyrimidines = ScanLimits::default().with_loop_alphabet(Some([false, true, false, true]));
+    let config = ScanConfig::new(2, 17, pyrimidines, SequenceTopology::Linear, QuartetBase::G);
+    let opposite = config.opposite_strand();
+    assert_eq!(opposite.target_base(), QuartetBase::C);
+    // Pyrimidine loops on the minus strand are purines on the plus strand.
+    assert_eq!(
+        opposite.limits().loop_alphabet,
+        Some([true, false, true, false])
+    );
+    assert_eq!(opposite.opposite_strand().limits(), config.limits());
+}
+
+#[test]
+fn target_base_loops_do_not_exceed_max_run() {
+    let raw = find_owned_bytes_with_topology_and_base(
+        arc_from_sequence("CCCCCCCCCCCCCCCC"),
+        2,
+        17,
+        ScanLimits::new(45, 10),
+        SequenceTopology::Linear,
+        QuartetBase::C,
+    );
+    assert!(!raw.is_empty());
+    assert!(
+        raw.iter()
+            .all(|g4| longest_target_run(g4.sequence().as_bytes(), b'C') <= 10)
+    );
+}
+
+#[test]
+fn g4_interval_helpers_use_one_based_inclusive_coordinates() {
+    let raw = find_in_str("GGGGAGGGGAGGGGAGGGG", 4, 17);
+    let at = |start: usize, end: usize| {
+        let mut g4 = raw[0].clone();
+        g4.start = start;
+        g4.end = end;
+        g4.length = end - start + 1;
+        g4
+    };
+    let hit = at(10, 20);
+    assert_eq!(hit.interval(), (10, 20));
+
+    // (other, overlaps, hit contains other, distance)
+    let cases = [
+        (at(10, 20), true, true, 0),   // identical
+        (at(12, 18), true, true, 0),   // nested
+        (at(10, 15), true, true, 0),   // shared start
+        (at(5, 25), true, false, 0),   // enclosing
+        (at(20, 30), true, false, 0),  // one shared base at the end
+        (at(1, 10), true, false, 0),   // one shared base at the start
+        (at(21, 30), false, false, 1), // touching after
+        (at(1, 9), false, false, 1),   // touching before
+        (at(25, 30), false, false, 5), // disjoint after
+        (at(1, 4), false, false, 6),   // disjoint before
+    ];
+    for (other, overlaps, contains, distance) in cases {
+        let label = format!("{:?}", other.interval());
+        assert_eq!(hit.overlaps(&other), overlaps, "{label}");
+        assert_eq!(other.overlaps(&hit), overlaps, "{label}");
+        assert_eq!(hit.contains(&other), contains, "{label}");
+        assert_eq!(hit.distance_to(&other), distance, "{label}");
+        assert_eq!(other.distance_to(&hit), distance, "{label}");
+    }
+    assert!(at(5, 25).contains(&hit));
+}
+
+#[test]
+// `G4`'s caches are interior-mutable but never hashed.
+#[allow(clippy::mutable_key_type)]
+fn g4_identity_ignores_sequence_caches() {
+    use std::collections::HashSet;
+
+    let sequence = "GGGGAGGGGAGGGGAGGGG";
+    let raw = find_in_str(sequence, 2, 17);
+    let (results, _ranges) = consolidate_g4s(raw.clone());
+    let cached = results[0].clone();
+    let _ = cached.sequence();
+    let fresh = results[0].clone();
+    let mut detached = results[0].clone();
+    detached.detach();
+    assert!(cached.sequence_is_cached() && !fresh.sequence_is_cached());
+    assert_eq!(cached, fresh);
+    assert_eq!(cached, detached);
+    let unique: HashSet<_> = [cached.clone(), fresh, detached].into_iter().collect();
+    assert_eq!(unique.len(), 1);
+
+    let mut weaker = cached.clone();
+    weaker.score -= 1;
+    assert_ne!(weaker, cached);
+    let mut other_loops = cached.clone();
+    other_loops.y1 += 1;
+    assert_ne!(other_loops, cached);
+
+    // Same span: higher score sorts first; raw hits come out in start order.
+    let mut sorted = vec![weaker.clone(), cached.clone()];
+    sorted.sort();
+    assert_eq!(sorted, [cached, weaker]);
+    let mut shuffled = raw.clone();
+    shuffled.reverse();
+    shuffled.sort();
+    assert!(
+        shuffled
+            .windows(2)
+            .all(|pair| pair[0].start <= pair[1].start)
+    );
+    assert_eq!(shuffled.len(), raw.iter().collect::<HashSet<_>>().len());
+}
+
+fn longest_target_run(sequence: &[u8], target: u8) -> usize {
+    let target = target.to_ascii_uppercase();
+    let mut longest = 0usize;
+    let mut current = 0usize;
+    for byte in sequence {
+        if byte.to_ascii_uppercase() == target {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+#[test]
+fn preserve_case_keeps_soft_masked_bases_in_the_sequence() {
+    let sequence = b"ttGGGGaGGGGaggggAGGGGtt";
+    let motif = "GGGGaGGGGaggggAGGGG";
+    for topology in [SequenceTopology::Linear, SequenceTopology::Circular] {
+        let config = ScanConfig::new(2, 17, ScanLimits::default(), topology, QuartetBase::G);
+        let default = scan_short(sequence, config);
+        let preserved = scan_short(sequence, config.with_preserve_case(true));
+        assert_eq!(default.len(), 1, "{topology:?}");
+        assert_eq!(
+            default[0].sequence,
+            motif.to_ascii_uppercase(),
+            "{topology:?}"
+        );
+        assert_eq!(preserved[0].sequence, motif, "{topology:?}");
+        assert_eq!(preserved[0].score, default[0].score, "{topology:?}");
+    }
+
+    let records = || vec![("a".to_string(), sequence.to_vec())];
+    let default = scan_many(records(), ScanConfig::default());
+    let preserved = scan_many(records(), ScanConfig::default().with_preserve_case(true));
+    assert_eq!(default[0].hits[0].sequence(), motif.to_ascii_uppercase());
+    assert!(!default[0].hits[0].preserves_case());
+    assert_eq!(preserved[0].hits[0].sequence(), motif);
+    assert_eq!(preserved[0].hits[0].to_record().sequence, motif);
+}
+
+#[test]
+fn circular_mode_finds_wraparound_hit_when_linear_does_not() {
+    let sequence = "GAGGGGAGGGGAGGGGGGG";
+    let arc = arc_from_sequence(sequence);
+    let limits = ScanLimits::default();
+
+    let linear_raw =
+        find_owned_bytes_with_topology(arc.clone(), 4, 17, limits, SequenceTopology::Linear);
+    let (linear_hits, _ranges) =
+        consolidate_g4s_with_topology(linear_raw, SequenceTopology::Linear, sequence.len());
+    assert!(linear_hits.is_empty());
+
+    let circular_raw =
+        find_owned_bytes_with_topology(arc, 4, 17, limits, SequenceTopology::Circular);
+    let (circular_hits, family_ranges) =
+        consolidate_g4s_with_topology(circular_raw, SequenceTopology::Circular, sequence.len());
+    assert_eq!(circular_hits.len(), 1);
+    assert_eq!(family_ranges.len(), 1);
+
+    let hit = &circular_hits[0];
+    assert!(hit.start > 1);
+    assert!(hit.end > sequence.len());
+    assert_eq!(hit.sequence(), "GGGGAGGGGAGGGGAGGGG");
+}
+
+#[test]
+fn circular_consolidation_merges_wraparound_family() {
+    let sequence = "GAGGGGAGGGGAGGGGGGG";
+    let limits = ScanLimits::default();
+    let raw = find_owned_bytes_with_topology(
+        arc_from_sequence(sequence),
+        4,
+        17,
+        limits,
+        SequenceTopology::Circular,
+    );
+    assert!(raw.len() > 1);
+    let wrap_count = raw.iter().filter(|g4| g4.end > sequence.len()).count();
+    assert!(wrap_count >= 2);
+
+    let (hits, ranges) =
+        consolidate_g4s_with_topology(raw, SequenceTopology::Circular, sequence.len());
+    assert_eq!(hits.len(), 1);
+    assert_eq!(ranges.len(), 1);
+    assert!(ranges[0].1 > sequence.len());
+}
+
+#[test]
+fn grun_scan_handles_case_and_trailing_run() {
+    let runs: Vec<GRun> = scan_bytes(b"ggggAcGGgTTgGGG", 3).collect();
+    assert_eq!(
+        runs,
+        [
+            GRun { start: 0, len: 4 },
+            GRun { start: 6, len: 3 },
+            GRun { start: 11, len: 4 },
+        ]
+    );
+
+    let short: Vec<GRun> = scan_bytes(b"GGAGGAG", 3).collect();
+    assert!(short.is_empty());
+
+    let runs: Vec<GRun> = scan_bytes_with_base(b"ccCGGGccc", 3, QuartetBase::C).collect();
+    assert_eq!(runs, [GRun { start: 0, len: 3 }, GRun { start: 6, len: 3 }]);
+}
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::qgrs::data::ChromSequence;
+use crate::qgrs::input::open_sequence_reader;
+use crate::qgrs::loaders::{RecordHeader, reverse_complement};
+use crate::qgrs::record::Strand;
+
+/// One transcript assembled from the `exon` rows of a GTF file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    pub id: String,
+    pub gene_id: Option<String>,
+    pub chrom: String,
+    pub strand: Strand,
+    /// 1-based inclusive genomic exon intervals, sorted by start and never
+    /// overlapping.
+    pub exons: Vec<(usize, usize)>,
+}
+
+/// Where a stretch of transcript bases lies on the genome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GenomicSpan {
+    /// Lowest genomic base covered, 1-based. On a minus-strand transcript
+    /// this is the stretch's last base in transcript order.
+    pub start: usize,
+    /// Highest genomic base covered, 1-based inclusive.
+    pub end: usize,
+    /// Whether the stretch crosses at least one exon-exon junction, so
+    /// `start..=end` also covers intronic bases that were never scanned.
+    pub spans_junction: bool,
+}
+
+impl Transcript {
+    /// Spliced length: the exon lengths summed.
+    pub fn len(&self) -> usize {
+        self.exons.iter().map(|(start, end)| end - start + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exons.is_empty()
+    }
+
+    /// Exons with their indices in transcript order: ascending on the plus
+    /// strand, descending on the minus strand.
+    fn exons_5_to_3(&self) -> impl Iterator<Item = (usize, (usize, usize))> + '_ {
+        let count = self.exons.len();
+        (0..count).map(move |step| {
+            let index = match self.strand {
+                Strand::Plus => step,
+                Strand::Minus => count - 1 - step,
+            };
+            (index, self.exons[index])
+        })
+    }
+
+    /// The spliced transcript read 5' to 3' from `genome`, the bases of its
+    /// chromosome: exons are joined in genomic order and the result is
+    /// reverse-complemented on the minus strand. `None` when an exon runs
+    /// past the end of `genome`.
+    pub fn splice(&self, genome: &[u8]) -> Option<Vec<u8>> {
+        let mut spliced = Vec::with_capacity(self.len());
+        for &(start, end) in &self.exons {
+            spliced.extend_from_slice(genome.get(start - 1..end)?);
+        }
+        if self.strand == Strand::Minus {
+            reverse_complement(&mut spliced);
+        }
+        Some(spliced)
+    }
+
+    /// Genomic base and exon index of the 1-based transcript position
+    /// `position`; `None` past the transcript's end.
+    fn locate(&self, position: usize) -> Option<(usize, usize)> {
+        let mut offset = position.checked_sub(1)?;
+        for (index, (start, end)) in self.exons_5_to_3() {
+            let len = end - start + 1;
+            if offset < len {
+                let base = match self.strand {
+                    Strand::Plus => start + offset,
+                    Strand::Minus => end - offset,
+                };
+                return Some((base, index));
+            }
+            offset -= len;
+        }
+        None
+    }
+
+    /// Genomic base of the 1-based transcript position `position`.
+    pub fn genomic_position(&self, position: usize) -> Option<usize> {
+        self.locate(position).map(|(base, _)| base)
+    }
+
+    /// Projects the 1-based inclusive transcript interval `start..=end`, e.g.
+    /// a hit's `start` and `end`, onto the genome.
+    pub fn project(&self, start: usize, end: usize) -> Option<GenomicSpan> {
+        let (first, first_exon) = self.locate(start)?;
+        let (last, last_exon) = self.locate(end)?;
+        Some(GenomicSpan {
+            start: first.min(last),
+            end: first.max(last),
+            spans_junction: first_exon != last_exon,
+        })
+    }
+
+    /// `chrom:start-end(strand)` of the transcript's genomic extent.
+    fn locus(&self) -> String {
+        let start = self.exons.first().map_or(0, |exon| exon.0);
+        let end = self.exons.last().map_or(0, |exon| exon.1);
+        format!(
+            "{}:{start}-{end}({})",
+            self.chrom,
+            strand_symbol(self.strand)
+        )
+    }
+}
+
+/// `+` or `-`, as in GTF and BED.
+pub fn strand_symbol(strand: Strand) -> &'static str {
+    match strand {
+        Strand::Plus => "+",
+        Strand::Minus => "-",
+    }
+}
+
+/// Reads the transcripts of a GTF file (optionally gzip-compressed).
+pub fn read_gtf_path(path: &Path) -> io::Result<Vec<Transcript>> {
+    read_gtf_reader(open_sequence_reader(path)?)
+}
+
+/// Groups the `exon` rows of a GTF stream by their `transcript_id`
+/// attribute, in order of each transcript's first exon. Other features and
+/// `#` lines are skipped. Every exon needs a `+` or `-` strand, and the
+/// exons of one transcript must share chromosome and strand and must not
+/// overlap.
+pub fn read_gtf_reader<R: BufRead>(reader: R) -> io::Result<Vec<Transcript>> {
+    let mut transcripts: Vec<Transcript> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("GTF line {}: {reason}", line_index + 1),
+            )
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(invalid("expected 9 tab-separated columns"));
+        }
+        if fields[2] != "exon" {
+            continue;
+        }
+        let start: usize = fields[3]
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&start| start > 0)
+            .ok_or_else(|| invalid("start must be a positive integer"))?;
+        let end: usize = fields[4]
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&end| end >= start)
+            .ok_or_else(|| invalid("end must be an integer no smaller than start"))?;
+        let strand = match fields[6] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => return Err(invalid("exon strand must be '+' or '-'")),
+        };
+        let attributes = parse_attributes(fields[8]);
+        let id = attributes
+            .get("transcript_id")
+            .ok_or_else(|| invalid("exon has no transcript_id"))?;
+        let chrom = fields[0];
+        let index = *by_id.entry(id.to_string()).or_insert_with(|| {
+            transcripts.push(Transcript {
+                id: id.to_string(),
+                gene_id: attributes.get("gene_id").map(|gene| gene.to_string()),
+                chrom: chrom.to_string(),
+                strand,
+                exons: Vec::new(),
+            });
+            transcripts.len() - 1
+        });
+        let transcript = &mut transcripts[index];
+        if transcript.chrom != chrom || transcript.strand != strand {
+            return Err(invalid(&format!(
+                "exon of {id} is on {chrom} {}, earlier ones on {} {}",
+                fields[6],
+                transcript.chrom,
+                strand_symbol(transcript.strand)
+            )));
+        }
+        transcript.exons.push((start, end));
+    }
+    for transcript in &mut transcripts {
+        transcript.exons.sort_unstable();
+        if transcript
+            .exons
+            .windows(2)
+            .any(|pair| pair[1].0 <= pair[0].1)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("GTF transcript {}: exons overlap", transcript.id),
+            ));
+        }
+    }
+    Ok(transcripts)
+}
+
+/// `key "value";` pairs of a GTF attribute column, quotes removed.
+fn parse_attributes(column: &str) -> HashMap<&str, &str> {
+    column
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once(char::is_whitespace))
+        .map(|(key, value)| (key, value.trim().trim_matches('"')))
+        .collect()
+}
+
+/// The records a scan of `transcripts` kept by `keep` would report, as
+/// [`crate::qgrs::read_record_headers`] does for a FASTA file. The index is
+/// the transcript's position in `transcripts`.
+pub fn transcript_headers<K>(transcripts: &[Transcript], keep: K) -> Vec<RecordHeader>
+where
+    K: Fn(&str) -> bool,
+{
+    transcripts
+        .iter()
+        .enumerate()
+        .filter(|(_, transcript)| keep(&transcript.id))
+        .map(|(index, transcript)| RecordHeader {
+            name: transcript.id.clone(),
+            occurrence: 0,
+            sequence_len: transcript.len(),
+            index,
+        })
+        .collect()
+}
+
+/// Splices every transcript kept by `keep` out of `genome` into a record
+/// named by its id and described by its genomic locus, ready for
+/// [`crate::qgrs::search_chromosome`]. Also returns the ids of kept
+/// transcripts left out because `genome` lacks their chromosome or it ends
+/// before their last exon. A chromosome name present more than once is read
+/// from its first record.
+pub fn splice_transcripts<K>(
+    genome: &[ChromSequence],
+    transcripts: &[Transcript],
+    keep: K,
+) -> (Vec<ChromSequence>, Vec<String>)
+where
+    K: Fn(&str) -> bool,
+{
+    let mut chroms: HashMap<&str, &ChromSequence> = HashMap::new();
+    for chrom in genome {
+        chroms.entry(chrom.name()).or_insert(chrom);
+    }
+    let mut spliced = Vec::new();
+    let mut unplaced = Vec::new();
+    for (index, transcript) in transcripts.iter().enumerate() {
+        if !keep(&transcript.id) {
+            continue;
+        }
+        let sequence = chroms
+            .get(transcript.chrom.as_str())
+            .and_then(|chrom| transcript.splice(&chrom.sequence));
+        match sequence {
+            Some(sequence) => spliced.push(ChromSequence {
+                name: transcript.id.clone(),
+                occurrence: 0,
+                description: Some(transcript.locus()),
+                sequence: Arc::new(sequence),
+                comment_lines: 0,
+                index,
+            }),
+            None => unplaced.push(transcript.id.clone()),
+        }
+    }
+    (spliced, unplaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GenomicSpan, Transcript, read_gtf_reader, splice_transcripts};
+    use crate::qgrs::record::Strand;
+    use crate::qgrs::{ScanConfig, load_sequences_from_reader, search_chromosome};
+
+    // Exon 1 is 11..=20 and exon 2 is 31..=40 on a 50 bp chromosome. Each
+    // exon holds half of GGGTGGGTGGGTGGG, so the motif only exists once the
+    // intron (21..=30, all T) is spliced out.
+    const GENOME: &str = ">chr1\n\
+        AAAAAAAAAA\
+        AAAGGGTGGG\
+        TTTTTTTTTT\
+        TGGGTGGGAA\
+        AAAAAAAAAA\n";
+
+    const GTF: &str = "#!genome-build test\n\
+        chr1\ttest\ttranscript\t11\t40\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+        chr1\ttest\texon\t31\t40\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number \"2\";\n\
+        chr1\ttest\texon\t11\t20\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number \"1\";\n";
+
+    fn two_exons(strand: Strand) -> Transcript {
+        Transcript {
+            id: "t1".to_string(),
+            gene_id: None,
+            chrom: "chr1".to_string(),
+            strand,
+            exons: vec![(11, 20), (31, 40)],
+        }
+    }
+
+    #[test]
+    fn exons_are_grouped_by_transcript_and_sorted() {
+        let transcripts = read_gtf_reader(GTF.as_bytes()).unwrap();
+        assert_eq!(transcripts.len(), 1);
+        let transcript = &transcripts[0];
+        assert_eq!(transcript.id, "t1");
+        assert_eq!(transcript.gene_id.as_deref(), Some("g1"));
+        assert_eq!(transcript.exons, [(11, 20), (31, 40)]);
+        assert_eq!(transcript.len(), 20);
+    }
+
+    #[test]
+    fn a_g4_across_the_junction_projects_onto_both_exons() {
+        let genome =
+            load_sequences_from_reader(&mut GENOME.as_bytes(), Default::default()).unwrap();
+        let transcripts = read_gtf_reader(GTF.as_bytes()).unwrap();
+        let (spliced, unplaced) = splice_transcripts(&genome, &transcripts, |_| true);
+        assert!(unplaced.is_empty());
+        assert_eq!(spliced[0].name(), "t1");
+        assert_eq!(spliced[0].description(), Some("chr1:11-40(+)"));
+        assert_eq!(spliced[0].sequence().as_slice(), b"AAAGGGTGGGTGGGTGGGAA");
+
+        let hits = search_chromosome(&spliced[0], ScanConfig::default(), false).hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].start, hits[0].end), (4, 18));
+        assert_eq!(
+            transcripts[0].project(hits[0].start, hits[0].end),
+            Some(GenomicSpan {
+                start: 14,
+                end: 38,
+                spans_junction: true,
+            })
+        );
+        // Positions 4..=10 stay inside exon 1.
+        assert_eq!(
+            transcripts[0].project(4, 10),
+            Some(GenomicSpan {
+                start: 14,
+                end: 20,
+                spans_junction: false,
+            })
+        );
+    }
+
+    #[test]
+    fn minus_strand_transcripts_are_reverse_complemented() {
+        let transcript = two_exons(Strand::Minus);
+        let genome = GENOME
+            .lines()
+            .nth(1)
+            .unwrap()
+            .to_ascii_lowercase()
+            .into_bytes();
+        // Exon 2 comes first and both are read on the other strand.
+        assert_eq!(transcript.splice(&genome).unwrap(), b"ttcccacccacccacccttt");
+        assert_eq!(transcript.genomic_position(1), Some(40));
+        assert_eq!(transcript.genomic_position(10), Some(31));
+        assert_eq!(transcript.genomic_position(11), Some(20));
+        assert_eq!(transcript.genomic_position(21), None);
+        // Transcript bases 3..=17 are genomic 38..=31 then 20..=14.
+        assert_eq!(
+            transcript.project(3, 17),
+            Some(GenomicSpan {
+                start: 14,
+                end: 38,
+                spans_junction: true,
+            })
+        );
+    }
+
+    #[test]
+    fn transcripts_off_the_genome_are_reported_as_unplaced() {
+        let genome =
+            load_sequences_from_reader(&mut GENOME.as_bytes(), Default::default()).unwrap();
+        let mut far = two_exons(Strand::Plus);
+        far.id = "far".to_string();
+        far.exons = vec![(41, 60)];
+        let mut elsewhere = two_exons(Strand::Plus);
+        elsewhere.id = "elsewhere".to_string();
+        elsewhere.chrom = "chr2".to_string();
+        let transcripts = [two_exons(Strand::Plus), far, elsewhere];
+        let (spliced, unplaced) = splice_transcripts(&genome, &transcripts, |_| true);
+        assert_eq!(spliced.len(), 1);
+        assert_eq!(unplaced, ["far", "elsewhere"]);
+    }
+
+    #[test]
+    fn malformed_exons_report_their_line_number() {
+        let missing_id = "chr1\tt\texon\t1\t10\t.\t+\t.\tgene_id \"g1\";\n";
+        let err = read_gtf_reader(missing_id.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("GTF line 1"), "{err}");
+        let unstranded = "chr1\tt\texon\t1\t10\t.\t.\t.\ttranscript_id \"t\";\n";
+        assert!(read_gtf_reader(unstranded.as_bytes()).is_err());
+        let overlapping = "chr1\tt\texon\t1\t10\t.\t+\t.\ttranscript_id \"t\";\n\
+            chr1\tt\texon\t10\t20\t.\t+\t.\ttranscript_id \"t\";\n";
+        let err = read_gtf_reader(overlapping.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("exons overlap"), "{err}");
+    }
+}
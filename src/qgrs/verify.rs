@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::{
+    G4, G4Record, InputMode, ScanConfig, load_sequences_from_path, search_chromosome, stream,
+};
+#[cfg(any(test, feature = "testkit"))]
+use super::{
+    QuartetBase, ScanLimits, SequenceTopology, parse_csv_records, parse_parquet_records,
+    render_csv_results, testing, write_parquet_results,
+};
+
+/// One differing position; a missing side means that mode produced fewer hits.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HitMismatch {
+    pub index: usize,
+    pub mmap: Option<G4Record>,
+    pub stream: Option<G4Record>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChromosomeReport {
+    pub name: String,
+    pub occurrence: usize,
+    pub mmap_hits: Option<usize>,
+    pub stream_hits: Option<usize>,
+    pub mismatches: Vec<HitMismatch>,
+}
+
+impl ChromosomeReport {
+    pub fn is_consistent(&self) -> bool {
+        self.mmap_hits.is_some() && self.stream_hits.is_some() && self.mismatches.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VerifyReport {
+    pub consistent: bool,
+    pub mmap_seconds: f64,
+    pub stream_seconds: f64,
+    pub chromosomes: Vec<ChromosomeReport>,
+}
+
+impl VerifyReport {
+    pub fn mismatch_count(&self) -> usize {
+        self.chromosomes
+            .iter()
+            .map(|chrom| {
+                let missing = usize::from(chrom.mmap_hits.is_none() || chrom.stream_hits.is_none());
+                missing + chrom.mismatches.len()
+            })
+            .sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+pub fn compare_hits(name: &str, occurrence: usize, mmap: &[G4], stream: &[G4]) -> ChromosomeReport {
+    let mut mismatches = Vec::new();
+    for index in 0..mmap.len().max(stream.len()) {
+        let (left, right) = (mmap.get(index), stream.get(index));
+        if left != right {
+            mismatches.push(HitMismatch {
+                index,
+                mmap: left.map(G4Record::from),
+                stream: right.map(G4Record::from),
+            });
+        }
+    }
+    ChromosomeReport {
+        name: name.to_string(),
+        occurrence,
+        mmap_hits: Some(mmap.len()),
+        stream_hits: Some(stream.len()),
+        mismatches,
+    }
+}
+
+/// Scans `path` in both input modes and compares each record as soon as the
+/// stream pipeline finishes it, so only one record's hits per mode are alive
+/// at a time.
+pub fn verify_modes(path: &Path, config: ScanConfig) -> io::Result<VerifyReport> {
+    let load_start = Instant::now();
+    let sequences = load_sequences_from_path(path, InputMode::Mmap)?;
+    let mut mmap_time = load_start.elapsed();
+    let mut pending: HashMap<(String, usize), usize> = sequences
+        .iter()
+        .enumerate()
+        .map(|(index, chrom)| ((chrom.name().to_string(), chrom.occurrence()), index))
+        .collect();
+
+    let mut chromosomes = Vec::new();
+    let mut callback_time = Duration::ZERO;
+    let stream_start = Instant::now();
+    stream::process_fasta_stream_with_config(path, config, false, |results| {
+        // The mmap loader skips empty records, so the stream side does too.
+        if results.sequence_len == 0 {
+            return Ok(());
+        }
+        let callback_start = Instant::now();
+        let key = (results.name, results.occurrence);
+        let report = match pending.remove(&key) {
+            Some(index) => {
+                let search_start = Instant::now();
+                let mmap = search_chromosome(&sequences[index], config, false);
+                mmap_time += search_start.elapsed();
+                compare_hits(&key.0, key.1, &mmap.hits, &results.hits)
+            }
+            None => ChromosomeReport {
+                name: key.0,
+                occurrence: key.1,
+                mmap_hits: None,
+                stream_hits: Some(results.hits.len()),
+                mismatches: Vec::new(),
+            },
+        };
+        chromosomes.push(report);
+        callback_time += callback_start.elapsed();
+        Ok(())
+    })?;
+    let stream_time = stream_start.elapsed().saturating_sub(callback_time);
+
+    let mut leftovers: Vec<usize> = pending.into_values().collect();
+    leftovers.sort_unstable();
+    for index in leftovers {
+        let search_start = Instant::now();
+        let mmap = search_chromosome(&sequences[index], config, false);
+        mmap_time += search_start.elapsed();
+        chromosomes.push(ChromosomeReport {
+            name: mmap.name,
+            occurrence: mmap.occurrence,
+            mmap_hits: Some(mmap.hits.len()),
+            stream_hits: None,
+            mismatches: Vec::new(),
+        });
+    }
+
+    Ok(VerifyReport {
+        consistent: chromosomes.iter().all(ChromosomeReport::is_consistent),
+        mmap_seconds: mmap_time.as_secs_f64(),
+        stream_seconds: stream_time.as_secs_f64(),
+        chromosomes,
+    })
+}
+
+/// One named check of [`self_test`]; `detail` says what went wrong.
+#[cfg(any(test, feature = "testkit"))]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+#[cfg(any(test, feature = "testkit"))]
+impl SelfTestCheck {
+    fn new(name: impl Into<String>, failure: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            passed: failure.is_none(),
+            detail: failure,
+        }
+    }
+}
+
+/// Parameter sets [`self_test`] scans with, by label.
+#[cfg(any(test, feature = "testkit"))]
+pub fn self_test_configs() -> [(&'static str, ScanConfig); 2] {
+    [
+        ("defaults", ScanConfig::default()),
+        (
+            "min-tetrads 3, min-score 30, circular",
+            ScanConfig::new(
+                3,
+                30,
+                ScanLimits::default(),
+                SequenceTopology::Circular,
+                QuartetBase::G,
+            ),
+        ),
+    ]
+}
+
+/// The installation check behind `qgrs self-test`. Writes
+/// [`testing::self_test_genome`] to `dir`, then checks that
+/// [`verify_modes`] finds mmap and stream scans identical under each of
+/// [`self_test_configs`], that the default scan reports exactly the planted
+/// G4s, and that its hits survive a CSV and a Parquet round trip. Files are
+/// left in `dir` for the caller to remove. I/O errors end the run early;
+/// everything else is a failed check.
+#[cfg(any(test, feature = "testkit"))]
+pub fn self_test(dir: &Path) -> io::Result<Vec<SelfTestCheck>> {
+    let path = dir.join("self_test.fa");
+    let planted = testing::self_test_genome().write_fasta(&path, 60)?;
+    let mut checks = Vec::new();
+    for (label, config) in self_test_configs() {
+        let report = verify_modes(&path, config)?;
+        let failure = (!report.consistent).then(|| {
+            format!(
+                "{} mismatch(es) between mmap and stream",
+                report.mismatch_count()
+            )
+        });
+        checks.push(SelfTestCheck::new(
+            format!("mmap/stream parity ({label})"),
+            failure,
+        ));
+    }
+
+    let sequences = load_sequences_from_path(&path, InputMode::Mmap)?;
+    let results: Vec<_> = sequences
+        .iter()
+        .map(|chrom| search_chromosome(chrom, ScanConfig::default(), false))
+        .collect();
+    let found: Vec<_> = results
+        .iter()
+        .flat_map(|results| {
+            results
+                .hits
+                .iter()
+                .map(|hit| (results.name.as_str(), hit.start, hit.end, hit.tetrads))
+        })
+        .collect();
+    let expected: Vec<_> = planted
+        .iter()
+        .map(|g4| (g4.chrom.as_str(), g4.start, g4.end, g4.tetrads))
+        .collect();
+    let failure = (found != expected).then(|| {
+        format!(
+            "found {} hits where {} G4s were planted",
+            found.len(),
+            expected.len()
+        )
+    });
+    checks.push(SelfTestCheck::new("planted G4s found", failure));
+
+    let mut csv_failure = None;
+    let mut parquet_failure = None;
+    for (index, results) in results.iter().enumerate() {
+        let records: Vec<G4Record> = results.hits.iter().map(G4Record::from).collect();
+        let csv = render_csv_results(&results.hits);
+        if csv_failure.is_none() && parse_csv_records(csv.as_bytes())? != records {
+            csv_failure = Some(format!("{} changed in CSV", results.name));
+        }
+        let parquet = dir.join(format!("self_test_{index}.parquet"));
+        write_parquet_results(&results.hits, std::fs::File::create(&parquet)?)
+            .map_err(io::Error::other)?;
+        if parquet_failure.is_none()
+            && parse_parquet_records(std::fs::File::open(&parquet)?)? != records
+        {
+            parquet_failure = Some(format!("{} changed in Parquet", results.name));
+        }
+    }
+    checks.push(SelfTestCheck::new("CSV round trip", csv_failure));
+    checks.push(SelfTestCheck::new("Parquet round trip", parquet_failure));
+    Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qgrs::{consolidate_g4s, find_in_str};
+
+    const TWO_MOTIFS: &str =
+        "GGGGAGGGGAGGGGAGGGGTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTGGGTTGGGTTGGGTTGGG";
+
+    fn hits(sequence: &str) -> Vec<G4> {
+        let raw = find_in_str(sequence, 2, 17);
+        consolidate_g4s(raw).0
+    }
+
+    #[test]
+    fn identical_hits_are_consistent() {
+        let left = hits(TWO_MOTIFS);
+        let right = hits(TWO_MOTIFS);
+        let report = compare_hits("chr1", 0, &left, &right);
+        assert!(report.is_consistent());
+        assert_eq!(report.mmap_hits, Some(left.len()));
+    }
+
+    #[test]
+    fn one_differing_field_is_reported_with_both_versions() {
+        let left = hits(TWO_MOTIFS);
+        let mut right = hits(TWO_MOTIFS);
+        assert_eq!(right.len(), 2);
+        right[1].score += 1;
+
+        let report = compare_hits("chr1", 0, &left, &right);
+        assert!(!report.is_consistent());
+        assert_eq!(report.mismatches.len(), 1);
+        let mismatch = &report.mismatches[0];
+        assert_eq!(mismatch.index, 1);
+        let (mmap, stream) = (
+            mismatch.mmap.as_ref().unwrap(),
+            mismatch.stream.as_ref().unwrap(),
+        );
+        assert_eq!(mmap.score + 1, stream.score);
+        assert_eq!((mmap.start, mmap.end), (stream.start, stream.end));
+
+        let json = VerifyReport {
+            consistent: false,
+            mmap_seconds: 0.0,
+            stream_seconds: 0.0,
+            chromosomes: vec![report],
+        }
+        .to_json()
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["consistent"], false);
+        assert_eq!(value["chromosomes"][0]["mismatches"][0]["index"], 1);
+    }
+
+    #[test]
+    fn extra_hits_on_one_side_are_reported() {
+        let left = hits(TWO_MOTIFS);
+        let right = &left[..1];
+        let report = compare_hits("chr1", 0, &left, right);
+        assert_eq!(report.stream_hits, Some(1));
+        assert!(report.mismatches.iter().all(|m| m.stream.is_none()));
+    }
+
+    #[test]
+    fn self_test_passes_every_check() {
+        let dir = std::env::temp_dir().join(format!("qgrs_self_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let checks = self_test(&dir).unwrap();
+        let names: Vec<&str> = checks.iter().map(|check| check.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mmap/stream parity (defaults)",
+                "mmap/stream parity (min-tetrads 3, min-score 30, circular)",
+                "planted G4s found",
+                "CSV round trip",
+                "Parquet round trip",
+            ]
+        );
+        for check in &checks {
+            assert!(check.passed, "{check:?}");
+            assert_eq!(check.detail, None);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}